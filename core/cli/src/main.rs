@@ -0,0 +1,516 @@
+//! Headless runner, captures a window and runs the configured services without the UI
+//!
+//! Commands are read from stdin (`help` lists them). With `--status-addr` the status report
+//! is also served over HTTP, e.g. `curl http://127.0.0.1:7878` from another machine, and with
+//! `--preview-addr` the minimap preview can be watched in a browser. `--api-addr` serves the
+//! REST control API for remote scripts, protected by `--api-token`. `--record` writes a
+//! session archive of frames and events to `sessions/` in the config directory, `--replay`
+//! plays one back instead of capturing a window.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use interface::config::{BotConfig, CaptureBackend, HotkeyAction};
+use interface::services::{
+    ChatMonitor, ChatMonitorConfig, ControlApi, ControlApiConfig, MinimapServiceV2, OcrConfig, OcrService, PluginContext,
+    PluginRegistry, PreviewServer, PreviewServerConfig, ReplaySource, Scheduler, Service, ServiceManager,
+    ServiceState, SessionRecorder, SessionRecorderConfig, SessionStats, SessionStatsConfig, Shutdown,
+};
+use interface::{find_window, kill_switch, list_window_handles, BotEvent, ConfigWatcher, EventBus, FrameAnalyzer, GraphicsCaptureService};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, watch};
+
+const USAGE: &str = "Usage: starry-cli [--window <title>] [--profile <name>] [--status-addr <ip:port>]
+                  [--preview-addr <ip:port>] [--preview-token <token>] [--api-addr <ip:port> --api-token <token>]
+                  [--record | --replay <archive>]
+
+  --window <title>          Part of the title of the window to capture, overrides config.toml
+  --profile <name>          Switch to a game profile of config.toml and keep using it
+  --status-addr <ip:port>   Serve the status report over HTTP, e.g. 127.0.0.1:7878
+  --preview-addr <ip:port>  Stream the minimap preview over HTTP, e.g. 0.0.0.0:7879
+  --preview-token <token>   Require `?token=<token>` to watch the preview
+  --api-addr <ip:port>      Serve the REST control API, e.g. 127.0.0.1:7880
+  --api-token <token>       Bearer token the control API requires
+  --record                  Record frames and events to a session archive while running
+  --replay <archive>        Run the services on a recorded session instead of a window";
+
+const COMMANDS: &str = "Commands:
+  status           Capture, performance and service status
+  windows          List window titles
+  start [window]   Capture a window, the configured one by default
+  stop             Stop capture and all services
+  quit             Stop everything and exit";
+
+#[derive(Debug, Default)]
+struct Options {
+    window: Option<String>,
+    profile: Option<String>,
+    status_addr: Option<SocketAddr>,
+    preview_addr: Option<SocketAddr>,
+    preview_token: Option<String>,
+    api_addr: Option<SocketAddr>,
+    api_token: Option<String>,
+    record: bool,
+    replay: Option<PathBuf>,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or_else(|| format!("Missing value for {}", name));
+            match arg.as_str() {
+                "--window" => options.window = Some(value("--window")?),
+                "--profile" => options.profile = Some(value("--profile")?),
+                "--status-addr" => {
+                    let addr = value("--status-addr")?;
+                    options.status_addr =
+                        Some(addr.parse().map_err(|e| format!("Invalid status address {}: {}", addr, e))?);
+                }
+                "--preview-addr" => {
+                    let addr = value("--preview-addr")?;
+                    options.preview_addr =
+                        Some(addr.parse().map_err(|e| format!("Invalid preview address {}: {}", addr, e))?);
+                }
+                "--preview-token" => options.preview_token = Some(value("--preview-token")?),
+                "--api-addr" => {
+                    let addr = value("--api-addr")?;
+                    options.api_addr = Some(addr.parse().map_err(|e| format!("Invalid API address {}: {}", addr, e))?);
+                }
+                "--api-token" => options.api_token = Some(value("--api-token")?),
+                "--record" => options.record = true,
+                "--replay" => options.replay = Some(value("--replay")?.into()),
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                _ => return Err(format!("Unknown argument {}", arg)),
+            }
+        }
+        if options.api_addr.is_some() && options.api_token.is_none() {
+            return Err("--api-addr needs --api-token".to_string());
+        }
+        if options.record && options.replay.is_some() {
+            return Err("--record and --replay can't be combined".to_string());
+        }
+        Ok(options)
+    }
+}
+
+/// Services the UI would run, driven from the console instead
+#[derive(Clone)]
+struct Runner {
+    graphics_service: Arc<GraphicsCaptureService>,
+    minimap_service: MinimapServiceV2,
+    services: Arc<ServiceManager>,
+}
+
+impl Runner {
+    fn new(config: &ConfigWatcher, record: bool) -> Self {
+        let bot_config = config.current();
+        let graphics_service = Arc::new(GraphicsCaptureService::with_target_fps(bot_config.capture.fps));
+        graphics_service.follow_config(config.subscribe());
+        let minimap_service = MinimapServiceV2::with_config(graphics_service.clone(), &bot_config);
+        minimap_service.follow_config(config.subscribe());
+        interface::services::thread_tuning::follow_config(config.subscribe());
+        interface::services::MemoryBudget::global().follow_config(config.subscribe());
+        interface::services::LowPowerMode::global().follow_config(config.subscribe());
+        interface::services::hotkeys::follow_config(config.subscribe());
+        let ocr_service = OcrService::new(graphics_service.clone(), OcrConfig::default());
+        let session_stats = SessionStats::new(ocr_service.clone(), SessionStatsConfig::load());
+        let chat_monitor = ChatMonitor::new(ocr_service.clone(), ChatMonitorConfig::load());
+
+        // Only run what is configured, like the UI does
+        let services = Arc::new(ServiceManager::new());
+        let watch_chat = chat_monitor.config().region.is_some();
+        let count_stats = !session_stats.config().counters.is_empty();
+        if watch_chat || count_stats {
+            Self::register(&services, Arc::new(ocr_service));
+        }
+        if watch_chat {
+            Self::register(&services, Arc::new(chat_monitor));
+        }
+        #[cfg(feature = "history")]
+        match interface::services::SessionStore::open(&interface::services::SessionStore::path()) {
+            Ok(store) => {
+                let history = interface::services::SessionHistory::new(Arc::new(store), minimap_service.clone());
+                let history = if count_stats { history.with_stats(session_stats.clone()) } else { history };
+                Self::register(&services, Arc::new(history));
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+        if count_stats {
+            Self::register(&services, Arc::new(session_stats));
+        }
+        if !bot_config.scheduler.jobs.is_empty() {
+            let frames = FrameAnalyzer::new(graphics_service.clone());
+            Self::register(&services, Arc::new(frames.clone()));
+            let scheduler = Scheduler::new(bot_config.scheduler.clone()).with_frames(frames);
+            scheduler.follow_config(config.subscribe());
+            Self::register(&services, Arc::new(scheduler));
+        }
+        #[cfg(feature = "notifications")]
+        {
+            let notifier_config = interface::services::NotifierConfig::load();
+            if notifier_config.is_enabled() {
+                match interface::services::Notifier::new(notifier_config) {
+                    Ok(notifier) => Self::register(
+                        &services,
+                        Arc::new(notifier.with_minimap(minimap_service.get_frame_receiver())),
+                    ),
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+        }
+        #[cfg(feature = "dynamic-plugins")]
+        PluginRegistry::global().load_dir(&interface::config_dir().join("plugins"));
+        if let Err(e) = PluginRegistry::global().init_all(&PluginContext::new(graphics_service.clone()), &services) {
+            eprintln!("{}", e);
+        }
+        if record {
+            let recorder = SessionRecorder::new(graphics_service.clone(), SessionRecorderConfig::default());
+            Self::register(&services, Arc::new(recorder));
+        }
+
+        Self {
+            graphics_service,
+            minimap_service,
+            services,
+        }
+    }
+
+    fn register(services: &ServiceManager, service: Arc<dyn Service>) {
+        if let Err(e) = services.register(service) {
+            eprintln!("{}", e);
+        }
+    }
+
+    /// Capture the first window whose title contains `pattern` and start the services
+    async fn start(&self, pattern: &str, config: &BotConfig) -> Result<(), String> {
+        let window = find_window(pattern).ok_or_else(|| format!("No window matching {:?}", pattern))?;
+        self.minimap_service.set_window(window.clone()).await?;
+        println!("Capturing {}", window);
+
+        if config.capture.backend != CaptureBackend::WindowsGraphicsCapture {
+            if let Err(e) = self.minimap_service.enable_dxgi_mode().await {
+                eprintln!("DXGI mode failed, using standard capture: {}", e);
+            }
+        }
+        self.services.start_all().await
+    }
+
+    /// Feed a session archive through capture and start the services
+    async fn replay(&self, archive: PathBuf) -> Result<(), String> {
+        self.graphics_service.start_replay(ReplaySource::new(&archive)).await?;
+        println!("Replaying {}", archive.display());
+        self.services.start_all().await
+    }
+
+    async fn stop(&self) {
+        if let Err(e) = self.services.stop_all().await {
+            eprintln!("{}", e);
+        }
+        if let Err(e) = self.minimap_service.stop_capture().await {
+            eprintln!("{}", e);
+        }
+        // A replay isn't driven by the minimap service
+        self.graphics_service.stop_capture().await;
+    }
+
+    /// Start capturing the configured window, or stop if capture is running
+    async fn toggle(&self, config: &BotConfig) {
+        if self.minimap_service.state() != ServiceState::Stopped {
+            println!("Stopping capture");
+            self.stop().await;
+            return;
+        }
+        let pattern = &config.window().pattern;
+        if pattern.is_empty() {
+            println!("No window configured, use `start <window>`");
+        } else if let Err(e) = self.start(pattern, config).await {
+            eprintln!("{}", e);
+        }
+    }
+
+    /// Stop everything before exiting, releasing held keys and finishing recordings
+    async fn shutdown(&self) {
+        let shutdown = Shutdown::new(self.services.clone(), self.graphics_service.clone())
+            .with_minimap(self.minimap_service.clone());
+        if let Err(e) = shutdown.run().await {
+            eprintln!("{}", e);
+        }
+    }
+
+    async fn status(&self) -> String {
+        let window = self
+            .minimap_service
+            .get_current_window_title()
+            .await
+            .unwrap_or_else(|| "-".to_string());
+        format!(
+            "Capture: {:?} ({})\n{}\nServices:\n{}\n",
+            self.minimap_service.state(),
+            window,
+            self.minimap_service.get_performance_metrics(),
+            self.services.status().await,
+        )
+    }
+}
+
+/// Answer every connection with the status report, whatever was requested
+async fn serve_status(runner: Runner, addr: SocketAddr) {
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Failed to serve status on {}: {}", addr, e);
+            return;
+        }
+    };
+    println!("Serving status on http://{}", addr);
+
+    loop {
+        let (mut stream, _) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("Failed to accept status connection: {}", e);
+                continue;
+            }
+        };
+        let runner = runner.clone();
+        tokio::spawn(async move {
+            // The request line and headers don't matter, but are read so clients see a response
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await;
+            let body = runner.status().await;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+            let _ = stream.shutdown().await;
+        });
+    }
+}
+
+/// Print every bot event as a JSON line, stop when the captured window goes away and toggle
+/// capture with the start/stop hotkey
+async fn follow_events(runner: Runner, configs: watch::Receiver<BotConfig>) {
+    let mut events = EventBus::global().subscribe();
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                if let Ok(json) = serde_json::to_string(&event) {
+                    println!("event: {}", json);
+                }
+                match event {
+                    BotEvent::WindowLost { .. } => runner.stop().await,
+                    BotEvent::HotkeyPressed { action: HotkeyAction::StartStop } => {
+                        let config = configs.borrow().clone();
+                        runner.toggle(&config).await;
+                    }
+                    _ => {}
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+async fn follow_kill_switch(runner: Runner) {
+    let mut presses = match kill_switch() {
+        Ok(receiver) => receiver,
+        Err(e) => {
+            eprintln!("{}", e);
+            return;
+        }
+    };
+    loop {
+        match presses.recv().await {
+            Ok(()) => {
+                println!("Kill switch pressed, stopping all services");
+                runner.stop().await;
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Run console commands until `quit`, end of input or Ctrl+C
+async fn run_console(runner: &Runner, config: &ConfigWatcher) {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => line,
+                // Without a console (e.g. as a service) run until Ctrl+C
+                Ok(None) | Err(_) => {
+                    let _ = tokio::signal::ctrl_c().await;
+                    return;
+                }
+            },
+            _ = tokio::signal::ctrl_c() => return,
+        };
+
+        let (command, argument) = match line.trim().split_once(' ') {
+            Some((command, argument)) => (command, Some(argument.trim())),
+            None => (line.trim(), None),
+        };
+        match command {
+            "" => {}
+            "status" => print!("{}", runner.status().await),
+            "windows" => {
+                for window in list_window_handles() {
+                    println!("{}", window);
+                }
+            }
+            "start" => {
+                let config = config.current();
+                let pattern = argument.unwrap_or(&config.window().pattern).to_string();
+                if pattern.is_empty() {
+                    println!("No window configured, use `start <window>`");
+                } else if let Err(e) = runner.start(&pattern, &config).await {
+                    eprintln!("{}", e);
+                }
+            }
+            "stop" => runner.stop().await,
+            "quit" | "exit" => return,
+            "help" => println!("{}", COMMANDS),
+            _ => println!("Unknown command {}\n{}", command, COMMANDS),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let _log = interface::init_logging().map_err(|e| eprintln!("{}", e)).ok();
+    // Installs the keyboard hook used by the kill switch hotkey
+    interface::init();
+
+    let config = ConfigWatcher::new();
+    if let Some(profile) = &options.profile {
+        // Selected before the services are built so they start with the profile's settings
+        let mut selected = Ok(());
+        let switched = config.update(|config| selected = config.select_profile(Some(profile)));
+        if let Err(e) = selected.and(switched) {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    }
+    let runner = Runner::new(&config, options.record);
+
+    let current = config.current();
+    let pattern = options.window.unwrap_or_else(|| current.window().pattern.clone());
+    if let Some(archive) = options.replay {
+        if let Err(e) = runner.replay(archive).await {
+            eprintln!("{}", e);
+        }
+    } else if pattern.is_empty() {
+        println!("No window configured, use `start <window>`");
+    } else if let Err(e) = runner.start(&pattern, &current).await {
+        eprintln!("{}", e);
+    }
+
+    if let Some(addr) = options.status_addr {
+        tokio::spawn(serve_status(runner.clone(), addr));
+    }
+    // Keeps serving while capture is stopped and restarted, viewers just see no frames
+    let preview = options.preview_addr.map(|addr| {
+        PreviewServer::new(
+            runner.minimap_service.get_frame_receiver(),
+            PreviewServerConfig {
+                addr,
+                token: options.preview_token.clone(),
+                ..PreviewServerConfig::default()
+            },
+        )
+    });
+    let api = options.api_addr.zip(options.api_token.clone()).map(|(addr, token)| {
+        ControlApi::new(
+            runner.minimap_service.clone(),
+            runner.graphics_service.clone(),
+            runner.services.clone(),
+            ControlApiConfig {
+                addr,
+                ..ControlApiConfig::new(token)
+            },
+        )
+    });
+    if let Some(api) = &api {
+        match api.start_api().await {
+            Ok(()) => println!("Serving the control API on http://{}", api.config().addr),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+    if let Some(preview) = &preview {
+        match preview.start_server().await {
+            Ok(()) => println!("Streaming the preview on http://{}", preview.config().addr),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+    // Outside the service manager so `/pause` doesn't stop it from hearing `/resume`
+    #[cfg(feature = "notifications")]
+    let remote = {
+        let remote_config = interface::services::RemoteControlConfig::load();
+        if remote_config.is_enabled() {
+            match interface::services::RemoteControl::new(
+                runner.services.clone(),
+                runner.graphics_service.clone(),
+                remote_config,
+            ) {
+                Ok(remote) => {
+                    let remote = Arc::new(remote);
+                    match remote.start_listening() {
+                        Ok(()) => println!("Listening for Telegram commands"),
+                        Err(e) => eprintln!("{}", e),
+                    }
+                    Some(remote)
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        }
+    };
+    let events = tokio::spawn(follow_events(runner.clone(), config.subscribe()));
+    let kill_switch = tokio::spawn(follow_kill_switch(runner.clone()));
+
+    println!("{}", COMMANDS);
+    run_console(&runner, &config).await;
+
+    println!("Shutting down, press Ctrl+C again to exit right away");
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+    // Nothing may start or stop services behind the shutdown's back
+    events.abort();
+    kill_switch.abort();
+    if let Some(preview) = preview {
+        preview.stop_server();
+    }
+    if let Some(api) = api {
+        api.stop_api();
+    }
+    #[cfg(feature = "notifications")]
+    if let Some(remote) = remote {
+        remote.stop_listening();
+    }
+    runner.shutdown().await;
+}
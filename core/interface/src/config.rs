@@ -0,0 +1,680 @@
+//! Bot settings persisted as TOML in the config directory
+//!
+//! Services take the parts they need at construction, e.g.
+//! [`GraphicsCaptureService::with_target_fps`](crate::services::GraphicsCaptureService::with_target_fps)
+//! and `MinimapService::with_config`. Running services pick up edits of the file through
+//! [`ConfigWatcher`] and their `follow_config` methods.
+//!
+//! People playing several games keep a [`GameProfile`] per game, the active one overrides
+//! the window, ROI and keybinds and has its own routes and templates in [`profile_dir`].
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use platforms::input::{InputProfile, KeyKind};
+use platforms::thread::ThreadPriority;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::services::frame_diff::DEFAULT_CHANGE_THRESHOLD;
+use crate::services::{BotEvent, EventBus, RestartPolicy, SchedulerConfig, CAPTURE_TARGET_FPS};
+use crate::services::vision::{MaxSize, Rect};
+
+/// Highest frame rate capture can be set to
+pub const MAX_CAPTURE_FPS: u32 = 240;
+
+/// Which window to capture
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    /// Case insensitive part of the window title
+    pub pattern: String,
+    /// Start capturing the first matching window on launch
+    pub auto_select: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            pattern: "BPSR".to_string(),
+            auto_select: true,
+        }
+    }
+}
+
+/// Capture API frames are taken with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureBackend {
+    /// Windows Graphics Capture, switching to DXGI Desktop Duplication when it is available
+    #[default]
+    Auto,
+    WindowsGraphicsCapture,
+    Dxgi,
+}
+
+impl CaptureBackend {
+    pub const ALL: [CaptureBackend; 3] = [CaptureBackend::Auto, CaptureBackend::WindowsGraphicsCapture, CaptureBackend::Dxgi];
+}
+
+impl fmt::Display for CaptureBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CaptureBackend::Auto => "Automatic",
+            CaptureBackend::WindowsGraphicsCapture => "Windows Graphics Capture",
+            CaptureBackend::Dxgi => "DXGI Desktop Duplication",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptureConfig {
+    pub backend: CaptureBackend,
+    /// Frame rate the capture aims for when consumers keep up
+    pub fps: u32,
+    /// Frames are cropped to this region before detection, overrides the saved minimap ROI
+    pub roi: Option<Rect>,
+    /// Preview frames are scaled down to fit before encoding, overrides the saved preview size
+    pub preview_size: Option<MaxSize>,
+    /// Quality (0 - 100) previews are encoded with, overrides the saved one
+    pub preview_quality: Option<i32>,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            backend: CaptureBackend::default(),
+            fps: CAPTURE_TARGET_FPS,
+            roi: None,
+            preview_size: None,
+            preview_quality: None,
+        }
+    }
+}
+
+/// Game keys the bot presses
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keybinds {
+    pub forward: KeyKind,
+    pub backward: KeyKind,
+    pub left: KeyKind,
+    pub right: KeyKind,
+    pub jump: KeyKind,
+    /// Held to turn away from obstacles when stuck
+    pub turn: KeyKind,
+}
+
+impl Default for Keybinds {
+    fn default() -> Self {
+        Self {
+            forward: KeyKind::W,
+            backward: KeyKind::S,
+            left: KeyKind::A,
+            right: KeyKind::D,
+            jump: KeyKind::Space,
+            turn: KeyKind::D,
+        }
+    }
+}
+
+#[cfg(feature = "opencv")]
+impl Keybinds {
+    pub fn movement_keys(&self) -> crate::services::MovementKeys {
+        crate::services::MovementKeys {
+            forward: self.forward,
+            backward: self.backward,
+            left: self.left,
+            right: self.right,
+        }
+    }
+
+    /// `config` with these movement keys and stuck recovery keys, recovery steps that are
+    /// turned off stay off
+    pub fn route_config(&self, mut config: crate::services::RouteConfig) -> crate::services::RouteConfig {
+        config.keys = self.movement_keys();
+        let recovery = &mut config.stuck.recovery;
+        recovery.jump_key = recovery.jump_key.map(|_| self.jump);
+        recovery.turn_key = recovery.turn_key.map(|_| self.turn);
+        config
+    }
+}
+
+/// Bot commands bound to global hotkeys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HotkeyAction {
+    /// Start capture, or stop it and everything started with it
+    StartStop,
+    /// The kill switch
+    PanicStop,
+    /// Save the next captured frame in `screenshots/`
+    Screenshot,
+    /// Stop the automation services without stopping capture, or start them again
+    PauseAutomation,
+}
+
+impl HotkeyAction {
+    pub const ALL: [HotkeyAction; 4] = [
+        HotkeyAction::StartStop,
+        HotkeyAction::PanicStop,
+        HotkeyAction::Screenshot,
+        HotkeyAction::PauseAutomation,
+    ];
+}
+
+impl fmt::Display for HotkeyAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HotkeyAction::StartStop => "Start / stop",
+            HotkeyAction::PanicStop => "Panic stop",
+            HotkeyAction::Screenshot => "Screenshot",
+            HotkeyAction::PauseAutomation => "Pause automation",
+        })
+    }
+}
+
+/// Global hotkeys, pressed while any window has focus, `None` leaves an action unbound
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hotkeys {
+    pub start_stop: Option<KeyKind>,
+    pub panic_stop: Option<KeyKind>,
+    pub screenshot: Option<KeyKind>,
+    pub pause_automation: Option<KeyKind>,
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Self {
+            start_stop: Some(KeyKind::F9),
+            panic_stop: Some(KeyKind::Pause),
+            screenshot: None,
+            pause_automation: None,
+        }
+    }
+}
+
+impl Hotkeys {
+    pub fn get(&self, action: HotkeyAction) -> Option<KeyKind> {
+        match action {
+            HotkeyAction::StartStop => self.start_stop,
+            HotkeyAction::PanicStop => self.panic_stop,
+            HotkeyAction::Screenshot => self.screenshot,
+            HotkeyAction::PauseAutomation => self.pause_automation,
+        }
+    }
+
+    pub fn set(&mut self, action: HotkeyAction, key: Option<KeyKind>) {
+        match action {
+            HotkeyAction::StartStop => self.start_stop = key,
+            HotkeyAction::PanicStop => self.panic_stop = key,
+            HotkeyAction::Screenshot => self.screenshot = key,
+            HotkeyAction::PauseAutomation => self.pause_automation = key,
+        }
+    }
+
+    /// Action bound to `key`
+    pub fn action(&self, key: KeyKind) -> Option<HotkeyAction> {
+        HotkeyAction::ALL.into_iter().find(|action| self.get(*action) == Some(key))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectionThresholds {
+    /// Frames differing less than this from the last processed one are skipped (0.0 - 1.0)
+    pub change: f64,
+    /// Minimum template score for the minimap to count as found (0.0 - 1.0)
+    pub minimap_match: f64,
+}
+
+impl Default for DetectionThresholds {
+    fn default() -> Self {
+        Self {
+            change: DEFAULT_CHANGE_THRESHOLD,
+            minimap_match: 0.7,
+        }
+    }
+}
+
+/// Scheduling of one kind of thread
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThreadSettings {
+    pub priority: ThreadPriority,
+    /// Logical cores (0 based) the threads may run on, every core if empty
+    pub cores: Vec<usize>,
+}
+
+/// Keeping frame pacing steady while the game saturates the CPU
+///
+/// Capture threads pick changes up when capture is started again, frame processing with the
+/// next frame.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PerformanceConfig {
+    /// Most memory queued frames may take up in MB before frames are dropped, 0 is unlimited
+    pub frame_memory_mb: u64,
+    /// Hotkey switching low-power mode on and off, none by default
+    pub low_power_key: Option<KeyKind>,
+    /// Windows Graphics Capture and DXGI capture threads
+    pub capture: ThreadSettings,
+    /// Threads running OpenCV, OCR, detection and preview encoding
+    pub processing: ThreadSettings,
+}
+
+impl Default for PerformanceConfig {
+    fn default() -> Self {
+        Self {
+            frame_memory_mb: 1024,
+            low_power_key: None,
+            capture: ThreadSettings::default(),
+            processing: ThreadSettings::default(),
+        }
+    }
+}
+
+/// Settings of one game, unset ones fall back to the base config
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameProfile {
+    pub window: Option<WindowConfig>,
+    /// Overrides `capture.roi`
+    pub roi: Option<Rect>,
+    pub keybinds: Option<Keybinds>,
+    pub hotkeys: Option<Hotkeys>,
+}
+
+/// Profile names end up in paths, so they can't leave the `profiles` directory
+fn check_profile_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.contains("..") {
+        return Err(format!("Invalid profile name {:?}", name));
+    }
+    Ok(())
+}
+
+fn check_roi(roi: Option<Rect>) -> Result<(), String> {
+    match roi {
+        Some(roi) if roi.is_empty() || roi.x < 0 || roi.y < 0 => {
+            Err(format!("Invalid ROI {}x{} at {}, {}", roi.width, roi.height, roi.x, roi.y))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Profile the bot runs with, set by [`ConfigWatcher`] from the config it publishes
+static ACTIVE_PROFILE: RwLock<Option<String>> = RwLock::new(None);
+
+/// Directory of the active profile's routes and templates, the config directory itself
+/// without a profile
+pub fn profile_dir() -> PathBuf {
+    match ACTIVE_PROFILE.read().unwrap().as_deref() {
+        Some(name) => crate::config_dir().join("profiles").join(name),
+        None => crate::config_dir(),
+    }
+}
+
+/// Settings of the bot, kept in `config.toml`
+///
+/// Rules edited from their own panels are saved next to it by their services, e.g.
+/// [`ProbeConfig`](crate::services::ProbeConfig) in `probes.json` and the minimap pipeline in
+/// `minimap.json`, as are the notifier, automation, chat monitor, session stats and remote
+/// control settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BotConfig {
+    /// Name of the profile in `profiles` in use, the base settings if `None`
+    // Plain values have to come before tables in TOML
+    pub profile: Option<String>,
+    pub window: WindowConfig,
+    pub capture: CaptureConfig,
+    pub keybinds: Keybinds,
+    pub hotkeys: Hotkeys,
+    pub detection: DetectionThresholds,
+    /// How failed capture and processing tasks are restarted
+    pub supervision: RestartPolicy,
+    pub performance: PerformanceConfig,
+    /// Humanization of the input sent to the game
+    pub input: InputProfile,
+    /// Jobs of the [`Scheduler`](crate::services::Scheduler)
+    pub scheduler: SchedulerConfig,
+    pub profiles: BTreeMap<String, GameProfile>,
+}
+
+impl BotConfig {
+    pub fn path() -> PathBuf {
+        crate::config_dir().join("config.toml")
+    }
+
+    /// Load the saved config, falling back to defaults if there is none or it is invalid
+    pub fn load() -> Self {
+        let path = Self::path();
+        if !path.exists() {
+            return Self::default();
+        }
+        Self::load_from(&path).unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "Ignoring invalid config");
+            Self::default()
+        })
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        toml::from_str(&text).map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let text = toml::to_string_pretty(self).map_err(|e| format!("Failed to serialize config: {}", e))?;
+        std::fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// The profile in use, `None` without one or if it doesn't exist
+    pub fn active_profile(&self) -> Option<&GameProfile> {
+        self.profile.as_ref().and_then(|name| self.profiles.get(name))
+    }
+
+    /// Use the profile `name`, or the base settings with `None`
+    pub fn select_profile(&mut self, name: Option<&str>) -> Result<(), String> {
+        if let Some(name) = name {
+            check_profile_name(name)?;
+            if !self.profiles.contains_key(name) {
+                return Err(format!("No profile named {:?}", name));
+            }
+        }
+        self.profile = name.map(str::to_string);
+        Ok(())
+    }
+
+    /// Window to capture, from the active profile if it sets one
+    pub fn window(&self) -> &WindowConfig {
+        self.active_profile()
+            .and_then(|profile| profile.window.as_ref())
+            .unwrap_or(&self.window)
+    }
+
+    /// Window settings to edit, the active profile's own once it has a profile
+    pub fn window_mut(&mut self) -> &mut WindowConfig {
+        match self.profile.as_ref().and_then(|name| self.profiles.get_mut(name)) {
+            Some(profile) => profile.window.get_or_insert_with(|| self.window.clone()),
+            None => &mut self.window,
+        }
+    }
+
+    /// Region frames are cropped to, from the active profile if it sets one
+    pub fn roi(&self) -> Option<Rect> {
+        self.active_profile()
+            .and_then(|profile| profile.roi)
+            .or(self.capture.roi)
+    }
+
+    /// ROI to edit, the active profile's own once it has a profile
+    pub fn roi_mut(&mut self) -> &mut Option<Rect> {
+        match self.profile.as_ref().and_then(|name| self.profiles.get_mut(name)) {
+            Some(profile) => &mut profile.roi,
+            None => &mut self.capture.roi,
+        }
+    }
+
+    /// Game keys, from the active profile if it sets them
+    pub fn keybinds(&self) -> Keybinds {
+        self.active_profile()
+            .and_then(|profile| profile.keybinds)
+            .unwrap_or(self.keybinds)
+    }
+
+    /// Global hotkeys, from the active profile if it sets them
+    pub fn hotkeys(&self) -> Hotkeys {
+        self.active_profile()
+            .and_then(|profile| profile.hotkeys)
+            .unwrap_or(self.hotkeys)
+    }
+
+    /// Hotkeys to edit, the active profile's own once it has a profile
+    pub fn hotkeys_mut(&mut self) -> &mut Hotkeys {
+        match self.profile.as_ref().and_then(|name| self.profiles.get_mut(name)) {
+            Some(profile) => profile.hotkeys.get_or_insert(self.hotkeys),
+            None => &mut self.hotkeys,
+        }
+    }
+
+    /// Check the values a file or form can get wrong but serde can't
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_CAPTURE_FPS).contains(&self.capture.fps) {
+            return Err(format!("Capture FPS must be between 1 and {}, not {}", MAX_CAPTURE_FPS, self.capture.fps));
+        }
+        if let Some(quality) = self.capture.preview_quality {
+            if !(0..=100).contains(&quality) {
+                return Err(format!("Preview quality must be between 0 and 100, not {}", quality));
+            }
+        }
+        if let Some(size) = self.capture.preview_size {
+            if size.width <= 0 || size.height <= 0 {
+                return Err(format!("Invalid preview size {}x{}", size.width, size.height));
+            }
+        }
+        check_roi(self.capture.roi)?;
+        if let Some(name) = &self.profile {
+            check_profile_name(name)?;
+        }
+        for (name, profile) in &self.profiles {
+            check_profile_name(name)?;
+            check_roi(profile.roi).map_err(|e| format!("{} of profile {:?}", e, name))?;
+        }
+        if self.input.mouse_speed.is_nan() || self.input.mouse_speed < 0.0 {
+            return Err(format!("Mouse speed can't be {}", self.input.mouse_speed));
+        }
+        let hotkeys = self.hotkeys();
+        for action in HotkeyAction::ALL {
+            if let Some(key) = hotkeys.get(action) {
+                let bound = hotkeys.action(key).unwrap_or(action);
+                if bound != action {
+                    return Err(format!("{:?} is bound to both {} and {}", key, bound, action));
+                }
+            }
+        }
+        self.scheduler.validate()
+    }
+
+    /// Change the config and save it if anything changed, a change failing [`Self::validate`]
+    /// is undone
+    pub fn update(&mut self, change: impl FnOnce(&mut Self)) -> Result<(), String> {
+        let previous = self.clone();
+        change(self);
+        if *self == previous {
+            return Ok(());
+        }
+        if let Err(e) = self.validate() {
+            *self = previous;
+            return Err(e);
+        }
+        self.save()
+    }
+}
+
+/// Watches `config.toml` and publishes the config whenever the file changes
+///
+/// Edits that don't parse are reported and ignored, the last valid config stays current.
+pub struct ConfigWatcher {
+    sender: Arc<watch::Sender<BotConfig>>,
+    /// Held from reading to publishing in [`Self::update`] so concurrent updates don't drop
+    /// each other's changes
+    updating: Mutex<()>,
+    // Stops watching when dropped, `None` if the file can't be watched
+    _watcher: Option<RecommendedWatcher>,
+}
+
+impl ConfigWatcher {
+    /// Load the config and start watching its file
+    ///
+    /// If watching fails the config still loads and [`Self::update`] still publishes, only
+    /// edits made outside the bot go unnoticed.
+    pub fn new() -> Self {
+        let config = BotConfig::load();
+        Self::activate_profile(&config);
+        let sender = Arc::new(watch::channel(config).0);
+        let watcher = Self::watch(sender.clone())
+            .map_err(|e| tracing::warn!(error = %e, "Config changes won't be picked up"))
+            .ok();
+        Self {
+            sender,
+            updating: Mutex::new(()),
+            _watcher: watcher,
+        }
+    }
+
+    fn watch(sender: Arc<watch::Sender<BotConfig>>) -> Result<RecommendedWatcher, String> {
+        let path = BotConfig::path();
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_else(crate::config_dir);
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        // Editors often replace the file instead of writing it, so the directory is watched
+        let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+            let event = match result {
+                Ok(event) => event,
+                Err(e) => {
+                    tracing::error!(error = %e, "Config watcher failed");
+                    return;
+                }
+            };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                || !event.paths.iter().any(|changed| changed.file_name() == path.file_name())
+            {
+                return;
+            }
+            match BotConfig::load_from(&path) {
+                Ok(config) => Self::publish(&sender, config),
+                Err(e) => tracing::warn!(error = %e, "Ignoring config change"),
+            }
+        })
+        .map_err(|e| format!("Failed to create config watcher: {}", e))?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("Failed to watch {}: {}", dir.display(), e))?;
+        Ok(watcher)
+    }
+
+    fn activate_profile(config: &BotConfig) {
+        // Edits of the file aren't validated, a bad name must not end up in `profile_dir`
+        let active = config
+            .active_profile()
+            .and(config.profile.clone())
+            .filter(|name| check_profile_name(name).is_ok());
+        let mut current = ACTIVE_PROFILE.write().unwrap();
+        if *current != active {
+            tracing::info!(profile = active.as_deref().unwrap_or("-"), "Using profile");
+            *current = active;
+        }
+    }
+
+    fn publish(sender: &watch::Sender<BotConfig>, config: BotConfig) {
+        Self::activate_profile(&config);
+        let changed = sender.send_if_modified(|current| {
+            if *current == config {
+                return false;
+            }
+            *current = config;
+            true
+        });
+        if changed {
+            EventBus::global().publish(BotEvent::ConfigChanged);
+        }
+    }
+
+    /// Receiver seeing every config change, marked as seen for the current one
+    pub fn subscribe(&self) -> watch::Receiver<BotConfig> {
+        self.sender.subscribe()
+    }
+
+    pub fn current(&self) -> BotConfig {
+        self.sender.borrow().clone()
+    }
+
+    /// Change the config, save it and publish it to subscribers if anything changed
+    pub fn update(&self, change: impl FnOnce(&mut BotConfig)) -> Result<(), String> {
+        let _updating = self.updating.lock().unwrap();
+        let mut config = self.current();
+        config.update(change)?;
+        Self::publish(&self.sender, config);
+        Ok(())
+    }
+}
+
+impl Default for ConfigWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_round_trips_through_toml() {
+        let config = BotConfig::default();
+        let text = toml::to_string_pretty(&config).unwrap();
+        assert_eq!(toml::from_str::<BotConfig>(&text).unwrap(), config);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_out_of_range_fps() {
+        let mut config = BotConfig::default();
+        config.capture.fps = 0;
+        assert!(config.validate().is_err());
+        config.capture.fps = MAX_CAPTURE_FPS + 1;
+        assert!(config.validate().is_err());
+        config.capture.fps = MAX_CAPTURE_FPS;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_duplicate_hotkeys() {
+        let mut config = BotConfig::default();
+        config.hotkeys.screenshot = config.hotkeys.start_stop;
+        assert!(config.validate().is_err());
+        config.hotkeys.screenshot = Some(KeyKind::F10);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_rejects_profile_names_leaving_profile_dir() {
+        let mut config = BotConfig::default();
+        for name in ["../escape", "a/b", "a\\b", ""] {
+            config.profiles = BTreeMap::from([(name.to_string(), GameProfile::default())]);
+            assert!(config.validate().is_err(), "{:?}", name);
+            assert!(config.select_profile(Some(name)).is_err(), "{:?}", name);
+        }
+        config.profiles = BTreeMap::from([("game".to_string(), GameProfile::default())]);
+        assert!(config.validate().is_ok());
+        assert!(config.select_profile(Some("game")).is_ok());
+    }
+
+    #[test]
+    fn test_validate_checks_inactive_profile_roi() {
+        let mut config = BotConfig::default();
+        let profile = GameProfile {
+            roi: Some(Rect::new(-5, 0, 10, 10)),
+            ..Default::default()
+        };
+        config.profiles = BTreeMap::from([("game".to_string(), profile)]);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_update_undoes_invalid_change() {
+        let mut config = BotConfig::default();
+        let previous = config.clone();
+        assert!(config.update(|config| config.capture.fps = 0).is_err());
+        assert_eq!(config, previous);
+    }
+}
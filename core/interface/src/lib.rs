@@ -1,20 +1,105 @@
+use std::fmt;
+use std::sync::Arc;
+
 use platforms::windows_capture::window::Window;
 
+use services::CaptureTarget;
+
+pub mod config;
+pub mod logging;
 pub mod services;
 
 // Public API for the interface library
-pub use services::{Service, GraphicsCaptureService, MinimapServiceV2};
+pub use config::{BotConfig, ConfigWatcher};
+pub use logging::init_logging;
+pub use services::{BotEvent, EventBus, Service, FrameAnalyzer, GraphicsCaptureService};
+#[cfg(feature = "opencv")]
+pub use services::MinimapServiceV2;
+
+/// Directory holding the bot's settings, `%APPDATA%\starry-bot` on Windows
+pub fn config_dir() -> std::path::PathBuf {
+    std::env::var_os("APPDATA")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| std::path::PathBuf::from("."))
+        .join("starry-bot")
+}
 
 /// Initialize the platforms subsystem
 pub fn init() {
     platforms::init();
 }
 
-/// List all available windows by title
-pub fn list_window_handles() -> Vec<String> {
+/// Subscribe to the emergency stop (kill switch hotkey, `Pause` by default)
+pub fn kill_switch() -> Result<tokio::sync::broadcast::Receiver<()>, String> {
+    platforms::input::kill_switch_receiver()
+        .map_err(|e| format!("Failed to subscribe to kill switch: {}", e))
+}
+
+/// Fire the emergency stop as if the hotkey was pressed
+pub fn trigger_kill_switch() {
+    platforms::input::trigger_kill_switch();
+}
+
+/// Exclude the bot's own windows (UI, overlays) from screen capture
+pub fn exclude_own_windows_from_capture() -> Result<usize, String> {
+    platforms::exclude_own_windows_from_capture()
+        .map_err(|e| format!("Failed to exclude windows from capture: {}", e))
+}
+
+/// A window that can be captured, shown as "Title — process.exe (PID)"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowEntry {
+    pub title: String,
+    pub process_name: Option<String>,
+    pub pid: Option<u32>,
+    /// Raw window handle, telling apart windows with the same title
+    pub hwnd: isize,
+    /// Icon of the window, usually the one of its executable
+    pub icon: Option<Arc<platforms::Icon>>,
+}
+
+impl WindowEntry {
+    /// Capture exactly this window rather than the first with its title
+    pub fn target(&self) -> CaptureTarget {
+        CaptureTarget::Hwnd(self.hwnd)
+    }
+}
+
+impl fmt::Display for WindowEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.title)?;
+        match (&self.process_name, self.pid) {
+            (Some(process_name), Some(pid)) => write!(f, " — {} ({})", process_name, pid),
+            (Some(process_name), None) => write!(f, " — {}", process_name),
+            (None, Some(pid)) => write!(f, " ({})", pid),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// List all available windows with their process and icon
+pub fn list_window_handles() -> Vec<WindowEntry> {
     Window::enumerate()
         .unwrap_or_default()
         .into_iter()
-        .filter_map(|w| w.title().ok())
+        .filter_map(|window| {
+            let hwnd = window.as_raw_hwnd() as isize;
+            Some(WindowEntry {
+                title: window.title().ok()?,
+                process_name: window.process_name().ok(),
+                pid: window.process_id().ok(),
+                hwnd,
+                icon: platforms::window_icon(hwnd).map(Arc::new),
+            })
+        })
         .collect()
 }
+
+/// Title of the first window whose title contains `pattern`, ignoring case
+pub fn find_window(pattern: &str) -> Option<String> {
+    let pattern = pattern.to_lowercase();
+    list_window_handles()
+        .into_iter()
+        .find(|window| window.title.to_lowercase().contains(&pattern))
+        .map(|window| window.title)
+}
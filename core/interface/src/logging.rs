@@ -0,0 +1,53 @@
+//! Structured logging through `tracing`, to stderr and a daily log file
+//!
+//! The level comes from `STARRY_LOG` in [`EnvFilter`] syntax, e.g. `STARRY_LOG=debug` or
+//! `STARRY_LOG=info,interface::services::minimap_v2=trace`, and is `info` by default.
+
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter};
+
+/// Environment variable holding the log filter
+pub const LOG_FILTER_ENV: &str = "STARRY_LOG";
+
+/// Log files kept in `logs/`, older ones are deleted
+const LOG_FILES_KEPT: usize = 7;
+
+/// Flushes the log file when dropped, keep it for as long as the bot runs
+pub struct LogGuard {
+    _file: Option<WorkerGuard>,
+}
+
+/// Log to stderr and `logs/starry-bot.<date>.log` in the config directory
+///
+/// If the log file can't be created only stderr is logged to.
+pub fn init_logging() -> Result<LogGuard, String> {
+    let filter = EnvFilter::try_from_env(LOG_FILTER_ENV).unwrap_or_else(|_| EnvFilter::new("info"));
+    let dir = crate::config_dir().join("logs");
+    let (file, guard) = match RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("starry-bot")
+        .filename_suffix("log")
+        .max_log_files(LOG_FILES_KEPT)
+        .build(&dir)
+    {
+        Ok(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (Some(fmt::layer().with_ansi(false).with_writer(writer)), Some(guard))
+        }
+        Err(e) => {
+            eprintln!("Failed to create log file in {}: {}", dir.display(), e);
+            (None, None)
+        }
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .with(file)
+        .try_init()
+        .map_err(|e| format!("Failed to initialize logging: {}", e))?;
+    Ok(LogGuard { _file: guard })
+}
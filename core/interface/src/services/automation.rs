@@ -0,0 +1,402 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use platforms::input::KeyKind;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::services::{Service, ServiceError, ServiceTask};
+use super::bar_reader::{BarConfig, BarReader};
+use super::event_bus::{BotEvent, EventBus};
+use super::graphics_capture::{CapturedFrame, GraphicsCaptureService};
+use super::input_scheduler::{InputAction, InputPriority, InputScheduler};
+use super::minimap_blips::{BlipClass, MinimapBlip};
+use super::minimap_events::MinimapEvent;
+use super::minimap_v2::MinimapService;
+use super::player_arrow::PlayerPosition;
+use super::route::{Route, RouteService};
+
+/// Capacity of the event channel, slow subscribers skip old events
+const EVENT_CHANNEL_CAPACITY: usize = 32;
+
+/// What has to hold for a rule to fire
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Condition {
+    /// Bar `bar` is filled less than `fraction` (0.0 - 1.0)
+    BarBelow { bar: String, fraction: f32 },
+    BarAbove { bar: String, fraction: f32 },
+    /// A blip of `class` is within `radius` minimap pixels of the player
+    BlipWithin { class: BlipClass, radius: f32 },
+    /// The player marker was found on the minimap
+    PlayerVisible,
+    All { conditions: Vec<Condition> },
+    Any { conditions: Vec<Condition> },
+    Not { condition: Box<Condition> },
+}
+
+impl Condition {
+    /// Conditions on something not detected yet, like an unread bar, don't hold
+    pub fn holds(&self, state: &WorldState) -> bool {
+        match self {
+            Condition::BarBelow { bar, fraction } => state.bars.get(bar).is_some_and(|fill| fill < fraction),
+            Condition::BarAbove { bar, fraction } => state.bars.get(bar).is_some_and(|fill| fill > fraction),
+            Condition::BlipWithin { class, radius } => state.player.is_some_and(|player| {
+                state
+                    .blips
+                    .iter()
+                    .any(|blip| blip.class == *class && (blip.x - player.x).hypot(blip.y - player.y) <= *radius)
+            }),
+            Condition::PlayerVisible => state.player.is_some(),
+            Condition::All { conditions } => conditions.iter().all(|condition| condition.holds(state)),
+            Condition::Any { conditions } => conditions.iter().any(|condition| condition.holds(state)),
+            Condition::Not { condition } => !condition.holds(state),
+        }
+    }
+}
+
+/// What a rule does when it fires
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleAction {
+    KeyTap { key: KeyKind },
+    KeyHold {
+        key: KeyKind,
+        #[serde(with = "super::duration_millis")]
+        duration: Duration,
+    },
+    KeyCombo { keys: Vec<KeyKind> },
+    /// Walk a saved route, `reverse` walks it back from its last waypoint
+    PlayRoute {
+        route: String,
+        #[serde(default)]
+        reverse: bool,
+    },
+    StopRoute,
+}
+
+/// If `condition` holds, do `action`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    pub condition: Condition,
+    pub action: RuleAction,
+    /// Minimum time between two firings, the condition usually still holds on the next tick
+    #[serde(with = "super::duration_millis", default = "Rule::default_cooldown")]
+    pub cooldown: Duration,
+    #[serde(default = "Rule::default_enabled")]
+    pub enabled: bool,
+}
+
+impl Rule {
+    pub fn new(name: impl Into<String>, condition: Condition, action: RuleAction) -> Self {
+        Self {
+            name: name.into(),
+            condition,
+            action,
+            cooldown: Self::default_cooldown(),
+            enabled: true,
+        }
+    }
+
+    fn default_cooldown() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    fn default_enabled() -> bool {
+        true
+    }
+}
+
+/// Rules and the bars they read, persisted across restarts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutomationConfig {
+    /// How often rules are evaluated
+    #[serde(with = "super::duration_millis")]
+    pub tick: Duration,
+    /// Bars conditions refer to by name
+    pub bars: BTreeMap<String, BarConfig>,
+    /// Evaluated in order, the first rule that holds and isn't cooling down fires
+    pub rules: Vec<Rule>,
+}
+
+impl Default for AutomationConfig {
+    fn default() -> Self {
+        Self {
+            tick: Duration::from_millis(200),
+            bars: BTreeMap::new(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl AutomationConfig {
+    pub fn path() -> PathBuf {
+        crate::config_dir().join("automation.json")
+    }
+
+    /// Load the saved rules, falling back to none if there are none
+    pub fn load() -> Self {
+        let path = Self::path();
+        let Ok(json) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&json).unwrap_or_else(|e| {
+            tracing::warn!(path = %path.display(), error = %e, "Ignoring invalid automation rules");
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize automation rules: {}", e))?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+/// Latest detections rules are evaluated against
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct WorldState {
+    /// Fill of every configured bar that could be read, 0.0 - 1.0
+    pub bars: HashMap<String, f32>,
+    pub player: Option<PlayerPosition>,
+    /// Blips of the last processed minimap, in minimap pixels
+    pub blips: Vec<MinimapBlip>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AutomationEvent {
+    RuleFired { rule: String },
+    ActionFailed { rule: String, reason: String },
+}
+
+/// Evaluates prioritized rules against the latest detections every tick and acts on the
+/// first one that holds, through the input scheduler or the route service
+///
+/// Bars are read from captured frames, player and blips come from the minimap service, so
+/// capture and the minimap service have to run for rules to see anything.
+#[derive(Clone)]
+pub struct AutomationEngine {
+    graphics_service: Arc<GraphicsCaptureService>,
+    minimap: Arc<MinimapService>,
+    scheduler: Arc<InputScheduler>,
+    route: RouteService,
+    config: Arc<StdMutex<AutomationConfig>>,
+    state: Arc<StdMutex<WorldState>>,
+    event_sender: broadcast::Sender<AutomationEvent>,
+    task: ServiceTask,
+}
+
+impl AutomationEngine {
+    pub fn new(
+        graphics_service: Arc<GraphicsCaptureService>,
+        minimap: Arc<MinimapService>,
+        scheduler: Arc<InputScheduler>,
+        route: RouteService,
+        config: AutomationConfig,
+    ) -> Self {
+        Self {
+            graphics_service,
+            minimap,
+            scheduler,
+            route,
+            config: Arc::new(StdMutex::new(config)),
+            state: Arc::new(StdMutex::new(WorldState::default())),
+            event_sender: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            task: ServiceTask::new(),
+        }
+    }
+
+    /// Fired rules and failed actions
+    pub fn subscribe(&self) -> broadcast::Receiver<AutomationEvent> {
+        self.event_sender.subscribe()
+    }
+
+    pub fn config(&self) -> AutomationConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Replace the rules and save them, they apply from the next tick
+    pub fn set_config(&self, config: AutomationConfig) -> Result<(), String> {
+        *self.config.lock().unwrap() = config.clone();
+        config.save()
+    }
+
+    /// Route service route actions are sent to
+    pub fn route(&self) -> &RouteService {
+        &self.route
+    }
+
+    /// Detections as of the last tick
+    pub fn state(&self) -> WorldState {
+        self.state.lock().unwrap().clone()
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.task.is_busy().await
+    }
+
+    pub async fn start_engine(&self) -> Result<(), String> {
+        let mut frames = self.graphics_service.subscribe();
+        let mut players = self.minimap.subscribe_player();
+        let mut blips = self.minimap.subscribe_blips();
+        let mut minimap_events = self.minimap.subscribe_events();
+        let engine = self.clone();
+
+        self.task.start(move |cancelled| async move {
+            let mut ticker = tokio::time::interval(engine.config().tick);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let mut latest_frame: Option<Arc<CapturedFrame>> = None;
+            let mut last_fired: HashMap<String, Instant> = HashMap::new();
+            let mut bar_readers: HashMap<String, BarReader> = HashMap::new();
+
+            loop {
+                tokio::select! {
+                    // Checked first so no rule fires once stopping was asked for
+                    biased;
+                    _ = cancelled.cancelled() => break,
+                    _ = ticker.tick() => {
+                        let config = engine.config();
+                        if ticker.period() != config.tick {
+                            ticker = tokio::time::interval(config.tick);
+                            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+                        }
+                        if let Some(frame) = latest_frame.take() {
+                            engine.read_bars(&config, &frame, &mut bar_readers);
+                        }
+                        engine.tick(&config, &mut last_fired).await;
+                    }
+                    frame = frames.recv() => match frame {
+                        Ok(frame) => latest_frame = Some(frame),
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    player = players.recv() => match player {
+                        Ok(player) => engine.state.lock().unwrap().player = Some(player),
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    found = blips.recv() => match found {
+                        Ok(found) => engine.state.lock().unwrap().blips = found,
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    // Per-frame channels stay quiet when nothing is found, losses come as events
+                    event = minimap_events.recv() => match event {
+                        Ok(MinimapEvent::PlayerLost) => engine.state.lock().unwrap().player = None,
+                        Ok(MinimapEvent::MinimapLost) => {
+                            let mut state = engine.state.lock().unwrap();
+                            state.player = None;
+                            state.blips.clear();
+                        }
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                }
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Stop firing rules, a tick in progress finishes its actions before this returns
+    pub async fn stop_engine(&self) {
+        self.task.stop().await;
+    }
+
+    fn read_bars(&self, config: &AutomationConfig, frame: &CapturedFrame, readers: &mut HashMap<String, BarReader>) {
+        readers.retain(|name, _| config.bars.contains_key(name));
+        let mut fills = HashMap::new();
+        for (name, bar) in &config.bars {
+            let reader = readers.entry(name.clone()).or_insert_with(|| BarReader::new(bar.clone()));
+            if reader.config() != bar {
+                reader.set_config(bar.clone());
+            }
+            match reader.read(frame) {
+                Ok(fill) => {
+                    fills.insert(name.clone(), fill);
+                }
+                Err(e) => tracing::debug!(bar = %name, error = %e, "Failed to read bar"),
+            }
+        }
+        self.state.lock().unwrap().bars = fills;
+    }
+
+    /// Fire the first rule that holds and isn't cooling down
+    async fn tick(&self, config: &AutomationConfig, last_fired: &mut HashMap<String, Instant>) {
+        let state = self.state();
+        let now = Instant::now();
+        let Some(rule) = config.rules.iter().find(|rule| {
+            rule.enabled
+                && last_fired
+                    .get(&rule.name)
+                    .map_or(true, |last| now.duration_since(*last) >= rule.cooldown)
+                && rule.condition.holds(&state)
+        }) else {
+            return;
+        };
+        last_fired.insert(rule.name.clone(), now);
+
+        let event = match self.perform(&rule.action).await {
+            Ok(()) => {
+                EventBus::global().publish(BotEvent::RuleFired { rule: rule.name.clone() });
+                AutomationEvent::RuleFired { rule: rule.name.clone() }
+            }
+            Err(reason) => {
+                tracing::warn!(rule = %rule.name, %reason, "Rule failed");
+                AutomationEvent::ActionFailed {
+                    rule: rule.name.clone(),
+                    reason,
+                }
+            }
+        };
+        let _ = self.event_sender.send(event);
+    }
+
+    /// Do `action` now, whether or not a rule asked for it
+    pub async fn perform(&self, action: &RuleAction) -> Result<(), String> {
+        let input = match action {
+            RuleAction::KeyTap { key } => InputAction::KeyTap(*key),
+            RuleAction::KeyHold { key, duration } => InputAction::KeyHold(*key, *duration),
+            RuleAction::KeyCombo { keys } => InputAction::KeyCombo(keys.clone()),
+            RuleAction::PlayRoute { route, reverse } => {
+                let route = Route::load(route)?;
+                let route = if *reverse { route.reversed() } else { route };
+                // Whatever is walked now is interrupted, e.g. retreating from a farming loop
+                self.route.stop().await;
+                return self.route.play(route).await;
+            }
+            RuleAction::StopRoute => {
+                self.route.stop().await;
+                return Ok(());
+            }
+        };
+        // Rules react to what is on screen, so they go ahead of queued routine input
+        self.scheduler.schedule(input, InputPriority::High).await.map(|_| ())
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for AutomationEngine {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_engine().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_engine().await;
+        Ok(())
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        vec!["MinimapService".to_string(), "InputScheduler".to_string()]
+    }
+}
@@ -0,0 +1,162 @@
+use opencv::{
+    core::{bitwise_or_def, Mat},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use super::graphics_capture::CapturedFrame;
+use super::vision::{bgra_mat_region, to_hsv, HsvRange, Rect};
+
+/// Filled slices shorter than this many pixels are treated as noise, e.g. a stray red pixel
+/// in the empty part of the bar
+const MIN_FILL_RUN: usize = 2;
+
+/// Which edge a bar fills from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BarDirection {
+    #[default]
+    LeftToRight,
+    RightToLeft,
+    BottomToTop,
+    TopToBottom,
+}
+
+impl BarDirection {
+    fn is_horizontal(self) -> bool {
+        matches!(self, BarDirection::LeftToRight | BarDirection::RightToLeft)
+    }
+}
+
+/// A bar on screen and what its filled part looks like
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BarConfig {
+    /// Bar interior in frame pixels, without the border
+    pub rect: Rect,
+    /// Colors of the filled part, several ranges for colors like red that wrap around the hue
+    pub colors: Vec<HsvRange>,
+    #[serde(default)]
+    pub direction: BarDirection,
+    /// Fraction of a slice across the bar that must match for the slice to count as filled,
+    /// below 1.0 so text drawn over the bar doesn't break the reading
+    #[serde(default = "BarConfig::default_slice_threshold")]
+    pub slice_threshold: f32,
+}
+
+impl BarConfig {
+    pub fn new(rect: Rect, colors: Vec<HsvRange>) -> Self {
+        Self {
+            rect,
+            colors,
+            direction: BarDirection::default(),
+            slice_threshold: Self::default_slice_threshold(),
+        }
+    }
+
+    /// A red health bar
+    pub fn health(rect: Rect) -> Self {
+        Self::new(
+            rect,
+            vec![
+                HsvRange::new([0, 120, 90], [10, 255, 255]),
+                HsvRange::new([170, 120, 90], [179, 255, 255]),
+            ],
+        )
+    }
+
+    /// A blue mana bar
+    pub fn mana(rect: Rect) -> Self {
+        Self::new(rect, vec![HsvRange::new([95, 120, 90], [130, 255, 255])])
+    }
+
+    fn default_slice_threshold() -> f32 {
+        0.4
+    }
+}
+
+/// Reads how full a health, mana or other resource bar is from its color
+#[derive(Debug, Clone)]
+pub struct BarReader {
+    config: BarConfig,
+}
+
+impl BarReader {
+    pub fn new(config: BarConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &BarConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: BarConfig) {
+        self.config = config;
+    }
+
+    /// Fill of the bar in a captured frame, 0.0 (empty) to 1.0 (full)
+    pub fn read(&self, frame: &CapturedFrame) -> Result<f32, String> {
+        let bgra = bgra_mat_region(frame, self.config.rect)?;
+        self.read_hsv(&to_hsv(&bgra)?)
+    }
+
+    /// Fill of the bar in an HSV image already cropped to the bar
+    pub fn read_hsv(&self, hsv: &Mat) -> Result<f32, String> {
+        let mut colors = self.config.colors.iter();
+        let Some(first) = colors.next() else {
+            return Err("Bar has no fill colors".to_string());
+        };
+        let mut mask = first.mask(hsv)?;
+        for color in colors {
+            let mut combined = Mat::default();
+            bitwise_or_def(&mask, &color.mask(hsv)?, &mut combined)
+                .map_err(|e| format!("Failed to combine masks: {}", e))?;
+            mask = combined;
+        }
+
+        let size = mask.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
+        let (width, height) = (size.width as usize, size.height as usize);
+        let data = mask
+            .data_bytes()
+            .map_err(|e| format!("Failed to access Mat data: {}", e))?;
+
+        // Fraction of matching pixels in each slice across the bar, ordered from the empty edge
+        // to the full edge
+        let horizontal = self.config.direction.is_horizontal();
+        let (length, across) = if horizontal { (width, height) } else { (height, width) };
+        let mut slices: Vec<f32> = (0..length)
+            .map(|i| {
+                let matching = (0..across)
+                    .filter(|&j| {
+                        let (x, y) = if horizontal { (i, j) } else { (j, i) };
+                        data[y * width + x] != 0
+                    })
+                    .count();
+                matching as f32 / across.max(1) as f32
+            })
+            .collect();
+        if matches!(self.config.direction, BarDirection::RightToLeft | BarDirection::BottomToTop) {
+            slices.reverse();
+        }
+
+        Ok(Self::fill(&slices, self.config.slice_threshold))
+    }
+
+    /// End of the last run of filled slices, as a fraction of the bar length
+    ///
+    /// Gaps inside the filled part (text, tick marks) don't cut the reading short.
+    fn fill(slices: &[f32], threshold: f32) -> f32 {
+        let mut end = 0;
+        let mut run = 0;
+        for (i, &slice) in slices.iter().enumerate() {
+            if slice >= threshold {
+                run += 1;
+                if run >= MIN_FILL_RUN.min(slices.len()) {
+                    end = i + 1;
+                }
+            } else {
+                run = 0;
+            }
+        }
+        end as f32 / slices.len().max(1) as f32
+    }
+}
@@ -0,0 +1,677 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::services::{Service, ServiceError, ServiceTask};
+use super::automation::{AutomationEngine, Condition, RuleAction, WorldState};
+use super::event_bus::{BotEvent, EventBus};
+use super::game_state::{GameState, GameStateService};
+use super::route::RouteEvent;
+
+/// Capacity of the status channel, slow subscribers skip old results
+const STATUS_CHANNEL_CAPACITY: usize = 16;
+
+/// Declarative node of a behavior tree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Node {
+    /// Runs children in order until one fails
+    Sequence { children: Vec<Node> },
+    /// Runs children in order until one succeeds
+    Selector { children: Vec<Node> },
+    /// Success becomes failure and the other way around
+    Inverter { child: Box<Node> },
+    /// Succeeds once the child finished, whatever its result
+    Succeed { child: Box<Node> },
+    /// Runs the child `times` times, forever without a count, stops at the first failure
+    Repeat {
+        child: Box<Node>,
+        #[serde(default)]
+        times: Option<u32>,
+    },
+    /// Runs the child again after a failure, up to `attempts` times in total
+    Retry { child: Box<Node>, attempts: u32 },
+    /// Fails if the child is still running after `duration`
+    Timeout {
+        child: Box<Node>,
+        #[serde(with = "super::duration_millis")]
+        duration: Duration,
+    },
+    /// Running for `duration`, then succeeds
+    Wait {
+        #[serde(with = "super::duration_millis")]
+        duration: Duration,
+    },
+    /// Succeeds if the condition holds on the latest detections
+    Check { condition: Condition },
+    /// Succeeds if the game state classifier is in `state`
+    InState { state: GameState },
+    /// Sends the action and succeeds right away
+    Action { action: RuleAction },
+    /// Walks a saved route and succeeds once it was finished, fails if walking it failed
+    FollowRoute {
+        route: String,
+        #[serde(default)]
+        reverse: bool,
+    },
+}
+
+/// A named tree, saved as JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BehaviorTree {
+    pub name: String,
+    pub root: Node,
+    /// How often the tree is ticked
+    #[serde(with = "super::duration_millis", default = "BehaviorTree::default_tick")]
+    pub tick: Duration,
+}
+
+impl BehaviorTree {
+    pub fn new(name: impl Into<String>, root: Node) -> Self {
+        Self {
+            name: name.into(),
+            root,
+            tick: Self::default_tick(),
+        }
+    }
+
+    fn default_tick() -> Duration {
+        Duration::from_millis(200)
+    }
+
+    /// Directory trees are saved in
+    pub fn dir() -> PathBuf {
+        crate::config_dir().join("behaviors")
+    }
+
+    pub fn path(name: &str) -> PathBuf {
+        Self::dir().join(format!("{}.json", name))
+    }
+
+    /// Names of all saved trees
+    pub fn list() -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(Self::dir()) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+            .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string))
+            .collect()
+    }
+
+    pub fn load(name: &str) -> Result<Self, String> {
+        Self::load_file(Self::path(name))
+    }
+
+    pub fn load_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read behavior tree {}: {}", path.display(), e))?;
+        serde_json::from_str(&json).map_err(|e| format!("Invalid behavior tree {}: {}", path.display(), e))
+    }
+
+    /// Save to [`BehaviorTree::dir`] under the tree's name
+    pub fn save(&self) -> Result<(), String> {
+        if self.name.is_empty() || self.name.contains(['/', '\\', '.']) {
+            return Err(format!("Invalid behavior tree name {:?}", self.name));
+        }
+        let dir = Self::dir();
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        let path = Self::path(&self.name);
+        let json =
+            serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize behavior tree: {}", e))?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write behavior tree {}: {}", path.display(), e))
+    }
+}
+
+/// Result of ticking a node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeStatus {
+    Success,
+    Failure,
+    /// Not done yet, ticked again on the next tick
+    Running,
+}
+
+/// What leaves see during a tick and what they ask for
+///
+/// Ticking doesn't block, actions are collected in `commands` and sent after the tick.
+struct TickContext<'a> {
+    world: &'a WorldState,
+    /// `None` without a game state classifier, `in_state` leaves fail then
+    game_state: Option<GameState>,
+    /// Outcome of routes walked since their `follow_route` leaf started, `true` if finished
+    route_results: &'a mut HashMap<String, bool>,
+    now: Instant,
+    commands: Vec<RuleAction>,
+}
+
+/// A node with the state it keeps between ticks
+#[derive(Debug)]
+enum TreeNode {
+    Sequence { children: Vec<TreeNode>, current: usize },
+    Selector { children: Vec<TreeNode>, current: usize },
+    Inverter(Box<TreeNode>),
+    Succeed(Box<TreeNode>),
+    Repeat { child: Box<TreeNode>, times: Option<u32>, done: u32 },
+    Retry { child: Box<TreeNode>, attempts: u32, failed: u32 },
+    Timeout { child: Box<TreeNode>, duration: Duration, started: Option<Instant> },
+    Wait { duration: Duration, started: Option<Instant> },
+    Check(Condition),
+    InState(GameState),
+    Action(RuleAction),
+    FollowRoute { route: String, reverse: bool, started: bool },
+}
+
+impl From<&Node> for TreeNode {
+    fn from(node: &Node) -> Self {
+        let boxed = |child: &Node| Box::new(TreeNode::from(child));
+        match node {
+            Node::Sequence { children } => TreeNode::Sequence {
+                children: children.iter().map(TreeNode::from).collect(),
+                current: 0,
+            },
+            Node::Selector { children } => TreeNode::Selector {
+                children: children.iter().map(TreeNode::from).collect(),
+                current: 0,
+            },
+            Node::Inverter { child } => TreeNode::Inverter(boxed(child)),
+            Node::Succeed { child } => TreeNode::Succeed(boxed(child)),
+            Node::Repeat { child, times } => TreeNode::Repeat {
+                child: boxed(child),
+                times: *times,
+                done: 0,
+            },
+            Node::Retry { child, attempts } => TreeNode::Retry {
+                child: boxed(child),
+                attempts: *attempts,
+                failed: 0,
+            },
+            Node::Timeout { child, duration } => TreeNode::Timeout {
+                child: boxed(child),
+                duration: *duration,
+                started: None,
+            },
+            Node::Wait { duration } => TreeNode::Wait {
+                duration: *duration,
+                started: None,
+            },
+            Node::Check { condition } => TreeNode::Check(condition.clone()),
+            Node::InState { state } => TreeNode::InState(*state),
+            Node::Action { action } => TreeNode::Action(action.clone()),
+            Node::FollowRoute { route, reverse } => TreeNode::FollowRoute {
+                route: route.clone(),
+                reverse: *reverse,
+                started: false,
+            },
+        }
+    }
+}
+
+impl TreeNode {
+    fn tick(&mut self, context: &mut TickContext) -> NodeStatus {
+        let status = match self {
+            TreeNode::Sequence { children, current } => {
+                Self::tick_children(children, current, context, NodeStatus::Failure)
+            }
+            TreeNode::Selector { children, current } => {
+                Self::tick_children(children, current, context, NodeStatus::Success)
+            }
+            TreeNode::Inverter(child) => match child.tick(context) {
+                NodeStatus::Success => NodeStatus::Failure,
+                NodeStatus::Failure => NodeStatus::Success,
+                NodeStatus::Running => NodeStatus::Running,
+            },
+            TreeNode::Succeed(child) => match child.tick(context) {
+                NodeStatus::Running => NodeStatus::Running,
+                _ => NodeStatus::Success,
+            },
+            TreeNode::Repeat { child, times, done } => match child.tick(context) {
+                NodeStatus::Success => {
+                    *done += 1;
+                    // The next round starts on the next tick, a child that succeeds right
+                    // away can't spin within one tick
+                    if times.is_some_and(|times| *done >= times) {
+                        NodeStatus::Success
+                    } else {
+                        NodeStatus::Running
+                    }
+                }
+                status => status,
+            },
+            TreeNode::Retry { child, attempts, failed } => match child.tick(context) {
+                NodeStatus::Failure => {
+                    *failed += 1;
+                    if *failed >= *attempts {
+                        NodeStatus::Failure
+                    } else {
+                        NodeStatus::Running
+                    }
+                }
+                status => status,
+            },
+            TreeNode::Timeout { child, duration, started } => {
+                let started = *started.get_or_insert(context.now);
+                if context.now.duration_since(started) >= *duration {
+                    child.reset();
+                    NodeStatus::Failure
+                } else {
+                    child.tick(context)
+                }
+            }
+            TreeNode::Wait { duration, started } => {
+                let started = *started.get_or_insert(context.now);
+                if context.now.duration_since(started) >= *duration {
+                    NodeStatus::Success
+                } else {
+                    NodeStatus::Running
+                }
+            }
+            TreeNode::Check(condition) => Self::status(condition.holds(context.world)),
+            TreeNode::InState(state) => Self::status(context.game_state == Some(*state)),
+            TreeNode::Action(action) => {
+                context.commands.push(action.clone());
+                NodeStatus::Success
+            }
+            TreeNode::FollowRoute { route, reverse, started } => {
+                if !*started {
+                    *started = true;
+                    context.route_results.remove(route.as_str());
+                    context.commands.push(RuleAction::PlayRoute {
+                        route: route.clone(),
+                        reverse: *reverse,
+                    });
+                    NodeStatus::Running
+                } else {
+                    match context.route_results.remove(route.as_str()) {
+                        Some(finished) => Self::status(finished),
+                        None => NodeStatus::Running,
+                    }
+                }
+            }
+        };
+
+        // Finished nodes start over the next time they are ticked
+        if status != NodeStatus::Running {
+            self.reset();
+        }
+        status
+    }
+
+    /// Tick children from `current` on until one is running or returns `stop`
+    fn tick_children(
+        children: &mut [TreeNode],
+        current: &mut usize,
+        context: &mut TickContext,
+        stop: NodeStatus,
+    ) -> NodeStatus {
+        while let Some(child) = children.get_mut(*current) {
+            match child.tick(context) {
+                NodeStatus::Running => return NodeStatus::Running,
+                status if status == stop => return stop,
+                _ => *current += 1,
+            }
+        }
+        // Every child continued the sequence (or selector) to its end
+        match stop {
+            NodeStatus::Failure => NodeStatus::Success,
+            _ => NodeStatus::Failure,
+        }
+    }
+
+    fn status(success: bool) -> NodeStatus {
+        if success {
+            NodeStatus::Success
+        } else {
+            NodeStatus::Failure
+        }
+    }
+
+    /// Forget progress, e.g. when a running branch is interrupted by a timeout
+    fn reset(&mut self) {
+        match self {
+            TreeNode::Sequence { children, current } | TreeNode::Selector { children, current } => {
+                *current = 0;
+                children.iter_mut().for_each(TreeNode::reset);
+            }
+            TreeNode::Inverter(child) | TreeNode::Succeed(child) => child.reset(),
+            TreeNode::Repeat { child, done, .. } => {
+                *done = 0;
+                child.reset();
+            }
+            TreeNode::Retry { child, failed, .. } => {
+                *failed = 0;
+                child.reset();
+            }
+            TreeNode::Timeout { child, started, .. } => {
+                *started = None;
+                child.reset();
+            }
+            TreeNode::Wait { started, .. } => *started = None,
+            TreeNode::FollowRoute { started, .. } => *started = false,
+            TreeNode::Check(_) | TreeNode::InState(_) | TreeNode::Action(_) => {}
+        }
+    }
+}
+
+/// Ticks a behavior tree against the automation engine's detections and sends the actions of
+/// its leaves through the engine
+///
+/// The engine has to run for detections to be current, its rules can stay empty.
+#[derive(Clone)]
+pub struct BehaviorTreeRunner {
+    engine: AutomationEngine,
+    game_state: Option<GameStateService>,
+    tree: Arc<StdMutex<BehaviorTree>>,
+    status_sender: broadcast::Sender<NodeStatus>,
+    task: ServiceTask,
+}
+
+impl BehaviorTreeRunner {
+    pub fn new(engine: AutomationEngine, tree: BehaviorTree) -> Self {
+        Self {
+            engine,
+            game_state: None,
+            tree: Arc::new(StdMutex::new(tree)),
+            status_sender: broadcast::channel(STATUS_CHANNEL_CAPACITY).0,
+            task: ServiceTask::new(),
+        }
+    }
+
+    /// Let `in_state` leaves see the classified game state
+    pub fn with_game_state(mut self, game_state: GameStateService) -> Self {
+        self.game_state = Some(game_state);
+        self
+    }
+
+    /// Status of the root after every tick that finished the tree
+    pub fn subscribe(&self) -> broadcast::Receiver<NodeStatus> {
+        self.status_sender.subscribe()
+    }
+
+    pub fn tree(&self) -> BehaviorTree {
+        self.tree.lock().unwrap().clone()
+    }
+
+    /// Replace the tree, a running tree starts over from its root on the next tick
+    pub fn set_tree(&self, tree: BehaviorTree) {
+        *self.tree.lock().unwrap() = tree;
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.task.is_busy().await
+    }
+
+    pub async fn start_tree(&self) -> Result<(), String> {
+        let mut route_events = self.engine.route().subscribe();
+        let runner = self.clone();
+
+        self.task.start(move |cancelled| async move {
+            let mut definition = runner.tree();
+            let mut root = TreeNode::from(&definition.root);
+            let mut route_results = HashMap::new();
+            let mut ticker = tokio::time::interval(definition.tick);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+            loop {
+                tokio::select! {
+                    // Checked first so the tree doesn't tick again once stopping was asked for
+                    biased;
+                    _ = cancelled.cancelled() => break,
+                    _ = ticker.tick() => {
+                        let current = runner.tree();
+                        if current != definition {
+                            root = TreeNode::from(&current.root);
+                            if current.tick != definition.tick {
+                                ticker = tokio::time::interval(current.tick);
+                                ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+                            }
+                            definition = current;
+                        }
+
+                        let world = runner.engine.state();
+                        let mut context = TickContext {
+                            world: &world,
+                            game_state: runner.game_state.as_ref().map(GameStateService::state),
+                            route_results: &mut route_results,
+                            now: Instant::now(),
+                            commands: Vec::new(),
+                        };
+                        let status = root.tick(&mut context);
+                        for action in std::mem::take(&mut context.commands) {
+                            if let Err(e) = runner.engine.perform(&action).await {
+                                tracing::warn!(tree = %definition.name, error = %e, "Behavior tree action failed");
+                                EventBus::global().publish(BotEvent::ScriptError {
+                                    script: definition.name.clone(),
+                                    error: e,
+                                });
+                            }
+                        }
+                        if status != NodeStatus::Running {
+                            let _ = runner.status_sender.send(status);
+                        }
+                    }
+                    event = route_events.recv() => match event {
+                        Ok(RouteEvent::Finished { route }) => {
+                            route_results.insert(route, true);
+                        }
+                        Ok(RouteEvent::Failed { route, .. }) | Ok(RouteEvent::Stopped { route }) => {
+                            route_results.insert(route, false);
+                        }
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                }
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Stop ticking, the actions of a tick in progress are sent before this returns
+    pub async fn stop_tree(&self) {
+        self.task.stop().await;
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for BehaviorTreeRunner {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_tree().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_tree().await;
+        Ok(())
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        vec!["AutomationEngine".to_string()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use platforms::input::KeyKind;
+
+    fn succeed() -> Node {
+        Node::InState { state: GameState::InWorld }
+    }
+
+    fn fail() -> Node {
+        Node::InState { state: GameState::Dead }
+    }
+
+    fn tap(key: KeyKind) -> Node {
+        Node::Action {
+            action: RuleAction::KeyTap { key },
+        }
+    }
+
+    fn wait(millis: u64) -> Node {
+        Node::Wait {
+            duration: Duration::from_millis(millis),
+        }
+    }
+
+    /// Tick `node` in the `in_world` state, returns the status and the actions it sent
+    fn tick(
+        node: &mut TreeNode,
+        now: Instant,
+        route_results: &mut HashMap<String, bool>,
+    ) -> (NodeStatus, Vec<RuleAction>) {
+        let world = WorldState::default();
+        let mut context = TickContext {
+            world: &world,
+            game_state: Some(GameState::InWorld),
+            route_results,
+            now,
+            commands: Vec::new(),
+        };
+        let status = node.tick(&mut context);
+        (status, context.commands)
+    }
+
+    fn tick_once(node: Node) -> (NodeStatus, Vec<RuleAction>) {
+        tick(&mut TreeNode::from(&node), Instant::now(), &mut HashMap::new())
+    }
+
+    fn taps(keys: &[KeyKind]) -> Vec<RuleAction> {
+        keys.iter().map(|&key| RuleAction::KeyTap { key }).collect()
+    }
+
+    #[test]
+    fn test_sequence_stops_at_first_failure() {
+        let (status, commands) = tick_once(Node::Sequence {
+            children: vec![tap(KeyKind::A), fail(), tap(KeyKind::B)],
+        });
+        assert_eq!(status, NodeStatus::Failure);
+        assert_eq!(commands, taps(&[KeyKind::A]));
+
+        let (status, commands) = tick_once(Node::Sequence {
+            children: vec![tap(KeyKind::A), tap(KeyKind::B)],
+        });
+        assert_eq!(status, NodeStatus::Success);
+        assert_eq!(commands, taps(&[KeyKind::A, KeyKind::B]));
+    }
+
+    #[test]
+    fn test_selector_stops_at_first_success() {
+        let (status, commands) = tick_once(Node::Selector {
+            children: vec![fail(), tap(KeyKind::A), tap(KeyKind::B)],
+        });
+        assert_eq!(status, NodeStatus::Success);
+        assert_eq!(commands, taps(&[KeyKind::A]));
+
+        let (status, commands) = tick_once(Node::Selector {
+            children: vec![fail(), fail()],
+        });
+        assert_eq!(status, NodeStatus::Failure);
+        assert!(commands.is_empty());
+    }
+
+    #[test]
+    fn test_running_child_resumes_on_next_tick() {
+        let mut node = TreeNode::from(&Node::Sequence {
+            children: vec![tap(KeyKind::A), wait(100), tap(KeyKind::B)],
+        });
+        let start = Instant::now();
+        let mut route_results = HashMap::new();
+
+        assert_eq!(tick(&mut node, start, &mut route_results), (NodeStatus::Running, taps(&[KeyKind::A])));
+        // Children before the running one aren't ticked again
+        let halfway = start + Duration::from_millis(50);
+        assert_eq!(tick(&mut node, halfway, &mut route_results), (NodeStatus::Running, Vec::new()));
+        let done = start + Duration::from_millis(100);
+        assert_eq!(tick(&mut node, done, &mut route_results), (NodeStatus::Success, taps(&[KeyKind::B])));
+        // A finished tree starts over from its first child
+        assert_eq!(tick(&mut node, done, &mut route_results), (NodeStatus::Running, taps(&[KeyKind::A])));
+    }
+
+    #[test]
+    fn test_inverter_and_succeed() {
+        assert_eq!(tick_once(Node::Inverter { child: Box::new(succeed()) }).0, NodeStatus::Failure);
+        assert_eq!(tick_once(Node::Inverter { child: Box::new(fail()) }).0, NodeStatus::Success);
+        assert_eq!(tick_once(Node::Inverter { child: Box::new(wait(100)) }).0, NodeStatus::Running);
+        assert_eq!(tick_once(Node::Succeed { child: Box::new(fail()) }).0, NodeStatus::Success);
+        assert_eq!(tick_once(Node::Succeed { child: Box::new(wait(100)) }).0, NodeStatus::Running);
+    }
+
+    #[test]
+    fn test_repeat_runs_one_round_per_tick() {
+        let mut node = TreeNode::from(&Node::Repeat {
+            child: Box::new(tap(KeyKind::A)),
+            times: Some(2),
+        });
+        let now = Instant::now();
+        let mut route_results = HashMap::new();
+        assert_eq!(tick(&mut node, now, &mut route_results), (NodeStatus::Running, taps(&[KeyKind::A])));
+        assert_eq!(tick(&mut node, now, &mut route_results), (NodeStatus::Success, taps(&[KeyKind::A])));
+
+        let mut node = TreeNode::from(&Node::Repeat {
+            child: Box::new(fail()),
+            times: None,
+        });
+        assert_eq!(tick(&mut node, now, &mut route_results).0, NodeStatus::Failure);
+    }
+
+    #[test]
+    fn test_retry_gives_up_after_attempts() {
+        let mut node = TreeNode::from(&Node::Retry {
+            child: Box::new(fail()),
+            attempts: 2,
+        });
+        let now = Instant::now();
+        let mut route_results = HashMap::new();
+        assert_eq!(tick(&mut node, now, &mut route_results).0, NodeStatus::Running);
+        assert_eq!(tick(&mut node, now, &mut route_results).0, NodeStatus::Failure);
+        // Attempts are counted again after giving up
+        assert_eq!(tick(&mut node, now, &mut route_results).0, NodeStatus::Running);
+    }
+
+    #[test]
+    fn test_timeout_fails_running_child() {
+        let mut node = TreeNode::from(&Node::Timeout {
+            child: Box::new(wait(100)),
+            duration: Duration::from_millis(50),
+        });
+        let start = Instant::now();
+        let mut route_results = HashMap::new();
+        assert_eq!(tick(&mut node, start, &mut route_results).0, NodeStatus::Running);
+        let late = start + Duration::from_millis(50);
+        assert_eq!(tick(&mut node, late, &mut route_results).0, NodeStatus::Failure);
+    }
+
+    #[test]
+    fn test_follow_route_waits_for_result() {
+        let mut node = TreeNode::from(&Node::FollowRoute {
+            route: "farm".to_string(),
+            reverse: false,
+        });
+        let now = Instant::now();
+        let mut route_results = HashMap::new();
+        let (status, commands) = tick(&mut node, now, &mut route_results);
+        assert_eq!(status, NodeStatus::Running);
+        assert_eq!(
+            commands,
+            vec![RuleAction::PlayRoute {
+                route: "farm".to_string(),
+                reverse: false,
+            }]
+        );
+        assert_eq!(tick(&mut node, now, &mut route_results), (NodeStatus::Running, Vec::new()));
+
+        route_results.insert("farm".to_string(), false);
+        assert_eq!(tick(&mut node, now, &mut route_results).0, NodeStatus::Failure);
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::services::{Service, ServiceError, ServiceTask};
+use super::event_bus::{BotEvent, EventBus};
+use super::ocr::{OcrRegion, OcrService};
+
+/// Capacity of the chat event channel, slow subscribers skip old events
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+/// Text to look out for in chat
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatKeyword {
+    /// Name events for this keyword are raised under
+    pub name: String,
+    /// Matched case insensitively anywhere in a line
+    pub text: String,
+    /// Fire the kill switch when the keyword shows up, stopping all automation
+    #[serde(default)]
+    pub pause: bool,
+}
+
+impl ChatKeyword {
+    pub fn new(name: impl Into<String>, text: impl Into<String>, pause: bool) -> Self {
+        Self {
+            name: name.into(),
+            text: text.into(),
+            pause,
+        }
+    }
+
+    pub fn matches(&self, line: &str) -> bool {
+        !self.text.is_empty() && line.to_lowercase().contains(&self.text.to_lowercase())
+    }
+}
+
+/// Where chat is read from and what to look for, persisted across restarts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChatMonitorConfig {
+    /// Chat box on screen, nothing is read until it is set
+    pub region: Option<OcrRegion>,
+    pub keywords: Vec<ChatKeyword>,
+    /// Lines remembered to skip repeats, chat stays on screen for many reads
+    pub history: usize,
+    /// Minimum time between two alerts of the same keyword, OCR noise can make a line
+    /// look new again
+    #[serde(with = "super::duration_millis")]
+    pub cooldown: Duration,
+}
+
+impl Default for ChatMonitorConfig {
+    fn default() -> Self {
+        Self {
+            region: None,
+            keywords: vec![
+                ChatKeyword::new("gm", "[GM]", true),
+                ChatKeyword::new("party_invite", "invited you", false),
+            ],
+            history: 200,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ChatMonitorConfig {
+    pub fn path() -> PathBuf {
+        crate::config_dir().join("chat_monitor.json")
+    }
+
+    /// Load the saved settings, falling back to defaults if there are none
+    pub fn load() -> Self {
+        let path = Self::path();
+        let Ok(json) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&json).unwrap_or_else(|e| {
+            tracing::warn!(path = %path.display(), error = %e, "Ignoring invalid chat monitor settings");
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize chat monitor settings: {}", e))?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChatEvent {
+    /// A chat line not seen before
+    Line { text: String },
+    /// A line containing a keyword
+    Keyword {
+        keyword: String,
+        line: String,
+        /// The kill switch was fired
+        paused: bool,
+    },
+}
+
+/// Recently seen chat lines, in order
+#[derive(Debug, Default)]
+struct ChatLog {
+    lines: VecDeque<String>,
+    seen: HashSet<String>,
+}
+
+impl ChatLog {
+    /// Whitespace and case differences between two reads of the same line don't count
+    fn normalize(line: &str) -> String {
+        line.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+    }
+
+    /// Lines of `text` not seen within the last `history` lines
+    fn new_lines(&mut self, text: &str, history: usize) -> Vec<String> {
+        let mut new_lines = Vec::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let key = Self::normalize(line);
+            if !self.seen.insert(key.clone()) {
+                continue;
+            }
+            self.lines.push_back(key);
+            new_lines.push(line.to_string());
+        }
+        while self.lines.len() > history {
+            if let Some(old) = self.lines.pop_front() {
+                self.seen.remove(&old);
+            }
+        }
+        new_lines
+    }
+}
+
+/// Reads the chat box, raises events for new lines and keyword hits and optionally stops
+/// automation, e.g. when a GM whispers while the bot runs unattended
+///
+/// The chat region is added to the OCR service on start and read at its interval.
+#[derive(Clone)]
+pub struct ChatMonitor {
+    ocr: OcrService,
+    config: Arc<StdMutex<ChatMonitorConfig>>,
+    log: Arc<StdMutex<ChatLog>>,
+    event_sender: broadcast::Sender<ChatEvent>,
+    task: ServiceTask,
+}
+
+impl ChatMonitor {
+    pub fn new(ocr: OcrService, config: ChatMonitorConfig) -> Self {
+        Self {
+            ocr,
+            config: Arc::new(StdMutex::new(config)),
+            log: Arc::new(StdMutex::new(ChatLog::default())),
+            event_sender: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            task: ServiceTask::new(),
+        }
+    }
+
+    /// New chat lines and keyword hits
+    pub fn subscribe(&self) -> broadcast::Receiver<ChatEvent> {
+        self.event_sender.subscribe()
+    }
+
+    /// OCR service the chat region is read by
+    pub fn ocr(&self) -> &OcrService {
+        &self.ocr
+    }
+
+    pub fn config(&self) -> ChatMonitorConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Replace the settings and save them, a new region is read from the next OCR pass
+    pub fn set_config(&self, config: ChatMonitorConfig) -> Result<(), String> {
+        let previous = std::mem::replace(&mut *self.config.lock().unwrap(), config.clone());
+        if let Some(region) = previous.region {
+            self.ocr.remove_region(&region.name);
+        }
+        if let Some(region) = config.region.clone() {
+            self.ocr.set_region(region);
+        }
+        config.save()
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.task.is_busy().await
+    }
+
+    /// Start watching chat, the OCR service has to be running for lines to arrive
+    pub async fn start_monitor(&self) -> Result<(), String> {
+        let Some(region) = self.config().region else {
+            return Err("No chat region configured".to_string());
+        };
+
+        self.ocr.set_region(region);
+
+        let mut receiver = self.ocr.subscribe();
+        let config = self.config.clone();
+        let log = self.log.clone();
+        let event_sender = self.event_sender.clone();
+
+        self.task.start(move |cancelled| async move {
+            let mut last_alert: HashMap<String, Instant> = HashMap::new();
+
+            loop {
+                // Stopping doesn't wait for the next reading
+                let reading = tokio::select! {
+                    _ = cancelled.cancelled() => break,
+                    reading = receiver.recv() => reading,
+                };
+                match reading {
+                    Ok(reading) => {
+                        let config = config.lock().unwrap().clone();
+                        // Only readings of the chat region are watched
+                        if !config.region.as_ref().is_some_and(|region| region.name == reading.region) {
+                            continue;
+                        }
+
+                        let lines = log.lock().unwrap().new_lines(&reading.text, config.history);
+                        for line in lines {
+                            let _ = event_sender.send(ChatEvent::Line { text: line.clone() });
+
+                            for keyword in config.keywords.iter().filter(|keyword| keyword.matches(&line)) {
+                                let now = Instant::now();
+                                if last_alert
+                                    .get(&keyword.name)
+                                    .is_some_and(|last| now.duration_since(*last) < config.cooldown)
+                                {
+                                    continue;
+                                }
+                                last_alert.insert(keyword.name.clone(), now);
+
+                                if keyword.pause {
+                                    tracing::warn!(keyword = %keyword.name, %line, "Chat keyword seen, stopping automation");
+                                    crate::trigger_kill_switch();
+                                }
+                                let _ = event_sender.send(ChatEvent::Keyword {
+                                    keyword: keyword.name.clone(),
+                                    line: line.clone(),
+                                    paused: keyword.pause,
+                                });
+                                EventBus::global().publish(BotEvent::ChatKeyword {
+                                    keyword: keyword.name.clone(),
+                                    line: line.clone(),
+                                    paused: keyword.pause,
+                                });
+                            }
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Stop watching chat, a reading in progress is handled before this returns
+    pub async fn stop_monitor(&self) {
+        self.task.stop().await;
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for ChatMonitor {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_monitor().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_monitor().await;
+        Ok(())
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        vec!["OcrService".to_string()]
+    }
+}
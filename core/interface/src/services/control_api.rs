@@ -0,0 +1,254 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post, put};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use subtle::ConstantTimeEq;
+
+use crate::services::{Service, ServiceError};
+use super::frame_analyzer::save_screenshot;
+use super::graphics_capture::GraphicsCaptureService;
+use super::http_server::HttpServer;
+use super::low_power::LowPowerMode;
+use super::minimap_v2::{MinimapService, PerformanceStats, ServiceState};
+use super::service_manager::{ManagerStatus, ServiceManager};
+
+/// Longest a screenshot waits for the next captured frame
+const SCREENSHOT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where the control API listens and the token clients authenticate with
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlApiConfig {
+    pub addr: SocketAddr,
+    /// Sent as `Authorization: Bearer <token>`, the API doesn't start without one
+    pub token: String,
+}
+
+impl ControlApiConfig {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self {
+            addr: SocketAddr::from(([127, 0, 0, 1], 7880)),
+            token: token.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ControlStatus {
+    pub capture: ServiceState,
+    pub window: Option<String>,
+    pub services: ManagerStatus,
+    pub low_power: bool,
+}
+
+#[derive(Deserialize)]
+struct LowPowerRequest {
+    enabled: bool,
+}
+
+#[derive(Deserialize)]
+struct WindowRequest {
+    /// Part of the window title, ignoring case
+    window: String,
+}
+
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+fn failed(error: String) -> ApiError {
+    ApiError(StatusCode::INTERNAL_SERVER_ERROR, error)
+}
+
+#[derive(Clone)]
+struct ApiState {
+    minimap: MinimapService,
+    graphics: Arc<GraphicsCaptureService>,
+    services: Arc<ServiceManager>,
+    token: Arc<str>,
+}
+
+/// Token protected REST API for driving the bot from scripts and home automation
+///
+/// - `GET /status` capture state, window and services
+/// - `GET /metrics` capture and minimap performance
+/// - `POST /start` capture `{"window": ...}` or the current window again, and start the services
+/// - `POST /stop` stop the services and capture
+/// - `PUT /window` switch capture to `{"window": ...}`
+/// - `POST /screenshot` save the next captured frame in `screenshots/`
+/// - `POST /automation/pause`, `POST /automation/resume` stop or start the services, capture keeps running
+/// - `PUT /low-power` switch low-power mode `{"enabled": ...}`, see [`LowPowerMode`]
+pub struct ControlApi {
+    state: ApiState,
+    config: ControlApiConfig,
+    server: HttpServer,
+}
+
+impl ControlApi {
+    pub fn new(
+        minimap: MinimapService,
+        graphics: Arc<GraphicsCaptureService>,
+        services: Arc<ServiceManager>,
+        config: ControlApiConfig,
+    ) -> Self {
+        Self {
+            state: ApiState {
+                minimap,
+                graphics,
+                services,
+                token: config.token.as_str().into(),
+            },
+            config,
+            server: HttpServer::new("Control API"),
+        }
+    }
+
+    pub fn config(&self) -> &ControlApiConfig {
+        &self.config
+    }
+
+    pub fn is_serving(&self) -> bool {
+        self.server.is_serving()
+    }
+
+    pub async fn start_api(&self) -> Result<(), String> {
+        if self.config.token.is_empty() {
+            return Err("The control API needs a token".to_string());
+        }
+        let router = Router::new()
+            .route("/status", get(status))
+            .route("/metrics", get(metrics))
+            .route("/start", post(start))
+            .route("/stop", post(stop))
+            .route("/window", put(set_window))
+            .route("/screenshot", post(screenshot))
+            .route("/automation/pause", post(pause))
+            .route("/automation/resume", post(resume))
+            .route("/low-power", put(set_low_power))
+            .layer(middleware::from_fn_with_state(self.state.clone(), authorize))
+            .with_state(self.state.clone());
+        self.server.start(self.config.addr, router).await
+    }
+
+    pub fn stop_api(&self) {
+        self.server.stop();
+    }
+}
+
+async fn authorize(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        // Constant time so the token can't be guessed byte by byte from response times
+        .is_some_and(|token| bool::from(token.as_bytes().ct_eq(state.token.as_bytes())));
+    if !authorized {
+        return ApiError(StatusCode::UNAUTHORIZED, "Missing or wrong token".to_string()).into_response();
+    }
+    next.run(request).await
+}
+
+async fn status(State(state): State<ApiState>) -> ApiResult<ControlStatus> {
+    Ok(Json(ControlStatus {
+        capture: state.minimap.state(),
+        window: state.minimap.get_current_window_title().await,
+        services: state.services.status().await,
+        low_power: LowPowerMode::global().is_enabled(),
+    }))
+}
+
+async fn metrics(State(state): State<ApiState>) -> ApiResult<PerformanceStats> {
+    Ok(Json(state.minimap.get_performance_metrics()))
+}
+
+/// Capture the window matching `pattern`, restarting capture if it already runs
+async fn capture_window(state: &ApiState, pattern: &str) -> Result<(), ApiError> {
+    let window = crate::find_window(pattern)
+        .ok_or_else(|| ApiError(StatusCode::NOT_FOUND, format!("No window matching {:?}", pattern)))?;
+    state.minimap.set_window(window).await.map_err(failed)
+}
+
+async fn start(State(state): State<ApiState>, request: Option<Json<WindowRequest>>) -> ApiResult<ControlStatus> {
+    match (request, state.minimap.get_current_window_title().await) {
+        (Some(Json(request)), _) => capture_window(&state, &request.window).await?,
+        (None, Some(window)) => {
+            if state.minimap.state() != ServiceState::Running {
+                capture_window(&state, &window).await?;
+            }
+        }
+        (None, None) => {
+            return Err(ApiError(StatusCode::BAD_REQUEST, "No window to capture".to_string()));
+        }
+    }
+    state.services.start_all().await.map_err(failed)?;
+    status(State(state)).await
+}
+
+async fn stop(State(state): State<ApiState>) -> ApiResult<ControlStatus> {
+    let stopped = state.services.stop_all().await;
+    state.minimap.stop_capture().await.map_err(failed)?;
+    stopped.map_err(failed)?;
+    status(State(state)).await
+}
+
+async fn set_window(State(state): State<ApiState>, Json(request): Json<WindowRequest>) -> ApiResult<ControlStatus> {
+    capture_window(&state, &request.window).await?;
+    status(State(state)).await
+}
+
+async fn screenshot(State(state): State<ApiState>) -> ApiResult<serde_json::Value> {
+    if !state.graphics.is_capturing().await {
+        return Err(ApiError(StatusCode::CONFLICT, "Capture is stopped".to_string()));
+    }
+    let mut frames = state.graphics.subscribe();
+    let frame = tokio::time::timeout(SCREENSHOT_TIMEOUT, frames.recv())
+        .await
+        .map_err(|_| failed("No frame captured in time".to_string()))?
+        .map_err(|e| failed(format!("Failed to receive frame: {}", e)))?;
+    let path = tokio::task::spawn_blocking(move || save_screenshot(&frame, "api"))
+        .await
+        .map_err(|e| failed(format!("Screenshot task failed: {}", e)))?
+        .map_err(failed)?;
+    Ok(Json(json!({ "path": path })))
+}
+
+async fn pause(State(state): State<ApiState>) -> ApiResult<ControlStatus> {
+    state.services.stop_all().await.map_err(failed)?;
+    status(State(state)).await
+}
+
+async fn resume(State(state): State<ApiState>) -> ApiResult<ControlStatus> {
+    state.services.start_all().await.map_err(failed)?;
+    status(State(state)).await
+}
+
+async fn set_low_power(State(state): State<ApiState>, Json(request): Json<LowPowerRequest>) -> ApiResult<ControlStatus> {
+    LowPowerMode::global().set_enabled(request.enabled);
+    status(State(state)).await
+}
+
+#[async_trait::async_trait]
+impl Service for ControlApi {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_api().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_api();
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+//! Per-frame CPU work (OpenCV, OCR, inference, encoding) run off the async runtime
+
+use std::sync::OnceLock;
+
+use tokio::sync::Semaphore;
+use tokio::task::JoinError;
+
+use super::thread_tuning::tune_processing_thread;
+
+/// One slot per core, more jobs at once only make each of them slower
+fn slots() -> &'static Semaphore {
+    static SLOTS: OnceLock<Semaphore> = OnceLock::new();
+    SLOTS.get_or_init(|| Semaphore::new(std::thread::available_parallelism().map_or(4, |cores| cores.get())))
+}
+
+/// Run `work` on the blocking pool as soon as a CPU slot is free
+///
+/// A drop-in for `spawn_blocking(work).await` limiting how many frame jobs run at once, so
+/// services processing every frame can't starve capture, the UI or each other. A service
+/// waiting for a slot falls behind, which its frame subscription turns into skipped frames.
+pub async fn spawn_cpu<T, F>(work: F) -> Result<T, JoinError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let _slot = slots().acquire().await.expect("CPU slots are never closed");
+    tokio::task::spawn_blocking(move || {
+        tune_processing_thread();
+        work()
+    })
+    .await
+}
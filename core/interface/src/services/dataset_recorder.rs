@@ -0,0 +1,345 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use opencv::{
+    core::{Mat, Vector},
+    imgcodecs::{imencode, IMWRITE_JPEG_QUALITY},
+    imgproc::{cvt_color_def, COLOR_BGRA2BGR},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::services::{Service, ServiceError, ServiceTask};
+#[cfg(feature = "onnx")]
+use super::detection::DetectionService;
+use super::graphics_capture::{CapturedFrame, GraphicsCaptureService};
+use super::low_power::LowPowerMode;
+use super::template_matcher::TemplateMatcher;
+use super::vision::{bgra_mat, Rect};
+
+/// File listing the class names, the line number is the YOLO class id
+const CLASSES_FILE: &str = "classes.txt";
+
+/// An object in a recorded frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatasetLabel {
+    pub class: String,
+    /// Bounding box in frame pixels
+    pub rect: Rect,
+    pub confidence: Option<f32>,
+}
+
+/// Anything that knows what is currently on screen
+pub trait LabelSource: Send + Sync {
+    /// Labels for the most recent frame the source looked at
+    fn labels(&self) -> Vec<DatasetLabel>;
+}
+
+impl LabelSource for TemplateMatcher {
+    fn labels(&self) -> Vec<DatasetLabel> {
+        self.matches()
+            .into_iter()
+            .map(|matched| DatasetLabel {
+                class: matched.name,
+                rect: matched.rect,
+                confidence: Some(matched.score as f32),
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "onnx")]
+impl LabelSource for DetectionService {
+    fn labels(&self) -> Vec<DatasetLabel> {
+        self.latest()
+            .into_iter()
+            .map(|detection| DatasetLabel {
+                class: detection.label,
+                rect: detection.rect,
+                confidence: Some(detection.confidence),
+            })
+            .collect()
+    }
+}
+
+/// Image format of recorded frames
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DatasetImageFormat {
+    #[default]
+    Png,
+    Jpeg,
+}
+
+/// Where and how often frames are recorded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DatasetConfig {
+    /// Gets `images/`, `labels/` and `classes.txt`, the layout YOLO trainers expect
+    pub dir: PathBuf,
+    /// Minimum time between two recorded frames, in milliseconds when serialized
+    #[serde(with = "super::duration_millis")]
+    pub interval: Duration,
+    pub format: DatasetImageFormat,
+    /// JPEG quality (0 - 100)
+    pub jpeg_quality: i32,
+    /// Don't record frames without any labels
+    pub skip_unlabeled: bool,
+}
+
+impl Default for DatasetConfig {
+    fn default() -> Self {
+        Self {
+            dir: crate::config_dir().join("dataset"),
+            interval: Duration::from_secs(1),
+            format: DatasetImageFormat::default(),
+            jpeg_quality: 95,
+            skip_unlabeled: false,
+        }
+    }
+}
+
+/// JSON label file written next to the YOLO one, keeps names, pixel rects and confidences
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetSample {
+    pub image: String,
+    pub width: u32,
+    pub height: u32,
+    pub captured_at: String,
+    pub labels: Vec<DatasetLabel>,
+}
+
+/// Class names of a dataset, ids are assigned in order of first appearance
+#[derive(Debug, Default)]
+struct DatasetClasses {
+    names: Vec<String>,
+}
+
+impl DatasetClasses {
+    fn load(dir: &Path) -> Self {
+        let names = std::fs::read_to_string(dir.join(CLASSES_FILE))
+            .map(|text| {
+                text.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { names }
+    }
+
+    /// Id of `name`, adding it and rewriting the class file if it is new
+    fn id(&mut self, name: &str, dir: &Path) -> Result<usize, String> {
+        if let Some(id) = self.names.iter().position(|known| known == name) {
+            return Ok(id);
+        }
+        self.names.push(name.to_string());
+        let path = dir.join(CLASSES_FILE);
+        std::fs::write(&path, self.names.join("\n") + "\n")
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(self.names.len() - 1)
+    }
+}
+
+/// Saves sampled frames with the labels of the attached sources as a training dataset
+///
+/// Labels are whatever the sources reported last, so they can lag the saved frame by one
+/// detection interval.
+#[derive(Clone)]
+pub struct DatasetRecorder {
+    graphics_service: Arc<GraphicsCaptureService>,
+    config: Arc<StdMutex<DatasetConfig>>,
+    sources: Arc<StdMutex<Vec<Arc<dyn LabelSource>>>>,
+    classes: Arc<StdMutex<DatasetClasses>>,
+    saved: Arc<AtomicUsize>,
+    task: ServiceTask,
+}
+
+impl DatasetRecorder {
+    pub fn new(graphics_service: Arc<GraphicsCaptureService>, config: DatasetConfig) -> Self {
+        Self {
+            graphics_service,
+            config: Arc::new(StdMutex::new(config)),
+            sources: Arc::new(StdMutex::new(Vec::new())),
+            classes: Arc::new(StdMutex::new(DatasetClasses::default())),
+            saved: Arc::new(AtomicUsize::new(0)),
+            task: ServiceTask::new(),
+        }
+    }
+
+    /// Include the labels of `source` in every recorded frame
+    pub fn add_source(&self, source: Arc<dyn LabelSource>) {
+        self.sources.lock().unwrap().push(source);
+    }
+
+    pub fn clear_sources(&self) {
+        self.sources.lock().unwrap().clear();
+    }
+
+    pub fn config(&self) -> DatasetConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Takes effect on the next start
+    pub fn set_config(&self, config: DatasetConfig) {
+        *self.config.lock().unwrap() = config;
+    }
+
+    /// Frames saved since the recorder was created
+    pub fn saved_count(&self) -> usize {
+        self.saved.load(Ordering::Relaxed)
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.task.is_busy().await
+    }
+
+    pub async fn start_recording(&self) -> Result<(), String> {
+        let config = self.config();
+        for dir in [config.dir.join("images"), config.dir.join("labels")] {
+            std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+
+        let mut receiver = self.graphics_service.subscribe();
+        let sources = self.sources.clone();
+        let classes = self.classes.clone();
+        let saved = self.saved.clone();
+
+        self.task
+            .start(move |cancelled| {
+                // A running recorder keeps the classes it numbered its labels with
+                *classes.lock().unwrap() = DatasetClasses::load(&config.dir);
+
+                async move {
+                    let mut last_saved: Option<Instant> = None;
+
+                    loop {
+                        // Stopping doesn't wait for the next frame
+                        let frame = tokio::select! {
+                            _ = cancelled.cancelled() => break,
+                            frame = receiver.recv() => frame,
+                        };
+                        match frame {
+                            Ok(frame) => {
+                                if LowPowerMode::global().is_enabled() {
+                                    continue;
+                                }
+                                if last_saved.is_some_and(|last| frame.timestamp.duration_since(last) < config.interval) {
+                                    continue;
+                                }
+
+                                let labels: Vec<DatasetLabel> =
+                                    sources.lock().unwrap().iter().flat_map(|source| source.labels()).collect();
+                                if labels.is_empty() && config.skip_unlabeled {
+                                    continue;
+                                }
+                                last_saved = Some(frame.timestamp);
+
+                                let config = config.clone();
+                                let classes = classes.clone();
+                                let result = tokio::task::spawn_blocking(move || {
+                                    Self::save_sample(&config, &mut classes.lock().unwrap(), &frame, labels)
+                                })
+                                .await;
+                                match result {
+                                    Ok(Ok(())) => {
+                                        saved.fetch_add(1, Ordering::Relaxed);
+                                    }
+                                    Ok(Err(e)) => tracing::warn!(error = %e, "Failed to record dataset frame"),
+                                    Err(e) => tracing::error!(error = %e, "Dataset recording task failed"),
+                                }
+                            }
+                            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        }
+                    }
+                }
+            })
+            .await;
+
+        Ok(())
+    }
+
+    /// Stop recording, the sample being written is finished before this returns
+    pub async fn stop_recording(&self) {
+        self.task.stop().await;
+    }
+
+    /// Write the image, its YOLO label file and its JSON label file
+    fn save_sample(
+        config: &DatasetConfig,
+        classes: &mut DatasetClasses,
+        frame: &CapturedFrame,
+        labels: Vec<DatasetLabel>,
+    ) -> Result<(), String> {
+        let (width, height) = (frame.width as i32, frame.height as i32);
+        let labels: Vec<DatasetLabel> = labels
+            .into_iter()
+            .filter_map(|label| Some(DatasetLabel { rect: label.rect.clamp_to(width, height)?, ..label }))
+            .collect();
+
+        let captured_at = Local::now();
+        let stem = captured_at.format("%Y%m%d_%H%M%S_%3f").to_string();
+        let (extension, params) = match config.format {
+            DatasetImageFormat::Png => ("png", Vec::new()),
+            DatasetImageFormat::Jpeg => ("jpg", vec![IMWRITE_JPEG_QUALITY, config.jpeg_quality.clamp(0, 100)]),
+        };
+        let image = format!("{}.{}", stem, extension);
+
+        let mut bgr = Mat::default();
+        cvt_color_def(&bgra_mat(frame)?, &mut bgr, COLOR_BGRA2BGR)
+            .map_err(|e| format!("Failed to convert frame: {}", e))?;
+        let mut buffer = Vector::<u8>::new();
+        imencode(&format!(".{}", extension), &bgr, &mut buffer, &Vector::<i32>::from_slice(&params))
+            .map_err(|e| format!("Failed to encode frame: {}", e))?;
+        let image_path = config.dir.join("images").join(&image);
+        std::fs::write(&image_path, buffer.as_slice())
+            .map_err(|e| format!("Failed to write {}: {}", image_path.display(), e))?;
+
+        // YOLO: class id and box center and size, normalized to the image size
+        let mut yolo = String::new();
+        for label in &labels {
+            let id = classes.id(&label.class, &config.dir)?;
+            let rect = label.rect;
+            yolo.push_str(&format!(
+                "{} {:.6} {:.6} {:.6} {:.6}\n",
+                id,
+                (rect.x as f64 + rect.width as f64 / 2.0) / width as f64,
+                (rect.y as f64 + rect.height as f64 / 2.0) / height as f64,
+                rect.width as f64 / width as f64,
+                rect.height as f64 / height as f64,
+            ));
+        }
+        let labels_dir = config.dir.join("labels");
+        let yolo_path = labels_dir.join(format!("{}.txt", stem));
+        std::fs::write(&yolo_path, yolo).map_err(|e| format!("Failed to write {}: {}", yolo_path.display(), e))?;
+
+        let sample = DatasetSample {
+            image,
+            width: frame.width,
+            height: frame.height,
+            captured_at: captured_at.to_rfc3339(),
+            labels,
+        };
+        let json = serde_json::to_string_pretty(&sample)
+            .map_err(|e| format!("Failed to serialize labels: {}", e))?;
+        let json_path = labels_dir.join(format!("{}.json", stem));
+        std::fs::write(&json_path, json).map_err(|e| format!("Failed to write {}: {}", json_path.display(), e))
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for DatasetRecorder {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_recording().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_recording().await;
+        Ok(())
+    }
+}
@@ -0,0 +1,431 @@
+//! Object detection with user-supplied ONNX models, e.g. YOLO or SSD exports
+//!
+//! Models run on OpenCV's dnn module instead of ONNX Runtime. It reads the same ONNX files and
+//! OpenCV is already linked for the other vision services, while the `ort` crate would mean
+//! shipping the ONNX Runtime library next to the bot. Operators dnn doesn't support fail when
+//! the model is loaded, see [`ObjectDetector::load`].
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use opencv::{
+    core::{copy_make_border, Mat, Rect as CvRect, Scalar, Size, Vector, BORDER_CONSTANT, CV_32F},
+    dnn::{blob_from_image, nms_boxes_def, read_net_from_onnx, Net},
+    imgproc::{cvt_color_def, resize, COLOR_BGRA2BGR, INTER_LINEAR},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::services::{Service, ServiceError, ServiceTask};
+use super::cpu_pool::spawn_cpu;
+use super::graphics_capture::GraphicsCaptureService;
+use super::low_power::LowPowerMode;
+use super::vision::{bgra_mat, Rect};
+
+/// Capacity of the detection channel, slow subscribers skip old detections
+const DETECTION_CHANNEL_CAPACITY: usize = 16;
+
+/// Gray used by YOLO exports to pad letterboxed images
+const LETTERBOX_COLOR: f64 = 114.0;
+
+/// Layout of the model's output tensor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionModelKind {
+    /// `[1, boxes, 5 + classes]` rows of center x, center y, width, height, objectness, class scores
+    YoloV5,
+    /// `[1, 4 + classes, boxes]` columns of center x, center y, width, height, class scores
+    #[default]
+    YoloV8,
+    /// `[1, 1, boxes, 7]` rows of image id, class, score and normalized corners
+    Ssd,
+}
+
+/// Which model is run and how its output is filtered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectionConfig {
+    /// Path to the `.onnx` model
+    pub model_path: PathBuf,
+    pub model_kind: DetectionModelKind,
+    /// Class names indexed by class id, ids without a name are reported as their number
+    pub labels: Vec<String>,
+    /// Square input size the model was exported with
+    pub input_size: i32,
+    /// Detections scoring lower than this are dropped
+    pub confidence_threshold: f32,
+    /// Overlap (IoU) above which the weaker of two same-class boxes is suppressed
+    pub nms_threshold: f32,
+    /// Minimum time between two inferences, in milliseconds when serialized
+    #[serde(with = "super::duration_millis")]
+    pub interval: Duration,
+}
+
+impl Default for DetectionConfig {
+    fn default() -> Self {
+        Self {
+            model_path: PathBuf::new(),
+            model_kind: DetectionModelKind::default(),
+            labels: Vec::new(),
+            input_size: 640,
+            confidence_threshold: 0.25,
+            nms_threshold: 0.45,
+            interval: Duration::from_millis(100),
+        }
+    }
+}
+
+impl DetectionConfig {
+    /// Read class names from a file with one label per line, e.g. a YOLO `classes.txt`
+    pub fn load_labels(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let labels = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read labels {}: {}", path.display(), e))?;
+        self.labels = labels.lines().map(|line| line.trim().to_string()).collect();
+        Ok(())
+    }
+}
+
+/// An object found in a frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Detection {
+    pub class_id: usize,
+    pub label: String,
+    pub confidence: f32,
+    /// Bounding box in frame pixels
+    pub rect: Rect,
+}
+
+/// Scale and padding that map model input coordinates back to the frame
+#[derive(Debug, Clone, Copy)]
+struct Letterbox {
+    scale: f32,
+    pad_x: f32,
+    pad_y: f32,
+}
+
+impl Letterbox {
+    fn to_frame(&self, x1: f32, y1: f32, x2: f32, y2: f32) -> Rect {
+        let x1 = (x1 - self.pad_x) / self.scale;
+        let y1 = (y1 - self.pad_y) / self.scale;
+        let x2 = (x2 - self.pad_x) / self.scale;
+        let y2 = (y2 - self.pad_y) / self.scale;
+        Rect::new(x1.round() as i32, y1.round() as i32, (x2 - x1).round() as i32, (y2 - y1).round() as i32)
+    }
+}
+
+/// Runs an ONNX detection model through OpenCV's dnn module
+pub struct ObjectDetector {
+    net: Net,
+    config: DetectionConfig,
+}
+
+impl ObjectDetector {
+    pub fn load(config: DetectionConfig) -> Result<Self, String> {
+        let path = config.model_path.to_string_lossy();
+        let net = read_net_from_onnx(&path).map_err(|e| format!("Failed to load model {}: {}", path, e))?;
+        if net.empty().map_err(|e| format!("Failed to load model {}: {}", path, e))? {
+            return Err(format!("Model {} has no layers", path));
+        }
+        Ok(Self { net, config })
+    }
+
+    pub fn config(&self) -> &DetectionConfig {
+        &self.config
+    }
+
+    /// Detect objects in a BGRA frame
+    pub fn detect(&mut self, bgra: &Mat) -> Result<Vec<Detection>, String> {
+        let (input, letterbox) = self.letterbox(bgra)?;
+        let size = self.config.input_size;
+        let blob = blob_from_image(&input, 1.0 / 255.0, Size::new(size, size), Scalar::default(), true, false, CV_32F)
+            .map_err(|e| format!("Failed to create input blob: {}", e))?;
+
+        self.net
+            .set_input(&blob, "", 1.0, Scalar::default())
+            .map_err(|e| format!("Failed to set model input: {}", e))?;
+        let mut outputs = Vector::<Mat>::new();
+        let names = self
+            .net
+            .get_unconnected_out_layers_names()
+            .map_err(|e| format!("Failed to get model outputs: {}", e))?;
+        self.net
+            .forward(&mut outputs, &names)
+            .map_err(|e| format!("Inference failed: {}", e))?;
+        let output = outputs.get(0).map_err(|_| "Model produced no output".to_string())?;
+
+        let candidates = match self.config.model_kind {
+            DetectionModelKind::YoloV5 => self.parse_yolo(&output, letterbox, true)?,
+            DetectionModelKind::YoloV8 => self.parse_yolo(&output, letterbox, false)?,
+            DetectionModelKind::Ssd => self.parse_ssd(&output, letterbox)?,
+        };
+        self.suppress(candidates)
+    }
+
+    /// Scale the frame to fit the model input keeping its aspect ratio and pad the rest
+    fn letterbox(&self, bgra: &Mat) -> Result<(Mat, Letterbox), String> {
+        let size = self.config.input_size;
+        let frame_size = bgra.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
+        if frame_size.width <= 0 || frame_size.height <= 0 {
+            return Err("Empty frame".to_string());
+        }
+
+        let scale = (size as f32 / frame_size.width as f32).min(size as f32 / frame_size.height as f32);
+        let width = ((frame_size.width as f32 * scale).round() as i32).clamp(1, size);
+        let height = ((frame_size.height as f32 * scale).round() as i32).clamp(1, size);
+
+        let mut bgr = Mat::default();
+        cvt_color_def(bgra, &mut bgr, COLOR_BGRA2BGR).map_err(|e| format!("Failed to convert to BGR: {}", e))?;
+        let mut resized = Mat::default();
+        resize(&bgr, &mut resized, Size::new(width, height), 0.0, 0.0, INTER_LINEAR)
+            .map_err(|e| format!("Failed to resize frame: {}", e))?;
+
+        let pad_x = (size - width) / 2;
+        let pad_y = (size - height) / 2;
+        let mut padded = Mat::default();
+        copy_make_border(
+            &resized,
+            &mut padded,
+            pad_y,
+            size - height - pad_y,
+            pad_x,
+            size - width - pad_x,
+            BORDER_CONSTANT,
+            Scalar::all(LETTERBOX_COLOR),
+        )
+        .map_err(|e| format!("Failed to pad frame: {}", e))?;
+
+        Ok((
+            padded,
+            Letterbox {
+                scale,
+                pad_x: pad_x as f32,
+                pad_y: pad_y as f32,
+            },
+        ))
+    }
+
+    fn parse_yolo(&self, output: &Mat, letterbox: Letterbox, has_objectness: bool) -> Result<Vec<Detection>, String> {
+        let dims = output.mat_size();
+        if dims.len() != 3 {
+            return Err(format!("Unexpected YOLO output with {} dimensions", dims.len()));
+        }
+        let data = output
+            .data_typed::<f32>()
+            .map_err(|e| format!("Failed to read model output: {}", e))?;
+
+        // YOLOv5 stores one box per row, YOLOv8 one box per column
+        let (boxes, features) = if has_objectness { (dims[1], dims[2]) } else { (dims[2], dims[1]) };
+        let (boxes, features) = (boxes as usize, features as usize);
+        let value = |b: usize, f: usize| if has_objectness { data[b * features + f] } else { data[f * boxes + b] };
+        let first_class = if has_objectness { 5 } else { 4 };
+        if features <= first_class {
+            return Err(format!("YOLO output has only {} values per box", features));
+        }
+
+        let mut detections = Vec::new();
+        for b in 0..boxes {
+            let objectness = if has_objectness { value(b, 4) } else { 1.0 };
+            let (class_id, class_score) = (first_class..features)
+                .map(|f| (f - first_class, value(b, f)))
+                .fold((0, f32::MIN), |best, class| if class.1 > best.1 { class } else { best });
+            let confidence = objectness * class_score;
+            if confidence < self.config.confidence_threshold {
+                continue;
+            }
+
+            let (cx, cy, w, h) = (value(b, 0), value(b, 1), value(b, 2), value(b, 3));
+            let rect = letterbox.to_frame(cx - w / 2.0, cy - h / 2.0, cx + w / 2.0, cy + h / 2.0);
+            detections.push(self.detection(class_id, confidence, rect));
+        }
+        Ok(detections)
+    }
+
+    fn parse_ssd(&self, output: &Mat, letterbox: Letterbox) -> Result<Vec<Detection>, String> {
+        let data = output
+            .data_typed::<f32>()
+            .map_err(|e| format!("Failed to read model output: {}", e))?;
+        let size = self.config.input_size as f32;
+
+        let mut detections = Vec::new();
+        for row in data.chunks_exact(7) {
+            let confidence = row[2];
+            if confidence < self.config.confidence_threshold || row[1] < 0.0 {
+                continue;
+            }
+            let rect = letterbox.to_frame(row[3] * size, row[4] * size, row[5] * size, row[6] * size);
+            detections.push(self.detection(row[1] as usize, confidence, rect));
+        }
+        Ok(detections)
+    }
+
+    fn detection(&self, class_id: usize, confidence: f32, rect: Rect) -> Detection {
+        let label = self
+            .config
+            .labels
+            .get(class_id)
+            .cloned()
+            .unwrap_or_else(|| class_id.to_string());
+        Detection {
+            class_id,
+            label,
+            confidence,
+            rect,
+        }
+    }
+
+    /// Class-aware non-maximum suppression
+    fn suppress(&self, candidates: Vec<Detection>) -> Result<Vec<Detection>, String> {
+        if candidates.is_empty() {
+            return Ok(candidates);
+        }
+
+        // Shifting every class far apart keeps boxes of different classes from suppressing each other
+        let offset = candidates
+            .iter()
+            .map(|d| (d.rect.x + d.rect.width).max(d.rect.y + d.rect.height))
+            .max()
+            .unwrap_or(0)
+            .max(1)
+            + 1;
+        let boxes: Vector<CvRect> = candidates
+            .iter()
+            .map(|d| {
+                let shift = d.class_id as i32 * offset;
+                CvRect::new(d.rect.x + shift, d.rect.y + shift, d.rect.width, d.rect.height)
+            })
+            .collect();
+        let scores: Vector<f32> = candidates.iter().map(|d| d.confidence).collect();
+
+        let mut indices = Vector::<i32>::new();
+        nms_boxes_def(
+            &boxes,
+            &scores,
+            self.config.confidence_threshold,
+            self.config.nms_threshold,
+            &mut indices,
+        )
+        .map_err(|e| format!("Non-maximum suppression failed: {}", e))?;
+
+        Ok(indices.iter().map(|i| candidates[i as usize].clone()).collect())
+    }
+}
+
+/// Runs an object detection model on captured frames and publishes what it finds
+#[derive(Clone)]
+pub struct DetectionService {
+    graphics_service: Arc<GraphicsCaptureService>,
+    detector: Arc<StdMutex<ObjectDetector>>,
+    latest: Arc<StdMutex<Vec<Detection>>>,
+    detection_sender: broadcast::Sender<Vec<Detection>>,
+    task: ServiceTask,
+}
+
+impl DetectionService {
+    /// Load the model in `config`, fails if it can't be read
+    pub fn new(graphics_service: Arc<GraphicsCaptureService>, config: DetectionConfig) -> Result<Self, String> {
+        Ok(Self {
+            graphics_service,
+            detector: Arc::new(StdMutex::new(ObjectDetector::load(config)?)),
+            latest: Arc::new(StdMutex::new(Vec::new())),
+            detection_sender: broadcast::channel(DETECTION_CHANNEL_CAPACITY).0,
+            task: ServiceTask::new(),
+        })
+    }
+
+    /// Detections of every processed frame, including empty ones
+    pub fn subscribe(&self) -> broadcast::Receiver<Vec<Detection>> {
+        self.detection_sender.subscribe()
+    }
+
+    pub fn latest(&self) -> Vec<Detection> {
+        self.latest.lock().unwrap().clone()
+    }
+
+    pub fn config(&self) -> DetectionConfig {
+        self.detector.lock().unwrap().config().clone()
+    }
+
+    /// Load a different model or change thresholds
+    pub fn set_config(&self, config: DetectionConfig) -> Result<(), String> {
+        let detector = ObjectDetector::load(config)?;
+        *self.detector.lock().unwrap() = detector;
+        Ok(())
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.task.is_busy().await
+    }
+
+    pub async fn start_detection(&self) -> Result<(), String> {
+        let mut receiver = self.graphics_service.subscribe();
+        let detector = self.detector.clone();
+        let latest = self.latest.clone();
+        let detection_sender = self.detection_sender.clone();
+
+        self.task.start(move |cancelled| async move {
+            let mut last_run: Option<Instant> = None;
+
+            loop {
+                // Stopping doesn't wait for the next frame
+                let frame = tokio::select! {
+                    _ = cancelled.cancelled() => break,
+                    frame = receiver.recv() => frame,
+                };
+                match frame {
+                    Ok(frame) => {
+                        if LowPowerMode::global().is_enabled() {
+                            continue;
+                        }
+                        let interval = detector.lock().unwrap().config().interval;
+                        if last_run.is_some_and(|last| frame.timestamp.duration_since(last) < interval) {
+                            continue;
+                        }
+                        last_run = Some(frame.timestamp);
+
+                        let detector = detector.clone();
+                        let result = spawn_cpu(move || {
+                            let mat = bgra_mat(&frame)?;
+                            detector.lock().unwrap().detect(&mat)
+                        })
+                        .await;
+
+                        match result {
+                            Ok(Ok(detections)) => {
+                                *latest.lock().unwrap() = detections.clone();
+                                let _ = detection_sender.send(detections);
+                            }
+                            Ok(Err(e)) => tracing::warn!(error = %e, "Object detection failed"),
+                            Err(e) => tracing::error!(error = %e, "Object detection task failed"),
+                        }
+                    }
+                    // Inference is slower than capture, skipping frames is expected
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Stop detecting, the frame in progress is finished before this returns
+    pub async fn stop_detection(&self) {
+        self.task.stop().await;
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for DetectionService {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_detection().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_detection().await;
+        Ok(())
+    }
+}
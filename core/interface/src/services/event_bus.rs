@@ -0,0 +1,92 @@
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::config::HotkeyAction;
+use super::graphics_capture::CaptureSource;
+use super::vision::Rect;
+
+/// Capacity of the event channel, slow subscribers skip old events
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Something that happened in one service that others, the UI or scripts may react to
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotEvent {
+    CaptureStarted { source: CaptureSource },
+    CaptureStopped,
+    /// Subscribers fall behind and the capture rate was lowered
+    FrameLagging { effective_fps: u32, target_fps: u32 },
+    /// Queued frames hold more memory than `performance.frame_memory_mb`, frames are dropped
+    MemoryBudgetExceeded { used_bytes: u64, limit_bytes: u64 },
+    /// Low-power mode was switched on or off
+    LowPowerChanged { enabled: bool },
+    /// A global hotkey was pressed, see [`super::hotkeys`]
+    HotkeyPressed { action: HotkeyAction },
+    /// The captured window closed or can't be captured anymore
+    WindowLost { reason: String },
+    /// The minimap was found or moved, `rect` is in frame (or ROI) pixels
+    MinimapDetected { rect: Rect },
+    MinimapLost,
+    /// An input action was sent to the game, `action` is its debug form
+    InputSent { action: String },
+    /// A chat keyword showed up, `paused` if it fired the kill switch
+    ChatKeyword { keyword: String, line: String, paused: bool },
+    /// `config.toml` changed
+    ConfigChanged,
+    /// An automation rule fired its action
+    RuleFired { rule: String },
+    /// A scheduled job fired its action
+    JobFired { job: String },
+    /// The game state classifier switched to `state`, e.g. `dead`
+    GameStateChanged { state: String },
+    /// A template appeared or moved, e.g. a rare item
+    TemplateMatched { template: String, score: f64 },
+    ServiceFailed { service: String, error: String },
+    /// A supervised task failed and is started again after `delay_ms`
+    TaskRestarting { task: String, error: String, attempt: u32, delay_ms: u64 },
+    /// A script printed `text`
+    ScriptOutput { script: String, text: String },
+    ScriptError { script: String, error: String },
+    /// A script ended, was stopped or failed
+    ScriptFinished { script: String },
+}
+
+/// Broadcast of [`BotEvent`]s between services
+///
+/// Services publish to [`EventBus::global`], cloning a bus shares the channel.
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<BotEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self {
+            sender: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+        }
+    }
+
+    /// Bus every service publishes to
+    pub fn global() -> &'static EventBus {
+        static GLOBAL: OnceLock<EventBus> = OnceLock::new();
+        GLOBAL.get_or_init(EventBus::new)
+    }
+
+    /// Events published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<BotEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Send `event` to every subscriber, dropped if there are none
+    pub fn publish(&self, event: BotEvent) {
+        let _ = self.sender.send(event);
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,153 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use chrono::Local;
+use tokio::sync::broadcast;
+
+use crate::services::{Service, ServiceError, ServiceTask};
+use super::graphics_capture::{CapturedFrame, GraphicsCaptureService};
+use super::image_ops;
+use super::vision::Rect;
+
+/// RGB color of the pixel at `x`, `y` of a frame, `None` outside of it
+pub fn pixel(frame: &CapturedFrame, x: i32, y: i32) -> Option<[u8; 3]> {
+    if x < 0 || y < 0 || x >= frame.width as i32 || y >= frame.height as i32 {
+        return None;
+    }
+    let i = (y as usize * frame.width as usize + x as usize) * 4;
+    let bgra = frame.data.get(i..i + 3)?;
+    Some([bgra[2], bgra[1], bgra[0]])
+}
+
+/// BGRA bytes of the pixel at `x`, `y` of a frame as captured, `None` outside of it
+pub fn pixel_bgra(frame: &CapturedFrame, x: i32, y: i32) -> Option<[u8; 4]> {
+    if x < 0 || y < 0 || x >= frame.width as i32 || y >= frame.height as i32 {
+        return None;
+    }
+    let i = (y as usize * frame.width as usize + x as usize) * 4;
+    frame.data.get(i..i + 4)?.try_into().ok()
+}
+
+/// Average RGB color of the part of `rect` inside a frame, `None` if they don't overlap
+pub fn average_color(frame: &CapturedFrame, rect: Rect) -> Option<[u8; 3]> {
+    let rect = rect.clamp_to(frame.width as i32, frame.height as i32)?;
+    let stride = frame.width as usize * 4;
+    let mut sum = [0u64; 3];
+    for y in rect.y..rect.y + rect.height {
+        let start = y as usize * stride + rect.x as usize * 4;
+        let row = frame.data.get(start..start + rect.width as usize * 4)?;
+        for bgra in row.chunks_exact(4) {
+            sum[0] += bgra[2] as u64;
+            sum[1] += bgra[1] as u64;
+            sum[2] += bgra[0] as u64;
+        }
+    }
+    let count = rect.area() as u64;
+    Some(sum.map(|channel| (channel / count) as u8))
+}
+
+/// Whether every channel of `rgb` is within `tolerance` of `expected`
+pub fn color_matches(rgb: [u8; 3], expected: [u8; 3], tolerance: u8) -> bool {
+    rgb.iter().zip(expected).all(|(channel, expected)| channel.abs_diff(expected) <= tolerance)
+}
+
+/// Save `frame` as PNG in `screenshots/`, named after the time and `name`
+pub fn save_screenshot(frame: &CapturedFrame, name: &str) -> Result<PathBuf, String> {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    let dir = crate::config_dir().join("screenshots");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(format!("{}-{}.png", Local::now().format("%Y%m%d-%H%M%S"), name));
+    let png = image_ops::encode_png(&image_ops::bgra_to_rgb(frame)?)?;
+    std::fs::write(&path, png).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Keeps the latest captured frame around for quick color checks, e.g. from scripts or the UI
+#[derive(Clone)]
+pub struct FrameAnalyzer {
+    graphics_service: Arc<GraphicsCaptureService>,
+    latest: Arc<StdMutex<Option<Arc<CapturedFrame>>>>,
+    task: ServiceTask,
+}
+
+impl FrameAnalyzer {
+    pub fn new(graphics_service: Arc<GraphicsCaptureService>) -> Self {
+        Self {
+            graphics_service,
+            latest: Arc::new(StdMutex::new(None)),
+            task: ServiceTask::new(),
+        }
+    }
+
+    /// Size of the latest frame
+    pub fn frame_size(&self) -> Option<(u32, u32)> {
+        self.latest.lock().unwrap().as_ref().map(|frame| (frame.width, frame.height))
+    }
+
+    /// RGB color of a pixel of the latest frame, `None` without a frame or outside of it
+    pub fn pixel(&self, x: i32, y: i32) -> Option<[u8; 3]> {
+        pixel(self.latest.lock().unwrap().as_deref()?, x, y)
+    }
+
+    /// Average RGB color of a region of the latest frame
+    pub fn average_color(&self, rect: Rect) -> Option<[u8; 3]> {
+        average_color(self.latest.lock().unwrap().as_deref()?, rect)
+    }
+
+    /// Whether a pixel of the latest frame is within `tolerance` of `expected` on every channel
+    pub fn pixel_matches(&self, x: i32, y: i32, expected: [u8; 3], tolerance: u8) -> bool {
+        self.pixel(x, y).is_some_and(|rgb| color_matches(rgb, expected, tolerance))
+    }
+
+    /// Run `f` on the latest frame without copying it
+    pub fn with_latest<T>(&self, f: impl FnOnce(&CapturedFrame) -> T) -> Option<T> {
+        self.latest.lock().unwrap().as_deref().map(f)
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.task.is_busy().await
+    }
+
+    pub async fn start_analyzer(&self) -> Result<(), String> {
+        let mut receiver = self.graphics_service.subscribe();
+        let latest = self.latest.clone();
+
+        self.task.start(move |cancelled| async move {
+            loop {
+                // Stopping doesn't wait for the next frame
+                let frame = tokio::select! {
+                    _ = cancelled.cancelled() => break,
+                    frame = receiver.recv() => frame,
+                };
+                match frame {
+                    Ok(frame) => *latest.lock().unwrap() = Some(frame),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Stop following the capture, the last frame stays available
+    pub async fn stop_analyzer(&self) {
+        self.task.stop().await;
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for FrameAnalyzer {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_analyzer().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_analyzer().await;
+        Ok(())
+    }
+}
@@ -0,0 +1,117 @@
+use super::graphics_capture::{CapturedFrame, PixelFormat};
+
+/// Number of samples taken along each axis when building a frame signature
+const SIGNATURE_GRID: u32 = 64;
+
+/// Default mean luma difference (0.0 - 1.0) below which a frame counts as unchanged
+pub const DEFAULT_CHANGE_THRESHOLD: f64 = 0.01;
+
+/// Downsampled luma fingerprint of a frame, cheap to compute and compare
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameSignature {
+    width: u32,
+    height: u32,
+    samples: Vec<u8>,
+}
+
+impl FrameSignature {
+    /// Sample a `SIGNATURE_GRID` x `SIGNATURE_GRID` grid of pixels and keep their luma
+    ///
+    /// Gray frames are sampled as is, so their signatures compare with those of BGRA frames.
+    pub fn from_frame(frame: &CapturedFrame) -> Self {
+        let grid_x = SIGNATURE_GRID.min(frame.width);
+        let grid_y = SIGNATURE_GRID.min(frame.height);
+        let mut samples = Vec::with_capacity((grid_x * grid_y) as usize);
+
+        for gy in 0..grid_y {
+            let y = (gy * frame.height / grid_y.max(1)) as usize;
+            for gx in 0..grid_x {
+                let x = (gx * frame.width / grid_x.max(1)) as usize;
+                let pixel = y * frame.width as usize + x;
+                let luma = match frame.format {
+                    PixelFormat::Bgra => match frame.data.get(pixel * 4..pixel * 4 + 3) {
+                        Some(px) => ((px[0] as u32 * 29 + px[1] as u32 * 150 + px[2] as u32 * 77) >> 8) as u8,
+                        None => 0,
+                    },
+                    PixelFormat::Gray => frame.data.get(pixel).copied().unwrap_or(0),
+                };
+                samples.push(luma);
+            }
+        }
+
+        Self {
+            width: frame.width,
+            height: frame.height,
+            samples,
+        }
+    }
+
+    /// Mean absolute luma difference in the range 0.0 (identical) to 1.0
+    ///
+    /// Signatures of differently sized frames are always considered completely different.
+    pub fn diff(&self, other: &FrameSignature) -> f64 {
+        if self.width != other.width
+            || self.height != other.height
+            || self.samples.len() != other.samples.len()
+        {
+            return 1.0;
+        }
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+
+        let total: u64 = self
+            .samples
+            .iter()
+            .zip(&other.samples)
+            .map(|(a, b)| a.abs_diff(*b) as u64)
+            .sum();
+
+        total as f64 / (self.samples.len() as f64 * 255.0)
+    }
+}
+
+/// Stateful change detector that remembers the last frame it let through
+#[derive(Debug)]
+pub struct FrameDiff {
+    threshold: f64,
+    last_signature: Option<FrameSignature>,
+}
+
+impl FrameDiff {
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            threshold,
+            last_signature: None,
+        }
+    }
+
+    pub fn set_threshold(&mut self, threshold: f64) {
+        self.threshold = threshold;
+    }
+
+    /// Returns true if `frame` differs enough from the last changed frame to be worth processing
+    pub fn is_changed(&mut self, frame: &CapturedFrame) -> bool {
+        let signature = FrameSignature::from_frame(frame);
+        let changed = match &self.last_signature {
+            Some(last) => last.diff(&signature) >= self.threshold,
+            None => true,
+        };
+
+        if changed {
+            self.last_signature = Some(signature);
+        }
+        changed
+    }
+
+    /// Forget the last frame so the next one is always reported as changed
+    pub fn reset(&mut self) {
+        self.last_signature = None;
+    }
+}
+
+impl Default for FrameDiff {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHANGE_THRESHOLD)
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+#[cfg(feature = "opencv")]
+use opencv::{
+    core::{Mat, Size, Vector, CV_8UC4},
+    imgcodecs::{imencode, IMWRITE_JPEG_QUALITY},
+    imgproc::{cvt_color_def, resize, COLOR_BGRA2BGR, INTER_AREA},
+    prelude::*,
+};
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+use crate::services::{Service, ServiceError, ServiceTask};
+use super::graphics_capture::{CapturedFrame, GraphicsCaptureService};
+#[cfg(not(feature = "opencv"))]
+use super::image_ops;
+use super::memory_budget::{MemoryAccount, MemoryBudget};
+
+/// How much history is kept and how it is stored
+#[derive(Debug, Clone)]
+pub struct FrameHistoryConfig {
+    /// Length of history kept in the ring buffer
+    pub duration: Duration,
+    /// Frames per second sampled from the capture stream
+    pub sample_fps: f64,
+    /// Downscale factor applied before compression (0.0 - 1.0)
+    pub scale: f64,
+    /// JPEG quality (0 - 100)
+    pub jpeg_quality: i32,
+    /// Directory dumps are written into
+    pub dump_dir: PathBuf,
+}
+
+impl Default for FrameHistoryConfig {
+    fn default() -> Self {
+        Self {
+            duration: Duration::from_secs(10),
+            sample_fps: 10.0,
+            scale: 0.5,
+            jpeg_quality: 80,
+            dump_dir: PathBuf::from("frame_history"),
+        }
+    }
+}
+
+/// A compressed frame in the history buffer
+#[derive(Clone)]
+struct HistoryFrame {
+    jpeg: Vec<u8>,
+    captured_at: Instant,
+    wall_time: DateTime<Local>,
+}
+
+/// Entry of the `frames.json` index written next to dumped frames
+#[derive(Serialize)]
+struct DumpEntry {
+    file: String,
+    captured_at: String,
+    /// Milliseconds before the dump was triggered
+    age_ms: u128,
+}
+
+/// Keeps the last few seconds of captured frames so they can be dumped to disk
+/// after something went wrong (detection failure, death, ...)
+#[derive(Clone)]
+pub struct FrameHistoryService {
+    graphics_service: Arc<GraphicsCaptureService>,
+    config: FrameHistoryConfig,
+    frames: Arc<StdMutex<VecDeque<HistoryFrame>>>,
+    memory: MemoryAccount,
+    task: ServiceTask,
+}
+
+impl FrameHistoryService {
+    pub fn new(graphics_service: Arc<GraphicsCaptureService>, config: FrameHistoryConfig) -> Self {
+        Self {
+            graphics_service,
+            config,
+            frames: Arc::new(StdMutex::new(VecDeque::new())),
+            memory: MemoryBudget::global().account("frame_history"),
+            task: ServiceTask::new(),
+        }
+    }
+
+    /// Number of frames currently buffered
+    pub fn len(&self) -> usize {
+        self.frames.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop all buffered frames
+    pub fn clear(&self) {
+        self.frames.lock().unwrap().clear();
+        self.memory.set(0);
+    }
+
+    pub async fn is_recording(&self) -> bool {
+        self.task.is_busy().await
+    }
+
+    /// Start sampling frames from the capture service into the ring buffer
+    pub async fn start_recording(&self) -> Result<(), String> {
+        let mut receiver = self.graphics_service.subscribe();
+        let frames = self.frames.clone();
+        let memory = self.memory.clone();
+        let config = self.config.clone();
+        let sample_interval = Duration::from_secs_f64(1.0 / config.sample_fps.max(0.1));
+
+        self.task.start(move |cancelled| async move {
+            let mut last_sample: Option<Instant> = None;
+
+            loop {
+                // Stopping doesn't wait for the next frame
+                let frame = tokio::select! {
+                    _ = cancelled.cancelled() => break,
+                    frame = receiver.recv() => frame,
+                };
+                match frame {
+                    Ok(frame) => {
+                        if last_sample.is_some_and(|last| frame.timestamp.duration_since(last) < sample_interval) {
+                            continue;
+                        }
+                        last_sample = Some(frame.timestamp);
+
+                        match Self::compress_frame(&frame, config.scale, config.jpeg_quality) {
+                            Ok(jpeg) => {
+                                let mut frames = frames.lock().unwrap();
+                                memory.add(jpeg.len() as u64);
+                                frames.push_back(HistoryFrame {
+                                    jpeg,
+                                    captured_at: frame.timestamp,
+                                    wall_time: Local::now(),
+                                });
+                                Self::prune(&mut frames, config.duration, &memory);
+                            }
+                            Err(e) => tracing::warn!(error = %e, "Frame history compression failed"),
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Stop recording, the frame in progress is finished before this returns
+    pub async fn stop_recording(&self) {
+        self.task.stop().await;
+    }
+
+    /// Write the buffered frames to `<dump_dir>/<timestamp>-<reason>/` and return that directory
+    pub async fn dump(&self, reason: &str) -> Result<PathBuf, String> {
+        let frames: Vec<HistoryFrame> = self.frames.lock().unwrap().iter().cloned().collect();
+        if frames.is_empty() {
+            return Err("Frame history is empty".to_string());
+        }
+
+        let reason: String = reason
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let dir = self
+            .config
+            .dump_dir
+            .join(format!("{}-{}", Local::now().format("%Y%m%d-%H%M%S"), reason));
+
+        tokio::task::spawn_blocking(move || Self::write_dump(&dir, &frames).map(|_| dir))
+            .await
+            .map_err(|e| format!("Frame history dump task failed: {}", e))?
+    }
+
+    fn write_dump(dir: &Path, frames: &[HistoryFrame]) -> Result<(), String> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        let dumped_at = Instant::now();
+        let mut index = Vec::with_capacity(frames.len());
+        for (i, frame) in frames.iter().enumerate() {
+            let file = format!("frame_{:04}.jpg", i);
+            std::fs::write(dir.join(&file), &frame.jpeg)
+                .map_err(|e| format!("Failed to write {}: {}", file, e))?;
+
+            index.push(DumpEntry {
+                file,
+                captured_at: frame.wall_time.to_rfc3339(),
+                age_ms: dumped_at.duration_since(frame.captured_at).as_millis(),
+            });
+        }
+
+        let index = serde_json::to_vec_pretty(&index)
+            .map_err(|e| format!("Failed to serialize frame index: {}", e))?;
+        std::fs::write(dir.join("frames.json"), index)
+            .map_err(|e| format!("Failed to write frame index: {}", e))
+    }
+
+    /// Drop frames older than `duration` relative to the newest frame, and the oldest ones
+    /// beyond that while the frame memory budget is exceeded
+    fn prune(frames: &mut VecDeque<HistoryFrame>, duration: Duration, memory: &MemoryAccount) {
+        let Some(newest) = frames.back().map(|frame| frame.captured_at) else {
+            return;
+        };
+        while frames.len() > 1
+            && frames
+                .front()
+                .is_some_and(|frame| newest.duration_since(frame.captured_at) > duration || memory.is_exceeded())
+        {
+            if let Some(frame) = frames.pop_front() {
+                memory.sub(frame.jpeg.len() as u64);
+            }
+        }
+    }
+
+    /// Downscale `frame` by `scale` (0.05 - 1.0) and encode it as JPEG
+    #[cfg(feature = "opencv")]
+    pub(crate) fn compress_frame(frame: &CapturedFrame, scale: f64, quality: i32) -> Result<Vec<u8>, String> {
+        let expected = frame.width as usize * frame.height as usize * 4;
+        if frame.data.len() < expected || expected == 0 {
+            return Err(format!("Frame data too small: {} < {}", frame.data.len(), expected));
+        }
+
+        let mut bgra = Mat::zeros(frame.height as i32, frame.width as i32, CV_8UC4)
+            .and_then(|mat| mat.to_mat())
+            .map_err(|e| format!("Failed to create Mat: {}", e))?;
+        bgra.data_bytes_mut()
+            .map_err(|e| format!("Failed to access Mat data: {}", e))?
+            .copy_from_slice(&frame.data[..expected]);
+
+        let scale = scale.clamp(0.05, 1.0);
+        let mut scaled = Mat::default();
+        resize(&bgra, &mut scaled, Size::new(0, 0), scale, scale, INTER_AREA)
+            .map_err(|e| format!("Failed to resize frame: {}", e))?;
+
+        let mut bgr = Mat::default();
+        cvt_color_def(&scaled, &mut bgr, COLOR_BGRA2BGR)
+            .map_err(|e| format!("Failed to convert frame: {}", e))?;
+
+        let mut buffer = Vector::<u8>::new();
+        let params = Vector::<i32>::from_slice(&[IMWRITE_JPEG_QUALITY, quality.clamp(0, 100)]);
+        imencode(".jpg", &bgr, &mut buffer, &params)
+            .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
+
+        Ok(buffer.to_vec())
+    }
+
+    #[cfg(not(feature = "opencv"))]
+    pub(crate) fn compress_frame(frame: &CapturedFrame, scale: f64, quality: i32) -> Result<Vec<u8>, String> {
+        let rgb = image_ops::bgra_to_rgb(frame)?;
+        image_ops::encode_jpeg(&image_ops::resize(&rgb, scale.clamp(0.05, 1.0)), quality)
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for FrameHistoryService {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_recording().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_recording().await;
+        Ok(())
+    }
+}
@@ -0,0 +1,334 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use opencv::{
+    core::{mean, mean_std_dev, no_array, Mat, Size},
+    imgcodecs::{imread, IMREAD_GRAYSCALE},
+    imgproc::{resize, INTER_AREA},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::services::{Service, ServiceError, ServiceTask};
+use super::cpu_pool::spawn_cpu;
+use super::event_bus::{BotEvent, EventBus};
+use super::graphics_capture::{CapturedFrame, GraphicsCaptureService};
+use super::template_matcher::{TemplateLibrary, TemplateSettings};
+use super::vision::{bgra_mat, to_gray, to_hsv};
+
+/// Capacity of the transition channel, slow subscribers skip old transitions
+const TRANSITION_CHANNEL_CAPACITY: usize = 16;
+
+/// Frames are shrunk to this width before the brightness and color statistics are taken
+const STATS_WIDTH: i32 = 160;
+
+/// Coarse state of the game client
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameState {
+    #[default]
+    Unknown,
+    Loading,
+    CharacterSelect,
+    InWorld,
+    Dead,
+    DialogOpen,
+}
+
+impl GameState {
+    /// Name the state is serialized under, e.g. `in_world`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GameState::Unknown => "unknown",
+            GameState::Loading => "loading",
+            GameState::CharacterSelect => "character_select",
+            GameState::InWorld => "in_world",
+            GameState::Dead => "dead",
+            GameState::DialogOpen => "dialog_open",
+        }
+    }
+}
+
+/// A template whose presence means the game is in `state`, e.g. the "Respawn" button
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StateIndicator {
+    pub state: GameState,
+    /// PNG of the indicator
+    pub template: PathBuf,
+    #[serde(default)]
+    pub settings: TemplateSettings,
+}
+
+/// Heuristics used to classify frames
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameStateRules {
+    /// Frames darker than this mean gray level (0 - 255) ...
+    pub loading_max_brightness: f64,
+    /// ... or more uniform than this gray level deviation are loading screens
+    pub loading_max_deviation: f64,
+    /// Frames with less mean saturation (0 - 255) are treated as the grayed out death screen,
+    /// 0 disables the check for games that don't desaturate on death
+    pub dead_max_saturation: f64,
+    /// Checked in order, the first visible indicator decides the state
+    pub indicators: Vec<StateIndicator>,
+    /// State of frames no rule matched
+    pub fallback: GameState,
+    /// A new state is only reported after this many consecutive frames agree
+    pub stable_frames: u32,
+    /// Minimum time between two classified frames, in milliseconds when serialized
+    #[serde(with = "super::duration_millis")]
+    pub interval: Duration,
+}
+
+impl Default for GameStateRules {
+    fn default() -> Self {
+        Self {
+            loading_max_brightness: 12.0,
+            loading_max_deviation: 6.0,
+            dead_max_saturation: 0.0,
+            indicators: Vec::new(),
+            fallback: GameState::InWorld,
+            stable_frames: 3,
+            interval: Duration::from_millis(250),
+        }
+    }
+}
+
+/// A change of the game state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameStateTransition {
+    pub from: GameState,
+    pub to: GameState,
+}
+
+/// Labels frames with a [`GameState`] from brightness and color statistics and indicator templates
+pub struct GameStateClassifier {
+    rules: GameStateRules,
+    /// Indicator templates named after their index in `rules.indicators`
+    templates: TemplateLibrary,
+    state: GameState,
+    candidate: GameState,
+    candidate_frames: u32,
+}
+
+impl GameStateClassifier {
+    pub fn new(rules: GameStateRules) -> Result<Self, String> {
+        let mut templates = TemplateLibrary::new();
+        for (index, indicator) in rules.indicators.iter().enumerate() {
+            let path = &indicator.template;
+            let image = imread(&path.to_string_lossy(), IMREAD_GRAYSCALE)
+                .map_err(|e| format!("Failed to read indicator {}: {}", path.display(), e))?;
+            if image.empty() {
+                return Err(format!("Indicator {} is empty or unreadable", path.display()));
+            }
+            templates.insert(index.to_string(), image, indicator.settings);
+        }
+
+        Ok(Self {
+            rules,
+            templates,
+            state: GameState::Unknown,
+            candidate: GameState::Unknown,
+            candidate_frames: 0,
+        })
+    }
+
+    pub fn rules(&self) -> &GameStateRules {
+        &self.rules
+    }
+
+    /// Last reported state
+    pub fn state(&self) -> GameState {
+        self.state
+    }
+
+    /// Classify a frame and return the transition once the new state is stable
+    pub fn update(&mut self, frame: &CapturedFrame) -> Result<Option<GameStateTransition>, String> {
+        let state = self.classify(frame)?;
+        if state == self.state {
+            self.candidate_frames = 0;
+            return Ok(None);
+        }
+
+        if state == self.candidate {
+            self.candidate_frames += 1;
+        } else {
+            self.candidate = state;
+            self.candidate_frames = 1;
+        }
+        if self.candidate_frames < self.rules.stable_frames.max(1) {
+            return Ok(None);
+        }
+
+        let transition = GameStateTransition {
+            from: self.state,
+            to: state,
+        };
+        self.state = state;
+        self.candidate_frames = 0;
+        Ok(Some(transition))
+    }
+
+    /// State of a single frame without any smoothing
+    pub fn classify(&self, frame: &CapturedFrame) -> Result<GameState, String> {
+        let small = Self::shrink(&bgra_mat(frame)?)?;
+
+        let mut gray_mean = Mat::default();
+        let mut gray_deviation = Mat::default();
+        mean_std_dev(&to_gray(&small)?, &mut gray_mean, &mut gray_deviation, &no_array())
+            .map_err(|e| format!("Failed to measure brightness: {}", e))?;
+        let brightness = *gray_mean.at::<f64>(0).map_err(|e| format!("Failed to read brightness: {}", e))?;
+        let deviation = *gray_deviation
+            .at::<f64>(0)
+            .map_err(|e| format!("Failed to read brightness deviation: {}", e))?;
+        if brightness <= self.rules.loading_max_brightness || deviation <= self.rules.loading_max_deviation {
+            return Ok(GameState::Loading);
+        }
+
+        if !self.templates.is_empty() {
+            let matches = self.templates.match_frame(frame)?;
+            let first = self
+                .rules
+                .indicators
+                .iter()
+                .enumerate()
+                .find(|(index, _)| matches.iter().any(|matched| matched.name == index.to_string()));
+            if let Some((_, indicator)) = first {
+                return Ok(indicator.state);
+            }
+        }
+
+        if self.rules.dead_max_saturation > 0.0 {
+            let saturation = mean(&to_hsv(&small)?, &no_array())
+                .map_err(|e| format!("Failed to measure saturation: {}", e))?[1];
+            if saturation <= self.rules.dead_max_saturation {
+                return Ok(GameState::Dead);
+            }
+        }
+
+        Ok(self.rules.fallback)
+    }
+
+    fn shrink(bgra: &Mat) -> Result<Mat, String> {
+        let size = bgra.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
+        if size.width <= STATS_WIDTH {
+            return bgra.try_clone().map_err(|e| format!("Failed to copy frame: {}", e));
+        }
+        let height = ((size.height as f64 * STATS_WIDTH as f64 / size.width as f64).round() as i32).max(1);
+        let mut small = Mat::default();
+        resize(bgra, &mut small, Size::new(STATS_WIDTH, height), 0.0, 0.0, INTER_AREA)
+            .map_err(|e| format!("Failed to shrink frame: {}", e))?;
+        Ok(small)
+    }
+}
+
+/// Classifies captured frames and publishes game state transitions
+#[derive(Clone)]
+pub struct GameStateService {
+    graphics_service: Arc<GraphicsCaptureService>,
+    classifier: Arc<StdMutex<GameStateClassifier>>,
+    transition_sender: broadcast::Sender<GameStateTransition>,
+    task: ServiceTask,
+}
+
+impl GameStateService {
+    pub fn new(graphics_service: Arc<GraphicsCaptureService>, rules: GameStateRules) -> Result<Self, String> {
+        Ok(Self {
+            graphics_service,
+            classifier: Arc::new(StdMutex::new(GameStateClassifier::new(rules)?)),
+            transition_sender: broadcast::channel(TRANSITION_CHANNEL_CAPACITY).0,
+            task: ServiceTask::new(),
+        })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<GameStateTransition> {
+        self.transition_sender.subscribe()
+    }
+
+    pub fn state(&self) -> GameState {
+        self.classifier.lock().unwrap().state()
+    }
+
+    pub fn rules(&self) -> GameStateRules {
+        self.classifier.lock().unwrap().rules().clone()
+    }
+
+    /// Replace the rules, the current state is kept until the new rules settle on one
+    pub fn set_rules(&self, rules: GameStateRules) -> Result<(), String> {
+        let mut replacement = GameStateClassifier::new(rules)?;
+        let mut classifier = self.classifier.lock().unwrap();
+        replacement.state = classifier.state;
+        *classifier = replacement;
+        Ok(())
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.task.is_busy().await
+    }
+
+    pub async fn start_classifier(&self) -> Result<(), String> {
+        let mut receiver = self.graphics_service.subscribe();
+        let classifier = self.classifier.clone();
+        let transition_sender = self.transition_sender.clone();
+
+        self.task.start(move |cancelled| async move {
+            let mut last_run: Option<Instant> = None;
+
+            loop {
+                // Stopping doesn't wait for the next frame
+                let frame = tokio::select! {
+                    _ = cancelled.cancelled() => break,
+                    frame = receiver.recv() => frame,
+                };
+                match frame {
+                    Ok(frame) => {
+                        let interval = classifier.lock().unwrap().rules().interval;
+                        if last_run.is_some_and(|last| frame.timestamp.duration_since(last) < interval) {
+                            continue;
+                        }
+                        last_run = Some(frame.timestamp);
+
+                        let classifier = classifier.clone();
+                        let result = spawn_cpu(move || classifier.lock().unwrap().update(&frame)).await;
+                        match result {
+                            Ok(Ok(Some(transition))) => {
+                                EventBus::global().publish(BotEvent::GameStateChanged {
+                                    state: transition.to.as_str().to_string(),
+                                });
+                                let _ = transition_sender.send(transition);
+                            }
+                            Ok(Ok(None)) => {}
+                            Ok(Err(e)) => tracing::warn!(error = %e, "Game state classification failed"),
+                            Err(e) => tracing::error!(error = %e, "Game state classification task failed"),
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Stop classifying, the frame in progress is finished before this returns
+    pub async fn stop_classifier(&self) {
+        self.task.stop().await;
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for GameStateService {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_classifier().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_classifier().await;
+        Ok(())
+    }
+}
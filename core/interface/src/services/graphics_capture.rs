@@ -1,6 +1,9 @@
-use std::sync::Arc;
+use std::fmt;
+use std::sync::{Arc, Mutex as StdMutex};
 use std::time::{Duration, Instant};
-use std::sync::atomic::{AtomicUsize, AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, AtomicU64, Ordering};
+
+use serde::Serialize;
 
 use platforms::windows_capture::{
     capture::{CaptureControl, GraphicsCaptureApiHandler, Context},
@@ -13,24 +16,280 @@ use platforms::windows_capture::{
     dxgi_desktop_duplication::{DxgiDesktopDuplication, DxgiError},
     texture_processor::TextureProcessor,
 };
-use tokio::sync::{broadcast, Mutex};
+#[cfg(feature = "mock-capture")]
+use platforms::mock_capture::{MockCapture, MockCaptureControl, MockSource};
+use tokio::sync::{broadcast, mpsc, watch, Mutex};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::{BotConfig, CaptureBackend};
+use crate::services::{Service, ServiceError, ServiceHealth, ServiceStatus};
+use super::event_bus::{BotEvent, EventBus};
+use super::frame_diff::FrameSignature;
+use super::low_power::{LowPowerMode, LOW_POWER_FPS};
+use super::memory_budget::{MemoryAccount, MemoryBudget};
+use super::metrics::{FrameRateMeter, LatencyPercentiles, LatencyTracker};
+use super::session_replay::{ReplayControl, ReplaySource};
+use super::supervisor::{supervise_blocking_until, RestartPolicy};
+use super::thread_tuning::tune_capture_thread;
+use super::vision::Rect;
 
 /// Raw frame data with metadata (komari-style: always BGRA)
 #[derive(Clone, Debug)]
 pub struct CapturedFrame {
-    pub data: Vec<u8>,         // Always BGRA format (4 bytes per pixel)
+    pub data: Vec<u8>,         // BGRA (4 bytes per pixel) unless `format` says otherwise
     pub width: u32,
     pub height: u32,
     pub timestamp: Instant,
     pub source: CaptureSource,
+    /// Only [`GraphicsCaptureService::subscribe_with`] delivers anything but BGRA
+    pub format: PixelFormat,
+}
+
+/// Layout of [`CapturedFrame::data`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum PixelFormat {
+    #[default]
+    Bgra,
+    /// One luma byte per pixel, weighted like OpenCV's `COLOR_BGRA2GRAY`
+    Gray,
 }
 
+/// How frames are shaped for one subscriber of [`GraphicsCaptureService::subscribe_with`]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SubscribeOptions {
+    /// Most frames per second delivered, every frame if `None`
+    pub max_fps: Option<f64>,
+    /// Crop every frame to this region, frame coordinates then start at its top left
+    pub roi: Option<Rect>,
+    pub format: PixelFormat,
+}
 
+impl SubscribeOptions {
+    pub fn max_fps(mut self, max_fps: f64) -> Self {
+        self.max_fps = Some(max_fps);
+        self
+    }
 
-#[derive(Clone, Debug)]
+    pub fn roi(mut self, roi: Rect) -> Self {
+        self.roi = Some(roi);
+        self
+    }
+
+    pub fn format(mut self, format: PixelFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// `frame` cropped and converted, shared as is if nothing changes, `None` if the ROI is
+    /// outside of it
+    fn shape(&self, frame: &Arc<CapturedFrame>) -> Option<Arc<CapturedFrame>> {
+        if self.roi.is_none() && self.format == PixelFormat::Bgra {
+            return Some(frame.clone());
+        }
+        let full = Rect::new(0, 0, frame.width as i32, frame.height as i32);
+        let rect = self.roi.unwrap_or(full).clamp_to(full.width, full.height)?;
+        let stride = frame.width as usize * 4;
+        let bytes_per_pixel = match self.format {
+            PixelFormat::Bgra => 4,
+            PixelFormat::Gray => 1,
+        };
+        let mut data = Vec::with_capacity(rect.area() as usize * bytes_per_pixel);
+        for y in rect.y..rect.y + rect.height {
+            let start = y as usize * stride + rect.x as usize * 4;
+            let row = frame.data.get(start..start + rect.width as usize * 4)?;
+            match self.format {
+                PixelFormat::Bgra => data.extend_from_slice(row),
+                PixelFormat::Gray => data.extend(
+                    row.chunks_exact(4)
+                        .map(|bgra| ((bgra[2] as u32 * 77 + bgra[1] as u32 * 150 + bgra[0] as u32 * 29) >> 8) as u8),
+                ),
+            }
+        }
+        Some(Arc::new(CapturedFrame {
+            data,
+            width: rect.width as u32,
+            height: rect.height as u32,
+            timestamp: frame.timestamp,
+            source: frame.source,
+            format: self.format,
+        }))
+    }
+}
+
+impl CapturedFrame {
+    /// Cheap perceptual difference to `other`, from 0.0 (identical) to 1.0
+    pub fn diff_score(&self, other: &CapturedFrame) -> f64 {
+        FrameSignature::from_frame(self).diff(&FrameSignature::from_frame(other))
+    }
+}
+
+/// Window to capture with Windows Graphics Capture
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CaptureTarget {
+    /// First window whose title contains the given text
+    Title(String),
+    /// First window owned by the process ID
+    Pid(u32),
+    /// First window owned by the executable, e.g. `MapleStory.exe`
+    ProcessName(String),
+    /// A specific window handle
+    Hwnd(isize),
+}
+
+impl CaptureTarget {
+    fn resolve(&self) -> Result<Window, String> {
+        match self {
+            CaptureTarget::Title(title) => Window::from_contains_name(title)
+                .map_err(|_| format!("Window '{}' not found", title)),
+            CaptureTarget::Pid(pid) => Window::from_pid(*pid)
+                .map_err(|_| format!("No window found for process {}", pid)),
+            CaptureTarget::ProcessName(name) => Window::from_process_name(name)
+                .map_err(|_| format!("No window found for process '{}'", name)),
+            CaptureTarget::Hwnd(hwnd) => {
+                let window = Window::from_raw_hwnd(*hwnd as *mut std::ffi::c_void);
+                if window.is_valid() {
+                    Ok(window)
+                } else {
+                    Err(format!("Window handle {:#x} is not capturable", hwnd))
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for CaptureTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureTarget::Title(title) => write!(f, "title '{}'", title),
+            CaptureTarget::Pid(pid) => write!(f, "pid {}", pid),
+            CaptureTarget::ProcessName(name) => write!(f, "process '{}'", name),
+            CaptureTarget::Hwnd(hwnd) => write!(f, "hwnd {:#x}", hwnd),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
 pub enum CaptureSource {
     WindowsGraphicsCapture,
     DxgiDesktopDuplication,
+    /// Recorded frames replayed by the `mock-capture` backend
+    Mock,
+    /// Frames of a session archive replayed by [`ReplaySource`]
+    Replay,
+}
+
+/// Capacity of the frame broadcast channel
+const FRAME_BUFFER_CAPACITY: usize = 100;
+
+/// Shaped frames waiting for a subscriber of [`GraphicsCaptureService::subscribe_with`],
+/// a busy one skips frames instead of falling behind
+const SHAPED_FRAME_CAPACITY: usize = 2;
+
+/// Frame rate the capture backends aim for when consumers keep up
+pub const CAPTURE_TARGET_FPS: u32 = 30;
+
+/// Lowest frame rate the adaptive controller will drop to
+const MIN_ADAPTIVE_FPS: u32 = 5;
+
+/// Minimum time between two frame rate adjustments
+const FPS_ADJUST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Lowers the capture frame rate while subscribers lag behind and restores it
+/// once they catch up, keeping the broadcast backlog bounded
+#[derive(Debug)]
+pub struct FrameRateController {
+    target_fps: AtomicU32,
+    effective_fps: AtomicU32,
+    state: StdMutex<FrameRateState>,
+}
+
+#[derive(Debug)]
+struct FrameRateState {
+    last_publish: Option<Instant>,
+    last_adjust: Option<Instant>,
+}
+
+impl FrameRateController {
+    pub fn new(target_fps: u32) -> Self {
+        let target_fps = target_fps.max(MIN_ADAPTIVE_FPS);
+        Self {
+            target_fps: AtomicU32::new(target_fps),
+            effective_fps: AtomicU32::new(target_fps),
+            state: StdMutex::new(FrameRateState { last_publish: None, last_adjust: None }),
+        }
+    }
+
+    /// Frame rate currently being delivered to subscribers
+    pub fn effective_fps(&self) -> u32 {
+        self.effective_fps.load(Ordering::Relaxed)
+    }
+
+    pub fn target_fps(&self) -> u32 {
+        self.target_fps.load(Ordering::Relaxed)
+    }
+
+    /// Change the frame rate aimed for, applies from the next frame
+    pub fn set_target_fps(&self, target_fps: u32) {
+        let target_fps = target_fps.max(MIN_ADAPTIVE_FPS);
+        self.target_fps.store(target_fps, Ordering::Relaxed);
+        self.effective_fps.store(target_fps, Ordering::Relaxed);
+    }
+
+    /// Time between frames at the effective frame rate, at most [`LOW_POWER_FPS`] in low-power mode
+    pub fn frame_interval(&self) -> Duration {
+        let fps = if LowPowerMode::global().is_enabled() {
+            self.effective_fps().min(LOW_POWER_FPS)
+        } else {
+            self.effective_fps()
+        };
+        Duration::from_secs_f64(1.0 / fps as f64)
+    }
+
+    /// Whether a frame arriving at `now` should be published, marks it as published if so
+    pub fn should_publish(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if let Some(last) = state.last_publish {
+            if now.saturating_duration_since(last) < self.frame_interval() {
+                return false;
+            }
+        }
+        state.last_publish = Some(now);
+        true
+    }
+
+    /// Adapt the frame rate to the number of frames still queued for the slowest subscriber
+    pub fn adjust(&self, queued: usize, capacity: usize, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(last) = state.last_adjust {
+            if now.saturating_duration_since(last) < FPS_ADJUST_INTERVAL {
+                return;
+            }
+        }
+
+        let current = self.effective_fps();
+        let next = if queued > capacity / 4 {
+            // Subscribers are falling behind - back off quickly
+            (current / 2).max(MIN_ADAPTIVE_FPS)
+        } else if queued == 0 {
+            // Caught up - recover gradually
+            (current + MIN_ADAPTIVE_FPS).min(self.target_fps())
+        } else {
+            current
+        };
+
+        if next != current {
+            self.effective_fps.store(next, Ordering::Relaxed);
+            state.last_adjust = Some(now);
+            if next < current {
+                tracing::debug!(effective_fps = next, target_fps = self.target_fps(), "Subscribers lag, capturing slower");
+                EventBus::global().publish(BotEvent::FrameLagging {
+                    effective_fps: next,
+                    target_fps: self.target_fps(),
+                });
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -39,44 +298,122 @@ pub struct CaptureMetrics {
     pub frames_dropped: AtomicUsize,
     pub total_capture_time_ms: AtomicU64,
     pub active_subscribers: AtomicUsize,
+    pub latency: LatencyTracker,
+    pub frame_rate: FrameRateController,
+    /// Rate of frames actually delivered to subscribers
+    pub delivered: FrameRateMeter,
+    last_source: StdMutex<Option<CaptureSource>>,
+}
+
+/// Snapshot of capture performance, serializable for external tooling
+#[derive(Clone, Debug, Serialize)]
+pub struct CaptureStats {
+    pub backend: Option<CaptureSource>,
+    /// Delivered frames per second, averaged over recent frames
+    pub fps: f64,
+    pub effective_fps: u32,
+    pub target_fps: u32,
+    pub frames_captured: usize,
+    pub frames_dropped: usize,
+    pub active_subscribers: usize,
+    /// Time spent publishing each frame
+    pub latency: LatencyPercentiles,
+    /// `None` before the first frame was delivered
+    pub since_last_frame_ms: Option<f64>,
+}
+
+impl fmt::Display for CaptureStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let backend = match self.backend {
+            Some(CaptureSource::WindowsGraphicsCapture) => "Windows Graphics Capture",
+            Some(CaptureSource::DxgiDesktopDuplication) => "DXGI Desktop Duplication",
+            Some(CaptureSource::Mock) => "Mock (recorded frames)",
+            Some(CaptureSource::Replay) => "Replay (session archive)",
+            None => "None",
+        };
+        let last_frame = match self.since_last_frame_ms {
+            Some(ms) => format!("{:.0}ms ago", ms),
+            None => "never".to_string(),
+        };
+        write!(
+            f,
+            "📊 Graphics Capture Service:\n\
+             🎯 FPS: {:.1} (adaptive {}/{})\n\
+             📈 Frames: {} captured, {} dropped\n\
+             ⏱️  Latency: {}\n\
+             🕐 Last frame: {}\n\
+             👥 Active subscribers: {}\n\
+             📺 Source: {}",
+            self.fps,
+            self.effective_fps,
+            self.target_fps,
+            self.frames_captured,
+            self.frames_dropped,
+            self.latency,
+            last_frame,
+            self.active_subscribers,
+            backend
+        )
+    }
 }
 
 impl CaptureMetrics {
     pub fn new() -> Self {
+        Self::with_target_fps(CAPTURE_TARGET_FPS)
+    }
+
+    pub fn with_target_fps(target_fps: u32) -> Self {
         Self {
             frames_captured: AtomicUsize::new(0),
             frames_dropped: AtomicUsize::new(0),
             total_capture_time_ms: AtomicU64::new(0),
             active_subscribers: AtomicUsize::new(0),
+            latency: LatencyTracker::new(),
+            frame_rate: FrameRateController::new(target_fps),
+            delivered: FrameRateMeter::new(),
+            last_source: StdMutex::new(None),
         }
     }
 
     pub fn get_fps(&self) -> f64 {
-        let frames = self.frames_captured.load(Ordering::Relaxed) as f64;
-        let time_ms = self.total_capture_time_ms.load(Ordering::Relaxed) as f64;
-        if time_ms > 0.0 { (frames * 1000.0) / time_ms } else { 0.0 }
+        self.delivered.fps(Instant::now())
     }
 
-    pub fn get_stats(&self) -> String {
-        format!(
-            "📊 Graphics Capture Service:\n\
-             🎯 FPS: {:.1}\n\
-             📈 Frames: {} captured, {} dropped\n\
-             👥 Active subscribers: {}\n\
-             📺 Source: Mixed (Windows Graphics Capture + DXGI)",
-            self.get_fps(),
-            self.frames_captured.load(Ordering::Relaxed),
-            self.frames_dropped.load(Ordering::Relaxed),
-            self.active_subscribers.load(Ordering::Relaxed)
-        )
+    /// Record the outcome of publishing one frame
+    pub fn record_frame(&self, source: CaptureSource, elapsed: Duration, delivered: bool, subscribers: usize) {
+        self.active_subscribers.store(subscribers, Ordering::Relaxed);
+        if delivered {
+            self.frames_captured.fetch_add(1, Ordering::Relaxed);
+            self.delivered.record(Instant::now());
+        } else {
+            self.frames_dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        self.total_capture_time_ms.fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+        self.latency.record(elapsed);
+        *self.last_source.lock().unwrap() = Some(source);
+    }
+
+    pub fn stats(&self) -> CaptureStats {
+        let now = Instant::now();
+        CaptureStats {
+            backend: *self.last_source.lock().unwrap(),
+            fps: self.delivered.fps(now),
+            effective_fps: self.frame_rate.effective_fps(),
+            target_fps: self.frame_rate.target_fps(),
+            frames_captured: self.frames_captured.load(Ordering::Relaxed),
+            frames_dropped: self.frames_dropped.load(Ordering::Relaxed),
+            active_subscribers: self.active_subscribers.load(Ordering::Relaxed),
+            latency: self.latency.percentiles(),
+            since_last_frame_ms: self.delivered.since_last_frame(now).map(|since| since.as_secs_f64() * 1000.0),
+        }
     }
 }
 
 /// High-performance graphics capture service with multiple consumers
 #[derive(Clone)]
 pub struct GraphicsCaptureService {
-    // Broadcast channel for multiple subscribers
-    frame_broadcast: broadcast::Sender<CapturedFrame>,
+    // Broadcast channel for multiple subscribers and the latest frame
+    frames: FrameSink,
     
     // Current capture state
     capture_control: Arc<Mutex<Option<CaptureControl<FrameHandler, ()>>>>,
@@ -86,23 +423,80 @@ pub struct GraphicsCaptureService {
     metrics: Arc<CaptureMetrics>,
     
     // DXGI fallback for high-performance mode
-    dxgi_capture: Arc<Mutex<Option<DxgiCapture>>>,
+    dxgi_capture: Arc<Mutex<Option<DxgiControl>>>,
+    gpu_processing: Arc<AtomicBool>,
+
+    // Offline replay of recorded frames
+    #[cfg(feature = "mock-capture")]
+    mock_capture: Arc<Mutex<Option<MockCaptureControl>>>,
+
+    // Replay of a recorded session
+    replay_capture: Arc<Mutex<Option<ReplayControl>>>,
+
+    // Applied to the DXGI capture thread
+    restart_policy: Arc<StdMutex<RestartPolicy>>,
 }
 
-struct FrameHandler {
-    frame_broadcast: broadcast::Sender<CapturedFrame>,
+/// Where captured frames go: the broadcast to every subscriber and the latest frame watch
+#[derive(Clone)]
+struct FrameSink {
+    broadcast: broadcast::Sender<Arc<CapturedFrame>>,
+    latest: Arc<watch::Sender<Option<Arc<CapturedFrame>>>>,
     metrics: Arc<CaptureMetrics>,
+    // Frames queued in the broadcast for the slowest subscriber
+    memory: MemoryAccount,
+}
+
+impl FrameSink {
+    fn new(metrics: Arc<CaptureMetrics>) -> Self {
+        Self {
+            broadcast: broadcast::channel(FRAME_BUFFER_CAPACITY).0,
+            latest: Arc::new(watch::channel(None).0),
+            metrics,
+            memory: MemoryBudget::global().account("capture"),
+        }
+    }
+
+    /// Hand `frame` to every subscriber and record how long it took since `started`
+    fn send(&self, frame: CapturedFrame, started: Instant) {
+        // Shared by every subscriber, the pixels are never copied per receiver
+        let frame = Arc::new(frame);
+        let source = frame.source;
+        // Only updated while followed, so an unwatched frame isn't kept alive
+        let watched = self.latest.receiver_count() > 0;
+        if watched {
+            self.latest.send_replace(Some(frame.clone()));
+        } else if self.latest.borrow().is_some() {
+            self.latest.send_replace(None);
+        }
+        let subscribers = self.broadcast.receiver_count();
+        // Queued frames are released as the slowest subscriber reads them, until then new
+        // frames over the memory budget are dropped instead of queued
+        let bytes = frame.data.len() as u64;
+        self.memory.set(self.broadcast.len() as u64 * bytes);
+        let delivered = (self.memory.fits(bytes) && self.broadcast.send(frame).is_ok()) || watched;
+        self.metrics.record_frame(source, started.elapsed(), delivered, subscribers);
+    }
+
+    /// [`Self::send`] for live capture, adapting the frame rate to how far subscribers lag
+    fn publish(&self, frame: CapturedFrame, started: Instant) {
+        self.send(frame, started);
+        self.metrics.frame_rate.adjust(self.broadcast.len(), FRAME_BUFFER_CAPACITY, Instant::now());
+    }
+}
+
+struct FrameHandler {
+    frames: FrameSink,
 }
 
 impl GraphicsCaptureApiHandler for FrameHandler {
-    type Flags = (broadcast::Sender<CapturedFrame>, Arc<CaptureMetrics>);
+    type Flags = FrameSink;
     type Error = ();
 
     fn new(ctx: Context<Self::Flags>) -> Result<Self, Self::Error> {
-        Ok(Self {
-            frame_broadcast: ctx.flags.0,
-            metrics: ctx.flags.1,
-        })
+        // Called on the capture thread
+        tune_capture_thread();
+        Ok(Self { frames: ctx.flags })
     }
 
     fn on_frame_arrived(
@@ -112,6 +506,11 @@ impl GraphicsCaptureApiHandler for FrameHandler {
     ) -> Result<(), Self::Error> {
         let capture_start = Instant::now();
 
+        // Skip frames while the adaptive rate is below the capture rate
+        if !self.frames.metrics.frame_rate.should_publish(capture_start) {
+            return Ok(());
+        }
+
         if let Ok(mut frame_buffer) = frame.buffer() {
             let width = frame_buffer.width();
             let height = frame_buffer.height();
@@ -123,41 +522,53 @@ impl GraphicsCaptureApiHandler for FrameHandler {
                     height,
                     timestamp: capture_start,
                     source: CaptureSource::WindowsGraphicsCapture,
+                    format: PixelFormat::Bgra,
                 };
 
-                let subscriber_count = self.frame_broadcast.receiver_count();
-                self.metrics.active_subscribers.store(subscriber_count, Ordering::Relaxed);
-                
-                match self.frame_broadcast.send(captured_frame) {
-                    Ok(_) => {
-                        self.metrics.frames_captured.fetch_add(1, Ordering::Relaxed);
-                    }
-                    Err(_) => {
-                        self.metrics.frames_dropped.fetch_add(1, Ordering::Relaxed);
-                    }
-                }
-
-                let elapsed = capture_start.elapsed().as_millis() as u64;
-                self.metrics.total_capture_time_ms.fetch_add(elapsed, Ordering::Relaxed);
+                self.frames.publish(captured_frame, capture_start);
             }
         }
 
         Ok(())
     }
+
+    fn on_closed(&mut self) -> Result<(), Self::Error> {
+        tracing::warn!("Captured window closed");
+        EventBus::global().publish(BotEvent::WindowLost {
+            reason: "Captured window closed".to_string(),
+        });
+        Ok(())
+    }
+}
+
+/// How long a DXGI acquire waits for the desktop to update
+const DXGI_FRAME_TIMEOUT_MS: u32 = 100;
+
+/// Running DXGI capture thread
+struct DxgiControl {
+    cancel: CancellationToken,
+    supervisor: JoinHandle<()>,
+}
+
+impl DxgiControl {
+    /// Cancel the capture loop and wait for its thread to return
+    async fn stop(self) {
+        self.cancel.cancel();
+        let _ = self.supervisor.await;
+    }
 }
 
 struct DxgiCapture {
     duplication: DxgiDesktopDuplication,
     texture_processor: TextureProcessor,
-    frame_broadcast: broadcast::Sender<CapturedFrame>,
-    metrics: Arc<CaptureMetrics>,
+    frames: FrameSink,
+    // Requested GPU processing and the setting last applied
+    gpu_processing: Arc<AtomicBool>,
+    gpu_applied: Option<bool>,
 }
 
 impl DxgiCapture {
-    pub fn new(
-        frame_broadcast: broadcast::Sender<CapturedFrame>,
-        metrics: Arc<CaptureMetrics>,
-    ) -> Result<Self, String> {
+    fn new(frames: FrameSink, gpu_processing: Arc<AtomicBool>) -> Result<Self, String> {
         let mut duplication = DxgiDesktopDuplication::new()
             .map_err(|e| format!("Failed to create DXGI duplication: {}", e))?;
         
@@ -172,16 +583,32 @@ impl DxgiCapture {
         Ok(Self {
             duplication,
             texture_processor,
-            frame_broadcast,
-            metrics,
+            frames,
+            gpu_processing,
+            gpu_applied: None,
         })
     }
+
+    fn apply_gpu_processing(&mut self) {
+        let enabled = self.gpu_processing.load(Ordering::Relaxed);
+        if self.gpu_applied != Some(enabled) {
+            self.duplication.set_gpu_processing(enabled);
+            self.texture_processor.set_gpu_processing(enabled);
+            self.gpu_applied = Some(enabled);
+        }
+    }
     
-    pub async fn start_capture_loop(&mut self) -> Result<(), String> {
-        loop {
+    /// Blocking capture loop, meant to run on a dedicated thread
+    ///
+    /// Waits on `AcquireNextFrame` so frames are published as soon as the desktop
+    /// updates, while an idle desktop just parks the thread in the driver. Returns once
+    /// `cancel` is cancelled, within one acquire timeout or frame interval.
+    fn run_capture_loop(&mut self, cancel: &CancellationToken) -> Result<(), String> {
+        while !cancel.is_cancelled() {
+            self.apply_gpu_processing();
             let capture_start = Instant::now();
             
-            match self.duplication.capture_frame() {
+            match self.duplication.capture_frame_with_timeout(DXGI_FRAME_TIMEOUT_MS) {
                 Ok(Some(texture)) => {
                     // Use platforms-based texture processing
                     if let Ok(processed_frame) = self.texture_processor.extract_frame_data(&texture) {
@@ -192,26 +619,21 @@ impl DxgiCapture {
                             height: processed_frame.height,
                             timestamp: processed_frame.timestamp,
                             source: CaptureSource::DxgiDesktopDuplication,
+                            format: PixelFormat::Bgra,
                         };
                         
-                        let subscriber_count = self.frame_broadcast.receiver_count();
-                        self.metrics.active_subscribers.store(subscriber_count, Ordering::Relaxed);
-                        
-                        match self.frame_broadcast.send(frame_data) {
-                            Ok(_) => {
-                                self.metrics.frames_captured.fetch_add(1, Ordering::Relaxed);
-                            }
-                            Err(_) => {
-                                self.metrics.frames_dropped.fetch_add(1, Ordering::Relaxed);
-                            }
-                        }
+                        self.frames.publish(frame_data, capture_start);
+                    }
+
+                    // Cap the delivery rate at the adaptive frame rate, the next acquire
+                    // returns immediately if the desktop changed in the meantime
+                    let remaining = self.frames.metrics.frame_rate.frame_interval().saturating_sub(capture_start.elapsed());
+                    if !remaining.is_zero() {
+                        std::thread::sleep(remaining);
                     }
-                    
-                    let elapsed = capture_start.elapsed().as_millis() as u64;
-                    self.metrics.total_capture_time_ms.fetch_add(elapsed, Ordering::Relaxed);
                 }
                 Ok(None) => {
-                    // No new frame - normal for DXGI
+                    // Timed out without a desktop update - normal for DXGI
                     continue;
                 }
                 Err(DxgiError::AccessLost) => {
@@ -227,38 +649,103 @@ impl DxgiCapture {
                 }
                 Err(e) => return Err(format!("DXGI capture error: {}", e)),
             }
-            
-            // Small delay to target ~30 FPS
-            tokio::time::sleep(Duration::from_millis(33)).await;
         }
+        Ok(())
     }
 }
 
 impl GraphicsCaptureService {
     pub fn new() -> Self {
-        // Create broadcast channel with buffer for multiple subscribers
-        let (frame_broadcast, _) = broadcast::channel(100);
-        let metrics = Arc::new(CaptureMetrics::new());
+        Self::with_target_fps(CAPTURE_TARGET_FPS)
+    }
+
+    /// Capture aiming for `target_fps` frames per second instead of [`CAPTURE_TARGET_FPS`]
+    pub fn with_target_fps(target_fps: u32) -> Self {
+        let metrics = Arc::new(CaptureMetrics::with_target_fps(target_fps));
         
         Self {
-            frame_broadcast,
+            frames: FrameSink::new(metrics.clone()),
             capture_control: Arc::new(Mutex::new(None)),
             current_window: Arc::new(Mutex::new(None)),
             metrics,
             dxgi_capture: Arc::new(Mutex::new(None)),
+            gpu_processing: Arc::new(AtomicBool::new(true)),
+            #[cfg(feature = "mock-capture")]
+            mock_capture: Arc::new(Mutex::new(None)),
+            replay_capture: Arc::new(Mutex::new(None)),
+            restart_policy: Arc::new(StdMutex::new(RestartPolicy::default())),
         }
     }
 
     /// Subscribe to frame updates - each subscriber gets their own stream
-    pub fn subscribe(&self) -> broadcast::Receiver<CapturedFrame> {
-        self.frame_broadcast.subscribe()
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<CapturedFrame>> {
+        self.frames.broadcast.subscribe()
+    }
+
+    /// Subscribe to frames capped, cropped and converted as `options` says, e.g. 2 FPS crops
+    /// of the chat box for OCR from the same capture the minimap gets full rate frames from
+    ///
+    /// Shaping runs on a relay task per subscriber, ending when the receiver is dropped. A
+    /// receiver that isn't keeping up misses frames, it never gets old ones.
+    pub fn subscribe_with(&self, options: SubscribeOptions) -> mpsc::Receiver<Arc<CapturedFrame>> {
+        let mut frames = self.subscribe();
+        let (sender, receiver) = mpsc::channel(SHAPED_FRAME_CAPACITY);
+        let min_interval = options
+            .max_fps
+            .filter(|fps| *fps > 0.0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps));
+
+        tokio::spawn(async move {
+            let mut last_sent: Option<Instant> = None;
+            loop {
+                let frame = tokio::select! {
+                    _ = sender.closed() => break,
+                    frame = frames.recv() => match frame {
+                        Ok(frame) => frame,
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                };
+                if min_interval
+                    .zip(last_sent)
+                    .is_some_and(|(interval, last)| frame.timestamp.duration_since(last) < interval)
+                {
+                    continue;
+                }
+                // Only the ROI is copied, cheap enough for the relay itself
+                let Some(shaped) = options.shape(&frame) else {
+                    continue;
+                };
+                match sender.try_send(shaped) {
+                    Ok(()) => last_sent = Some(frame.timestamp),
+                    Err(mpsc::error::TrySendError::Full(_)) => continue,
+                    Err(mpsc::error::TrySendError::Closed(_)) => break,
+                }
+            }
+        });
+
+        receiver
     }
 
-    /// Start Windows Graphics Capture for specific window
+    /// Follow only the newest frame, for consumers that never need every frame
+    ///
+    /// Never lags and buffers nothing, `None` while no frame was captured since subscribing
+    /// or capture is stopped.
+    pub fn subscribe_latest(&self) -> watch::Receiver<Option<Arc<CapturedFrame>>> {
+        self.frames.latest.subscribe()
+    }
+
+    /// Start Windows Graphics Capture for the first window whose title contains `window_title`
     pub async fn start_window_capture(&self, window_title: &str) -> Result<(), String> {
-        let window = Window::from_contains_name(window_title)
-            .map_err(|_| format!("Window '{}' not found", window_title))?;
+        self.start_target_capture(CaptureTarget::Title(window_title.to_string())).await
+    }
+
+    /// Start Windows Graphics Capture for a window selected by title, process or handle
+    pub async fn start_target_capture(&self, target: CaptureTarget) -> Result<(), String> {
+        self.capture_window(target.resolve()?).await
+    }
 
+    async fn capture_window(&self, window: Window) -> Result<(), String> {
         *self.current_window.lock().await = Some(window.clone());
 
         let settings = Settings::new(
@@ -266,15 +753,21 @@ impl GraphicsCaptureService {
             CursorCaptureSettings::WithoutCursor,
             DrawBorderSettings::Default,
             SecondaryWindowSettings::Default,
-            MinimumUpdateIntervalSettings::Custom(Duration::from_millis(33)), // 30 FPS target
+            MinimumUpdateIntervalSettings::Custom(Duration::from_secs_f64(
+                1.0 / self.metrics.frame_rate.target_fps() as f64,
+            )),
             DirtyRegionSettings::Default,
             ColorFormat::Bgra8,
-            (self.frame_broadcast.clone(), self.metrics.clone()),
+            self.frames.clone(),
         );
 
         match FrameHandler::start_free_threaded(settings) {
             Ok(capture_control) => {
                 *self.capture_control.lock().await = Some(capture_control);
+                tracing::info!(source = ?CaptureSource::WindowsGraphicsCapture, "Capture started");
+                EventBus::global().publish(BotEvent::CaptureStarted {
+                    source: CaptureSource::WindowsGraphicsCapture,
+                });
                 Ok(())
             }
             Err(_) => Err("Failed to start Windows Graphics Capture".to_string()),
@@ -283,55 +776,342 @@ impl GraphicsCaptureService {
 
     /// Start DXGI Desktop Duplication for maximum performance
     pub async fn start_dxgi_capture(&self) -> Result<(), String> {
-        let dxgi = DxgiCapture::new(self.frame_broadcast.clone(), self.metrics.clone())
+        let mut dxgi_capture = self.dxgi_capture.lock().await;
+        if let Some(previous) = dxgi_capture.take() {
+            previous.stop().await;
+        }
+
+        let dxgi = DxgiCapture::new(self.frames.clone(), self.gpu_processing.clone())
             .map_err(|e| format!("Failed to create DXGI capture: {:?}", e))?;
 
-        // Store the capture instance
-        *self.dxgi_capture.lock().await = Some(dxgi);
+        // Start capture loop on a blocking thread, it waits inside AcquireNextFrame. After a
+        // failure the duplication is created again, ending once capture was stopped.
+        let cancel = CancellationToken::new();
+        let mut first = Some(dxgi);
+        let frames = self.frames.clone();
+        let gpu_processing = self.gpu_processing.clone();
+        let loop_cancel = cancel.clone();
+        let supervisor = supervise_blocking_until("dxgi_capture", self.restart_policy(), cancel.clone(), move || {
+            let dxgi = first.take();
+            let frames = frames.clone();
+            let gpu_processing = gpu_processing.clone();
+            let cancel = loop_cancel.clone();
+            let span = tracing::info_span!("capture", source = ?CaptureSource::DxgiDesktopDuplication);
+            move || {
+                let _span = span.enter();
+                tune_capture_thread();
+                let mut capture = match dxgi {
+                    Some(capture) => capture,
+                    None => DxgiCapture::new(frames, gpu_processing)?,
+                };
+                capture.run_capture_loop(&cancel)
+            }
+        });
+        *dxgi_capture = Some(DxgiControl { cancel, supervisor });
 
-        // Start capture loop in background task
-        let dxgi_ref = self.dxgi_capture.clone();
-        tokio::spawn(async move {
-            if let Some(dxgi) = dxgi_ref.lock().await.as_mut() {
-                if let Err(e) = dxgi.start_capture_loop().await {
-                    eprintln!("DXGI capture failed: {:?}", e);
+        tracing::info!(source = ?CaptureSource::DxgiDesktopDuplication, "Capture started");
+        EventBus::global().publish(BotEvent::CaptureStarted {
+            source: CaptureSource::DxgiDesktopDuplication,
+        });
+        Ok(())
+    }
+
+    /// Replay recorded frames from an image directory or video file at `fps`
+    ///
+    /// Frames go through the same broadcast as live capture, so detection can be
+    /// developed without the game running.
+    #[cfg(feature = "mock-capture")]
+    pub async fn start_mock_capture(&self, path: impl Into<std::path::PathBuf>, fps: f64) -> Result<(), String> {
+        let frames = self.frames.clone();
+
+        let control = MockCapture::new(MockSource::from_path(path), fps)
+            .start(move |frame| {
+                let capture_start = Instant::now();
+                if !frames.metrics.frame_rate.should_publish(capture_start) {
+                    return;
                 }
-            }
+
+                let captured_frame = CapturedFrame {
+                    data: frame.data,
+                    width: frame.width,
+                    height: frame.height,
+                    timestamp: capture_start,
+                    source: CaptureSource::Mock,
+                    format: PixelFormat::Bgra,
+                };
+
+                frames.publish(captured_frame, capture_start);
+            })
+            .map_err(|e| format!("Failed to start mock capture: {}", e))?;
+
+        *self.mock_capture.lock().await = Some(control);
+        tracing::info!(source = ?CaptureSource::Mock, "Capture started");
+        EventBus::global().publish(BotEvent::CaptureStarted {
+            source: CaptureSource::Mock,
         });
+        Ok(())
+    }
+
+    /// Feed the frames of a session archive through the broadcast at their recorded timing
+    ///
+    /// Every frame is published, the adaptive frame rate doesn't drop any, so detectors and
+    /// rules see the same frames in the same order on every run.
+    pub async fn start_replay(&self, source: ReplaySource) -> Result<(), String> {
+        let mut replay_capture = self.replay_capture.lock().await;
+        if let Some(previous) = replay_capture.take() {
+            let _ = previous.stop();
+        }
 
+        let frames = self.frames.clone();
+        let control = source.start(move |frame| frames.send(frame, Instant::now()))?;
+
+        *replay_capture = Some(control);
+        tracing::info!(source = ?CaptureSource::Replay, "Capture started");
+        EventBus::global().publish(BotEvent::CaptureStarted {
+            source: CaptureSource::Replay,
+        });
         Ok(())
     }
 
+    /// Capture with `backend` instead of the live backend running now, returns the one in use
+    ///
+    /// The new backend is started before the old one is stopped, so subscribers keep their
+    /// receivers and only see the [`CaptureSource`] of the frames change. If the new backend
+    /// fails to start the old one keeps running. `Auto` picks DXGI, falling back to Windows
+    /// Graphics Capture of the window captured last.
+    pub async fn switch_backend(&self, backend: CaptureBackend) -> Result<CaptureSource, String> {
+        match backend {
+            CaptureBackend::Dxgi => self.switch_to_dxgi().await,
+            CaptureBackend::WindowsGraphicsCapture => self.switch_to_window_capture().await,
+            CaptureBackend::Auto => match self.switch_to_dxgi().await {
+                Ok(source) => Ok(source),
+                Err(e) => {
+                    tracing::warn!(error = %e, "DXGI capture unavailable, using Windows Graphics Capture");
+                    self.switch_to_window_capture().await
+                }
+            },
+        }
+    }
+
+    /// Live backend running now, `None` while stopped or replaying
+    pub async fn active_backend(&self) -> Option<CaptureSource> {
+        if self.is_dxgi_running().await {
+            Some(CaptureSource::DxgiDesktopDuplication)
+        } else if self.capture_control.lock().await.is_some() {
+            Some(CaptureSource::WindowsGraphicsCapture)
+        } else {
+            None
+        }
+    }
+
+    async fn switch_to_dxgi(&self) -> Result<CaptureSource, String> {
+        if !self.is_dxgi_running().await {
+            self.start_dxgi_capture().await?;
+        }
+        self.stop_window_capture().await;
+        Ok(CaptureSource::DxgiDesktopDuplication)
+    }
+
+    async fn switch_to_window_capture(&self) -> Result<CaptureSource, String> {
+        if self.capture_control.lock().await.is_none() {
+            let window = self
+                .current_window
+                .lock()
+                .await
+                .clone()
+                .ok_or_else(|| "No window selected for Windows Graphics Capture".to_string())?;
+            self.capture_window(window).await?;
+        }
+        self.stop_dxgi_capture().await;
+        Ok(CaptureSource::WindowsGraphicsCapture)
+    }
+
+    async fn is_dxgi_running(&self) -> bool {
+        self.dxgi_capture
+            .lock()
+            .await
+            .as_ref()
+            .is_some_and(|control| !control.supervisor.is_finished())
+    }
+
+    /// Stop Windows Graphics Capture, `true` if it was running
+    async fn stop_window_capture(&self) -> bool {
+        let Some(control) = self.capture_control.lock().await.take() else {
+            return false;
+        };
+        // Stopping joins the capture thread
+        match tokio::task::spawn_blocking(move || control.stop()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::warn!(error = %e, "Window capture failed to stop cleanly"),
+            Err(e) => tracing::warn!(error = %e, "Window capture stop task failed"),
+        }
+        true
+    }
+
+    /// Stop DXGI capture and wait for the capture thread to return, `true` if it was running
+    async fn stop_dxgi_capture(&self) -> bool {
+        let Some(control) = self.dxgi_capture.lock().await.take() else {
+            return false;
+        };
+        control.stop().await;
+        true
+    }
+
     /// Stop all capture
     pub async fn stop_capture(&self) {
-        // Stop Windows Graphics Capture
-        if let Some(control) = self.capture_control.lock().await.take() {
-            let _ = control.stop();
+        let mut stopped = self.stop_window_capture().await;
+        stopped |= self.stop_dxgi_capture().await;
+
+        // Stopping joins the mock and replay threads, off the runtime threads
+        #[cfg(feature = "mock-capture")]
+        if let Some(control) = self.mock_capture.lock().await.take() {
+            match tokio::task::spawn_blocking(move || control.stop()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => tracing::warn!(error = %e, "Mock capture failed"),
+                Err(e) => tracing::warn!(error = %e, "Mock capture stop task failed"),
+            }
+            stopped = true;
+        }
+
+        if let Some(control) = self.replay_capture.lock().await.take() {
+            match tokio::task::spawn_blocking(move || control.stop()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => tracing::warn!(error = %e, "Replay failed"),
+                Err(e) => tracing::warn!(error = %e, "Replay stop task failed"),
+            }
+            stopped = true;
         }
 
-        // Stop DXGI capture
-        *self.dxgi_capture.lock().await = None;
+        self.frames.latest.send_replace(None);
+        self.frames.memory.set(0);
+        if stopped {
+            tracing::info!("Capture stopped");
+            EventBus::global().publish(BotEvent::CaptureStopped);
+        }
+    }
+
+    pub fn target_fps(&self) -> u32 {
+        self.metrics.frame_rate.target_fps()
+    }
+
+    /// Change the frame rate aimed for, Windows Graphics Capture keeps its minimum update
+    /// interval until it is restarted but frames above the rate are still dropped
+    pub fn set_target_fps(&self, target_fps: u32) {
+        self.metrics.frame_rate.set_target_fps(target_fps);
+    }
+
+    pub fn restart_policy(&self) -> RestartPolicy {
+        *self.restart_policy.lock().unwrap()
+    }
+
+    /// Policy for capture threads started from now on
+    pub fn set_restart_policy(&self, policy: RestartPolicy) {
+        *self.restart_policy.lock().unwrap() = policy;
+    }
+
+    /// Apply the capture frame rate, backend and restart policy of every config published on `configs`
+    ///
+    /// A changed backend is switched to while capturing, see [`Self::switch_backend`].
+    pub fn follow_config(&self, mut configs: watch::Receiver<BotConfig>) {
+        let service = self.clone();
+        // The frame rate was given at construction, the policy wasn't
+        let mut backend = {
+            let config = configs.borrow();
+            service.set_restart_policy(config.supervision);
+            config.capture.backend
+        };
+        tokio::spawn(async move {
+            while configs.changed().await.is_ok() {
+                let (fps, next_backend, policy) = {
+                    let config = configs.borrow_and_update();
+                    (config.capture.fps, config.capture.backend, config.supervision)
+                };
+                if fps != service.target_fps() {
+                    service.set_target_fps(fps);
+                }
+                service.set_restart_policy(policy);
+                if next_backend != backend {
+                    backend = next_backend;
+                    if service.active_backend().await.is_some() {
+                        if let Err(e) = service.switch_backend(backend).await {
+                            tracing::warn!(error = %e, ?backend, "Failed to switch capture backend");
+                        }
+                    }
+                }
+            }
+        });
     }
 
     /// Get performance metrics
-    pub fn get_metrics(&self) -> String {
-        self.metrics.get_stats()
+    pub fn get_metrics(&self) -> CaptureStats {
+        self.metrics.stats()
     }
 
     /// Check if actively capturing
     pub async fn is_capturing(&self) -> bool {
-        self.capture_control.lock().await.is_some() || 
-        self.dxgi_capture.lock().await.is_some()
+        #[cfg(feature = "mock-capture")]
+        if self.mock_capture.lock().await.is_some() {
+            return true;
+        }
+        if self.replay_capture.lock().await.as_ref().is_some_and(|control| !control.is_finished()) {
+            return true;
+        }
+
+        self.capture_control.lock().await.is_some() || self.is_dxgi_running().await
     }
     
     /// Configure GPU processing for DXGI capture
     /// Set to false to use CPU processing (more stable, slower)
     /// Set to true to use GPU processing (faster, may have compatibility issues)
     pub async fn set_gpu_processing(&self, enabled: bool) {
-        if let Some(dxgi) = self.dxgi_capture.lock().await.as_mut() {
-            dxgi.duplication.set_gpu_processing(enabled);
+        // Applied by the capture thread before its next frame
+        self.gpu_processing.store(enabled, Ordering::Relaxed);
+    }
+}
+
+/// Starting captures the window captured last, capture has to be started on a window
+/// (or another source) once before
+#[async_trait::async_trait]
+impl Service for GraphicsCaptureService {
+    async fn start(&self) -> Result<(), ServiceError> {
+        if self.is_capturing().await {
+            return Ok(());
+        }
+        let window = self
+            .current_window
+            .lock()
+            .await
+            .clone()
+            .ok_or_else(|| ServiceError::NotConfigured("no window selected to capture".to_string()))?;
+        self.capture_window(window).await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_capture().await;
+        Ok(())
+    }
+
+    async fn status(&self) -> ServiceStatus {
+        if self.is_capturing().await {
+            ServiceStatus::Running
+        } else {
+            ServiceStatus::Stopped
         }
     }
+
+    async fn health_check(&self) -> ServiceHealth {
+        if !self.is_capturing().await {
+            return ServiceHealth::Unhealthy("capture stopped".to_string());
+        }
+        let stats = self.get_metrics();
+        if stats.effective_fps < stats.target_fps {
+            return ServiceHealth::Degraded(format!(
+                "throttled to {} of {} fps, subscribers are falling behind",
+                stats.effective_fps, stats.target_fps
+            ));
+        }
+        ServiceHealth::Healthy
+    }
 }
 
 #[cfg(test)]
@@ -351,4 +1131,51 @@ mod tests {
         assert!(sub1.len() == 0);
         assert!(sub2.len() == 0);
     }
+
+    #[test]
+    fn test_diff_score() {
+        let frame = |value: u8| CapturedFrame {
+            data: vec![value; 320 * 180 * 4],
+            width: 320,
+            height: 180,
+            timestamp: Instant::now(),
+            source: CaptureSource::WindowsGraphicsCapture,
+            format: PixelFormat::Bgra,
+        };
+
+        assert_eq!(frame(10).diff_score(&frame(10)), 0.0);
+        assert!(frame(0).diff_score(&frame(255)) > 0.9);
+    }
+
+    #[test]
+    fn test_diff_score_gray() {
+        let frame = |value: u8, format: PixelFormat| CapturedFrame {
+            data: vec![value; 320 * 180 * if format == PixelFormat::Gray { 1 } else { 4 }],
+            width: 320,
+            height: 180,
+            timestamp: Instant::now(),
+            source: CaptureSource::WindowsGraphicsCapture,
+            format,
+        };
+
+        assert_eq!(frame(10, PixelFormat::Gray).diff_score(&frame(10, PixelFormat::Gray)), 0.0);
+        assert_eq!(frame(10, PixelFormat::Gray).diff_score(&frame(10, PixelFormat::Bgra)), 0.0);
+        assert!(frame(0, PixelFormat::Gray).diff_score(&frame(255, PixelFormat::Gray)) > 0.9);
+    }
+
+    #[test]
+    fn test_adaptive_frame_rate() {
+        let controller = FrameRateController::new(30);
+        let start = Instant::now();
+
+        controller.adjust(FRAME_BUFFER_CAPACITY, FRAME_BUFFER_CAPACITY, start);
+        assert_eq!(controller.effective_fps(), 15);
+
+        // Adjustments are rate limited
+        controller.adjust(FRAME_BUFFER_CAPACITY, FRAME_BUFFER_CAPACITY, start);
+        assert_eq!(controller.effective_fps(), 15);
+
+        controller.adjust(0, FRAME_BUFFER_CAPACITY, start + FPS_ADJUST_INTERVAL);
+        assert_eq!(controller.effective_fps(), 20);
+    }
 }
@@ -0,0 +1,86 @@
+//! Global hotkeys, bound in `hotkeys` of the config or the active profile
+//!
+//! The panic stop key is the kill switch of the keyboard hook, see [`crate::kill_switch`]. The
+//! other actions are published as [`BotEvent::HotkeyPressed`] for the UI or CLI to carry out.
+
+use platforms::input::{InputEvent, KeyKind, KeyState};
+use tokio::sync::{broadcast, watch};
+
+use crate::config::{BotConfig, HotkeyAction, Hotkeys};
+use super::event_bus::{BotEvent, EventBus};
+
+/// Bind the hotkeys of the current config and every one published on `configs`
+pub fn follow_config(mut configs: watch::Receiver<BotConfig>) {
+    let mut hotkeys = configs.borrow().hotkeys();
+    platforms::input::set_kill_switch_key(hotkeys.panic_stop);
+    tokio::spawn(async move {
+        let mut events = listen(&hotkeys);
+        // Holding a key down repeats the press, only the first one counts
+        let mut held = None;
+        loop {
+            let event = tokio::select! {
+                changed = configs.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    let next = configs.borrow_and_update().hotkeys();
+                    if next != hotkeys {
+                        hotkeys = next;
+                        platforms::input::set_kill_switch_key(hotkeys.panic_stop);
+                        events = listen(&hotkeys);
+                        held = None;
+                    }
+                    continue;
+                }
+                event = async {
+                    match events.as_mut() {
+                        Some(events) => events.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => event,
+            };
+            match event {
+                Ok(InputEvent::Key { key, state: KeyState::Pressed }) if held != Some(key) => {
+                    held = Some(key);
+                    match hotkeys.action(key) {
+                        // Already handled by the hook
+                        Some(HotkeyAction::PanicStop) | None => {}
+                        Some(action) => {
+                            tracing::info!(?action, ?key, "Hotkey pressed");
+                            EventBus::global().publish(BotEvent::HotkeyPressed { action });
+                        }
+                    }
+                }
+                Ok(InputEvent::Key { key, state: KeyState::Released }) if held == Some(key) => held = None,
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => events = None,
+            }
+        }
+    });
+}
+
+/// Next key pressed while any window has focus, for binding it to an action
+pub async fn next_key_press() -> Result<KeyKind, String> {
+    let mut events = platforms::input::input_event_receiver()
+        .map_err(|e| format!("Failed to listen to the keyboard: {}", e))?;
+    loop {
+        match events.recv().await {
+            Ok(InputEvent::Key { key, state: KeyState::Pressed }) => return Ok(key),
+            Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+            Err(broadcast::error::RecvError::Closed) => return Err("Keyboard hook stopped".to_string()),
+        }
+    }
+}
+
+/// Keyboard events, only listened to while an action besides the panic stop is bound
+fn listen(hotkeys: &Hotkeys) -> Option<broadcast::Receiver<InputEvent>> {
+    let bound = HotkeyAction::ALL
+        .into_iter()
+        .any(|action| action != HotkeyAction::PanicStop && hotkeys.get(action).is_some());
+    if !bound {
+        return None;
+    }
+    platforms::input::input_event_receiver()
+        .map_err(|e| tracing::warn!(error = %e, "Hotkeys are not available"))
+        .ok()
+}
@@ -0,0 +1,63 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use axum::Router;
+use tokio_util::sync::CancellationToken;
+
+/// Serves a router in the background until stopped, shared by the HTTP services
+#[derive(Clone)]
+pub(crate) struct HttpServer {
+    name: &'static str,
+    shutdown: Arc<StdMutex<Option<CancellationToken>>>,
+}
+
+impl HttpServer {
+    pub(crate) fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            shutdown: Arc::new(StdMutex::new(None)),
+        }
+    }
+
+    pub(crate) fn is_serving(&self) -> bool {
+        self.shutdown.lock().unwrap().is_some()
+    }
+
+    /// Bind `addr` and serve `router`, nothing happens if already serving
+    pub(crate) async fn start(&self, addr: SocketAddr, router: Router) -> Result<(), String> {
+        if self.is_serving() {
+            return Ok(());
+        }
+
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|e| format!("Failed to bind {} to {}: {}", self.name, addr, e))?;
+
+        let token = CancellationToken::new();
+        *self.shutdown.lock().unwrap() = Some(token.clone());
+        let shutdown = self.shutdown.clone();
+        let name = self.name;
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, router)
+                .with_graceful_shutdown(token.clone().cancelled_owned())
+                .await
+            {
+                tracing::error!(server = name, %addr, error = %e, "HTTP server failed");
+            }
+            // Clear the token unless the server was stopped, possibly followed by a new start
+            token.cancel();
+            let mut shutdown = shutdown.lock().unwrap();
+            if shutdown.as_ref().is_some_and(CancellationToken::is_cancelled) {
+                shutdown.take();
+            }
+        });
+
+        Ok(())
+    }
+
+    pub(crate) fn stop(&self) {
+        if let Some(token) = self.shutdown.lock().unwrap().take() {
+            token.cancel();
+        }
+    }
+}
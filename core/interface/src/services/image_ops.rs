@@ -0,0 +1,205 @@
+//! Pure Rust versions of the few image operations needed without OpenCV
+//!
+//! Slower than their OpenCV counterparts but keep frame history, previews, screenshots and
+//! template matching working when the crate is built without the `opencv` feature.
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::{self, FilterType};
+use image::{ExtendedColorType, GrayImage, ImageBuffer, ImageEncoder, Pixel, RgbImage};
+use imageproc::template_matching::{self, MatchTemplateMethod};
+
+use super::graphics_capture::CapturedFrame;
+use super::vision::Rect;
+
+fn frame_pixels(frame: &CapturedFrame) -> Result<&[u8], String> {
+    let size = frame.width as usize * frame.height as usize * 4;
+    if frame.data.len() < size {
+        return Err(format!("Frame data too small: {} < {}", frame.data.len(), size));
+    }
+    Ok(&frame.data[..size])
+}
+
+/// Copy a BGRA frame into an RGB image
+pub fn bgra_to_rgb(frame: &CapturedFrame) -> Result<RgbImage, String> {
+    let rgb = frame_pixels(frame)?
+        .chunks_exact(4)
+        .flat_map(|bgra| [bgra[2], bgra[1], bgra[0]])
+        .collect();
+    RgbImage::from_raw(frame.width, frame.height, rgb).ok_or_else(|| "Failed to create RGB image".to_string())
+}
+
+/// Gray value of a BGRA pixel, weighted like OpenCV's `COLOR_BGRA2GRAY`
+fn gray_value(bgra: &[u8]) -> u8 {
+    ((bgra[2] as u32 * 77 + bgra[1] as u32 * 150 + bgra[0] as u32 * 29) >> 8) as u8
+}
+
+/// Copy a BGRA frame into a grayscale image
+pub fn bgra_to_gray(frame: &CapturedFrame) -> Result<GrayImage, String> {
+    let gray = frame_pixels(frame)?.chunks_exact(4).map(gray_value).collect();
+    GrayImage::from_raw(frame.width, frame.height, gray).ok_or_else(|| "Failed to create gray image".to_string())
+}
+
+/// Copy `rect` of a BGRA frame into a grayscale image, the rest of the frame is never converted
+pub fn bgra_region_to_gray(frame: &CapturedFrame, rect: Rect) -> Result<GrayImage, String> {
+    let rect = rect
+        .clamp_to(frame.width as i32, frame.height as i32)
+        .ok_or_else(|| format!("Region {:?} is outside the frame", rect))?;
+    let pixels = frame_pixels(frame)?;
+    let stride = frame.width as usize * 4;
+    let gray = (rect.y as usize..(rect.y + rect.height) as usize)
+        .flat_map(|y| pixels[y * stride + rect.x as usize * 4..][..rect.width as usize * 4].chunks_exact(4))
+        .map(gray_value)
+        .collect();
+    GrayImage::from_raw(rect.width as u32, rect.height as u32, gray)
+        .ok_or_else(|| "Failed to create gray image".to_string())
+}
+
+/// Scale an image by `scale` with bilinear filtering
+pub fn resize<P: Pixel + 'static>(
+    image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    scale: f64,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let width = ((image.width() as f64 * scale).round() as u32).max(1);
+    let height = ((image.height() as f64 * scale).round() as u32).max(1);
+    imageops::resize(image, width, height, FilterType::Triangle)
+}
+
+/// Find the best match of `template` in `image` by normalized cross correlation (0.0 - 1.0)
+///
+/// `None` if the template is empty or larger than the image. Unlike OpenCV's
+/// `TM_CCOEFF_NORMED` the score isn't mean corrected, so flat areas score higher and
+/// thresholds need to be stricter. Brute force, keep the searched image small, e.g. with a
+/// region of interest.
+pub fn match_template(image: &GrayImage, template: &GrayImage) -> Option<(Rect, f64)> {
+    let (width, height) = template.dimensions();
+    if width == 0 || height == 0 || width > image.width() || height > image.height() {
+        return None;
+    }
+    let scores = template_matching::match_template(image, template, MatchTemplateMethod::CrossCorrelationNormalized);
+    let best = template_matching::find_extremes(&scores);
+    let (x, y) = best.max_value_location;
+    Some((Rect::new(x as i32, y as i32, width as i32, height as i32), best.max_value as f64))
+}
+
+fn encode(encoder: impl ImageEncoder, image: &RgbImage, format: &str) -> Result<(), String> {
+    encoder
+        .write_image(image.as_raw(), image.width(), image.height(), ExtendedColorType::Rgb8)
+        .map_err(|e| format!("Failed to encode {}: {}", format, e))
+}
+
+/// A frame scaled down to at most `max_width` pixels wide, as raw RGBA (see
+/// [`encode_raw_rgba`]) with `outline` in frame pixels drawn on it in `rgb`
+///
+/// Takes every nth pixel instead of filtering, cheap enough to run next to the detectors.
+pub fn frame_thumbnail(frame: &CapturedFrame, max_width: u32, outline: Option<(Rect, [u8; 3])>) -> Result<Vec<u8>, String> {
+    let pixels = frame_pixels(frame)?;
+    let step = frame.width.div_ceil(max_width.max(1)).max(1) as usize;
+    let (frame_width, frame_height) = (frame.width as usize, frame.height as usize);
+    let (width, height) = (frame_width.div_ceil(step), frame_height.div_ceil(step));
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for y in (0..frame_height).step_by(step) {
+        for x in (0..frame_width).step_by(step) {
+            let bgra = &pixels[(y * frame_width + x) * 4..][..4];
+            rgba.extend_from_slice(&[bgra[2], bgra[1], bgra[0], 255]);
+        }
+    }
+    if let Some((rect, rgb)) = outline.filter(|_| width > 0 && height > 0) {
+        let scaled = |value: i32, limit: usize| (value.max(0) as usize / step).min(limit.saturating_sub(1));
+        let (left, right) = (scaled(rect.x, width), scaled(rect.x + rect.width - 1, width));
+        let (top, bottom) = (scaled(rect.y, height), scaled(rect.y + rect.height - 1, height));
+        let mut paint = |x: usize, y: usize| rgba[(y * width + x) * 4..][..3].copy_from_slice(&rgb);
+        for x in left..=right {
+            paint(x, top);
+            paint(x, bottom);
+        }
+        for y in top..=bottom {
+            paint(left, y);
+            paint(right, y);
+        }
+    }
+    Ok(encode_raw_rgba(width as u32, height as u32, &rgba))
+}
+
+/// Decode an encoded image into BGRA pixels of `width` x `height`, scaling it if its size differs
+pub fn decode_bgra(bytes: &[u8], width: u32, height: u32) -> Result<Vec<u8>, String> {
+    let mut rgba = image::load_from_memory(bytes)
+        .map_err(|e| format!("Failed to decode image: {}", e))?
+        .into_rgba8();
+    if rgba.dimensions() != (width, height) {
+        rgba = imageops::resize(&rgba, width.max(1), height.max(1), FilterType::Triangle);
+    }
+    let mut bgra = rgba.into_raw();
+    bgra.chunks_exact_mut(4).for_each(|pixel| pixel.swap(0, 2));
+    Ok(bgra)
+}
+
+/// Encode as JPEG, `quality` is 0 - 100
+pub fn encode_jpeg(image: &RgbImage, quality: i32) -> Result<Vec<u8>, String> {
+    let mut buffer = Vec::new();
+    encode(JpegEncoder::new_with_quality(&mut buffer, quality.clamp(1, 100) as u8), image, "JPEG")?;
+    Ok(buffer)
+}
+
+/// Encode as lossless WebP, the image crate has no lossy WebP encoder
+pub fn encode_webp(image: &RgbImage) -> Result<Vec<u8>, String> {
+    let mut buffer = Vec::new();
+    encode(WebPEncoder::new_lossless(&mut buffer), image, "WebP")?;
+    Ok(buffer)
+}
+
+pub fn encode_png(image: &RgbImage) -> Result<Vec<u8>, String> {
+    let mut buffer = Vec::new();
+    encode(PngEncoder::new(&mut buffer), image, "PNG")?;
+    Ok(buffer)
+}
+
+/// Bytes in front of the pixels of a raw RGBA frame
+const RAW_RGBA_HEADER: usize = 8;
+
+/// Raw RGBA frame for previews that skip compression: width and height as little endian
+/// `u32` followed by the pixels
+pub fn encode_raw_rgba(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(RAW_RGBA_HEADER + pixels.len());
+    buffer.extend_from_slice(&width.to_le_bytes());
+    buffer.extend_from_slice(&height.to_le_bytes());
+    buffer.extend_from_slice(pixels);
+    buffer
+}
+
+/// Width, height and pixels of a frame from [`encode_raw_rgba`], `None` for encoded images
+pub fn decode_raw_rgba(bytes: &[u8]) -> Option<(u32, u32, &[u8])> {
+    if bytes.len() < RAW_RGBA_HEADER {
+        return None;
+    }
+    let (header, pixels) = bytes.split_at(RAW_RGBA_HEADER);
+    let width = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let height = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    (pixels.len() == width as usize * height as usize * 4).then_some((width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Image without repeating patches, so only the true position matches exactly
+    fn noise(width: u32, height: u32) -> GrayImage {
+        GrayImage::from_fn(width, height, |x, y| image::Luma([((x * 37 + y * 91 + x * y * 13) % 251) as u8]))
+    }
+
+    #[test]
+    fn test_match_template_finds_patch() {
+        let image = noise(48, 32);
+        let template = imageops::crop_imm(&image, 21, 9, 10, 8).to_image();
+        let (rect, score) = match_template(&image, &template).unwrap();
+        assert_eq!(rect, Rect::new(21, 9, 10, 8));
+        assert!(score > 0.999, "score {}", score);
+    }
+
+    #[test]
+    fn test_match_template_rejects_larger_template() {
+        assert!(match_template(&noise(8, 8), &noise(9, 4)).is_none());
+        assert!(match_template(&noise(8, 8), &GrayImage::new(0, 0)).is_none());
+    }
+}
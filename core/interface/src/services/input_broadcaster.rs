@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use platforms::input::{InputBackendKind, InputKind, InputProfile};
+use platforms::Window;
+use tokio::sync::Mutex;
+
+use crate::services::{Service, ServiceError};
+use super::input_scheduler::{ActionHandle, InputAction, InputPriority, InputScheduler};
+
+/// A window driven by the broadcaster
+struct BroadcastTarget {
+    window: Window,
+    /// Added to every mouse coordinate sent to this window
+    offset: (i32, i32),
+    scheduler: InputScheduler,
+}
+
+/// Mirrors every input action to several game clients (multiboxing)
+///
+/// Each window gets its own [`InputScheduler`] so a slow or unfocused client doesn't hold up
+/// the others. Mouse coordinates are shifted by a per-window offset for clients whose UI isn't
+/// laid out identically. Use [`InputBackendKind::Message`] unless the clients are arranged so
+/// [`InputKind::Foreground`] input reaches all of them.
+#[derive(Clone)]
+pub struct InputBroadcaster {
+    input_kind: InputKind,
+    backend: InputBackendKind,
+    profile: Arc<std::sync::Mutex<InputProfile>>,
+    targets: Arc<Mutex<Vec<BroadcastTarget>>>,
+    is_running: Arc<Mutex<bool>>,
+}
+
+impl InputBroadcaster {
+    pub fn new(input_kind: InputKind, backend: InputBackendKind) -> Self {
+        Self {
+            input_kind,
+            backend,
+            profile: Arc::new(std::sync::Mutex::new(InputProfile::default())),
+            targets: Arc::new(Mutex::new(Vec::new())),
+            is_running: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    pub async fn is_running(&self) -> bool {
+        *self.is_running.lock().await
+    }
+
+    pub async fn windows(&self) -> Vec<Window> {
+        self.targets.lock().await.iter().map(|target| target.window).collect()
+    }
+
+    /// Add a window to mirror input to, started right away if the broadcaster is running
+    pub async fn add_window(&self, window: Window, offset: (i32, i32)) -> Result<(), String> {
+        // Same lock order as start_broadcaster
+        let is_running = self.is_running.lock().await;
+        let mut targets = self.targets.lock().await;
+        if targets.iter().any(|target| target.window == window) {
+            return Err("Window is already a broadcast target".to_string());
+        }
+
+        let scheduler = InputScheduler::new(window, self.input_kind, self.backend);
+        let profile = *self.profile.lock().unwrap();
+        scheduler.set_profile(profile).await;
+        if *is_running {
+            scheduler.start_scheduler().await?;
+        }
+
+        targets.push(BroadcastTarget { window, offset, scheduler });
+        Ok(())
+    }
+
+    /// Change the mouse offset of an already added window
+    pub async fn set_offset(&self, window: Window, offset: (i32, i32)) -> Result<(), String> {
+        let mut targets = self.targets.lock().await;
+        let target = targets
+            .iter_mut()
+            .find(|target| target.window == window)
+            .ok_or_else(|| "Window is not a broadcast target".to_string())?;
+        target.offset = offset;
+        Ok(())
+    }
+
+    /// Stop mirroring to `window`, releasing any keys held in it
+    pub async fn remove_window(&self, window: Window) {
+        let removed = {
+            let mut targets = self.targets.lock().await;
+            targets
+                .iter()
+                .position(|target| target.window == window)
+                .map(|index| targets.remove(index))
+        };
+        if let Some(target) = removed {
+            target.scheduler.stop_scheduler().await;
+        }
+    }
+
+    /// Start the schedulers of every window
+    pub async fn start_broadcaster(&self) -> Result<(), String> {
+        let mut is_running = self.is_running.lock().await;
+        if *is_running {
+            return Ok(());
+        }
+
+        let targets = self.targets.lock().await;
+        for target in targets.iter() {
+            if let Err(e) = target.scheduler.start_scheduler().await {
+                for target in targets.iter() {
+                    target.scheduler.stop_scheduler().await;
+                }
+                return Err(e);
+            }
+        }
+
+        *is_running = true;
+        Ok(())
+    }
+
+    pub async fn stop_broadcaster(&self) {
+        let mut is_running = self.is_running.lock().await;
+        for target in self.targets.lock().await.iter() {
+            target.scheduler.stop_scheduler().await;
+        }
+        *is_running = false;
+    }
+
+    /// Switch the humanization profile of every window
+    ///
+    /// Each window applies its own jitter, so mirrored actions don't land in lockstep.
+    pub async fn set_profile(&self, profile: InputProfile) {
+        *self.profile.lock().unwrap() = profile;
+        for target in self.targets.lock().await.iter() {
+            target.scheduler.set_profile(profile).await;
+        }
+    }
+
+    /// Schedule `action` on every window, returning one handle per window in the order added
+    pub async fn broadcast(&self, action: InputAction, priority: InputPriority) -> Result<Vec<ActionHandle>, String> {
+        let targets = self.targets.lock().await;
+        if targets.is_empty() {
+            return Err("No broadcast targets".to_string());
+        }
+
+        let mut handles = Vec::with_capacity(targets.len());
+        for target in targets.iter() {
+            let action = offset_action(action.clone(), target.offset);
+            handles.push(target.scheduler.schedule(action, priority).await?);
+        }
+        Ok(handles)
+    }
+
+    /// Cancel queued and running actions on every window
+    pub async fn cancel_all(&self) {
+        for target in self.targets.lock().await.iter() {
+            target.scheduler.cancel_all().await;
+        }
+    }
+}
+
+/// Shift the mouse coordinates of `action` by `(dx, dy)`
+fn offset_action(action: InputAction, (dx, dy): (i32, i32)) -> InputAction {
+    match action {
+        InputAction::MouseMove { x, y } => InputAction::MouseMove { x: x + dx, y: y + dy },
+        InputAction::MouseClick { x, y, button } => InputAction::MouseClick { x: x + dx, y: y + dy, button },
+        InputAction::MousePath { points, step_delay } => InputAction::MousePath {
+            points: points.into_iter().map(|(x, y)| (x + dx, y + dy)).collect(),
+            step_delay,
+        },
+        InputAction::Scroll { x, y, delta } => InputAction::Scroll { x: x + dx, y: y + dy, delta },
+        action => action,
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for InputBroadcaster {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_broadcaster().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_broadcaster().await;
+        Ok(())
+    }
+}
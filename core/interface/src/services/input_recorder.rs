@@ -0,0 +1,317 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use platforms::input::{InputEvent, KeyKind, KeyState, MouseButton, ScrollDelta};
+use platforms::Window;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, Mutex};
+
+use super::input_scheduler::{ActionHandle, InputAction, InputPriority, InputScheduler};
+
+/// Mouse moves closer together than this are merged while recording
+const MOUSE_MOVE_INTERVAL: Duration = Duration::from_millis(16);
+
+/// One recorded input, mouse coordinates are client coordinates of the recorded window
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MacroAction {
+    KeyDown { key: KeyKind },
+    KeyUp { key: KeyKind },
+    MouseMove { x: i32, y: i32 },
+    MouseClick { x: i32, y: i32, button: MouseButton },
+    Scroll { x: i32, y: i32, delta: ScrollDelta },
+}
+
+impl From<MacroAction> for InputAction {
+    fn from(action: MacroAction) -> Self {
+        match action {
+            MacroAction::KeyDown { key } => InputAction::KeyDown(key),
+            MacroAction::KeyUp { key } => InputAction::KeyUp(key),
+            MacroAction::MouseMove { x, y } => InputAction::MouseMove { x, y },
+            MacroAction::MouseClick { x, y, button } => InputAction::MouseClick { x, y, button },
+            MacroAction::Scroll { x, y, delta } => InputAction::Scroll { x, y, delta },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MacroEvent {
+    /// Milliseconds since the recording started
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub action: MacroAction,
+}
+
+/// How a saved macro is played back, kept in its file
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MacroPlayback {
+    /// Playback speed multiplier, 2.0 plays twice as fast
+    pub speed: f64,
+    /// Times the macro is played in a row
+    pub repeat: u32,
+}
+
+impl Default for MacroPlayback {
+    fn default() -> Self {
+        Self { speed: 1.0, repeat: 1 }
+    }
+}
+
+/// A recorded input routine, saved as JSON
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputMacro {
+    pub name: String,
+    pub events: Vec<MacroEvent>,
+    #[serde(default)]
+    pub playback: MacroPlayback,
+}
+
+impl InputMacro {
+    /// Directory macros are saved in by name, per game profile
+    pub fn dir() -> PathBuf {
+        crate::config::profile_dir().join("macros")
+    }
+
+    pub fn path(name: &str) -> PathBuf {
+        Self::dir().join(format!("{}.json", name))
+    }
+
+    /// Names of all macros saved in [`InputMacro::dir`]
+    pub fn list() -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(Self::dir()) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+            .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string))
+            .collect()
+    }
+
+    pub fn load_named(name: &str) -> Result<Self, String> {
+        Self::load(Self::path(name))
+    }
+
+    /// Save to [`InputMacro::dir`] under the macro's name
+    pub fn save_named(&self) -> Result<(), String> {
+        if self.name.is_empty() || self.name.contains(['/', '\\', '.']) {
+            return Err(format!("Invalid macro name {:?}", self.name));
+        }
+        let dir = Self::dir();
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        self.save(Self::path(&self.name))
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.events.last().map(|event| event.at_ms).unwrap_or_default())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read macro {}: {}", path.display(), e))?;
+        serde_json::from_str(&json).map_err(|e| format!("Invalid macro {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        let path = path.as_ref();
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize macro: {}", e))?;
+        std::fs::write(path, json).map_err(|e| format!("Failed to write macro {}: {}", path.display(), e))
+    }
+}
+
+/// Records real user keyboard and mouse input over a window into an [`InputMacro`]
+#[derive(Clone)]
+pub struct InputRecorder {
+    events: Arc<StdMutex<Vec<MacroEvent>>>,
+    task: Arc<Mutex<Option<tokio::task::JoinHandle<()>>>>,
+}
+
+impl InputRecorder {
+    pub fn new() -> Self {
+        Self {
+            events: Arc::new(StdMutex::new(Vec::new())),
+            task: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub async fn is_recording(&self) -> bool {
+        self.task.lock().await.is_some()
+    }
+
+    /// Start recording, mouse coordinates are converted to client coordinates of `window`
+    pub async fn start_recording(&self, window: Window) -> Result<(), String> {
+        let mut task = self.task.lock().await;
+        if task.is_some() {
+            return Err("Already recording".to_string());
+        }
+
+        let mut receiver = platforms::input::input_event_receiver()
+            .map_err(|e| format!("Failed to subscribe to input events: {}", e))?;
+        let events = self.events.clone();
+        events.lock().unwrap().clear();
+
+        *task = Some(tokio::spawn(async move {
+            let start = Instant::now();
+            let mut held = HashSet::new();
+            let mut last_move: Option<Instant> = None;
+
+            loop {
+                let event = match receiver.recv().await {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let now = Instant::now();
+                let client = |x, y| window.screen_to_client(x, y).ok();
+
+                let action = match event {
+                    // Key repeat from holding a key down is dropped, replay holds it instead
+                    InputEvent::Key { key, state: KeyState::Pressed } => {
+                        held.insert(key).then_some(MacroAction::KeyDown { key })
+                    }
+                    InputEvent::Key { key, state: KeyState::Released } => {
+                        held.remove(&key);
+                        Some(MacroAction::KeyUp { key })
+                    }
+                    InputEvent::MouseMove { x, y } => {
+                        if last_move.is_some_and(|last| now.duration_since(last) < MOUSE_MOVE_INTERVAL) {
+                            None
+                        } else {
+                            last_move = Some(now);
+                            client(x, y).map(|(x, y)| MacroAction::MouseMove { x, y })
+                        }
+                    }
+                    InputEvent::MouseButton { x, y, button, state: KeyState::Pressed } => {
+                        client(x, y).map(|(x, y)| MacroAction::MouseClick { x, y, button })
+                    }
+                    InputEvent::MouseButton { state: KeyState::Released, .. } => None,
+                    InputEvent::Scroll { x, y, delta } => {
+                        client(x, y).map(|(x, y)| MacroAction::Scroll { x, y, delta })
+                    }
+                };
+
+                if let Some(action) = action {
+                    events.lock().unwrap().push(MacroEvent {
+                        at_ms: now.duration_since(start).as_millis() as u64,
+                        action,
+                    });
+                }
+            }
+        }));
+
+        Ok(())
+    }
+
+    /// Stop recording and return the recorded macro
+    pub async fn stop_recording(&self, name: impl Into<String>) -> InputMacro {
+        if let Some(task) = self.task.lock().await.take() {
+            task.abort();
+        }
+
+        InputMacro {
+            name: name.into(),
+            events: std::mem::take(&mut *self.events.lock().unwrap()),
+            playback: MacroPlayback::default(),
+        }
+    }
+}
+
+impl Default for InputRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Replays an [`InputMacro`] through an [`InputScheduler`]
+#[derive(Debug, Clone)]
+pub struct MacroPlayer {
+    speed: f64,
+    jitter: Duration,
+    priority: InputPriority,
+}
+
+impl MacroPlayer {
+    pub fn new() -> Self {
+        Self {
+            speed: 1.0,
+            jitter: Duration::ZERO,
+            priority: InputPriority::Normal,
+        }
+    }
+
+    /// Playback speed multiplier, 2.0 plays twice as fast
+    pub fn speed(mut self, speed: f64) -> Self {
+        self.speed = speed.max(0.01);
+        self
+    }
+
+    /// Shift each event by a random amount up to `jitter` in either direction
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn priority(mut self, priority: InputPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Schedule every event of `input_macro`, returning the handles in playback order
+    ///
+    /// Awaiting the last handle waits for the whole macro, cancelling any handle's token
+    /// leaves the others running so use [`InputScheduler::cancel_all`] to abort playback.
+    pub async fn play(&self, scheduler: &InputScheduler, input_macro: &InputMacro) -> Result<Vec<ActionHandle>, String> {
+        let start = Instant::now();
+        let jitter_ms = self.jitter.as_millis() as i64;
+
+        // Offsets are computed up front so the thread-local RNG isn't held across awaits
+        let offsets = {
+            let mut rng = rand::thread_rng();
+            let mut last_offset = Duration::ZERO;
+            input_macro
+                .events
+                .iter()
+                .map(|event| {
+                    let jitter = if jitter_ms > 0 { rng.gen_range(-jitter_ms..=jitter_ms) } else { 0 };
+                    let offset_ms = (event.at_ms as f64 / self.speed) as i64 + jitter;
+                    // Jitter must never reorder events, a key up can't overtake its key down
+                    last_offset = Duration::from_millis(offset_ms.max(0) as u64).max(last_offset);
+                    last_offset
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut handles = Vec::with_capacity(input_macro.events.len());
+        for (event, offset) in input_macro.events.iter().zip(offsets) {
+            handles.push(scheduler.schedule_at(event.action.into(), self.priority, start + offset).await?);
+        }
+
+        Ok(handles)
+    }
+
+    /// Play `input_macro` `times` times in a row, each pass once the previous one ended
+    ///
+    /// Stops early when an action fails or is cancelled, e.g. by [`InputScheduler::cancel_all`].
+    pub async fn play_repeated(&self, scheduler: &InputScheduler, input_macro: &InputMacro, times: u32) -> Result<(), String> {
+        for _ in 0..times {
+            let handles = self.play(scheduler, input_macro).await?;
+            if let Some(last) = handles.into_iter().last() {
+                last.wait().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for MacroPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,504 @@
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use platforms::input::{
+    Input, InputBackendKind, InputKind, InputProfile, KeyKind, MouseButton, MouseKind, ScrollDelta,
+};
+use platforms::Window;
+use tokio::sync::{broadcast, oneshot, watch, Mutex};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::BotConfig;
+use crate::services::{Service, ServiceError};
+use super::event_bus::{BotEvent, EventBus};
+
+/// Longest the worker sleeps before checking for cancellation while idle or holding a key
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Sent to every worker by [`release_all_keys`], dropped by each once its keys are released
+type ReleaseAck = tokio::sync::mpsc::UnboundedSender<()>;
+
+fn release_signal() -> &'static broadcast::Sender<ReleaseAck> {
+    static SIGNAL: OnceLock<broadcast::Sender<ReleaseAck>> = OnceLock::new();
+    SIGNAL.get_or_init(|| broadcast::channel(4).0)
+}
+
+/// Cancel the actions of every running scheduler and release the keys they hold, e.g. on exit
+///
+/// Works like the kill switch without firing it, so nothing listening for the kill switch
+/// reacts. Returns once every worker released its keys; one busy with an action that can't
+/// be interrupted answers when it's done, so callers may want a timeout.
+pub async fn release_all_keys() {
+    let (ack, mut released) = tokio::sync::mpsc::unbounded_channel::<()>();
+    // Without workers the ack is dropped right away
+    let _ = release_signal().send(ack);
+    while released.recv().await.is_some() {}
+}
+
+/// A timed input action executed by the scheduler
+#[derive(Debug, Clone)]
+pub enum InputAction {
+    KeyTap(KeyKind),
+    KeyDown(KeyKind),
+    KeyUp(KeyKind),
+    /// Hold a key down for the given duration
+    KeyHold(KeyKind, Duration),
+    /// Press keys in order and release them in reverse, e.g. `[Ctrl, Shift, F1]`
+    KeyCombo(Vec<KeyKind>),
+    MouseMove { x: i32, y: i32 },
+    MouseClick { x: i32, y: i32, button: MouseButton },
+    /// Move through client coordinates, waiting `step_delay` between points
+    MousePath { points: Vec<(i32, i32)>, step_delay: Duration },
+    Scroll { x: i32, y: i32, delta: ScrollDelta },
+    /// Occupy the scheduler without sending input, e.g. to space out a sequence
+    Wait(Duration),
+}
+
+/// Due actions with a higher priority run first, actions never preempt a running one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InputPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Critical,
+}
+
+/// Handle to a scheduled action
+#[derive(Debug)]
+pub struct ActionHandle {
+    id: u64,
+    token: CancellationToken,
+    result: oneshot::Receiver<Result<(), String>>,
+}
+
+impl ActionHandle {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Cancel the action, a running hold or mouse path stops at the next step
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Wait until the action finished, failed or was cancelled
+    pub async fn wait(self) -> Result<(), String> {
+        self.result
+            .await
+            .unwrap_or_else(|_| Err("Input scheduler stopped".to_string()))
+    }
+}
+
+struct ScheduledAction {
+    action: InputAction,
+    priority: InputPriority,
+    due: Instant,
+    seq: u64,
+    token: CancellationToken,
+    result: oneshot::Sender<Result<(), String>>,
+}
+
+impl PartialEq for ScheduledAction {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for ScheduledAction {}
+
+impl PartialOrd for ScheduledAction {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScheduledAction {
+    // Max-heap order: earliest due first, then highest priority, then first enqueued
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        other
+            .due
+            .cmp(&self.due)
+            .then(self.priority.cmp(&other.priority))
+            .then(other.seq.cmp(&self.seq))
+    }
+}
+
+enum Command {
+    Schedule(ScheduledAction),
+    SetProfile(InputProfile),
+    CancelAll,
+    Shutdown,
+}
+
+/// Serializes input from all services onto one dedicated thread owning the [`Input`]
+///
+/// Callers enqueue actions with a priority and an optional delay and get an [`ActionHandle`]
+/// to cancel or await them. The kill switch cancels everything and releases held keys.
+#[derive(Clone)]
+pub struct InputScheduler {
+    window: Window,
+    input_kind: InputKind,
+    backend: InputBackendKind,
+    profile: Arc<std::sync::Mutex<InputProfile>>,
+    sender: Arc<Mutex<Option<mpsc::Sender<Command>>>>,
+    worker: Arc<Mutex<Option<JoinHandle<()>>>>,
+    next_id: Arc<AtomicU64>,
+    /// Cancelled by `cancel_all`, parent of every action token
+    generation: Arc<std::sync::Mutex<CancellationToken>>,
+}
+
+impl InputScheduler {
+    pub fn new(window: Window, input_kind: InputKind, backend: InputBackendKind) -> Self {
+        Self {
+            window,
+            input_kind,
+            backend,
+            profile: Arc::new(std::sync::Mutex::new(InputProfile::default())),
+            sender: Arc::new(Mutex::new(None)),
+            worker: Arc::new(Mutex::new(None)),
+            next_id: Arc::new(AtomicU64::new(0)),
+            generation: Arc::new(std::sync::Mutex::new(CancellationToken::new())),
+        }
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.sender.lock().await.is_some()
+    }
+
+    pub fn profile(&self) -> InputProfile {
+        *self.profile.lock().unwrap()
+    }
+
+    /// Switch the humanization profile, applies from the next action if already running
+    pub async fn set_profile(&self, profile: InputProfile) {
+        *self.profile.lock().unwrap() = profile;
+        if let Some(sender) = self.sender.lock().await.as_ref() {
+            let _ = sender.send(Command::SetProfile(profile));
+        }
+    }
+
+    /// Use the humanization in `input` of the current config and every one published on `configs`
+    pub fn follow_config(&self, mut configs: watch::Receiver<BotConfig>) {
+        let scheduler = self.clone();
+        tokio::spawn(async move {
+            let mut profile = configs.borrow_and_update().input;
+            scheduler.set_profile(profile).await;
+            while configs.changed().await.is_ok() {
+                let next = configs.borrow_and_update().input;
+                if next != profile {
+                    profile = next;
+                    scheduler.set_profile(profile).await;
+                }
+            }
+        });
+    }
+
+    /// Spawn the worker thread
+    pub async fn start_scheduler(&self) -> Result<(), String> {
+        let mut sender = self.sender.lock().await;
+        if sender.is_some() {
+            return Ok(());
+        }
+
+        let (tx, rx) = mpsc::channel();
+        let (ready_tx, ready_rx) = oneshot::channel();
+        let kill_switch = platforms::input::kill_switch_receiver().ok();
+        let release = release_signal().subscribe();
+        let (window, input_kind, backend) = (self.window, self.input_kind, self.backend);
+        let profile = self.profile();
+        let generation = self.generation.clone();
+
+        let worker = thread::Builder::new()
+            .name("input-scheduler".to_string())
+            .spawn(move || {
+                let mut input = match Input::with_backend(window, input_kind, backend) {
+                    Ok(input) => {
+                        let _ = ready_tx.send(Ok(()));
+                        input
+                    }
+                    Err(e) => {
+                        let _ = ready_tx.send(Err(format!("Failed to create input: {}", e)));
+                        return;
+                    }
+                };
+                input.set_profile(profile);
+                Worker {
+                    input,
+                    rx,
+                    kill_switch,
+                    release,
+                    acks: Vec::new(),
+                    generation,
+                    queue: BinaryHeap::new(),
+                }
+                .run();
+            })
+            .map_err(|e| format!("Failed to spawn input scheduler: {}", e))?;
+
+        ready_rx
+            .await
+            .map_err(|_| "Input scheduler exited during startup".to_string())??;
+
+        *sender = Some(tx);
+        *self.worker.lock().await = Some(worker);
+        Ok(())
+    }
+
+    /// Stop the worker, cancelling queued actions and releasing held keys
+    pub async fn stop_scheduler(&self) {
+        if let Some(sender) = self.sender.lock().await.take() {
+            let _ = sender.send(Command::Shutdown);
+        }
+        if let Some(worker) = self.worker.lock().await.take() {
+            let _ = tokio::task::spawn_blocking(move || worker.join()).await;
+        }
+    }
+
+    /// Enqueue `action` to run as soon as possible
+    pub async fn schedule(&self, action: InputAction, priority: InputPriority) -> Result<ActionHandle, String> {
+        self.schedule_after(action, priority, Duration::ZERO).await
+    }
+
+    /// Enqueue `action` to run no earlier than `delay` from now
+    pub async fn schedule_after(
+        &self,
+        action: InputAction,
+        priority: InputPriority,
+        delay: Duration,
+    ) -> Result<ActionHandle, String> {
+        self.schedule_at(action, priority, Instant::now() + delay).await
+    }
+
+    /// Enqueue `action` to run no earlier than `due`
+    pub async fn schedule_at(
+        &self,
+        action: InputAction,
+        priority: InputPriority,
+        due: Instant,
+    ) -> Result<ActionHandle, String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let token = self.generation.lock().unwrap().child_token();
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let scheduled = ScheduledAction {
+            action,
+            priority,
+            due,
+            seq: id,
+            token: token.clone(),
+            result: result_tx,
+        };
+
+        self.sender
+            .lock()
+            .await
+            .as_ref()
+            .ok_or_else(|| "Input scheduler is not running".to_string())?
+            .send(Command::Schedule(scheduled))
+            .map_err(|_| "Input scheduler stopped".to_string())?;
+
+        Ok(ActionHandle { id, token, result: result_rx })
+    }
+
+    /// Cancel every queued and running action
+    pub async fn cancel_all(&self) {
+        {
+            let mut generation = self.generation.lock().unwrap();
+            generation.cancel();
+            *generation = CancellationToken::new();
+        }
+        if let Some(sender) = self.sender.lock().await.as_ref() {
+            let _ = sender.send(Command::CancelAll);
+        }
+    }
+}
+
+struct Worker {
+    input: Input,
+    rx: mpsc::Receiver<Command>,
+    kill_switch: Option<broadcast::Receiver<()>>,
+    release: broadcast::Receiver<ReleaseAck>,
+    /// Answered by the next `kill`
+    acks: Vec<ReleaseAck>,
+    generation: Arc<std::sync::Mutex<CancellationToken>>,
+    queue: BinaryHeap<ScheduledAction>,
+}
+
+impl Worker {
+    fn run(mut self) {
+        loop {
+            let timeout = self
+                .queue
+                .peek()
+                .map(|next| next.due.saturating_duration_since(Instant::now()))
+                .unwrap_or(POLL_INTERVAL)
+                .min(POLL_INTERVAL);
+
+            match self.rx.recv_timeout(timeout) {
+                Ok(Command::Schedule(action)) => self.queue.push(action),
+                Ok(Command::SetProfile(profile)) => self.input.set_profile(profile),
+                Ok(Command::CancelAll) => self.cancel_queued(),
+                Ok(Command::Shutdown) | Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+
+            if self.kill_requested() {
+                self.kill();
+                continue;
+            }
+
+            let Some(next) = self.queue.peek() else {
+                continue;
+            };
+            if next.due > Instant::now() {
+                continue;
+            }
+
+            // Pick the highest priority among all due actions
+            let now = Instant::now();
+            let mut due = Vec::new();
+            while self.queue.peek().is_some_and(|action| action.due <= now) {
+                due.extend(self.queue.pop());
+            }
+            due.sort_by(|a, b| b.priority.cmp(&a.priority).then(a.seq.cmp(&b.seq)));
+            let mut due = due.into_iter();
+            let scheduled = due.next().expect("at least one due action");
+            self.queue.extend(due);
+
+            let result = if scheduled.token.is_cancelled() {
+                Err("Cancelled".to_string())
+            } else {
+                self.execute(&scheduled.action, &scheduled.token)
+            };
+            if result.is_ok() && !matches!(scheduled.action, InputAction::Wait(_)) {
+                EventBus::global().publish(BotEvent::InputSent {
+                    action: format!("{:?}", scheduled.action),
+                });
+            }
+            let _ = scheduled.result.send(result);
+        }
+
+        self.cancel_queued();
+        let _ = self.input.release_all();
+    }
+
+    /// Whether the kill switch fired or [`release_all_keys`] was called since the last check
+    fn kill_requested(&mut self) -> bool {
+        let mut requested = false;
+        if let Some(kill_switch) = self.kill_switch.as_mut() {
+            loop {
+                match kill_switch.try_recv() {
+                    Ok(()) | Err(broadcast::error::TryRecvError::Lagged(_)) => requested = true,
+                    Err(_) => break,
+                }
+            }
+        }
+        loop {
+            match self.release.try_recv() {
+                Ok(ack) => {
+                    self.acks.push(ack);
+                    requested = true;
+                }
+                Err(broadcast::error::TryRecvError::Lagged(_)) => requested = true,
+                Err(_) => break,
+            }
+        }
+        requested
+    }
+
+    fn kill(&mut self) {
+        {
+            let mut generation = self.generation.lock().unwrap();
+            generation.cancel();
+            *generation = CancellationToken::new();
+        }
+        self.cancel_queued();
+        let _ = self.input.release_all();
+        for ack in self.acks.drain(..) {
+            let _ = ack.send(());
+        }
+    }
+
+    fn cancel_queued(&mut self) {
+        for action in self.queue.drain() {
+            let _ = action.result.send(Err("Cancelled".to_string()));
+        }
+    }
+
+    /// Sleep for `duration`, returning false if cancelled first
+    fn wait(&mut self, duration: Duration, token: &CancellationToken) -> bool {
+        let deadline = Instant::now() + duration;
+        loop {
+            if token.is_cancelled() {
+                return false;
+            }
+            if self.kill_requested() {
+                self.kill();
+                return false;
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return true;
+            }
+            thread::sleep(remaining.min(POLL_INTERVAL));
+        }
+    }
+
+    fn execute(&mut self, action: &InputAction, token: &CancellationToken) -> Result<(), String> {
+        let map_err = |e: platforms::Error| e.to_string();
+
+        match action {
+            InputAction::KeyTap(key) => self.input.send_key(*key).map_err(map_err),
+            InputAction::KeyDown(key) => self.input.send_key_down(*key).map_err(map_err),
+            InputAction::KeyUp(key) => self.input.send_key_up(*key).map_err(map_err),
+            InputAction::KeyHold(key, duration) => {
+                self.input.send_key_down(*key).map_err(map_err)?;
+                let completed = self.wait(*duration, token);
+                self.input.send_key_up(*key).map_err(map_err)?;
+                if completed { Ok(()) } else { Err("Cancelled".to_string()) }
+            }
+            InputAction::KeyCombo(keys) => self.input.send_key_combo(keys).map_err(map_err),
+            InputAction::MouseMove { x, y } => self.input.send_mouse(*x, *y, MouseKind::Move).map_err(map_err),
+            InputAction::MouseClick { x, y, button } => {
+                self.input.send_mouse(*x, *y, MouseKind::Click(*button)).map_err(map_err)
+            }
+            InputAction::MousePath { points, step_delay } => {
+                for (i, (x, y)) in points.iter().enumerate() {
+                    if i > 0 && !self.wait(*step_delay, token) {
+                        return Err("Cancelled".to_string());
+                    }
+                    self.input.send_mouse(*x, *y, MouseKind::Move).map_err(map_err)?;
+                }
+                Ok(())
+            }
+            InputAction::Scroll { x, y, delta } => {
+                self.input.send_mouse(*x, *y, MouseKind::Scroll(*delta)).map_err(map_err)
+            }
+            InputAction::Wait(duration) => {
+                if self.wait(*duration, token) { Ok(()) } else { Err("Cancelled".to_string()) }
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for InputScheduler {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_scheduler().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_scheduler().await;
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+//! Low-power background mode, for running the bot next to other work on the same machine
+//!
+//! While enabled capture drops to [`LOW_POWER_FPS`], the minimap pipeline skips its encode
+//! stages so no previews are produced, and the detectors no automation depends on (OCR and
+//! with it the chat monitor, motion, object detection and the dataset recorder) skip every
+//! frame. The minimap, game state, templates, probes and the automation engine keep running
+//! on the frames still captured.
+
+use std::sync::OnceLock;
+
+use platforms::input::{InputEvent, KeyState};
+use tokio::sync::{broadcast, watch};
+
+use crate::config::BotConfig;
+use super::event_bus::{BotEvent, EventBus};
+
+/// Frame rate capture drops to while low-power mode is on
+pub const LOW_POWER_FPS: u32 = 2;
+
+/// Whether the bot runs in low-power mode, toggled through the control API or a hotkey
+pub struct LowPowerMode {
+    enabled: watch::Sender<bool>,
+}
+
+impl LowPowerMode {
+    fn new() -> Self {
+        Self {
+            enabled: watch::channel(false).0,
+        }
+    }
+
+    /// Mode every service checks
+    pub fn global() -> &'static LowPowerMode {
+        static GLOBAL: OnceLock<LowPowerMode> = OnceLock::new();
+        GLOBAL.get_or_init(LowPowerMode::new)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        *self.enabled.borrow()
+    }
+
+    /// Publishes [`BotEvent::LowPowerChanged`] if the mode changes
+    pub fn set_enabled(&self, enabled: bool) {
+        if self.enabled.send_replace(enabled) != enabled {
+            Self::announce(enabled);
+        }
+    }
+
+    /// Switch the mode, returns whether it is on now
+    pub fn toggle(&self) -> bool {
+        let mut enabled = false;
+        self.enabled.send_modify(|mode| {
+            *mode = !*mode;
+            enabled = *mode;
+        });
+        Self::announce(enabled);
+        enabled
+    }
+
+    /// Current mode, marked changed on every switch
+    pub fn subscribe(&self) -> watch::Receiver<bool> {
+        self.enabled.subscribe()
+    }
+
+    /// Toggle the mode with `performance.low_power_key` of the current config and every one
+    /// published on `configs`
+    pub fn follow_config(&'static self, mut configs: watch::Receiver<BotConfig>) {
+        let mut key = configs.borrow().performance.low_power_key;
+        tokio::spawn(async move {
+            // Only listen to the keyboard while there is a hotkey
+            let mut events = key.and_then(|_| hotkey_events());
+            // Holding the key down repeats the press, only the first one toggles
+            let mut held = false;
+            loop {
+                let event = tokio::select! {
+                    changed = configs.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                        let next = configs.borrow_and_update().performance.low_power_key;
+                        if next != key {
+                            key = next;
+                            events = key.and_then(|_| hotkey_events());
+                            held = false;
+                        }
+                        continue;
+                    }
+                    event = async {
+                        match events.as_mut() {
+                            Some(events) => events.recv().await,
+                            None => std::future::pending().await,
+                        }
+                    } => event,
+                };
+                match event {
+                    Ok(InputEvent::Key { key: pressed, state }) if Some(pressed) == key => match state {
+                        KeyState::Pressed if !held => {
+                            held = true;
+                            self.toggle();
+                        }
+                        KeyState::Pressed => {}
+                        KeyState::Released => held = false,
+                    },
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => events = None,
+                }
+            }
+        });
+    }
+
+    fn announce(enabled: bool) {
+        tracing::info!(enabled, fps = LOW_POWER_FPS, "Low-power mode switched");
+        EventBus::global().publish(BotEvent::LowPowerChanged { enabled });
+    }
+}
+
+fn hotkey_events() -> Option<broadcast::Receiver<InputEvent>> {
+    platforms::input::input_event_receiver()
+        .map_err(|e| tracing::warn!(error = %e, "Low-power hotkey is not available"))
+        .ok()
+}
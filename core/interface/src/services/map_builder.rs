@@ -0,0 +1,393 @@
+use std::path::PathBuf;
+
+use opencv::{
+    core::{Mat, Point, Point2f, Scalar, Size, Vector, BORDER_CONSTANT, CV_32F, CV_8UC1, CV_8UC4},
+    imgcodecs::{imencode, imread, IMREAD_UNCHANGED},
+    imgproc::{
+        circle, create_hanning_window, cvt_color_def, get_rotation_matrix_2d, phase_correlate, resize, warp_affine,
+        COLOR_BGRA2BGR, COLOR_BGR2BGRA, FILLED, INTER_AREA, INTER_LINEAR, LINE_8,
+    },
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use super::player_arrow::PlayerPosition;
+use super::scale_search::ScaleSearch;
+use super::vision::{crop, to_gray, Rect};
+
+/// Room added around the canvas whenever it has to grow, so it doesn't grow on every frame
+const GROW_MARGIN: i32 = 256;
+
+/// The canvas stops growing at this side length, a drifting track would otherwise eat memory
+const MAX_CANVAS_SIDE: i32 = 8192;
+
+/// How the explored map is stitched together
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapBuilderConfig {
+    /// File name of the map under the maps directory, without extension
+    pub name: String,
+    /// The minimap turns with the player so the heading always points up, crops are rotated
+    /// back to north up before stitching
+    pub rotating_minimap: bool,
+    /// Only stitch the circle inscribed in the minimap rect
+    pub circular_minimap: bool,
+    /// Pixels cut from each side of a crop, drops borders and frame decorations
+    pub inset: i32,
+    /// Radius around the player marker left out of the map so the arrow isn't stamped along
+    /// the route
+    pub player_mask_radius: i32,
+    /// Minimum phase correlation response for a shift between two crops to be trusted
+    pub min_response: f64,
+    /// Shifts longer than this many pixels between two crops are treated as a lost track
+    pub max_step: f64,
+    /// Minimum template match score to find a crop on the existing map after the track was
+    /// lost or a saved map was loaded
+    pub relocate_threshold: f64,
+}
+
+impl Default for MapBuilderConfig {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            rotating_minimap: false,
+            circular_minimap: true,
+            inset: 4,
+            player_mask_radius: 8,
+            min_response: 0.2,
+            max_step: 48.0,
+            relocate_threshold: 0.7,
+        }
+    }
+}
+
+/// Stitches minimap crops into a growing map of everything explored so far
+///
+/// Consecutive crops are aligned by phase correlation, the player position within the crop
+/// places the player on the map. When tracking is lost the next crop is searched on the map
+/// built so far, the same happens for the first crop after loading a saved map.
+///
+/// The canvas is BGRA with alpha 0 for unexplored pixels. Map coordinates are canvas pixels,
+/// internally crops are tracked in world coordinates that don't move when the canvas grows.
+pub struct MapBuilder {
+    config: MapBuilderConfig,
+    canvas: Mat,
+    /// World position of the canvas' top left pixel
+    origin: (i32, i32),
+    /// World position of the top left of the last stitched crop
+    position: Option<(f64, f64)>,
+    /// Last stitched crop as float grayscale, what the next crop is correlated with
+    previous: Option<Mat>,
+    /// Hanning window matching the size of `previous`
+    window: Mat,
+    /// Player position in world coordinates
+    player: Option<(f32, f32)>,
+}
+
+impl MapBuilder {
+    pub fn new(config: MapBuilderConfig) -> Self {
+        Self {
+            config,
+            canvas: Mat::default(),
+            origin: (0, 0),
+            position: None,
+            previous: None,
+            window: Mat::default(),
+            player: None,
+        }
+    }
+
+    /// Continue the map saved under `config.name`, or start a new one if there is none
+    pub fn load(config: MapBuilderConfig) -> Result<Self, String> {
+        let path = Self::path(&config.name);
+        let mut builder = Self::new(config);
+        if path.exists() {
+            let canvas = imread(&path.to_string_lossy(), IMREAD_UNCHANGED)
+                .map_err(|e| format!("Failed to load map {}: {}", path.display(), e))?;
+            if canvas.typ() != CV_8UC4 {
+                return Err(format!("Map {} has no alpha channel", path.display()));
+            }
+            builder.canvas = canvas;
+        }
+        Ok(builder)
+    }
+
+    /// Directory maps are saved to
+    pub fn dir() -> PathBuf {
+        crate::config_dir().join("maps")
+    }
+
+    pub fn path(name: &str) -> PathBuf {
+        Self::dir().join(format!("{}.png", name))
+    }
+
+    pub fn config(&self) -> &MapBuilderConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: MapBuilderConfig) {
+        self.config = config;
+        self.lose_track();
+    }
+
+    /// Size of the map in pixels, (0, 0) before anything was stitched
+    pub fn size(&self) -> (i32, i32) {
+        (self.canvas.cols(), self.canvas.rows())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.canvas.empty()
+    }
+
+    /// Player position on the map in pixels, `None` while tracking is lost
+    pub fn player_position(&self) -> Option<(f32, f32)> {
+        let (x, y) = self.player?;
+        Some((x - self.origin.0 as f32, y - self.origin.1 as f32))
+    }
+
+    /// Forget where the last crop was, the next one is searched on the map
+    pub fn lose_track(&mut self) {
+        self.position = None;
+        self.previous = None;
+        self.player = None;
+    }
+
+    /// Throw the map away and start over
+    pub fn clear(&mut self) {
+        self.canvas = Mat::default();
+        self.origin = (0, 0);
+        self.lose_track();
+    }
+
+    /// Stitch a BGRA minimap crop into the map
+    ///
+    /// `player` is the player position found on the same crop. Returns the player position on
+    /// the map, `None` if the crop couldn't be placed.
+    pub fn add(&mut self, minimap: &Mat, player: Option<&PlayerPosition>) -> Result<Option<(f32, f32)>, String> {
+        let size = minimap.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
+        let mask = self.mask(size, player)?;
+
+        // Turn rotating minimaps back to north up, without a heading there is no way to tell
+        let (image, mask, player) = match (self.config.rotating_minimap, player) {
+            (false, _) => {
+                let image = minimap.try_clone().map_err(|e| format!("Failed to copy minimap: {}", e))?;
+                (image, mask, player.map(|player| (player.x, player.y)))
+            }
+            (true, Some(PlayerPosition { x, y, heading_degrees: Some(degrees) })) => {
+                let rotation = get_rotation_matrix_2d(
+                    Point2f::new(size.width as f32 / 2.0, size.height as f32 / 2.0),
+                    -*degrees as f64,
+                    1.0,
+                )
+                .map_err(|e| format!("Failed to create rotation: {}", e))?;
+                let at = |row: i32, col: i32| rotation.at_2d::<f64>(row, col).map(|value| *value as f32);
+                let transform = |x: f32, y: f32| -> opencv::Result<(f32, f32)> {
+                    Ok((
+                        at(0, 0)? * x + at(0, 1)? * y + at(0, 2)?,
+                        at(1, 0)? * x + at(1, 1)? * y + at(1, 2)?,
+                    ))
+                };
+                let player = transform(*x, *y).map_err(|e| format!("Failed to rotate player: {}", e))?;
+                (rotate(minimap, &rotation, size)?, rotate(&mask, &rotation, size)?, Some(player))
+            }
+            (true, _) => {
+                self.lose_track();
+                return Ok(None);
+            }
+        };
+
+        let inset = self.config.inset.max(0);
+        let tile_rect = Rect::new(inset, inset, size.width - 2 * inset, size.height - 2 * inset);
+        if tile_rect.width <= 0 || tile_rect.height <= 0 {
+            return Ok(None);
+        }
+        let tile = crop(&image, tile_rect)?;
+        let mask = crop(&mask, tile_rect)?;
+        let gray = to_gray(&tile)?;
+        let mut gray_float = Mat::default();
+        gray.convert_to(&mut gray_float, CV_32F, 1.0, 0.0)
+            .map_err(|e| format!("Failed to convert minimap: {}", e))?;
+
+        let Some(position) = self.place(&gray, &gray_float)? else {
+            self.lose_track();
+            return Ok(None);
+        };
+        self.paste(&tile, &mask, position)?;
+
+        self.position = Some(position);
+        self.previous = Some(gray_float);
+        self.player = player.map(|(x, y)| {
+            (
+                position.0 as f32 + x - inset as f32,
+                position.1 as f32 + y - inset as f32,
+            )
+        });
+        Ok(self.player_position())
+    }
+
+    /// Write the map to [`Self::path`]
+    pub fn save(&self) -> Result<PathBuf, String> {
+        if self.is_empty() {
+            return Err("Map is empty".to_string());
+        }
+        let path = Self::path(&self.config.name);
+        std::fs::create_dir_all(Self::dir())
+            .map_err(|e| format!("Failed to create {}: {}", Self::dir().display(), e))?;
+        let png = encode_png(&self.canvas)?;
+        std::fs::write(&path, png).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+
+    /// PNG of the map scaled down to at most `max_width` pixels, for the UI
+    pub fn preview(&self, max_width: i32) -> Result<Vec<u8>, String> {
+        if self.is_empty() {
+            return Err("Map is empty".to_string());
+        }
+        let width = self.canvas.cols();
+        if width <= max_width {
+            return encode_png(&self.canvas);
+        }
+        let scale = max_width as f64 / width as f64;
+        let mut scaled = Mat::default();
+        resize(&self.canvas, &mut scaled, Size::default(), scale, scale, INTER_AREA)
+            .map_err(|e| format!("Failed to scale map: {}", e))?;
+        encode_png(&scaled)
+    }
+
+    /// Pixels of a crop that belong on the map
+    fn mask(&self, size: Size, player: Option<&PlayerPosition>) -> Result<Mat, String> {
+        let draw = |mask: &mut Mat, center: Point, radius: i32, value: f64| {
+            circle(mask, center, radius, Scalar::all(value), FILLED, LINE_8, 0)
+                .map_err(|e| format!("Failed to draw mask: {}", e))
+        };
+
+        let mut mask = if self.config.circular_minimap {
+            let mut mask = Mat::zeros(size.height, size.width, CV_8UC1)
+                .and_then(|mask| mask.to_mat())
+                .map_err(|e| format!("Failed to create mask: {}", e))?;
+            let center = Point::new(size.width / 2, size.height / 2);
+            draw(&mut mask, center, size.width.min(size.height) / 2, 255.0)?;
+            mask
+        } else {
+            Mat::new_size_with_default(size, CV_8UC1, Scalar::all(255.0))
+                .map_err(|e| format!("Failed to create mask: {}", e))?
+        };
+        if let Some(player) = player {
+            let center = Point::new(player.x.round() as i32, player.y.round() as i32);
+            draw(&mut mask, center, self.config.player_mask_radius, 0.0)?;
+        }
+        Ok(mask)
+    }
+
+    /// World position of a crop, from its shift against the previous crop or by searching the
+    /// map, `None` if it can't be placed
+    fn place(&mut self, gray: &Mat, gray_float: &Mat) -> Result<Option<(f64, f64)>, String> {
+        if let (Some(position), Some(previous)) = (self.position, &self.previous) {
+            let size = gray_float.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
+            let previous_size = previous.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
+            if size == previous_size {
+                if self.window.size().map_or(true, |window| window != size) {
+                    create_hanning_window(&mut self.window, size, CV_32F)
+                        .map_err(|e| format!("Failed to create window: {}", e))?;
+                }
+                let mut response = 0.0;
+                let shift = phase_correlate(previous, gray_float, &self.window, &mut response)
+                    .map_err(|e| format!("Phase correlation failed: {}", e))?;
+                if response >= self.config.min_response && shift.x.hypot(shift.y) <= self.config.max_step {
+                    // The map content moved by `shift` so the view moved the other way
+                    return Ok(Some((position.0 - shift.x, position.1 - shift.y)));
+                }
+            }
+        }
+
+        if self.is_empty() {
+            return Ok(Some((0.0, 0.0)));
+        }
+        let canvas_gray = to_gray(&self.canvas)?;
+        let found = ScaleSearch::match_at(&canvas_gray, gray, 1.0)?
+            .filter(|found| found.score >= self.config.relocate_threshold);
+        Ok(found.map(|found| {
+            (
+                (found.rect.x + self.origin.0) as f64,
+                (found.rect.y + self.origin.1) as f64,
+            )
+        }))
+    }
+
+    /// Copy the masked pixels of `tile` onto the canvas at world `position`
+    fn paste(&mut self, tile: &Mat, mask: &Mat, position: (f64, f64)) -> Result<(), String> {
+        let rect = Rect::new(
+            position.0.round() as i32,
+            position.1.round() as i32,
+            tile.cols(),
+            tile.rows(),
+        );
+        self.grow_to(rect)?;
+
+        let mut bgr = Mat::default();
+        cvt_color_def(tile, &mut bgr, COLOR_BGRA2BGR).map_err(|e| format!("Failed to convert tile: {}", e))?;
+        let mut opaque = Mat::default();
+        cvt_color_def(&bgr, &mut opaque, COLOR_BGR2BGRA).map_err(|e| format!("Failed to convert tile: {}", e))?;
+
+        let target = Rect::new(rect.x - self.origin.0, rect.y - self.origin.1, rect.width, rect.height);
+        let mut roi = Mat::roi_mut(&mut self.canvas, target.into())
+            .map_err(|e| format!("Failed to access map region {:?}: {}", target, e))?;
+        opaque
+            .copy_to_masked(&mut *roi, mask)
+            .map_err(|e| format!("Failed to stitch minimap: {}", e))
+    }
+
+    /// Grow the canvas until it covers the world `rect`
+    fn grow_to(&mut self, rect: Rect) -> Result<(), String> {
+        if self.is_empty() {
+            self.origin = (rect.x, rect.y);
+        }
+        let (width, height) = self.size();
+        let (x, y) = (rect.x - self.origin.0, rect.y - self.origin.1);
+        let grow = |before: i32, after: i32| -> (i32, i32) {
+            (
+                if before < 0 { GROW_MARGIN - before } else { 0 },
+                if after > 0 { GROW_MARGIN + after } else { 0 },
+            )
+        };
+        let (left, right) = grow(x, x + rect.width - width);
+        let (top, bottom) = grow(y, y + rect.height - height);
+        if left == 0 && right == 0 && top == 0 && bottom == 0 {
+            return Ok(());
+        }
+
+        let (new_width, new_height) = (width + left + right, height + top + bottom);
+        if new_width > MAX_CANVAS_SIDE || new_height > MAX_CANVAS_SIDE {
+            return Err(format!(
+                "Map would grow to {}x{}, more than {} pixels on a side",
+                new_width, new_height, MAX_CANVAS_SIDE
+            ));
+        }
+        let mut canvas = Mat::zeros(new_height, new_width, CV_8UC4)
+            .and_then(|canvas| canvas.to_mat())
+            .map_err(|e| format!("Failed to create map: {}", e))?;
+        if !self.is_empty() {
+            let mut roi = Mat::roi_mut(&mut canvas, Rect::new(left, top, width, height).into())
+                .map_err(|e| format!("Failed to access map region: {}", e))?;
+            self.canvas
+                .copy_to(&mut *roi)
+                .map_err(|e| format!("Failed to grow map: {}", e))?;
+        }
+        self.canvas = canvas;
+        self.origin = (self.origin.0 - left, self.origin.1 - top);
+        Ok(())
+    }
+}
+
+fn rotate(image: &Mat, rotation: &Mat, size: Size) -> Result<Mat, String> {
+    let mut rotated = Mat::default();
+    warp_affine(image, &mut rotated, rotation, size, INTER_LINEAR, BORDER_CONSTANT, Scalar::default())
+        .map_err(|e| format!("Failed to rotate minimap: {}", e))?;
+    Ok(rotated)
+}
+
+fn encode_png(image: &Mat) -> Result<Vec<u8>, String> {
+    let mut buffer = Vector::<u8>::new();
+    imencode(".png", image, &mut buffer, &Vector::new()).map_err(|e| format!("Failed to encode map: {}", e))?;
+    Ok(buffer.to_vec())
+}
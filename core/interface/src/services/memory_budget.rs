@@ -0,0 +1,194 @@
+//! Accounting of the memory held by queued frames
+//!
+//! A 100 frame broadcast of 4K frames alone can hold ~800 MB when a subscriber stalls, so
+//! everything queueing frames reports what it holds to one [`MemoryBudget`]. Once the total
+//! is over the limit new frames are dropped at the capture and recorders drop their oldest.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+
+use tokio::sync::watch;
+
+use crate::config::BotConfig;
+use super::event_bus::{BotEvent, EventBus};
+
+/// Limit on the bytes held by frame queues, shared by all of them
+pub struct MemoryBudget {
+    /// 0 is unlimited
+    limit: AtomicU64,
+    /// Weak so accounts whose queues are gone stop counting right away
+    accounts: Mutex<Vec<(&'static str, Weak<AtomicU64>)>>,
+    exceeded: AtomicBool,
+}
+
+impl MemoryBudget {
+    fn new() -> Self {
+        Self {
+            limit: AtomicU64::new(0),
+            accounts: Mutex::new(Vec::new()),
+            exceeded: AtomicBool::new(false),
+        }
+    }
+
+    /// Budget every frame queue reports to
+    pub fn global() -> &'static MemoryBudget {
+        static GLOBAL: OnceLock<MemoryBudget> = OnceLock::new();
+        GLOBAL.get_or_init(MemoryBudget::new)
+    }
+
+    /// Limit in bytes, `None` while unlimited
+    pub fn limit(&self) -> Option<u64> {
+        Some(self.limit.load(Ordering::Relaxed)).filter(|&limit| limit > 0)
+    }
+
+    pub fn set_limit(&self, limit: Option<u64>) {
+        self.limit.store(limit.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Apply `performance.frame_memory_mb` of the current config and every one published on `configs`
+    pub fn follow_config(&'static self, mut configs: watch::Receiver<BotConfig>) {
+        let megabytes = |config: &BotConfig| Some(config.performance.frame_memory_mb * 1024 * 1024).filter(|&bytes| bytes > 0);
+        self.set_limit(megabytes(&configs.borrow()));
+        tokio::spawn(async move {
+            while configs.changed().await.is_ok() {
+                let limit = megabytes(&configs.borrow_and_update());
+                self.set_limit(limit);
+            }
+        });
+    }
+
+    /// Bytes held by each open account, closed ones are forgotten
+    fn balances(&self) -> Vec<(&'static str, u64)> {
+        let mut balances = Vec::new();
+        self.accounts.lock().unwrap().retain(|(name, bytes)| match bytes.upgrade() {
+            Some(bytes) => {
+                balances.push((*name, bytes.load(Ordering::Relaxed)));
+                true
+            }
+            None => false,
+        });
+        balances
+    }
+
+    /// Bytes held by all accounts
+    pub fn used(&self) -> u64 {
+        self.balances().into_iter().map(|(_, bytes)| bytes).sum()
+    }
+
+    /// Bytes held per account, accounts of the same name added up
+    pub fn usage(&self) -> Vec<(&'static str, u64)> {
+        let mut usage: Vec<(&'static str, u64)> = Vec::new();
+        for (name, bytes) in self.balances() {
+            match usage.iter_mut().find(|(other, _)| *other == name) {
+                Some((_, total)) => *total += bytes,
+                None => usage.push((name, bytes)),
+            }
+        }
+        usage
+    }
+
+    /// Whether the accounts hold more than the limit
+    pub fn is_exceeded(&self) -> bool {
+        self.limit().is_some_and(|limit| self.used() > limit)
+    }
+
+    /// Account for one frame queue, closed when the last clone is dropped
+    pub fn account(&'static self, name: &'static str) -> MemoryAccount {
+        let bytes = Arc::new(AtomicU64::new(0));
+        let mut accounts = self.accounts.lock().unwrap();
+        // Forget accounts whose queues are gone
+        accounts.retain(|(_, bytes)| bytes.strong_count() > 0);
+        accounts.push((name, Arc::downgrade(&bytes)));
+        MemoryAccount {
+            name,
+            bytes,
+            budget: self,
+        }
+    }
+
+    /// Whether `bytes` more fit, [`BotEvent::MemoryBudgetExceeded`] is published the first
+    /// time they don't
+    fn fits(&self, bytes: u64, name: &'static str) -> bool {
+        let Some(limit) = self.limit() else {
+            return true;
+        };
+        let used = self.used();
+        if used + bytes <= limit {
+            self.exceeded.store(false, Ordering::Relaxed);
+            return true;
+        }
+        if !self.exceeded.swap(true, Ordering::Relaxed) {
+            tracing::warn!(used_bytes = used, limit_bytes = limit, account = name, "Frame memory budget exceeded, dropping frames");
+            EventBus::global().publish(BotEvent::MemoryBudgetExceeded {
+                used_bytes: used,
+                limit_bytes: limit,
+            });
+        }
+        false
+    }
+}
+
+/// Bytes held by one frame queue
+#[derive(Clone)]
+pub struct MemoryAccount {
+    name: &'static str,
+    bytes: Arc<AtomicU64>,
+    budget: &'static MemoryBudget,
+}
+
+impl MemoryAccount {
+    pub fn held(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, bytes: u64) {
+        self.bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn sub(&self, bytes: u64) {
+        let _ = self
+            .bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |held| Some(held.saturating_sub(bytes)));
+    }
+
+    /// Whether holding `bytes` more stays within the budget
+    pub fn fits(&self, bytes: u64) -> bool {
+        self.budget.fits(bytes, self.name)
+    }
+
+    /// Whether the budget is exceeded, queues drop their oldest frames while it is
+    pub fn is_exceeded(&self) -> bool {
+        self.budget.is_exceeded()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dropped_accounts_stop_counting() {
+        let budget: &'static MemoryBudget = Box::leak(Box::new(MemoryBudget::new()));
+        budget.set_limit(Some(100));
+        let kept = budget.account("kept");
+        kept.set(40);
+        let dropped = budget.account("dropped");
+        dropped.set(80);
+        let clone = dropped.clone();
+
+        drop(dropped);
+        assert_eq!(budget.used(), 120);
+        assert!(budget.is_exceeded());
+
+        // No new account is needed for the bytes of the closed one to be released
+        drop(clone);
+        assert_eq!(budget.used(), 40);
+        assert!(!budget.is_exceeded());
+        assert_eq!(budget.usage(), vec![("kept", 40)]);
+        assert!(kept.fits(60));
+    }
+}
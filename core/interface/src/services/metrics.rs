@@ -0,0 +1,133 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Number of most recent samples kept for percentile calculation
+const LATENCY_WINDOW: usize = 512;
+
+/// Weight of the newest frame interval in the frame rate average
+const FRAME_RATE_SMOOTHING: f64 = 0.1;
+
+/// Latency percentiles over the most recent samples, in milliseconds
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct LatencyPercentiles {
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+}
+
+impl fmt::Display for LatencyPercentiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "p50 {:.1}ms, p95 {:.1}ms, p99 {:.1}ms",
+            self.p50_ms, self.p95_ms, self.p99_ms
+        )
+    }
+}
+
+/// Sliding window of latency samples
+#[derive(Debug)]
+pub struct LatencyTracker {
+    samples: Mutex<VecDeque<Duration>>,
+}
+
+impl LatencyTracker {
+    pub fn new() -> Self {
+        Self {
+            samples: Mutex::new(VecDeque::with_capacity(LATENCY_WINDOW)),
+        }
+    }
+
+    pub fn record(&self, latency: Duration) {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == LATENCY_WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back(latency);
+    }
+
+    pub fn percentiles(&self) -> LatencyPercentiles {
+        let mut sorted: Vec<Duration> = self.samples.lock().unwrap().iter().copied().collect();
+        if sorted.is_empty() {
+            return LatencyPercentiles::default();
+        }
+        sorted.sort_unstable();
+
+        let at = |p: f64| {
+            let index = ((sorted.len() - 1) as f64 * p).round() as usize;
+            sorted[index].as_secs_f64() * 1000.0
+        };
+
+        LatencyPercentiles {
+            p50_ms: at(0.50),
+            p95_ms: at(0.95),
+            p99_ms: at(0.99),
+        }
+    }
+
+    pub fn reset(&self) {
+        self.samples.lock().unwrap().clear();
+    }
+}
+
+impl Default for LatencyTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Frame rate from the time between frames, as an exponential moving average
+#[derive(Debug, Default)]
+pub struct FrameRateMeter {
+    state: Mutex<FrameRateState>,
+}
+
+#[derive(Debug, Default)]
+struct FrameRateState {
+    last_frame: Option<Instant>,
+    /// Average seconds between frames
+    interval: Option<f64>,
+}
+
+impl FrameRateMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(last) = state.last_frame {
+            let interval = now.saturating_duration_since(last).as_secs_f64();
+            state.interval = Some(match state.interval {
+                Some(average) => average + FRAME_RATE_SMOOTHING * (interval - average),
+                None => interval,
+            });
+        }
+        state.last_frame = Some(now);
+    }
+
+    /// Frames per second, falling towards zero once frames stop arriving
+    pub fn fps(&self, now: Instant) -> f64 {
+        let state = self.state.lock().unwrap();
+        match (state.interval, state.last_frame) {
+            (Some(interval), Some(last)) => {
+                let interval = interval.max(now.saturating_duration_since(last).as_secs_f64());
+                if interval > 0.0 { 1.0 / interval } else { 0.0 }
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Time since the last frame, `None` before the first one
+    pub fn since_last_frame(&self, now: Instant) -> Option<Duration> {
+        self.state.lock().unwrap().last_frame.map(|last| now.saturating_duration_since(last))
+    }
+
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = FrameRateState::default();
+    }
+}
@@ -0,0 +1,119 @@
+#[cfg(feature = "opencv")]
+use opencv::{
+    core::{Mat, Point, Vector},
+    imgproc::{contour_area_def, find_contours_def, moments_def, CHAIN_APPROX_SIMPLE, RETR_EXTERNAL},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use super::vision::HsvRange;
+
+/// Kind of entity a minimap dot stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlipClass {
+    Enemy,
+    Npc,
+    Ally,
+}
+
+/// Pixels within `color` forming a blob of `min_area..=max_area` minimap pixels are a `class` blip
+///
+/// A class can have several rules, e.g. red wraps around the hue range and needs two.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BlipRule {
+    pub class: BlipClass,
+    pub color: HsvRange,
+    pub min_area: f64,
+    pub max_area: f64,
+}
+
+impl BlipRule {
+    pub const fn new(class: BlipClass, color: HsvRange) -> Self {
+        Self {
+            class,
+            color,
+            min_area: 2.0,
+            max_area: 150.0,
+        }
+    }
+}
+
+/// A dot found on the minimap, centered at `x`, `y` minimap pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MinimapBlip {
+    pub class: BlipClass,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Finds colored dots on a minimap by HSV segmentation
+#[cfg(feature = "opencv")]
+#[derive(Debug, Clone)]
+pub struct BlipDetector {
+    rules: Vec<BlipRule>,
+}
+
+#[cfg(feature = "opencv")]
+impl BlipDetector {
+    pub fn new(rules: Vec<BlipRule>) -> Self {
+        Self { rules }
+    }
+
+    pub fn rules(&self) -> &[BlipRule] {
+        &self.rules
+    }
+
+    pub fn set_rules(&mut self, rules: Vec<BlipRule>) {
+        self.rules = rules;
+    }
+
+    /// Detect blips of every class in an HSV minimap image
+    pub fn detect(&self, hsv: &Mat) -> Result<Vec<MinimapBlip>, String> {
+        let mut blips = Vec::new();
+
+        for rule in &self.rules {
+            let mask = rule.color.mask(hsv)?;
+            let mut contours = Vector::<Vector<Point>>::new();
+            find_contours_def(&mask, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE)
+                .map_err(|e| format!("Failed to find contours: {}", e))?;
+
+            for contour in contours {
+                let area = contour_area_def(&contour).map_err(|e| format!("Failed to measure contour: {}", e))?;
+                // Single pixel blips have no contour area, fall back to their point count
+                let area = area.max(contour.len() as f64);
+                if area < rule.min_area || area > rule.max_area {
+                    continue;
+                }
+
+                let moments = moments_def(&contour).map_err(|e| format!("Failed to compute moments: {}", e))?;
+                let (x, y) = if moments.m00 > 0.0 {
+                    ((moments.m10 / moments.m00) as f32, (moments.m01 / moments.m00) as f32)
+                } else {
+                    let count = contour.len().max(1) as f32;
+                    let (sum_x, sum_y) = contour
+                        .iter()
+                        .fold((0.0, 0.0), |(x, y), point| (x + point.x as f32, y + point.y as f32));
+                    (sum_x / count, sum_y / count)
+                };
+
+                blips.push(MinimapBlip { class: rule.class, x, y });
+            }
+        }
+
+        Ok(blips)
+    }
+}
+
+#[cfg(feature = "opencv")]
+impl Default for BlipDetector {
+    /// Red enemies, green NPCs and blue allies
+    fn default() -> Self {
+        Self::new(vec![
+            BlipRule::new(BlipClass::Enemy, HsvRange::new([0, 150, 150], [8, 255, 255])),
+            BlipRule::new(BlipClass::Enemy, HsvRange::new([172, 150, 150], [179, 255, 255])),
+            BlipRule::new(BlipClass::Npc, HsvRange::new([45, 150, 150], [75, 255, 255])),
+            BlipRule::new(BlipClass::Ally, HsvRange::new([100, 150, 150], [125, 255, 255])),
+        ])
+    }
+}
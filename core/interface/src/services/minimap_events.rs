@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+
+use super::minimap_blips::MinimapBlip;
+use super::player_arrow::PlayerPosition;
+use super::vision::Rect;
+
+/// Player moves smaller than this many minimap pixels are not reported
+const MIN_PLAYER_MOVE: f32 = 0.5;
+/// Heading changes smaller than this many degrees are not reported
+const MIN_HEADING_CHANGE: f32 = 2.0;
+
+/// Detection changes published by the minimap service
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MinimapEvent {
+    /// The minimap was found or moved, `rect` is in frame (or ROI) pixels
+    MinimapFound { rect: Rect },
+    MinimapLost,
+    /// The player marker moved or turned, in minimap pixels
+    PlayerMoved { position: PlayerPosition },
+    /// The player marker is no longer visible
+    PlayerLost,
+    /// The set of blips changed, in minimap pixels
+    BlipsUpdated { blips: Vec<MinimapBlip> },
+}
+
+/// Turns per-frame detections into change events
+#[derive(Debug, Default)]
+pub struct MinimapEventTracker {
+    minimap: Option<Rect>,
+    player: Option<PlayerPosition>,
+    blips: Vec<MinimapBlip>,
+}
+
+impl MinimapEventTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forget everything so the next detections are reported again
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn update_minimap(&mut self, rect: Option<Rect>) -> Option<MinimapEvent> {
+        if self.minimap == rect {
+            return None;
+        }
+        self.minimap = rect;
+
+        match rect {
+            Some(rect) => Some(MinimapEvent::MinimapFound { rect }),
+            None => {
+                // Nothing on a lost minimap can be seen anymore
+                self.player = None;
+                self.blips.clear();
+                Some(MinimapEvent::MinimapLost)
+            }
+        }
+    }
+
+    pub fn update_player(&mut self, position: Option<PlayerPosition>) -> Option<MinimapEvent> {
+        let event = match (self.player, position) {
+            (None, None) => None,
+            (Some(_), None) => Some(MinimapEvent::PlayerLost),
+            (Some(last), Some(position)) if !Self::player_changed(&last, &position) => return None,
+            (_, Some(position)) => Some(MinimapEvent::PlayerMoved { position }),
+        };
+        self.player = position;
+        event
+    }
+
+    pub fn update_blips(&mut self, blips: &[MinimapBlip]) -> Option<MinimapEvent> {
+        if self.blips == blips {
+            return None;
+        }
+        self.blips = blips.to_vec();
+        Some(MinimapEvent::BlipsUpdated { blips: self.blips.clone() })
+    }
+
+    fn player_changed(last: &PlayerPosition, position: &PlayerPosition) -> bool {
+        let moved = (position.x - last.x).hypot(position.y - last.y) >= MIN_PLAYER_MOVE;
+        let turned = match (last.heading_degrees, position.heading_degrees) {
+            (Some(last), Some(heading)) => {
+                let diff = (heading - last).rem_euclid(360.0);
+                diff.min(360.0 - diff) >= MIN_HEADING_CHANGE
+            }
+            (last, heading) => last.is_some() != heading.is_some(),
+        };
+        moved || turned
+    }
+}
@@ -0,0 +1,233 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "opencv")]
+use opencv::{
+    core::{Mat, Point, Vector},
+    imgproc::{approx_poly_dp, arc_length, bounding_rect, canny, find_contours_def, CHAIN_APPROX_SIMPLE, RETR_EXTERNAL},
+    prelude::*,
+};
+
+use super::scale_search::{gray_size, load_gray, GrayFrame, ScaleSearch, DEFAULT_REFERENCE_HEIGHT};
+#[cfg(feature = "opencv")]
+use super::vision::to_gray;
+use super::vision::Rect;
+
+/// How long a located minimap is trusted before it is detected again
+pub const DEFAULT_REVALIDATE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Minimum normalized correlation for a template match to count as the minimap
+pub const DEFAULT_MATCH_THRESHOLD: f64 = 0.7;
+
+/// Template scales tried relative to the resolution estimate, covers 50% - 150% UI scaling
+const TEMPLATE_SCALES: [f64; 11] = [0.5, 0.6, 0.7, 0.8, 0.9, 1.0, 1.1, 1.2, 1.3, 1.4, 1.5];
+
+/// Minimap border candidates must cover this fraction of the frame area
+const MIN_AREA_FRACTION: f64 = 0.005;
+const MAX_AREA_FRACTION: f64 = 0.25;
+
+/// Accepted width / height ratio of a minimap border
+const MIN_ASPECT: f64 = 0.5;
+const MAX_ASPECT: f64 = 4.0;
+
+/// Finds the minimap's bounding rect within a frame
+///
+/// With a template (e.g. a screenshot of the minimap frame or its title bar) the frame is
+/// searched with multi-scale template matching around the scale expected for the capture
+/// resolution, trying the last winning scale first. Without one the minimap is taken to be the
+/// largest rectangular border near a corner of the frame, which needs OpenCV. A found rect is
+/// cached and only detected again after the revalidation interval.
+#[derive(Debug)]
+pub struct MinimapLocator {
+    template: Option<GrayFrame>,
+    scale_search: ScaleSearch,
+    match_threshold: f64,
+    revalidate_interval: Duration,
+    cached: Option<Rect>,
+    validated_at: Option<Instant>,
+}
+
+impl MinimapLocator {
+    pub fn new() -> Self {
+        Self {
+            template: None,
+            scale_search: ScaleSearch::new(DEFAULT_REFERENCE_HEIGHT, TEMPLATE_SCALES.to_vec()),
+            match_threshold: DEFAULT_MATCH_THRESHOLD,
+            revalidate_interval: DEFAULT_REVALIDATE_INTERVAL,
+            cached: None,
+            validated_at: None,
+        }
+    }
+
+    /// Use a template image loaded from `path` instead of border detection
+    pub fn with_template_file(mut self, path: impl AsRef<Path>) -> Result<Self, String> {
+        self.set_template_file(path)?;
+        Ok(self)
+    }
+
+    pub fn set_template_file(&mut self, path: impl AsRef<Path>) -> Result<(), String> {
+        self.set_template(Some(load_gray(path.as_ref())?));
+        Ok(())
+    }
+
+    /// Set a grayscale template, `None` falls back to border detection
+    pub fn set_template(&mut self, template: Option<GrayFrame>) {
+        self.template = template;
+        self.scale_search.reset();
+        self.invalidate();
+    }
+
+    /// Height of the game window the template was cut from, 1080 by default
+    pub fn set_reference_height(&mut self, height: u32) {
+        self.scale_search = ScaleSearch::new(height, TEMPLATE_SCALES.to_vec());
+        self.invalidate();
+    }
+
+    pub fn set_match_threshold(&mut self, threshold: f64) {
+        self.match_threshold = threshold.clamp(0.0, 1.0);
+    }
+
+    pub fn set_revalidate_interval(&mut self, interval: Duration) {
+        self.revalidate_interval = interval;
+    }
+
+    /// Last located rect, if any
+    pub fn cached(&self) -> Option<Rect> {
+        self.cached
+    }
+
+    /// Forget the cached rect so the next frame is searched again
+    pub fn invalidate(&mut self) {
+        self.cached = None;
+        self.validated_at = None;
+    }
+
+    /// Locate the minimap in a BGRA or grayscale frame, using the cached rect while it is fresh
+    ///
+    /// `frame_height` is the height of the whole captured frame in pixels of `frame`, which
+    /// can be just a region of it, and decides the scale the template is searched at.
+    #[cfg(feature = "opencv")]
+    pub fn locate(&mut self, frame: &Mat, frame_height: i32) -> Result<Option<Rect>, String> {
+        if let Some(cached) = self.fresh() {
+            return Ok(Some(cached));
+        }
+        if frame.channels() == 1 {
+            self.search(frame, frame_height)
+        } else {
+            self.search(&to_gray(frame)?, frame_height)
+        }
+    }
+
+    /// Locate the minimap in a grayscale frame, using the cached rect while it is fresh
+    #[cfg(not(feature = "opencv"))]
+    pub fn locate(&mut self, frame: &GrayFrame, frame_height: i32) -> Result<Option<Rect>, String> {
+        if let Some(cached) = self.fresh() {
+            return Ok(Some(cached));
+        }
+        self.search(frame, frame_height)
+    }
+
+    /// Cached rect if it was validated within the revalidation interval
+    fn fresh(&self) -> Option<Rect> {
+        let is_fresh = self
+            .validated_at
+            .is_some_and(|validated_at| validated_at.elapsed() < self.revalidate_interval);
+        self.cached.filter(|_| is_fresh)
+    }
+
+    fn search(&mut self, gray: &GrayFrame, frame_height: i32) -> Result<Option<Rect>, String> {
+        let now = Instant::now();
+        let found = match &self.template {
+            Some(template) => self
+                .scale_search
+                .find(gray, template, frame_height, self.match_threshold)?
+                .filter(|found| found.score >= self.match_threshold)
+                .map(|found| found.rect),
+            None => Self::detect_border(gray)?,
+        };
+
+        // A frame whose size changed can't reuse the old rect
+        let (width, height) = gray_size(gray)?;
+        self.cached = found.and_then(|rect| rect.clamp_to(width, height));
+        self.validated_at = Some(now);
+        Ok(self.cached)
+    }
+
+    /// Border detection needs OpenCV's contours, without it only templates locate the minimap
+    #[cfg(not(feature = "opencv"))]
+    fn detect_border(_gray: &GrayFrame) -> Result<Option<Rect>, String> {
+        Ok(None)
+    }
+
+    #[cfg(feature = "opencv")]
+    fn detect_border(gray: &Mat) -> Result<Option<Rect>, String> {
+        let size = gray.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
+        let frame_area = size.width as f64 * size.height as f64;
+        let diagonal = (size.width as f64).hypot(size.height as f64);
+        if frame_area <= 0.0 {
+            return Ok(None);
+        }
+
+        let mut edges = Mat::default();
+        canny(gray, &mut edges, 50.0, 150.0, 3, false).map_err(|e| format!("Edge detection failed: {}", e))?;
+
+        let mut contours = Vector::<Vector<Point>>::new();
+        find_contours_def(&edges, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE)
+            .map_err(|e| format!("Failed to find contours: {}", e))?;
+
+        let corners = [
+            (0.0, 0.0),
+            (size.width as f64, 0.0),
+            (0.0, size.height as f64),
+            (size.width as f64, size.height as f64),
+        ];
+
+        let mut best: Option<(f64, Rect)> = None;
+        for contour in contours.iter() {
+            let perimeter = arc_length(&contour, true).map_err(|e| format!("Failed to measure contour: {}", e))?;
+            let mut approx = Vector::<Point>::new();
+            approx_poly_dp(&contour, &mut approx, perimeter * 0.02, true)
+                .map_err(|e| format!("Failed to simplify contour: {}", e))?;
+            if approx.len() != 4 {
+                continue;
+            }
+
+            let rect: Rect = bounding_rect(&approx)
+                .map_err(|e| format!("Failed to get bounding rect: {}", e))?
+                .into();
+            let area_fraction = rect.area() as f64 / frame_area;
+            let aspect = rect.width as f64 / rect.height.max(1) as f64;
+            if !(MIN_AREA_FRACTION..=MAX_AREA_FRACTION).contains(&area_fraction)
+                || !(MIN_ASPECT..=MAX_ASPECT).contains(&aspect)
+            {
+                continue;
+            }
+
+            // Minimaps sit in a corner of the screen, prefer large borders close to one
+            let rect_corners = [
+                (rect.x as f64, rect.y as f64),
+                ((rect.x + rect.width) as f64, rect.y as f64),
+                (rect.x as f64, (rect.y + rect.height) as f64),
+                ((rect.x + rect.width) as f64, (rect.y + rect.height) as f64),
+            ];
+            let corner_distance = corners
+                .iter()
+                .zip(rect_corners)
+                .map(|(frame, rect)| (frame.0 - rect.0).hypot(frame.1 - rect.1))
+                .fold(f64::MAX, f64::min);
+            let score = area_fraction * (1.0 - corner_distance / diagonal);
+
+            if best.map_or(true, |(best_score, _)| score > best_score) {
+                best = Some((score, rect));
+            }
+        }
+
+        Ok(best.map(|(_, rect)| rect))
+    }
+}
+
+impl Default for MinimapLocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,19 +1,64 @@
-use std::sync::Arc;
-use std::time::Instant;
-use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 
+use serde::{Deserialize, Serialize};
 use tokio::sync::{Mutex, watch, broadcast};
-use opencv::{
-    core::{Mat, MatTraitConst, CV_8UC4},
-    imgcodecs::{imencode, IMWRITE_WEBP_QUALITY},
-    core::Vector,
-    prelude::*,
+use tracing::Instrument;
+
+use crate::config::{BotConfig, CaptureBackend};
+use crate::services::{Service, ServiceError, ServiceHealth, ServiceStatus};
+use super::event_bus::{BotEvent, EventBus};
+use super::graphics_capture::{GraphicsCaptureService, CapturedFrame, CaptureStats, CaptureTarget};
+use super::image_ops;
+use super::low_power::LowPowerMode;
+use super::map_builder::{MapBuilder, MapBuilderConfig};
+use super::metrics::{FrameRateMeter, LatencyPercentiles, LatencyTracker};
+use super::frame_diff::{FrameDiff, DEFAULT_CHANGE_THRESHOLD};
+use super::minimap_locator::MinimapLocator;
+use super::minimap_blips::{BlipClass, BlipDetector, BlipRule, MinimapBlip};
+use super::minimap_events::{MinimapEvent, MinimapEventTracker};
+use super::pipeline::{
+    Annotation, EncodeFormat, FrameContext, Pipeline, PipelineBuilder, PipelineConfig, PipelineStage, Preprocess,
+    StageConfig, StageStats,
 };
+use super::player_arrow::{PlayerArrowConfig, PlayerArrowDetector, PlayerPosition};
+use super::probes::ProbeService;
+use super::cpu_pool::spawn_cpu;
+use super::supervisor::supervise;
+use super::vision::{MaxSize, Rect};
+
+/// Preview frames are scaled down to fit the size the UI shows them at
+const DEFAULT_PREVIEW_SIZE: MaxSize = MaxSize::new(400, 225);
+
+/// Width full frame previews are scaled down to
+const FRAME_PREVIEW_WIDTH: u32 = 400;
+
+/// Full frames are previewed at most this often, they are only for lining up the ROI
+const FRAME_PREVIEW_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often [`MinimapService::subscribe_performance`] publishes while capturing
+const PERFORMANCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Capacity of the detection channels, slow subscribers skip old detections
+const DETECTION_CHANNEL_CAPACITY: usize = 32;
 
-use crate::services::Service;
-use super::graphics_capture::{GraphicsCaptureService, CapturedFrame};
+/// Overlay colors, RGB
+const OVERLAY_MINIMAP_COLOR: [u8; 3] = [0, 255, 0];
+const OVERLAY_PLAYER_COLOR: [u8; 3] = [255, 255, 0];
+const OVERLAY_PROBE_ACTIVE_COLOR: [u8; 3] = [0, 255, 255];
+const OVERLAY_PROBE_INACTIVE_COLOR: [u8; 3] = [128, 128, 128];
 
-#[derive(Debug, Clone, PartialEq)]
+/// Side of the box drawn around a blip, in minimap pixels
+const OVERLAY_BLIP_SIZE: i32 = 8;
+
+/// Lifecycle of the minimap capture, `Stopped -> Starting -> Running -> Stopping -> Stopped`
+///
+/// A start can fail (`Starting -> Stopped`) or be cancelled by a stop (`Starting -> Stopping`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ServiceState {
     Stopped,
     Starting,
@@ -21,14 +66,37 @@ pub enum ServiceState {
     Stopping,
 }
 
+impl ServiceState {
+    pub fn can_transition_to(self, next: ServiceState) -> bool {
+        use ServiceState::*;
+        matches!(
+            (self, next),
+            (Stopped, Starting)
+                | (Starting, Running)
+                | (Starting, Stopping)
+                | (Starting, Stopped)
+                | (Running, Stopping)
+                | (Stopping, Stopped)
+        )
+    }
+}
+
 #[derive(Debug)]
 pub struct MinimapMetrics {
     pub frames_processed: AtomicUsize,
     pub frames_dropped: AtomicUsize,
+    pub frames_unchanged: AtomicUsize,
     pub opencv_detections: AtomicUsize,
     pub total_processing_time_ms: AtomicU64,
     pub total_opencv_time_ms: AtomicU64,
     pub total_encode_time_ms: AtomicU64,
+    pub latency: LatencyTracker,
+    /// From capturing a frame to handing it on processed
+    pub end_to_end_latency: LatencyTracker,
+    pub opencv_latency: LatencyTracker,
+    pub encode_latency: LatencyTracker,
+    /// Rate of processed frames handed to the UI
+    pub processed: FrameRateMeter,
 }
 
 impl MinimapMetrics {
@@ -36,47 +104,438 @@ impl MinimapMetrics {
         Self {
             frames_processed: AtomicUsize::new(0),
             frames_dropped: AtomicUsize::new(0),
+            frames_unchanged: AtomicUsize::new(0),
             opencv_detections: AtomicUsize::new(0),
             total_processing_time_ms: AtomicU64::new(0),
             total_opencv_time_ms: AtomicU64::new(0),
             total_encode_time_ms: AtomicU64::new(0),
+            latency: LatencyTracker::new(),
+            end_to_end_latency: LatencyTracker::new(),
+            opencv_latency: LatencyTracker::new(),
+            encode_latency: LatencyTracker::new(),
+            processed: FrameRateMeter::new(),
         }
     }
 
     pub fn get_fps(&self) -> f64 {
-        let frames = self.frames_processed.load(Ordering::Relaxed) as f64;
-        let time_ms = self.total_processing_time_ms.load(Ordering::Relaxed) as f64;
-        if time_ms > 0.0 { (frames * 1000.0) / time_ms } else { 0.0 }
+        self.processed.fps(Instant::now())
     }
 
-    pub fn get_stats(&self) -> String {
+    pub fn stats(&self) -> MinimapStats {
         let frames = self.frames_processed.load(Ordering::Relaxed);
-        let dropped = self.frames_dropped.load(Ordering::Relaxed);
         let detections = self.opencv_detections.load(Ordering::Relaxed);
-        let fps = self.get_fps();
-        
-        let avg_opencv = if frames > 0 {
-            self.total_opencv_time_ms.load(Ordering::Relaxed) as f64 / frames as f64
-        } else { 0.0 };
-        
-        let avg_encode = if frames > 0 {
-            self.total_encode_time_ms.load(Ordering::Relaxed) as f64 / frames as f64
-        } else { 0.0 };
+        let average = |total: &AtomicU64| {
+            if frames > 0 { total.load(Ordering::Relaxed) as f64 / frames as f64 } else { 0.0 }
+        };
+
+        let now = Instant::now();
+        MinimapStats {
+            fps: self.processed.fps(now),
+            frames_processed: frames,
+            frames_dropped: self.frames_dropped.load(Ordering::Relaxed),
+            frames_unchanged: self.frames_unchanged.load(Ordering::Relaxed),
+            detections,
+            detection_rate: if frames > 0 { detections as f64 / frames as f64 } else { 0.0 },
+            avg_opencv_ms: average(&self.total_opencv_time_ms),
+            avg_encode_ms: average(&self.total_encode_time_ms),
+            latency: self.latency.percentiles(),
+            end_to_end_latency: self.end_to_end_latency.percentiles(),
+            opencv_latency: self.opencv_latency.percentiles(),
+            encode_latency: self.encode_latency.percentiles(),
+            since_last_frame_ms: self.processed.since_last_frame(now).map(|since| since.as_secs_f64() * 1000.0),
+        }
+    }
+}
 
-        format!(
+/// Snapshot of minimap processing performance
+#[derive(Clone, Debug, Serialize)]
+pub struct MinimapStats {
+    /// Processed frames per second, averaged over recent frames
+    pub fps: f64,
+    pub frames_processed: usize,
+    pub frames_dropped: usize,
+    pub frames_unchanged: usize,
+    pub detections: usize,
+    /// Fraction of processed frames with a minimap detection (0.0 - 1.0)
+    pub detection_rate: f64,
+    pub avg_opencv_ms: f64,
+    pub avg_encode_ms: f64,
+    /// Whole frame, from receiving it to handing it to the UI
+    pub latency: LatencyPercentiles,
+    /// From capturing the frame to handing it to the UI, including time queued
+    pub end_to_end_latency: LatencyPercentiles,
+    pub opencv_latency: LatencyPercentiles,
+    pub encode_latency: LatencyPercentiles,
+    /// `None` before the first frame was processed
+    pub since_last_frame_ms: Option<f64>,
+}
+
+impl fmt::Display for MinimapStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let last_frame = match self.since_last_frame_ms {
+            Some(ms) => format!("{:.0}ms ago", ms),
+            None => "never".to_string(),
+        };
+        write!(
+            f,
             "🎯 Minimap Service:\n\
              📈 Processing FPS: {:.1}\n\
-             🔍 Frames: {} processed, {} dropped\n\
+             🔍 Frames: {} processed, {} dropped, {} unchanged\n\
              🎮 Minimap detections: {}\n\
              ⏱️  Avg times: OpenCV {:.1}ms, Encode {:.1}ms\n\
+             ⏱️  Latency: {}\n\
+             ⏱️  End-to-end: {}\n\
+             ⏱️  OpenCV: {}\n\
+             ⏱️  Encode: {}\n\
+             🕐 Last frame: {}\n\
              🎨 Detection rate: {:.1}%",
-            fps, frames, dropped, detections,
-            avg_opencv, avg_encode,
-            if frames > 0 { (detections as f64 / frames as f64) * 100.0 } else { 0.0 }
+            self.fps, self.frames_processed, self.frames_dropped, self.frames_unchanged,
+            self.detections, self.avg_opencv_ms, self.avg_encode_ms, self.latency,
+            self.end_to_end_latency, self.opencv_latency, self.encode_latency, last_frame,
+            self.detection_rate * 100.0
         )
     }
 }
 
+/// Combined capture and minimap statistics
+#[derive(Clone, Debug, Serialize)]
+pub struct PerformanceStats {
+    pub capture: CaptureStats,
+    pub minimap: MinimapStats,
+}
+
+impl fmt::Display for PerformanceStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n\n{}", self.capture, self.minimap)
+    }
+}
+
+/// Minimap settings persisted across restarts, the ROI is kept in [`BotConfig`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MinimapSettings {
+    /// Stages run on every processed frame
+    #[serde(default = "MinimapSettings::default_pipeline")]
+    pub pipeline: PipelineConfig,
+}
+
+impl Default for MinimapSettings {
+    fn default() -> Self {
+        Self {
+            pipeline: Self::default_pipeline(),
+        }
+    }
+}
+
+impl MinimapSettings {
+    /// Locate the minimap, find the player and blips on it and encode a WebP preview
+    pub fn default_pipeline() -> PipelineConfig {
+        let detect = |name: &str| StageConfig::Detect {
+            name: name.to_string(),
+            preprocess: None,
+        };
+        PipelineConfig {
+            stages: vec![
+                detect(MinimapStage::NAME),
+                detect(PlayerArrowStage::NAME),
+                detect(BlipStage::NAME),
+                StageConfig::Encode {
+                    format: EncodeFormat::Webp,
+                    quality: EncodeFormat::Webp.default_quality(),
+                    max_size: Some(DEFAULT_PREVIEW_SIZE),
+                },
+            ],
+        }
+    }
+
+    pub fn path() -> PathBuf {
+        crate::config_dir().join("minimap.json")
+    }
+
+    /// Load the saved settings, falling back to defaults if there are none
+    pub fn load() -> Self {
+        let path = Self::path();
+        let Ok(json) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&json).unwrap_or_else(|e| {
+            tracing::warn!(path = %path.display(), error = %e, "Ignoring invalid minimap settings");
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize minimap settings: {}", e))?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+/// Detectors run on every processed frame, shared with the processing task
+#[derive(Clone)]
+struct Detectors {
+    // Finds and caches the minimap rect within captured frames
+    locator: Arc<StdMutex<MinimapLocator>>,
+    player: Arc<StdMutex<PlayerArrowDetector>>,
+    player_sender: broadcast::Sender<PlayerPosition>,
+    blips: Arc<StdMutex<BlipDetector>>,
+    blips_sender: broadcast::Sender<Vec<MinimapBlip>>,
+    events: Arc<StdMutex<MinimapEventTracker>>,
+    event_sender: broadcast::Sender<MinimapEvent>,
+    // Player found on the current frame, for stages that run after the player arrow
+    last_player: Arc<StdMutex<Option<PlayerPosition>>>,
+    // Explored map, `None` unless mapping was started
+    map: Arc<StdMutex<Option<MapBuilder>>>,
+}
+
+impl Detectors {
+    fn publish(&self, event: Option<MinimapEvent>) {
+        let Some(event) = event else {
+            return;
+        };
+        match &event {
+            MinimapEvent::MinimapFound { rect } => {
+                EventBus::global().publish(BotEvent::MinimapDetected { rect: *rect });
+            }
+            MinimapEvent::MinimapLost => EventBus::global().publish(BotEvent::MinimapLost),
+            _ => {}
+        }
+        let _ = self.event_sender.send(event);
+    }
+
+    /// Pipeline builder that knows the minimap detectors
+    fn pipeline_builder(&self) -> PipelineBuilder {
+        let minimap = self.clone();
+        let player = self.clone();
+        let blips = self.clone();
+        let map = self.clone();
+        Pipeline::builder()
+            .register_detector(MinimapStage::NAME, Preprocess::gray(), move |preprocess| {
+                Box::new(MinimapStage(minimap.clone(), preprocess))
+            })
+            .register_detector(PlayerArrowStage::NAME, Preprocess::hsv(), move |preprocess| {
+                Box::new(PlayerArrowStage(player.clone(), preprocess))
+            })
+            .register_detector(BlipStage::NAME, Preprocess::hsv(), move |preprocess| {
+                Box::new(BlipStage(blips.clone(), preprocess))
+            })
+            .register_detector(MapStage::NAME, Preprocess::default(), move |preprocess| {
+                Box::new(MapStage(map.clone(), preprocess))
+            })
+    }
+
+    fn build_pipeline(&self, config: &PipelineConfig) -> Result<Pipeline, String> {
+        Ok(self.pipeline_builder().stages_from(config)?.build())
+    }
+}
+
+/// Locates the minimap and focuses the following detectors on it
+struct MinimapStage(Detectors, Preprocess);
+
+impl MinimapStage {
+    const NAME: &'static str = "minimap";
+}
+
+impl PipelineStage for MinimapStage {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn process(&mut self, context: &mut FrameContext) -> Result<(), String> {
+        let preprocess = self.1;
+        let frame_height = (context.frame_height() * preprocess.scale).round() as i32;
+        let minimap_rect = self
+            .0
+            .locator
+            .lock()
+            .unwrap()
+            .locate(context.preprocessed(&preprocess)?, frame_height)?
+            .map(|rect| {
+                let (x, y) = preprocess.unscale(rect.x as f32, rect.y as f32);
+                let (width, height) = preprocess.unscale(rect.width as f32, rect.height as f32);
+                Rect::new(x.round() as i32, y.round() as i32, width.round() as i32, height.round() as i32)
+            });
+        self.0.publish(self.0.events.lock().unwrap().update_minimap(minimap_rect));
+        if let Some(rect) = minimap_rect {
+            let (x, y) = context.to_frame(rect.x as f32, rect.y as f32);
+            let (right, bottom) = context.to_frame((rect.x + rect.width) as f32, (rect.y + rect.height) as f32);
+            context.annotate(Annotation::Box {
+                rect: Rect::new(x as i32, y as i32, (right - x) as i32, (bottom - y) as i32),
+                label: Some(Self::NAME.to_string()),
+                rgb: OVERLAY_MINIMAP_COLOR,
+            });
+        }
+        context.set_focus(minimap_rect);
+        context.set_skip_detection(minimap_rect.is_none());
+        Ok(())
+    }
+}
+
+struct PlayerArrowStage(Detectors, Preprocess);
+
+impl PlayerArrowStage {
+    const NAME: &'static str = "player_arrow";
+}
+
+impl PipelineStage for PlayerArrowStage {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn process(&mut self, context: &mut FrameContext) -> Result<(), String> {
+        if context.skip_detection() {
+            return Ok(());
+        }
+        let preprocess = self.1;
+        let position = self
+            .0
+            .player
+            .lock()
+            .unwrap()
+            .detect(context.preprocessed(&preprocess)?)?
+            .map(|position| {
+                let (x, y) = preprocess.unscale(position.x, position.y);
+                PlayerPosition { x, y, ..position }
+            });
+        self.0.publish(self.0.events.lock().unwrap().update_player(position));
+        *self.0.last_player.lock().unwrap() = position;
+        if let Some(position) = position {
+            let (x, y) = context.focus_to_frame(position.x, position.y);
+            context.annotate(Annotation::Point {
+                x,
+                y,
+                label: None,
+                rgb: OVERLAY_PLAYER_COLOR,
+            });
+            if let Some(degrees) = position.heading_degrees {
+                context.annotate(Annotation::Heading {
+                    x,
+                    y,
+                    degrees,
+                    rgb: OVERLAY_PLAYER_COLOR,
+                });
+            }
+            let _ = self.0.player_sender.send(position);
+        }
+        Ok(())
+    }
+}
+
+struct BlipStage(Detectors, Preprocess);
+
+impl BlipStage {
+    const NAME: &'static str = "blips";
+}
+
+impl PipelineStage for BlipStage {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn process(&mut self, context: &mut FrameContext) -> Result<(), String> {
+        if context.skip_detection() {
+            return Ok(());
+        }
+        let preprocess = self.1;
+        let mut blips = self.0.blips.lock().unwrap().detect(context.preprocessed(&preprocess)?)?;
+        for blip in &mut blips {
+            (blip.x, blip.y) = preprocess.unscale(blip.x, blip.y);
+        }
+        self.0.publish(self.0.events.lock().unwrap().update_blips(&blips));
+        for blip in &blips {
+            let (x, y) = context.focus_to_frame(blip.x, blip.y);
+            let half = OVERLAY_BLIP_SIZE as f32 / 2.0;
+            let (label, rgb) = match blip.class {
+                BlipClass::Enemy => ("enemy", [255, 0, 0]),
+                BlipClass::Npc => ("npc", [255, 200, 0]),
+                BlipClass::Ally => ("ally", [0, 128, 255]),
+            };
+            context.annotate(Annotation::Box {
+                rect: Rect::new((x - half) as i32, (y - half) as i32, OVERLAY_BLIP_SIZE, OVERLAY_BLIP_SIZE),
+                label: Some(label.to_string()),
+                rgb,
+            });
+        }
+        let _ = self.0.blips_sender.send(blips);
+        Ok(())
+    }
+}
+
+/// Stitches the minimap into the explored map while mapping is started
+struct MapStage(Detectors, Preprocess);
+
+impl MapStage {
+    const NAME: &'static str = "map";
+}
+
+impl PipelineStage for MapStage {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn process(&mut self, context: &mut FrameContext) -> Result<(), String> {
+        let mut map = self.0.map.lock().unwrap();
+        let Some(map) = map.as_mut() else {
+            return Ok(());
+        };
+        if context.skip_detection() {
+            map.lose_track();
+            return Ok(());
+        }
+        let preprocess = self.1;
+        // Player positions are unscaled, the crop may not be
+        let player = self.0.last_player.lock().unwrap().map(|position| PlayerPosition {
+            x: position.x * preprocess.scale as f32,
+            y: position.y * preprocess.scale as f32,
+            ..position
+        });
+        map.add(context.preprocessed(&preprocess)?, player.as_ref())?;
+        Ok(())
+    }
+}
+
+/// Scaled down full frames with the ROI outlined, only made while someone asked for them
+struct FramePreview {
+    enabled: AtomicBool,
+    last: StdMutex<Option<Instant>>,
+    sender: watch::Sender<Option<Vec<u8>>>,
+}
+
+impl FramePreview {
+    /// Whether to preview the current frame, the next one is due after [`FRAME_PREVIEW_INTERVAL`]
+    fn due(&self) -> bool {
+        if !self.enabled.load(Ordering::Relaxed) || LowPowerMode::global().is_enabled() {
+            return false;
+        }
+        let mut last = self.last.lock().unwrap();
+        if last.is_some_and(|last| last.elapsed() < FRAME_PREVIEW_INTERVAL) {
+            return false;
+        }
+        *last = Some(Instant::now());
+        true
+    }
+
+    /// Preview `frame` without holding up the processing of the next one
+    fn render(self: &Arc<Self>, frame: Arc<CapturedFrame>, roi: Option<Rect>) {
+        let preview = self.clone();
+        tokio::spawn(async move {
+            let outline = roi.map(|roi| (roi, OVERLAY_MINIMAP_COLOR));
+            match spawn_cpu(move || image_ops::frame_thumbnail(&frame, FRAME_PREVIEW_WIDTH, outline)).await {
+                Ok(Ok(thumbnail)) => {
+                    if preview.enabled.load(Ordering::Relaxed) {
+                        preview.sender.send_replace(Some(thumbnail));
+                    }
+                }
+                Ok(Err(e)) => tracing::debug!(error = %e, "Dropped frame preview"),
+                Err(e) => tracing::debug!(error = %e, "Frame preview task failed"),
+            }
+        });
+    }
+}
+
 /// Minimap detection service that processes frames from GraphicsCaptureService
 #[derive(Clone)]
 pub struct MinimapService {
@@ -84,60 +543,201 @@ pub struct MinimapService {
     current_window_title: Arc<Mutex<Option<String>>>,
     
     // Frame processing
-    frame_receiver: Arc<Mutex<Option<broadcast::Receiver<CapturedFrame>>>>,
+    frame_receiver: Arc<Mutex<Option<broadcast::Receiver<Arc<CapturedFrame>>>>>,
     frame_sender: watch::Sender<Option<Vec<u8>>>,
     frame_watch: watch::Receiver<Option<Vec<u8>>>,
     
-    // Processing control
-    is_processing: Arc<Mutex<bool>>,
-    is_stopping: Arc<Mutex<bool>>,
-    is_starting: Arc<Mutex<bool>>,
+    // Processing control, changed only through `transition`
+    state: Arc<watch::Sender<ServiceState>>,
+
+    // Frames closer than this to the last processed frame are skipped
+    change_threshold: Arc<Mutex<f64>>,
+
+    detectors: Detectors,
+    pipeline: Arc<StdMutex<Pipeline>>,
+    pipeline_config: Arc<StdMutex<PipelineConfig>>,
+
+    // Region frames are cropped to before detection and encoding
+    roi: Arc<StdMutex<Option<Rect>>>,
+
+    // Probes drawn by the debug overlay
+    overlay_probes: Arc<StdMutex<Option<ProbeService>>>,
+
+    frame_preview: Arc<FramePreview>,
     
     // Metrics
     metrics: Arc<MinimapMetrics>,
+    performance: watch::Sender<Option<PerformanceStats>>,
 }
 
 impl MinimapService {
     pub fn new(graphics_service: Arc<GraphicsCaptureService>) -> Self {
         let (frame_sender, frame_watch) = watch::channel(None);
         let metrics = Arc::new(MinimapMetrics::new());
-        
+        let settings = MinimapSettings::load();
+        let detectors = Detectors {
+            locator: Arc::new(StdMutex::new(MinimapLocator::new())),
+            player: Arc::new(StdMutex::new(PlayerArrowDetector::default())),
+            player_sender: broadcast::channel(DETECTION_CHANNEL_CAPACITY).0,
+            blips: Arc::new(StdMutex::new(BlipDetector::default())),
+            blips_sender: broadcast::channel(DETECTION_CHANNEL_CAPACITY).0,
+            events: Arc::new(StdMutex::new(MinimapEventTracker::new())),
+            event_sender: broadcast::channel(DETECTION_CHANNEL_CAPACITY).0,
+            last_player: Arc::new(StdMutex::new(None)),
+            map: Arc::new(StdMutex::new(None)),
+        };
+        let (pipeline, pipeline_config) = match detectors.build_pipeline(&settings.pipeline) {
+            Ok(pipeline) => (pipeline, settings.pipeline),
+            Err(e) => {
+                tracing::warn!(error = %e, "Ignoring invalid minimap pipeline");
+                let config = MinimapSettings::default_pipeline();
+                let pipeline = detectors
+                    .build_pipeline(&config)
+                    .expect("default minimap pipeline only uses registered detectors");
+                (pipeline, config)
+            }
+        };
+
         Self {
             graphics_service,
             current_window_title: Arc::new(Mutex::new(None)),
             frame_receiver: Arc::new(Mutex::new(None)),
             frame_sender,
             frame_watch,
-            is_processing: Arc::new(Mutex::new(false)),
-            is_stopping: Arc::new(Mutex::new(false)),
-            is_starting: Arc::new(Mutex::new(false)),
+            state: Arc::new(watch::channel(ServiceState::Stopped).0),
+            change_threshold: Arc::new(Mutex::new(DEFAULT_CHANGE_THRESHOLD)),
+            detectors,
+            pipeline: Arc::new(StdMutex::new(pipeline)),
+            pipeline_config: Arc::new(StdMutex::new(pipeline_config)),
+            roi: Arc::new(StdMutex::new(None)),
+            overlay_probes: Arc::new(StdMutex::new(None)),
+            frame_preview: Arc::new(FramePreview {
+                enabled: AtomicBool::new(false),
+                last: StdMutex::new(None),
+                sender: watch::channel(None).0,
+            }),
             metrics,
+            performance: watch::channel(None).0,
+        }
+    }
+
+    /// Service using the ROI and detection thresholds of `config`
+    pub fn with_config(graphics_service: Arc<GraphicsCaptureService>, config: &BotConfig) -> Self {
+        let mut service = Self::new(graphics_service);
+        service.change_threshold = Arc::new(Mutex::new(config.detection.change.clamp(0.0, 1.0)));
+        *service.roi.lock().unwrap() = config.roi();
+        service
+            .detectors
+            .locator
+            .lock()
+            .unwrap()
+            .set_match_threshold(config.detection.minimap_match);
+        service
+    }
+
+    /// Apply the ROI, preview settings and detection thresholds of `config` to the running service
+    ///
+    /// Without a ROI in `config` full frames are processed again.
+    pub async fn apply_config(&self, config: &BotConfig) -> Result<(), String> {
+        self.set_change_threshold(config.detection.change).await;
+        self.detectors
+            .locator
+            .lock()
+            .unwrap()
+            .set_match_threshold(config.detection.minimap_match);
+        if let Some(preview_size) = config.capture.preview_size {
+            if self.preview_size() != Some(preview_size) {
+                self.set_preview_size(Some(preview_size))?;
+            }
+        }
+        if let Some(quality) = config.capture.preview_quality {
+            match self.preview_encoding() {
+                Some((_, current)) if current == quality => {}
+                Some((format, _)) => self.set_preview_encoding(format, quality)?,
+                None => self.set_preview_encoding(EncodeFormat::Webp, quality)?,
+            }
+        }
+        match config.roi() {
+            roi if roi == self.roi() => {}
+            Some(roi) => self.set_roi(roi)?,
+            None => self.clear_roi(),
         }
+        Ok(())
+    }
+
+    /// Apply every config published on `configs`, see [`Self::apply_config`]
+    pub fn follow_config(&self, mut configs: watch::Receiver<BotConfig>) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            while configs.changed().await.is_ok() {
+                let config = configs.borrow_and_update().clone();
+                if let Err(e) = service.apply_config(&config).await {
+                    tracing::warn!(error = %e, "Failed to apply config to minimap service");
+                }
+            }
+        });
     }
 
     pub fn get_frame_receiver(&self) -> watch::Receiver<Option<Vec<u8>>> {
         self.frame_watch.clone()
     }
 
+    /// Make full frame previews, a scaled down copy of a few frames a second
+    pub fn set_frame_preview(&self, enabled: bool) {
+        self.frame_preview.enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            self.frame_preview.sender.send_replace(None);
+        }
+    }
+
+    /// Full frames with the ROI outlined as raw RGBA, see [`Self::set_frame_preview`]
+    pub fn subscribe_frame_preview(&self) -> watch::Receiver<Option<Vec<u8>>> {
+        self.frame_preview.sender.subscribe()
+    }
+
     pub async fn is_capturing(&self) -> bool {
-        *self.is_processing.lock().await
+        self.state() == ServiceState::Running
     }
 
     pub async fn get_service_state(&self) -> ServiceState {
-        let is_processing = *self.is_processing.lock().await;
-        let is_stopping = *self.is_stopping.lock().await;
-        let is_starting = *self.is_starting.lock().await;
-        let has_window = self.current_window_title.lock().await.is_some();
-        let graphics_active = self.graphics_service.is_capturing().await;
-        
-        if is_stopping {
-            ServiceState::Stopping
-        } else if is_starting {
-            ServiceState::Starting
-        } else if is_processing && graphics_active && has_window {
-            ServiceState::Running
+        self.state()
+    }
+
+    pub fn state(&self) -> ServiceState {
+        *self.state.borrow()
+    }
+
+    /// Every state change from now on, the current state is marked as seen
+    pub fn subscribe_state(&self) -> watch::Receiver<ServiceState> {
+        let mut receiver = self.state.subscribe();
+        receiver.borrow_and_update();
+        receiver
+    }
+
+    /// Wait until the service reaches `state`, returns at once if it already is there
+    pub async fn wait_for(&self, state: ServiceState) {
+        let mut receiver = self.state.subscribe();
+        // The sender lives as long as `self`, so this can't fail
+        let _ = receiver.wait_for(|current| *current == state).await;
+    }
+
+    /// Move to `next` if that is valid from the current state, otherwise returns the current state
+    fn transition(&self, next: ServiceState) -> Result<(), ServiceState> {
+        let mut current = next;
+        let changed = self.state.send_if_modified(|state| {
+            current = *state;
+            if state.can_transition_to(next) {
+                *state = next;
+                true
+            } else {
+                false
+            }
+        });
+        if changed {
+            tracing::debug!(from = ?current, to = ?next, "Minimap service state changed");
+            Ok(())
         } else {
-            ServiceState::Stopped
+            Err(current)
         }
     }
 
@@ -145,27 +745,342 @@ impl MinimapService {
         self.current_window_title.lock().await.clone()
     }
 
-    pub fn get_performance_metrics(&self) -> Option<String> {
-        let graphics_metrics = self.graphics_service.get_metrics();
-        let minimap_metrics = self.metrics.get_stats();
-        
-        Some(format!("{}\n\n{}", graphics_metrics, minimap_metrics))
+    pub fn get_performance_metrics(&self) -> PerformanceStats {
+        PerformanceStats {
+            capture: self.graphics_service.get_metrics(),
+            minimap: self.metrics.stats(),
+        }
+    }
+
+    /// Performance published every [`PERFORMANCE_INTERVAL`] while capturing, `None` before the
+    /// first capture
+    pub fn subscribe_performance(&self) -> watch::Receiver<Option<PerformanceStats>> {
+        self.performance.subscribe()
+    }
+
+    /// Publish the performance until the service stops running
+    fn publish_performance(&self) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            let mut state = service.state.subscribe();
+            let mut ticks = tokio::time::interval(PERFORMANCE_INTERVAL);
+            while *state.borrow_and_update() == ServiceState::Running {
+                tokio::select! {
+                    changed = state.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                    }
+                    _ = ticks.tick() => {
+                        service.performance.send_replace(Some(service.get_performance_metrics()));
+                    }
+                }
+            }
+        });
     }
 
     /// Reset metrics
     pub fn reset_metrics(&self) {
         self.metrics.frames_processed.store(0, Ordering::Relaxed);
         self.metrics.frames_dropped.store(0, Ordering::Relaxed);
+        self.metrics.frames_unchanged.store(0, Ordering::Relaxed);
         self.metrics.opencv_detections.store(0, Ordering::Relaxed);
         self.metrics.total_processing_time_ms.store(0, Ordering::Relaxed);
         self.metrics.total_opencv_time_ms.store(0, Ordering::Relaxed);
         self.metrics.total_encode_time_ms.store(0, Ordering::Relaxed);
+        self.metrics.latency.reset();
+        self.metrics.end_to_end_latency.reset();
+        self.metrics.opencv_latency.reset();
+        self.metrics.encode_latency.reset();
+        self.metrics.processed.reset();
+    }
+
+    /// Set how different (0.0 - 1.0) a frame must be from the last processed one.
+    /// Use 0.0 to process every frame.
+    pub async fn set_change_threshold(&self, threshold: f64) {
+        *self.change_threshold.lock().await = threshold.clamp(0.0, 1.0);
+    }
+
+    /// Region of interest frames are cropped to, `None` processes the full frame
+    pub fn roi(&self) -> Option<Rect> {
+        *self.roi.lock().unwrap()
+    }
+
+    /// Crop frames to `roi` before detection and encoding
+    ///
+    /// Only until the next config change, the saved ROI is the one in [`BotConfig`].
+    /// Rects reported by the service, such as [`Self::minimap_rect`], are relative to the ROI.
+    pub fn set_roi(&self, roi: Rect) -> Result<(), String> {
+        if roi.is_empty() {
+            return Err(format!("Invalid minimap ROI {:?}", roi));
+        }
+        self.update_roi(Some(roi));
+        Ok(())
+    }
+
+    /// Process full frames again, until the next config change like [`Self::set_roi`]
+    pub fn clear_roi(&self) {
+        self.update_roi(None);
+    }
+
+    fn update_roi(&self, roi: Option<Rect>) {
+        *self.roi.lock().unwrap() = roi;
+        self.detectors.locator.lock().unwrap().invalidate();
+    }
+
+    fn save_settings(&self) -> Result<(), String> {
+        MinimapSettings {
+            pipeline: self.pipeline_config.lock().unwrap().clone(),
+        }
+        .save()
+    }
+
+    /// Stages currently run on every processed frame
+    pub fn pipeline_config(&self) -> PipelineConfig {
+        self.pipeline_config.lock().unwrap().clone()
+    }
+
+    /// Replace the processing stages, saved for the next start
+    ///
+    /// `detect` stages can use the `minimap`, `player_arrow`, `blips` and `map` detectors.
+    /// Without an `encode` stage no preview frames are produced.
+    pub fn set_pipeline(&self, config: PipelineConfig) -> Result<(), String> {
+        let pipeline = self.detectors.build_pipeline(&config)?;
+        *self.pipeline.lock().unwrap() = pipeline;
+        *self.pipeline_config.lock().unwrap() = config;
+        self.save_settings()
+    }
+
+    /// Go back to the default stages
+    pub fn reset_pipeline(&self) -> Result<(), String> {
+        self.set_pipeline(MinimapSettings::default_pipeline())
+    }
+
+    /// Format and quality of the preview frames, `None` if the pipeline has no encode stage
+    pub fn preview_encoding(&self) -> Option<(EncodeFormat, i32)> {
+        self.pipeline_config.lock().unwrap().stages.iter().find_map(|stage| match stage {
+            StageConfig::Encode { format, quality, .. } => Some((*format, *quality)),
+            _ => None,
+        })
+    }
+
+    /// Size preview frames are scaled down to fit before encoding, `None` encodes them at full size
+    pub fn preview_size(&self) -> Option<MaxSize> {
+        self.pipeline_config
+            .lock()
+            .unwrap()
+            .stages
+            .iter()
+            .find_map(|stage| match stage {
+                StageConfig::Encode { max_size, .. } => Some(*max_size),
+                _ => None,
+            })
+            .flatten()
+    }
+
+    /// Scale preview frames down to fit `max_size` before encoding, saved for the next start
+    ///
+    /// Frames are encoded at full size with `None`. Detection always runs on the full frame.
+    pub fn set_preview_size(&self, max_size: Option<MaxSize>) -> Result<(), String> {
+        let mut config = self.pipeline_config();
+        let encode = config.stages.iter_mut().find_map(|stage| match stage {
+            StageConfig::Encode { max_size, .. } => Some(max_size),
+            _ => None,
+        });
+        match encode {
+            Some(size) => *size = max_size,
+            None => return Err("The minimap pipeline has no encode stage".to_string()),
+        }
+        self.set_pipeline(config)
+    }
+
+    /// Encode preview frames as `format` at `quality` (0 - 100), saved for the next start
+    ///
+    /// Replaces the first encode stage, or adds one at the end if there is none. JPEG is the
+    /// cheapest for slow machines, WebP the smallest for streaming to other devices.
+    pub fn set_preview_encoding(&self, format: EncodeFormat, quality: i32) -> Result<(), String> {
+        let mut config = self.pipeline_config();
+        let quality = quality.clamp(0, 100);
+        let encode = config.stages.iter_mut().find_map(|stage| match stage {
+            StageConfig::Encode { format, quality, .. } => Some((format, quality)),
+            _ => None,
+        });
+        match encode {
+            Some((stage_format, stage_quality)) => {
+                *stage_format = format;
+                *stage_quality = quality;
+            }
+            None => config.stages.push(StageConfig::Encode {
+                format,
+                quality,
+                max_size: Some(DEFAULT_PREVIEW_SIZE),
+            }),
+        }
+        self.set_pipeline(config)
+    }
+
+    /// Whether detection results are drawn onto the preview frames
+    pub fn debug_overlay(&self) -> bool {
+        self.pipeline_config
+            .lock()
+            .unwrap()
+            .stages
+            .iter()
+            .any(|stage| matches!(stage, StageConfig::Overlay))
+    }
+
+    /// Draw the minimap rect, player heading, blips and overlay probes onto the preview frames
+    ///
+    /// The overlay stage goes right before the first encode stage.
+    pub fn set_debug_overlay(&self, enabled: bool) -> Result<(), String> {
+        let mut config = self.pipeline_config();
+        config.stages.retain(|stage| !matches!(stage, StageConfig::Overlay));
+        if enabled {
+            let index = config
+                .stages
+                .iter()
+                .position(|stage| matches!(stage, StageConfig::Encode { .. }))
+                .unwrap_or(config.stages.len());
+            config.stages.insert(index, StageConfig::Overlay);
+        }
+        self.set_pipeline(config)
+    }
+
+    /// Draw the probes of `probes` on the debug overlay, colored by whether they are active
+    pub fn set_overlay_probes(&self, probes: Option<ProbeService>) {
+        *self.overlay_probes.lock().unwrap() = probes;
+    }
+
+    /// Whether the explored map is being built
+    pub fn is_mapping(&self) -> bool {
+        self.detectors.map.lock().unwrap().is_some()
+    }
+
+    /// Start stitching the minimap into the explored map, continuing the saved map of the
+    /// same name if there is one
+    ///
+    /// A `map` stage is added after the detectors if the pipeline has none. Uses the player
+    /// position, so the `player_arrow` stage should run before it.
+    pub fn start_map(&self, config: MapBuilderConfig) -> Result<(), String> {
+        let builder = MapBuilder::load(config)?;
+        let mut pipeline = self.pipeline_config();
+        let has_stage = pipeline
+            .stages
+            .iter()
+            .any(|stage| matches!(stage, StageConfig::Detect { name, .. } if name == MapStage::NAME));
+        if !has_stage {
+            let index = pipeline
+                .stages
+                .iter()
+                .position(|stage| matches!(stage, StageConfig::Overlay | StageConfig::Encode { .. }))
+                .unwrap_or(pipeline.stages.len());
+            pipeline.stages.insert(
+                index,
+                StageConfig::Detect {
+                    name: MapStage::NAME.to_string(),
+                    preprocess: None,
+                },
+            );
+            self.set_pipeline(pipeline)?;
+        }
+        *self.detectors.map.lock().unwrap() = Some(builder);
+        Ok(())
+    }
+
+    /// Stop mapping and save the map, the `map` stage stays in the pipeline but does nothing
+    pub fn stop_map(&self) -> Result<Option<PathBuf>, String> {
+        match self.detectors.map.lock().unwrap().take() {
+            Some(map) if !map.is_empty() => map.save().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Save the map built so far to [`MapBuilder::path`]
+    pub fn save_map(&self) -> Result<PathBuf, String> {
+        match self.detectors.map.lock().unwrap().as_ref() {
+            Some(map) => map.save(),
+            None => Err("Mapping is not started".to_string()),
+        }
+    }
+
+    /// PNG of the map built so far, at most `max_width` pixels wide
+    pub fn map_preview(&self, max_width: i32) -> Option<Vec<u8>> {
+        let map = self.detectors.map.lock().unwrap();
+        let map = map.as_ref().filter(|map| !map.is_empty())?;
+        map.preview(max_width)
+            .map_err(|e| tracing::warn!(error = %e, "Failed to render map preview"))
+            .ok()
+    }
+
+    /// Player position on the map in pixels, `None` while not mapping or tracking is lost
+    pub fn map_position(&self) -> Option<(f32, f32)> {
+        self.detectors.map.lock().unwrap().as_ref()?.player_position()
+    }
+
+    /// Performance of each pipeline stage
+    pub fn pipeline_stats(&self) -> Vec<StageStats> {
+        self.pipeline.lock().unwrap().stats()
+    }
+
+    /// Bounding rect of the minimap in the last processed frame, if it was found
+    pub fn minimap_rect(&self) -> Option<Rect> {
+        self.detectors.locator.lock().unwrap().cached()
+    }
+
+    /// Locate the minimap by matching the template image at `path` instead of its border
+    pub fn set_minimap_template(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        self.detectors.locator.lock().unwrap().set_template_file(path)
+    }
+
+    /// Go back to locating the minimap by its border
+    pub fn clear_minimap_template(&self) {
+        self.detectors.locator.lock().unwrap().set_template(None);
+    }
+
+    /// Subscribe to detection changes (minimap found/lost, player moved, blips updated)
+    ///
+    /// Unlike the per-frame channels, events are only sent when a detection changes.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<MinimapEvent> {
+        self.detectors.event_sender.subscribe()
+    }
+
+    /// Subscribe to the player position found on each processed frame
+    ///
+    /// Positions are in pixels of the minimap rect, see [`Self::minimap_rect`].
+    pub fn subscribe_player(&self) -> broadcast::Receiver<PlayerPosition> {
+        self.detectors.player_sender.subscribe()
+    }
+
+    pub fn player_arrow_config(&self) -> PlayerArrowConfig {
+        self.detectors.player.lock().unwrap().config()
+    }
+
+    pub fn set_player_arrow_config(&self, config: PlayerArrowConfig) {
+        self.detectors.player.lock().unwrap().set_config(config);
+    }
+
+    /// Subscribe to the blips found on each processed frame, in pixels of the minimap rect
+    pub fn subscribe_blips(&self) -> broadcast::Receiver<Vec<MinimapBlip>> {
+        self.detectors.blips_sender.subscribe()
+    }
+
+    pub fn blip_rules(&self) -> Vec<BlipRule> {
+        self.detectors.blips.lock().unwrap().rules().to_vec()
+    }
+
+    pub fn set_blip_rules(&self, rules: Vec<BlipRule>) {
+        self.detectors.blips.lock().unwrap().set_rules(rules);
     }
 
     pub async fn set_window(&self, title: String) -> Result<(), String> {
+        self.set_window_target(CaptureTarget::Title(title.clone()), title).await
+    }
+
+    /// Capture the window picked by `target`, e.g. one of several windows sharing `title`
+    pub async fn set_window_target(&self, target: CaptureTarget, title: String) -> Result<(), String> {
         self.stop_capture().await?;
-        
-        self.graphics_service.start_window_capture(&title).await?;
+
+        self.graphics_service.start_target_capture(target).await?;
         
         let frame_receiver = self.graphics_service.subscribe();
         *self.frame_receiver.lock().await = Some(frame_receiver);
@@ -176,185 +1091,283 @@ impl MinimapService {
     }
 
     pub async fn start_capture(&self) -> Result<(), String> {
-        *self.is_starting.lock().await = true;
-        *self.is_stopping.lock().await = false;
-        
-        if *self.is_processing.lock().await {
-            *self.is_starting.lock().await = false;
-            self.stop_capture().await?;
-            tokio::time::sleep(std::time::Duration::from_millis(150)).await;
-            *self.is_starting.lock().await = true;
+        loop {
+            match self.transition(ServiceState::Starting) {
+                Ok(()) => break,
+                Err(ServiceState::Running) => return Ok(()),
+                // Let the other start or stop finish, then try again
+                Err(busy) => {
+                    let mut receiver = self.state.subscribe();
+                    let _ = receiver.wait_for(|current| *current != busy).await;
+                }
+            }
         }
 
         let receiver_guard = self.frame_receiver.lock().await;
-        let mut receiver = match receiver_guard.as_ref() {
+        let receiver = match receiver_guard.as_ref() {
             Some(r) => r.resubscribe(),
-            None => return Err("No graphics capture subscription".to_string()),
+            None => {
+                let _ = self.transition(ServiceState::Stopped);
+                return Err("No graphics capture subscription".to_string());
+            }
         };
         drop(receiver_guard);
 
-        *self.is_processing.lock().await = true;
-        *self.is_starting.lock().await = false;
+        self.detectors.locator.lock().unwrap().invalidate();
+        self.detectors.events.lock().unwrap().reset();
+        if self.transition(ServiceState::Running).is_err() {
+            return Err("Minimap capture was stopped while starting".to_string());
+        }
+        self.publish_performance();
 
         let frame_sender = self.frame_sender.clone();
         let metrics = self.metrics.clone();
-        let is_processing = self.is_processing.clone();
+        let state = self.state.clone();
+        let change_threshold = self.change_threshold.clone();
+        let pipeline = self.pipeline.clone();
+        let roi = self.roi.clone();
+        let overlay_probes = self.overlay_probes.clone();
+        let frame_preview = self.frame_preview.clone();
+        let graphics_service = self.graphics_service.clone();
+        let span = tracing::info_span!("minimap", window = ?self.current_window_title.lock().await.as_deref());
+        let mut first_receiver = Some(receiver);
 
-        tokio::spawn(async move {
-            while *is_processing.lock().await {
-                match receiver.recv().await {
-                    Ok(captured_frame) => {
-                        let process_start = Instant::now();
-                        
-                        match Self::process_minimap_frame(captured_frame, &metrics).await {
-                            Ok(processed_webp) => {
-                                if frame_sender.send(Some(processed_webp)).is_ok() {
+        // A panicking detector takes the processing task down, it is started again with a
+        // fresh subscription until the service is stopped
+        supervise("minimap_processing", self.graphics_service.restart_policy(), move || {
+            let mut receiver = first_receiver.take().unwrap_or_else(|| graphics_service.subscribe());
+            let mut state = state.subscribe();
+            let frame_sender = frame_sender.clone();
+            let metrics = metrics.clone();
+            let change_threshold = change_threshold.clone();
+            let pipeline = pipeline.clone();
+            let roi = roi.clone();
+            let overlay_probes = overlay_probes.clone();
+            let frame_preview = frame_preview.clone();
+            // Whatever the panic left behind is still usable
+            pipeline.clear_poison();
+            roi.clear_poison();
+            overlay_probes.clear_poison();
+
+            async move {
+                let mut frame_diff = FrameDiff::default();
+
+                while *state.borrow_and_update() == ServiceState::Running {
+                    let frame = tokio::select! {
+                        changed = state.changed() => {
+                            if changed.is_err() {
+                                break;
+                            }
+                            continue;
+                        }
+                        frame = receiver.recv() => frame,
+                    };
+                    match frame {
+                        Ok(captured_frame) => {
+                            frame_diff.set_threshold(*change_threshold.lock().await);
+                            if !frame_diff.is_changed(&captured_frame) {
+                                metrics.frames_unchanged.fetch_add(1, Ordering::Relaxed);
+                                continue;
+                            }
+                            if frame_preview.due() {
+                                frame_preview.render(captured_frame.clone(), *roi.lock().unwrap());
+                            }
+
+                            let process_start = Instant::now();
+                            let captured_at = captured_frame.timestamp;
+                            
+                            // OpenCV and WebP encoding take milliseconds, too long for a runtime thread
+                            let work = (metrics.clone(), pipeline.clone(), roi.clone(), overlay_probes.clone());
+                            let processed = spawn_cpu(move || {
+                                let (metrics, pipeline, roi, overlay_probes) = work;
+                                Self::process_minimap_frame(captured_frame, &metrics, &pipeline, &roi, &overlay_probes)
+                            })
+                            .await;
+                            // A panicking detector takes this task down, so the supervisor restarts it
+                            let processed = match processed {
+                                Ok(processed) => processed,
+                                Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+                                Err(e) => Err(format!("Minimap processing task failed: {}", e)),
+                            };
+                            match processed {
+                                Ok(Some(processed_webp)) => {
+                                    if frame_sender.send(Some(processed_webp)).is_ok() {
+                                        metrics.frames_processed.fetch_add(1, Ordering::Relaxed);
+                                        metrics.processed.record(Instant::now());
+                                        metrics.end_to_end_latency.record(captured_at.elapsed());
+                                    } else {
+                                        metrics.frames_dropped.fetch_add(1, Ordering::Relaxed);
+                                    }
+                                }
+                                // Processed without a preview in low-power mode
+                                Ok(None) => {
                                     metrics.frames_processed.fetch_add(1, Ordering::Relaxed);
-                                } else {
+                                    metrics.processed.record(Instant::now());
+                                    metrics.end_to_end_latency.record(captured_at.elapsed());
+                                }
+                                Err(e) => {
+                                    tracing::debug!(error = %e, "Dropped minimap frame");
                                     metrics.frames_dropped.fetch_add(1, Ordering::Relaxed);
                                 }
                             }
-                            Err(_) => {
-                                metrics.frames_dropped.fetch_add(1, Ordering::Relaxed);
-                            }
+                            
+                            let elapsed = process_start.elapsed();
+                            metrics.total_processing_time_ms.fetch_add(elapsed.as_millis() as u64, Ordering::Relaxed);
+                            metrics.latency.record(elapsed);
+                        }
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            metrics.frames_dropped.fetch_add(skipped as usize, Ordering::Relaxed);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => {
+                            break;
                         }
-                        
-                        let elapsed = process_start.elapsed().as_millis() as u64;
-                        metrics.total_processing_time_ms.fetch_add(elapsed, Ordering::Relaxed);
-                    }
-                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
-                        metrics.frames_dropped.fetch_add(skipped as usize, Ordering::Relaxed);
-                    }
-                    Err(broadcast::error::RecvError::Closed) => {
-                        break;
                     }
                 }
+                Ok(())
             }
+            .instrument(span.clone())
         });
 
         Ok(())
     }
 
     pub async fn stop_capture(&self) -> Result<(), String> {
-        {
-            let mut stopping = self.is_stopping.lock().await;
-            if *stopping {
+        match self.transition(ServiceState::Stopping) {
+            Ok(()) => {}
+            // Another stop is releasing the capture
+            Err(ServiceState::Stopping) => {
+                self.wait_for(ServiceState::Stopped).await;
+                return Ok(());
+            }
+            // Nothing is processing, but the graphics capture may have been started by `set_window`
+            Err(_) => {
+                self.release_capture().await;
                 return Ok(());
             }
-            *stopping = true;
         }
-        
-        *self.is_processing.lock().await = false;
-        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-        
+
+        self.release_capture().await;
+        let _ = self.transition(ServiceState::Stopped);
+        Ok(())
+    }
+
+    async fn release_capture(&self) {
         *self.current_window_title.lock().await = None;
         *self.frame_receiver.lock().await = None;
         let _ = self.frame_sender.send(None);
-        
+        self.frame_preview.sender.send_replace(None);
+
         self.graphics_service.stop_capture().await;
-        
-        *self.is_stopping.lock().await = false;
-        *self.is_starting.lock().await = false;
-        
-        Ok(())
     }
 
-    async fn process_minimap_frame(
-        frame: CapturedFrame,
+    fn process_minimap_frame(
+        frame: Arc<CapturedFrame>,
         metrics: &MinimapMetrics,
-    ) -> Result<Vec<u8>, String> {
+        pipeline: &StdMutex<Pipeline>,
+        roi: &StdMutex<Option<Rect>>,
+        overlay_probes: &StdMutex<Option<ProbeService>>,
+    ) -> Result<Option<Vec<u8>>, String> {
         if frame.data.is_empty() {
             return Err("Empty frame data".to_string());
         }
-        
-        let opencv_start = Instant::now();
-        let minimap_detected = Self::detect_minimap_with_opencv(&frame).await?;
-        let opencv_time = opencv_start.elapsed().as_millis() as u64;
-        metrics.total_opencv_time_ms.fetch_add(opencv_time, Ordering::Relaxed);
-        
-        if minimap_detected {
-            metrics.opencv_detections.fetch_add(1, Ordering::Relaxed);
+        // An ROI outside of a smaller frame (e.g. after a resolution change) falls back to the full frame
+        let roi = *roi.lock().unwrap();
+        let mut context = FrameContext::from_frame(&frame, roi)?;
+        if let Some(probes) = overlay_probes.lock().unwrap().as_ref() {
+            for probe in probes.probes() {
+                let rgb = if probes.is_active(&probe.name).unwrap_or(false) {
+                    OVERLAY_PROBE_ACTIVE_COLOR
+                } else {
+                    OVERLAY_PROBE_INACTIVE_COLOR
+                };
+                context.annotate(Annotation::Point {
+                    x: probe.x as f32,
+                    y: probe.y as f32,
+                    label: Some(probe.name),
+                    rgb,
+                });
+            }
         }
 
-        let encode_start = Instant::now();
-        let result = Self::encode_frame_webp_opencv(&frame).await?;
-        
-        let encode_time = encode_start.elapsed().as_millis() as u64;
-        metrics.total_encode_time_ms.fetch_add(encode_time, Ordering::Relaxed);
-
-        Ok(result)
-    }
+        // Low-power mode keeps detection running but produces no previews
+        let encode = !LowPowerMode::global().is_enabled();
+        let run = if encode {
+            pipeline.lock().unwrap().run(&mut context)?
+        } else {
+            pipeline.lock().unwrap().run_without_encoding(&mut context)?
+        };
+        metrics.total_opencv_time_ms.fetch_add(run.processing.as_millis() as u64, Ordering::Relaxed);
+        metrics.total_encode_time_ms.fetch_add(run.encoding.as_millis() as u64, Ordering::Relaxed);
+        metrics.opencv_latency.record(run.processing);
+        if encode {
+            metrics.encode_latency.record(run.encoding);
+        }
 
+        if context.focus().is_some() {
+            metrics.opencv_detections.fetch_add(1, Ordering::Relaxed);
+        }
 
-    async fn detect_minimap_with_opencv(frame: &CapturedFrame) -> Result<bool, String> {
-        let mat = Self::create_bgra_mat(frame)?;
-        
-        let size = mat.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
-        let has_minimap = size.width >= 640 && size.height >= 360;
+        if !encode {
+            return Ok(None);
+        }
+        context
+            .take_encoded()
+            .map(Some)
+            .ok_or_else(|| "Minimap pipeline has no encode stage".to_string())
+    }
 
-        tokio::time::sleep(std::time::Duration::from_micros(100)).await;
-        
-        Ok(has_minimap)
+    /// Enable high-performance capture mode, replacing Windows Graphics Capture
+    pub async fn enable_dxgi_mode(&self) -> Result<(), String> {
+        self.graphics_service.switch_backend(CaptureBackend::Dxgi).await.map(|_| ())
     }
+}
 
-    async fn encode_frame_webp_opencv(frame: &CapturedFrame) -> Result<Vec<u8>, String> {
-        let mat = Self::create_bgra_mat(frame)?;
+#[async_trait::async_trait]
+impl Service for MinimapService {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_capture().await.map_err(ServiceError::from)
+    }
 
-        let mut buffer = Vector::<u8>::new();
-        let params = Vector::<i32>::from_slice(&[IMWRITE_WEBP_QUALITY, 75]);
-        
-        imencode(".webp", &mat, &mut buffer, &params)
-            .map_err(|e| format!("Failed to encode WebP: {}", e))?;
-        
-        Ok(buffer.to_vec())
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_capture().await.map_err(ServiceError::from)
     }
 
-    fn create_bgra_mat(frame: &CapturedFrame) -> Result<Mat, String> {
-        let rows = frame.height as i32;
-        let cols = frame.width as i32;
-        
-        let mut mat = Mat::zeros(rows, cols, CV_8UC4)
-            .map_err(|e| format!("Failed to create Mat: {}", e))?
-            .to_mat()
-            .map_err(|e| format!("Failed to convert to Mat: {}", e))?;
-        
-        unsafe {
-            let mat_ptr = mat.ptr_mut(0).map_err(|e| format!("Failed to get Mat pointer: {}", e))?;
-            let mat_size = (rows * cols * 4) as usize; // 4 bytes per BGRA pixel
-            
-            if frame.data.len() >= mat_size {
-                std::ptr::copy_nonoverlapping(
-                    frame.data.as_ptr(),
-                    mat_ptr,
-                    mat_size,
-                );
-            } else {
-                return Err(format!("Frame data too small: {} < {}", frame.data.len(), mat_size));
-            }
+    async fn status(&self) -> ServiceStatus {
+        match self.get_service_state().await {
+            ServiceState::Stopped => ServiceStatus::Stopped,
+            ServiceState::Starting => ServiceStatus::Starting,
+            ServiceState::Running => ServiceStatus::Running,
+            ServiceState::Stopping => ServiceStatus::Stopping,
         }
-        
-        Ok(mat)
     }
 
-    /// Enable high-performance capture mode
-    pub async fn enable_dxgi_mode(&self) -> Result<(), String> {
-        self.graphics_service.start_dxgi_capture().await
+    async fn health_check(&self) -> ServiceHealth {
+        match self.get_service_state().await {
+            ServiceState::Running => ServiceHealth::Healthy,
+            ServiceState::Starting => ServiceHealth::Degraded("starting".to_string()),
+            ServiceState::Stopping => ServiceHealth::Degraded("stopping".to_string()),
+            ServiceState::Stopped => ServiceHealth::Unhealthy("capture stopped".to_string()),
+        }
     }
 }
 
-#[async_trait::async_trait]
-impl Service for MinimapService {
-    async fn start(&self) -> Result<(), ()> {
-        match self.start_capture().await {
-            Ok(_) => Ok(()),
-            Err(_) => Err(()),
-        }
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    async fn stop(&self) -> Result<(), ()> {
-        match self.stop_capture().await {
-            Ok(_) => Ok(()),
-            Err(_) => Err(()),
+    #[test]
+    fn test_service_state_transitions() {
+        use ServiceState::*;
+        let table = [
+            (Stopped, [(Stopped, false), (Starting, true), (Running, false), (Stopping, false)]),
+            (Starting, [(Stopped, true), (Starting, false), (Running, true), (Stopping, true)]),
+            (Running, [(Stopped, false), (Starting, false), (Running, false), (Stopping, true)]),
+            (Stopping, [(Stopped, true), (Starting, false), (Running, false), (Stopping, false)]),
+        ];
+        for (from, row) in table {
+            for (to, allowed) in row {
+                assert_eq!(from.can_transition_to(to), allowed, "{:?} -> {:?}", from, to);
+            }
         }
     }
 }
@@ -1,12 +1,266 @@
+// Only code needing OpenCV is gated behind the `opencv` feature. The minimap service runs the
+// OpenCV detectors and the chat monitor and session stats read OCR, so they and what acts on
+// the detections (automation, routes, behavior trees) are gated as a whole.
 
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+mod cpu_pool;
+mod duration_millis;
 mod graphics_capture;
+#[cfg(feature = "opencv")]
+pub mod automation;
+#[cfg(feature = "opencv")]
+pub mod bar_reader;
+#[cfg(feature = "opencv")]
+pub mod behavior_tree;
+#[cfg(feature = "opencv")]
+pub mod chat_monitor;
+#[cfg(all(feature = "server", feature = "opencv"))]
+pub mod control_api;
+#[cfg(feature = "opencv")]
+pub mod dataset_recorder;
+#[cfg(feature = "onnx")]
+pub mod detection;
+pub mod event_bus;
+pub mod frame_analyzer;
+pub mod frame_diff;
+pub mod frame_history;
+#[cfg(feature = "opencv")]
+pub mod game_state;
+pub mod hotkeys;
+#[cfg(feature = "server")]
+mod http_server;
+pub mod image_ops;
+pub mod input_broadcaster;
+pub mod input_recorder;
+pub mod input_scheduler;
+pub mod low_power;
+#[cfg(feature = "opencv")]
+pub mod map_builder;
+pub mod memory_budget;
+pub mod metrics;
+pub mod minimap_blips;
+pub mod minimap_events;
+pub mod minimap_locator;
+#[cfg(feature = "opencv")]
 pub mod minimap_v2;
+#[cfg(feature = "opencv")]
+pub mod motion_detector;
+#[cfg(feature = "notifications")]
+pub mod notifier;
+#[cfg(feature = "opencv")]
+pub mod ocr;
+#[cfg(feature = "opencv")]
+pub mod pipeline;
+pub mod plugin;
+pub mod player_arrow;
+#[cfg(feature = "server")]
+pub mod preview_server;
+pub mod probes;
+#[cfg(feature = "notifications")]
+pub mod remote_control;
+#[cfg(feature = "opencv")]
+pub mod route;
+pub mod scale_search;
+#[cfg(feature = "opencv")]
+pub mod scene_recognizer;
+pub mod scheduler;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(feature = "opencv")]
+pub mod session_stats;
+pub mod service_manager;
+pub mod shutdown;
+pub mod supervisor;
+pub mod thread_tuning;
+#[cfg(feature = "history")]
+pub mod session_history;
+pub mod session_recorder;
+pub mod session_replay;
+pub mod stuck_detector;
+pub mod template_matcher;
+pub mod vision;
 
-pub use graphics_capture::GraphicsCaptureService;
-pub use minimap_v2::{MinimapService as MinimapServiceV2, ServiceState};
+#[cfg(feature = "opencv")]
+pub use automation::{AutomationConfig, AutomationEngine, AutomationEvent, Condition, Rule, RuleAction, WorldState};
+#[cfg(feature = "opencv")]
+pub use bar_reader::{BarConfig, BarDirection, BarReader};
+#[cfg(feature = "opencv")]
+pub use behavior_tree::{BehaviorTree, BehaviorTreeRunner, Node as BehaviorNode, NodeStatus};
+#[cfg(feature = "opencv")]
+pub use chat_monitor::{ChatEvent, ChatKeyword, ChatMonitor, ChatMonitorConfig};
+#[cfg(feature = "opencv")]
+pub use dataset_recorder::{DatasetConfig, DatasetImageFormat, DatasetLabel, DatasetRecorder, DatasetSample, LabelSource};
+#[cfg(feature = "onnx")]
+pub use detection::{Detection, DetectionConfig, DetectionModelKind, DetectionService, ObjectDetector};
+#[cfg(all(feature = "server", feature = "opencv"))]
+pub use control_api::{ControlApi, ControlApiConfig, ControlStatus};
+pub use event_bus::{BotEvent, EventBus};
+pub use frame_analyzer::FrameAnalyzer;
+pub use frame_diff::FrameDiff;
+pub use frame_history::{FrameHistoryConfig, FrameHistoryService};
+#[cfg(feature = "opencv")]
+pub use game_state::{GameState, GameStateClassifier, GameStateRules, GameStateService, GameStateTransition, StateIndicator};
+pub use input_broadcaster::InputBroadcaster;
+pub use input_recorder::{InputMacro, InputRecorder, MacroPlayback, MacroPlayer};
+pub use low_power::{LowPowerMode, LOW_POWER_FPS};
+pub use memory_budget::{MemoryAccount, MemoryBudget};
+pub use input_scheduler::{release_all_keys, ActionHandle, InputAction, InputPriority, InputScheduler};
+pub use graphics_capture::{
+    CaptureSource, CaptureStats, CaptureTarget, CapturedFrame, GraphicsCaptureService, PixelFormat, SubscribeOptions,
+    CAPTURE_TARGET_FPS,
+};
+#[cfg(feature = "opencv")]
+pub use map_builder::{MapBuilder, MapBuilderConfig};
+pub use minimap_blips::{BlipClass, BlipRule, MinimapBlip};
+#[cfg(feature = "opencv")]
+pub use minimap_blips::BlipDetector;
+pub use minimap_events::MinimapEvent;
+pub use minimap_locator::MinimapLocator;
+#[cfg(feature = "opencv")]
+pub use motion_detector::{MotionConfig, MotionDetector, MotionEvent, MotionMethod, MotionRegion, MotionTracker};
+#[cfg(feature = "notifications")]
+pub use notifier::{Notifier, NotifierConfig, NotifyTrigger};
+#[cfg(feature = "opencv")]
+pub use ocr::{OcrConfig, OcrEngineKind, OcrReading, OcrRegion, OcrService};
+#[cfg(feature = "opencv")]
+pub use pipeline::{
+    Annotation, EncodeFormat, FrameContext, Pipeline, PipelineBuilder, PipelineConfig, PipelineStage, Preprocess,
+    PreprocessColor, StageConfig, StageStats,
+};
+pub use plugin::{Plugin, PluginContext, PluginCreate, PluginRegistry};
+pub use player_arrow::{PlayerArrowConfig, PlayerPosition};
+#[cfg(feature = "opencv")]
+pub use player_arrow::PlayerArrowDetector;
+#[cfg(feature = "server")]
+pub use preview_server::{PreviewServer, PreviewServerConfig};
+pub use probes::{Probe, ProbeCondition, ProbeConfig, ProbeEvent, ProbeService, ProbeSet, ProbeState};
+#[cfg(feature = "notifications")]
+pub use remote_control::{RemoteControl, RemoteControlConfig};
+#[cfg(feature = "opencv")]
+pub use route::{MovementKeys, MovementMode, Route, RouteConfig, RouteEvent, RouteService, RouteStatus, Waypoint};
+pub use scale_search::{ScaleSearch, ScaledMatch};
+#[cfg(feature = "opencv")]
+pub use scene_recognizer::{SceneMatch, SceneRecognizer};
+pub use scheduler::{CronExpression, Job, JobAction, Scheduler, SchedulerConfig, SchedulerEvent, Timing};
+#[cfg(feature = "scripting")]
+pub use scripting::{Script, ScriptHost, ScriptService};
+#[cfg(feature = "opencv")]
+pub use session_stats::{
+    CounterStats, SessionReport, SessionStats, SessionStatsConfig, StatCounter, StatDelta, StatKind,
+};
+pub use stuck_detector::{PlayerStuck, RecoveryAction, StuckConfig, StuckDetector, StuckRecovery};
+pub use template_matcher::{TemplateEvent, TemplateLibrary, TemplateMatch, TemplateMatcher, TemplateSettings};
+pub use service_manager::{
+    ManagedServiceStatus, ManagerStatus, ServiceError, ServiceHealth, ServiceManager, ServiceStatus,
+};
+pub use shutdown::{Shutdown, SHUTDOWN_STEP_TIMEOUT};
+pub use supervisor::{supervise, supervise_blocking, supervise_blocking_until, RestartPolicy};
+#[cfg(feature = "history")]
+pub use session_history::{CounterPoint, SessionHistory, SessionStore, SessionSummary};
+pub use session_recorder::{SessionHeader, SessionReader, SessionRecord, SessionRecorder, SessionRecorderConfig};
+pub use session_replay::{ReplayControl, ReplaySource};
+pub use vision::{rgb_to_hsv, MaxSize, Rect};
+#[cfg(feature = "opencv")]
+pub use minimap_v2::{MinimapService as MinimapServiceV2, MinimapSettings, MinimapStats, PerformanceStats, ServiceState};
 
 #[async_trait::async_trait]
 pub trait Service: Send + Sync {
-  async fn start(&self) -> Result<(), ()>;
-  async fn stop(&self) -> Result<(), ()>;
+  async fn start(&self) -> Result<(), ServiceError>;
+  async fn stop(&self) -> Result<(), ServiceError>;
+
+  /// Name the service is registered and depended on under, the type name by default
+  fn name(&self) -> &str {
+    let name = std::any::type_name::<Self>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+  }
+
+  /// Names of the services that have to run before this one
+  fn dependencies(&self) -> Vec<String> {
+    Vec::new()
+  }
+
+  /// Lifecycle state, for services that track it themselves
+  async fn status(&self) -> ServiceStatus {
+    ServiceStatus::Unknown
+  }
+
+  /// Only asked while the service is running
+  async fn health_check(&self) -> ServiceHealth {
+    ServiceHealth::Healthy
+  }
+}
+
+/// Background task of a service, at most one runs at a time
+///
+/// The task is handed a token that is cancelled on [`ServiceTask::stop`] and has to return
+/// soon after, typically by selecting on it next to its receiver:
+///
+/// ```ignore
+/// task.start(|cancelled| async move {
+///     loop {
+///         let event = tokio::select! {
+///             _ = cancelled.cancelled() => break,
+///             event = receiver.recv() => event,
+///         };
+///         // ...
+///     }
+/// })
+/// .await;
+/// ```
+#[derive(Clone, Default)]
+pub struct ServiceTask {
+    task: Arc<Mutex<Option<(JoinHandle<()>, CancellationToken)>>>,
+}
+
+impl ServiceTask {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the task is running, one that returned on its own isn't
+    pub async fn is_busy(&self) -> bool {
+        Self::is_alive(&self.task.lock().await)
+    }
+
+    /// Spawn the future `task` makes unless one is still running, `false` if one was. `task`
+    /// is only called when spawning.
+    pub async fn start<F, Fut>(&self, task: F) -> bool
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let mut current = self.task.lock().await;
+        if Self::is_alive(&current) {
+            return false;
+        }
+        let token = CancellationToken::new();
+        let handle = tokio::spawn(task(token.clone()));
+        *current = Some((handle, token));
+        true
+    }
+
+    /// Cancel the task and wait for it to return, `false` if none was started. Starting waits
+    /// until it has, so an old task never runs next to a new one.
+    pub async fn stop(&self) -> bool {
+        let mut current = self.task.lock().await;
+        let Some((handle, token)) = current.take() else {
+            return false;
+        };
+        token.cancel();
+        if let Err(e) = handle.await {
+            tracing::warn!(error = %e, "Service task failed");
+        }
+        true
+    }
+
+    fn is_alive(task: &Option<(JoinHandle<()>, CancellationToken)>) -> bool {
+        task.as_ref().is_some_and(|(handle, _)| !handle.is_finished())
+    }
 }
@@ -0,0 +1,325 @@
+use std::sync::{Arc, Mutex as StdMutex};
+
+use opencv::{
+    core::{absdiff, no_array, Mat, Point, Size, Vector, BORDER_CONSTANT, CV_32F},
+    imgproc::{
+        accumulate_weighted, bounding_rect, contour_area_def, dilate, find_contours_def, gaussian_blur_def,
+        moments_def, morphology_default_border_value, threshold, CHAIN_APPROX_SIMPLE, RETR_EXTERNAL, THRESH_BINARY,
+    },
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::services::{Service, ServiceError, ServiceTask};
+use super::cpu_pool::spawn_cpu;
+use super::graphics_capture::{CapturedFrame, GraphicsCaptureService};
+use super::low_power::LowPowerMode;
+use super::vision::{bgra_mat, bgra_mat_region, to_gray, Rect};
+
+/// Capacity of the event channel, slow subscribers skip old events
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+/// How the current frame is compared to find moving pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MotionMethod {
+    /// Difference to the previous frame, reacts to any change but only at moving edges
+    FrameDifference,
+    /// Difference to a running average of past frames, finds whole moving objects and
+    /// ignores slow lighting changes. Higher `learning_rate` (0.0 - 1.0) adapts faster.
+    Background { learning_rate: f64 },
+}
+
+impl Default for MotionMethod {
+    fn default() -> Self {
+        MotionMethod::Background { learning_rate: 0.05 }
+    }
+}
+
+/// Where and how motion is detected
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MotionConfig {
+    /// Only look at this part of the frame, e.g. the water around a fishing bobber
+    pub roi: Option<Rect>,
+    pub method: MotionMethod,
+    /// Per-pixel gray level difference (0 - 255) that counts as changed
+    pub threshold: u8,
+    /// Changed blobs smaller than this many pixels are ignored as noise
+    pub min_area: f64,
+}
+
+impl Default for MotionConfig {
+    fn default() -> Self {
+        Self {
+            roi: None,
+            method: MotionMethod::default(),
+            threshold: 25,
+            min_area: 50.0,
+        }
+    }
+}
+
+/// A moving blob
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MotionRegion {
+    /// Bounding box in frame pixels
+    pub rect: Rect,
+    /// Changed pixels in the blob
+    pub area: f64,
+}
+
+/// Motion found in a frame, all coordinates are frame pixels
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MotionEvent {
+    pub regions: Vec<MotionRegion>,
+    /// Sum of the region areas
+    pub total_area: f64,
+    /// Area-weighted center of all regions
+    pub centroid: (f32, f32),
+}
+
+/// Frame to frame motion state for one ROI
+#[derive(Debug, Default)]
+pub struct MotionTracker {
+    config: MotionConfig,
+    /// Previous blurred frame or the running average, depending on the method
+    reference: Option<Mat>,
+}
+
+impl MotionTracker {
+    pub fn new(config: MotionConfig) -> Self {
+        Self {
+            config,
+            reference: None,
+        }
+    }
+
+    pub fn config(&self) -> MotionConfig {
+        self.config
+    }
+
+    /// Change the configuration, the next frame becomes the new reference
+    pub fn set_config(&mut self, config: MotionConfig) {
+        self.config = config;
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.reference = None;
+    }
+
+    /// Compare a frame to the reference, `None` if nothing moved
+    pub fn process(&mut self, frame: &CapturedFrame) -> Result<Option<MotionEvent>, String> {
+        let roi = self
+            .config
+            .roi
+            .and_then(|roi| roi.clamp_to(frame.width as i32, frame.height as i32));
+        let (bgra, offset) = match roi {
+            Some(roi) => (bgra_mat_region(frame, roi)?, (roi.x, roi.y)),
+            None => (bgra_mat(frame)?, (0, 0)),
+        };
+
+        let mut blurred = Mat::default();
+        gaussian_blur_def(&to_gray(&bgra)?, &mut blurred, Size::new(5, 5), 0.0)
+            .map_err(|e| format!("Failed to blur frame: {}", e))?;
+
+        let size = blurred.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
+        let reference = match self.reference.take() {
+            Some(reference) if reference.size().is_ok_and(|reference| reference == size) => reference,
+            // First frame or the ROI changed size, nothing to compare against yet
+            _ => {
+                self.reference = Some(Self::new_reference(&blurred, self.config.method)?);
+                return Ok(None);
+            }
+        };
+
+        let mut diff = Mat::default();
+        match self.config.method {
+            MotionMethod::FrameDifference => {
+                absdiff(&blurred, &reference, &mut diff).map_err(|e| format!("Failed to diff frames: {}", e))?;
+                self.reference = Some(blurred);
+            }
+            MotionMethod::Background { learning_rate } => {
+                let mut background = Mat::default();
+                reference
+                    .convert_to(&mut background, blurred.typ(), 1.0, 0.0)
+                    .map_err(|e| format!("Failed to convert background: {}", e))?;
+                absdiff(&blurred, &background, &mut diff).map_err(|e| format!("Failed to diff frames: {}", e))?;
+
+                let mut reference = reference;
+                accumulate_weighted(&blurred, &mut reference, learning_rate.clamp(0.0, 1.0), &no_array())
+                    .map_err(|e| format!("Failed to update background: {}", e))?;
+                self.reference = Some(reference);
+            }
+        }
+
+        let mut mask = Mat::default();
+        threshold(&diff, &mut mask, self.config.threshold as f64, 255.0, THRESH_BINARY)
+            .map_err(|e| format!("Failed to threshold difference: {}", e))?;
+        // Join the fragments of one moving object
+        let mut dilated = Mat::default();
+        dilate(
+            &mask,
+            &mut dilated,
+            &Mat::default(),
+            Point::new(-1, -1),
+            2,
+            BORDER_CONSTANT,
+            morphology_default_border_value().map_err(|e| format!("Failed to dilate mask: {}", e))?,
+        )
+        .map_err(|e| format!("Failed to dilate mask: {}", e))?;
+
+        Self::collect(&dilated, self.config.min_area, offset)
+    }
+
+    fn new_reference(blurred: &Mat, method: MotionMethod) -> Result<Mat, String> {
+        match method {
+            MotionMethod::FrameDifference => blurred.try_clone().map_err(|e| format!("Failed to copy frame: {}", e)),
+            // The running average needs float precision to accumulate small weights
+            MotionMethod::Background { .. } => {
+                let mut reference = Mat::default();
+                blurred
+                    .convert_to(&mut reference, CV_32F, 1.0, 0.0)
+                    .map_err(|e| format!("Failed to convert frame: {}", e))?;
+                Ok(reference)
+            }
+        }
+    }
+
+    fn collect(mask: &Mat, min_area: f64, offset: (i32, i32)) -> Result<Option<MotionEvent>, String> {
+        let mut contours = Vector::<Vector<Point>>::new();
+        find_contours_def(mask, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_SIMPLE)
+            .map_err(|e| format!("Failed to find contours: {}", e))?;
+
+        let mut regions = Vec::new();
+        let (mut sum_x, mut sum_y, mut total_area) = (0.0, 0.0, 0.0);
+        for contour in contours {
+            let area = contour_area_def(&contour).map_err(|e| format!("Failed to measure contour: {}", e))?;
+            if area < min_area {
+                continue;
+            }
+            let moments = moments_def(&contour).map_err(|e| format!("Failed to compute moments: {}", e))?;
+            if moments.m00 <= 0.0 {
+                continue;
+            }
+            sum_x += moments.m10;
+            sum_y += moments.m01;
+            total_area += moments.m00;
+
+            let rect: Rect = bounding_rect(&contour)
+                .map_err(|e| format!("Failed to get bounding rect: {}", e))?
+                .into();
+            regions.push(MotionRegion {
+                rect: Rect::new(rect.x + offset.0, rect.y + offset.1, rect.width, rect.height),
+                area,
+            });
+        }
+
+        if regions.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(MotionEvent {
+            regions,
+            total_area,
+            centroid: (
+                (sum_x / total_area) as f32 + offset.0 as f32,
+                (sum_y / total_area) as f32 + offset.1 as f32,
+            ),
+        }))
+    }
+}
+
+/// Watches captured frames for movement and publishes where it happens
+#[derive(Clone)]
+pub struct MotionDetector {
+    graphics_service: Arc<GraphicsCaptureService>,
+    tracker: Arc<StdMutex<MotionTracker>>,
+    event_sender: broadcast::Sender<MotionEvent>,
+    task: ServiceTask,
+}
+
+impl MotionDetector {
+    pub fn new(graphics_service: Arc<GraphicsCaptureService>, config: MotionConfig) -> Self {
+        Self {
+            graphics_service,
+            tracker: Arc::new(StdMutex::new(MotionTracker::new(config))),
+            event_sender: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            task: ServiceTask::new(),
+        }
+    }
+
+    /// Events of frames that contain motion
+    pub fn subscribe(&self) -> broadcast::Receiver<MotionEvent> {
+        self.event_sender.subscribe()
+    }
+
+    pub fn config(&self) -> MotionConfig {
+        self.tracker.lock().unwrap().config()
+    }
+
+    pub fn set_config(&self, config: MotionConfig) {
+        self.tracker.lock().unwrap().set_config(config);
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.task.is_busy().await
+    }
+
+    pub async fn start_detection(&self) -> Result<(), String> {
+        let mut receiver = self.graphics_service.subscribe();
+        let tracker = self.tracker.clone();
+        tracker.lock().unwrap().reset();
+        let event_sender = self.event_sender.clone();
+
+        self.task.start(move |cancelled| async move {
+            loop {
+                // Stopping doesn't wait for the next frame
+                let frame = tokio::select! {
+                    _ = cancelled.cancelled() => break,
+                    frame = receiver.recv() => frame,
+                };
+                match frame {
+                    Ok(frame) => {
+                        if LowPowerMode::global().is_enabled() {
+                            continue;
+                        }
+                        let tracker = tracker.clone();
+                        let result = spawn_cpu(move || tracker.lock().unwrap().process(&frame)).await;
+                        match result {
+                            Ok(Ok(Some(event))) => {
+                                let _ = event_sender.send(event);
+                            }
+                            Ok(Ok(None)) => {}
+                            Ok(Err(e)) => tracing::warn!(error = %e, "Motion detection failed"),
+                            Err(e) => tracing::error!(error = %e, "Motion detection task failed"),
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Stop detecting, the frame in progress is finished before this returns
+    pub async fn stop_detection(&self) {
+        self.task.stop().await;
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for MotionDetector {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_detection().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_detection().await;
+        Ok(())
+    }
+}
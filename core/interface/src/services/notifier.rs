@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use reqwest::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::{broadcast, watch};
+
+use crate::services::{Service, ServiceError, ServiceTask};
+use super::event_bus::{BotEvent, EventBus};
+use super::image_ops;
+
+/// Longest a webhook request may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Event worth a notification
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "on", rename_all = "snake_case")]
+pub enum NotifyTrigger {
+    /// The bot stopped on its own: the window was lost or a service failed
+    BotStopped,
+    /// The game state classifier switched to `state`, e.g. `dead`
+    GameState { state: String },
+    /// The template named `template` showed up, e.g. a rare item
+    Template { template: String },
+    /// The chat keyword named `keyword` showed up, e.g. one matching whispers
+    ChatKeyword { keyword: String },
+}
+
+impl NotifyTrigger {
+    /// Message to post if `event` fires this trigger
+    pub fn message(&self, event: &BotEvent) -> Option<String> {
+        match (self, event) {
+            (NotifyTrigger::BotStopped, BotEvent::WindowLost { reason }) => {
+                Some(format!("Bot stopped, window lost: {}", reason))
+            }
+            (NotifyTrigger::BotStopped, BotEvent::ServiceFailed { service, error }) => {
+                Some(format!("{} failed: {}", service, error))
+            }
+            (NotifyTrigger::GameState { state }, BotEvent::GameStateChanged { state: changed }) if state == changed => {
+                Some(format!("Game state changed to {}", changed))
+            }
+            (NotifyTrigger::Template { template }, BotEvent::TemplateMatched { template: matched, score })
+                if template == matched =>
+            {
+                Some(format!("Spotted {} ({:.0}%)", matched, score * 100.0))
+            }
+            (NotifyTrigger::ChatKeyword { keyword }, BotEvent::ChatKeyword { keyword: seen, line, .. })
+                if keyword == seen =>
+            {
+                Some(format!("Chat: {}", line))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Where notifications are posted and for what, persisted across restarts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotifierConfig {
+    /// Discord webhook URL, nothing is posted while it is empty
+    pub webhook_url: String,
+    pub triggers: Vec<NotifyTrigger>,
+    /// Attach the current minimap to every notification
+    pub attach_minimap: bool,
+    /// Minimum time between two notifications of the same trigger
+    #[serde(with = "super::duration_millis")]
+    pub cooldown: Duration,
+}
+
+impl Default for NotifierConfig {
+    fn default() -> Self {
+        Self {
+            webhook_url: String::new(),
+            triggers: vec![
+                NotifyTrigger::BotStopped,
+                NotifyTrigger::GameState {
+                    state: "dead".to_string(),
+                },
+            ],
+            attach_minimap: true,
+            cooldown: Duration::from_secs(60),
+        }
+    }
+}
+
+impl NotifierConfig {
+    pub fn path() -> PathBuf {
+        crate::config_dir().join("notifier.json")
+    }
+
+    /// Load the saved settings, falling back to defaults if there are none
+    pub fn load() -> Self {
+        let path = Self::path();
+        let Ok(json) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&json).unwrap_or_else(|e| {
+            tracing::warn!(path = %path.display(), error = %e, "Ignoring invalid notifier settings");
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize notifier settings: {}", e))?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.webhook_url.is_empty()
+    }
+}
+
+/// Posts selected bot events to a Discord webhook, so unattended runs raise push alerts
+#[derive(Clone)]
+pub struct Notifier {
+    config: Arc<NotifierConfig>,
+    client: reqwest::Client,
+    minimap: Option<watch::Receiver<Option<Vec<u8>>>>,
+    task: ServiceTask,
+}
+
+impl Notifier {
+    pub fn new(config: NotifierConfig) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        Ok(Self {
+            config: Arc::new(config),
+            client,
+            minimap: None,
+            task: ServiceTask::new(),
+        })
+    }
+
+    /// Attach the latest minimap from `minimap` (encoded previews, e.g. `MinimapService::get_frame_receiver`)
+    pub fn with_minimap(mut self, minimap: watch::Receiver<Option<Vec<u8>>>) -> Self {
+        self.minimap = Some(minimap);
+        self
+    }
+
+    pub fn config(&self) -> &NotifierConfig {
+        &self.config
+    }
+
+    /// Current minimap if attaching it is turned on and one was captured
+    fn snapshot(&self) -> Option<Vec<u8>> {
+        if !self.config.attach_minimap {
+            return None;
+        }
+        self.minimap.as_ref().and_then(|minimap| minimap.borrow().clone())
+    }
+
+    /// Post `message` to the webhook, with the current minimap if configured
+    pub async fn send(&self, message: &str) -> Result<(), String> {
+        self.post(message, self.snapshot()).await
+    }
+
+    /// Post `message` with `image` (an encoded image or raw RGBA preview) attached
+    pub async fn post(&self, message: &str, image: Option<Vec<u8>>) -> Result<(), String> {
+        if !self.config.is_enabled() {
+            return Err("No webhook configured".to_string());
+        }
+        let request = self.client.post(&self.config.webhook_url);
+        let request = match image {
+            Some(image) => {
+                let (image, format) = Self::attachment(image)?;
+                let filename = format!("minimap.{}", format.extensions_str()[0]);
+                let payload = json!({
+                    "content": message,
+                    "attachments": [{ "id": 0, "filename": filename }],
+                });
+                let file = Part::bytes(image)
+                    .file_name(filename)
+                    .mime_str(format.to_mime_type())
+                    .map_err(|e| format!("Failed to attach minimap: {}", e))?;
+                request.multipart(Form::new().text("payload_json", payload.to_string()).part("files[0]", file))
+            }
+            None => request.json(&json!({ "content": message })),
+        };
+        request
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to post notification: {}", e))
+    }
+
+    /// Webhooks need an image file, raw RGBA previews are converted to PNG
+    fn attachment(image: Vec<u8>) -> Result<(Vec<u8>, image::ImageFormat), String> {
+        if let Ok(format) = image::guess_format(&image) {
+            return Ok((image, format));
+        }
+        let (width, height, pixels) =
+            image_ops::decode_raw_rgba(&image).ok_or_else(|| "Minimap is not an image".to_string())?;
+        let rgb: Vec<u8> = pixels.chunks_exact(4).flat_map(|rgba| [rgba[0], rgba[1], rgba[2]]).collect();
+        let rgb = image::RgbImage::from_raw(width, height, rgb).ok_or_else(|| "Failed to create RGB image".to_string())?;
+        Ok((image_ops::encode_png(&rgb)?, image::ImageFormat::Png))
+    }
+
+    pub async fn start_notifier(&self) -> Result<(), String> {
+        if !self.config.is_enabled() {
+            return Err("No webhook configured".to_string());
+        }
+
+        let mut events = EventBus::global().subscribe();
+        let notifier = self.clone();
+
+        self.task.start(move |cancelled| async move {
+            // Last notification per trigger index
+            let mut last_sent: HashMap<usize, Instant> = HashMap::new();
+
+            loop {
+                // Stopping doesn't wait for the next event
+                let event = tokio::select! {
+                    _ = cancelled.cancelled() => break,
+                    event = events.recv() => event,
+                };
+                let event = match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                for (i, trigger) in notifier.config.triggers.iter().enumerate() {
+                    let Some(message) = trigger.message(&event) else {
+                        continue;
+                    };
+                    let now = Instant::now();
+                    if last_sent.get(&i).is_some_and(|last| now.duration_since(*last) < notifier.config.cooldown) {
+                        continue;
+                    }
+                    last_sent.insert(i, now);
+
+                    // Posting must not hold up the events behind this one
+                    let notifier = notifier.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = notifier.send(&message).await {
+                            tracing::warn!(error = %e, "Notification failed");
+                        }
+                    });
+                }
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Stop posting, notifications already being posted still go out
+    pub async fn stop_notifier(&self) {
+        self.task.stop().await;
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for Notifier {
+    async fn start(&self) -> Result<(), ServiceError> {
+        if !self.config.is_enabled() {
+            return Err(ServiceError::NotConfigured("no webhook URL".to_string()));
+        }
+        self.start_notifier().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_notifier().await;
+        Ok(())
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use opencv::{
+    core::{Mat, Size},
+    imgproc::{resize, INTER_CUBIC},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::services::{Service, ServiceError, ServiceTask};
+use super::cpu_pool::spawn_cpu;
+use super::graphics_capture::{CapturedFrame, GraphicsCaptureService};
+use super::low_power::LowPowerMode;
+use super::vision::{bgra_mat_region, Rect};
+
+/// Capacity of the reading channel, slow subscribers skip old readings
+const READING_CHANNEL_CAPACITY: usize = 64;
+
+/// Engine used to recognize text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OcrEngineKind {
+    /// Windows.Media.Ocr, needs an OCR language pack but nothing else. Reports no confidence.
+    #[default]
+    Windows,
+    /// The `tesseract` command line tool, which must be on `PATH`
+    #[cfg(feature = "tesseract")]
+    Tesseract,
+}
+
+/// A screen region text is read from, e.g. the HP bar label or the coordinates display
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OcrRegion {
+    /// Name readings of this region are published under
+    pub name: String,
+    /// Region in frame pixels
+    pub rect: Rect,
+    /// Upscale factor applied before recognition, small game fonts need 2 - 3
+    #[serde(default = "OcrRegion::default_scale")]
+    pub scale: f64,
+}
+
+impl OcrRegion {
+    pub fn new(name: impl Into<String>, rect: Rect) -> Self {
+        Self {
+            name: name.into(),
+            rect,
+            scale: Self::default_scale(),
+        }
+    }
+
+    fn default_scale() -> f64 {
+        2.0
+    }
+}
+
+/// Which regions are read, how often and with which engine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OcrConfig {
+    pub regions: Vec<OcrRegion>,
+    /// Time between two reads of all regions, in milliseconds when serialized
+    #[serde(with = "super::duration_millis")]
+    pub interval: Duration,
+    pub engine: OcrEngineKind,
+}
+
+impl Default for OcrConfig {
+    fn default() -> Self {
+        Self {
+            regions: Vec::new(),
+            interval: Duration::from_millis(500),
+            engine: OcrEngineKind::default(),
+        }
+    }
+}
+
+/// Text recognized in a region
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OcrReading {
+    /// Name of the [`OcrRegion`] this was read from
+    pub region: String,
+    /// Recognized lines joined with `\n`
+    pub text: String,
+    /// Mean word confidence between 0 and 1, `None` if the engine doesn't report one
+    pub confidence: Option<f32>,
+}
+
+impl OcrReading {
+    /// All numbers in the text, e.g. `[1200.0, 3400.0]` for `HP 1,200 / 3,400`
+    pub fn numbers(&self) -> Vec<f64> {
+        let mut numbers = Vec::new();
+        let mut current = String::new();
+        for c in self.text.chars().chain(std::iter::once(' ')) {
+            match c {
+                '0'..='9' => current.push(c),
+                // Thousands separators inside a number
+                ',' if !current.is_empty() => {}
+                '.' if !current.is_empty() && !current.contains('.') => current.push(c),
+                _ => {
+                    if let Ok(number) = current.trim_end_matches('.').parse() {
+                        numbers.push(number);
+                    }
+                    current.clear();
+                }
+            }
+        }
+        numbers
+    }
+
+    /// The first two numbers as `current / max`, for bar labels like `1200/3400`
+    pub fn ratio(&self) -> Option<f64> {
+        match self.numbers()[..] {
+            [current, max, ..] if max > 0.0 => Some(current / max),
+            _ => None,
+        }
+    }
+}
+
+/// Reads text from configured screen regions at a fixed rate and publishes it
+#[derive(Clone)]
+pub struct OcrService {
+    graphics_service: Arc<GraphicsCaptureService>,
+    config: Arc<StdMutex<OcrConfig>>,
+    latest: Arc<StdMutex<HashMap<String, OcrReading>>>,
+    reading_sender: broadcast::Sender<OcrReading>,
+    task: ServiceTask,
+}
+
+impl OcrService {
+    pub fn new(graphics_service: Arc<GraphicsCaptureService>, config: OcrConfig) -> Self {
+        Self {
+            graphics_service,
+            config: Arc::new(StdMutex::new(config)),
+            latest: Arc::new(StdMutex::new(HashMap::new())),
+            reading_sender: broadcast::channel(READING_CHANNEL_CAPACITY).0,
+            task: ServiceTask::new(),
+        }
+    }
+
+    /// Readings of every region, published on each read
+    pub fn subscribe(&self) -> broadcast::Receiver<OcrReading> {
+        self.reading_sender.subscribe()
+    }
+
+    /// Last reading of a region
+    pub fn latest(&self, region: &str) -> Option<OcrReading> {
+        self.latest.lock().unwrap().get(region).cloned()
+    }
+
+    pub fn config(&self) -> OcrConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Replace the configuration, applied from the next read on
+    pub fn set_config(&self, config: OcrConfig) {
+        let mut latest = self.latest.lock().unwrap();
+        latest.retain(|name, _| config.regions.iter().any(|region| &region.name == name));
+        *self.config.lock().unwrap() = config;
+    }
+
+    /// Add a region or replace the one with the same name
+    pub fn set_region(&self, region: OcrRegion) {
+        let mut config = self.config.lock().unwrap();
+        match config.regions.iter_mut().find(|r| r.name == region.name) {
+            Some(existing) => *existing = region,
+            None => config.regions.push(region),
+        }
+    }
+
+    pub fn remove_region(&self, name: &str) {
+        self.config.lock().unwrap().regions.retain(|region| region.name != name);
+        self.latest.lock().unwrap().remove(name);
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.task.is_busy().await
+    }
+
+    pub async fn start_ocr(&self) -> Result<(), String> {
+        let mut receiver = self.graphics_service.subscribe();
+        let config = self.config.clone();
+        let latest = self.latest.clone();
+        let reading_sender = self.reading_sender.clone();
+
+        self.task.start(move |cancelled| async move {
+            let mut last_read: Option<Instant> = None;
+
+            loop {
+                // Stopping doesn't wait for the next frame
+                let frame = tokio::select! {
+                    _ = cancelled.cancelled() => break,
+                    frame = receiver.recv() => frame,
+                };
+                match frame {
+                    Ok(frame) => {
+                        if LowPowerMode::global().is_enabled() {
+                            continue;
+                        }
+                        let current = config.lock().unwrap().clone();
+                        if current.regions.is_empty()
+                            || last_read.is_some_and(|last| frame.timestamp.duration_since(last) < current.interval)
+                        {
+                            continue;
+                        }
+                        last_read = Some(frame.timestamp);
+
+                        // Recognition blocks for tens of milliseconds per region
+                        let readings = spawn_cpu(move || Self::read_regions(&frame, &current)).await;
+                        let Ok(readings) = readings else {
+                            continue;
+                        };
+
+                        for reading in readings {
+                            match reading {
+                                Ok(reading) => {
+                                    latest.lock().unwrap().insert(reading.region.clone(), reading.clone());
+                                    let _ = reading_sender.send(reading);
+                                }
+                                Err(e) => tracing::warn!(error = %e, "OCR failed"),
+                            }
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Stop reading, a read in progress is finished before this returns
+    pub async fn stop_ocr(&self) {
+        self.task.stop().await;
+    }
+
+    fn read_regions(frame: &CapturedFrame, config: &OcrConfig) -> Vec<Result<OcrReading, String>> {
+        config
+            .regions
+            .iter()
+            .map(|region| {
+                let mat = Self::prepare(frame, region)?;
+                let (text, confidence) = Self::recognize(&mat, config.engine)?;
+                Ok(OcrReading {
+                    region: region.name.clone(),
+                    text,
+                    confidence,
+                })
+            })
+            .collect()
+    }
+
+    fn prepare(frame: &CapturedFrame, region: &OcrRegion) -> Result<Mat, String> {
+        let mat = bgra_mat_region(frame, region.rect)?;
+        if (region.scale - 1.0).abs() < f64::EPSILON || region.scale <= 0.0 {
+            return Ok(mat);
+        }
+
+        let mut scaled = Mat::default();
+        resize(&mat, &mut scaled, Size::default(), region.scale, region.scale, INTER_CUBIC)
+            .map_err(|e| format!("Failed to scale region {}: {}", region.name, e))?;
+        Ok(scaled)
+    }
+
+    fn recognize(bgra: &Mat, engine: OcrEngineKind) -> Result<(String, Option<f32>), String> {
+        match engine {
+            OcrEngineKind::Windows => {
+                let size = bgra.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
+                let data = bgra.data_bytes().map_err(|e| format!("Failed to access Mat data: {}", e))?;
+                let lines = platforms::ocr::recognize_text(data, size.width as u32, size.height as u32)
+                    .map_err(|e| format!("Windows OCR failed: {}", e))?;
+                let text = lines.into_iter().map(|line| line.text).collect::<Vec<_>>().join("\n");
+                Ok((text, None))
+            }
+            #[cfg(feature = "tesseract")]
+            OcrEngineKind::Tesseract => tesseract::recognize(bgra),
+        }
+    }
+}
+
+#[cfg(feature = "tesseract")]
+mod tesseract {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    use opencv::{
+        core::{Mat, Vector},
+        imgcodecs::imencode,
+    };
+
+    /// Run `tesseract` on a BGRA image, returning the text and mean word confidence
+    pub fn recognize(bgra: &Mat) -> Result<(String, Option<f32>), String> {
+        let mut png = Vector::<u8>::new();
+        imencode(".png", bgra, &mut png, &Vector::new()).map_err(|e| format!("Failed to encode PNG: {}", e))?;
+
+        // Page segmentation mode 6 treats the region as a single block of text
+        let mut child = Command::new("tesseract")
+            .args(["stdin", "stdout", "--psm", "6", "tsv"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to spawn tesseract: {}", e))?;
+        child
+            .stdin
+            .take()
+            .ok_or_else(|| "tesseract stdin is not available".to_string())?
+            .write_all(png.as_slice())
+            .map_err(|e| format!("Failed to write to tesseract: {}", e))?;
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to wait for tesseract: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("tesseract exited with {}", output.status));
+        }
+
+        Ok(parse_tsv(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Join the words of tesseract's TSV output into lines
+    ///
+    /// Columns are `level page_num block_num par_num line_num word_num left top width height conf
+    /// text`, only word rows (level 5) carry text and a non-negative confidence.
+    fn parse_tsv(tsv: &str) -> (String, Option<f32>) {
+        let mut lines: Vec<String> = Vec::new();
+        let mut last_line = None;
+        let mut confidences = Vec::new();
+
+        for row in tsv.lines().skip(1) {
+            let columns: Vec<&str> = row.split('\t').collect();
+            if columns.len() < 12 || columns[0] != "5" {
+                continue;
+            }
+            let text = columns[11].trim();
+            let Ok(confidence) = columns[10].parse::<f32>() else {
+                continue;
+            };
+            if text.is_empty() || confidence < 0.0 {
+                continue;
+            }
+
+            let line = (columns[2], columns[3], columns[4]);
+            match lines.last_mut() {
+                Some(current) if last_line == Some(line) => {
+                    current.push(' ');
+                    current.push_str(text);
+                }
+                _ => lines.push(text.to_string()),
+            }
+            last_line = Some(line);
+            confidences.push(confidence / 100.0);
+        }
+
+        let confidence = if confidences.is_empty() {
+            None
+        } else {
+            Some(confidences.iter().sum::<f32>() / confidences.len() as f32)
+        };
+        (lines.join("\n"), confidence)
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for OcrService {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_ocr().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_ocr().await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(text: &str) -> OcrReading {
+        OcrReading {
+            region: "test".to_string(),
+            text: text.to_string(),
+            confidence: None,
+        }
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eq!(reading("HP 1,200 / 3,400").numbers(), [1200.0, 3400.0]);
+        assert_eq!(reading("12.5% done").numbers(), [12.5]);
+        assert_eq!(reading("Level 7.").numbers(), [7.0]);
+        assert_eq!(reading("1.2.3").numbers(), [1.2, 3.0]);
+        assert_eq!(reading(", 5,").numbers(), [5.0]);
+        assert!(reading("no numbers").numbers().is_empty());
+    }
+
+    #[test]
+    fn test_ratio() {
+        assert_eq!(reading("1200/3400").ratio(), Some(1200.0 / 3400.0));
+        assert_eq!(reading("0/0").ratio(), None);
+        assert_eq!(reading("HP 5").ratio(), None);
+    }
+}
@@ -0,0 +1,823 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use opencv::{
+    core::{Mat, Point, Rect as CvRect, Scalar, Size, Vector},
+    imgcodecs::{imencode, IMWRITE_JPEG_QUALITY, IMWRITE_PNG_COMPRESSION, IMWRITE_WEBP_QUALITY},
+    imgproc::{
+        circle, cvt_color_def, gaussian_blur_def, line, put_text, rectangle, resize, threshold, COLOR_BGR2RGBA,
+        COLOR_BGRA2BGR, COLOR_BGRA2GRAY, COLOR_BGRA2RGBA, COLOR_GRAY2RGBA, FONT_HERSHEY_SIMPLEX, INTER_AREA, INTER_LINEAR, LINE_AA, THRESH_BINARY,
+    },
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use super::graphics_capture::CapturedFrame;
+use super::image_ops::encode_raw_rgba;
+use super::metrics::{LatencyPercentiles, LatencyTracker};
+use super::vision::{bgra_mat, bgra_mat_region, crop, to_gray, to_hsv, MaxSize, Rect};
+
+/// Image and results handed from stage to stage while processing one frame
+pub struct FrameContext {
+    image: Mat,
+    /// Position of `image` in the captured frame and its scale relative to it
+    origin: (i32, i32),
+    scale: f64,
+    /// Height of the whole captured frame
+    frame_height: i32,
+    focus: Option<Rect>,
+    /// Preprocessed copies of the focused region, shared by the detectors asking for the same one
+    preprocessed: Vec<(Preprocess, Mat)>,
+    skip_detection: bool,
+    annotations: Vec<Annotation>,
+    encoded: Option<Vec<u8>>,
+}
+
+impl FrameContext {
+    /// Start from the `region` of a captured frame, or all of it
+    pub fn from_frame(frame: &CapturedFrame, region: Option<Rect>) -> Result<Self, String> {
+        let region = region.and_then(|region| region.clamp_to(frame.width as i32, frame.height as i32));
+        let (image, origin) = match region {
+            Some(region) => (bgra_mat_region(frame, region)?, (region.x, region.y)),
+            None => (bgra_mat(frame)?, (0, 0)),
+        };
+        let mut context = Self::new(image, origin);
+        context.frame_height = frame.height as i32;
+        Ok(context)
+    }
+
+    pub fn new(image: Mat, origin: (i32, i32)) -> Self {
+        Self {
+            frame_height: image.rows(),
+            image,
+            origin,
+            scale: 1.0,
+            focus: None,
+            preprocessed: Vec::new(),
+            skip_detection: false,
+            annotations: Vec::new(),
+            encoded: None,
+        }
+    }
+
+    /// Current image, BGRA unless a convert stage changed it
+    pub fn image(&self) -> &Mat {
+        &self.image
+    }
+
+    pub fn set_image(&mut self, image: Mat) {
+        self.image = image;
+        self.preprocessed.clear();
+    }
+
+    /// Map a point of the current image back to captured frame pixels
+    pub fn to_frame(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            x / self.scale as f32 + self.origin.0 as f32,
+            y / self.scale as f32 + self.origin.1 as f32,
+        )
+    }
+
+    /// Height of the captured frame in pixels of the current image, what templates are scaled
+    /// to for the capture resolution
+    pub fn frame_height(&self) -> f64 {
+        self.frame_height as f64 * self.scale
+    }
+
+    /// Map a point of captured frame pixels into the current image
+    pub fn from_frame_point(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            (x - self.origin.0 as f32) * self.scale as f32,
+            (y - self.origin.1 as f32) * self.scale as f32,
+        )
+    }
+
+    /// Map a point of the focused region, as seen by detectors, back to captured frame pixels
+    pub fn focus_to_frame(&self, x: f32, y: f32) -> (f32, f32) {
+        let (left, top) = self.focus.map_or((0, 0), |focus| (focus.x, focus.y));
+        self.to_frame(x + left as f32, y + top as f32)
+    }
+
+    /// Part of the image detectors look at, e.g. the located minimap, `None` for all of it
+    pub fn focus(&self) -> Option<Rect> {
+        self.focus
+    }
+
+    pub fn set_focus(&mut self, focus: Option<Rect>) {
+        self.focus = focus;
+        self.preprocessed.clear();
+    }
+
+    /// The focused region after `preprocess`, converted once per frame and shared by all
+    /// detectors asking for the same preprocessing
+    pub fn preprocessed(&mut self, preprocess: &Preprocess) -> Result<&Mat, String> {
+        let index = match self.preprocessed.iter().position(|(cached, _)| cached == preprocess) {
+            Some(index) => index,
+            None => {
+                let image = match self.focus {
+                    Some(focus) => preprocess.apply(&crop(&self.image, focus)?)?,
+                    None => preprocess.apply(&self.image)?,
+                };
+                self.preprocessed.push((*preprocess, image));
+                self.preprocessed.len() - 1
+            }
+        };
+        Ok(&self.preprocessed[index].1)
+    }
+
+    /// HSV copy of the focused region
+    pub fn focus_hsv(&mut self) -> Result<&Mat, String> {
+        self.preprocessed(&Preprocess::hsv())
+    }
+
+    /// Whether an earlier stage found nothing for detectors to look at, e.g. no minimap
+    pub fn skip_detection(&self) -> bool {
+        self.skip_detection
+    }
+
+    pub fn set_skip_detection(&mut self, skip: bool) {
+        self.skip_detection = skip;
+    }
+
+    /// Something for an overlay stage to draw, in captured frame pixels
+    pub fn annotate(&mut self, annotation: Annotation) {
+        self.annotations.push(annotation);
+    }
+
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    pub fn encoded(&self) -> Option<&[u8]> {
+        self.encoded.as_deref()
+    }
+
+    pub fn take_encoded(&mut self) -> Option<Vec<u8>> {
+        self.encoded.take()
+    }
+
+    pub fn set_encoded(&mut self, encoded: Vec<u8>) {
+        self.encoded = Some(encoded);
+    }
+}
+
+/// A detection result drawn by an overlay stage, positions are in captured frame pixels
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Annotation {
+    Box {
+        rect: Rect,
+        label: Option<String>,
+        rgb: [u8; 3],
+    },
+    Point {
+        x: f32,
+        y: f32,
+        label: Option<String>,
+        rgb: [u8; 3],
+    },
+    /// A compass heading from a point, 0 is up and 90 is right
+    Heading {
+        x: f32,
+        y: f32,
+        degrees: f32,
+        rgb: [u8; 3],
+    },
+}
+
+/// One step of a [`Pipeline`]
+pub trait PipelineStage: Send {
+    fn name(&self) -> &str;
+
+    fn process(&mut self, context: &mut FrameContext) -> Result<(), String>;
+
+    /// Encoders are timed separately from the detection stages
+    fn is_encoder(&self) -> bool {
+        false
+    }
+}
+
+/// Color space a convert stage switches the image to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorConversion {
+    Gray,
+    Bgr,
+}
+
+/// Color space a detector wants its input in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PreprocessColor {
+    #[default]
+    Bgra,
+    Gray,
+    Hsv,
+}
+
+/// How the focused region is prepared for a detector, applied in field order
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preprocess {
+    /// Scale applied first so the other steps work on fewer pixels, results of the detector are
+    /// in scaled pixels
+    pub scale: f64,
+    pub color: PreprocessColor,
+    /// Gaussian blur kernel size, 0 disables blurring and even sizes are rounded up
+    pub blur: i32,
+    /// Binary threshold (0 - 255), every channel above it becomes 255 and the rest 0
+    pub threshold: Option<f64>,
+}
+
+impl Default for Preprocess {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            color: PreprocessColor::Bgra,
+            blur: 0,
+            threshold: None,
+        }
+    }
+}
+
+impl Preprocess {
+    pub fn gray() -> Self {
+        Self {
+            color: PreprocessColor::Gray,
+            ..Self::default()
+        }
+    }
+
+    pub fn hsv() -> Self {
+        Self {
+            color: PreprocessColor::Hsv,
+            ..Self::default()
+        }
+    }
+
+    /// Map a point of the preprocessed image back to pixels of the unscaled region
+    pub fn unscale(&self, x: f32, y: f32) -> (f32, f32) {
+        (x / self.scale as f32, y / self.scale as f32)
+    }
+
+    /// Run the preprocessing on a BGRA image
+    pub fn apply(&self, bgra: &Mat) -> Result<Mat, String> {
+        if self.scale <= 0.0 {
+            return Err(format!("Invalid preprocess scale {}", self.scale));
+        }
+
+        let mut image = if self.scale == 1.0 {
+            bgra.try_clone().map_err(|e| format!("Failed to copy image: {}", e))?
+        } else {
+            let interpolation = if self.scale < 1.0 { INTER_AREA } else { INTER_LINEAR };
+            let mut resized = Mat::default();
+            resize(bgra, &mut resized, Size::default(), self.scale, self.scale, interpolation)
+                .map_err(|e| format!("Failed to resize image: {}", e))?;
+            resized
+        };
+
+        image = match self.color {
+            PreprocessColor::Bgra => image,
+            PreprocessColor::Gray => to_gray(&image)?,
+            PreprocessColor::Hsv => to_hsv(&image)?,
+        };
+
+        if self.blur > 0 {
+            let kernel = self.blur | 1;
+            let mut blurred = Mat::default();
+            gaussian_blur_def(&image, &mut blurred, Size::new(kernel, kernel), 0.0)
+                .map_err(|e| format!("Failed to blur image: {}", e))?;
+            image = blurred;
+        }
+
+        if let Some(level) = self.threshold {
+            let mut binary = Mat::default();
+            threshold(&image, &mut binary, level, 255.0, THRESH_BINARY)
+                .map_err(|e| format!("Failed to threshold image: {}", e))?;
+            image = binary;
+        }
+
+        Ok(image)
+    }
+}
+
+/// Image format of an encode stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodeFormat {
+    Webp,
+    /// Through libjpeg-turbo with the `turbojpeg` feature, the cheapest to encode
+    Jpeg,
+    Png,
+    /// Uncompressed, see [`encode_raw_rgba`](super::image_ops::encode_raw_rgba). Costs no CPU
+    /// but is far too large to stream, meant for the local UI
+    Rgba,
+}
+
+impl EncodeFormat {
+    /// Quality used when none is given
+    pub fn default_quality(self) -> i32 {
+        match self {
+            EncodeFormat::Webp => 75,
+            EncodeFormat::Jpeg => 80,
+            EncodeFormat::Png | EncodeFormat::Rgba => 100,
+        }
+    }
+}
+
+/// Serializable description of a stage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StageConfig {
+    /// Crop the image to `rect`, in pixels of the image entering the stage
+    Crop { rect: Rect },
+    /// Scale the image by `scale`
+    Resize { scale: f64 },
+    /// Convert the image, detectors expect BGRA so this belongs right before encoding
+    Convert { color: ColorConversion },
+    /// A detector registered with [`PipelineBuilder::register_detector`] under `name`,
+    /// `preprocess` replaces the preprocessing the detector was registered with
+    Detect {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        preprocess: Option<Preprocess>,
+    },
+    /// Draw the annotations of earlier stages onto the image, belongs right before encoding
+    Overlay,
+    /// Encode the image for previews, `quality` is 0 - 100 (compression effort for PNG, unused for RGBA)
+    ///
+    /// Larger images are scaled down to fit `max_size` first, encoding time grows with the pixel count.
+    Encode {
+        format: EncodeFormat,
+        quality: i32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_size: Option<MaxSize>,
+    },
+}
+
+/// Stages of a pipeline in order
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PipelineConfig {
+    pub stages: Vec<StageConfig>,
+}
+
+/// Time spent in one [`Pipeline::run`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PipelineRun {
+    pub processing: Duration,
+    pub encoding: Duration,
+}
+
+#[derive(Debug)]
+struct StageMetrics {
+    runs: AtomicUsize,
+    failures: AtomicUsize,
+    total_time_us: AtomicU64,
+    latency: LatencyTracker,
+}
+
+impl StageMetrics {
+    fn new() -> Self {
+        Self {
+            runs: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+            total_time_us: AtomicU64::new(0),
+            latency: LatencyTracker::new(),
+        }
+    }
+
+    fn record(&self, elapsed: Duration, failed: bool) {
+        self.runs.fetch_add(1, Ordering::Relaxed);
+        if failed {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        self.total_time_us.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        self.latency.record(elapsed);
+    }
+}
+
+/// Snapshot of one stage's performance
+#[derive(Clone, Debug, Serialize)]
+pub struct StageStats {
+    pub name: String,
+    pub runs: usize,
+    pub failures: usize,
+    pub avg_ms: f64,
+    pub latency: LatencyPercentiles,
+}
+
+/// A chain of stages run on every processed frame
+pub struct Pipeline {
+    stages: Vec<(Box<dyn PipelineStage>, StageMetrics)>,
+}
+
+impl Pipeline {
+    pub fn builder() -> PipelineBuilder {
+        PipelineBuilder::new()
+    }
+
+    pub fn stage_names(&self) -> Vec<String> {
+        self.stages.iter().map(|(stage, _)| stage.name().to_string()).collect()
+    }
+
+    /// Run every stage in order, stopping at the first failure
+    pub fn run(&mut self, context: &mut FrameContext) -> Result<PipelineRun, String> {
+        self.run_stages(context, true)
+    }
+
+    /// Like [`Self::run`] but skipping the encoders, nothing is left to take from `context`
+    pub fn run_without_encoding(&mut self, context: &mut FrameContext) -> Result<PipelineRun, String> {
+        self.run_stages(context, false)
+    }
+
+    fn run_stages(&mut self, context: &mut FrameContext, encode: bool) -> Result<PipelineRun, String> {
+        let mut run = PipelineRun::default();
+        for (stage, metrics) in &mut self.stages {
+            if stage.is_encoder() && !encode {
+                continue;
+            }
+            let start = Instant::now();
+            let result = stage.process(context);
+            let elapsed = start.elapsed();
+            metrics.record(elapsed, result.is_err());
+            if stage.is_encoder() {
+                run.encoding += elapsed;
+            } else {
+                run.processing += elapsed;
+            }
+            result.map_err(|e| format!("Stage {} failed: {}", stage.name(), e))?;
+        }
+        Ok(run)
+    }
+
+    pub fn stats(&self) -> Vec<StageStats> {
+        self.stages
+            .iter()
+            .map(|(stage, metrics)| {
+                let runs = metrics.runs.load(Ordering::Relaxed);
+                let total_ms = metrics.total_time_us.load(Ordering::Relaxed) as f64 / 1000.0;
+                StageStats {
+                    name: stage.name().to_string(),
+                    runs,
+                    failures: metrics.failures.load(Ordering::Relaxed),
+                    avg_ms: if runs > 0 { total_ms / runs as f64 } else { 0.0 },
+                    latency: metrics.latency.percentiles(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Creates a fresh detector stage with the given preprocessing each time a pipeline is built
+pub type DetectorFactory = Box<dyn Fn(Preprocess) -> Box<dyn PipelineStage> + Send + Sync>;
+
+/// Assembles a [`Pipeline`] from stages or from a [`PipelineConfig`]
+#[derive(Default)]
+pub struct PipelineBuilder {
+    detectors: HashMap<String, (Preprocess, DetectorFactory)>,
+    stages: Vec<Box<dyn PipelineStage>>,
+}
+
+impl PipelineBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make a detector available to `detect` stages of configs under `name`
+    ///
+    /// `preprocess` is what the detector gets unless the stage config overrides it.
+    pub fn register_detector(
+        mut self,
+        name: impl Into<String>,
+        preprocess: Preprocess,
+        factory: impl Fn(Preprocess) -> Box<dyn PipelineStage> + Send + Sync + 'static,
+    ) -> Self {
+        self.detectors.insert(name.into(), (preprocess, Box::new(factory)));
+        self
+    }
+
+    /// Preprocessing a detector was registered with
+    pub fn detector_preprocess(&self, name: &str) -> Option<Preprocess> {
+        self.detectors.get(name).map(|(preprocess, _)| *preprocess)
+    }
+
+    /// Names of the registered detectors
+    pub fn detector_names(&self) -> Vec<String> {
+        self.detectors.keys().cloned().collect()
+    }
+
+    /// Append a stage
+    pub fn stage(mut self, stage: impl PipelineStage + 'static) -> Self {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// Append the stages of `config`
+    pub fn stages_from(mut self, config: &PipelineConfig) -> Result<Self, String> {
+        for stage in &config.stages {
+            let stage: Box<dyn PipelineStage> = match stage {
+                StageConfig::Crop { rect } => Box::new(CropStage { rect: *rect }),
+                StageConfig::Resize { scale } => {
+                    if *scale <= 0.0 {
+                        return Err(format!("Invalid resize scale {}", scale));
+                    }
+                    Box::new(ResizeStage { scale: *scale })
+                }
+                StageConfig::Convert { color } => Box::new(ConvertStage { color: *color }),
+                StageConfig::Detect { name, preprocess } => match self.detectors.get(name) {
+                    Some((default, factory)) => {
+                        let preprocess = preprocess.unwrap_or(*default);
+                        if preprocess.scale <= 0.0 {
+                            return Err(format!("Invalid preprocess scale {} for {}", preprocess.scale, name));
+                        }
+                        factory(preprocess)
+                    }
+                    None => return Err(format!("Unknown detector {}", name)),
+                },
+                StageConfig::Overlay => Box::new(OverlayStage),
+                StageConfig::Encode { format, quality, max_size } => {
+                    if let Some(max_size) = max_size {
+                        if max_size.width <= 0 || max_size.height <= 0 {
+                            return Err(format!("Invalid encode size {}x{}", max_size.width, max_size.height));
+                        }
+                    }
+                    Box::new(EncodeStage {
+                        format: *format,
+                        quality: (*quality).clamp(0, 100),
+                        max_size: *max_size,
+                    })
+                }
+            };
+            self.stages.push(stage);
+        }
+        Ok(self)
+    }
+
+    pub fn build(self) -> Pipeline {
+        Pipeline {
+            stages: self
+                .stages
+                .into_iter()
+                .map(|stage| (stage, StageMetrics::new()))
+                .collect(),
+        }
+    }
+}
+
+struct CropStage {
+    rect: Rect,
+}
+
+impl PipelineStage for CropStage {
+    fn name(&self) -> &str {
+        "crop"
+    }
+
+    fn process(&mut self, context: &mut FrameContext) -> Result<(), String> {
+        let size = context.image.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
+        let rect = self
+            .rect
+            .clamp_to(size.width, size.height)
+            .ok_or_else(|| format!("Crop {:?} is outside the {}x{} image", self.rect, size.width, size.height))?;
+        let image = crop(&context.image, rect)?;
+        context.origin = (
+            context.origin.0 + (rect.x as f64 / context.scale).round() as i32,
+            context.origin.1 + (rect.y as f64 / context.scale).round() as i32,
+        );
+        context.set_focus(None);
+        context.set_image(image);
+        Ok(())
+    }
+}
+
+struct ResizeStage {
+    scale: f64,
+}
+
+impl PipelineStage for ResizeStage {
+    fn name(&self) -> &str {
+        "resize"
+    }
+
+    fn process(&mut self, context: &mut FrameContext) -> Result<(), String> {
+        let interpolation = if self.scale < 1.0 { INTER_AREA } else { INTER_LINEAR };
+        let mut resized = Mat::default();
+        resize(&context.image, &mut resized, Size::default(), self.scale, self.scale, interpolation)
+            .map_err(|e| format!("Failed to resize image: {}", e))?;
+        context.scale *= self.scale;
+        context.set_focus(None);
+        context.set_image(resized);
+        Ok(())
+    }
+}
+
+struct ConvertStage {
+    color: ColorConversion,
+}
+
+impl PipelineStage for ConvertStage {
+    fn name(&self) -> &str {
+        "convert"
+    }
+
+    fn process(&mut self, context: &mut FrameContext) -> Result<(), String> {
+        let code = match self.color {
+            ColorConversion::Gray => COLOR_BGRA2GRAY,
+            ColorConversion::Bgr => COLOR_BGRA2BGR,
+        };
+        let mut converted = Mat::default();
+        cvt_color_def(&context.image, &mut converted, code).map_err(|e| format!("Failed to convert image: {}", e))?;
+        context.set_image(converted);
+        Ok(())
+    }
+}
+
+/// Length of heading lines in image pixels
+const OVERLAY_HEADING_LENGTH: f32 = 14.0;
+
+struct OverlayStage;
+
+impl OverlayStage {
+    fn draw(image: &mut Mat, context: &FrameContext, annotation: &Annotation) -> opencv::Result<()> {
+        let point = |x: f32, y: f32| {
+            let (x, y) = context.from_frame_point(x, y);
+            Point::new(x.round() as i32, y.round() as i32)
+        };
+        let color = |rgb: [u8; 3]| Scalar::new(rgb[2] as f64, rgb[1] as f64, rgb[0] as f64, 255.0);
+        let label = |image: &mut Mat, text: &str, at: Point, rgb: [u8; 3]| {
+            put_text(
+                image,
+                text,
+                Point::new(at.x, at.y - 4),
+                FONT_HERSHEY_SIMPLEX,
+                0.35,
+                color(rgb),
+                1,
+                LINE_AA,
+                false,
+            )
+        };
+
+        match annotation {
+            Annotation::Box { rect, label: text, rgb } => {
+                let top_left = point(rect.x as f32, rect.y as f32);
+                let bottom_right = point((rect.x + rect.width) as f32, (rect.y + rect.height) as f32);
+                let cv_rect = CvRect::new(
+                    top_left.x,
+                    top_left.y,
+                    (bottom_right.x - top_left.x).max(1),
+                    (bottom_right.y - top_left.y).max(1),
+                );
+                rectangle(image, cv_rect, color(*rgb), 1, LINE_AA, 0)?;
+                if let Some(text) = text {
+                    label(image, text, top_left, *rgb)?;
+                }
+            }
+            Annotation::Point { x, y, label: text, rgb } => {
+                let center = point(*x, *y);
+                circle(image, center, 3, color(*rgb), 1, LINE_AA, 0)?;
+                if let Some(text) = text {
+                    label(image, text, Point::new(center.x + 4, center.y), *rgb)?;
+                }
+            }
+            Annotation::Heading { x, y, degrees, rgb } => {
+                let start = point(*x, *y);
+                let radians = degrees.to_radians();
+                let end = Point::new(
+                    start.x + (radians.sin() * OVERLAY_HEADING_LENGTH).round() as i32,
+                    start.y - (radians.cos() * OVERLAY_HEADING_LENGTH).round() as i32,
+                );
+                line(image, start, end, color(*rgb), 1, LINE_AA, 0)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PipelineStage for OverlayStage {
+    fn name(&self) -> &str {
+        "overlay"
+    }
+
+    fn process(&mut self, context: &mut FrameContext) -> Result<(), String> {
+        if context.annotations.is_empty() {
+            return Ok(());
+        }
+        // Draw in place, the image is handed back even when drawing fails
+        let mut image = std::mem::take(&mut context.image);
+        let result = context
+            .annotations
+            .iter()
+            .try_for_each(|annotation| Self::draw(&mut image, context, annotation))
+            .map_err(|e| format!("Failed to draw overlay: {}", e));
+        context.set_image(image);
+        result
+    }
+}
+
+struct EncodeStage {
+    format: EncodeFormat,
+    quality: i32,
+    max_size: Option<MaxSize>,
+}
+
+impl PipelineStage for EncodeStage {
+    fn name(&self) -> &str {
+        "encode"
+    }
+
+    fn process(&mut self, context: &mut FrameContext) -> Result<(), String> {
+        // Later stages still see the full image, only the encoded copy is scaled down
+        let downscaled = self.downscale(&context.image)?;
+        let image = downscaled.as_ref().unwrap_or(&context.image);
+        let encoded = match self.format {
+            EncodeFormat::Webp => Self::imencode(image, ".webp", [IMWRITE_WEBP_QUALITY, self.quality])?,
+            #[cfg(feature = "turbojpeg")]
+            EncodeFormat::Jpeg => Self::turbojpeg(image, self.quality)?,
+            #[cfg(not(feature = "turbojpeg"))]
+            EncodeFormat::Jpeg => Self::imencode(image, ".jpg", [IMWRITE_JPEG_QUALITY, self.quality])?,
+            // PNG is lossless, map quality onto compression effort instead
+            EncodeFormat::Png => Self::imencode(image, ".png", [IMWRITE_PNG_COMPRESSION, 9 - self.quality * 9 / 100])?,
+            EncodeFormat::Rgba => Self::raw_rgba(image)?,
+        };
+        context.set_encoded(encoded);
+        Ok(())
+    }
+
+    fn is_encoder(&self) -> bool {
+        true
+    }
+}
+
+impl EncodeStage {
+    /// `image` scaled to fit the max size, `None` if it already fits
+    fn downscale(&self, image: &Mat) -> Result<Option<Mat>, String> {
+        let Some(max_size) = self.max_size else {
+            return Ok(None);
+        };
+        let size = image.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
+        let scale = max_size.scale_for(size.width, size.height);
+        if scale >= 1.0 {
+            return Ok(None);
+        }
+        let target = Size::new(
+            ((size.width as f64 * scale).round() as i32).max(1),
+            ((size.height as f64 * scale).round() as i32).max(1),
+        );
+        let mut resized = Mat::default();
+        resize(image, &mut resized, target, 0.0, 0.0, INTER_AREA).map_err(|e| format!("Failed to resize image: {}", e))?;
+        Ok(Some(resized))
+    }
+
+    fn imencode(image: &Mat, extension: &str, params: [i32; 2]) -> Result<Vec<u8>, String> {
+        let mut buffer = Vector::<u8>::new();
+        imencode(extension, image, &mut buffer, &Vector::<i32>::from_slice(&params))
+            .map_err(|e| format!("Failed to encode {}: {}", extension, e))?;
+        Ok(buffer.to_vec())
+    }
+
+    #[cfg(feature = "turbojpeg")]
+    fn turbojpeg(image: &Mat, quality: i32) -> Result<Vec<u8>, String> {
+        use turbojpeg::{Image, PixelFormat, Subsamp};
+
+        // Cropped images are views into the frame, turbojpeg needs the rows back to back
+        let continuous;
+        let image = if image.is_continuous() {
+            image
+        } else {
+            continuous = image.try_clone().map_err(|e| format!("Failed to copy image: {}", e))?;
+            &continuous
+        };
+        let (format, subsamp) = match image.channels() {
+            1 => (PixelFormat::GRAY, Subsamp::Gray),
+            3 => (PixelFormat::BGR, Subsamp::Sub2x2),
+            4 => (PixelFormat::BGRA, Subsamp::Sub2x2),
+            channels => return Err(format!("Can't encode a {} channel image as JPEG", channels)),
+        };
+        let size = image.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
+        let pixels = image.data_bytes().map_err(|e| format!("Failed to read image: {}", e))?;
+        let image = Image {
+            pixels,
+            width: size.width as usize,
+            pitch: size.width as usize * image.channels() as usize,
+            height: size.height as usize,
+            format,
+        };
+        let jpeg = turbojpeg::compress(image, quality, subsamp).map_err(|e| format!("Failed to encode .jpg: {}", e))?;
+        Ok(jpeg.to_vec())
+    }
+
+    fn raw_rgba(image: &Mat) -> Result<Vec<u8>, String> {
+        let code = match image.channels() {
+            1 => COLOR_GRAY2RGBA,
+            3 => COLOR_BGR2RGBA,
+            _ => COLOR_BGRA2RGBA,
+        };
+        let mut rgba = Mat::default();
+        cvt_color_def(image, &mut rgba, code).map_err(|e| format!("Failed to convert image: {}", e))?;
+        let size = rgba.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
+        let pixels = rgba.data_bytes().map_err(|e| format!("Failed to read image: {}", e))?;
+        Ok(encode_raw_rgba(size.width as u32, size.height as u32, pixels))
+    }
+}
@@ -0,0 +1,135 @@
+#[cfg(feature = "opencv")]
+use opencv::{
+    core::{Mat, Point, Vector},
+    imgproc::{contour_area_def, find_contours_def, moments_def, CHAIN_APPROX_NONE, RETR_EXTERNAL},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use super::vision::HsvRange;
+
+/// Markers whose farthest point is at least this much further from the center than the average
+/// point are treated as arrows with a heading, rounder markers as dots without one
+#[cfg(feature = "opencv")]
+const MIN_ARROW_ELONGATION: f32 = 1.2;
+
+/// How the player marker looks on the minimap
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerArrowConfig {
+    /// Color of the marker
+    pub color: HsvRange,
+    /// Accepted marker contour area in minimap pixels
+    pub min_area: f64,
+    pub max_area: f64,
+}
+
+impl Default for PlayerArrowConfig {
+    fn default() -> Self {
+        Self {
+            // Bright yellow
+            color: HsvRange::new([20, 120, 180], [35, 255, 255]),
+            min_area: 6.0,
+            max_area: 400.0,
+        }
+    }
+}
+
+/// Player marker found on the minimap
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlayerPosition {
+    /// Marker center in minimap pixels
+    pub x: f32,
+    pub y: f32,
+    /// Compass heading the arrow points at, 0 is up and 90 is right
+    ///
+    /// `None` for round markers that don't show a direction.
+    pub heading_degrees: Option<f32>,
+}
+
+/// Finds the player arrow on a cropped minimap by color and shape
+#[cfg(feature = "opencv")]
+#[derive(Debug, Clone, Default)]
+pub struct PlayerArrowDetector {
+    config: PlayerArrowConfig,
+}
+
+#[cfg(feature = "opencv")]
+impl PlayerArrowDetector {
+    pub fn new(config: PlayerArrowConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> PlayerArrowConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: PlayerArrowConfig) {
+        self.config = config;
+    }
+
+    /// Detect the marker in an HSV minimap image
+    ///
+    /// When several blobs match the color, the one whose area is closest to the middle of the
+    /// accepted range is picked.
+    pub fn detect(&self, hsv: &Mat) -> Result<Option<PlayerPosition>, String> {
+        let mask = self.config.color.mask(hsv)?;
+
+        let mut contours = Vector::<Vector<Point>>::new();
+        find_contours_def(&mask, &mut contours, RETR_EXTERNAL, CHAIN_APPROX_NONE)
+            .map_err(|e| format!("Failed to find contours: {}", e))?;
+
+        let target_area = (self.config.min_area + self.config.max_area) / 2.0;
+        let mut best: Option<(f64, Vector<Point>)> = None;
+        for contour in contours {
+            let area = contour_area_def(&contour).map_err(|e| format!("Failed to measure contour: {}", e))?;
+            if area < self.config.min_area || area > self.config.max_area {
+                continue;
+            }
+            let distance = (area - target_area).abs();
+            if best.as_ref().map_or(true, |(best_distance, _)| distance < *best_distance) {
+                best = Some((distance, contour));
+            }
+        }
+
+        match best {
+            Some((_, contour)) => Self::measure(&contour).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn measure(contour: &Vector<Point>) -> Result<PlayerPosition, String> {
+        let moments = moments_def(contour).map_err(|e| format!("Failed to compute moments: {}", e))?;
+        if moments.m00 <= 0.0 {
+            return Err("Degenerate player marker contour".to_string());
+        }
+        let cx = (moments.m10 / moments.m00) as f32;
+        let cy = (moments.m01 / moments.m00) as f32;
+
+        // The tip of an arrow is the contour point farthest from its center
+        let mut tip = (0.0f32, 0.0f32, 0.0f32);
+        let mut total_distance = 0.0;
+        for point in contour.iter() {
+            let (dx, dy) = (point.x as f32 - cx, point.y as f32 - cy);
+            let distance = dx.hypot(dy);
+            total_distance += distance;
+            if distance > tip.2 {
+                tip = (dx, dy, distance);
+            }
+        }
+        let mean_distance = total_distance / contour.len().max(1) as f32;
+
+        let heading_degrees = if mean_distance > 0.0 && tip.2 / mean_distance >= MIN_ARROW_ELONGATION {
+            // Screen y grows downwards, so up is -dy
+            Some(tip.0.atan2(-tip.1).to_degrees().rem_euclid(360.0))
+        } else {
+            None
+        };
+
+        Ok(PlayerPosition {
+            x: cx,
+            y: cy,
+            heading_degrees,
+        })
+    }
+}
@@ -0,0 +1,192 @@
+//! Extension point for detectors and automation modules living outside this crate
+//!
+//! A plugin is handed a [`PluginContext`] and returns the services it wants run, which are
+//! registered with the [`ServiceManager`] like the built-in ones. Plugins are registered on
+//! [`PluginRegistry::global`] before the bot starts, or with the `dynamic-plugins` feature
+//! loaded from libraries exporting a constructor with [`export_plugin!`](crate::export_plugin).
+
+use std::sync::{Arc, Mutex as StdMutex, OnceLock};
+
+use tokio::sync::broadcast;
+
+use crate::services::Service;
+use super::event_bus::EventBus;
+use super::graphics_capture::{CapturedFrame, GraphicsCaptureService};
+use super::input_scheduler::InputScheduler;
+use super::service_manager::ServiceManager;
+
+/// Symbol plugin libraries export their constructor under
+pub const PLUGIN_ENTRY_SYMBOL: &str = "starry_plugin_create";
+
+/// Constructor exported by plugin libraries
+pub type PluginCreate = fn() -> Box<dyn Plugin>;
+
+/// Export `$constructor` from a `cdylib` so the `dynamic-plugins` loader finds it
+///
+/// The library has to be built with the same compiler and `interface` version as the bot,
+/// plugins are passed as Rust trait objects.
+#[macro_export]
+macro_rules! export_plugin {
+    ($constructor:expr) => {
+        #[no_mangle]
+        pub fn starry_plugin_create() -> Box<dyn $crate::services::Plugin> {
+            Box::new($constructor)
+        }
+    };
+}
+
+/// What a plugin gets to work with: events, frames and input
+#[derive(Clone)]
+pub struct PluginContext {
+    graphics_service: Arc<GraphicsCaptureService>,
+    input: Option<InputScheduler>,
+}
+
+impl PluginContext {
+    pub fn new(graphics_service: Arc<GraphicsCaptureService>) -> Self {
+        Self {
+            graphics_service,
+            input: None,
+        }
+    }
+
+    /// Let plugins send input through `input`
+    pub fn with_input(mut self, input: InputScheduler) -> Self {
+        self.input = Some(input);
+        self
+    }
+
+    /// Bus to subscribe to and publish [`BotEvent`](super::event_bus::BotEvent)s on
+    pub fn events(&self) -> &'static EventBus {
+        EventBus::global()
+    }
+
+    /// Captured frames from now on
+    pub fn subscribe_frames(&self) -> broadcast::Receiver<Arc<CapturedFrame>> {
+        self.graphics_service.subscribe()
+    }
+
+    pub fn graphics_service(&self) -> &Arc<GraphicsCaptureService> {
+        &self.graphics_service
+    }
+
+    /// `None` when the bot runs without input, e.g. replaying a session
+    pub fn input(&self) -> Option<&InputScheduler> {
+        self.input.as_ref()
+    }
+}
+
+/// A detector or automation module added without changing `core/interface`
+pub trait Plugin: Send + Sync {
+    /// Unique name, used in logs and to reject duplicate registrations
+    fn name(&self) -> &str;
+
+    /// Set the plugin up and return the services to run with capture
+    ///
+    /// Services are started and stopped by the [`ServiceManager`], their
+    /// [`Service::dependencies`] may name built-in services.
+    fn init(&self, context: &PluginContext) -> Result<Vec<Arc<dyn Service>>, String>;
+}
+
+/// Plugins to initialize when the bot starts
+#[derive(Default)]
+pub struct PluginRegistry {
+    plugins: StdMutex<Vec<Arc<dyn Plugin>>>,
+    // Kept loaded for as long as the registry lives, plugin code runs from them
+    #[cfg(feature = "dynamic-plugins")]
+    libraries: StdMutex<Vec<libloading::Library>>,
+}
+
+impl PluginRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry the UI and CLI initialize plugins from
+    pub fn global() -> &'static PluginRegistry {
+        static GLOBAL: OnceLock<PluginRegistry> = OnceLock::new();
+        GLOBAL.get_or_init(PluginRegistry::new)
+    }
+
+    pub fn register(&self, plugin: Arc<dyn Plugin>) -> Result<(), String> {
+        let mut plugins = self.plugins.lock().unwrap();
+        if plugins.iter().any(|registered| registered.name() == plugin.name()) {
+            return Err(format!("Plugin {} is already registered", plugin.name()));
+        }
+        plugins.push(plugin);
+        Ok(())
+    }
+
+    /// Names of the registered plugins in registration order
+    pub fn names(&self) -> Vec<String> {
+        self.plugins.lock().unwrap().iter().map(|plugin| plugin.name().to_string()).collect()
+    }
+
+    /// Initialize every plugin and register its services with `services`
+    ///
+    /// A failing plugin doesn't keep the others from being initialized, the error names all
+    /// that failed.
+    pub fn init_all(&self, context: &PluginContext, services: &ServiceManager) -> Result<(), String> {
+        let plugins = self.plugins.lock().unwrap().clone();
+        let mut failed = Vec::new();
+        for plugin in plugins {
+            let registered = plugin.init(context).and_then(|plugin_services| {
+                plugin_services
+                    .into_iter()
+                    .try_for_each(|service| services.register(service))
+            });
+            match registered {
+                Ok(()) => tracing::info!(plugin = plugin.name(), "Plugin initialized"),
+                Err(e) => {
+                    tracing::error!(plugin = plugin.name(), error = %e, "Plugin failed to initialize");
+                    failed.push(plugin.name().to_string());
+                }
+            }
+        }
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Plugins failed to initialize: {}", failed.join(", ")))
+        }
+    }
+
+    /// Load and register every plugin library in `dir`, returning how many were registered
+    ///
+    /// Libraries that fail to load are skipped with a warning, a missing directory has none.
+    #[cfg(feature = "dynamic-plugins")]
+    pub fn load_dir(&self, dir: &std::path::Path) -> usize {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return 0;
+        };
+        let mut loaded = 0;
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            if !path.extension().is_some_and(|extension| extension == std::env::consts::DLL_EXTENSION) {
+                continue;
+            }
+            match self.load(&path) {
+                Ok(()) => loaded += 1,
+                Err(e) => tracing::warn!(path = %path.display(), error = %e, "Ignoring plugin library"),
+            }
+        }
+        loaded
+    }
+
+    /// Load a library exporting a plugin with [`export_plugin!`](crate::export_plugin) and register it
+    #[cfg(feature = "dynamic-plugins")]
+    pub fn load(&self, path: &std::path::Path) -> Result<(), String> {
+        // SAFETY: loading runs the library's initializers and calls its constructor, only
+        // libraries from the plugin directory the user put there are loaded
+        let (library, plugin) = unsafe {
+            let library = libloading::Library::new(path)
+                .map_err(|e| format!("Failed to load {}: {}", path.display(), e))?;
+            let create = library
+                .get::<PluginCreate>(PLUGIN_ENTRY_SYMBOL.as_bytes())
+                .map_err(|e| format!("{} exports no plugin: {}", path.display(), e))?;
+            let plugin = create();
+            (library, plugin)
+        };
+        self.register(Arc::from(plugin))?;
+        self.libraries.lock().unwrap().push(library);
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use axum::body::{Body, Bytes};
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+use tokio::sync::watch;
+use tokio_stream::wrappers::WatchStream;
+use tokio_stream::StreamExt;
+
+use crate::services::{Service, ServiceError};
+use super::http_server::HttpServer;
+
+/// Separates the frames of the multipart stream
+const STREAM_BOUNDARY: &str = "frame";
+
+/// Page watching the WebSocket stream, `token` is passed on from the page's own URL
+const PREVIEW_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Starry Bot</title>
+<style>body { margin: 0; background: #111; } img { width: 100%; height: 100vh; object-fit: contain; }</style>
+</head>
+<body>
+<img id="preview" alt="Waiting for frames...">
+<script>
+const preview = document.getElementById("preview");
+function connect() {
+  const socket = new WebSocket(location.href.replace(/^http/, "ws").replace(/\/?(\?|$)/, "/ws$1"));
+  socket.binaryType = "blob";
+  socket.onmessage = (message) => {
+    const url = URL.createObjectURL(message.data);
+    preview.onload = () => URL.revokeObjectURL(url);
+    preview.src = url;
+  };
+  socket.onclose = () => setTimeout(connect, 2000);
+}
+connect();
+</script>
+</body>
+</html>
+"#;
+
+/// Where and how fast the preview is served
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewServerConfig {
+    /// `127.0.0.1` only serves this PC, `0.0.0.0` also phones and other PCs on the network
+    pub addr: SocketAddr,
+    /// Frames sent to each viewer per second at most
+    pub max_fps: u32,
+    /// Required as `?token=` on every request if set, anyone who can connect sees the game otherwise
+    pub token: Option<String>,
+}
+
+impl Default for PreviewServerConfig {
+    fn default() -> Self {
+        Self {
+            addr: SocketAddr::from(([127, 0, 0, 1], 7879)),
+            max_fps: 10,
+            token: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Auth {
+    token: Option<String>,
+}
+
+#[derive(Clone)]
+struct PreviewState {
+    frames: watch::Receiver<Option<Vec<u8>>>,
+    config: PreviewServerConfig,
+}
+
+impl PreviewState {
+    fn authorize(&self, auth: &Auth) -> Result<(), Response> {
+        let Some(token) = &self.config.token else {
+            return Ok(());
+        };
+        // Compared in constant time so response timing doesn't leak the token
+        let authorized = auth
+            .token
+            .as_ref()
+            .is_some_and(|given| bool::from(given.as_bytes().ct_eq(token.as_bytes())));
+        if authorized {
+            Ok(())
+        } else {
+            Err((StatusCode::UNAUTHORIZED, "Missing or wrong token").into_response())
+        }
+    }
+
+    /// Latest frames, at most `max_fps` a second, nothing while capture is stopped
+    fn frames(&self) -> impl tokio_stream::Stream<Item = Vec<u8>> {
+        let period = Duration::from_secs_f64(1.0 / self.config.max_fps.max(1) as f64);
+        WatchStream::new(self.frames.clone()).filter_map(|frame| frame).throttle(period)
+    }
+}
+
+/// Serves the minimap preview over HTTP, to watch the bot from a phone or another PC
+///
+/// - `/` a page showing the stream
+/// - `/ws` a WebSocket sending every frame as a binary message
+/// - `/stream` a `multipart/x-mixed-replace` stream, usable as an `<img>` source
+/// - `/frame.webp` the latest frame, in whatever format the preview is encoded as
+///
+/// Browsers can't show raw RGBA previews, use one of the image formats when serving them.
+pub struct PreviewServer {
+    frames: watch::Receiver<Option<Vec<u8>>>,
+    config: PreviewServerConfig,
+    server: HttpServer,
+}
+
+impl PreviewServer {
+    /// `frames` are encoded preview frames like `MinimapService::get_frame_receiver`'s
+    pub fn new(frames: watch::Receiver<Option<Vec<u8>>>, config: PreviewServerConfig) -> Self {
+        Self {
+            frames,
+            config,
+            server: HttpServer::new("Preview server"),
+        }
+    }
+
+    pub fn config(&self) -> &PreviewServerConfig {
+        &self.config
+    }
+
+    pub fn is_serving(&self) -> bool {
+        self.server.is_serving()
+    }
+
+    pub async fn start_server(&self) -> Result<(), String> {
+        let state = PreviewState {
+            frames: self.frames.clone(),
+            config: self.config.clone(),
+        };
+        let router = Router::new()
+            .route("/", get(page))
+            .route("/ws", get(websocket))
+            .route("/stream", get(stream))
+            .route("/frame.webp", get(latest_frame))
+            .with_state(state);
+        self.server.start(self.config.addr, router).await
+    }
+
+    pub fn stop_server(&self) {
+        self.server.stop();
+    }
+}
+
+async fn page(State(state): State<PreviewState>, Query(auth): Query<Auth>) -> Response {
+    if let Err(response) = state.authorize(&auth) {
+        return response;
+    }
+    Html(PREVIEW_PAGE).into_response()
+}
+
+async fn websocket(
+    State(state): State<PreviewState>,
+    Query(auth): Query<Auth>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    if let Err(response) = state.authorize(&auth) {
+        return response;
+    }
+    upgrade.on_upgrade(move |socket| send_frames(socket, state))
+}
+
+async fn send_frames(mut socket: WebSocket, state: PreviewState) {
+    let frames = state.frames();
+    tokio::pin!(frames);
+    loop {
+        tokio::select! {
+            frame = frames.next() => {
+                let Some(frame) = frame else { break };
+                if socket.send(Message::Binary(frame)).await.is_err() {
+                    break;
+                }
+            }
+            // Viewers don't send anything, this only notices them leaving
+            message = socket.recv() => match message {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+async fn stream(State(state): State<PreviewState>, Query(auth): Query<Auth>) -> Response {
+    if let Err(response) = state.authorize(&auth) {
+        return response;
+    }
+    let parts = state.frames().map(|frame| {
+        let mut part = format!(
+            "--{}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n",
+            STREAM_BOUNDARY,
+            content_type(&frame),
+            frame.len()
+        )
+        .into_bytes();
+        part.extend_from_slice(&frame);
+        part.extend_from_slice(b"\r\n");
+        Ok::<_, Infallible>(Bytes::from(part))
+    });
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                format!("multipart/x-mixed-replace; boundary={}", STREAM_BOUNDARY),
+            ),
+            (header::CACHE_CONTROL, "no-cache".to_string()),
+        ],
+        Body::from_stream(parts),
+    )
+        .into_response()
+}
+
+/// Preview frames are WebP unless the encoding was changed, tell from the data
+fn content_type(frame: &[u8]) -> &'static str {
+    image::guess_format(frame).map_or("application/octet-stream", |format| format.to_mime_type())
+}
+
+async fn latest_frame(State(state): State<PreviewState>, Query(auth): Query<Auth>) -> Response {
+    if let Err(response) = state.authorize(&auth) {
+        return response;
+    }
+    let frame = state.frames.borrow().clone();
+    match frame {
+        Some(frame) => ([(header::CONTENT_TYPE, content_type(&frame))], frame).into_response(),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "Capture is stopped").into_response(),
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for PreviewServer {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_server().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_server();
+        Ok(())
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::services::{Service, ServiceError, ServiceTask};
+use super::frame_analyzer::{average_color, color_matches};
+use super::graphics_capture::{CapturedFrame, GraphicsCaptureService};
+use super::vision::Rect;
+
+/// Capacity of the event channel, slow subscribers skip old events
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+/// When a probe counts as active
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProbeCondition {
+    /// The sampled color is within `tolerance` of `rgb` on every channel
+    Color { rgb: [u8; 3], tolerance: u8 },
+    /// The sampled luma (0 - 255) is at least `min`, e.g. an ability icon that is lit
+    Brightness { min: u8 },
+    /// The sampled luma (0 - 255) is at most `max`, e.g. a grayed out icon
+    Darkness { max: u8 },
+}
+
+impl ProbeCondition {
+    fn is_met(&self, rgb: [u8; 3]) -> bool {
+        match *self {
+            ProbeCondition::Color { rgb: expected, tolerance } => color_matches(rgb, expected, tolerance),
+            ProbeCondition::Brightness { min } => luma(rgb) >= min,
+            ProbeCondition::Darkness { max } => luma(rgb) <= max,
+        }
+    }
+}
+
+/// Rec. 601 luma of an RGB color
+fn luma(rgb: [u8; 3]) -> u8 {
+    ((rgb[0] as u32 * 77 + rgb[1] as u32 * 150 + rgb[2] as u32 * 29) >> 8) as u8
+}
+
+/// A named point checked on every frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Probe {
+    pub name: String,
+    /// Point in frame pixels
+    pub x: i32,
+    pub y: i32,
+    /// Pixels around the point averaged into the sample, 0 samples just the point
+    #[serde(default)]
+    pub radius: i32,
+    pub condition: ProbeCondition,
+}
+
+impl Probe {
+    pub fn new(name: impl Into<String>, x: i32, y: i32, condition: ProbeCondition) -> Self {
+        Self {
+            name: name.into(),
+            x,
+            y,
+            radius: 0,
+            condition,
+        }
+    }
+
+    /// Average RGB color of the probe's square in a frame, `None` if it is outside the frame
+    pub fn sample(&self, frame: &CapturedFrame) -> Option<[u8; 3]> {
+        let radius = self.radius.max(0);
+        let side = radius * 2 + 1;
+        average_color(frame, Rect::new(self.x - radius, self.y - radius, side, side))
+    }
+}
+
+/// Probes saved across restarts, e.g. the ones added with the color picker
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProbeConfig {
+    pub probes: Vec<Probe>,
+}
+
+impl ProbeConfig {
+    pub fn path() -> PathBuf {
+        crate::config_dir().join("probes.json")
+    }
+
+    /// Load the saved probes, none if there are none
+    pub fn load() -> Self {
+        let path = Self::path();
+        let Ok(json) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&json).unwrap_or_else(|e| {
+            tracing::warn!(path = %path.display(), error = %e, "Ignoring invalid probes");
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize probes: {}", e))?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Add a probe or replace the one with the same name
+    pub fn insert(&mut self, probe: Probe) {
+        match self.probes.iter_mut().find(|existing| existing.name == probe.name) {
+            Some(existing) => *existing = probe,
+            None => self.probes.push(probe),
+        }
+    }
+}
+
+/// A probe flipped between inactive and active
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProbeEvent {
+    pub name: String,
+    pub active: bool,
+    /// Sampled RGB color that caused the flip
+    pub rgb: [u8; 3],
+}
+
+/// Last evaluation of a probe
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ProbeState {
+    pub active: bool,
+    pub rgb: [u8; 3],
+}
+
+/// Probes and their last state
+#[derive(Debug, Default)]
+pub struct ProbeSet {
+    probes: Vec<Probe>,
+    states: HashMap<String, ProbeState>,
+}
+
+impl ProbeSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn probes(&self) -> &[Probe] {
+        &self.probes
+    }
+
+    /// Add a probe or replace the one with the same name
+    pub fn insert(&mut self, probe: Probe) {
+        self.states.remove(&probe.name);
+        match self.probes.iter_mut().find(|existing| existing.name == probe.name) {
+            Some(existing) => *existing = probe,
+            None => self.probes.push(probe),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.states.remove(name);
+        let len = self.probes.len();
+        self.probes.retain(|probe| probe.name != name);
+        self.probes.len() != len
+    }
+
+    pub fn state(&self, name: &str) -> Option<ProbeState> {
+        self.states.get(name).copied()
+    }
+
+    /// Sample every probe and return the ones that flipped
+    ///
+    /// The first evaluation of a probe always reports its state.
+    pub fn evaluate(&mut self, frame: &CapturedFrame) -> Vec<ProbeEvent> {
+        let mut events = Vec::new();
+        for probe in &self.probes {
+            let Some(rgb) = probe.sample(frame) else {
+                continue;
+            };
+            let active = probe.condition.is_met(rgb);
+            let previous = self.states.insert(probe.name.clone(), ProbeState { active, rgb });
+            if previous.map_or(true, |previous| previous.active != active) {
+                events.push(ProbeEvent {
+                    name: probe.name.clone(),
+                    active,
+                    rgb,
+                });
+            }
+        }
+        events
+    }
+}
+
+/// Evaluates pixel probes on every captured frame, e.g. to know when an ability is off cooldown
+#[derive(Clone)]
+pub struct ProbeService {
+    graphics_service: Arc<GraphicsCaptureService>,
+    probes: Arc<StdMutex<ProbeSet>>,
+    event_sender: broadcast::Sender<ProbeEvent>,
+    task: ServiceTask,
+}
+
+impl ProbeService {
+    pub fn new(graphics_service: Arc<GraphicsCaptureService>) -> Self {
+        Self {
+            graphics_service,
+            probes: Arc::new(StdMutex::new(ProbeSet::new())),
+            event_sender: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            task: ServiceTask::new(),
+        }
+    }
+
+    /// Service evaluating the saved probes of `config`
+    pub fn with_config(graphics_service: Arc<GraphicsCaptureService>, config: &ProbeConfig) -> Self {
+        let service = Self::new(graphics_service);
+        for probe in &config.probes {
+            service.add_probe(probe.clone());
+        }
+        service
+    }
+
+    /// Probe flips
+    pub fn subscribe(&self) -> broadcast::Receiver<ProbeEvent> {
+        self.event_sender.subscribe()
+    }
+
+    pub fn probes(&self) -> Vec<Probe> {
+        self.probes.lock().unwrap().probes().to_vec()
+    }
+
+    pub fn add_probe(&self, probe: Probe) {
+        self.probes.lock().unwrap().insert(probe);
+    }
+
+    pub fn remove_probe(&self, name: &str) -> bool {
+        self.probes.lock().unwrap().remove(name)
+    }
+
+    /// Whether a probe was active on the last frame, `None` before it was first evaluated
+    pub fn is_active(&self, name: &str) -> Option<bool> {
+        self.probes.lock().unwrap().state(name).map(|state| state.active)
+    }
+
+    pub fn state(&self, name: &str) -> Option<ProbeState> {
+        self.probes.lock().unwrap().state(name)
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.task.is_busy().await
+    }
+
+    pub async fn start_probes(&self) -> Result<(), String> {
+        // Probes only care about the current state of the screen, never about every frame
+        let mut latest = self.graphics_service.subscribe_latest();
+        let probes = self.probes.clone();
+        let event_sender = self.event_sender.clone();
+
+        self.task.start(move |cancelled| async move {
+            loop {
+                // Stopping doesn't wait for the next frame
+                let changed = tokio::select! {
+                    _ = cancelled.cancelled() => break,
+                    changed = latest.changed() => changed,
+                };
+                if changed.is_err() {
+                    break;
+                }
+                let Some(frame) = latest.borrow_and_update().clone() else {
+                    continue;
+                };
+                // Sampling a few pixels is cheap enough to do inline
+                let events = probes.lock().unwrap().evaluate(&frame);
+                for event in events {
+                    let _ = event_sender.send(event);
+                }
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Stop evaluating, the frame in progress is finished before this returns
+    pub async fn stop_probes(&self) {
+        self.task.stop().await;
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for ProbeService {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_probes().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_probes().await;
+        Ok(())
+    }
+}
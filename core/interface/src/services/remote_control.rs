@@ -0,0 +1,354 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use reqwest::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio_util::sync::CancellationToken;
+
+use super::graphics_capture::GraphicsCaptureService;
+use super::image_ops;
+use super::service_manager::ServiceManager;
+
+/// How long a `getUpdates` request waits for new messages
+const POLL_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// Pause after a failed poll, e.g. while offline
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// Longest a screenshot waits for the next captured frame
+const SCREENSHOT_TIMEOUT: Duration = Duration::from_secs(2);
+
+const HELP: &str = "/status - running services and capture\n\
+                    /pause - stop all services, capture keeps running\n\
+                    /resume - start all services\n\
+                    /screenshot - the current frame";
+
+/// Telegram bot taking commands and the chats allowed to send them, persisted across restarts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteControlConfig {
+    /// Token from @BotFather, nothing is polled while it is empty
+    pub telegram_token: String,
+    /// Chats commands are accepted from, others are told their id so it can be added here
+    pub allowed_chats: Vec<i64>,
+}
+
+impl RemoteControlConfig {
+    pub fn path() -> PathBuf {
+        crate::config_dir().join("remote_control.json")
+    }
+
+    /// Load the saved settings, falling back to defaults if there are none
+    pub fn load() -> Self {
+        let path = Self::path();
+        let Ok(json) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&json).unwrap_or_else(|e| {
+            tracing::warn!(path = %path.display(), error = %e, "Ignoring invalid remote control settings");
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize remote control settings: {}", e))?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.telegram_token.is_empty()
+    }
+}
+
+#[derive(Deserialize)]
+struct TelegramResponse<T> {
+    ok: bool,
+    #[serde(default)]
+    description: Option<String>,
+    result: Option<T>,
+}
+
+#[derive(Deserialize)]
+struct Update {
+    update_id: i64,
+    #[serde(default)]
+    message: Option<Message>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    chat: Chat,
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Chat {
+    id: i64,
+}
+
+/// Remote commands a bot may be sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Status,
+    Pause,
+    Resume,
+    Screenshot,
+    Help,
+}
+
+impl Command {
+    /// `/status` or `/status@SomeBot`, `None` for other text
+    fn parse(text: &str) -> Option<Self> {
+        let command = text.split_whitespace().next()?.strip_prefix('/')?;
+        let command = command.split('@').next().unwrap_or(command);
+        match command.to_lowercase().as_str() {
+            "status" => Some(Command::Status),
+            "pause" | "stop" => Some(Command::Pause),
+            "resume" => Some(Command::Resume),
+            "screenshot" => Some(Command::Screenshot),
+            "help" | "start" => Some(Command::Help),
+            _ => None,
+        }
+    }
+}
+
+/// Reply to a command
+enum Reply {
+    Text(String),
+    Photo(Vec<u8>),
+}
+
+/// Telegram bot to check on, pause and resume the bot from a phone
+///
+/// Commands from chats in [`RemoteControlConfig::allowed_chats`] are routed through the
+/// [`ServiceManager`]. It runs outside the manager, like the control API, so `/pause` doesn't
+/// stop it from hearing `/resume`.
+pub struct RemoteControl {
+    config: RemoteControlConfig,
+    client: reqwest::Client,
+    services: Arc<ServiceManager>,
+    graphics_service: Arc<GraphicsCaptureService>,
+    shutdown: StdMutex<Option<CancellationToken>>,
+}
+
+impl RemoteControl {
+    pub fn new(
+        services: Arc<ServiceManager>,
+        graphics_service: Arc<GraphicsCaptureService>,
+        config: RemoteControlConfig,
+    ) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(POLL_TIMEOUT + Duration::from_secs(10))
+            .build()
+            .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+        Ok(Self {
+            config,
+            client,
+            services,
+            graphics_service,
+            shutdown: StdMutex::new(None),
+        })
+    }
+
+    pub fn config(&self) -> &RemoteControlConfig {
+        &self.config
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.shutdown.lock().unwrap().is_some()
+    }
+
+    /// Start polling for commands, nothing happens if already listening
+    pub fn start_listening(self: &Arc<Self>) -> Result<(), String> {
+        if !self.config.is_enabled() {
+            return Err("No Telegram bot token configured".to_string());
+        }
+        let token = {
+            let mut shutdown = self.shutdown.lock().unwrap();
+            if shutdown.is_some() {
+                return Ok(());
+            }
+            shutdown.insert(CancellationToken::new()).clone()
+        };
+
+        let remote = self.clone();
+        tokio::spawn(async move {
+            let mut offset = 0;
+            loop {
+                let updates = tokio::select! {
+                    _ = token.cancelled() => break,
+                    updates = remote.updates(offset) => updates,
+                };
+                let updates = match updates {
+                    Ok(updates) => updates,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "Polling Telegram failed");
+                        tokio::select! {
+                            _ = token.cancelled() => break,
+                            _ = tokio::time::sleep(RETRY_DELAY) => continue,
+                        }
+                    }
+                };
+                for update in updates {
+                    offset = offset.max(update.update_id + 1);
+                    if let Some(message) = update.message {
+                        remote.handle(message).await;
+                    }
+                }
+            }
+        });
+
+        Ok(())
+    }
+
+    pub fn stop_listening(&self) {
+        if let Some(token) = self.shutdown.lock().unwrap().take() {
+            token.cancel();
+        }
+    }
+
+    fn url(&self, method: &str) -> String {
+        format!("https://api.telegram.org/bot{}/{}", self.config.telegram_token, method)
+    }
+
+    async fn call<T: serde::de::DeserializeOwned>(&self, request: reqwest::RequestBuilder) -> Result<T, String> {
+        // The token is part of the URL, keep it out of error messages
+        let response: TelegramResponse<T> = request
+            .send()
+            .await
+            .map_err(|e| e.without_url().to_string())?
+            .json()
+            .await
+            .map_err(|e| e.without_url().to_string())?;
+        match response.result {
+            Some(result) if response.ok => Ok(result),
+            _ => Err(response.description.unwrap_or_else(|| "Telegram request failed".to_string())),
+        }
+    }
+
+    async fn updates(&self, offset: i64) -> Result<Vec<Update>, String> {
+        let request = self.client.get(self.url("getUpdates")).query(&[
+            ("offset", offset.to_string()),
+            ("timeout", POLL_TIMEOUT.as_secs().to_string()),
+            ("allowed_updates", "[\"message\"]".to_string()),
+        ]);
+        self.call(request).await
+    }
+
+    async fn handle(&self, message: Message) {
+        let chat = message.chat.id;
+        let Some(command) = message.text.as_deref().and_then(Command::parse) else {
+            return;
+        };
+        let reply = if self.config.allowed_chats.contains(&chat) {
+            tracing::info!(chat, ?command, "Remote command");
+            self.run(command).await
+        } else {
+            tracing::warn!(chat, "Ignoring command from a chat that isn't allowed");
+            Reply::Text(format!("This chat ({}) isn't allowed to control the bot", chat))
+        };
+        if let Err(e) = self.reply(chat, reply).await {
+            tracing::warn!(chat, error = %e, "Failed to reply to remote command");
+        }
+    }
+
+    async fn run(&self, command: Command) -> Reply {
+        let result = match command {
+            Command::Status => Ok(self.status().await),
+            Command::Pause => self.services.stop_all().await.map(|_| "Paused".to_string()),
+            Command::Resume => self.services.start_all().await.map(|_| "Resumed".to_string()),
+            Command::Screenshot => return self.screenshot().await.map_or_else(Reply::Text, Reply::Photo),
+            Command::Help => Ok(HELP.to_string()),
+        };
+        Reply::Text(result.unwrap_or_else(|e| format!("Failed: {}", e)))
+    }
+
+    async fn status(&self) -> String {
+        let capture = if self.graphics_service.is_capturing().await {
+            "running"
+        } else {
+            "stopped"
+        };
+        format!(
+            "Capture: {}\n{}\n\n{}",
+            capture,
+            self.graphics_service.get_metrics(),
+            self.services.status().await
+        )
+    }
+
+    /// Next captured frame as PNG
+    async fn screenshot(&self) -> Result<Vec<u8>, String> {
+        if !self.graphics_service.is_capturing().await {
+            return Err("Capture is stopped".to_string());
+        }
+        let mut frames = self.graphics_service.subscribe();
+        let frame = tokio::time::timeout(SCREENSHOT_TIMEOUT, frames.recv())
+            .await
+            .map_err(|_| "No frame captured in time".to_string())?
+            .map_err(|e| format!("Failed to receive frame: {}", e))?;
+        tokio::task::spawn_blocking(move || image_ops::encode_png(&image_ops::bgra_to_rgb(&frame)?))
+            .await
+            .map_err(|e| format!("Screenshot task failed: {}", e))?
+    }
+
+    async fn reply(&self, chat: i64, reply: Reply) -> Result<(), String> {
+        let request = match reply {
+            Reply::Text(text) => self
+                .client
+                .post(self.url("sendMessage"))
+                .json(&json!({ "chat_id": chat, "text": text })),
+            Reply::Photo(png) => {
+                let photo = Part::bytes(png)
+                    .file_name("screenshot.png")
+                    .mime_str("image/png")
+                    .map_err(|e| format!("Failed to attach screenshot: {}", e))?;
+                self.client
+                    .post(self.url("sendPhoto"))
+                    .multipart(Form::new().text("chat_id", chat.to_string()).part("photo", photo))
+            }
+        };
+        self.call::<serde_json::Value>(request).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(Command::parse("/status"), Some(Command::Status));
+        assert_eq!(Command::parse("/pause"), Some(Command::Pause));
+        assert_eq!(Command::parse("/stop"), Some(Command::Pause));
+        assert_eq!(Command::parse("/resume"), Some(Command::Resume));
+        assert_eq!(Command::parse("/screenshot"), Some(Command::Screenshot));
+        assert_eq!(Command::parse("/start"), Some(Command::Help));
+        assert_eq!(Command::parse("/help"), Some(Command::Help));
+    }
+
+    #[test]
+    fn test_parse_mentions_and_arguments() {
+        // Groups address commands to one bot as `/command@BotName`
+        assert_eq!(Command::parse("/status@StarryBot"), Some(Command::Status));
+        assert_eq!(Command::parse("/Resume@StarryBot now"), Some(Command::Resume));
+        assert_eq!(Command::parse("  /PAUSE please"), Some(Command::Pause));
+    }
+
+    #[test]
+    fn test_parse_ignores_other_text() {
+        assert_eq!(Command::parse("status"), None);
+        assert_eq!(Command::parse("/unknown"), None);
+        assert_eq!(Command::parse("/"), None);
+        assert_eq!(Command::parse("@StarryBot /status"), None);
+        assert_eq!(Command::parse(""), None);
+    }
+}
@@ -0,0 +1,571 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use platforms::input::KeyKind;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, watch};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::BotConfig;
+use crate::services::{Service, ServiceError, ServiceTask};
+use super::input_scheduler::{InputAction, InputPriority, InputScheduler};
+use super::minimap_v2::MinimapService;
+use super::player_arrow::PlayerPosition;
+use super::stuck_detector::{PlayerStuck, RecoveryAction, StuckConfig, StuckDetector};
+
+/// Capacity of the event channel, slow subscribers skip old events
+const EVENT_CHANNEL_CAPACITY: usize = 32;
+
+/// A point of a route in minimap pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Waypoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Waypoint {
+    pub fn distance_to(&self, position: &PlayerPosition) -> f32 {
+        (position.x - self.x).hypot(position.y - self.y)
+    }
+}
+
+/// Named list of waypoints, saved as JSON
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Route {
+    pub name: String,
+    pub waypoints: Vec<Waypoint>,
+    /// Start over from the first waypoint after reaching the last
+    #[serde(default)]
+    pub looped: bool,
+}
+
+impl Route {
+    /// Directory routes are saved in, per game profile
+    pub fn dir() -> PathBuf {
+        crate::config::profile_dir().join("routes")
+    }
+
+    pub fn path(name: &str) -> PathBuf {
+        Self::dir().join(format!("{}.json", name))
+    }
+
+    /// Names of all saved routes
+    pub fn list() -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(Self::dir()) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "json"))
+            .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string))
+            .collect()
+    }
+
+    pub fn load(name: &str) -> Result<Self, String> {
+        Self::load_file(Self::path(name))
+    }
+
+    pub fn load_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read route {}: {}", path.display(), e))?;
+        serde_json::from_str(&json).map_err(|e| format!("Invalid route {}: {}", path.display(), e))
+    }
+
+    /// The same waypoints walked from the last to the first, e.g. to retreat the way the
+    /// player came
+    pub fn reversed(&self) -> Self {
+        let mut route = self.clone();
+        route.waypoints.reverse();
+        route
+    }
+
+    /// Save to [`Route::dir`] under the route's name
+    pub fn save(&self) -> Result<(), String> {
+        if self.name.is_empty() || self.name.contains(['/', '\\', '.']) {
+            return Err(format!("Invalid route name {:?}", self.name));
+        }
+        let dir = Self::dir();
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        let path = Self::path(&self.name);
+        let json = serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize route: {}", e))?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write route {}: {}", path.display(), e))
+    }
+}
+
+/// How movement keys move the character
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MovementMode {
+    /// Keys move along the screen axes (up, left, down, right), typical for top-down games
+    #[default]
+    Screen,
+    /// Left and right turn the character and forward moves towards its heading, needs an
+    /// arrow shaped player marker
+    Tank,
+}
+
+/// Keys used to move the character
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MovementKeys {
+    pub forward: KeyKind,
+    pub backward: KeyKind,
+    pub left: KeyKind,
+    pub right: KeyKind,
+}
+
+impl Default for MovementKeys {
+    fn default() -> Self {
+        Self {
+            forward: KeyKind::W,
+            backward: KeyKind::S,
+            left: KeyKind::A,
+            right: KeyKind::D,
+        }
+    }
+}
+
+/// How routes are recorded and walked
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteConfig {
+    pub mode: MovementMode,
+    pub keys: MovementKeys,
+    /// A waypoint is reached within this many minimap pixels
+    pub arrive_radius: f32,
+    /// Recorded waypoints are at least this many minimap pixels apart
+    pub record_spacing: f32,
+    /// In tank mode the character turns until it faces the waypoint within this many degrees
+    pub heading_tolerance: f32,
+    /// How long a movement key is held per step, in milliseconds when serialized
+    #[serde(with = "super::duration_millis")]
+    pub step: Duration,
+    /// Playback fails if a waypoint isn't reached within this time
+    #[serde(with = "super::duration_millis")]
+    pub waypoint_timeout: Duration,
+    /// Playback fails if the player marker isn't seen for this long
+    #[serde(with = "super::duration_millis")]
+    pub position_timeout: Duration,
+    /// Detecting and getting out of spots the player can't walk through
+    pub stuck: StuckConfig,
+}
+
+impl Default for RouteConfig {
+    fn default() -> Self {
+        Self {
+            mode: MovementMode::default(),
+            keys: MovementKeys::default(),
+            arrive_radius: 3.0,
+            record_spacing: 6.0,
+            heading_tolerance: 20.0,
+            step: Duration::from_millis(150),
+            waypoint_timeout: Duration::from_secs(20),
+            position_timeout: Duration::from_secs(2),
+            stuck: StuckConfig::default(),
+        }
+    }
+}
+
+/// Progress of a route being walked
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RouteEvent {
+    WaypointReached { route: String, index: usize },
+    /// The player didn't move towards waypoint `index`, `recovery` is what is tried next
+    PlayerStuck {
+        route: String,
+        index: usize,
+        stuck: PlayerStuck,
+        recovery: Option<RecoveryAction>,
+    },
+    /// Waypoint `index` was given up on to get unstuck
+    WaypointSkipped { route: String, index: usize },
+    Finished { route: String },
+    Failed { route: String, reason: String },
+    Stopped { route: String },
+}
+
+/// What the route service is doing
+#[derive(Debug, Clone, PartialEq)]
+pub enum RouteStatus {
+    Idle,
+    Recording { name: String, waypoints: usize },
+    Playing { name: String, index: usize },
+}
+
+type LatestPosition = Arc<StdMutex<Option<(PlayerPosition, Instant)>>>;
+
+/// Records player positions into waypoint routes and walks them by sending movement input
+///
+/// Positions come from the minimap service's player arrow detector, so the minimap service
+/// must be running for both recording and playback.
+#[derive(Clone)]
+pub struct RouteService {
+    minimap: Arc<MinimapService>,
+    scheduler: Arc<InputScheduler>,
+    config: Arc<StdMutex<RouteConfig>>,
+    status: Arc<StdMutex<RouteStatus>>,
+    recording: Arc<StdMutex<Route>>,
+    task: ServiceTask,
+    event_sender: broadcast::Sender<RouteEvent>,
+}
+
+impl RouteService {
+    pub fn new(minimap: Arc<MinimapService>, scheduler: Arc<InputScheduler>, config: RouteConfig) -> Self {
+        Self {
+            minimap,
+            scheduler,
+            config: Arc::new(StdMutex::new(config)),
+            status: Arc::new(StdMutex::new(RouteStatus::Idle)),
+            recording: Arc::new(StdMutex::new(Route::default())),
+            task: ServiceTask::new(),
+            event_sender: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<RouteEvent> {
+        self.event_sender.subscribe()
+    }
+
+    pub fn status(&self) -> RouteStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    pub fn config(&self) -> RouteConfig {
+        *self.config.lock().unwrap()
+    }
+
+    /// Change the configuration, a route being walked uses it from the next step on
+    pub fn set_config(&self, config: RouteConfig) {
+        *self.config.lock().unwrap() = config;
+    }
+
+    /// Use the keybinds of every config published on `configs`
+    pub fn follow_config(&self, mut configs: watch::Receiver<BotConfig>) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            while configs.changed().await.is_ok() {
+                let keybinds = configs.borrow_and_update().keybinds();
+                service.set_config(keybinds.route_config(service.config()));
+            }
+        });
+    }
+
+    /// Start adding the player position to a new route whenever it moved far enough
+    pub async fn start_recording(&self, name: impl Into<String>) -> Result<(), String> {
+        let name = name.into();
+        let mut receiver = self.minimap.subscribe_player();
+        let recording = self.recording.clone();
+        let status = self.status.clone();
+        let config = self.config.clone();
+
+        let started = self.task.start(move |cancelled| {
+            *recording.lock().unwrap() = Route {
+                name: name.clone(),
+                waypoints: Vec::new(),
+                looped: false,
+            };
+            *status.lock().unwrap() = RouteStatus::Recording { name, waypoints: 0 };
+
+            async move {
+                loop {
+                    let position = tokio::select! {
+                        _ = cancelled.cancelled() => break,
+                        position = receiver.recv() => match position {
+                            Ok(position) => position,
+                            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                            Err(broadcast::error::RecvError::Closed) => break,
+                        },
+                    };
+
+                    let spacing = config.lock().unwrap().record_spacing;
+                    let mut route = recording.lock().unwrap();
+                    let far_enough = route
+                        .waypoints
+                        .last()
+                        .map_or(true, |last| last.distance_to(&position) >= spacing);
+                    if far_enough {
+                        route.waypoints.push(Waypoint {
+                            x: position.x,
+                            y: position.y,
+                        });
+                        *status.lock().unwrap() = RouteStatus::Recording {
+                            name: route.name.clone(),
+                            waypoints: route.waypoints.len(),
+                        };
+                    }
+                }
+            }
+        })
+        .await;
+
+        if !started {
+            return Err("A route is already being recorded or played".to_string());
+        }
+        Ok(())
+    }
+
+    /// Stop recording and save the route, returns it
+    pub async fn stop_recording(&self) -> Result<Route, String> {
+        if !matches!(self.status(), RouteStatus::Recording { .. }) {
+            return Err("No route is being recorded".to_string());
+        }
+        self.stop_task().await;
+
+        let route = std::mem::take(&mut *self.recording.lock().unwrap());
+        if route.waypoints.is_empty() {
+            return Err("No player positions were recorded".to_string());
+        }
+        route.save()?;
+        Ok(route)
+    }
+
+    /// Walk a route from its first waypoint, progress is reported through [`Self::subscribe`]
+    pub async fn play(&self, route: Route) -> Result<(), String> {
+        if route.waypoints.is_empty() {
+            return Err(format!("Route {} has no waypoints", route.name));
+        }
+        let started = self.task.start(|cancelled| {
+            *self.status.lock().unwrap() = RouteStatus::Playing {
+                name: route.name.clone(),
+                index: 0,
+            };
+
+            let latest: LatestPosition = Arc::new(StdMutex::new(None));
+            Self::track_position(self.minimap.subscribe_player(), latest.clone(), cancelled.clone());
+
+            let walker = Walker {
+                scheduler: self.scheduler.clone(),
+                config: self.config.clone(),
+                status: self.status.clone(),
+                latest,
+                token: cancelled,
+                event_sender: self.event_sender.clone(),
+            };
+            let status = self.status.clone();
+            async move {
+                let event = match walker.walk(&route).await {
+                    Ok(()) => RouteEvent::Finished {
+                        route: route.name.clone(),
+                    },
+                    Err(_) if walker.token.is_cancelled() => RouteEvent::Stopped {
+                        route: route.name.clone(),
+                    },
+                    Err(reason) => RouteEvent::Failed {
+                        route: route.name.clone(),
+                        reason,
+                    },
+                };
+                walker.token.cancel();
+                *status.lock().unwrap() = RouteStatus::Idle;
+                let _ = walker.event_sender.send(event);
+            }
+        })
+        .await;
+
+        if !started {
+            return Err("A route is already being recorded or played".to_string());
+        }
+        Ok(())
+    }
+
+    /// Stop walking or recording, a recording in progress is discarded
+    pub async fn stop(&self) {
+        self.stop_task().await;
+        self.recording.lock().unwrap().waypoints.clear();
+    }
+
+    async fn stop_task(&self) {
+        self.task.stop().await;
+        *self.status.lock().unwrap() = RouteStatus::Idle;
+    }
+
+    /// Keep `latest` up to date with the detected player position until cancelled
+    fn track_position(
+        mut receiver: broadcast::Receiver<PlayerPosition>,
+        latest: LatestPosition,
+        token: CancellationToken,
+    ) {
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    position = receiver.recv() => match position {
+                        Ok(position) => *latest.lock().unwrap() = Some((position, Instant::now())),
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                }
+            }
+        });
+    }
+}
+
+/// State of one route playback
+struct Walker {
+    scheduler: Arc<InputScheduler>,
+    config: Arc<StdMutex<RouteConfig>>,
+    status: Arc<StdMutex<RouteStatus>>,
+    latest: LatestPosition,
+    token: CancellationToken,
+    event_sender: broadcast::Sender<RouteEvent>,
+}
+
+impl Walker {
+    async fn walk(&self, route: &Route) -> Result<(), String> {
+        loop {
+            for (index, waypoint) in route.waypoints.iter().enumerate() {
+                *self.status.lock().unwrap() = RouteStatus::Playing {
+                    name: route.name.clone(),
+                    index,
+                };
+                let event = if self.reach(route, index, waypoint).await? {
+                    RouteEvent::WaypointReached {
+                        route: route.name.clone(),
+                        index,
+                    }
+                } else {
+                    RouteEvent::WaypointSkipped {
+                        route: route.name.clone(),
+                        index,
+                    }
+                };
+                let _ = self.event_sender.send(event);
+            }
+            if !route.looped {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Step towards `waypoint` until the player is within the arrive radius
+    ///
+    /// Returns `false` if the waypoint was skipped to get the player unstuck.
+    async fn reach(&self, route: &Route, index: usize, waypoint: &Waypoint) -> Result<bool, String> {
+        let started = Instant::now();
+        let mut stuck_detector = StuckDetector::new(self.config.lock().unwrap().stuck);
+        let mut attempt = 0;
+        loop {
+            if self.token.is_cancelled() {
+                return Err("Stopped".to_string());
+            }
+            let config = *self.config.lock().unwrap();
+            if started.elapsed() > config.waypoint_timeout {
+                return Err(format!("Waypoint ({:.0}, {:.0}) not reached in time", waypoint.x, waypoint.y));
+            }
+
+            let latest = *self.latest.lock().unwrap();
+            let position = match latest {
+                Some((position, seen_at)) if seen_at.elapsed() <= config.position_timeout => position,
+                Some(_) => return Err("Lost track of the player".to_string()),
+                None if started.elapsed() > config.position_timeout => {
+                    return Err("Player position is not being detected".to_string())
+                }
+                None => {
+                    tokio::time::sleep(config.step).await;
+                    continue;
+                }
+            };
+            if waypoint.distance_to(&position) <= config.arrive_radius {
+                return Ok(true);
+            }
+
+            stuck_detector.set_config(config.stuck);
+            if let Some(stuck) = stuck_detector.update(&position, Instant::now()) {
+                let recovery = config.stuck.recovery.action(attempt);
+                attempt += 1;
+                let _ = self.event_sender.send(RouteEvent::PlayerStuck {
+                    route: route.name.clone(),
+                    index,
+                    stuck,
+                    recovery,
+                });
+                let action = match (recovery, config.stuck.recovery.jump_key, config.stuck.recovery.turn_key) {
+                    (Some(RecoveryAction::Jump), Some(key), _) => InputAction::KeyTap(key),
+                    (Some(RecoveryAction::Turn), _, Some(key)) => {
+                        InputAction::KeyHold(key, config.stuck.recovery.turn_duration)
+                    }
+                    (Some(RecoveryAction::Repath), _, _) => return Ok(false),
+                    _ => {
+                        return Err(format!(
+                            "Player is stuck at ({:.0}, {:.0}) on the way to waypoint {}",
+                            stuck.x, stuck.y, index
+                        ))
+                    }
+                };
+                self.send(action).await?;
+                continue;
+            }
+
+            let key = Self::step_key(&config, &position, waypoint);
+            self.send(InputAction::KeyHold(key, config.step)).await?;
+        }
+    }
+
+    /// Schedule an action and wait until it ran, cancelling it when playback is stopped
+    async fn send(&self, action: InputAction) -> Result<(), String> {
+        let handle = self.scheduler.schedule(action, InputPriority::Normal).await?;
+        let step = handle.cancellation_token();
+        tokio::select! {
+            _ = self.token.cancelled() => {
+                step.cancel();
+                Err("Stopped".to_string())
+            }
+            result = handle.wait() => result,
+        }
+    }
+
+    /// Key that moves the player closer to `waypoint`
+    fn step_key(config: &RouteConfig, position: &PlayerPosition, waypoint: &Waypoint) -> KeyKind {
+        let (dx, dy) = (waypoint.x - position.x, waypoint.y - position.y);
+        let keys = config.keys;
+
+        match (config.mode, position.heading_degrees) {
+            (MovementMode::Tank, Some(heading)) => {
+                // Compass bearing of the waypoint, screen y grows downwards
+                let bearing = dx.atan2(-dy).to_degrees().rem_euclid(360.0);
+                let turn = (bearing - heading + 540.0).rem_euclid(360.0) - 180.0;
+                if turn.abs() <= config.heading_tolerance {
+                    keys.forward
+                } else if turn > 0.0 {
+                    keys.right
+                } else {
+                    keys.left
+                }
+            }
+            // Without a heading, moving along the screen axes is the best guess
+            _ => {
+                if dx.abs() >= dy.abs() {
+                    if dx > 0.0 {
+                        keys.right
+                    } else {
+                        keys.left
+                    }
+                } else if dy > 0.0 {
+                    keys.backward
+                } else {
+                    keys.forward
+                }
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for RouteService {
+    async fn start(&self) -> Result<(), ServiceError> {
+        Ok(())
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        RouteService::stop(self).await;
+        Ok(())
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        vec!["MinimapService".to_string(), "InputScheduler".to_string()]
+    }
+}
@@ -0,0 +1,250 @@
+use std::path::Path;
+
+#[cfg(not(feature = "opencv"))]
+use image::{imageops, GrayImage};
+#[cfg(feature = "opencv")]
+use opencv::{
+    core::{min_max_loc, no_array, Mat, Point, Size},
+    imgcodecs::{imread, IMREAD_GRAYSCALE},
+    imgproc::{match_template_def, resize, INTER_AREA, INTER_LINEAR, TM_CCOEFF_NORMED},
+    prelude::*,
+};
+
+use super::graphics_capture::CapturedFrame;
+#[cfg(not(feature = "opencv"))]
+use super::image_ops;
+#[cfg(feature = "opencv")]
+use super::vision::{bgra_mat, bgra_mat_region, to_gray};
+use super::vision::Rect;
+
+/// Grayscale image templates are searched in, a `Mat` with OpenCV and an `image` buffer without
+#[cfg(feature = "opencv")]
+pub type GrayFrame = Mat;
+#[cfg(not(feature = "opencv"))]
+pub type GrayFrame = GrayImage;
+
+/// Read an image file as a grayscale template
+#[cfg(feature = "opencv")]
+pub fn load_gray(path: &Path) -> Result<GrayFrame, String> {
+    let image = imread(&path.to_string_lossy(), IMREAD_GRAYSCALE)
+        .map_err(|e| format!("Failed to read template {}: {}", path.display(), e))?;
+    if image.empty() {
+        return Err(format!("Template {} is empty or unreadable", path.display()));
+    }
+    Ok(image)
+}
+
+#[cfg(not(feature = "opencv"))]
+pub fn load_gray(path: &Path) -> Result<GrayFrame, String> {
+    let image = image::open(path).map_err(|e| format!("Failed to read template {}: {}", path.display(), e))?;
+    if image.width() == 0 || image.height() == 0 {
+        return Err(format!("Template {} is empty", path.display()));
+    }
+    Ok(image.to_luma8())
+}
+
+/// Grayscale copy of a BGRA frame, or only of `region` of it
+#[cfg(feature = "opencv")]
+pub fn gray_frame(frame: &CapturedFrame, region: Option<Rect>) -> Result<GrayFrame, String> {
+    match region {
+        Some(region) => to_gray(&bgra_mat_region(frame, region)?),
+        None => to_gray(&bgra_mat(frame)?),
+    }
+}
+
+#[cfg(not(feature = "opencv"))]
+pub fn gray_frame(frame: &CapturedFrame, region: Option<Rect>) -> Result<GrayFrame, String> {
+    match region {
+        Some(region) => image_ops::bgra_region_to_gray(frame, region),
+        None => image_ops::bgra_to_gray(frame),
+    }
+}
+
+/// Width and height of a grayscale image
+#[cfg(feature = "opencv")]
+pub fn gray_size(image: &GrayFrame) -> Result<(i32, i32), String> {
+    let size = image.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
+    Ok((size.width, size.height))
+}
+
+#[cfg(not(feature = "opencv"))]
+pub fn gray_size(image: &GrayFrame) -> Result<(i32, i32), String> {
+    Ok((image.width() as i32, image.height() as i32))
+}
+
+/// Window height templates are cut at unless configured otherwise
+pub const DEFAULT_REFERENCE_HEIGHT: u32 = 1080;
+
+/// Templates scaled below this many pixels on either side are too small to match reliably
+const MIN_SCALED_SIDE: i32 = 8;
+
+/// Best match of a template across the searched scales
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScaledMatch {
+    /// Matched area in pixels of the searched image
+    pub rect: Rect,
+    /// Normalized correlation (-1.0 - 1.0)
+    pub score: f64,
+    /// Scale the template was resized by
+    pub scale: f64,
+}
+
+/// Multi-scale template search that adapts templates to the captured resolution
+///
+/// The template is expected at `image height / reference height` times its size and searched
+/// at that estimate times each factor. The winning scale is remembered and tried alone first
+/// as long as the image height doesn't change.
+#[derive(Debug, Clone)]
+pub struct ScaleSearch {
+    reference_height: u32,
+    factors: Vec<f64>,
+    /// Image height and scale of the last match above the threshold
+    winner: Option<(i32, f64)>,
+}
+
+impl ScaleSearch {
+    pub fn new(reference_height: u32, factors: Vec<f64>) -> Self {
+        Self {
+            reference_height: reference_height.max(1),
+            factors,
+            winner: None,
+        }
+    }
+
+    /// `steps` factors spread evenly over `1.0 - spread ..= 1.0 + spread`
+    pub fn around(reference_height: u32, spread: f64, steps: usize) -> Self {
+        let spread = spread.clamp(0.0, 0.9);
+        let factors = if spread == 0.0 || steps < 2 {
+            vec![1.0]
+        } else {
+            (0..steps)
+                .map(|i| 1.0 - spread + 2.0 * spread * i as f64 / (steps - 1) as f64)
+                .collect()
+        };
+        Self::new(reference_height, factors)
+    }
+
+    pub fn reference_height(&self) -> u32 {
+        self.reference_height
+    }
+
+    /// Template scale expected in an image `image_height` pixels high
+    pub fn estimate(&self, image_height: i32) -> f64 {
+        image_height as f64 / self.reference_height as f64
+    }
+
+    /// Scale of the last good match, if any
+    pub fn winner(&self) -> Option<f64> {
+        self.winner.map(|(_, scale)| scale)
+    }
+
+    /// Forget the winning scale, e.g. after the template changed
+    pub fn reset(&mut self) {
+        self.winner = None;
+    }
+
+    /// Find `template` in `gray`, scaled for an image `image_height` pixels high
+    ///
+    /// `image_height` is the height of the whole captured frame even when `gray` is only a
+    /// region of it. Returns the best match across all scales, whether or not it reaches
+    /// `threshold`.
+    pub fn find(
+        &mut self,
+        gray: &GrayFrame,
+        template: &GrayFrame,
+        image_height: i32,
+        threshold: f64,
+    ) -> Result<Option<ScaledMatch>, String> {
+        if let Some((height, scale)) = self.winner {
+            if height == image_height {
+                if let Some(found) = Self::match_at(gray, template, scale)? {
+                    if found.score >= threshold {
+                        return Ok(Some(found));
+                    }
+                }
+            }
+        }
+
+        let estimate = self.estimate(image_height);
+        let mut best: Option<ScaledMatch> = None;
+        for factor in &self.factors {
+            let Some(found) = Self::match_at(gray, template, estimate * factor)? else {
+                continue;
+            };
+            if best.map_or(true, |best| found.score > best.score) {
+                best = Some(found);
+            }
+        }
+
+        self.winner = best
+            .filter(|best| best.score >= threshold)
+            .map(|best| (image_height, best.scale));
+        Ok(best)
+    }
+
+    /// Template size scaled by `scale`, `None` if it ends up too small or doesn't fit in `gray`
+    fn scaled_size(gray: &GrayFrame, template: &GrayFrame, scale: f64) -> Result<Option<(i32, i32)>, String> {
+        let (image_width, image_height) = gray_size(gray)?;
+        let (template_width, template_height) = gray_size(template)?;
+        let width = (template_width as f64 * scale).round() as i32;
+        let height = (template_height as f64 * scale).round() as i32;
+        // Templates already smaller than the minimum may still be matched at their own size
+        let min_side = MIN_SCALED_SIDE.min(template_width).min(template_height).max(1);
+        if width < min_side || height < min_side || width > image_width || height > image_height {
+            return Ok(None);
+        }
+        Ok(Some((width, height)))
+    }
+
+    /// Match the template resized by `scale`, `None` if it ends up too small or doesn't fit
+    #[cfg(feature = "opencv")]
+    pub fn match_at(gray: &GrayFrame, template: &GrayFrame, scale: f64) -> Result<Option<ScaledMatch>, String> {
+        let Some((width, height)) = Self::scaled_size(gray, template, scale)? else {
+            return Ok(None);
+        };
+
+        let scaled;
+        let template = if (width, height) == gray_size(template)? {
+            template
+        } else {
+            let interpolation = if scale < 1.0 { INTER_AREA } else { INTER_LINEAR };
+            let mut resized = Mat::default();
+            resize(template, &mut resized, Size::new(width, height), 0.0, 0.0, interpolation)
+                .map_err(|e| format!("Failed to scale template: {}", e))?;
+            scaled = resized;
+            &scaled
+        };
+
+        let mut result = Mat::default();
+        match_template_def(gray, template, &mut result, TM_CCOEFF_NORMED)
+            .map_err(|e| format!("Template matching failed: {}", e))?;
+
+        let mut max_val = 0.0;
+        let mut max_loc = Point::default();
+        min_max_loc(&result, None, Some(&mut max_val), None, Some(&mut max_loc), &no_array())
+            .map_err(|e| format!("Failed to find best match: {}", e))?;
+
+        Ok(Some(ScaledMatch {
+            rect: Rect::new(max_loc.x, max_loc.y, width, height),
+            score: max_val,
+            scale,
+        }))
+    }
+
+    #[cfg(not(feature = "opencv"))]
+    pub fn match_at(gray: &GrayFrame, template: &GrayFrame, scale: f64) -> Result<Option<ScaledMatch>, String> {
+        let Some((width, height)) = Self::scaled_size(gray, template, scale)? else {
+            return Ok(None);
+        };
+
+        let scaled;
+        let template = if (width, height) == gray_size(template)? {
+            template
+        } else {
+            scaled = imageops::resize(template, width as u32, height as u32, imageops::FilterType::Triangle);
+            &scaled
+        };
+
+        Ok(image_ops::match_template(gray, template).map(|(rect, score)| ScaledMatch { rect, score, scale }))
+    }
+}
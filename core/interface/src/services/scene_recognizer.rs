@@ -0,0 +1,197 @@
+use std::path::Path;
+
+use opencv::{
+    core::{no_array, DMatch, KeyPoint, Mat, Ptr, Size, Vector, NORM_HAMMING},
+    features2d::{BFMatcher, ORB},
+    imgcodecs::{imread, IMREAD_GRAYSCALE},
+    imgproc::{resize, INTER_AREA},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use super::graphics_capture::CapturedFrame;
+use super::vision::{bgra_mat, to_gray};
+
+/// Images are downscaled to this width before feature extraction, enough detail to tell
+/// screens apart and keeps extraction within a few milliseconds
+const WORKING_WIDTH: i32 = 640;
+
+/// Keypoints extracted per image
+const MAX_FEATURES: i32 = 500;
+
+/// Lowe's ratio test, a match is kept if it is clearly better than the second best candidate
+const RATIO_TEST: f32 = 0.75;
+
+/// Minimum number of good matches for a scene to be recognized
+pub const DEFAULT_MIN_MATCHES: usize = 20;
+
+/// How well the current frame matches a reference scene
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneMatch {
+    pub name: String,
+    /// Matches passing the ratio test
+    pub good_matches: usize,
+    /// Good matches relative to the keypoints of the reference (0.0 - 1.0)
+    pub score: f32,
+}
+
+struct SceneReference {
+    name: String,
+    keypoints: usize,
+    descriptors: Mat,
+}
+
+/// Recognizes which game screen is shown by matching ORB features against reference images
+///
+/// Unlike template matching this tolerates animated backgrounds, particles and small layout
+/// shifts since only a fraction of the keypoints has to match.
+pub struct SceneRecognizer {
+    orb: Ptr<ORB>,
+    matcher: Ptr<BFMatcher>,
+    references: Vec<SceneReference>,
+    min_matches: usize,
+}
+
+impl SceneRecognizer {
+    pub fn new() -> Result<Self, String> {
+        let mut orb = ORB::create_def().map_err(|e| format!("Failed to create ORB: {}", e))?;
+        orb.set_max_features(MAX_FEATURES)
+            .map_err(|e| format!("Failed to configure ORB: {}", e))?;
+        // Binary descriptors are compared by Hamming distance, cross check is replaced by the ratio test
+        let matcher = BFMatcher::create(NORM_HAMMING, false).map_err(|e| format!("Failed to create matcher: {}", e))?;
+
+        Ok(Self {
+            orb,
+            matcher,
+            references: Vec::new(),
+            min_matches: DEFAULT_MIN_MATCHES,
+        })
+    }
+
+    /// Load every PNG or JPEG in `dir` as a reference scene named after its file stem
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Self, String> {
+        let dir = dir.as_ref();
+        let mut recognizer = Self::new()?;
+
+        let entries = std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let path = entry.map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?.path();
+            let is_image = path.extension().and_then(|extension| extension.to_str()).is_some_and(|extension| {
+                ["png", "jpg", "jpeg"].iter().any(|image| extension.eq_ignore_ascii_case(image))
+            });
+            if !is_image {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let image = imread(&path.to_string_lossy(), IMREAD_GRAYSCALE)
+                .map_err(|e| format!("Failed to read scene {}: {}", path.display(), e))?;
+            if image.empty() {
+                return Err(format!("Scene {} is empty or unreadable", path.display()));
+            }
+            recognizer.add_reference(name, &image)?;
+        }
+
+        Ok(recognizer)
+    }
+
+    /// Add a grayscale screenshot of a scene, replacing one with the same name
+    pub fn add_reference(&mut self, name: impl Into<String>, gray: &Mat) -> Result<(), String> {
+        let name = name.into();
+        let (keypoints, descriptors) = self.extract(gray)?;
+        if keypoints == 0 {
+            return Err(format!("Scene {} has no distinctive features", name));
+        }
+
+        self.references.retain(|reference| reference.name != name);
+        self.references.push(SceneReference {
+            name,
+            keypoints,
+            descriptors,
+        });
+        Ok(())
+    }
+
+    pub fn remove_reference(&mut self, name: &str) {
+        self.references.retain(|reference| reference.name != name);
+    }
+
+    pub fn scene_names(&self) -> Vec<String> {
+        self.references.iter().map(|reference| reference.name.clone()).collect()
+    }
+
+    pub fn set_min_matches(&mut self, min_matches: usize) {
+        self.min_matches = min_matches;
+    }
+
+    /// The best matching scene of a captured frame, `None` if no scene has enough matches
+    pub fn recognize(&mut self, frame: &CapturedFrame) -> Result<Option<SceneMatch>, String> {
+        let gray = to_gray(&bgra_mat(frame)?)?;
+        self.recognize_gray(&gray)
+    }
+
+    pub fn recognize_gray(&mut self, gray: &Mat) -> Result<Option<SceneMatch>, String> {
+        let best = self
+            .scores(gray)?
+            .into_iter()
+            .filter(|scene| scene.good_matches >= self.min_matches)
+            .max_by(|a, b| a.score.total_cmp(&b.score));
+        Ok(best)
+    }
+
+    /// Match scores of every reference scene
+    pub fn scores(&mut self, gray: &Mat) -> Result<Vec<SceneMatch>, String> {
+        let (keypoints, descriptors) = self.extract(gray)?;
+        if keypoints == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut scores = Vec::with_capacity(self.references.len());
+        for reference in &self.references {
+            let mut matches = Vector::<Vector<DMatch>>::new();
+            self.matcher
+                .knn_train_match(&descriptors, &reference.descriptors, &mut matches, 2, &no_array(), false)
+                .map_err(|e| format!("Feature matching failed: {}", e))?;
+
+            let good_matches = matches
+                .iter()
+                .filter(|pair| match (pair.get(0), pair.get(1)) {
+                    (Ok(best), Ok(second)) => best.distance < RATIO_TEST * second.distance,
+                    (Ok(_), Err(_)) => true,
+                    _ => false,
+                })
+                .count();
+
+            scores.push(SceneMatch {
+                name: reference.name.clone(),
+                good_matches,
+                score: good_matches as f32 / reference.keypoints.max(1) as f32,
+            });
+        }
+
+        Ok(scores)
+    }
+
+    /// Downscale to the working width and compute ORB keypoints and descriptors
+    fn extract(&mut self, gray: &Mat) -> Result<(usize, Mat), String> {
+        let size = gray.size().map_err(|e| format!("Failed to get Mat size: {}", e))?;
+        let mut scaled = Mat::default();
+        let input = if size.width > WORKING_WIDTH {
+            let height = (size.height as f64 * WORKING_WIDTH as f64 / size.width as f64).round() as i32;
+            resize(gray, &mut scaled, Size::new(WORKING_WIDTH, height.max(1)), 0.0, 0.0, INTER_AREA)
+                .map_err(|e| format!("Failed to scale image: {}", e))?;
+            &scaled
+        } else {
+            gray
+        };
+
+        let mut keypoints = Vector::<KeyPoint>::new();
+        let mut descriptors = Mat::default();
+        self.orb
+            .detect_and_compute(input, &no_array(), &mut keypoints, &mut descriptors, false)
+            .map_err(|e| format!("Feature extraction failed: {}", e))?;
+        Ok((keypoints.len(), descriptors))
+    }
+}
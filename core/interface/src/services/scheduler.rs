@@ -0,0 +1,541 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, watch};
+
+use crate::config::BotConfig;
+use crate::services::{Service, ServiceError, ServiceTask};
+#[cfg(feature = "opencv")]
+use super::automation::{AutomationEngine, RuleAction};
+use super::event_bus::{BotEvent, EventBus};
+use super::frame_analyzer::{save_screenshot, FrameAnalyzer};
+#[cfg(feature = "scripting")]
+use super::scripting::ScriptService;
+
+/// Capacity of the event channel, slow subscribers skip old events
+const EVENT_CHANNEL_CAPACITY: usize = 32;
+
+/// How often due jobs are looked for, cron jobs fire at most this late
+const SCHEDULER_TICK: Duration = Duration::from_secs(1);
+
+/// Format of [`Timing::Once`] times
+const ONCE_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// When a job fires
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Timing {
+    /// Every `interval`, the first time one `interval` after the scheduler starts
+    Every {
+        #[serde(with = "super::duration_millis")]
+        interval: Duration,
+    },
+    /// Whenever the local time matches `minute hour day month weekday`, e.g. `"0 6 * * *"`
+    Cron { expression: String },
+    /// Once at the local time `at`, e.g. `"2026-10-15 06:00"`, never if that has passed
+    Once { at: String },
+}
+
+impl Timing {
+    /// Next time the job is due after `now`, `None` if it never is again
+    pub fn next_after(&self, now: DateTime<Local>) -> Result<Option<DateTime<Local>>, String> {
+        match self {
+            Timing::Every { interval } => {
+                if interval.is_zero() {
+                    return Err("Interval must not be zero".to_string());
+                }
+                let interval = chrono::Duration::from_std(*interval)
+                    .map_err(|e| format!("Invalid interval: {}", e))?;
+                Ok(Some(now + interval))
+            }
+            Timing::Cron { expression } => Ok(CronExpression::parse(expression)?.next_after(now)),
+            Timing::Once { at } => {
+                let at = NaiveDateTime::parse_from_str(at, ONCE_FORMAT)
+                    .map_err(|e| format!("Invalid time {:?}, expected YYYY-MM-DD HH:MM: {}", at, e))?;
+                Ok(Local.from_local_datetime(&at).earliest().filter(|at| *at > now))
+            }
+        }
+    }
+}
+
+/// Parsed cron expression, five fields of `*`, numbers, ranges `a-b`, steps `/n` and lists
+///
+/// Like cron, if both day and weekday are restricted a time matching either one matches. A field
+/// starting with `*`, e.g. `*/2`, doesn't count as restricted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronExpression {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl CronExpression {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!(
+                "Cron expression {:?} needs 5 fields (minute hour day month weekday)",
+                expression
+            ));
+        };
+        // Sunday is both 0 and 7
+        let mut weekday_bits = Self::parse_field(weekdays, 0, 7, "weekday")?;
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits = (weekday_bits | 1) & !(1 << 7);
+        }
+        Ok(Self {
+            minutes: Self::parse_field(minutes, 0, 59, "minute")?,
+            hours: Self::parse_field(hours, 0, 23, "hour")?,
+            days: Self::parse_field(days, 1, 31, "day")?,
+            months: Self::parse_field(months, 1, 12, "month")?,
+            weekdays: weekday_bits,
+            any_day: days.starts_with('*'),
+            any_weekday: weekdays.starts_with('*'),
+        })
+    }
+
+    /// Bit `n` is set if value `n` matches
+    fn parse_field(field: &str, min: u32, max: u32, name: &str) -> Result<u64, String> {
+        let invalid = |part: &str| format!("Invalid cron {} {:?}, expected {}-{}", name, part, min, max);
+        let mut bits = 0;
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => {
+                    let step = step.parse::<u32>().ok().filter(|step| *step > 0);
+                    (range, step.ok_or_else(|| invalid(part))?)
+                }
+                None => (part, 1),
+            };
+            let (start, end) = if range == "*" {
+                (min, max)
+            } else if let Some((start, end)) = range.split_once('-') {
+                let start = start.parse().map_err(|_| invalid(part))?;
+                let end = end.parse().map_err(|_| invalid(part))?;
+                (start, end)
+            } else {
+                let value = range.parse().map_err(|_| invalid(part))?;
+                // `5/15` means from 5 to the end in steps of 15
+                (value, if part.contains('/') { max } else { value })
+            };
+            if start < min || end > max || start > end {
+                return Err(invalid(part));
+            }
+            for value in (start..=end).step_by(step as usize) {
+                bits |= 1 << value;
+            }
+        }
+        Ok(bits)
+    }
+
+    pub fn matches(&self, time: &NaiveDateTime) -> bool {
+        self.matches_day(time) && self.hours & (1 << time.hour()) != 0 && self.minutes & (1 << time.minute()) != 0
+    }
+
+    fn matches_day(&self, time: &NaiveDateTime) -> bool {
+        let day = self.days & (1 << time.day()) != 0;
+        let weekday = self.weekdays & (1 << time.weekday().num_days_from_sunday()) != 0;
+        let day = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        day && self.months & (1 << time.month()) != 0
+    }
+
+    /// First matching minute after `now`, `None` if there is none within the next years
+    /// (e.g. `0 0 31 2 *`)
+    pub fn next_after(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = now.naive_local().with_second(0)?.with_nanosecond(0)? + chrono::Duration::minutes(1);
+        let mut time = start;
+        // Leap days repeat every four years
+        while time < start + chrono::Duration::days(4 * 366) {
+            if !self.matches_day(&time) {
+                time = (time.date() + chrono::Duration::days(1)).and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if self.hours & (1 << time.hour()) == 0 {
+                time = time.with_minute(0)? + chrono::Duration::hours(1);
+                continue;
+            }
+            if self.minutes & (1 << time.minute()) == 0 {
+                time += chrono::Duration::minutes(1);
+                continue;
+            }
+            // Times skipped by a daylight saving change don't exist locally
+            if let Some(time) = Local.from_local_datetime(&time).earliest() {
+                return Some(time);
+            }
+            time += chrono::Duration::minutes(1);
+        }
+        None
+    }
+}
+
+/// What a job does when it fires, every firing also publishes [`BotEvent::JobFired`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobAction {
+    /// Only the event, for scripts and the UI to react to
+    Event,
+    /// Input or route action through the automation engine, e.g. re-buffing
+    #[cfg(feature = "opencv")]
+    Action { action: RuleAction },
+    /// Run a saved script, e.g. logging out
+    Script { script: String },
+    /// Save the latest captured frame to `screenshots/`
+    Screenshot,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    // Plain values have to come before tables in TOML
+    pub name: String,
+    #[serde(default = "Job::default_enabled")]
+    pub enabled: bool,
+    pub timing: Timing,
+    pub action: JobAction,
+}
+
+impl Job {
+    pub fn new(name: impl Into<String>, timing: Timing, action: JobAction) -> Self {
+        Self {
+            name: name.into(),
+            timing,
+            action,
+            enabled: true,
+        }
+    }
+
+    fn default_enabled() -> bool {
+        true
+    }
+}
+
+/// Scheduled jobs, saved with the rest of the [`BotConfig`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    pub jobs: Vec<Job>,
+}
+
+impl SchedulerConfig {
+    /// Check every job's timing, e.g. before saving jobs entered by the user
+    pub fn validate(&self) -> Result<(), String> {
+        let now = Local::now();
+        for job in &self.jobs {
+            job.timing
+                .next_after(now)
+                .map_err(|e| format!("Job {}: {}", job.name, e))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SchedulerEvent {
+    JobFired { job: String },
+    JobFailed { job: String, reason: String },
+}
+
+/// When a job is due next, recomputed if its timing changes
+struct Due {
+    timing: Timing,
+    at: Option<DateTime<Local>>,
+}
+
+/// Fires recurring and one-shot jobs, re-buffing every 30 minutes or logging out at 6am
+///
+/// Actions need the service that carries them out to be attached, jobs whose action can't
+/// be carried out fail without stopping the scheduler.
+#[derive(Clone)]
+pub struct Scheduler {
+    config: Arc<StdMutex<SchedulerConfig>>,
+    #[cfg(feature = "opencv")]
+    automation: Option<AutomationEngine>,
+    frames: Option<FrameAnalyzer>,
+    #[cfg(feature = "scripting")]
+    scripts: Option<ScriptService>,
+    event_sender: broadcast::Sender<SchedulerEvent>,
+    task: ServiceTask,
+}
+
+impl Scheduler {
+    pub fn new(config: SchedulerConfig) -> Self {
+        Self {
+            config: Arc::new(StdMutex::new(config)),
+            #[cfg(feature = "opencv")]
+            automation: None,
+            frames: None,
+            #[cfg(feature = "scripting")]
+            scripts: None,
+            event_sender: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            task: ServiceTask::new(),
+        }
+    }
+
+    /// Carry out [`JobAction::Action`] jobs
+    #[cfg(feature = "opencv")]
+    pub fn with_automation(mut self, automation: AutomationEngine) -> Self {
+        self.automation = Some(automation);
+        self
+    }
+
+    /// Take [`JobAction::Screenshot`]s from the latest analyzed frame
+    pub fn with_frames(mut self, frames: FrameAnalyzer) -> Self {
+        self.frames = Some(frames);
+        self
+    }
+
+    /// Run [`JobAction::Script`] jobs
+    #[cfg(feature = "scripting")]
+    pub fn with_scripts(mut self, scripts: ScriptService) -> Self {
+        self.scripts = Some(scripts);
+        self
+    }
+
+    /// Fired and failed jobs
+    pub fn subscribe(&self) -> broadcast::Receiver<SchedulerEvent> {
+        self.event_sender.subscribe()
+    }
+
+    pub fn config(&self) -> SchedulerConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Replace the jobs, jobs with changed timings are rescheduled
+    pub fn set_config(&self, config: SchedulerConfig) -> Result<(), String> {
+        config.validate()?;
+        *self.config.lock().unwrap() = config;
+        Ok(())
+    }
+
+    /// Use the jobs of every config published on `configs`
+    pub fn follow_config(&self, mut configs: watch::Receiver<BotConfig>) {
+        let scheduler = self.clone();
+        tokio::spawn(async move {
+            while configs.changed().await.is_ok() {
+                let config = configs.borrow_and_update().scheduler.clone();
+                if let Err(e) = scheduler.set_config(config) {
+                    tracing::warn!(error = %e, "Ignoring invalid schedules");
+                }
+            }
+        });
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.task.is_busy().await
+    }
+
+    pub async fn start_scheduler(&self) -> Result<(), String> {
+        let scheduler = self.clone();
+        self.task.start(move |cancelled| async move {
+            let mut ticker = tokio::time::interval(SCHEDULER_TICK);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            let mut due: HashMap<String, Due> = HashMap::new();
+
+            loop {
+                tokio::select! {
+                    _ = cancelled.cancelled() => break,
+                    _ = ticker.tick() => scheduler.tick(&mut due).await,
+                }
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Stop firing jobs, a job being performed finishes before this returns
+    pub async fn stop_scheduler(&self) {
+        self.task.stop().await;
+    }
+
+    async fn tick(&self, due: &mut HashMap<String, Due>) {
+        let config = self.config();
+        let now = Local::now();
+        due.retain(|name, _| config.jobs.iter().any(|job| job.enabled && job.name == *name));
+
+        for job in config.jobs.iter().filter(|job| job.enabled) {
+            if due.get(&job.name).map_or(true, |next| next.timing != job.timing) {
+                let at = job.timing.next_after(now).unwrap_or_else(|e| {
+                    tracing::warn!(job = %job.name, error = %e, "Job won't fire");
+                    None
+                });
+                due.insert(job.name.clone(), Due { timing: job.timing.clone(), at });
+                continue;
+            }
+            let Some(next) = due.get_mut(&job.name) else {
+                continue;
+            };
+            if next.at.map_or(true, |at| at > now) {
+                continue;
+            }
+            // Jobs missed while the bot was busy or asleep fire once, not once per missed time
+            next.at = job.timing.next_after(now).ok().flatten();
+            self.fire(job).await;
+        }
+    }
+
+    async fn fire(&self, job: &Job) {
+        let event = match self.perform(job).await {
+            Ok(()) => {
+                EventBus::global().publish(BotEvent::JobFired { job: job.name.clone() });
+                SchedulerEvent::JobFired { job: job.name.clone() }
+            }
+            Err(reason) => {
+                tracing::warn!(job = %job.name, %reason, "Job failed");
+                SchedulerEvent::JobFailed {
+                    job: job.name.clone(),
+                    reason,
+                }
+            }
+        };
+        let _ = self.event_sender.send(event);
+    }
+
+    /// Do what `job` does now, whether or not it is due
+    pub async fn perform(&self, job: &Job) -> Result<(), String> {
+        match &job.action {
+            JobAction::Event => Ok(()),
+            #[cfg(feature = "opencv")]
+            JobAction::Action { action } => {
+                let automation = self.automation.as_ref().ok_or("No automation engine to perform actions")?;
+                automation.perform(action).await
+            }
+            JobAction::Script { script } => self.run_script(script).await,
+            JobAction::Screenshot => {
+                let frames = self.frames.as_ref().ok_or("No frame analyzer to take screenshots")?;
+                let frame = frames.with_latest(|frame| frame.clone()).ok_or("No frame captured yet")?;
+                let name = job.name.clone();
+                tokio::task::spawn_blocking(move || save_screenshot(&frame, &name).map(|_| ()))
+                    .await
+                    .map_err(|e| format!("Screenshot task failed: {}", e))?
+            }
+        }
+    }
+
+    #[cfg(feature = "scripting")]
+    async fn run_script(&self, script: &str) -> Result<(), String> {
+        let scripts = self.scripts.as_ref().ok_or("No script service to run scripts")?;
+        scripts.run_script(script).await
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    async fn run_script(&self, _script: &str) -> Result<(), String> {
+        Err("Scripting is not enabled in this build".to_string())
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for Scheduler {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_scheduler().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_scheduler().await;
+        Ok(())
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        let mut dependencies = Vec::new();
+        #[cfg(feature = "opencv")]
+        if self.automation.is_some() {
+            dependencies.push("AutomationEngine".to_string());
+        }
+        if self.frames.is_some() {
+            dependencies.push("FrameAnalyzer".to_string());
+        }
+        #[cfg(feature = "scripting")]
+        if self.scripts.is_some() {
+            dependencies.push("ScriptService".to_string());
+        }
+        dependencies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn at(date: &str, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn matches(expression: &str, time: NaiveDateTime) -> bool {
+        CronExpression::parse(expression).unwrap().matches(&time)
+    }
+
+    #[test]
+    fn test_steps() {
+        for minute in [0, 15, 30, 45] {
+            assert!(matches("*/15 * * * *", at("2026-10-15", 6, minute)));
+        }
+        assert!(!matches("*/15 * * * *", at("2026-10-15", 6, 10)));
+        // A step from a start value runs to the end of the field
+        assert!(matches("5/20 * * * *", at("2026-10-15", 6, 45)));
+        assert!(!matches("5/20 * * * *", at("2026-10-15", 6, 0)));
+        assert!(matches("0 8-18/5 * * *", at("2026-10-15", 13, 0)));
+        assert!(!matches("0 8-18/5 * * *", at("2026-10-15", 12, 0)));
+    }
+
+    #[test]
+    fn test_ranges() {
+        assert!(matches("0 9-17 * * *", at("2026-10-15", 9, 0)));
+        assert!(matches("0 9-17 * * *", at("2026-10-15", 17, 0)));
+        assert!(!matches("0 9-17 * * *", at("2026-10-15", 8, 0)));
+        assert!(!matches("0 9-17 * * *", at("2026-10-15", 18, 0)));
+    }
+
+    #[test]
+    fn test_lists() {
+        assert!(matches("0,30 6,18 * * *", at("2026-10-15", 18, 30)));
+        assert!(matches("0,30 6,18 * * *", at("2026-10-15", 6, 0)));
+        assert!(!matches("0,30 6,18 * * *", at("2026-10-15", 12, 30)));
+        assert!(matches("0 0 1,10-12 * *", at("2026-10-11", 0, 0)));
+        assert!(!matches("0 0 1,10-12 * *", at("2026-10-13", 0, 0)));
+    }
+
+    #[test]
+    fn test_day_or_weekday() {
+        // The 1st and every Monday
+        assert!(matches("0 0 1 * 1", at("2026-10-01", 0, 0)));
+        assert!(matches("0 0 1 * 1", at("2026-10-19", 0, 0)));
+        assert!(!matches("0 0 1 * 1", at("2026-10-02", 0, 0)));
+        // A stepped `*` isn't a restriction, only Mondays on odd days
+        assert!(matches("0 0 */2 * 1", at("2026-10-19", 0, 0)));
+        assert!(!matches("0 0 */2 * 1", at("2026-10-15", 0, 0)));
+        assert!(!matches("0 0 */2 * 1", at("2026-10-26", 0, 0)));
+        assert!(matches("0 0 1 * */2", at("2026-10-01", 0, 0)));
+        assert!(!matches("0 0 1 * */2", at("2026-10-17", 0, 0)));
+    }
+
+    #[test]
+    fn test_sunday_as_seven() {
+        assert!(matches("0 0 * * 7", at("2026-10-18", 0, 0)));
+        assert!(matches("0 0 * * 0", at("2026-10-18", 0, 0)));
+        assert!(!matches("0 0 * * 7", at("2026-10-17", 0, 0)));
+        assert!(matches("0 0 * * 5-7", at("2026-10-16", 0, 0)));
+        assert!(matches("0 0 * * 5-7", at("2026-10-18", 0, 0)));
+        assert!(!matches("0 0 * * 5-7", at("2026-10-19", 0, 0)));
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        for expression in ["* * * *", "60 * * * *", "*/0 * * * *", "0 0 0 * *", "0 0 * 13 *", "0 0 * * 8", "5-1 * * * *"] {
+            assert!(CronExpression::parse(expression).is_err(), "{}", expression);
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use platforms::input::{KeyKind, MouseButton};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Position};
+use tokio::runtime::Handle;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use crate::services::{Service, ServiceError};
+use super::automation::{AutomationEngine, RuleAction};
+use super::event_bus::{BotEvent, EventBus};
+use super::frame_analyzer::FrameAnalyzer;
+use super::input_scheduler::{InputAction, InputPriority, InputScheduler};
+use super::probes::ProbeService;
+
+/// Longest a sleeping script waits before checking whether it was stopped
+const SLEEP_STEP: Duration = Duration::from_millis(20);
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Source of a user script, saved as `.rhai`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script {
+    pub name: String,
+    pub source: String,
+}
+
+impl Script {
+    /// Directory scripts are loaded from
+    pub fn dir() -> PathBuf {
+        crate::config_dir().join("scripts")
+    }
+
+    pub fn path(name: &str) -> PathBuf {
+        Self::dir().join(format!("{}.rhai", name))
+    }
+
+    /// Names of all saved scripts
+    pub fn list() -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(Self::dir()) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "rhai"))
+            .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string))
+            .collect()
+    }
+
+    pub fn load(name: &str) -> Result<Self, String> {
+        Self::load_file(Self::path(name))
+    }
+
+    pub fn load_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let source =
+            std::fs::read_to_string(path).map_err(|e| format!("Failed to read script {}: {}", path.display(), e))?;
+        let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default().to_string();
+        Ok(Self { name, source })
+    }
+
+    /// Save to [`Script::dir`] under the script's name
+    pub fn save(&self) -> Result<(), String> {
+        if self.name.is_empty() || self.name.contains(['/', '\\', '.']) {
+            return Err(format!("Invalid script name {:?}", self.name));
+        }
+        let dir = Self::dir();
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        let path = Self::path(&self.name);
+        std::fs::write(&path, &self.source).map_err(|e| format!("Failed to write script {}: {}", path.display(), e))
+    }
+}
+
+/// Services scripts can reach, everything else stays out of their hands
+#[derive(Clone)]
+pub struct ScriptHost {
+    frames: FrameAnalyzer,
+    automation: AutomationEngine,
+    scheduler: Arc<InputScheduler>,
+    probes: Option<ProbeService>,
+}
+
+impl ScriptHost {
+    pub fn new(frames: FrameAnalyzer, automation: AutomationEngine, scheduler: Arc<InputScheduler>) -> Self {
+        Self {
+            frames,
+            automation,
+            scheduler,
+            probes: None,
+        }
+    }
+
+    /// Let scripts read pixel probes with `probe(name)`
+    pub fn with_probes(mut self, probes: ProbeService) -> Self {
+        self.probes = Some(probes);
+        self
+    }
+
+    /// Engine with the script API registered, runs until `cancel` is set
+    ///
+    /// Calls that wait for input or events block on `runtime`, so the script has to run on a
+    /// blocking thread.
+    fn engine(&self, name: &str, cancel: Arc<AtomicBool>, runtime: Handle) -> Engine {
+        let mut engine = Engine::new();
+
+        let stopped = cancel.clone();
+        engine.on_progress(move |_| stopped.load(Ordering::Relaxed).then_some(Dynamic::UNIT));
+        let script = name.to_string();
+        engine.on_print(move |text| {
+            tracing::info!(target: "script", script = %script, "{}", text);
+            EventBus::global().publish(BotEvent::ScriptOutput {
+                script: script.clone(),
+                text: text.to_string(),
+            });
+        });
+        let script = name.to_string();
+        engine.on_debug(move |text, _, position| {
+            tracing::debug!(target: "script", script = %script, %position, "{}", text)
+        });
+
+        // Timers
+        let started = Instant::now();
+        engine.register_fn("now_ms", move || started.elapsed().as_millis() as i64);
+        let stopped = cancel.clone();
+        engine.register_fn("sleep", move |ms: i64| -> ScriptResult<()> {
+            let deadline = Instant::now() + Duration::from_millis(ms.max(0) as u64);
+            loop {
+                if stopped.load(Ordering::Relaxed) {
+                    return Err(EvalAltResult::ErrorTerminated(Dynamic::UNIT, Position::NONE).into());
+                }
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Ok(());
+                }
+                std::thread::sleep(remaining.min(SLEEP_STEP));
+            }
+        });
+
+        // Frames
+        let frames = self.frames.clone();
+        engine.register_fn("frame_size", move || -> Dynamic {
+            frames.frame_size().map_or(Dynamic::UNIT, |(width, height)| {
+                Dynamic::from_array(vec![Dynamic::from(width as i64), Dynamic::from(height as i64)])
+            })
+        });
+        let frames = self.frames.clone();
+        engine.register_fn("pixel", move |x: i64, y: i64| -> Dynamic {
+            frames.pixel(x as i32, y as i32).map_or(Dynamic::UNIT, rgb_to_dynamic)
+        });
+        let frames = self.frames.clone();
+        engine.register_fn("pixel_matches", move |x: i64, y: i64, rgb: Array, tolerance: i64| -> ScriptResult<bool> {
+            let expected = rgb_from_array(&rgb)?;
+            Ok(frames.pixel_matches(x as i32, y as i32, expected, tolerance.clamp(0, 255) as u8))
+        });
+
+        // Detections
+        let automation = self.automation.clone();
+        engine.register_fn("player", move || -> ScriptResult<Dynamic> {
+            rhai::serde::to_dynamic(automation.state().player)
+        });
+        let automation = self.automation.clone();
+        engine.register_fn("blips", move || -> ScriptResult<Dynamic> {
+            rhai::serde::to_dynamic(automation.state().blips)
+        });
+        let automation = self.automation.clone();
+        engine.register_fn("bar", move |name: &str| -> Dynamic {
+            automation.state().bars.get(name).map_or(Dynamic::UNIT, |fill| Dynamic::from(*fill as f64))
+        });
+        let probes = self.probes.clone();
+        engine.register_fn("probe", move |name: &str| -> Dynamic {
+            probes
+                .as_ref()
+                .and_then(|probes| probes.is_active(name))
+                .map_or(Dynamic::UNIT, Dynamic::from)
+        });
+
+        // Input and routes
+        let (automation, handle) = (self.automation.clone(), runtime.clone());
+        engine.register_fn("key_tap", move |key: &str| -> ScriptResult<()> {
+            let action = RuleAction::KeyTap { key: parse_key(key)? };
+            handle.block_on(automation.perform(&action)).map_err(Into::into)
+        });
+        let (automation, handle) = (self.automation.clone(), runtime.clone());
+        engine.register_fn("key_hold", move |key: &str, ms: i64| -> ScriptResult<()> {
+            let action = RuleAction::KeyHold {
+                key: parse_key(key)?,
+                duration: Duration::from_millis(ms.max(0) as u64),
+            };
+            handle.block_on(automation.perform(&action)).map_err(Into::into)
+        });
+        let (automation, handle) = (self.automation.clone(), runtime.clone());
+        engine.register_fn("key_combo", move |keys: Array| -> ScriptResult<()> {
+            let keys = keys
+                .iter()
+                .map(|key| parse_key(&key.to_string()))
+                .collect::<ScriptResult<Vec<_>>>()?;
+            handle.block_on(automation.perform(&RuleAction::KeyCombo { keys })).map_err(Into::into)
+        });
+        let (scheduler, handle) = (self.scheduler.clone(), runtime.clone());
+        engine.register_fn("move_mouse", move |x: i64, y: i64| -> ScriptResult<()> {
+            let action = InputAction::MouseMove { x: x as i32, y: y as i32 };
+            handle.block_on(scheduler.schedule(action, InputPriority::Normal)).map(|_| ()).map_err(Into::into)
+        });
+        let (scheduler, handle) = (self.scheduler.clone(), runtime.clone());
+        engine.register_fn("click", move |x: i64, y: i64, button: &str| -> ScriptResult<()> {
+            let button: MouseButton = parse_name(button, "mouse button")?;
+            let action = InputAction::MouseClick { x: x as i32, y: y as i32, button };
+            handle.block_on(scheduler.schedule(action, InputPriority::Normal)).map(|_| ()).map_err(Into::into)
+        });
+        let (automation, handle) = (self.automation.clone(), runtime.clone());
+        engine.register_fn("play_route", move |route: &str, reverse: bool| -> ScriptResult<()> {
+            let action = RuleAction::PlayRoute {
+                route: route.to_string(),
+                reverse,
+            };
+            handle.block_on(automation.perform(&action)).map_err(Into::into)
+        });
+        let (automation, handle) = (self.automation.clone(), runtime.clone());
+        engine.register_fn("stop_route", move || -> ScriptResult<()> {
+            handle.block_on(automation.perform(&RuleAction::StopRoute)).map_err(Into::into)
+        });
+
+        // Events, only those published after the script started
+        let events = Arc::new(StdMutex::new(EventBus::global().subscribe()));
+        let (stopped, handle) = (cancel, runtime);
+        engine.register_fn("next_event", move |timeout_ms: i64| -> ScriptResult<Dynamic> {
+            let deadline = Instant::now() + Duration::from_millis(timeout_ms.max(0) as u64);
+            let mut events = events.lock().unwrap();
+            loop {
+                if stopped.load(Ordering::Relaxed) {
+                    return Err(EvalAltResult::ErrorTerminated(Dynamic::UNIT, Position::NONE).into());
+                }
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Ok(Dynamic::UNIT);
+                }
+                match handle.block_on(tokio::time::timeout(remaining.min(SLEEP_STEP), events.recv())) {
+                    Ok(Ok(event)) => return rhai::serde::to_dynamic(event),
+                    Ok(Err(broadcast::error::RecvError::Lagged(_))) | Err(_) => continue,
+                    Ok(Err(broadcast::error::RecvError::Closed)) => return Ok(Dynamic::UNIT),
+                }
+            }
+        });
+
+        engine
+    }
+}
+
+/// Key or button name as in the config file, e.g. `"W"` or `"Space"`
+fn parse_name<T: serde::de::DeserializeOwned>(name: &str, kind: &str) -> ScriptResult<T> {
+    serde_json::from_value(serde_json::Value::String(name.to_string()))
+        .map_err(|_| format!("Unknown {} {:?}", kind, name).into())
+}
+
+fn parse_key(name: &str) -> ScriptResult<KeyKind> {
+    parse_name(name, "key")
+}
+
+fn rgb_to_dynamic(rgb: [u8; 3]) -> Dynamic {
+    Dynamic::from_array(rgb.iter().map(|channel| Dynamic::from(*channel as i64)).collect())
+}
+
+fn rgb_from_array(rgb: &Array) -> ScriptResult<[u8; 3]> {
+    let channel = |i: usize| -> ScriptResult<u8> {
+        rgb.get(i)
+            .and_then(|value| value.as_int().ok())
+            .map(|value| value.clamp(0, 255) as u8)
+            .ok_or_else(|| "Colors are arrays of three integers [r, g, b]".into())
+    };
+    Ok([channel(0)?, channel(1)?, channel(2)?])
+}
+
+struct RunningScript {
+    cancel: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+/// Loads, runs, reloads and stops user scripts
+///
+/// Every script runs on its own blocking thread with the API of its [`ScriptHost`]. What it
+/// prints, its errors and its end are published on the event bus as [`BotEvent::ScriptOutput`],
+/// [`BotEvent::ScriptError`] and [`BotEvent::ScriptFinished`].
+#[derive(Clone)]
+pub struct ScriptService {
+    host: ScriptHost,
+    running: Arc<StdMutex<HashMap<String, RunningScript>>>,
+}
+
+impl ScriptService {
+    pub fn new(host: ScriptHost) -> Self {
+        Self {
+            host,
+            running: Arc::new(StdMutex::new(HashMap::new())),
+        }
+    }
+
+    /// Names of the scripts currently running
+    pub fn running(&self) -> Vec<String> {
+        self.running.lock().unwrap().keys().cloned().collect()
+    }
+
+    pub fn is_script_running(&self, name: &str) -> bool {
+        self.running.lock().unwrap().contains_key(name)
+    }
+
+    /// Compile a script without running it, to report syntax errors early
+    pub fn check(&self, script: &Script) -> Result<(), String> {
+        Engine::new()
+            .compile(&script.source)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to compile script {}: {}", script.name, e))
+    }
+
+    /// Load a saved script and run it
+    pub async fn run_script(&self, name: &str) -> Result<(), String> {
+        self.run(Script::load(name)?).await
+    }
+
+    /// Stop a script if it runs and start it again from its saved source
+    pub async fn reload_script(&self, name: &str) -> Result<(), String> {
+        let script = Script::load(name)?;
+        self.check(&script)?;
+        self.stop_script(name).await;
+        self.run(script).await
+    }
+
+    /// Run a script, a script of the same name must not be running
+    pub async fn run(&self, script: Script) -> Result<(), String> {
+        if self.is_script_running(&script.name) {
+            return Err(format!("Script {} is already running", script.name));
+        }
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let engine = self.host.engine(&script.name, cancel.clone(), Handle::current());
+        let ast = engine
+            .compile(&script.source)
+            .map_err(|e| format!("Failed to compile script {}: {}", script.name, e))?;
+
+        let name = script.name.clone();
+        let running = self.running.clone();
+        let stopped = cancel.clone();
+        // Held until the script is listed, so a script ending right away still unlists itself
+        let mut scripts = self.running.lock().unwrap();
+        let handle = tokio::task::spawn_blocking(move || {
+            match engine.run_ast(&ast) {
+                Ok(()) => {}
+                Err(e) if matches!(*e, EvalAltResult::ErrorTerminated(..)) => {}
+                Err(e) => {
+                    tracing::warn!(script = %name, error = %e, "Script failed");
+                    EventBus::global().publish(BotEvent::ScriptError {
+                        script: name.clone(),
+                        error: e.to_string(),
+                    });
+                }
+            }
+            // A reload may have put a new run under the same name already
+            let mut running = running.lock().unwrap();
+            if running.get(&name).is_some_and(|script| Arc::ptr_eq(&script.cancel, &stopped)) {
+                running.remove(&name);
+            }
+            drop(running);
+            EventBus::global().publish(BotEvent::ScriptFinished { script: name });
+        });
+
+        scripts.insert(script.name, RunningScript { cancel, handle });
+        Ok(())
+    }
+
+    /// Stop a script and wait for it to end
+    pub async fn stop_script(&self, name: &str) {
+        let Some(script) = self.running.lock().unwrap().remove(name) else {
+            return;
+        };
+        script.cancel.store(true, Ordering::Relaxed);
+        let _ = script.handle.await;
+    }
+
+    pub async fn stop_all_scripts(&self) {
+        for name in self.running() {
+            self.stop_script(&name).await;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for ScriptService {
+    // Scripts are started one by one with `run_script`
+    async fn start(&self) -> Result<(), ServiceError> {
+        Ok(())
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_all_scripts().await;
+        Ok(())
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        vec!["FrameAnalyzer".to_string(), "AutomationEngine".to_string(), "InputScheduler".to_string()]
+    }
+}
@@ -0,0 +1,473 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, Mutex as StdMutex};
+
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use super::event_bus::{BotEvent, EventBus};
+use super::Service;
+
+/// Name failures of the manager itself, e.g. a dependency cycle, are published under
+const MANAGER_NAME: &str = "Service manager";
+
+/// Why a service failed to start or stop
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "reason", rename_all = "snake_case")]
+pub enum ServiceError {
+    /// Something the service needs wasn't set up, e.g. no window selected or no webhook
+    NotConfigured(String),
+    Failed(String),
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceError::NotConfigured(reason) => write!(f, "not configured: {}", reason),
+            ServiceError::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ServiceError {}
+
+/// The `String` errors used throughout the services are plain failures
+impl From<String> for ServiceError {
+    fn from(error: String) -> Self {
+        ServiceError::Failed(error)
+    }
+}
+
+/// Where a service is in its lifecycle, as reported by [`Service::status`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceStatus {
+    Stopped,
+    Starting,
+    Running,
+    Stopping,
+    /// Not tracked by the service, the manager goes by whether it started it
+    Unknown,
+}
+
+impl fmt::Display for ServiceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            ServiceStatus::Stopped => "stopped",
+            ServiceStatus::Starting => "starting",
+            ServiceStatus::Running => "running",
+            ServiceStatus::Stopping => "stopping",
+            ServiceStatus::Unknown => "unknown",
+        };
+        f.write_str(status)
+    }
+}
+
+/// How well a service is doing, as reported by [`Service::health_check`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", content = "reason", rename_all = "snake_case")]
+pub enum ServiceHealth {
+    Healthy,
+    /// Working, but not as it should, e.g. falling behind
+    Degraded(String),
+    Unhealthy(String),
+}
+
+impl fmt::Display for ServiceHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceHealth::Healthy => write!(f, "healthy"),
+            ServiceHealth::Degraded(reason) => write!(f, "degraded ({})", reason),
+            ServiceHealth::Unhealthy(reason) => write!(f, "unhealthy ({})", reason),
+        }
+    }
+}
+
+/// Status of a registered service
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ManagedServiceStatus {
+    pub name: String,
+    pub dependencies: Vec<String>,
+    /// Started through the manager and not stopped since
+    pub running: bool,
+    /// As reported by the service, never [`ServiceStatus::Unknown`]
+    pub status: ServiceStatus,
+    /// `None` while the service isn't running
+    pub health: Option<ServiceHealth>,
+}
+
+/// Status of all registered services, in start order
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ManagerStatus {
+    pub services: Vec<ManagedServiceStatus>,
+}
+
+impl ManagerStatus {
+    /// Every service is running and healthy
+    pub fn all_healthy(&self) -> bool {
+        self.services
+            .iter()
+            .all(|service| service.running && service.health == Some(ServiceHealth::Healthy))
+    }
+}
+
+impl fmt::Display for ManagerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, service) in self.services.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            match &service.health {
+                Some(health) => write!(f, "{}: {}, {}", service.name, service.status, health)?,
+                None => write!(f, "{}: {}", service.name, service.status)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct ManagedService {
+    name: String,
+    service: Arc<dyn Service>,
+    dependencies: Vec<String>,
+    running: bool,
+}
+
+/// Starts registered services after their dependencies and stops them before
+///
+/// Services are identified by [`Service::name`], dependencies refer to those names. Start and
+/// stop calls are serialized, so a stop can't interleave with a half finished start.
+#[derive(Default)]
+pub struct ServiceManager {
+    services: StdMutex<Vec<ManagedService>>,
+    // Held while services are started or stopped
+    lifecycle: Mutex<()>,
+}
+
+impl ServiceManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a service under its own name with its own dependencies
+    pub fn register(&self, service: Arc<dyn Service>) -> Result<(), String> {
+        let name = service.name().to_string();
+        self.register_with(name, service, &[])
+    }
+
+    /// Register a service under `name`, depending on `dependencies` in addition to its own
+    ///
+    /// Needed when several services of the same type are registered, or a dependency is only
+    /// known when wiring services together.
+    pub fn register_with(
+        &self,
+        name: impl Into<String>,
+        service: Arc<dyn Service>,
+        dependencies: &[&str],
+    ) -> Result<(), String> {
+        let name = name.into();
+        let mut services = self.services.lock().unwrap();
+        if services.iter().any(|managed| managed.name == name) {
+            return Err(format!("Service {} is already registered", name));
+        }
+        let mut all_dependencies = service.dependencies();
+        for dependency in dependencies {
+            if !all_dependencies.iter().any(|known| known == dependency) {
+                all_dependencies.push(dependency.to_string());
+            }
+        }
+        services.push(ManagedService {
+            name,
+            service,
+            dependencies: all_dependencies,
+            running: false,
+        });
+        Ok(())
+    }
+
+    pub fn is_registered(&self, name: &str) -> bool {
+        self.services.lock().unwrap().iter().any(|managed| managed.name == name)
+    }
+
+    /// Whether the service was started through the manager, `None` if it isn't registered
+    pub fn is_running(&self, name: &str) -> Option<bool> {
+        let services = self.services.lock().unwrap();
+        services.iter().find(|managed| managed.name == name).map(|managed| managed.running)
+    }
+
+    /// Names of the registered services in the order they are started
+    pub fn start_order(&self) -> Result<Vec<String>, String> {
+        let services = self.services.lock().unwrap();
+        Self::order(&services).map(|order| order.into_iter().map(|i| services[i].name.clone()).collect())
+    }
+
+    /// Start every service that isn't running, dependencies first
+    ///
+    /// If one fails to start, the services started by this call are stopped again.
+    pub async fn start_all(&self) -> Result<(), String> {
+        let _lifecycle = self.lifecycle.lock().await;
+        let services = self.snapshot();
+        let order = Self::order(&services).map_err(|e| Self::publish_failure(MANAGER_NAME, e))?;
+        let mut started = Vec::new();
+        for i in order {
+            if services[i].running {
+                continue;
+            }
+            if let Err(e) = services[i].service.start().await {
+                for &j in started.iter().rev() {
+                    self.stop_one(&services[j]).await;
+                }
+                return Err(Self::start_failed(&services[i].name, e));
+            }
+            self.set_running(&services[i].name, true);
+            started.push(i);
+        }
+        Ok(())
+    }
+
+    /// Stop every running service, dependents first
+    pub async fn stop_all(&self) -> Result<(), String> {
+        let _lifecycle = self.lifecycle.lock().await;
+        let services = self.snapshot();
+        // A cycle only prevents a dependency order, stop in reverse registration order then
+        let order = Self::order(&services).unwrap_or_else(|_| (0..services.len()).collect());
+        let mut failed = Vec::new();
+        for i in order.into_iter().rev() {
+            if !self.stop_one(&services[i]).await {
+                failed.push(services[i].name.clone());
+            }
+        }
+        Self::stop_result(failed)
+    }
+
+    /// Start a service and whatever it depends on
+    pub async fn start(&self, name: &str) -> Result<(), String> {
+        let _lifecycle = self.lifecycle.lock().await;
+        let services = self.snapshot();
+        let order = Self::order(&services).map_err(|e| Self::publish_failure(MANAGER_NAME, e))?;
+        let needed = Self::closure(&services, name, |service, other| service.dependencies.contains(&other.name))?;
+        for i in order.into_iter().filter(|i| needed.contains(i)) {
+            if services[i].running {
+                continue;
+            }
+            if let Err(e) = services[i].service.start().await {
+                return Err(Self::start_failed(&services[i].name, e));
+            }
+            self.set_running(&services[i].name, true);
+        }
+        Ok(())
+    }
+
+    /// Stop a service and everything depending on it
+    pub async fn stop(&self, name: &str) -> Result<(), String> {
+        let _lifecycle = self.lifecycle.lock().await;
+        let services = self.snapshot();
+        let order = Self::order(&services).unwrap_or_else(|_| (0..services.len()).collect());
+        let dependents = Self::closure(&services, name, |service, other| other.dependencies.contains(&service.name))?;
+        let mut failed = Vec::new();
+        for i in order.into_iter().rev().filter(|i| dependents.contains(i)) {
+            if !self.stop_one(&services[i]).await {
+                failed.push(services[i].name.clone());
+            }
+        }
+        Self::stop_result(failed)
+    }
+
+    /// Running state and health of every service, in start order if there is one
+    pub async fn status(&self) -> ManagerStatus {
+        let services = self.snapshot();
+        let order = Self::order(&services).unwrap_or_else(|_| (0..services.len()).collect());
+        let mut statuses = Vec::with_capacity(order.len());
+        for i in order {
+            let managed = &services[i];
+            let status = match managed.service.status().await {
+                ServiceStatus::Unknown if managed.running => ServiceStatus::Running,
+                ServiceStatus::Unknown => ServiceStatus::Stopped,
+                status => status,
+            };
+            let health = if managed.running {
+                Some(managed.service.health_check().await)
+            } else {
+                None
+            };
+            statuses.push(ManagedServiceStatus {
+                name: managed.name.clone(),
+                dependencies: managed.dependencies.clone(),
+                running: managed.running,
+                status,
+                health,
+            });
+        }
+        ManagerStatus { services: statuses }
+    }
+
+    /// Copy of the registry, services are awaited without holding its lock
+    fn snapshot(&self) -> Vec<ManagedService> {
+        self.services.lock().unwrap().clone()
+    }
+
+    fn set_running(&self, name: &str, running: bool) {
+        let mut services = self.services.lock().unwrap();
+        if let Some(managed) = services.iter_mut().find(|managed| managed.name == name) {
+            managed.running = running;
+        }
+    }
+
+    /// Error for a service that failed to start, also published on the event bus
+    fn start_failed(name: &str, error: ServiceError) -> String {
+        Self::publish_failure(name, format!("Failed to start {}: {}", name, error))
+    }
+
+    /// Publish `error` of the service `name` on the event bus, for the UI to show, and return it
+    fn publish_failure(name: &str, error: String) -> String {
+        EventBus::global().publish(BotEvent::ServiceFailed {
+            service: name.to_string(),
+            error: error.clone(),
+        });
+        error
+    }
+
+    fn stop_result(failed: Vec<String>) -> Result<(), String> {
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Failed to stop {}", failed.join(", ")))
+        }
+    }
+
+    /// Returns whether the service stopped cleanly, it counts as stopped either way
+    async fn stop_one(&self, managed: &ManagedService) -> bool {
+        if self.is_running(&managed.name) != Some(true) {
+            return true;
+        }
+        self.set_running(&managed.name, false);
+        match managed.service.stop().await {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!(service = %managed.name, error = %e, "Service failed to stop");
+                Self::publish_failure(&managed.name, format!("Failed to stop {}: {}", managed.name, e));
+                false
+            }
+        }
+    }
+
+    /// Indices of `services` with every service after its dependencies, registration order
+    /// is kept where dependencies allow it
+    fn order(services: &[ManagedService]) -> Result<Vec<usize>, String> {
+        for managed in services {
+            for dependency in &managed.dependencies {
+                if !services.iter().any(|other| &other.name == dependency) {
+                    return Err(format!("Service {} depends on unknown service {}", managed.name, dependency));
+                }
+            }
+        }
+
+        let mut order = Vec::with_capacity(services.len());
+        let mut placed = HashSet::new();
+        while order.len() < services.len() {
+            let next = (0..services.len()).find(|&i| {
+                !placed.contains(&i)
+                    && services[i]
+                        .dependencies
+                        .iter()
+                        .all(|dependency| placed.iter().any(|&j: &usize| &services[j].name == dependency))
+            });
+            let Some(next) = next else {
+                let stuck: Vec<&str> = (0..services.len())
+                    .filter(|i| !placed.contains(i))
+                    .map(|i| services[i].name.as_str())
+                    .collect();
+                return Err(format!("Dependency cycle between {}", stuck.join(", ")));
+            };
+            placed.insert(next);
+            order.push(next);
+        }
+        Ok(order)
+    }
+
+    /// Index of `name` and of every service reachable from it through `linked`
+    fn closure(
+        services: &[ManagedService],
+        name: &str,
+        linked: impl Fn(&ManagedService, &ManagedService) -> bool,
+    ) -> Result<HashSet<usize>, String> {
+        let start = services
+            .iter()
+            .position(|managed| managed.name == name)
+            .ok_or_else(|| format!("Service {} is not registered", name))?;
+        let mut found = HashSet::from([start]);
+        let mut pending = vec![start];
+        while let Some(i) = pending.pop() {
+            for j in 0..services.len() {
+                if !found.contains(&j) && linked(&services[i], &services[j]) {
+                    found.insert(j);
+                    pending.push(j);
+                }
+            }
+        }
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Noop;
+
+    #[async_trait::async_trait]
+    impl Service for Noop {
+        async fn start(&self) -> Result<(), ServiceError> {
+            Ok(())
+        }
+
+        async fn stop(&self) -> Result<(), ServiceError> {
+            Ok(())
+        }
+    }
+
+    fn managed(name: &str, dependencies: &[&str]) -> ManagedService {
+        ManagedService {
+            name: name.to_string(),
+            service: Arc::new(Noop),
+            dependencies: dependencies.iter().map(|dependency| dependency.to_string()).collect(),
+            running: false,
+        }
+    }
+
+    fn order(services: &[ManagedService]) -> Result<Vec<&str>, String> {
+        let order = ServiceManager::order(services)?;
+        Ok(order.into_iter().map(|i| services[i].name.as_str()).collect())
+    }
+
+    #[test]
+    fn test_order_starts_dependencies_first() {
+        let services = [
+            managed("ChatMonitor", &["OcrService"]),
+            managed("SessionHistory", &["SessionStats", "OcrService"]),
+            managed("SessionStats", &["OcrService"]),
+            managed("OcrService", &[]),
+        ];
+        assert_eq!(order(&services).unwrap(), ["OcrService", "ChatMonitor", "SessionStats", "SessionHistory"]);
+    }
+
+    #[test]
+    fn test_order_keeps_registration_order() {
+        let services = [managed("b", &[]), managed("a", &[]), managed("c", &[])];
+        assert_eq!(order(&services).unwrap(), ["b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_order_detects_cycles() {
+        let services = [managed("a", &["b"]), managed("b", &["a"]), managed("c", &[])];
+        assert_eq!(order(&services).unwrap_err(), "Dependency cycle between a, b");
+        assert_eq!(order(&[managed("a", &["a"])]).unwrap_err(), "Dependency cycle between a");
+    }
+
+    #[test]
+    fn test_order_rejects_unknown_dependencies() {
+        let services = [managed("a", &["missing"])];
+        assert_eq!(order(&services).unwrap_err(), "Service a depends on unknown service missing");
+    }
+}
@@ -0,0 +1,404 @@
+//! Stats of every bot session kept in an SQLite database, for charts across sessions
+//!
+//! A session lasts from starting [`SessionHistory`] to stopping it. Its row is written on
+//! start and updated every [`FLUSH_INTERVAL`], a crash loses at most the last interval.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use tokio::sync::{broadcast, Mutex};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::services::{Service, ServiceError};
+use super::event_bus::{BotEvent, EventBus};
+use super::minimap_v2::{MinimapService, PerformanceStats};
+use super::session_stats::{SessionStats, StatDelta};
+
+/// Time between writes of the running session
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS sessions (
+        id INTEGER PRIMARY KEY,
+        started_at INTEGER NOT NULL,
+        runtime_ms INTEGER NOT NULL DEFAULT 0,
+        frames INTEGER NOT NULL DEFAULT 0,
+        detections INTEGER NOT NULL DEFAULT 0,
+        actions INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE TABLE IF NOT EXISTS session_counters (
+        session_id INTEGER NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        total REAL NOT NULL,
+        PRIMARY KEY (session_id, name)
+    );
+";
+
+/// Stats of one past or running session
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionSummary {
+    pub id: i64,
+    /// Unix time in seconds
+    pub started_at: i64,
+    #[serde(with = "super::duration_millis")]
+    pub runtime: Duration,
+    pub frames: u64,
+    /// Processed frames the minimap was found on
+    pub detections: u64,
+    /// Input actions sent to the game
+    pub actions: u64,
+    /// Totals of the [`SessionStats`] counters (loot, XP, ...) by name
+    pub counters: BTreeMap<String, f64>,
+}
+
+impl SessionSummary {
+    pub fn started(&self) -> DateTime<Local> {
+        DateTime::from_timestamp(self.started_at, 0).unwrap_or_default().with_timezone(&Local)
+    }
+
+    /// Total of `counter` per hour of runtime, `None` if the session didn't count it
+    pub fn per_hour(&self, counter: &str) -> Option<f64> {
+        self.counters.get(counter).map(|total| per_hour(*total, self.runtime))
+    }
+}
+
+/// One session's total of a counter, a point of a chart across sessions
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CounterPoint {
+    pub session_id: i64,
+    /// Unix time in seconds
+    pub started_at: i64,
+    pub total: f64,
+    pub per_hour: f64,
+}
+
+fn per_hour(total: f64, runtime: Duration) -> f64 {
+    if runtime.is_zero() {
+        0.0
+    } else {
+        total * 3600.0 / runtime.as_secs_f64()
+    }
+}
+
+fn query_failed(e: rusqlite::Error) -> String {
+    format!("Failed to query session history: {}", e)
+}
+
+/// SQLite database of the stats of past sessions
+pub struct SessionStore {
+    connection: StdMutex<Connection>,
+}
+
+impl SessionStore {
+    pub fn path() -> PathBuf {
+        crate::config_dir().join("sessions.db")
+    }
+
+    /// Open the database at `path`, creating it if there is none
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let connection = Connection::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        connection
+            .execute_batch("PRAGMA foreign_keys = ON;")
+            .and_then(|_| connection.execute_batch(SCHEMA))
+            .map_err(|e| format!("Failed to create session history tables: {}", e))?;
+        Ok(Self {
+            connection: StdMutex::new(connection),
+        })
+    }
+
+    /// Add a session started at `started_at` (unix seconds) and return its id
+    fn begin(&self, started_at: i64) -> Result<i64, String> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute("INSERT INTO sessions (started_at) VALUES (?1)", params![started_at])
+            .map_err(|e| format!("Failed to add session: {}", e))?;
+        Ok(connection.last_insert_rowid())
+    }
+
+    fn update(&self, session: &SessionSummary) -> Result<(), String> {
+        let failed = |e: rusqlite::Error| format!("Failed to save session {}: {}", session.id, e);
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction().map_err(failed)?;
+        transaction
+            .execute(
+                "UPDATE sessions SET runtime_ms = ?2, frames = ?3, detections = ?4, actions = ?5 WHERE id = ?1",
+                params![
+                    session.id,
+                    session.runtime.as_millis() as i64,
+                    session.frames as i64,
+                    session.detections as i64,
+                    session.actions as i64,
+                ],
+            )
+            .map_err(failed)?;
+        for (name, total) in &session.counters {
+            transaction
+                .execute(
+                    "INSERT INTO session_counters (session_id, name, total) VALUES (?1, ?2, ?3)
+                     ON CONFLICT (session_id, name) DO UPDATE SET total = excluded.total",
+                    params![session.id, name, total],
+                )
+                .map_err(failed)?;
+        }
+        transaction.commit().map_err(failed)
+    }
+
+    /// The latest `limit` sessions, newest first
+    pub fn sessions(&self, limit: usize) -> Result<Vec<SessionSummary>, String> {
+        let connection = self.connection.lock().unwrap();
+        let mut sessions = connection
+            .prepare(
+                "SELECT id, started_at, runtime_ms, frames, detections, actions FROM sessions
+                 ORDER BY started_at DESC, id DESC LIMIT ?1",
+            )
+            .and_then(|mut statement| {
+                let rows = statement.query_map(params![limit as i64], |row| {
+                    Ok(SessionSummary {
+                        id: row.get(0)?,
+                        started_at: row.get(1)?,
+                        runtime: Duration::from_millis(row.get::<_, i64>(2)? as u64),
+                        frames: row.get::<_, i64>(3)? as u64,
+                        detections: row.get::<_, i64>(4)? as u64,
+                        actions: row.get::<_, i64>(5)? as u64,
+                        counters: BTreeMap::new(),
+                    })
+                })?;
+                rows.collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(query_failed)?;
+
+        let mut counters = connection
+            .prepare("SELECT name, total FROM session_counters WHERE session_id = ?1")
+            .map_err(query_failed)?;
+        for session in &mut sessions {
+            session.counters = counters
+                .query_map(params![session.id], |row| Ok((row.get(0)?, row.get(1)?)))
+                .and_then(|rows| rows.collect())
+                .map_err(query_failed)?;
+        }
+        Ok(sessions)
+    }
+
+    /// `counter` in the latest `limit` sessions that counted it, oldest first
+    pub fn counter_history(&self, counter: &str, limit: usize) -> Result<Vec<CounterPoint>, String> {
+        let connection = self.connection.lock().unwrap();
+        let mut points = connection
+            .prepare(
+                "SELECT s.id, s.started_at, s.runtime_ms, c.total FROM session_counters c
+                 JOIN sessions s ON s.id = c.session_id
+                 WHERE c.name = ?1 ORDER BY s.started_at DESC, s.id DESC LIMIT ?2",
+            )
+            .and_then(|mut statement| {
+                let rows = statement.query_map(params![counter, limit as i64], |row| {
+                    let runtime = Duration::from_millis(row.get::<_, i64>(2)? as u64);
+                    let total: f64 = row.get(3)?;
+                    Ok(CounterPoint {
+                        session_id: row.get(0)?,
+                        started_at: row.get(1)?,
+                        total,
+                        per_hour: per_hour(total, runtime),
+                    })
+                })?;
+                rows.collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(query_failed)?;
+        points.reverse();
+        Ok(points)
+    }
+}
+
+/// Running totals of the minimap service's metrics turned into this session's share
+struct MetricTotals {
+    frames: usize,
+    detections: usize,
+}
+
+impl MetricTotals {
+    fn new(stats: &PerformanceStats) -> Self {
+        Self {
+            frames: stats.capture.frames_captured,
+            detections: stats.minimap.detections,
+        }
+    }
+
+    /// Add what was counted since the last call to `session`
+    fn apply(&mut self, stats: &PerformanceStats, session: &mut SessionSummary) {
+        // Metrics count from zero again after a reset
+        let increase = |last: &mut usize, current: usize| {
+            let increase = if current >= *last { current - *last } else { current };
+            *last = current;
+            increase as u64
+        };
+        session.frames += increase(&mut self.frames, stats.capture.frames_captured);
+        session.detections += increase(&mut self.detections, stats.minimap.detections);
+    }
+}
+
+struct Recording {
+    cancel: CancellationToken,
+    task: JoinHandle<()>,
+}
+
+/// Records the stats of every session into a [`SessionStore`]
+///
+/// Frames and detections come from the minimap service's metrics, actions from
+/// [`BotEvent::InputSent`] and loot or XP from the counters of [`SessionStats`] if given.
+#[derive(Clone)]
+pub struct SessionHistory {
+    store: Arc<SessionStore>,
+    minimap: MinimapService,
+    stats: Option<SessionStats>,
+    recording: Arc<Mutex<Option<Recording>>>,
+}
+
+impl SessionHistory {
+    pub fn new(store: Arc<SessionStore>, minimap: MinimapService) -> Self {
+        Self {
+            store,
+            minimap,
+            stats: None,
+            recording: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Also record the totals of the counters of `stats`
+    pub fn with_stats(mut self, stats: SessionStats) -> Self {
+        self.stats = Some(stats);
+        self
+    }
+
+    /// Past sessions to query, the running one included
+    pub fn store(&self) -> &Arc<SessionStore> {
+        &self.store
+    }
+
+    pub async fn is_recording(&self) -> bool {
+        self.recording.lock().await.is_some()
+    }
+
+    /// Start a new session
+    pub async fn start_recording(&self) -> Result<(), String> {
+        let mut recording = self.recording.lock().await;
+        if recording.is_some() {
+            return Ok(());
+        }
+
+        let started_at = Local::now().timestamp();
+        let store = self.store.clone();
+        let id = tokio::task::spawn_blocking(move || store.begin(started_at))
+            .await
+            .map_err(|e| format!("Session history task failed: {}", e))??;
+        let session = SessionSummary {
+            id,
+            started_at,
+            runtime: Duration::ZERO,
+            frames: 0,
+            detections: 0,
+            actions: 0,
+            counters: BTreeMap::new(),
+        };
+
+        let cancel = CancellationToken::new();
+        let deltas = self.stats.as_ref().map(|stats| stats.subscribe());
+        let task = tokio::spawn(Self::record(
+            session,
+            self.store.clone(),
+            self.minimap.clone(),
+            deltas,
+            cancel.clone(),
+        ));
+        *recording = Some(Recording { cancel, task });
+        tracing::info!(session = id, "Recording session history");
+        Ok(())
+    }
+
+    /// End the session, its stats are saved before this returns
+    pub async fn stop_recording(&self) {
+        let Some(recording) = self.recording.lock().await.take() else {
+            return;
+        };
+        recording.cancel.cancel();
+        if let Err(e) = recording.task.await {
+            tracing::warn!(error = %e, "Session history task failed");
+        }
+    }
+
+    async fn record(
+        mut session: SessionSummary,
+        store: Arc<SessionStore>,
+        minimap: MinimapService,
+        mut deltas: Option<broadcast::Receiver<StatDelta>>,
+        cancel: CancellationToken,
+    ) {
+        let started = Instant::now();
+        let mut totals = MetricTotals::new(&minimap.get_performance_metrics());
+        let mut events = EventBus::global().subscribe();
+        let mut flush = tokio::time::interval_at(tokio::time::Instant::now() + FLUSH_INTERVAL, FLUSH_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = flush.tick() => {
+                    Self::flush(&mut session, started, &mut totals, &minimap, &store).await;
+                }
+                event = events.recv() => {
+                    if let Ok(BotEvent::InputSent { .. }) = event {
+                        session.actions += 1;
+                    }
+                }
+                delta = async {
+                    match deltas.as_mut() {
+                        Some(deltas) => deltas.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => match delta {
+                    Ok(delta) => *session.counters.entry(delta.name).or_default() += delta.delta,
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => deltas = None,
+                },
+            }
+        }
+
+        Self::flush(&mut session, started, &mut totals, &minimap, &store).await;
+        tracing::info!(session = session.id, runtime_s = session.runtime.as_secs(), "Session history saved");
+    }
+
+    async fn flush(
+        session: &mut SessionSummary,
+        started: Instant,
+        totals: &mut MetricTotals,
+        minimap: &MinimapService,
+        store: &Arc<SessionStore>,
+    ) {
+        totals.apply(&minimap.get_performance_metrics(), session);
+        session.runtime = started.elapsed();
+        let store = store.clone();
+        let snapshot = session.clone();
+        match tokio::task::spawn_blocking(move || store.update(&snapshot)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::warn!(error = %e, "Failed to save session history"),
+            Err(e) => tracing::warn!(error = %e, "Session history task failed"),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for SessionHistory {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_recording().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_recording().await;
+        Ok(())
+    }
+}
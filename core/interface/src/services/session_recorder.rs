@@ -0,0 +1,443 @@
+//! Session archives: sampled frames and bot events (including sent input) in one file
+//!
+//! An archive starts with [`ARCHIVE_MAGIC`] and a length-prefixed JSON [`SessionHeader`],
+//! followed by records of `kind: u8`, `offset_ms: u64`, `length: u32` and the payload, all
+//! little endian. Frame payloads are `width: u32`, `height: u32` and a JPEG, event payloads
+//! are the JSON of a [`BotEvent`].
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::services::{Service, ServiceError, ServiceTask};
+use super::event_bus::{BotEvent, EventBus};
+use super::frame_history::FrameHistoryService;
+use super::memory_budget::{MemoryAccount, MemoryBudget};
+use super::graphics_capture::{CapturedFrame, GraphicsCaptureService};
+
+/// First bytes of every session archive
+pub const ARCHIVE_MAGIC: &[u8; 8] = b"STRYSES1";
+
+/// Extension of session archives
+pub const ARCHIVE_EXTENSION: &str = "session";
+
+const RECORD_FRAME: u8 = 0;
+const RECORD_EVENT: u8 = 1;
+
+/// Records larger than this are treated as a corrupt archive
+const MAX_RECORD_BYTES: u32 = 64 * 1024 * 1024;
+
+/// What is recorded and how much disk it may use
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionRecorderConfig {
+    /// Directory archives are written into
+    pub dir: PathBuf,
+    /// Frames per second sampled from the capture stream
+    pub sample_fps: f64,
+    /// Downscale factor applied before compression (0.05 - 1.0)
+    pub scale: f64,
+    /// JPEG quality (0 - 100)
+    pub jpeg_quality: i32,
+    /// An archive growing past this is closed and a new one started
+    pub max_archive_bytes: u64,
+    /// Oldest archives are deleted beyond this many
+    pub max_archives: usize,
+}
+
+impl Default for SessionRecorderConfig {
+    fn default() -> Self {
+        Self {
+            dir: crate::config_dir().join("sessions"),
+            sample_fps: 5.0,
+            scale: 0.5,
+            jpeg_quality: 70,
+            max_archive_bytes: 256 * 1024 * 1024,
+            max_archives: 10,
+        }
+    }
+}
+
+/// Describes an archive, written once at its start
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionHeader {
+    /// Local time the archive was started, RFC 3339
+    pub started_at: String,
+    pub sample_fps: f64,
+    pub scale: f64,
+}
+
+/// A frame or event read back from an archive
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionRecord {
+    /// `jpeg` is the downscaled frame, `width` and `height` are those of the captured frame
+    Frame { offset: Duration, width: u32, height: u32, jpeg: Vec<u8> },
+    /// The event as JSON, `{"type": "input_sent", ...}` for sent input
+    Event { offset: Duration, event: serde_json::Value },
+}
+
+impl SessionRecord {
+    /// Time since the archive was started
+    pub fn offset(&self) -> Duration {
+        match self {
+            SessionRecord::Frame { offset, .. } | SessionRecord::Event { offset, .. } => *offset,
+        }
+    }
+}
+
+/// Reads the records of an archive in the order they were written
+///
+/// A record cut off by a crash ends the archive without an error.
+pub struct SessionReader {
+    header: SessionHeader,
+    reader: BufReader<File>,
+}
+
+impl SessionReader {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0; ARCHIVE_MAGIC.len()];
+        reader
+            .read_exact(&mut magic)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if &magic != ARCHIVE_MAGIC {
+            return Err(format!("{} is not a session archive", path.display()));
+        }
+        let header = read_payload(&mut reader)
+            .and_then(|json| serde_json::from_slice(&json).map_err(|e| e.to_string()))
+            .map_err(|e| format!("Failed to read header of {}: {}", path.display(), e))?;
+
+        Ok(Self { header, reader })
+    }
+
+    pub fn header(&self) -> &SessionHeader {
+        &self.header
+    }
+
+    fn read_record(&mut self) -> Result<Option<SessionRecord>, String> {
+        let mut kind = [0; 1];
+        if self.reader.read_exact(&mut kind).is_err() {
+            return Ok(None);
+        }
+        let mut offset = [0; 8];
+        if self.reader.read_exact(&mut offset).is_err() {
+            return Ok(None);
+        }
+        let offset = Duration::from_millis(u64::from_le_bytes(offset));
+        let Ok(payload) = read_payload(&mut self.reader) else {
+            return Ok(None);
+        };
+
+        match kind[0] {
+            RECORD_FRAME => {
+                if payload.len() < 8 {
+                    return Err("Frame record too short".to_string());
+                }
+                let (size, jpeg) = payload.split_at(8);
+                Ok(Some(SessionRecord::Frame {
+                    offset,
+                    width: u32::from_le_bytes(size[..4].try_into().unwrap()),
+                    height: u32::from_le_bytes(size[4..].try_into().unwrap()),
+                    jpeg: jpeg.to_vec(),
+                }))
+            }
+            RECORD_EVENT => Ok(Some(SessionRecord::Event {
+                offset,
+                event: serde_json::from_slice(&payload).map_err(|e| format!("Invalid event record: {}", e))?,
+            })),
+            kind => Err(format!("Unknown record kind {}", kind)),
+        }
+    }
+}
+
+impl Iterator for SessionReader {
+    type Item = Result<SessionRecord, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+fn read_payload(reader: &mut impl Read) -> Result<Vec<u8>, String> {
+    let mut length = [0; 4];
+    reader.read_exact(&mut length).map_err(|e| e.to_string())?;
+    let length = u32::from_le_bytes(length);
+    if length > MAX_RECORD_BYTES {
+        return Err(format!("Record of {} bytes is too large", length));
+    }
+    let mut payload = vec![0; length as usize];
+    reader.read_exact(&mut payload).map_err(|e| e.to_string())?;
+    Ok(payload)
+}
+
+/// Archives of `dir`, oldest first
+pub fn list_archives(dir: &Path) -> Vec<PathBuf> {
+    let mut archives: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|extension| extension == ARCHIVE_EXTENSION))
+                .collect()
+        })
+        .unwrap_or_default();
+    // Names start with the time they were started at
+    archives.sort();
+    archives
+}
+
+/// Something to append to the current archive
+enum Entry {
+    Frame { offset: Duration, frame: Arc<CapturedFrame> },
+    Event { offset: Duration, event: BotEvent },
+}
+
+/// Writes entries on its own thread, starting a new archive when one gets too large
+struct ArchiveWriter {
+    config: SessionRecorderConfig,
+    // Frames queued for the writer
+    memory: MemoryAccount,
+    writer: Option<BufWriter<File>>,
+    written: u64,
+    current: Arc<StdMutex<Option<PathBuf>>>,
+}
+
+impl ArchiveWriter {
+    fn run(mut self, entries: mpsc::Receiver<Entry>) {
+        for entry in entries {
+            if let Entry::Frame { frame, .. } = &entry {
+                self.memory.sub(frame.data.len() as u64);
+                // Drop the oldest queued frames until the queues are back within budget
+                if self.memory.is_exceeded() {
+                    continue;
+                }
+            }
+            if let Err(e) = self.write(entry) {
+                tracing::error!(error = %e, "Session recording failed");
+                // Try a fresh archive with the next entry
+                self.writer = None;
+            }
+        }
+        if let Some(mut writer) = self.writer.take() {
+            let _ = writer.flush();
+        }
+        *self.current.lock().unwrap() = None;
+    }
+
+    fn write(&mut self, entry: Entry) -> Result<(), String> {
+        let (kind, offset, payload) = match entry {
+            Entry::Frame { offset, frame } => {
+                let jpeg = FrameHistoryService::compress_frame(&frame, self.config.scale, self.config.jpeg_quality)?;
+                let mut payload = Vec::with_capacity(8 + jpeg.len());
+                payload.extend_from_slice(&frame.width.to_le_bytes());
+                payload.extend_from_slice(&frame.height.to_le_bytes());
+                payload.extend_from_slice(&jpeg);
+                (RECORD_FRAME, offset, payload)
+            }
+            Entry::Event { offset, event } => {
+                let json = serde_json::to_vec(&event).map_err(|e| format!("Failed to serialize event: {}", e))?;
+                (RECORD_EVENT, offset, json)
+            }
+        };
+
+        if self.writer.is_none() || self.written >= self.config.max_archive_bytes {
+            self.open_archive()?;
+        }
+        let writer = self.writer.as_mut().expect("archive was just opened");
+        writer
+            .write_all(&[kind])
+            .and_then(|_| writer.write_all(&(offset.as_millis() as u64).to_le_bytes()))
+            .and_then(|_| writer.write_all(&(payload.len() as u32).to_le_bytes()))
+            .and_then(|_| writer.write_all(&payload))
+            // Flushed per record so a crash loses as little as possible
+            .and_then(|_| writer.flush())
+            .map_err(|e| format!("Failed to write session record: {}", e))?;
+        self.written += 13 + payload.len() as u64;
+        Ok(())
+    }
+
+    fn open_archive(&mut self) -> Result<(), String> {
+        if let Some(mut writer) = self.writer.take() {
+            let _ = writer.flush();
+        }
+
+        let dir = &self.config.dir;
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let now = Local::now();
+        let path = dir.join(format!("{}.{}", now.format("%Y%m%d-%H%M%S%.3f"), ARCHIVE_EXTENSION));
+        let file = File::create(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        let mut writer = BufWriter::new(file);
+
+        let header = serde_json::to_vec(&SessionHeader {
+            started_at: now.to_rfc3339(),
+            sample_fps: self.config.sample_fps,
+            scale: self.config.scale,
+        })
+        .map_err(|e| format!("Failed to serialize session header: {}", e))?;
+        writer
+            .write_all(ARCHIVE_MAGIC)
+            .and_then(|_| writer.write_all(&(header.len() as u32).to_le_bytes()))
+            .and_then(|_| writer.write_all(&header))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+        tracing::info!(path = %path.display(), "Recording session");
+        self.written = (ARCHIVE_MAGIC.len() + 4 + header.len()) as u64;
+        self.writer = Some(writer);
+        *self.current.lock().unwrap() = Some(path);
+        self.prune();
+        Ok(())
+    }
+
+    /// Delete the oldest archives beyond the limit, the current one always stays
+    fn prune(&self) {
+        let archives = list_archives(&self.config.dir);
+        let excess = archives.len().saturating_sub(self.config.max_archives.max(1));
+        for archive in &archives[..excess] {
+            if let Err(e) = std::fs::remove_file(archive) {
+                tracing::warn!(path = %archive.display(), error = %e, "Failed to delete old session archive");
+            }
+        }
+    }
+}
+
+/// Records sampled frames and every bot event, including sent input, into session archives
+/// for post-mortem analysis and bug reports
+///
+/// Compression and writing happen on a separate thread, the capture is never held up.
+#[derive(Clone)]
+pub struct SessionRecorder {
+    graphics_service: Arc<GraphicsCaptureService>,
+    config: SessionRecorderConfig,
+    current: Arc<StdMutex<Option<PathBuf>>>,
+    /// Records until cancelled, then waits for the writer thread to finish the archive
+    task: ServiceTask,
+}
+
+impl SessionRecorder {
+    pub fn new(graphics_service: Arc<GraphicsCaptureService>, config: SessionRecorderConfig) -> Self {
+        Self {
+            graphics_service,
+            config,
+            current: Arc::new(StdMutex::new(None)),
+            task: ServiceTask::new(),
+        }
+    }
+
+    pub fn config(&self) -> &SessionRecorderConfig {
+        &self.config
+    }
+
+    /// Archive being written, `None` before the first record
+    pub fn current_archive(&self) -> Option<PathBuf> {
+        self.current.lock().unwrap().clone()
+    }
+
+    pub async fn is_recording(&self) -> bool {
+        self.task.is_busy().await
+    }
+
+    pub async fn start_recording(&self) -> Result<(), String> {
+        let mut frames = self.graphics_service.subscribe();
+        let mut events = EventBus::global().subscribe();
+        let sample_interval = Duration::from_secs_f64(1.0 / self.config.sample_fps.max(0.1));
+        let mut failed = None;
+
+        self.task
+            .start(|cancelled| {
+                let (sender, entries) = mpsc::channel();
+                let memory = MemoryBudget::global().account("session_recorder");
+                let writer = ArchiveWriter {
+                    config: self.config.clone(),
+                    memory: memory.clone(),
+                    writer: None,
+                    written: 0,
+                    current: self.current.clone(),
+                };
+                let writer = std::thread::Builder::new()
+                    .name("session-recorder".to_string())
+                    .spawn(move || writer.run(entries))
+                    .map_err(|e| format!("Failed to start session recorder: {}", e));
+                failed = writer.as_ref().err().cloned();
+
+                async move {
+                    let Ok(writer) = writer else {
+                        return;
+                    };
+                    let started = Instant::now();
+                    let mut last_sample: Option<Instant> = None;
+
+                    loop {
+                        let entry = tokio::select! {
+                            _ = cancelled.cancelled() => break,
+                            frame = frames.recv() => match frame {
+                                Ok(frame) => {
+                                    if last_sample.is_some_and(|last| frame.timestamp.duration_since(last) < sample_interval) {
+                                        continue;
+                                    }
+                                    let bytes = frame.data.len() as u64;
+                                    if !memory.fits(bytes) {
+                                        continue;
+                                    }
+                                    memory.add(bytes);
+                                    last_sample = Some(frame.timestamp);
+                                    Entry::Frame {
+                                        offset: frame.timestamp.saturating_duration_since(started),
+                                        frame,
+                                    }
+                                }
+                                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                                Err(broadcast::error::RecvError::Closed) => break,
+                            },
+                            event = events.recv() => match event {
+                                Ok(event) => Entry::Event {
+                                    offset: started.elapsed(),
+                                    event,
+                                },
+                                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                                Err(broadcast::error::RecvError::Closed) => break,
+                            },
+                        };
+                        if sender.send(entry).is_err() {
+                            break;
+                        }
+                    }
+
+                    // The writer drains the queue and flushes the archive once the sender is gone
+                    drop(sender);
+                    let _ = tokio::task::spawn_blocking(move || writer.join()).await;
+                }
+            })
+            .await;
+
+        match failed {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Stop recording, returns once everything recorded so far is written to the archive
+    pub async fn stop_recording(&self) {
+        self.task.stop().await;
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for SessionRecorder {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_recording().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_recording().await;
+        Ok(())
+    }
+}
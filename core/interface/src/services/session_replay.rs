@@ -0,0 +1,129 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::graphics_capture::{CaptureSource, CapturedFrame, PixelFormat};
+use super::image_ops::decode_bgra;
+use super::session_recorder::{SessionReader, SessionRecord};
+
+/// Longest the replay thread sleeps before checking whether it was stopped
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Frames of a session archive played back at the timing they were recorded with
+///
+/// Frames are scaled back to the size they were captured at, so regions of interest and
+/// detector coordinates line up with live capture. Events in the archive are not replayed.
+#[derive(Debug, Clone)]
+pub struct ReplaySource {
+    path: PathBuf,
+    speed: f64,
+    looping: bool,
+}
+
+impl ReplaySource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            speed: 1.0,
+            looping: false,
+        }
+    }
+
+    /// Play `speed` times as fast as recorded, e.g. 2.0 for double speed
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Start over at the end of the archive instead of stopping
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Call `on_frame` with every frame on a replay thread until the archive ends or the
+    /// replay is stopped
+    pub fn start(self, mut on_frame: impl FnMut(CapturedFrame) + Send + 'static) -> Result<ReplayControl, String> {
+        if !(self.speed > 0.0 && self.speed.is_finite()) {
+            return Err(format!("Invalid replay speed {}", self.speed));
+        }
+        // Opened here so a missing or invalid archive fails the start
+        let reader = SessionReader::open(&self.path)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name("session-replay".to_string())
+            .spawn(move || {
+                let mut reader = reader;
+                loop {
+                    if !self.play(reader, &stopped, &mut on_frame)? || !self.looping {
+                        return Ok(());
+                    }
+                    reader = SessionReader::open(&self.path)?;
+                }
+            })
+            .map_err(|e| format!("Failed to start replay: {}", e))?;
+
+        Ok(ReplayControl { stop, thread })
+    }
+
+    /// Play the archive once, `false` if stopped before its end
+    fn play(
+        &self,
+        reader: SessionReader,
+        stop: &AtomicBool,
+        on_frame: &mut impl FnMut(CapturedFrame),
+    ) -> Result<bool, String> {
+        let started = Instant::now();
+        for record in reader {
+            let SessionRecord::Frame { offset, width, height, jpeg } = record? else {
+                continue;
+            };
+
+            let due = started + offset.div_f64(self.speed);
+            loop {
+                if stop.load(Ordering::Relaxed) {
+                    return Ok(false);
+                }
+                match due.checked_duration_since(Instant::now()) {
+                    Some(wait) if !wait.is_zero() => std::thread::sleep(wait.min(STOP_POLL_INTERVAL)),
+                    _ => break,
+                }
+            }
+
+            on_frame(CapturedFrame {
+                data: decode_bgra(&jpeg, width, height)?,
+                width,
+                height,
+                timestamp: Instant::now(),
+                source: CaptureSource::Replay,
+                format: PixelFormat::Bgra,
+            });
+        }
+        Ok(true)
+    }
+}
+
+/// Handle of a running replay
+pub struct ReplayControl {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Result<(), String>>,
+}
+
+impl ReplayControl {
+    /// Whether the archive has been played to its end or failed
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Stop the replay and wait for its thread, returning the error it failed with if any
+    pub fn stop(self) -> Result<(), String> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread
+            .join()
+            .map_err(|_| "Replay thread panicked".to_string())?
+    }
+}
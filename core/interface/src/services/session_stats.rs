@@ -0,0 +1,384 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::services::{Service, ServiceError, ServiceTask};
+use super::ocr::{OcrReading, OcrRegion, OcrService};
+
+/// Capacity of the delta channel, slow subscribers skip old deltas
+const DELTA_CHANNEL_CAPACITY: usize = 64;
+
+/// What the number read from a region means
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatKind {
+    /// A running total like the gold or XP display, increases are counted and decreases start
+    /// a new baseline (gold spent, XP bar reset on level up)
+    #[default]
+    Total,
+    /// Gains shown one at a time like `+120 XP` popups or loot messages, each new reading
+    /// is counted once
+    Gain,
+}
+
+/// A number tracked over the session, read from its own OCR region
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatCounter {
+    pub region: OcrRegion,
+    #[serde(default)]
+    pub kind: StatKind,
+    /// Deltas above this are treated as misreads, e.g. a dropped separator turning `1,200`
+    /// into `1200` is fine but an extra digit isn't
+    #[serde(default)]
+    pub max_delta: Option<f64>,
+}
+
+impl StatCounter {
+    pub fn new(region: OcrRegion, kind: StatKind) -> Self {
+        Self {
+            region,
+            kind,
+            max_delta: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.region.name
+    }
+}
+
+/// Tracked counters, persisted across restarts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionStatsConfig {
+    pub counters: Vec<StatCounter>,
+    /// Length of the window the recent rate is computed over
+    #[serde(with = "super::duration_millis")]
+    pub recent_window: Duration,
+}
+
+impl Default for SessionStatsConfig {
+    fn default() -> Self {
+        Self {
+            counters: Vec::new(),
+            recent_window: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+impl SessionStatsConfig {
+    pub fn path() -> PathBuf {
+        crate::config_dir().join("session_stats.json")
+    }
+
+    /// Load the saved counters, falling back to none if there are none
+    pub fn load() -> Self {
+        let path = Self::path();
+        let Ok(json) = std::fs::read_to_string(&path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&json).unwrap_or_else(|e| {
+            tracing::warn!(path = %path.display(), error = %e, "Ignoring invalid session stats config");
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize session stats config: {}", e))?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+/// A counted increase of a counter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatDelta {
+    pub name: String,
+    pub delta: f64,
+    /// Sum of all deltas of the counter this session
+    pub total: f64,
+}
+
+/// Totals and rates of a counter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CounterStats {
+    pub name: String,
+    /// Sum of all deltas this session
+    pub total: f64,
+    /// Total divided by the session length
+    pub per_hour: f64,
+    /// Rate over the recent window, reacts faster when the route changes
+    pub recent_per_hour: f64,
+    /// Last number read, `None` until the region was read once
+    pub last_value: Option<f64>,
+}
+
+/// Snapshot of the session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionReport {
+    #[serde(with = "super::duration_millis")]
+    pub elapsed: Duration,
+    pub counters: Vec<CounterStats>,
+}
+
+impl fmt::Display for SessionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let minutes = self.elapsed.as_secs() / 60;
+        write!(f, "Session: {}h {:02}m", minutes / 60, minutes % 60)?;
+        for counter in &self.counters {
+            write!(
+                f,
+                "\n  {}: {:.0} ({:.0}/h, recent {:.0}/h)",
+                counter.name, counter.total, counter.per_hour, counter.recent_per_hour
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Running aggregation of one counter
+#[derive(Debug, Default)]
+struct CounterState {
+    last_value: Option<f64>,
+    /// Text of the last gain reading, a popup shown across several reads counts once
+    last_text: Option<String>,
+    total: f64,
+    recent: VecDeque<(Instant, f64)>,
+}
+
+impl CounterState {
+    /// Delta counted for a new reading, if any
+    fn update(&mut self, counter: &StatCounter, reading: &OcrReading, now: Instant) -> Option<f64> {
+        let value = reading.numbers().first().copied();
+        let delta = match counter.kind {
+            StatKind::Total => {
+                let value = value?;
+                let previous = self.last_value.replace(value);
+                previous.map(|previous| value - previous).filter(|delta| *delta > 0.0)
+            }
+            StatKind::Gain => {
+                let text = reading.text.trim();
+                if text.is_empty() {
+                    self.last_text = None;
+                    return None;
+                }
+                if self.last_text.as_deref() == Some(text) {
+                    return None;
+                }
+                self.last_text = Some(text.to_string());
+                self.last_value = value;
+                value.filter(|value| *value > 0.0)
+            }
+        }?;
+        if counter.max_delta.is_some_and(|max| delta > max) {
+            return None;
+        }
+
+        self.total += delta;
+        self.recent.push_back((now, delta));
+        Some(delta)
+    }
+
+    fn stats(&self, name: &str, elapsed: Duration, recent_window: Duration, now: Instant) -> CounterStats {
+        let per_hour = |amount: f64, duration: Duration| {
+            if duration.is_zero() {
+                0.0
+            } else {
+                amount * 3600.0 / duration.as_secs_f64()
+            }
+        };
+        let recent: f64 = self
+            .recent
+            .iter()
+            .filter(|(at, _)| now.duration_since(*at) <= recent_window)
+            .map(|(_, delta)| delta)
+            .sum();
+        CounterStats {
+            name: name.to_string(),
+            total: self.total,
+            per_hour: per_hour(self.total, elapsed),
+            recent_per_hour: per_hour(recent, elapsed.min(recent_window)),
+            last_value: self.last_value,
+        }
+    }
+
+    fn prune(&mut self, recent_window: Duration, now: Instant) {
+        while self.recent.front().is_some_and(|(at, _)| now.duration_since(*at) > recent_window) {
+            self.recent.pop_front();
+        }
+    }
+}
+
+struct Session {
+    started: Instant,
+    counters: HashMap<String, CounterState>,
+}
+
+impl Session {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            counters: HashMap::new(),
+        }
+    }
+}
+
+/// Turns OCR readings of XP, gold or kill counters into session totals and per hour rates
+///
+/// Counter regions are added to the OCR service on start and read at its interval.
+#[derive(Clone)]
+pub struct SessionStats {
+    ocr: OcrService,
+    config: Arc<StdMutex<SessionStatsConfig>>,
+    session: Arc<StdMutex<Session>>,
+    delta_sender: broadcast::Sender<StatDelta>,
+    task: ServiceTask,
+}
+
+impl SessionStats {
+    pub fn new(ocr: OcrService, config: SessionStatsConfig) -> Self {
+        Self {
+            ocr,
+            config: Arc::new(StdMutex::new(config)),
+            session: Arc::new(StdMutex::new(Session::new())),
+            delta_sender: broadcast::channel(DELTA_CHANNEL_CAPACITY).0,
+            task: ServiceTask::new(),
+        }
+    }
+
+    /// OCR service the counter regions are read by
+    pub fn ocr(&self) -> &OcrService {
+        &self.ocr
+    }
+
+    /// Counted increases of every counter
+    pub fn subscribe(&self) -> broadcast::Receiver<StatDelta> {
+        self.delta_sender.subscribe()
+    }
+
+    pub fn config(&self) -> SessionStatsConfig {
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Replace the counters and save them, regions of removed counters stop being read
+    pub fn set_config(&self, config: SessionStatsConfig) -> Result<(), String> {
+        let previous = std::mem::replace(&mut *self.config.lock().unwrap(), config.clone());
+        for counter in &previous.counters {
+            if !config.counters.iter().any(|c| c.name() == counter.name()) {
+                self.ocr.remove_region(counter.name());
+            }
+        }
+        for counter in &config.counters {
+            self.ocr.set_region(counter.region.clone());
+        }
+        config.save()
+    }
+
+    /// Totals and rates since the session started
+    pub fn report(&self) -> SessionReport {
+        let config = self.config();
+        let now = Instant::now();
+        let mut session = self.session.lock().unwrap();
+        let elapsed = now.duration_since(session.started);
+        let counters = config
+            .counters
+            .iter()
+            .map(|counter| {
+                let state = session.counters.entry(counter.name().to_string()).or_default();
+                state.prune(config.recent_window, now);
+                state.stats(counter.name(), elapsed, config.recent_window, now)
+            })
+            .collect();
+        SessionReport { elapsed, counters }
+    }
+
+    /// Start a new session, totals go back to zero
+    pub fn reset(&self) {
+        *self.session.lock().unwrap() = Session::new();
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.task.is_busy().await
+    }
+
+    /// Start counting, the OCR service has to be running for readings to arrive
+    pub async fn start_stats(&self) -> Result<(), String> {
+        for counter in self.config().counters {
+            self.ocr.set_region(counter.region);
+        }
+
+        let mut receiver = self.ocr.subscribe();
+        let config = self.config.clone();
+        let session = self.session.clone();
+        let delta_sender = self.delta_sender.clone();
+
+        self.task.start(move |cancelled| async move {
+            loop {
+                // Stopping doesn't wait for the next reading
+                let reading = tokio::select! {
+                    _ = cancelled.cancelled() => break,
+                    reading = receiver.recv() => reading,
+                };
+                match reading {
+                    Ok(reading) => {
+                        let counter = config
+                            .lock()
+                            .unwrap()
+                            .counters
+                            .iter()
+                            .find(|counter| counter.name() == reading.region)
+                            .cloned();
+                        let Some(counter) = counter else {
+                            continue;
+                        };
+
+                        let mut session = session.lock().unwrap();
+                        let state = session.counters.entry(reading.region.clone()).or_default();
+                        if let Some(delta) = state.update(&counter, &reading, Instant::now()) {
+                            let _ = delta_sender.send(StatDelta {
+                                name: reading.region,
+                                delta,
+                                total: state.total,
+                            });
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Stop counting, a reading in progress is counted before this returns
+    pub async fn stop_stats(&self) {
+        self.task.stop().await;
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for SessionStats {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_stats().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_stats().await;
+        Ok(())
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        vec!["OcrService".to_string()]
+    }
+}
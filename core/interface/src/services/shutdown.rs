@@ -0,0 +1,105 @@
+//! Stopping everything in order when the bot exits
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::graphics_capture::GraphicsCaptureService;
+use super::input_scheduler;
+#[cfg(feature = "opencv")]
+use super::minimap_v2::MinimapService;
+use super::service_manager::ServiceManager;
+
+/// Longest each shutdown step is waited for by default
+pub const SHUTDOWN_STEP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Stops the bot on exit (Ctrl+C, closing the window) without leaving keys held down or
+/// half written recordings behind
+///
+/// Steps run in order, each waited for at most the step timeout so one hanging task can't
+/// keep the process alive or skip the steps after it:
+/// 1. stop the managed services: automation, recorders (flushing their files) and the rest
+/// 2. cancel queued input and release every held key
+/// 3. save the explored map and stop the minimap capture, if given
+/// 4. stop the graphics capture
+pub struct Shutdown {
+    services: Arc<ServiceManager>,
+    graphics_service: Arc<GraphicsCaptureService>,
+    #[cfg(feature = "opencv")]
+    minimap_service: Option<MinimapService>,
+    step_timeout: Duration,
+}
+
+impl Shutdown {
+    pub fn new(services: Arc<ServiceManager>, graphics_service: Arc<GraphicsCaptureService>) -> Self {
+        Self {
+            services,
+            graphics_service,
+            #[cfg(feature = "opencv")]
+            minimap_service: None,
+            step_timeout: SHUTDOWN_STEP_TIMEOUT,
+        }
+    }
+
+    /// Also save the minimap's explored map and stop its processing before the capture
+    #[cfg(feature = "opencv")]
+    pub fn with_minimap(mut self, minimap_service: MinimapService) -> Self {
+        self.minimap_service = Some(minimap_service);
+        self
+    }
+
+    pub fn with_step_timeout(mut self, step_timeout: Duration) -> Self {
+        self.step_timeout = step_timeout;
+        self
+    }
+
+    /// Run every step, the error lists the ones that failed or timed out
+    pub async fn run(&self) -> Result<(), String> {
+        tracing::info!("Shutting down");
+        let mut failed = Vec::new();
+
+        self.step("services", self.services.stop_all(), &mut failed).await;
+        let release_keys = async {
+            input_scheduler::release_all_keys().await;
+            Ok::<_, String>(())
+        };
+        self.step("input", release_keys, &mut failed).await;
+        #[cfg(feature = "opencv")]
+        if let Some(minimap_service) = &self.minimap_service {
+            let save_map = async { minimap_service.stop_map().map(|_| ()) };
+            self.step("map", save_map, &mut failed).await;
+            self.step("minimap", minimap_service.stop_capture(), &mut failed).await;
+        }
+        let stop_capture = async {
+            self.graphics_service.stop_capture().await;
+            Ok::<_, String>(())
+        };
+        self.step("capture", stop_capture, &mut failed).await;
+
+        if failed.is_empty() {
+            tracing::info!("Shutdown complete");
+            Ok(())
+        } else {
+            Err(format!("Shutdown incomplete: {}", failed.join(", ")))
+        }
+    }
+
+    async fn step(
+        &self,
+        name: &'static str,
+        step: impl Future<Output = Result<(), String>>,
+        failed: &mut Vec<String>,
+    ) {
+        match tokio::time::timeout(self.step_timeout, step).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                tracing::warn!(step = name, error = %e, "Shutdown step failed");
+                failed.push(format!("{} ({})", name, e));
+            }
+            Err(_) => {
+                tracing::warn!(step = name, timeout = ?self.step_timeout, "Shutdown step timed out");
+                failed.push(format!("{} (timed out)", name));
+            }
+        }
+    }
+}
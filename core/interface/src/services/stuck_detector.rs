@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use platforms::input::KeyKind;
+use serde::{Deserialize, Serialize};
+
+use super::player_arrow::PlayerPosition;
+
+/// What is tried, in order, when the player is stuck
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StuckRecovery {
+    /// Tapped to hop over low obstacles, `None` skips jumping
+    pub jump_key: Option<KeyKind>,
+    /// Held for `turn_duration` to walk around the obstacle, `None` skips turning
+    pub turn_key: Option<KeyKind>,
+    #[serde(with = "super::duration_millis")]
+    pub turn_duration: Duration,
+    /// Give up on the current waypoint and head for the next one
+    pub repath: bool,
+}
+
+impl Default for StuckRecovery {
+    fn default() -> Self {
+        Self {
+            jump_key: Some(KeyKind::Space),
+            turn_key: Some(KeyKind::D),
+            turn_duration: Duration::from_millis(400),
+            repath: true,
+        }
+    }
+}
+
+/// A single recovery attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryAction {
+    Jump,
+    Turn,
+    Repath,
+}
+
+impl StuckRecovery {
+    /// Recovery for the `attempt`th time in a row the player got stuck, counted from 0,
+    /// `None` once everything was tried
+    pub fn action(&self, attempt: usize) -> Option<RecoveryAction> {
+        [
+            self.jump_key.map(|_| RecoveryAction::Jump),
+            self.turn_key.map(|_| RecoveryAction::Turn),
+            self.repath.then_some(RecoveryAction::Repath),
+        ]
+        .into_iter()
+        .flatten()
+        .nth(attempt)
+    }
+}
+
+/// When the player counts as stuck
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StuckConfig {
+    /// Length of the sliding window positions are compared over
+    #[serde(with = "super::duration_millis")]
+    pub window: Duration,
+    /// The player is stuck if it stayed within this many minimap pixels during the window
+    pub min_distance: f32,
+    pub recovery: StuckRecovery,
+}
+
+impl Default for StuckConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(3),
+            min_distance: 2.0,
+            recovery: StuckRecovery::default(),
+        }
+    }
+}
+
+/// The player didn't move while movement input was being sent
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PlayerStuck {
+    /// Position the player is stuck at, in minimap pixels
+    pub x: f32,
+    pub y: f32,
+    /// How long movement input had no effect
+    #[serde(with = "super::duration_millis")]
+    pub duration: Duration,
+}
+
+/// Watchdog comparing player positions over a sliding window while movement input is active
+#[derive(Debug, Clone)]
+pub struct StuckDetector {
+    config: StuckConfig,
+    positions: VecDeque<(Instant, f32, f32)>,
+}
+
+impl StuckDetector {
+    pub fn new(config: StuckConfig) -> Self {
+        Self {
+            config,
+            positions: VecDeque::new(),
+        }
+    }
+
+    pub fn config(&self) -> &StuckConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: StuckConfig) {
+        self.config = config;
+    }
+
+    /// Forget the window, e.g. after a recovery or when movement input stops
+    pub fn reset(&mut self) {
+        self.positions.clear();
+    }
+
+    /// Add a position seen while movement input was active
+    ///
+    /// Returns the stuck player once a full window of positions stayed within `min_distance`,
+    /// the window starts over afterwards so each stall is only reported once per window.
+    pub fn update(&mut self, position: &PlayerPosition, now: Instant) -> Option<PlayerStuck> {
+        self.positions.push_back((now, position.x, position.y));
+        while self
+            .positions
+            .get(1)
+            .is_some_and(|&(seen_at, _, _)| now.duration_since(seen_at) >= self.config.window)
+        {
+            self.positions.pop_front();
+        }
+
+        let &(oldest, _, _) = self.positions.front()?;
+        let duration = now.duration_since(oldest);
+        if duration < self.config.window {
+            return None;
+        }
+        let moved = self.positions.iter().any(|&(_, x, y)| {
+            (x - position.x).hypot(y - position.y) > self.config.min_distance
+        });
+        if moved {
+            return None;
+        }
+
+        self.reset();
+        Some(PlayerStuck {
+            x: position.x,
+            y: position.y,
+            duration,
+        })
+    }
+}
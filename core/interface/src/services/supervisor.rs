@@ -0,0 +1,181 @@
+//! Restarting background tasks that fail or panic instead of letting them die silently
+
+use std::any::Any;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::task::{JoinError, JoinHandle};
+use tokio_util::sync::CancellationToken;
+
+use super::event_bus::{BotEvent, EventBus};
+
+/// When and how often a failed task is restarted
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RestartPolicy {
+    /// Restarts in a row before giving up, 0 never restarts
+    pub max_restarts: u32,
+    /// Delay before the first restart, doubled for each one after it
+    #[serde(with = "super::duration_millis")]
+    pub initial_backoff: Duration,
+    #[serde(with = "super::duration_millis")]
+    pub max_backoff: Duration,
+    /// A task running this long before failing counts as recovered, the restarts start over
+    #[serde(with = "super::duration_millis")]
+    pub reset_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            reset_after: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Never restart, failures are still reported
+    pub fn never() -> Self {
+        Self {
+            max_restarts: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before restart number `restart` (0 based)
+    pub fn backoff(&self, restart: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(restart))
+            .min(self.max_backoff)
+    }
+}
+
+/// Run the task `start` creates, starting a new one when it fails or panics
+///
+/// A task returning `Ok` is done and not restarted, so tasks end supervision by returning
+/// `Ok` when their service was stopped. Every restart is published as
+/// [`BotEvent::TaskRestarting`], giving up as [`BotEvent::ServiceFailed`].
+pub fn supervise<F, Fut>(task: &'static str, policy: RestartPolicy, mut start: F) -> JoinHandle<()>
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), String>> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut supervisor = Supervisor::new(task, policy);
+        loop {
+            let started = Instant::now();
+            let Some(error) = failure(tokio::spawn(start()).await) else {
+                return;
+            };
+            if !supervisor.restart_after(error, started).await {
+                return;
+            }
+        }
+    })
+}
+
+/// [`supervise`] for blocking tasks, each run on the blocking thread pool
+pub fn supervise_blocking<F, G>(task: &'static str, policy: RestartPolicy, start: F) -> JoinHandle<()>
+where
+    F: FnMut() -> G + Send + 'static,
+    G: FnOnce() -> Result<(), String> + Send + 'static,
+{
+    supervise_blocking_until(task, policy, CancellationToken::new(), start)
+}
+
+/// [`supervise_blocking`] ending once `cancel` is cancelled, without waiting out a backoff
+///
+/// The running task has to watch `cancel` itself and return `Ok`, awaiting the returned
+/// handle after cancelling then waits for just that.
+pub fn supervise_blocking_until<F, G>(
+    task: &'static str,
+    policy: RestartPolicy,
+    cancel: CancellationToken,
+    mut start: F,
+) -> JoinHandle<()>
+where
+    F: FnMut() -> G + Send + 'static,
+    G: FnOnce() -> Result<(), String> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut supervisor = Supervisor::new(task, policy);
+        loop {
+            let started = Instant::now();
+            let Some(error) = failure(tokio::task::spawn_blocking(start()).await) else {
+                return;
+            };
+            let restart = tokio::select! {
+                restart = supervisor.restart_after(error, started) => restart,
+                _ = cancel.cancelled() => false,
+            };
+            if !restart || cancel.is_cancelled() {
+                return;
+            }
+        }
+    })
+}
+
+/// Why a task ended, `None` if it finished or the runtime is shutting down
+fn failure(result: Result<Result<(), String>, JoinError>) -> Option<String> {
+    match result {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e),
+        Err(e) if e.is_panic() => Some(format!("panicked: {}", panic_message(e.into_panic()))),
+        Err(_) => None,
+    }
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+struct Supervisor {
+    task: &'static str,
+    policy: RestartPolicy,
+    restarts: u32,
+}
+
+impl Supervisor {
+    fn new(task: &'static str, policy: RestartPolicy) -> Self {
+        Self {
+            task,
+            policy,
+            restarts: 0,
+        }
+    }
+
+    /// Report the failure and wait out the backoff, `false` if the task isn't restarted
+    async fn restart_after(&mut self, error: String, started: Instant) -> bool {
+        if started.elapsed() >= self.policy.reset_after {
+            self.restarts = 0;
+        }
+        if self.restarts >= self.policy.max_restarts {
+            tracing::error!(task = self.task, error = %error, restarts = self.restarts, "Task failed, giving up");
+            EventBus::global().publish(BotEvent::ServiceFailed {
+                service: self.task.to_string(),
+                error,
+            });
+            return false;
+        }
+
+        let delay = self.policy.backoff(self.restarts);
+        self.restarts += 1;
+        tracing::warn!(task = self.task, error = %error, attempt = self.restarts, ?delay, "Task failed, restarting");
+        EventBus::global().publish(BotEvent::TaskRestarting {
+            task: self.task.to_string(),
+            error,
+            attempt: self.restarts,
+            delay_ms: delay.as_millis() as u64,
+        });
+        tokio::time::sleep(delay).await;
+        true
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::services::{Service, ServiceError, ServiceTask};
+use super::cpu_pool::spawn_cpu;
+use super::event_bus::{BotEvent, EventBus};
+use super::frame_diff::FrameDiff;
+use super::graphics_capture::{CapturedFrame, GraphicsCaptureService};
+use super::scale_search::{gray_frame, load_gray, GrayFrame, ScaleSearch, DEFAULT_REFERENCE_HEIGHT};
+use super::vision::Rect;
+
+/// Capacity of the event channel, slow subscribers skip old events
+const EVENT_CHANNEL_CAPACITY: usize = 64;
+
+/// Optional file in a template directory holding per-template settings keyed by template name
+pub const TEMPLATE_SETTINGS_FILE: &str = "templates.json";
+
+/// Default minimum normalized correlation for a template to count as matched
+pub const DEFAULT_TEMPLATE_THRESHOLD: f64 = 0.85;
+
+/// A matched template must move more than this many pixels to be reported again
+const MIN_MATCH_MOVE: i32 = 2;
+
+/// Scales tried around the resolution estimate when the spread is above zero
+const SCALE_STEPS: usize = 5;
+
+/// How a template is searched for
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TemplateSettings {
+    /// Minimum normalized correlation (0.0 - 1.0)
+    pub threshold: f64,
+    /// Only search this part of the frame, much faster than the full frame
+    pub roi: Option<Rect>,
+    /// Height of the game window the template was cut from, the template is scaled by the
+    /// captured height over this so it keeps matching at other resolutions
+    pub reference_height: u32,
+    /// Fraction the scale may be off from the resolution estimate, e.g. 0.1 also tries
+    /// 90% - 110% of it for UI scaling, 0 only tries the estimate
+    pub scale_spread: f64,
+}
+
+impl Default for TemplateSettings {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_TEMPLATE_THRESHOLD,
+            roi: None,
+            reference_height: DEFAULT_REFERENCE_HEIGHT,
+            scale_spread: 0.1,
+        }
+    }
+}
+
+impl TemplateSettings {
+    fn scale_search(&self) -> ScaleSearch {
+        ScaleSearch::around(self.reference_height, self.scale_spread, SCALE_STEPS)
+    }
+}
+
+/// Where a template was found
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateMatch {
+    pub name: String,
+    /// Matched area in frame pixels, click `rect.center()` to hit a button
+    pub rect: Rect,
+    pub score: f64,
+}
+
+/// Change of a template's visibility
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TemplateEvent {
+    /// The template appeared or moved
+    Matched { matched: TemplateMatch },
+    /// The template is no longer visible
+    Lost { name: String },
+}
+
+#[derive(Debug)]
+struct Template {
+    image: GrayFrame,
+    settings: TemplateSettings,
+    /// Remembers the winning scale, behind a lock so matching doesn't need the library mutably
+    search: StdMutex<ScaleSearch>,
+}
+
+/// Named grayscale templates, e.g. buttons, icons or loot
+#[derive(Debug, Default)]
+pub struct TemplateLibrary {
+    templates: HashMap<String, Template>,
+}
+
+impl TemplateLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Directory detection templates are kept in, per game profile
+    pub fn dir() -> PathBuf {
+        crate::config::profile_dir().join("templates")
+    }
+
+    /// Load every PNG in `dir`, named after its file stem
+    ///
+    /// Settings are read from [`TEMPLATE_SETTINGS_FILE`] in the same directory if present,
+    /// templates without an entry use the defaults.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Self, String> {
+        let dir = dir.as_ref();
+        let settings_path = dir.join(TEMPLATE_SETTINGS_FILE);
+        let mut settings: HashMap<String, TemplateSettings> = match std::fs::read(&settings_path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|e| format!("Failed to parse {}: {}", settings_path.display(), e))?,
+            Err(_) => HashMap::new(),
+        };
+
+        let entries = std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        let mut library = Self::new();
+        for entry in entries {
+            let path = entry.map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?.path();
+            let is_png = path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+            if !is_png {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let image = load_gray(&path)?;
+            let template_settings = settings.remove(name).unwrap_or_default();
+            library.insert(name, image, template_settings);
+        }
+
+        Ok(library)
+    }
+
+    /// Add a grayscale template or replace the one with the same name
+    pub fn insert(&mut self, name: impl Into<String>, image: GrayFrame, settings: TemplateSettings) {
+        let search = StdMutex::new(settings.scale_search());
+        self.templates.insert(name.into(), Template { image, settings, search });
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.templates.remove(name).is_some()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.templates.keys().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+
+    pub fn settings(&self, name: &str) -> Option<TemplateSettings> {
+        self.templates.get(name).map(|template| template.settings)
+    }
+
+    pub fn set_settings(&mut self, name: &str, settings: TemplateSettings) -> Result<(), String> {
+        let template = self
+            .templates
+            .get_mut(name)
+            .ok_or_else(|| format!("Unknown template {}", name))?;
+        template.settings = settings;
+        template.search = StdMutex::new(settings.scale_search());
+        Ok(())
+    }
+
+    /// Find the best match of every template in a frame
+    ///
+    /// Templates with an ROI only copy that region of the frame, the full frame is converted
+    /// at most once for the rest. Templates are scaled to the frame's resolution.
+    pub fn match_frame(&self, frame: &CapturedFrame) -> Result<Vec<TemplateMatch>, String> {
+        let mut full_gray: Option<GrayFrame> = None;
+        let mut matches = Vec::new();
+
+        let frame_height = frame.height as i32;
+        for (name, template) in &self.templates {
+            let mut search = template.search.lock().unwrap();
+            let threshold = template.settings.threshold;
+            let roi = template
+                .settings
+                .roi
+                .and_then(|roi| roi.clamp_to(frame.width as i32, frame.height as i32));
+            let found = match roi {
+                Some(roi) => {
+                    let gray = gray_frame(frame, Some(roi))?;
+                    search.find(&gray, &template.image, frame_height, threshold)?.map(|found| {
+                        let rect = found.rect;
+                        (Rect::new(rect.x + roi.x, rect.y + roi.y, rect.width, rect.height), found.score)
+                    })
+                }
+                None => {
+                    if full_gray.is_none() {
+                        full_gray = Some(gray_frame(frame, None)?);
+                    }
+                    search
+                        .find(full_gray.as_ref().unwrap(), &template.image, frame_height, threshold)?
+                        .map(|found| (found.rect, found.score))
+                }
+            };
+
+            if let Some((rect, score)) = found.filter(|(_, score)| *score >= threshold) {
+                matches.push(TemplateMatch {
+                    name: name.clone(),
+                    rect,
+                    score,
+                });
+            }
+        }
+
+        Ok(matches)
+    }
+}
+
+/// Searches captured frames for a library of templates and reports when they appear or vanish
+#[derive(Clone)]
+pub struct TemplateMatcher {
+    graphics_service: Arc<GraphicsCaptureService>,
+    library: Arc<StdMutex<TemplateLibrary>>,
+    latest: Arc<StdMutex<HashMap<String, TemplateMatch>>>,
+    event_sender: broadcast::Sender<TemplateEvent>,
+    task: ServiceTask,
+}
+
+impl TemplateMatcher {
+    pub fn new(graphics_service: Arc<GraphicsCaptureService>, library: TemplateLibrary) -> Self {
+        Self {
+            graphics_service,
+            library: Arc::new(StdMutex::new(library)),
+            latest: Arc::new(StdMutex::new(HashMap::new())),
+            event_sender: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            task: ServiceTask::new(),
+        }
+    }
+
+    /// Appear, move and vanish events of all templates
+    pub fn subscribe(&self) -> broadcast::Receiver<TemplateEvent> {
+        self.event_sender.subscribe()
+    }
+
+    /// Where a template currently is, `None` if it is not visible
+    pub fn find(&self, name: &str) -> Option<TemplateMatch> {
+        self.latest.lock().unwrap().get(name).cloned()
+    }
+
+    /// All currently visible templates
+    pub fn matches(&self) -> Vec<TemplateMatch> {
+        self.latest.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Replace the template library, e.g. after the template directory changed
+    pub fn set_library(&self, library: TemplateLibrary) {
+        *self.library.lock().unwrap() = library;
+    }
+
+    pub fn set_settings(&self, name: &str, settings: TemplateSettings) -> Result<(), String> {
+        self.library.lock().unwrap().set_settings(name, settings)
+    }
+
+    pub async fn is_running(&self) -> bool {
+        self.task.is_busy().await
+    }
+
+    pub async fn start_matching(&self) -> Result<(), String> {
+        let mut receiver = self.graphics_service.subscribe();
+        let library = self.library.clone();
+        let latest = self.latest.clone();
+        let event_sender = self.event_sender.clone();
+
+        self.task.start(move |cancelled| async move {
+            let mut frame_diff = FrameDiff::default();
+
+            loop {
+                // Stopping doesn't wait for the next frame
+                let frame = tokio::select! {
+                    _ = cancelled.cancelled() => break,
+                    frame = receiver.recv() => frame,
+                };
+                match frame {
+                    Ok(frame) => {
+                        if !frame_diff.is_changed(&frame) {
+                            continue;
+                        }
+
+                        let library = library.clone();
+                        let result = spawn_cpu(move || library.lock().unwrap().match_frame(&frame)).await;
+                        match result {
+                            Ok(Ok(matches)) => {
+                                for event in Self::update(&mut latest.lock().unwrap(), matches) {
+                                    if let TemplateEvent::Matched { matched } = &event {
+                                        EventBus::global().publish(BotEvent::TemplateMatched {
+                                            template: matched.name.clone(),
+                                            score: matched.score,
+                                        });
+                                    }
+                                    let _ = event_sender.send(event);
+                                }
+                            }
+                            Ok(Err(e)) => tracing::warn!(error = %e, "Template matching failed"),
+                            Err(e) => tracing::error!(error = %e, "Template matching task failed"),
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Stop matching, the frame in progress is finished before this returns
+    pub async fn stop_matching(&self) {
+        self.task.stop().await;
+        self.latest.lock().unwrap().clear();
+    }
+
+    /// Replace the visible templates with `matches` and return what changed
+    fn update(latest: &mut HashMap<String, TemplateMatch>, matches: Vec<TemplateMatch>) -> Vec<TemplateEvent> {
+        let mut events: Vec<TemplateEvent> = latest
+            .keys()
+            .filter(|name| !matches.iter().any(|matched| &matched.name == *name))
+            .map(|name| TemplateEvent::Lost { name: name.clone() })
+            .collect();
+
+        let mut visible = HashMap::with_capacity(matches.len());
+        for matched in matches {
+            let moved = latest.get(&matched.name).map_or(true, |last| {
+                (last.rect.x - matched.rect.x).abs() > MIN_MATCH_MOVE
+                    || (last.rect.y - matched.rect.y).abs() > MIN_MATCH_MOVE
+            });
+            if moved {
+                events.push(TemplateEvent::Matched { matched: matched.clone() });
+                visible.insert(matched.name.clone(), matched);
+            } else {
+                // Keep the reported position so slow drifts are still noticed
+                let name = matched.name.clone();
+                visible.insert(name.clone(), latest.remove(&name).unwrap_or(matched));
+            }
+        }
+
+        *latest = visible;
+        events
+    }
+}
+
+#[async_trait::async_trait]
+impl Service for TemplateMatcher {
+    async fn start(&self) -> Result<(), ServiceError> {
+        self.start_matching().await.map_err(ServiceError::from)
+    }
+
+    async fn stop(&self) -> Result<(), ServiceError> {
+        self.stop_matching().await;
+        Ok(())
+    }
+}
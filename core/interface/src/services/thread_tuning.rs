@@ -0,0 +1,61 @@
+//! Priority and core affinity of the capture and frame processing threads
+
+use std::cell::RefCell;
+use std::sync::RwLock;
+
+use platforms::thread::{set_current_thread_affinity, set_current_thread_priority};
+use tokio::sync::watch;
+
+use crate::config::{BotConfig, PerformanceConfig, ThreadSettings};
+
+static SETTINGS: RwLock<Option<PerformanceConfig>> = RwLock::new(None);
+
+thread_local! {
+    // Settings last applied to this thread, `None` while it was never changed
+    static APPLIED: RefCell<Option<ThreadSettings>> = const { RefCell::new(None) };
+}
+
+/// Use `config` for capture threads started and frames processed from now on
+pub fn configure(config: &PerformanceConfig) {
+    *SETTINGS.write().unwrap() = Some(config.clone());
+}
+
+/// Apply the performance section of the current config and every one published on `configs`
+pub fn follow_config(mut configs: watch::Receiver<BotConfig>) {
+    configure(&configs.borrow().performance);
+    tokio::spawn(async move {
+        while configs.changed().await.is_ok() {
+            let performance = configs.borrow_and_update().performance.clone();
+            configure(&performance);
+        }
+    });
+}
+
+/// Called on a capture thread as it starts
+pub(crate) fn tune_capture_thread() {
+    let settings = SETTINGS.read().unwrap().as_ref().map(|config| config.capture.clone());
+    tune(settings.unwrap_or_default(), "capture");
+}
+
+/// Called before each frame job, the blocking pool threads also run other work
+pub(crate) fn tune_processing_thread() {
+    let settings = SETTINGS.read().unwrap().as_ref().map(|config| config.processing.clone());
+    tune(settings.unwrap_or_default(), "processing");
+}
+
+fn tune(settings: ThreadSettings, kind: &'static str) {
+    APPLIED.with(|applied| {
+        let mut applied = applied.borrow_mut();
+        // Untouched threads already run with the defaults
+        if applied.as_ref().unwrap_or(&ThreadSettings::default()) == &settings {
+            return;
+        }
+        if let Err(e) = set_current_thread_priority(settings.priority) {
+            tracing::warn!(thread = kind, priority = ?settings.priority, error = %e, "Failed to set thread priority");
+        }
+        if let Err(e) = set_current_thread_affinity(&settings.cores) {
+            tracing::warn!(thread = kind, cores = ?settings.cores, error = %e, "Failed to set thread affinity");
+        }
+        *applied = Some(settings);
+    });
+}
@@ -0,0 +1,207 @@
+#[cfg(feature = "opencv")]
+use opencv::{
+    core::{in_range, Mat, Rect as CvRect, Scalar, CV_8UC4},
+    imgproc::{cvt_color_def, COLOR_BGR2HSV, COLOR_BGRA2BGR, COLOR_BGRA2GRAY},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "opencv")]
+use super::graphics_capture::CapturedFrame;
+
+/// Axis-aligned rectangle in frame pixel coordinates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    pub fn area(&self) -> i64 {
+        self.width.max(0) as i64 * self.height.max(0) as i64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+
+    /// Part of this rect inside a `width` x `height` frame, `None` if they don't overlap
+    pub fn clamp_to(&self, width: i32, height: i32) -> Option<Rect> {
+        let x = self.x.clamp(0, width);
+        let y = self.y.clamp(0, height);
+        let right = (self.x + self.width).clamp(0, width);
+        let bottom = (self.y + self.height).clamp(0, height);
+        let rect = Rect::new(x, y, right - x, bottom - y);
+        if rect.is_empty() {
+            None
+        } else {
+            Some(rect)
+        }
+    }
+}
+
+/// Bounding box images are scaled down to fit in, keeping their aspect ratio
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MaxSize {
+    pub width: i32,
+    pub height: i32,
+}
+
+impl MaxSize {
+    pub const fn new(width: i32, height: i32) -> Self {
+        Self { width, height }
+    }
+
+    /// Scale fitting a `width` x `height` image inside, 1.0 if it already fits
+    pub fn scale_for(&self, width: i32, height: i32) -> f64 {
+        if width <= 0 || height <= 0 || self.width <= 0 || self.height <= 0 {
+            return 1.0;
+        }
+        (self.width as f64 / width as f64)
+            .min(self.height as f64 / height as f64)
+            .min(1.0)
+    }
+}
+
+/// Inclusive HSV color range using OpenCV's scale (H 0 - 179, S and V 0 - 255)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HsvRange {
+    pub lower: [u8; 3],
+    pub upper: [u8; 3],
+}
+
+impl HsvRange {
+    pub const fn new(lower: [u8; 3], upper: [u8; 3]) -> Self {
+        Self { lower, upper }
+    }
+
+    /// Binary mask of the pixels of an HSV Mat within this range
+    #[cfg(feature = "opencv")]
+    pub fn mask(&self, hsv: &Mat) -> Result<Mat, String> {
+        let scalar = |[h, s, v]: [u8; 3]| Scalar::new(h as f64, s as f64, v as f64, 0.0);
+        let mut mask = Mat::default();
+        in_range(hsv, &scalar(self.lower), &scalar(self.upper), &mut mask)
+            .map_err(|e| format!("Failed to threshold HSV range: {}", e))?;
+        Ok(mask)
+    }
+}
+
+/// HSV of an RGB color on OpenCV's scale, what [`HsvRange`] bounds are written in
+pub fn rgb_to_hsv(rgb: [u8; 3]) -> [u8; 3] {
+    let [r, g, b] = rgb.map(|channel| channel as f32);
+    let max = r.max(g).max(b);
+    let delta = max - r.min(g).min(b);
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta)
+    } else if max == g {
+        60.0 * ((b - r) / delta) + 120.0
+    } else {
+        60.0 * ((r - g) / delta) + 240.0
+    };
+    let hue = if hue < 0.0 { hue + 360.0 } else { hue };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max * 255.0 };
+    [((hue / 2.0).round() as u8).min(179), saturation.round() as u8, max as u8]
+}
+
+#[cfg(feature = "opencv")]
+impl From<Rect> for CvRect {
+    fn from(rect: Rect) -> Self {
+        CvRect::new(rect.x, rect.y, rect.width, rect.height)
+    }
+}
+
+#[cfg(feature = "opencv")]
+impl From<CvRect> for Rect {
+    fn from(rect: CvRect) -> Self {
+        Rect::new(rect.x, rect.y, rect.width, rect.height)
+    }
+}
+
+#[cfg(feature = "opencv")]
+/// Copy a BGRA frame into an owned `CV_8UC4` Mat
+pub fn bgra_mat(frame: &CapturedFrame) -> Result<Mat, String> {
+    let rows = frame.height as i32;
+    let cols = frame.width as i32;
+    let size = frame.width as usize * frame.height as usize * 4;
+    if frame.data.len() < size {
+        return Err(format!("Frame data too small: {} < {}", frame.data.len(), size));
+    }
+
+    let mut mat = Mat::zeros(rows, cols, CV_8UC4)
+        .and_then(|mat| mat.to_mat())
+        .map_err(|e| format!("Failed to create Mat: {}", e))?;
+    mat.data_bytes_mut()
+        .map_err(|e| format!("Failed to access Mat data: {}", e))?
+        .copy_from_slice(&frame.data[..size]);
+
+    Ok(mat)
+}
+
+#[cfg(feature = "opencv")]
+/// Copy only the `rect` region of a BGRA frame into an owned `CV_8UC4` Mat
+///
+/// Cheaper than [`bgra_mat`] followed by [`crop`] since the rest of the frame is never copied.
+pub fn bgra_mat_region(frame: &CapturedFrame, rect: Rect) -> Result<Mat, String> {
+    let rect = rect
+        .clamp_to(frame.width as i32, frame.height as i32)
+        .ok_or_else(|| format!("Region {:?} is outside the {}x{} frame", rect, frame.width, frame.height))?;
+    let size = frame.width as usize * frame.height as usize * 4;
+    if frame.data.len() < size {
+        return Err(format!("Frame data too small: {} < {}", frame.data.len(), size));
+    }
+
+    let mut mat = Mat::zeros(rect.height, rect.width, CV_8UC4)
+        .and_then(|mat| mat.to_mat())
+        .map_err(|e| format!("Failed to create Mat: {}", e))?;
+    let data = mat
+        .data_bytes_mut()
+        .map_err(|e| format!("Failed to access Mat data: {}", e))?;
+
+    let stride = frame.width as usize * 4;
+    let row_len = rect.width as usize * 4;
+    for (row, dst) in data.chunks_exact_mut(row_len).enumerate() {
+        let start = (rect.y as usize + row) * stride + rect.x as usize * 4;
+        dst.copy_from_slice(&frame.data[start..start + row_len]);
+    }
+
+    Ok(mat)
+}
+
+#[cfg(feature = "opencv")]
+/// Copy the `rect` region out of `mat`
+pub fn crop(mat: &Mat, rect: Rect) -> Result<Mat, String> {
+    Mat::roi(mat, rect.into())
+        .and_then(|roi| roi.try_clone())
+        .map_err(|e| format!("Failed to crop {:?}: {}", rect, e))
+}
+
+#[cfg(feature = "opencv")]
+/// Convert a BGRA Mat to single channel grayscale
+pub fn to_gray(bgra: &Mat) -> Result<Mat, String> {
+    let mut gray = Mat::default();
+    cvt_color_def(bgra, &mut gray, COLOR_BGRA2GRAY)
+        .map_err(|e| format!("Failed to convert to grayscale: {}", e))?;
+    Ok(gray)
+}
+
+#[cfg(feature = "opencv")]
+/// Convert a BGRA Mat to HSV
+pub fn to_hsv(bgra: &Mat) -> Result<Mat, String> {
+    let mut bgr = Mat::default();
+    cvt_color_def(bgra, &mut bgr, COLOR_BGRA2BGR).map_err(|e| format!("Failed to convert to BGR: {}", e))?;
+    let mut hsv = Mat::default();
+    cvt_color_def(&bgr, &mut hsv, COLOR_BGR2HSV).map_err(|e| format!("Failed to convert to HSV: {}", e))?;
+    Ok(hsv)
+}
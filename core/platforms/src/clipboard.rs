@@ -0,0 +1,19 @@
+use crate::{Error, Result};
+
+/// Reads the clipboard as text, `None` if it holds no text (e.g. an image or nothing).
+pub fn get_text() -> Result<Option<String>> {
+    if cfg!(windows) {
+        return crate::windows::get_clipboard_text();
+    }
+
+    Err(Error::PlatformNotSupported)
+}
+
+/// Replaces the clipboard content with `text`.
+pub fn set_text(text: &str) -> Result<()> {
+    if cfg!(windows) {
+        return crate::windows::set_clipboard_text(text);
+    }
+
+    Err(Error::PlatformNotSupported)
+}
@@ -0,0 +1,18 @@
+use std::time::Duration;
+
+use crate::{Error, Result, Window};
+
+/// How long [`Window::focus`] waits for the window to come to the foreground.
+pub const DEFAULT_FOCUS_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Brings `window` to the foreground, waiting up to `timeout` for the focus change.
+///
+/// Returns whether the window is in the foreground afterwards. Unlike a plain
+/// `SetForegroundWindow`, this also works when another process currently owns the foreground.
+pub fn ensure_foreground(window: Window, timeout: Duration) -> Result<bool> {
+    if cfg!(windows) {
+        return crate::windows::ensure_foreground(window.windows, timeout);
+    }
+
+    Err(Error::PlatformNotSupported)
+}
@@ -1,21 +1,57 @@
+use std::{str::FromStr, time::Duration};
+
 use crate::{Error, Result, Window};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
 #[cfg(windows)]
 use crate::{windows::WindowsInput, windows::WindowsInputReceiver};
 
+mod rate_limit;
+
+pub use rate_limit::{RateLimit, rate_limit, set_rate_limit};
+
 #[derive(Debug, Clone, Copy)]
 pub enum MouseKind {
     Move,
-    Click,
-    Scroll,
+    Click(MouseButton),
+    Scroll(ScrollDelta),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MouseButton {
+    #[default]
+    Left,
+    Right,
+    Middle,
+}
+
+/// Number of wheel ticks to scroll, positive scrolls up (away from the user) and negative down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrollDelta(pub i32);
+
+impl ScrollDelta {
+    pub const fn up(ticks: u16) -> Self {
+        Self(ticks as i32)
+    }
+
+    pub const fn down(ticks: u16) -> Self {
+        Self(-(ticks as i32))
+    }
+
+    pub const fn ticks(self) -> i32 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum KeyState {
     Pressed,
     Released,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum KeyKind {
     A,
     B,
@@ -79,6 +115,7 @@ pub enum KeyKind {
     PageDown,
     Insert,
     Delete,
+    Pause,
     Ctrl,
     Enter,
     Space,
@@ -91,6 +128,326 @@ pub enum KeyKind {
     Esc,
     Shift,
     Alt,
+
+    Tab,
+    Backspace,
+    CapsLock,
+    Minus,
+    Equals,
+    LeftBracket,
+    RightBracket,
+    Backslash,
+    Win,
+
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    NumpadAdd,
+    NumpadSubtract,
+    NumpadMultiply,
+    NumpadDivide,
+    NumpadDecimal,
+
+    VolumeMute,
+    VolumeDown,
+    VolumeUp,
+    MediaPlayPause,
+    MediaStop,
+    MediaNext,
+    MediaPrevious,
+}
+
+impl FromStr for KeyKind {
+    type Err = Error;
+
+    /// Parses a key name as written in config files.
+    ///
+    /// Accepts the variant name in any case (`"numpad5"`, `"PageUp"`), the character a key
+    /// types (`"a"`, `"7"`, `"["`) and a few common aliases (`"escape"`, `"control"`, `"del"`).
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            let key = match c.to_ascii_uppercase() {
+                c @ 'A'..='Z' => KEY_LETTERS[(c as u8 - b'A') as usize],
+                c @ '0'..='9' => KEY_DIGITS[(c as u8 - b'0') as usize],
+                '`' | '~' => KeyKind::Tilde,
+                '\'' => KeyKind::Quote,
+                ';' => KeyKind::Semicolon,
+                ',' => KeyKind::Comma,
+                '.' => KeyKind::Period,
+                '/' => KeyKind::Slash,
+                '-' => KeyKind::Minus,
+                '=' => KeyKind::Equals,
+                '[' => KeyKind::LeftBracket,
+                ']' => KeyKind::RightBracket,
+                '\\' => KeyKind::Backslash,
+                ' ' => KeyKind::Space,
+                _ => return Err(Error::KeyNotFound),
+            };
+            return Ok(key);
+        }
+
+        let name = s
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '_' | '-'))
+            .collect::<String>()
+            .to_ascii_lowercase();
+        let key = match name.as_str() {
+            "zero" => KeyKind::Zero,
+            "one" => KeyKind::One,
+            "two" => KeyKind::Two,
+            "three" => KeyKind::Three,
+            "four" => KeyKind::Four,
+            "five" => KeyKind::Five,
+            "six" => KeyKind::Six,
+            "seven" => KeyKind::Seven,
+            "eight" => KeyKind::Eight,
+            "nine" => KeyKind::Nine,
+            "f1" => KeyKind::F1,
+            "f2" => KeyKind::F2,
+            "f3" => KeyKind::F3,
+            "f4" => KeyKind::F4,
+            "f5" => KeyKind::F5,
+            "f6" => KeyKind::F6,
+            "f7" => KeyKind::F7,
+            "f8" => KeyKind::F8,
+            "f9" => KeyKind::F9,
+            "f10" => KeyKind::F10,
+            "f11" => KeyKind::F11,
+            "f12" => KeyKind::F12,
+            "up" => KeyKind::Up,
+            "down" => KeyKind::Down,
+            "left" => KeyKind::Left,
+            "right" => KeyKind::Right,
+            "home" => KeyKind::Home,
+            "end" => KeyKind::End,
+            "pageup" | "pgup" => KeyKind::PageUp,
+            "pagedown" | "pgdn" => KeyKind::PageDown,
+            "insert" | "ins" => KeyKind::Insert,
+            "delete" | "del" => KeyKind::Delete,
+            "pause" | "break" => KeyKind::Pause,
+            "ctrl" | "control" => KeyKind::Ctrl,
+            "enter" | "return" => KeyKind::Enter,
+            "space" => KeyKind::Space,
+            "tilde" | "backquote" | "grave" => KeyKind::Tilde,
+            "quote" | "apostrophe" => KeyKind::Quote,
+            "semicolon" => KeyKind::Semicolon,
+            "comma" => KeyKind::Comma,
+            "period" | "dot" => KeyKind::Period,
+            "slash" => KeyKind::Slash,
+            "esc" | "escape" => KeyKind::Esc,
+            "shift" => KeyKind::Shift,
+            "alt" => KeyKind::Alt,
+            "tab" => KeyKind::Tab,
+            "backspace" | "back" => KeyKind::Backspace,
+            "capslock" | "caps" => KeyKind::CapsLock,
+            "minus" => KeyKind::Minus,
+            "equals" | "equal" => KeyKind::Equals,
+            "leftbracket" | "lbracket" => KeyKind::LeftBracket,
+            "rightbracket" | "rbracket" => KeyKind::RightBracket,
+            "backslash" => KeyKind::Backslash,
+            "win" | "windows" | "super" | "meta" => KeyKind::Win,
+            "numpad0" | "num0" => KeyKind::Numpad0,
+            "numpad1" | "num1" => KeyKind::Numpad1,
+            "numpad2" | "num2" => KeyKind::Numpad2,
+            "numpad3" | "num3" => KeyKind::Numpad3,
+            "numpad4" | "num4" => KeyKind::Numpad4,
+            "numpad5" | "num5" => KeyKind::Numpad5,
+            "numpad6" | "num6" => KeyKind::Numpad6,
+            "numpad7" | "num7" => KeyKind::Numpad7,
+            "numpad8" | "num8" => KeyKind::Numpad8,
+            "numpad9" | "num9" => KeyKind::Numpad9,
+            "numpadadd" | "numpadplus" => KeyKind::NumpadAdd,
+            "numpadsubtract" | "numpadminus" => KeyKind::NumpadSubtract,
+            "numpadmultiply" => KeyKind::NumpadMultiply,
+            "numpaddivide" => KeyKind::NumpadDivide,
+            "numpaddecimal" => KeyKind::NumpadDecimal,
+            "volumemute" | "mute" => KeyKind::VolumeMute,
+            "volumedown" => KeyKind::VolumeDown,
+            "volumeup" => KeyKind::VolumeUp,
+            "mediaplaypause" | "playpause" => KeyKind::MediaPlayPause,
+            "mediastop" => KeyKind::MediaStop,
+            "medianext" | "nexttrack" => KeyKind::MediaNext,
+            "mediaprevious" | "mediaprev" | "previoustrack" => KeyKind::MediaPrevious,
+            _ => return Err(Error::KeyNotFound),
+        };
+        Ok(key)
+    }
+}
+
+const KEY_LETTERS: [KeyKind; 26] = [
+    KeyKind::A,
+    KeyKind::B,
+    KeyKind::C,
+    KeyKind::D,
+    KeyKind::E,
+    KeyKind::F,
+    KeyKind::G,
+    KeyKind::H,
+    KeyKind::I,
+    KeyKind::J,
+    KeyKind::K,
+    KeyKind::L,
+    KeyKind::M,
+    KeyKind::N,
+    KeyKind::O,
+    KeyKind::P,
+    KeyKind::Q,
+    KeyKind::R,
+    KeyKind::S,
+    KeyKind::T,
+    KeyKind::U,
+    KeyKind::V,
+    KeyKind::W,
+    KeyKind::X,
+    KeyKind::Y,
+    KeyKind::Z,
+];
+
+const KEY_DIGITS: [KeyKind; 10] = [
+    KeyKind::Zero,
+    KeyKind::One,
+    KeyKind::Two,
+    KeyKind::Three,
+    KeyKind::Four,
+    KeyKind::Five,
+    KeyKind::Six,
+    KeyKind::Seven,
+    KeyKind::Eight,
+    KeyKind::Nine,
+];
+
+/// A real (not injected) user input event observed by the low-level hooks.
+///
+/// Mouse coordinates are in screen space, see [`Window::screen_to_client`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    Key { key: KeyKind, state: KeyState },
+    MouseMove { x: i32, y: i32 },
+    MouseButton { x: i32, y: i32, button: MouseButton, state: KeyState },
+    Scroll { x: i32, y: i32, delta: ScrollDelta },
+}
+
+/// How input is delivered to the [`Window`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputBackendKind {
+    /// Injects input through the OS input queue, requires the window to be in the foreground
+    /// as specified by [`InputKind`].
+    #[default]
+    SendInput,
+    /// Posts window messages (`WM_KEYDOWN`, `WM_LBUTTONDOWN`, ...) directly to the [`Window`].
+    ///
+    /// Works while the window is unfocused or covered, but [`InputKind`] is ignored and games
+    /// polling the keyboard state instead of handling messages won't see the input.
+    Message,
+    /// Injects input through the Interception driver, seen by the game as hardware input.
+    ///
+    /// Requires the driver and `interception.dll` to be installed, see
+    /// [`InputBackendKind::is_available`]. Unlike `SendInput`, these keys are not tagged as
+    /// injected so [`InputReceiver`] will also receive them.
+    #[cfg(feature = "interception")]
+    Interception,
+}
+
+/// How keys are identified in injected keyboard input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyInjectionMode {
+    /// Sends virtual-key codes along with their scan codes.
+    #[default]
+    VirtualKey,
+    /// Sends hardware scan codes only (`KEYEVENTF_SCANCODE`).
+    ///
+    /// Needed by games reading raw scan codes, which ignore virtual-key input. Keys without a
+    /// scan code, such as media keys, are still sent as virtual keys.
+    ScanCode,
+}
+
+impl InputBackendKind {
+    /// Whether this backend can be used on the current machine.
+    pub fn is_available(self) -> bool {
+        match self {
+            InputBackendKind::SendInput | InputBackendKind::Message => cfg!(windows),
+            #[cfg(feature = "interception")]
+            InputBackendKind::Interception => {
+                cfg!(windows) && crate::windows::interception_available()
+            }
+        }
+    }
+}
+
+/// Timing used by [`Input`] to make its actions look less machine-perfect.
+///
+/// Durations are written as milliseconds in config files. The default sends input without any
+/// humanization, see [`InputProfile::human`] for a preset.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputProfile {
+    /// How long a key or mouse button is held for a single press.
+    #[serde(with = "duration_millis")]
+    pub key_press_duration: Duration,
+    /// Minimum time between the start of two consecutive actions.
+    #[serde(with = "duration_millis")]
+    pub inter_action_delay: Duration,
+    /// Maximum random offset, in either direction, applied to every delay above.
+    #[serde(with = "duration_millis")]
+    pub jitter: Duration,
+    /// Cursor speed in pixels per second when moving between points, `0` teleports the cursor.
+    pub mouse_speed: f32,
+}
+
+impl InputProfile {
+    /// A preset roughly matching a player's timings.
+    pub const fn human() -> Self {
+        Self {
+            key_press_duration: Duration::from_millis(60),
+            inter_action_delay: Duration::from_millis(40),
+            jitter: Duration::from_millis(20),
+            mouse_speed: 2500.0,
+        }
+    }
+
+    /// Returns `duration` shifted by a random amount up to [`Self::jitter`].
+    pub fn jittered(&self, duration: Duration) -> Duration {
+        let jitter = self.jitter.as_secs_f64();
+        if jitter <= 0.0 {
+            return duration;
+        }
+        let offset = rand::thread_rng().gen_range(-jitter..=jitter);
+        Duration::from_secs_f64((duration.as_secs_f64() + offset).max(0.0))
+    }
+}
+
+impl Default for InputProfile {
+    fn default() -> Self {
+        Self {
+            key_press_duration: Duration::ZERO,
+            inter_action_delay: Duration::ZERO,
+            jitter: Duration::ZERO,
+            mouse_speed: 0.0,
+        }
+    }
+}
+
+mod duration_millis {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_millis() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
 }
 
 /// Kind of input to send.
@@ -112,9 +469,14 @@ pub struct Input {
 
 impl Input {
     pub fn new(window: Window, kind: InputKind) -> Result<Self> {
+        Self::with_backend(window, kind, InputBackendKind::default())
+    }
+
+    /// Creates an [`Input`] delivering input through `backend`.
+    pub fn with_backend(window: Window, kind: InputKind, backend: InputBackendKind) -> Result<Self> {
         if cfg!(windows) {
             return Ok(Self {
-                windows: WindowsInput::new(window.windows, kind),
+                windows: WindowsInput::new(window.windows, kind, backend),
             });
         }
 
@@ -123,6 +485,9 @@ impl Input {
 
     /// Sends mouse `kind` with coordinates `x`, `y` in relative to the provided [`Window`].
     pub fn send_mouse(&self, x: i32, y: i32, kind: MouseKind) -> Result<()> {
+        if !matches!(kind, MouseKind::Move) {
+            rate_limit::acquire(None)?;
+        }
         if cfg!(windows) {
             return self.windows.send_mouse(x, y, kind);
         }
@@ -141,6 +506,7 @@ impl Input {
 
     /// Sends a single key press `kind`.
     pub fn send_key(&self, kind: KeyKind) -> Result<()> {
+        rate_limit::acquire(Some(kind))?;
         if cfg!(windows) {
             return self.windows.send_key(kind);
         }
@@ -150,6 +516,7 @@ impl Input {
 
     /// Holds down key `kind`.
     pub fn send_key_down(&self, kind: KeyKind) -> Result<()> {
+        rate_limit::acquire(Some(kind))?;
         if cfg!(windows) {
             return self.windows.send_key_down(kind);
         }
@@ -165,6 +532,149 @@ impl Input {
 
         Err(Error::PlatformNotSupported)
     }
+
+    /// Holds key `kind` for `duration`, then releases it.
+    ///
+    /// A single key down is sent for the whole hold, repeated downs for a key that is already
+    /// held are suppressed so the hold is never interrupted. The key is also released if the
+    /// returned future is dropped before the hold completes.
+    pub async fn send_key_hold(&self, kind: KeyKind, duration: Duration) -> Result<()> {
+        self.send_key_down(kind)?;
+        let hold = KeyHold {
+            input: self,
+            kind,
+            released: false,
+        };
+        tokio::time::sleep(duration).await;
+        hold.release()
+    }
+
+    /// Sends a key combination such as `[Ctrl, Shift, F1]`.
+    ///
+    /// Keys are pressed in order and released in reverse, waiting the key combo delay between
+    /// each event. The focus check is done once up front and every pressed key is released even
+    /// if a later key fails.
+    pub fn send_key_combo(&self, keys: &[KeyKind]) -> Result<()> {
+        for key in keys {
+            rate_limit::acquire(Some(*key))?;
+        }
+        if cfg!(windows) {
+            return self.windows.send_key_combo(keys);
+        }
+
+        Err(Error::PlatformNotSupported)
+    }
+
+    /// Releases every key this [`Input`] currently holds down.
+    ///
+    /// Also done automatically when the [`Input`] is dropped.
+    pub fn release_all(&self) -> Result<()> {
+        if cfg!(windows) {
+            return self.windows.release_all();
+        }
+
+        Err(Error::PlatformNotSupported)
+    }
+
+    /// Sets the delay between key events of [`Self::send_key_combo`] (default 30ms).
+    pub fn set_key_combo_delay(&mut self, delay: Duration) {
+        if cfg!(windows) {
+            self.windows.set_key_combo_delay(delay);
+        }
+    }
+
+    /// Brings the window to the foreground before sending when [`InputKind::Focused`] input
+    /// would otherwise fail with [`Error::KeyNotSent`], waiting up to `timeout`.
+    ///
+    /// `None` (the default) disables it.
+    pub fn set_auto_focus(&mut self, timeout: Option<Duration>) {
+        if cfg!(windows) {
+            self.windows.set_auto_focus(timeout);
+        }
+    }
+
+    /// Sets how keys are identified when sending through [`InputBackendKind::SendInput`].
+    pub fn set_key_injection_mode(&mut self, mode: KeyInjectionMode) {
+        if cfg!(windows) {
+            self.windows.set_key_injection_mode(mode);
+        }
+    }
+
+    /// Returns the humanization profile applied to every action.
+    pub fn profile(&self) -> InputProfile {
+        if cfg!(windows) {
+            return self.windows.profile();
+        }
+
+        InputProfile::default()
+    }
+
+    /// Switches the humanization profile applied to every following action.
+    pub fn set_profile(&mut self, profile: InputProfile) {
+        if cfg!(windows) {
+            self.windows.set_profile(profile);
+        }
+    }
+}
+
+/// Subscribes to the emergency stop signal.
+///
+/// Fired by the kill switch hotkey (`Pause` by default) or [`trigger_kill_switch`]. Anything
+/// driving [`Input`] should stop queued actions and call [`Input::release_all`] when it fires.
+/// The hotkey requires [`crate::init`] to have been called.
+pub fn kill_switch_receiver() -> Result<broadcast::Receiver<()>> {
+    if cfg!(windows) {
+        return Ok(crate::windows::kill_switch_receiver());
+    }
+
+    Err(Error::PlatformNotSupported)
+}
+
+/// Subscribes to real user keyboard and mouse events, e.g. for recording macros.
+///
+/// Requires [`crate::init`] to have been called. Events sent by this library are not included.
+pub fn input_event_receiver() -> Result<broadcast::Receiver<InputEvent>> {
+    if cfg!(windows) {
+        return Ok(crate::windows::input_event_receiver());
+    }
+
+    Err(Error::PlatformNotSupported)
+}
+
+/// Fires the emergency stop signal as if the kill switch hotkey was pressed.
+pub fn trigger_kill_switch() {
+    if cfg!(windows) {
+        crate::windows::trigger_kill_switch();
+    }
+}
+
+/// Sets the kill switch hotkey, `None` disables it.
+pub fn set_kill_switch_key(key: Option<KeyKind>) {
+    if cfg!(windows) {
+        crate::windows::set_kill_switch_key(key);
+    }
+}
+
+/// Releases a held key when dropped, so a cancelled [`Input::send_key_hold`] can't leave it down.
+struct KeyHold<'a> {
+    input: &'a Input,
+    kind: KeyKind,
+    released: bool,
+}
+
+impl KeyHold<'_> {
+    fn release(mut self) -> Result<()> {
+        self.released = true;
+        self.input.send_key_up(self.kind)
+    }
+}
+
+impl Drop for KeyHold<'_> {
+    fn drop(&mut self) {
+        if !self.released {
+            let _ = self.input.send_key_up(self.kind);
+        }
+    }
 }
 
 #[derive(Debug)]
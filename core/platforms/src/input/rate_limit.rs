@@ -0,0 +1,161 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use super::KeyKind;
+use crate::{Error, Result};
+
+static RATE_LIMITER: LazyLock<Mutex<RateLimiter>> =
+    LazyLock::new(|| Mutex::new(RateLimiter::new(Some(RateLimit::default()))));
+
+/// Process-wide limits on how fast [`super::Input`] may send, shared by every instance.
+///
+/// Key presses, mouse clicks and scrolls count as actions. Key releases and mouse moves are
+/// never limited so held keys can always be let go. Actions over the limit fail with
+/// [`Error::RateLimited`] instead of being delayed, so a runaway loop can't build a backlog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimit {
+    /// Maximum actions across all keys and buttons within any one second.
+    pub max_actions_per_second: u32,
+    /// Minimum time between two presses of the same key.
+    #[serde(with = "super::duration_millis")]
+    pub min_key_interval: Duration,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            max_actions_per_second: 50,
+            min_key_interval: Duration::from_millis(15),
+        }
+    }
+}
+
+/// Returns the current global rate limit, `None` if disabled.
+pub fn rate_limit() -> Option<RateLimit> {
+    RATE_LIMITER.lock().limit
+}
+
+/// Replaces the global rate limit, `None` disables it.
+pub fn set_rate_limit(limit: Option<RateLimit>) {
+    *RATE_LIMITER.lock() = RateLimiter::new(limit);
+}
+
+/// Records a key press of `key` or a mouse action (`None`) if allowed by the rate limit.
+pub(super) fn acquire(key: Option<KeyKind>) -> Result<()> {
+    RATE_LIMITER.lock().acquire(key, Instant::now())
+}
+
+struct RateLimiter {
+    limit: Option<RateLimit>,
+    /// Start times of actions within the last second
+    actions: VecDeque<Instant>,
+    last_key_press: HashMap<KeyKind, Instant>,
+}
+
+impl RateLimiter {
+    fn new(limit: Option<RateLimit>) -> Self {
+        Self {
+            limit,
+            actions: VecDeque::new(),
+            last_key_press: HashMap::new(),
+        }
+    }
+
+    fn acquire(&mut self, key: Option<KeyKind>, now: Instant) -> Result<()> {
+        let Some(limit) = self.limit else {
+            return Ok(());
+        };
+
+        while self
+            .actions
+            .front()
+            .is_some_and(|action| now.duration_since(*action) >= Duration::from_secs(1))
+        {
+            self.actions.pop_front();
+        }
+        if self.actions.len() >= limit.max_actions_per_second as usize {
+            return Err(Error::RateLimited);
+        }
+        if let Some(key) = key
+            && self
+                .last_key_press
+                .get(&key)
+                .is_some_and(|last| now.duration_since(*last) < limit.min_key_interval)
+        {
+            return Err(Error::RateLimited);
+        }
+
+        self.actions.push_back(now);
+        if let Some(key) = key {
+            self.last_key_press.insert(key, now);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(max_actions_per_second: u32, min_key_interval_ms: u64) -> RateLimiter {
+        RateLimiter::new(Some(RateLimit {
+            max_actions_per_second,
+            min_key_interval: Duration::from_millis(min_key_interval_ms),
+        }))
+    }
+
+    #[test]
+    fn test_actions_per_second() {
+        let mut limiter = limiter(3, 0);
+        let start = Instant::now();
+        for i in 0..3 {
+            assert!(limiter.acquire(None, start + Duration::from_millis(i * 100)).is_ok());
+        }
+        assert!(matches!(limiter.acquire(None, start + Duration::from_millis(900)), Err(Error::RateLimited)));
+        // The first action leaves the window a second after it was sent
+        assert!(limiter.acquire(None, start + Duration::from_secs(1)).is_ok());
+        assert!(matches!(limiter.acquire(None, start + Duration::from_millis(1050)), Err(Error::RateLimited)));
+    }
+
+    #[test]
+    fn test_rejected_actions_dont_count() {
+        let mut limiter = limiter(1, 0);
+        let start = Instant::now();
+        assert!(limiter.acquire(None, start).is_ok());
+        for i in 1..10 {
+            assert!(limiter.acquire(None, start + Duration::from_millis(i * 50)).is_err());
+        }
+        assert!(limiter.acquire(None, start + Duration::from_secs(1)).is_ok());
+    }
+
+    #[test]
+    fn test_min_key_interval() {
+        let mut limiter = limiter(100, 15);
+        let start = Instant::now();
+        assert!(limiter.acquire(Some(KeyKind::A), start).is_ok());
+        assert!(matches!(
+            limiter.acquire(Some(KeyKind::A), start + Duration::from_millis(10)),
+            Err(Error::RateLimited)
+        ));
+        // Other keys and mouse actions aren't held back by it
+        assert!(limiter.acquire(Some(KeyKind::B), start + Duration::from_millis(10)).is_ok());
+        assert!(limiter.acquire(None, start + Duration::from_millis(10)).is_ok());
+        assert!(limiter.acquire(Some(KeyKind::A), start + Duration::from_millis(15)).is_ok());
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut limiter = RateLimiter::new(None);
+        let now = Instant::now();
+        for _ in 0..1000 {
+            assert!(limiter.acquire(Some(KeyKind::A), now).is_ok());
+        }
+    }
+}
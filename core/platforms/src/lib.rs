@@ -1,10 +1,19 @@
 use thiserror::Error;
 
 #[cfg(windows)]
-use crate::windows::{Handle, HandleKind, client_to_monitor_or_frame};
+use crate::windows::{
+    Handle, HandleKind, client_to_monitor_or_frame, exclude_process_handles_from_capture,
+    screen_to_client,
+};
 
 pub mod capture;
+pub mod clipboard;
+pub mod focus;
 pub mod input;
+#[cfg(feature = "mock-capture")]
+pub mod mock_capture;
+pub mod ocr;
+pub mod thread;
 pub mod windows_capture;
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -23,6 +32,10 @@ pub enum Error {
     KeyNotReceived,
     #[error("mouse was not sent due to the window not focused or other error")]
     MouseNotSent,
+    #[error("the selected input backend is not available")]
+    InputBackendNotAvailable,
+    #[error("input was not sent because the rate limit was exceeded")]
+    RateLimited,
 
     #[error("window not found")]
     WindowNotFound,
@@ -31,6 +44,14 @@ pub enum Error {
     #[error("window capture frame is not available")]
     WindowFrameNotAvailable,
 
+    #[error("no OCR language is installed for the user's languages")]
+    OcrNotAvailable,
+    #[error("the image is empty or too large for OCR")]
+    OcrInvalidImage,
+
+    #[error("none of the requested cores can be used by this process")]
+    InvalidThreadAffinity,
+
     #[error("platform is not supported")]
     PlatformNotSupported,
 
@@ -59,6 +80,21 @@ pub struct ConvertedCoordinates {
     pub x: i32,
     /// y coordinate in relative to the monitor or client area.
     pub y: i32,
+    /// DPI scale factor of the window's monitor (e.g. `1.25` at 125%).
+    ///
+    /// All other fields are physical pixels, divide by this to get logical pixels.
+    pub scale_factor: f32,
+}
+
+impl ConvertedCoordinates {
+    /// Returns the `(x, y)` coordinates in logical (96 DPI) pixels.
+    #[inline]
+    pub fn to_logical(&self) -> (i32, i32) {
+        (
+            (self.x as f32 / self.scale_factor).round() as i32,
+            (self.y as f32 / self.scale_factor).round() as i32,
+        )
+    }
 }
 
 /// A platform-specific handle to a window on screen.
@@ -79,6 +115,17 @@ impl Window {
         }
     }
 
+    /// The window with the raw handle `hwnd`, e.g. one picked from a list of windows.
+    #[cfg(windows)]
+    pub fn from_raw_hwnd(hwnd: isize) -> Self {
+        Self {
+            windows: Handle::from_raw_hwnd(hwnd),
+        }
+    }
+
+    /// Converts client coordinates of this window to coordinates relative to `relative`.
+    ///
+    /// Coordinates are physical pixels, as seen in captured frames, on any display scaling.
     #[inline]
     pub fn convert_coordinate(
         &self,
@@ -97,6 +144,37 @@ impl Window {
 
         Err(Error::PlatformNotSupported)
     }
+
+    /// Converts screen coordinates to client coordinates of this window.
+    #[inline]
+    pub fn screen_to_client(&self, x: i32, y: i32) -> Result<(i32, i32)> {
+        if cfg!(windows) {
+            return screen_to_client(self.windows, x, y);
+        }
+
+        Err(Error::PlatformNotSupported)
+    }
+
+    /// Brings this window to the foreground, returning whether it was focused.
+    ///
+    /// Waits up to [`focus::DEFAULT_FOCUS_TIMEOUT`], see [`focus::ensure_foreground`].
+    #[inline]
+    pub fn focus(&self) -> Result<bool> {
+        focus::ensure_foreground(*self, focus::DEFAULT_FOCUS_TIMEOUT)
+    }
+
+    /// Excludes or re-includes this window from screen capture.
+    ///
+    /// An excluded window is left out of BitBlt, WGC and DXGI frames. Only windows owned by this
+    /// process (e.g. overlays) can be excluded.
+    #[inline]
+    pub fn set_excluded_from_capture(&self, excluded: bool) -> Result<()> {
+        if cfg!(windows) {
+            return self.windows.set_excluded_from_capture(excluded);
+        }
+
+        Err(Error::PlatformNotSupported)
+    }
 }
 
 #[cfg(windows)]
@@ -106,6 +184,35 @@ impl From<Handle> for Window {
     }
 }
 
+/// An icon as RGBA pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Icon {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Returns the icon of the window with the raw handle `hwnd`, usually the one of its
+/// executable, `None` if it has none.
+pub fn window_icon(hwnd: isize) -> Option<Icon> {
+    if cfg!(windows) {
+        return windows::window_icon(hwnd);
+    }
+
+    None
+}
+
+/// Excludes all visible top-level windows of this process from screen capture.
+///
+/// Returns the number of windows excluded.
+pub fn exclude_own_windows_from_capture() -> Result<usize> {
+    if cfg!(windows) {
+        return Ok(exclude_process_handles_from_capture());
+    }
+
+    Err(Error::PlatformNotSupported)
+}
+
 pub fn init() {
     if cfg!(windows) {
         windows::init();
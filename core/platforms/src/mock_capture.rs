@@ -0,0 +1,321 @@
+// Offline capture backend replaying recorded gameplay
+// Frames come from an image sequence directory or a video file decoded by ffmpeg
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// File extensions picked up from an image sequence directory
+const IMAGE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+
+/// Longest wait before reopening a source that keeps coming up empty, e.g. a video ffmpeg
+/// can't decode
+const MAX_REOPEN_BACKOFF: Duration = Duration::from_secs(1);
+
+#[derive(thiserror::Error, Debug)]
+pub enum MockCaptureError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Failed to decode image {0}: {1}")]
+    ImageDecode(PathBuf, String),
+    #[error("No frames found in {0}")]
+    NoFrames(PathBuf),
+    #[error("Invalid frame rate: {0}")]
+    InvalidFps(f64),
+    #[error("ffmpeg failed: {0}")]
+    Ffmpeg(String),
+    #[error("Mock capture thread panicked")]
+    ThreadPanicked,
+}
+
+/// A decoded frame, always tightly packed BGRA like the other capture backends
+#[derive(Debug, Clone)]
+pub struct MockFrame {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Where the mock backend reads its frames from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockSource {
+    /// Directory of images played in file name order
+    ImageSequence(PathBuf),
+    /// Video file (e.g. MP4), decoded with `ffmpeg` which must be on `PATH`
+    Video(PathBuf),
+}
+
+impl MockSource {
+    /// Pick `ImageSequence` for directories and `Video` for anything else
+    pub fn from_path(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        if path.is_dir() {
+            MockSource::ImageSequence(path)
+        } else {
+            MockSource::Video(path)
+        }
+    }
+}
+
+/// Replays a [`MockSource`] at a fixed frame rate on a background thread
+#[derive(Debug, Clone)]
+pub struct MockCapture {
+    source: MockSource,
+    fps: f64,
+    looping: bool,
+}
+
+impl MockCapture {
+    pub fn new(source: MockSource, fps: f64) -> Self {
+        Self {
+            source,
+            fps,
+            looping: true,
+        }
+    }
+
+    /// Restart from the first frame when the source runs out (default: true)
+    pub fn looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Start replaying, calling `on_frame` for every frame at the configured rate
+    pub fn start<F>(self, on_frame: F) -> Result<MockCaptureControl, MockCaptureError>
+    where
+        F: FnMut(MockFrame) + Send + 'static,
+    {
+        if !self.fps.is_finite() || self.fps <= 0.0 {
+            return Err(MockCaptureError::InvalidFps(self.fps));
+        }
+
+        // Fail early on an empty or unreadable source instead of inside the thread
+        let mut reader = FrameReader::open(&self.source)?;
+
+        let halt = Arc::new(AtomicBool::new(false));
+        let thread_halt = halt.clone();
+        let thread = thread::Builder::new()
+            .name("mock-capture".to_string())
+            .spawn(move || self.run(&mut reader, &thread_halt, on_frame))?;
+
+        Ok(MockCaptureControl {
+            thread: Some(thread),
+            halt,
+        })
+    }
+
+    fn run<F>(&self, reader: &mut FrameReader, halt: &AtomicBool, mut on_frame: F) -> Result<(), MockCaptureError>
+    where
+        F: FnMut(MockFrame),
+    {
+        let interval = Duration::from_secs_f64(1.0 / self.fps);
+        let mut next_frame = Instant::now();
+        // Reopens in a row without a frame in between
+        let mut empty_reopens = 0u32;
+
+        while !halt.load(Ordering::Relaxed) {
+            let frame = match reader.next_frame()? {
+                Some(frame) => frame,
+                None if self.looping => {
+                    // Reopening right away again would spin on a source without frames
+                    if empty_reopens > 0 {
+                        thread::sleep(interval.saturating_mul(1 << empty_reopens.min(16)).min(MAX_REOPEN_BACKOFF));
+                    }
+                    empty_reopens += 1;
+                    *reader = FrameReader::open(&self.source)?;
+                    continue;
+                }
+                None => break,
+            };
+            empty_reopens = 0;
+
+            on_frame(frame);
+
+            next_frame += interval;
+            let now = Instant::now();
+            if next_frame > now {
+                thread::sleep(next_frame - now);
+            } else {
+                // Decoding fell behind, don't try to catch up with a burst
+                next_frame = now;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Used to control a running mock capture
+pub struct MockCaptureControl {
+    thread: Option<JoinHandle<Result<(), MockCaptureError>>>,
+    halt: Arc<AtomicBool>,
+}
+
+impl MockCaptureControl {
+    /// Whether the replay thread has finished, e.g. a non-looping source ran out
+    pub fn is_finished(&self) -> bool {
+        self.thread.as_ref().is_none_or(JoinHandle::is_finished)
+    }
+
+    /// Stop the replay and wait for the thread to exit
+    pub fn stop(mut self) -> Result<(), MockCaptureError> {
+        self.halt.store(true, Ordering::Relaxed);
+
+        match self.thread.take() {
+            Some(thread) => thread.join().map_err(|_| MockCaptureError::ThreadPanicked)?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for MockCaptureControl {
+    fn drop(&mut self) {
+        self.halt.store(true, Ordering::Relaxed);
+    }
+}
+
+enum FrameReader {
+    Images { paths: Vec<PathBuf>, next: usize },
+    Video(VideoReader),
+}
+
+impl FrameReader {
+    fn open(source: &MockSource) -> Result<Self, MockCaptureError> {
+        match source {
+            MockSource::ImageSequence(dir) => {
+                let mut paths = fs::read_dir(dir)?
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| {
+                        path.extension()
+                            .and_then(|ext| ext.to_str())
+                            .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+                    })
+                    .collect::<Vec<_>>();
+                if paths.is_empty() {
+                    return Err(MockCaptureError::NoFrames(dir.clone()));
+                }
+                paths.sort();
+
+                Ok(FrameReader::Images { paths, next: 0 })
+            }
+            MockSource::Video(path) => Ok(FrameReader::Video(VideoReader::open(path)?)),
+        }
+    }
+
+    fn next_frame(&mut self) -> Result<Option<MockFrame>, MockCaptureError> {
+        match self {
+            FrameReader::Images { paths, next } => {
+                let Some(path) = paths.get(*next) else {
+                    return Ok(None);
+                };
+                *next += 1;
+                load_image(path).map(Some)
+            }
+            FrameReader::Video(reader) => reader.next_frame(),
+        }
+    }
+}
+
+fn load_image(path: &Path) -> Result<MockFrame, MockCaptureError> {
+    let image = image::open(path)
+        .map_err(|e| MockCaptureError::ImageDecode(path.to_path_buf(), e.to_string()))?
+        .into_rgba8();
+    let (width, height) = image.dimensions();
+
+    let mut data = image.into_raw();
+    for pixel in data.chunks_exact_mut(4) {
+        pixel.swap(0, 2); // RGBA -> BGRA
+    }
+
+    Ok(MockFrame { data, width, height })
+}
+
+/// Streams raw BGRA frames out of an `ffmpeg` child process
+struct VideoReader {
+    child: Child,
+    stdout: ChildStdout,
+    width: u32,
+    height: u32,
+}
+
+impl VideoReader {
+    fn open(path: &Path) -> Result<Self, MockCaptureError> {
+        let (width, height) = probe_video_size(path)?;
+
+        let mut child = Command::new("ffmpeg")
+            .args(["-loglevel", "error", "-i"])
+            .arg(path)
+            .args(["-f", "rawvideo", "-pix_fmt", "bgra", "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| MockCaptureError::Ffmpeg(format!("failed to spawn ffmpeg: {}", e)))?;
+        let stdout = child.stdout.take().ok_or_else(|| MockCaptureError::Ffmpeg("no stdout".to_string()))?;
+
+        Ok(Self {
+            child,
+            stdout,
+            width,
+            height,
+        })
+    }
+
+    fn next_frame(&mut self) -> Result<Option<MockFrame>, MockCaptureError> {
+        let mut data = vec![0u8; self.width as usize * self.height as usize * 4];
+        match self.stdout.read_exact(&mut data) {
+            Ok(()) => Ok(Some(MockFrame {
+                data,
+                width: self.width,
+                height: self.height,
+            })),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for VideoReader {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn probe_video_size(path: &Path) -> Result<(u32, u32), MockCaptureError> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-show_entries",
+            "stream=width,height",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(path)
+        .output()
+        .map_err(|e| MockCaptureError::Ffmpeg(format!("failed to spawn ffprobe: {}", e)))?;
+    if !output.status.success() {
+        return Err(MockCaptureError::Ffmpeg(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (width, height) = stdout
+        .trim()
+        .split_once(',')
+        .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)))
+        .ok_or_else(|| MockCaptureError::Ffmpeg(format!("unexpected ffprobe output: {}", stdout.trim())))?;
+
+    if width == 0 || height == 0 {
+        return Err(MockCaptureError::NoFrames(path.to_path_buf()));
+    }
+
+    Ok((width, height))
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+/// A word recognized by [`recognize_text`], bounds are in pixels of the recognized image.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OcrWord {
+    pub text: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// A line of text recognized by [`recognize_text`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OcrLine {
+    pub text: String,
+    pub words: Vec<OcrWord>,
+}
+
+/// Recognizes text in a `width` x `height` BGRA image using the OS OCR engine.
+///
+/// Uses the first of the user's profile languages that has an OCR language pack installed.
+/// The engine does not report confidence. Small text is recognized much more reliably after
+/// upscaling the image 2 - 3 times.
+pub fn recognize_text(bgra: &[u8], width: u32, height: u32) -> Result<Vec<OcrLine>> {
+    if cfg!(windows) {
+        return crate::windows::recognize_text(bgra, width, height);
+    }
+
+    Err(Error::PlatformNotSupported)
+}
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+/// Scheduling priority of a thread, relative to the priority class of its process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreadPriority {
+    Lowest,
+    BelowNormal,
+    #[default]
+    Normal,
+    AboveNormal,
+    Highest,
+    /// Preempts nearly everything else, including the game, use with care.
+    TimeCritical,
+}
+
+/// Sets the scheduling priority of the calling thread.
+pub fn set_current_thread_priority(priority: ThreadPriority) -> Result<()> {
+    if cfg!(windows) {
+        return crate::windows::set_current_thread_priority(priority);
+    }
+
+    Err(Error::PlatformNotSupported)
+}
+
+/// Restricts the calling thread to the logical processors `cores` (0 based).
+///
+/// An empty `cores` lets the thread run on every processor of the process again. Cores the
+/// process may not use are ignored, [`Error::InvalidThreadAffinity`] is returned if none is left.
+pub fn set_current_thread_affinity(cores: &[usize]) -> Result<()> {
+    if cfg!(windows) {
+        return crate::windows::set_current_thread_affinity(cores);
+    }
+
+    Err(Error::PlatformNotSupported)
+}
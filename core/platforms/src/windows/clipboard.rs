@@ -0,0 +1,94 @@
+use std::{ptr, slice, thread, time::Duration};
+
+use windows::Win32::{
+    Foundation::{HANDLE, HGLOBAL},
+    System::{
+        DataExchange::{
+            CloseClipboard, EmptyClipboard, GetClipboardData, IsClipboardFormatAvailable,
+            OpenClipboard, SetClipboardData,
+        },
+        Memory::{GMEM_MOVEABLE, GlobalAlloc, GlobalFree, GlobalLock, GlobalSize, GlobalUnlock},
+        Ole::CF_UNICODETEXT,
+    },
+};
+
+use crate::{Error, Result};
+
+/// Another process (e.g. a clipboard manager) may briefly hold the clipboard open.
+const OPEN_ATTEMPTS: u32 = 10;
+const OPEN_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// Closes the clipboard when dropped.
+struct ClipboardGuard;
+
+impl ClipboardGuard {
+    fn open() -> Result<Self> {
+        let mut attempt = 1;
+        loop {
+            match unsafe { OpenClipboard(None) } {
+                Ok(()) => return Ok(Self),
+                Err(err) if attempt >= OPEN_ATTEMPTS => return Err(err.into()),
+                Err(_) => {
+                    attempt += 1;
+                    thread::sleep(OPEN_RETRY_DELAY);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ClipboardGuard {
+    fn drop(&mut self) {
+        let _ = unsafe { CloseClipboard() };
+    }
+}
+
+pub fn get_clipboard_text() -> Result<Option<String>> {
+    let format = CF_UNICODETEXT.0 as u32;
+    if unsafe { IsClipboardFormatAvailable(format) }.is_err() {
+        return Ok(None);
+    }
+
+    let _guard = ClipboardGuard::open()?;
+    let data = HGLOBAL(unsafe { GetClipboardData(format) }?.0);
+    let text = unsafe { GlobalLock(data) } as *const u16;
+    if text.is_null() {
+        return Err(Error::from_last_win_error());
+    }
+
+    // The data is null terminated but may be followed by garbage up to the allocation size
+    let len = unsafe { GlobalSize(data) } / size_of::<u16>();
+    let text = unsafe { slice::from_raw_parts(text, len) };
+    let text = text.split(|&c| c == 0).next().unwrap_or_default();
+    let text = String::from_utf16_lossy(text);
+    let _ = unsafe { GlobalUnlock(data) };
+
+    Ok(Some(text))
+}
+
+pub fn set_clipboard_text(text: &str) -> Result<()> {
+    let text = text.encode_utf16().chain([0]).collect::<Vec<u16>>();
+
+    let _guard = ClipboardGuard::open()?;
+    unsafe { EmptyClipboard() }?;
+
+    let data = unsafe { GlobalAlloc(GMEM_MOVEABLE, text.len() * size_of::<u16>()) }?;
+    let buffer = unsafe { GlobalLock(data) } as *mut u16;
+    if buffer.is_null() {
+        let error = Error::from_last_win_error();
+        let _ = unsafe { GlobalFree(Some(data)) };
+        return Err(error);
+    }
+    unsafe {
+        ptr::copy_nonoverlapping(text.as_ptr(), buffer, text.len());
+        let _ = GlobalUnlock(data);
+    }
+
+    // The clipboard owns the memory once set, it must only be freed on failure
+    if let Err(err) = unsafe { SetClipboardData(CF_UNICODETEXT.0 as u32, Some(HANDLE(data.0))) } {
+        let _ = unsafe { GlobalFree(Some(data)) };
+        return Err(err.into());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,130 @@
+//! Bringing a window to the foreground.
+//!
+//! Windows only lets the process that received the last input event take the foreground, a
+//! plain `SetForegroundWindow` from a background process just flashes the taskbar button. The
+//! usual workarounds are tried in order until the window is focused.
+
+use std::{
+    mem::size_of,
+    thread,
+    time::{Duration, Instant},
+};
+
+use windows::Win32::{
+    Foundation::HWND,
+    System::Threading::{AttachThreadInput, GetCurrentThreadId},
+    UI::{
+        Input::KeyboardAndMouse::{
+            INPUT, INPUT_0, INPUT_KEYBOARD, KEYBD_EVENT_FLAGS, KEYBDINPUT, KEYEVENTF_KEYUP,
+            SendInput, VK_MENU,
+        },
+        WindowsAndMessaging::{
+            BringWindowToTop, GetForegroundWindow, GetWindowThreadProcessId, IsIconic, SW_RESTORE,
+            SetForegroundWindow, ShowWindow,
+        },
+    },
+};
+
+use super::Handle;
+use crate::{Error, Result};
+
+/// How long each attempt waits for the focus change before trying the next one.
+const ATTEMPT_TIMEOUT: Duration = Duration::from_millis(50);
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+pub fn ensure_foreground(handle: Handle, timeout: Duration) -> Result<bool> {
+    let handle = handle.as_inner().ok_or(Error::WindowNotFound)?;
+    Ok(focus_window(handle, timeout))
+}
+
+/// Tries to make `handle` the foreground window, returning whether it is within `timeout`.
+pub(crate) fn focus_window(handle: HWND, timeout: Duration) -> bool {
+    if unsafe { GetForegroundWindow() } == handle {
+        return true;
+    }
+
+    let deadline = Instant::now() + timeout;
+    if unsafe { IsIconic(handle) }.as_bool() {
+        let _ = unsafe { ShowWindow(handle, SW_RESTORE) };
+    }
+
+    let attempts: [fn(HWND); 3] = [
+        set_foreground,
+        set_foreground_attached,
+        set_foreground_after_alt,
+    ];
+    for attempt in attempts {
+        attempt(handle);
+        if wait_foreground(handle, deadline.min(Instant::now() + ATTEMPT_TIMEOUT)) {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+    }
+
+    wait_foreground(handle, deadline)
+}
+
+fn wait_foreground(handle: HWND, deadline: Instant) -> bool {
+    loop {
+        if unsafe { GetForegroundWindow() } == handle {
+            return true;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL.min(deadline - now));
+    }
+}
+
+fn set_foreground(handle: HWND) {
+    unsafe {
+        let _ = SetForegroundWindow(handle);
+    }
+}
+
+/// Shares the input state of the current foreground thread, which is allowed to change focus.
+fn set_foreground_attached(handle: HWND) {
+    unsafe {
+        let foreground_thread = GetWindowThreadProcessId(GetForegroundWindow(), None);
+        let current_thread = GetCurrentThreadId();
+        let attached = foreground_thread != 0
+            && foreground_thread != current_thread
+            && AttachThreadInput(current_thread, foreground_thread, true).as_bool();
+
+        let _ = BringWindowToTop(handle);
+        let _ = SetForegroundWindow(handle);
+
+        if attached {
+            let _ = AttachThreadInput(current_thread, foreground_thread, false);
+        }
+    }
+}
+
+/// Taps `Alt` so this process received the last input event and may set the foreground.
+fn set_foreground_after_alt(handle: HWND) {
+    #[inline]
+    fn alt_input(flags: KEYBD_EVENT_FLAGS) -> INPUT {
+        INPUT {
+            r#type: INPUT_KEYBOARD,
+            Anonymous: INPUT_0 {
+                ki: KEYBDINPUT {
+                    wVk: VK_MENU,
+                    dwFlags: flags,
+                    ..KEYBDINPUT::default()
+                },
+            },
+        }
+    }
+
+    let inputs = [
+        alt_input(KEYBD_EVENT_FLAGS::default()),
+        alt_input(KEYEVENTF_KEYUP),
+    ];
+    unsafe {
+        SendInput(&inputs, size_of::<INPUT>() as i32);
+        let _ = SetForegroundWindow(handle);
+    }
+}
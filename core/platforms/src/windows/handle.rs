@@ -3,15 +3,26 @@ use std::{cell::Cell, ffi::OsString, os::windows::ffi::OsStringExt, ptr, str};
 use windows::{
     Win32::{
         Foundation::{HWND, LPARAM},
-        Graphics::Dwm::{DWMWA_CLOAKED, DwmGetWindowAttribute},
+        Graphics::{
+            Dwm::{DWMWA_CLOAKED, DwmGetWindowAttribute},
+            Gdi::{
+                BI_RGB, BITMAP, BITMAPINFO, BITMAPINFOHEADER, DIB_RGB_COLORS, DeleteObject, GetDC,
+                GetDIBits, GetObjectW, ReleaseDC,
+            },
+        },
+        System::Threading::GetCurrentProcessId,
         UI::WindowsAndMessaging::{
-            EnumWindows, GWL_EXSTYLE, GWL_STYLE, GetClassNameW, GetWindowLongPtrW, GetWindowTextW,
-            IsWindowVisible, WS_DISABLED, WS_EX_TOOLWINDOW,
+            EnumWindows, GCLP_HICON, GCLP_HICONSM, GWL_EXSTYLE, GWL_STYLE, GetClassLongPtrW,
+            GetClassNameW, GetIconInfo, GetWindowLongPtrW, GetWindowTextW, GetWindowThreadProcessId,
+            HICON, ICONINFO, IsWindowVisible, SetWindowDisplayAffinity, WDA_EXCLUDEFROMCAPTURE,
+            WDA_NONE, WS_DISABLED, WS_EX_TOOLWINDOW,
         },
     },
     core::BOOL,
 };
 
+use crate::{Error, Icon, Result};
+
 #[derive(Clone, Debug)]
 pub struct HandleCell {
     inner: Handle,
@@ -63,12 +74,133 @@ impl Handle {
         Self { kind }
     }
 
+    /// A handle fixed to the window with the raw handle `hwnd`.
+    pub fn from_raw_hwnd(hwnd: isize) -> Self {
+        Self::new(HandleKind::Fixed(HWND(hwnd as *mut _)))
+    }
+
     pub fn as_inner(&self) -> Option<HWND> {
         match self.kind {
             HandleKind::Fixed(handle) => Some(handle),
             HandleKind::Dynamic(class) => query_handle(class),
         }
     }
+
+    /// Excludes or re-includes this window from screen capture.
+    ///
+    /// Only works for top-level windows owned by the current process.
+    pub fn set_excluded_from_capture(&self, excluded: bool) -> Result<()> {
+        let handle = self.as_inner().ok_or(Error::WindowNotFound)?;
+        set_excluded_from_capture(handle, excluded)
+    }
+}
+
+#[inline]
+pub fn set_excluded_from_capture(handle: HWND, excluded: bool) -> Result<()> {
+    let affinity = if excluded {
+        WDA_EXCLUDEFROMCAPTURE
+    } else {
+        WDA_NONE
+    };
+    unsafe { SetWindowDisplayAffinity(handle, affinity)? };
+    Ok(())
+}
+
+/// Excludes every visible top-level window of the current process from screen capture.
+///
+/// Returns the number of windows that were marked.
+pub fn exclude_process_handles_from_capture() -> usize {
+    unsafe extern "system" fn callback(handle: HWND, params: LPARAM) -> BOOL {
+        if !unsafe { IsWindowVisible(handle) }.as_bool() {
+            return true.into();
+        }
+
+        let mut pid = 0;
+        unsafe { GetWindowThreadProcessId(handle, Some(&raw mut pid)) };
+        if pid == unsafe { GetCurrentProcessId() }
+            && set_excluded_from_capture(handle, true).is_ok()
+        {
+            let count = unsafe { &mut *(params.0 as *mut usize) };
+            *count += 1;
+        }
+        true.into()
+    }
+
+    let mut count = 0usize;
+    let _ = unsafe { EnumWindows(Some(callback), LPARAM(&raw mut count as isize)) };
+    count
+}
+
+/// Returns the icon of the window's class, usually the one of its executable.
+pub fn window_icon(hwnd: isize) -> Option<Icon> {
+    let handle = HWND(hwnd as *mut _);
+    let icon = [GCLP_HICONSM, GCLP_HICON]
+        .into_iter()
+        .map(|index| unsafe { GetClassLongPtrW(handle, index) })
+        .find(|icon| *icon != 0)?;
+    icon_to_rgba(HICON(icon as *mut _))
+}
+
+fn icon_to_rgba(icon: HICON) -> Option<Icon> {
+    let mut info = ICONINFO::default();
+    unsafe { GetIconInfo(icon, &raw mut info) }.ok()?;
+
+    // Monochrome icons only have a mask
+    let pixels = (!info.hbmColor.is_invalid())
+        .then(|| {
+            let mut bitmap = BITMAP::default();
+            let size = std::mem::size_of::<BITMAP>() as i32;
+            if unsafe { GetObjectW(info.hbmColor.into(), size, Some((&raw mut bitmap).cast())) } == 0 {
+                return None;
+            }
+            let (width, height) = (bitmap.bmWidth, bitmap.bmHeight);
+            let mut header = BITMAPINFO {
+                bmiHeader: BITMAPINFOHEADER {
+                    biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                    biWidth: width,
+                    // Top-down rows
+                    biHeight: -height,
+                    biPlanes: 1,
+                    biBitCount: 32,
+                    biCompression: BI_RGB.0,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let mut pixels = vec![0u8; width as usize * height as usize * 4];
+            let dc = unsafe { GetDC(None) };
+            let lines = unsafe {
+                GetDIBits(
+                    dc,
+                    info.hbmColor,
+                    0,
+                    height as u32,
+                    Some(pixels.as_mut_ptr().cast()),
+                    &raw mut header,
+                    DIB_RGB_COLORS,
+                )
+            };
+            unsafe { ReleaseDC(None, dc) };
+            (lines > 0).then_some((width as u32, height as u32, pixels))
+        })
+        .flatten();
+
+    // GetIconInfo hands out copies of the bitmaps
+    unsafe {
+        let _ = DeleteObject(info.hbmColor.into());
+        let _ = DeleteObject(info.hbmMask.into());
+    }
+
+    let (width, height, mut rgba) = pixels?;
+    // Icons without an alpha channel leave it at zero
+    let opaque = rgba.chunks_exact(4).all(|pixel| pixel[3] == 0);
+    for pixel in rgba.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+        if opaque {
+            pixel[3] = 255;
+        }
+    }
+    Some(Icon { width, height, rgba })
 }
 
 pub fn query_capture_name_handle_pairs() -> Vec<(String, Handle)> {
@@ -159,7 +291,7 @@ fn is_class_matched(handle: HWND, class: &'static str) -> bool {
         .to_string_lossy()
         .into_owned();
 
-    println!("Class name for handle {:?} is {}", handle, class_name_string);
+    tracing::trace!(?handle, class_name = %class_name_string, "Matching window class");
 
     class_name_string.starts_with(class)
 }
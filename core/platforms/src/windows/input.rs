@@ -1,9 +1,12 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     mem::{self, size_of},
-    sync::LazyLock,
+    sync::{
+        LazyLock,
+        atomic::{AtomicU16, Ordering},
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use bit_vec::BitVec;
@@ -12,44 +15,75 @@ use windows::{
     Win32::{
         Foundation::{HWND, LPARAM, LRESULT, POINT, RECT, WPARAM},
         Graphics::Gdi::{
-            ClientToScreen, GetMonitorInfoW, IntersectRect, MONITOR_DEFAULTTONULL, MONITORINFO,
+            ClientToScreen, GetMonitorInfoW, ScreenToClient, IntersectRect, MONITOR_DEFAULTTONULL, MONITORINFO,
             MonitorFromWindow,
         },
         System::Threading::GetCurrentProcessId,
         UI::{
+            HiDpi::{
+                DPI_AWARENESS_CONTEXT, DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+                GetDpiForWindow, SetThreadDpiAwarenessContext,
+            },
             Input::KeyboardAndMouse::{
                 GetAsyncKeyState, INPUT, INPUT_0, INPUT_KEYBOARD, INPUT_MOUSE, KEYBD_EVENT_FLAGS,
-                KEYBDINPUT, KEYEVENTF_EXTENDEDKEY, KEYEVENTF_KEYUP, MAPVK_VK_TO_VSC_EX,
+                KEYBDINPUT, KEYEVENTF_EXTENDEDKEY, KEYEVENTF_KEYUP, KEYEVENTF_SCANCODE,
+                MAPVK_VK_TO_VSC_EX,
                 MOUSE_EVENT_FLAGS, MOUSEEVENTF_ABSOLUTE, MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP,
-                MOUSEEVENTF_MOVE, MOUSEEVENTF_VIRTUALDESK, MOUSEEVENTF_WHEEL, MOUSEINPUT,
+                MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP, MOUSEEVENTF_MOVE,
+                MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP, MOUSEEVENTF_VIRTUALDESK,
+                MOUSEEVENTF_WHEEL, MOUSEINPUT,
                 MapVirtualKeyW, SendInput, VIRTUAL_KEY, VK_0, VK_1, VK_2, VK_3, VK_4, VK_5, VK_6,
-                VK_7, VK_8, VK_9, VK_A, VK_B, VK_C, VK_CONTROL, VK_D, VK_DELETE, VK_DOWN, VK_E,
-                VK_END, VK_ESCAPE, VK_F, VK_F1, VK_F2, VK_F3, VK_F4, VK_F5, VK_F6, VK_F7, VK_F8,
-                VK_F9, VK_F10, VK_F11, VK_F12, VK_G, VK_H, VK_HOME, VK_I, VK_INSERT, VK_J, VK_K,
-                VK_L, VK_LEFT, VK_M, VK_MENU, VK_N, VK_NEXT, VK_O, VK_OEM_1, VK_OEM_2, VK_OEM_3,
-                VK_OEM_7, VK_OEM_COMMA, VK_OEM_PERIOD, VK_P, VK_PRIOR, VK_Q, VK_R, VK_RETURN,
-                VK_RIGHT, VK_S, VK_SHIFT, VK_SPACE, VK_T, VK_U, VK_UP, VK_V, VK_W, VK_X, VK_Y,
-                VK_Z,
+                VK_7, VK_8, VK_9, VK_A, VK_ADD, VK_B, VK_BACK, VK_C, VK_CAPITAL, VK_CONTROL, VK_D,
+                VK_DECIMAL, VK_DELETE, VK_DIVIDE, VK_DOWN, VK_E, VK_END, VK_ESCAPE, VK_F, VK_F1,
+                VK_F2, VK_F3, VK_F4, VK_F5, VK_F6, VK_F7, VK_F8, VK_F9, VK_F10, VK_F11, VK_F12,
+                VK_G, VK_H, VK_HOME, VK_I, VK_INSERT, VK_J, VK_K, VK_L, VK_LEFT, VK_LWIN, VK_M,
+                VK_MEDIA_NEXT_TRACK, VK_MEDIA_PLAY_PAUSE, VK_MEDIA_PREV_TRACK, VK_MEDIA_STOP,
+                VK_MENU, VK_MULTIPLY, VK_N, VK_NEXT, VK_NUMPAD0, VK_NUMPAD1, VK_NUMPAD2, VK_NUMPAD3,
+                VK_NUMPAD4, VK_NUMPAD5, VK_NUMPAD6, VK_NUMPAD7, VK_NUMPAD8, VK_NUMPAD9, VK_O,
+                VK_OEM_1, VK_OEM_2, VK_OEM_3, VK_OEM_4, VK_OEM_5, VK_OEM_6, VK_OEM_7, VK_OEM_COMMA,
+                VK_OEM_MINUS, VK_OEM_PERIOD, VK_OEM_PLUS, VK_P, VK_PAUSE, VK_PRIOR, VK_Q, VK_R,
+                VK_RETURN, VK_RIGHT, VK_S, VK_SHIFT, VK_SPACE, VK_SUBTRACT, VK_T, VK_TAB, VK_U,
+                VK_UP, VK_V, VK_VOLUME_DOWN, VK_VOLUME_MUTE, VK_VOLUME_UP, VK_W, VK_X, VK_Y, VK_Z,
             },
             WindowsAndMessaging::{
-                CallNextHookEx, GetForegroundWindow, GetSystemMetrics, GetWindowRect,
+                CallNextHookEx, LLMHF_INJECTED, MSLLHOOKSTRUCT, WH_MOUSE_LL, WHEEL_DELTA, GetForegroundWindow, GetSystemMetrics, GetWindowRect,
                 GetWindowThreadProcessId, HC_ACTION, HHOOK, KBDLLHOOKSTRUCT, LLKHF_INJECTED,
                 LLKHF_LOWER_IL_INJECTED, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN,
                 SM_YVIRTUALSCREEN, SetWindowsHookExW, WH_KEYBOARD_LL, WM_KEYDOWN, WM_KEYUP,
+                PostMessageW, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN, WM_MBUTTONUP,
+                WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SYSKEYDOWN,
+                WM_SYSKEYUP,
             },
         },
     },
     core::Owned,
 };
 
-use super::{HandleCell, handle::Handle};
+#[cfg(feature = "interception")]
+use super::interception;
+use super::{HandleCell, focus::focus_window, handle::Handle};
 use crate::{
     ConvertedCoordinates, Error, Result,
-    input::{InputKind, KeyKind, KeyState, MouseKind},
+    input::{
+        InputBackendKind, InputEvent, InputKind, InputProfile, KeyInjectionMode, KeyKind,
+        KeyState, MouseButton, MouseKind, ScrollDelta,
+    },
 };
 
+/// Shortest time a mouse button is held for a click, double-clicks are missed without it.
+const MOUSE_CLICK_HOLD: Duration = Duration::from_millis(80);
+/// Time between intermediate cursor moves when gliding at [`InputProfile::mouse_speed`].
+const MOUSE_GLIDE_STEP: Duration = Duration::from_millis(10);
+/// Longest a single glide may take regardless of distance.
+const MOUSE_GLIDE_MAX: Duration = Duration::from_secs(1);
+
 static KEY_CHANNEL: LazyLock<Sender<KeyKind>> = LazyLock::new(|| broadcast::channel(1).0);
 static PROCESS_ID: LazyLock<u32> = LazyLock::new(|| unsafe { GetCurrentProcessId() });
+static INPUT_EVENT_CHANNEL: LazyLock<Sender<InputEvent>> =
+    LazyLock::new(|| broadcast::channel(256).0);
+static KILL_SWITCH: LazyLock<Sender<()>> = LazyLock::new(|| broadcast::channel(1).0);
+/// Virtual key of the kill switch hotkey, 0 when disabled.
+static KILL_SWITCH_KEY: AtomicU16 = AtomicU16::new(VK_PAUSE.0);
 
 pub fn init() -> Owned<HHOOK> {
     unsafe extern "system" fn keyboard_ll(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
@@ -60,6 +94,23 @@ pub fn init() -> Owned<HHOOK> {
             let vkey = unsafe { mem::transmute::<u16, VIRTUAL_KEY>(key.vkCode as u16) };
             let key_kind = KeyKind::try_from(vkey);
             let ignore = key.dwExtraInfo == *PROCESS_ID as usize;
+            if !ignore
+                && INPUT_EVENT_CHANNEL.receiver_count() > 0
+                && let Ok(key) = key_kind
+            {
+                let state = if msg == WM_KEYDOWN {
+                    KeyState::Pressed
+                } else {
+                    KeyState::Released
+                };
+                let _ = INPUT_EVENT_CHANNEL.send(InputEvent::Key { key, state });
+            }
+            if !ignore
+                && msg == WM_KEYDOWN
+                && key.vkCode == KILL_SWITCH_KEY.load(Ordering::Relaxed) as u32
+            {
+                let _ = KILL_SWITCH.send(());
+            }
             if !ignore
                 && msg == WM_KEYUP
                 && let Ok(key) = key_kind
@@ -79,6 +130,66 @@ pub fn init() -> Owned<HHOOK> {
     unsafe { Owned::new(SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_ll), None, 0).unwrap()) }
 }
 
+pub fn init_mouse() -> Owned<HHOOK> {
+    unsafe extern "system" fn mouse_ll(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        if code as u32 == HC_ACTION && INPUT_EVENT_CHANNEL.receiver_count() > 0 {
+            let info = unsafe { (lparam.0 as *const MSLLHOOKSTRUCT).read() };
+            let (x, y) = (info.pt.x, info.pt.y);
+            let button = |button, state| InputEvent::MouseButton {
+                x,
+                y,
+                button,
+                state,
+            };
+            let event = match wparam.0 as u32 {
+                _ if info.flags & LLMHF_INJECTED != 0 => None,
+                WM_MOUSEMOVE => Some(InputEvent::MouseMove { x, y }),
+                WM_LBUTTONDOWN => Some(button(MouseButton::Left, KeyState::Pressed)),
+                WM_LBUTTONUP => Some(button(MouseButton::Left, KeyState::Released)),
+                WM_RBUTTONDOWN => Some(button(MouseButton::Right, KeyState::Pressed)),
+                WM_RBUTTONUP => Some(button(MouseButton::Right, KeyState::Released)),
+                WM_MBUTTONDOWN => Some(button(MouseButton::Middle, KeyState::Pressed)),
+                WM_MBUTTONUP => Some(button(MouseButton::Middle, KeyState::Released)),
+                WM_MOUSEWHEEL => {
+                    let wheel = (info.mouseData >> 16) as u16 as i16 as i32;
+                    let delta = ScrollDelta(wheel / WHEEL_DELTA as i32);
+                    Some(InputEvent::Scroll { x, y, delta })
+                }
+                _ => None,
+            };
+            if let Some(event) = event {
+                let _ = INPUT_EVENT_CHANNEL.send(event);
+            }
+        }
+        unsafe { CallNextHookEx(None, code, wparam, lparam) }
+    }
+    unsafe { Owned::new(SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_ll), None, 0).unwrap()) }
+}
+
+pub fn input_event_receiver() -> Receiver<InputEvent> {
+    INPUT_EVENT_CHANNEL.subscribe()
+}
+
+pub fn screen_to_client(handle: Handle, x: i32, y: i32) -> Result<(i32, i32)> {
+    let handle = handle.as_inner().ok_or(Error::WindowNotFound)?;
+    let mut point = POINT { x, y };
+    unsafe { ScreenToClient(handle, &raw mut point).ok()? };
+    Ok((point.x, point.y))
+}
+
+pub fn kill_switch_receiver() -> Receiver<()> {
+    KILL_SWITCH.subscribe()
+}
+
+pub fn trigger_kill_switch() {
+    let _ = KILL_SWITCH.send(());
+}
+
+pub fn set_kill_switch_key(key: Option<KeyKind>) {
+    let key = key.map(|key| VIRTUAL_KEY::from(key).0).unwrap_or_default();
+    KILL_SWITCH_KEY.store(key, Ordering::Relaxed);
+}
+
 #[derive(Debug)]
 pub struct WindowsInputReceiver {
     handle: HandleCell,
@@ -123,18 +234,84 @@ pub struct WindowsInput {
     handle: HandleCell,
     input_kind: InputKind,
     key_down: RefCell<BitVec>,
+    key_combo_delay: Duration,
+    backend: InputBackendKind,
+    profile: InputProfile,
+    last_action: Cell<Option<Instant>>,
+    cursor: Cell<Option<(i32, i32)>>,
+    auto_focus: Option<Duration>,
+    injection_mode: KeyInjectionMode,
 }
 
 impl WindowsInput {
-    pub fn new(handle: Handle, kind: InputKind) -> Self {
+    pub fn new(handle: Handle, kind: InputKind, backend: InputBackendKind) -> Self {
         Self {
             handle: HandleCell::new(handle),
             input_kind: kind,
             key_down: RefCell::new(BitVec::from_elem(256, false)),
+            key_combo_delay: Duration::from_millis(30),
+            backend,
+            profile: InputProfile::default(),
+            last_action: Cell::new(None),
+            cursor: Cell::new(None),
+            auto_focus: None,
+            injection_mode: KeyInjectionMode::default(),
         }
     }
 
+    pub fn set_key_combo_delay(&mut self, delay: Duration) {
+        self.key_combo_delay = delay;
+    }
+
+    pub fn set_auto_focus(&mut self, timeout: Option<Duration>) {
+        self.auto_focus = timeout;
+    }
+
+    pub fn set_key_injection_mode(&mut self, mode: KeyInjectionMode) {
+        self.injection_mode = mode;
+    }
+
+    pub fn profile(&self) -> InputProfile {
+        self.profile
+    }
+
+    pub fn set_profile(&mut self, profile: InputProfile) {
+        self.profile = profile;
+    }
+
     pub fn send_mouse(&self, x: i32, y: i32, kind: MouseKind) -> Result<()> {
+        self.pace();
+        if self.profile.mouse_speed > 0.0
+            && let Some(from) = self.cursor.get()
+        {
+            self.glide(from, (x, y))?;
+        }
+        self.send_mouse_at(x, y, kind)?;
+        self.cursor.set(Some((x, y)));
+        Ok(())
+    }
+
+    /// Moves the cursor from `from` towards `to` at the profile mouse speed instead of jumping,
+    /// stopping one step short of `to`.
+    fn glide(&self, from: (i32, i32), to: (i32, i32)) -> Result<()> {
+        let (dx, dy) = ((to.0 - from.0) as f32, (to.1 - from.1) as f32);
+        let duration = Duration::from_secs_f32(dx.hypot(dy) / self.profile.mouse_speed)
+            .min(MOUSE_GLIDE_MAX);
+        let steps = (duration.as_millis() / MOUSE_GLIDE_STEP.as_millis()) as u32;
+
+        for step in 1..steps {
+            let t = step as f32 / steps as f32;
+            // Ease in and out like a hand accelerating and settling on the target
+            let t = t * t * (3.0 - 2.0 * t);
+            let x = from.0 + (dx * t).round() as i32;
+            let y = from.1 + (dy * t).round() as i32;
+            self.send_mouse_at(x, y, MouseKind::Move)?;
+            thread::sleep(MOUSE_GLIDE_STEP);
+        }
+        Ok(())
+    }
+
+    fn send_mouse_at(&self, x: i32, y: i32, kind: MouseKind) -> Result<()> {
         #[inline]
         fn mouse_input(dx: i32, dy: i32, flags: MOUSE_EVENT_FLAGS, data: i32) -> [INPUT; 1] {
             [INPUT {
@@ -152,7 +329,10 @@ impl WindowsInput {
         }
 
         let mut handle = self.get_handle()?;
-        if !is_foreground(handle, self.input_kind) {
+        if matches!(self.backend, InputBackendKind::Message) {
+            return post_mouse_message(handle, x, y, kind, self.click_hold());
+        }
+        if !self.can_send(handle) {
             return Err(Error::WindowNotFound);
         }
         if matches!(self.input_kind, InputKind::Foreground) {
@@ -160,18 +340,27 @@ impl WindowsInput {
         }
 
         let (dx, dy) = client_to_absolute_coordinate_raw(handle, x, y)?;
+        #[cfg(feature = "interception")]
+        if matches!(self.backend, InputBackendKind::Interception) {
+            return send_interception_mouse(dx, dy, kind, self.click_hold());
+        }
         let base_flags = MOUSEEVENTF_ABSOLUTE | MOUSEEVENTF_MOVE | MOUSEEVENTF_VIRTUALDESK;
 
         match kind {
             MouseKind::Move => send_input(mouse_input(dx, dy, base_flags, 0)),
-            MouseKind::Click => {
-                send_input(mouse_input(dx, dy, base_flags | MOUSEEVENTF_LEFTDOWN, 0))?;
-                // TODO: Hack or double-click won't work...
-                thread::sleep(Duration::from_millis(80));
-                send_input(mouse_input(dx, dy, base_flags | MOUSEEVENTF_LEFTUP, 0))
+            MouseKind::Click(button) => {
+                let (down, up) = match button {
+                    MouseButton::Left => (MOUSEEVENTF_LEFTDOWN, MOUSEEVENTF_LEFTUP),
+                    MouseButton::Right => (MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP),
+                    MouseButton::Middle => (MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP),
+                };
+                send_input(mouse_input(dx, dy, base_flags | down, 0))?;
+                thread::sleep(self.click_hold());
+                send_input(mouse_input(dx, dy, base_flags | up, 0))
             }
-            MouseKind::Scroll => {
-                send_input(mouse_input(dx, dy, base_flags | MOUSEEVENTF_WHEEL, -300))
+            MouseKind::Scroll(delta) => {
+                let data = delta.ticks().saturating_mul(WHEEL_DELTA as i32);
+                send_input(mouse_input(dx, dy, base_flags | MOUSEEVENTF_WHEEL, data))
             }
         }
     }
@@ -189,26 +378,121 @@ impl WindowsInput {
     }
 
     pub fn send_key(&self, kind: KeyKind) -> Result<()> {
-        self.send_key_down(kind)?;
-        self.send_key_up(kind)?;
-        Ok(())
+        self.pace();
+        self.send_input(kind, true)?;
+        let hold = self.profile.jittered(self.profile.key_press_duration);
+        if !hold.is_zero() {
+            thread::sleep(hold);
+        }
+        self.send_input(kind, false)
     }
 
     pub fn send_key_up(&self, kind: KeyKind) -> Result<()> {
+        self.pace();
         self.send_input(kind, false)
     }
 
     pub fn send_key_down(&self, kind: KeyKind) -> Result<()> {
+        self.pace();
         self.send_input(kind, true)
     }
 
+    pub fn send_key_combo(&self, keys: &[KeyKind]) -> Result<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        // Checked once so the window losing focus mid-combo can't leave modifiers held
+        let handle = self.get_handle()?;
+        if !self.can_send(handle) {
+            return Err(Error::KeyNotSent);
+        }
+        self.pace();
+
+        let mut pressed = Vec::with_capacity(keys.len());
+        let mut result = Ok(());
+        for (i, &key) in keys.iter().enumerate() {
+            if i > 0 {
+                thread::sleep(self.profile.jittered(self.key_combo_delay));
+            }
+            if let Err(err) = self.send_input_unchecked(key, true) {
+                result = Err(err);
+                break;
+            }
+            pressed.push(key);
+        }
+        for key in pressed.into_iter().rev() {
+            thread::sleep(self.profile.jittered(self.key_combo_delay));
+            let _ = self.send_input_unchecked(key, false);
+        }
+
+        result
+    }
+
     #[inline]
     fn send_input(&self, kind: KeyKind, is_down: bool) -> Result<()> {
         let handle = self.get_handle()?;
-        if is_down && !is_foreground(handle, self.input_kind) {
+        if is_down && !self.can_send(handle) {
             return Err(Error::KeyNotSent);
         }
-        let key = kind.into();
+        self.send_input_unchecked(kind, is_down)
+    }
+
+    /// Waits until the profile inter-action delay has passed since the previous action started.
+    fn pace(&self) {
+        let delay = self.profile.jittered(self.profile.inter_action_delay);
+        if let Some(last) = self.last_action.get() {
+            let elapsed = last.elapsed();
+            if elapsed < delay {
+                thread::sleep(delay - elapsed);
+            }
+        }
+        self.last_action.set(Some(Instant::now()));
+    }
+
+    #[inline]
+    fn click_hold(&self) -> Duration {
+        self.profile.jittered(self.profile.key_press_duration).max(MOUSE_CLICK_HOLD)
+    }
+
+    /// Messages are delivered to the window directly, so only `SendInput` needs the focus check.
+    ///
+    /// With auto focus enabled, an unfocused window is brought to the foreground first.
+    #[inline]
+    fn can_send(&self, handle: HWND) -> bool {
+        if matches!(self.backend, InputBackendKind::Message) || is_foreground(handle, self.input_kind) {
+            return true;
+        }
+        matches!(self.input_kind, InputKind::Focused)
+            && self
+                .auto_focus
+                .is_some_and(|timeout| focus_window(handle, timeout))
+    }
+
+    pub fn release_all(&self) -> Result<()> {
+        let held = self
+            .key_down
+            .borrow()
+            .iter()
+            .enumerate()
+            .filter_map(|(key, is_down)| is_down.then_some(VIRTUAL_KEY(key as u16)))
+            .collect::<Vec<_>>();
+
+        let mut result = Ok(());
+        for key in held {
+            if let Err(err) = self.send_virtual_key(key, false) {
+                result = Err(err);
+            }
+        }
+        result
+    }
+
+    #[inline]
+    fn send_input_unchecked(&self, kind: KeyKind, is_down: bool) -> Result<()> {
+        self.send_virtual_key(kind.into(), is_down)
+    }
+
+    #[inline]
+    fn send_virtual_key(&self, key: VIRTUAL_KEY, is_down: bool) -> Result<()> {
         let (scan_code, is_extended) = to_scan_code(key);
         let mut key_down = self.key_down.borrow_mut();
         // SAFETY: VIRTUAL_KEY is from range 0..254 (inclusive) and BitVec
@@ -220,7 +504,20 @@ impl WindowsInput {
                 key_down.set(key.0 as usize, is_down);
             }
         }
-        send_input(to_input(key, scan_code, is_extended, is_down))
+        match self.backend {
+            InputBackendKind::SendInput => {
+                let scan_code_only =
+                    matches!(self.injection_mode, KeyInjectionMode::ScanCode) && scan_code != 0;
+                send_input(to_input(key, scan_code, is_extended, is_down, scan_code_only))
+            }
+            InputBackendKind::Message => {
+                post_key_message(self.get_handle()?, key, scan_code, is_extended, is_down)
+            }
+            #[cfg(feature = "interception")]
+            InputBackendKind::Interception => {
+                interception::send_key(scan_code, is_extended, is_down)
+            }
+        }
     }
 
     #[inline]
@@ -229,6 +526,13 @@ impl WindowsInput {
     }
 }
 
+impl Drop for WindowsInput {
+    fn drop(&mut self) {
+        // Don't leave keys stuck down in the game when the owner goes away or panics
+        let _ = self.release_all();
+    }
+}
+
 impl TryFrom<VIRTUAL_KEY> for KeyKind {
     type Error = Error;
 
@@ -292,6 +596,7 @@ impl TryFrom<VIRTUAL_KEY> for KeyKind {
             VK_NEXT => KeyKind::PageDown,
             VK_INSERT => KeyKind::Insert,
             VK_DELETE => KeyKind::Delete,
+            VK_PAUSE => KeyKind::Pause,
             VK_CONTROL => KeyKind::Ctrl,
             VK_RETURN => KeyKind::Enter,
             VK_SPACE => KeyKind::Space,
@@ -304,6 +609,37 @@ impl TryFrom<VIRTUAL_KEY> for KeyKind {
             VK_ESCAPE => KeyKind::Esc,
             VK_SHIFT => KeyKind::Shift,
             VK_MENU => KeyKind::Alt,
+            VK_TAB => KeyKind::Tab,
+            VK_BACK => KeyKind::Backspace,
+            VK_CAPITAL => KeyKind::CapsLock,
+            VK_OEM_MINUS => KeyKind::Minus,
+            VK_OEM_PLUS => KeyKind::Equals,
+            VK_OEM_4 => KeyKind::LeftBracket,
+            VK_OEM_6 => KeyKind::RightBracket,
+            VK_OEM_5 => KeyKind::Backslash,
+            VK_LWIN => KeyKind::Win,
+            VK_NUMPAD0 => KeyKind::Numpad0,
+            VK_NUMPAD1 => KeyKind::Numpad1,
+            VK_NUMPAD2 => KeyKind::Numpad2,
+            VK_NUMPAD3 => KeyKind::Numpad3,
+            VK_NUMPAD4 => KeyKind::Numpad4,
+            VK_NUMPAD5 => KeyKind::Numpad5,
+            VK_NUMPAD6 => KeyKind::Numpad6,
+            VK_NUMPAD7 => KeyKind::Numpad7,
+            VK_NUMPAD8 => KeyKind::Numpad8,
+            VK_NUMPAD9 => KeyKind::Numpad9,
+            VK_ADD => KeyKind::NumpadAdd,
+            VK_SUBTRACT => KeyKind::NumpadSubtract,
+            VK_MULTIPLY => KeyKind::NumpadMultiply,
+            VK_DIVIDE => KeyKind::NumpadDivide,
+            VK_DECIMAL => KeyKind::NumpadDecimal,
+            VK_VOLUME_MUTE => KeyKind::VolumeMute,
+            VK_VOLUME_DOWN => KeyKind::VolumeDown,
+            VK_VOLUME_UP => KeyKind::VolumeUp,
+            VK_MEDIA_PLAY_PAUSE => KeyKind::MediaPlayPause,
+            VK_MEDIA_STOP => KeyKind::MediaStop,
+            VK_MEDIA_NEXT_TRACK => KeyKind::MediaNext,
+            VK_MEDIA_PREV_TRACK => KeyKind::MediaPrevious,
             _ => return Err(Error::KeyNotFound),
         })
     }
@@ -370,6 +706,7 @@ impl From<KeyKind> for VIRTUAL_KEY {
             KeyKind::PageDown => VK_NEXT,
             KeyKind::Insert => VK_INSERT,
             KeyKind::Delete => VK_DELETE,
+            KeyKind::Pause => VK_PAUSE,
             KeyKind::Ctrl => VK_CONTROL,
             KeyKind::Enter => VK_RETURN,
             KeyKind::Space => VK_SPACE,
@@ -382,10 +719,72 @@ impl From<KeyKind> for VIRTUAL_KEY {
             KeyKind::Esc => VK_ESCAPE,
             KeyKind::Shift => VK_SHIFT,
             KeyKind::Alt => VK_MENU,
+            KeyKind::Tab => VK_TAB,
+            KeyKind::Backspace => VK_BACK,
+            KeyKind::CapsLock => VK_CAPITAL,
+            KeyKind::Minus => VK_OEM_MINUS,
+            KeyKind::Equals => VK_OEM_PLUS,
+            KeyKind::LeftBracket => VK_OEM_4,
+            KeyKind::RightBracket => VK_OEM_6,
+            KeyKind::Backslash => VK_OEM_5,
+            KeyKind::Win => VK_LWIN,
+            KeyKind::Numpad0 => VK_NUMPAD0,
+            KeyKind::Numpad1 => VK_NUMPAD1,
+            KeyKind::Numpad2 => VK_NUMPAD2,
+            KeyKind::Numpad3 => VK_NUMPAD3,
+            KeyKind::Numpad4 => VK_NUMPAD4,
+            KeyKind::Numpad5 => VK_NUMPAD5,
+            KeyKind::Numpad6 => VK_NUMPAD6,
+            KeyKind::Numpad7 => VK_NUMPAD7,
+            KeyKind::Numpad8 => VK_NUMPAD8,
+            KeyKind::Numpad9 => VK_NUMPAD9,
+            KeyKind::NumpadAdd => VK_ADD,
+            KeyKind::NumpadSubtract => VK_SUBTRACT,
+            KeyKind::NumpadMultiply => VK_MULTIPLY,
+            KeyKind::NumpadDivide => VK_DIVIDE,
+            KeyKind::NumpadDecimal => VK_DECIMAL,
+            KeyKind::VolumeMute => VK_VOLUME_MUTE,
+            KeyKind::VolumeDown => VK_VOLUME_DOWN,
+            KeyKind::VolumeUp => VK_VOLUME_UP,
+            KeyKind::MediaPlayPause => VK_MEDIA_PLAY_PAUSE,
+            KeyKind::MediaStop => VK_MEDIA_STOP,
+            KeyKind::MediaNext => VK_MEDIA_NEXT_TRACK,
+            KeyKind::MediaPrevious => VK_MEDIA_PREV_TRACK,
         }
     }
 }
 
+/// The DPI Windows treats as 100% scaling.
+const DEFAULT_DPI: f32 = 96.0;
+
+/// Runs `f` with the calling thread set to per-monitor DPI awareness.
+///
+/// Window and monitor APIs then report physical pixels, matching captured frames, regardless of
+/// the DPI awareness declared by the process.
+fn with_physical_dpi<T>(f: impl FnOnce() -> T) -> T {
+    let previous: DPI_AWARENESS_CONTEXT =
+        unsafe { SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) };
+    let result = f();
+    if !previous.0.is_null() {
+        unsafe { SetThreadDpiAwarenessContext(previous) };
+    }
+    result
+}
+
+/// Ratio between the window DPI and 96 DPI, e.g. `1.5` on a 150% display.
+fn window_scale_factor(handle: HWND) -> f32 {
+    let dpi = unsafe { GetDpiForWindow(handle) };
+    if dpi == 0 {
+        1.0
+    } else {
+        dpi as f32 / DEFAULT_DPI
+    }
+}
+
+/// Converts physical client coordinates of `handle` to monitor or window frame coordinates.
+///
+/// All coordinates and sizes are in physical pixels. The returned scale factor can be used to
+/// convert them to logical pixels.
 pub fn client_to_monitor_or_frame(
     handle: Handle,
     x: i32,
@@ -393,6 +792,16 @@ pub fn client_to_monitor_or_frame(
     monitor_coordinate: bool,
 ) -> Result<ConvertedCoordinates> {
     let handle = handle.as_inner().ok_or(Error::WindowNotFound)?;
+    with_physical_dpi(|| client_to_monitor_or_frame_physical(handle, x, y, monitor_coordinate))
+}
+
+fn client_to_monitor_or_frame_physical(
+    handle: HWND,
+    x: i32,
+    y: i32,
+    monitor_coordinate: bool,
+) -> Result<ConvertedCoordinates> {
+    let scale_factor = window_scale_factor(handle);
     let mut point = POINT { x, y };
     unsafe { ClientToScreen(handle, &raw mut point).ok()? };
 
@@ -410,6 +819,7 @@ pub fn client_to_monitor_or_frame(
             height,
             x,
             y,
+            scale_factor,
         });
     }
 
@@ -435,10 +845,15 @@ pub fn client_to_monitor_or_frame(
         height,
         x,
         y,
+        scale_factor,
     })
 }
 
 fn client_to_absolute_coordinate_raw(handle: HWND, x: i32, y: i32) -> Result<(i32, i32)> {
+    with_physical_dpi(|| client_to_absolute_coordinate_physical(handle, x, y))
+}
+
+fn client_to_absolute_coordinate_physical(handle: HWND, x: i32, y: i32) -> Result<(i32, i32)> {
     let mut point = POINT { x, y };
     unsafe { ClientToScreen(handle, &raw mut point).ok()? };
 
@@ -506,6 +921,106 @@ fn send_input(input: [INPUT; 1]) -> Result<()> {
     }
 }
 
+#[cfg(feature = "interception")]
+fn send_interception_mouse(dx: i32, dy: i32, kind: MouseKind, hold: Duration) -> Result<()> {
+    match kind {
+        MouseKind::Move => interception::send_mouse(dx, dy, 0, 0),
+        MouseKind::Click(button) => {
+            let (down, up) = match button {
+                MouseButton::Left => (
+                    interception::MOUSE_LEFT_BUTTON_DOWN,
+                    interception::MOUSE_LEFT_BUTTON_UP,
+                ),
+                MouseButton::Right => (
+                    interception::MOUSE_RIGHT_BUTTON_DOWN,
+                    interception::MOUSE_RIGHT_BUTTON_UP,
+                ),
+                MouseButton::Middle => (
+                    interception::MOUSE_MIDDLE_BUTTON_DOWN,
+                    interception::MOUSE_MIDDLE_BUTTON_UP,
+                ),
+            };
+            interception::send_mouse(dx, dy, down, 0)?;
+            thread::sleep(hold);
+            interception::send_mouse(dx, dy, up, 0)
+        }
+        MouseKind::Scroll(delta) => {
+            let rolling = delta.ticks().saturating_mul(WHEEL_DELTA as i32) as i16;
+            interception::send_mouse(dx, dy, interception::MOUSE_WHEEL, rolling)
+        }
+    }
+}
+
+// MK_* button state flags for mouse messages' wParam
+const MK_LBUTTON: usize = 0x0001;
+const MK_RBUTTON: usize = 0x0002;
+const MK_MBUTTON: usize = 0x0010;
+
+#[inline]
+fn post_message(handle: HWND, msg: u32, wparam: usize, lparam: isize) -> Result<()> {
+    unsafe { PostMessageW(Some(handle), msg, WPARAM(wparam), LPARAM(lparam))? };
+    Ok(())
+}
+
+fn post_key_message(
+    handle: HWND,
+    key: VIRTUAL_KEY,
+    scan_code: u16,
+    is_extended: bool,
+    is_down: bool,
+) -> Result<()> {
+    // Alt goes through the system key messages like it does for real key presses
+    let msg = match (key == VK_MENU, is_down) {
+        (false, true) => WM_KEYDOWN,
+        (false, false) => WM_KEYUP,
+        (true, true) => WM_SYSKEYDOWN,
+        (true, false) => WM_SYSKEYUP,
+    };
+    // Repeat count 1, scan code, extended flag, and previous state / transition bits for key up
+    let mut lparam = 1 | ((scan_code as u32) << 16);
+    if is_extended {
+        lparam |= 1 << 24;
+    }
+    if !is_down {
+        lparam |= (1 << 30) | (1 << 31);
+    }
+    post_message(handle, msg, key.0 as usize, lparam as i32 as isize)
+}
+
+fn post_mouse_message(
+    handle: HWND,
+    x: i32,
+    y: i32,
+    kind: MouseKind,
+    hold: Duration,
+) -> Result<()> {
+    let client_point = (((y as u16 as u32) << 16) | x as u16 as u32) as i32 as isize;
+
+    match kind {
+        MouseKind::Move => post_message(handle, WM_MOUSEMOVE, 0, client_point),
+        MouseKind::Click(button) => {
+            let (down, up, state) = match button {
+                MouseButton::Left => (WM_LBUTTONDOWN, WM_LBUTTONUP, MK_LBUTTON),
+                MouseButton::Right => (WM_RBUTTONDOWN, WM_RBUTTONUP, MK_RBUTTON),
+                MouseButton::Middle => (WM_MBUTTONDOWN, WM_MBUTTONUP, MK_MBUTTON),
+            };
+            post_message(handle, WM_MOUSEMOVE, 0, client_point)?;
+            post_message(handle, down, state, client_point)?;
+            thread::sleep(hold);
+            post_message(handle, up, 0, client_point)
+        }
+        MouseKind::Scroll(delta) => {
+            // WM_MOUSEWHEEL carries screen coordinates
+            let mut point = POINT { x, y };
+            unsafe { ClientToScreen(handle, &raw mut point).ok()? };
+            let screen_point =
+                (((point.y as u16 as u32) << 16) | point.x as u16 as u32) as i32 as isize;
+            let data = delta.ticks().saturating_mul(WHEEL_DELTA as i32) as i16;
+            post_message(handle, WM_MOUSEWHEEL, (data as u16 as usize) << 16, screen_point)
+        }
+    }
+}
+
 #[inline]
 fn to_scan_code(key: VIRTUAL_KEY) -> (u16, bool) {
     let scan_code = unsafe { MapVirtualKeyW(key.0 as u32, MAPVK_VK_TO_VSC_EX) } as u16;
@@ -519,7 +1034,13 @@ fn to_scan_code(key: VIRTUAL_KEY) -> (u16, bool) {
 }
 
 #[inline]
-fn to_input(key: VIRTUAL_KEY, scan_code: u16, is_extended: bool, is_down: bool) -> [INPUT; 1] {
+fn to_input(
+    key: VIRTUAL_KEY,
+    scan_code: u16,
+    is_extended: bool,
+    is_down: bool,
+    scan_code_only: bool,
+) -> [INPUT; 1] {
     let is_extended = if is_extended {
         KEYEVENTF_EXTENDEDKEY
     } else {
@@ -530,13 +1051,19 @@ fn to_input(key: VIRTUAL_KEY, scan_code: u16, is_extended: bool, is_down: bool)
     } else {
         KEYEVENTF_KEYUP
     };
+    // With KEYEVENTF_SCANCODE the virtual key is ignored and derived from the scan code instead
+    let (key, is_scan_code) = if scan_code_only {
+        (VIRTUAL_KEY::default(), KEYEVENTF_SCANCODE)
+    } else {
+        (key, KEYBD_EVENT_FLAGS::default())
+    };
     [INPUT {
         r#type: INPUT_KEYBOARD,
         Anonymous: INPUT_0 {
             ki: KEYBDINPUT {
                 wVk: key,
                 wScan: scan_code,
-                dwFlags: is_extended | is_up,
+                dwFlags: is_extended | is_up | is_scan_code,
                 dwExtraInfo: *PROCESS_ID as usize,
                 ..KEYBDINPUT::default()
             },
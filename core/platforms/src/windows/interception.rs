@@ -0,0 +1,130 @@
+//! Input injection through the Interception driver (https://github.com/oblitum/Interception).
+//!
+//! `interception.dll` is loaded at runtime so the driver stays an optional install. Strokes are
+//! injected below the OS input queue, so they look like hardware input to the game.
+
+use std::{ffi::c_void, mem, sync::LazyLock};
+
+use windows::{
+    Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryW},
+    core::{s, w},
+};
+
+use crate::{Error, Result};
+
+/// First keyboard device, `INTERCEPTION_KEYBOARD(0)`.
+const KEYBOARD_DEVICE: i32 = 1;
+/// First mouse device, `INTERCEPTION_MOUSE(0)`.
+const MOUSE_DEVICE: i32 = 11;
+
+const KEY_DOWN: u16 = 0x00;
+const KEY_UP: u16 = 0x01;
+const KEY_E0: u16 = 0x02;
+
+pub const MOUSE_LEFT_BUTTON_DOWN: u16 = 0x001;
+pub const MOUSE_LEFT_BUTTON_UP: u16 = 0x002;
+pub const MOUSE_RIGHT_BUTTON_DOWN: u16 = 0x004;
+pub const MOUSE_RIGHT_BUTTON_UP: u16 = 0x008;
+pub const MOUSE_MIDDLE_BUTTON_DOWN: u16 = 0x010;
+pub const MOUSE_MIDDLE_BUTTON_UP: u16 = 0x020;
+pub const MOUSE_WHEEL: u16 = 0x400;
+
+const MOUSE_MOVE_ABSOLUTE: u16 = 0x001;
+const MOUSE_VIRTUAL_DESKTOP: u16 = 0x002;
+
+#[repr(C)]
+#[derive(Default)]
+struct KeyStroke {
+    code: u16,
+    state: u16,
+    information: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct MouseStroke {
+    state: u16,
+    flags: u16,
+    rolling: i16,
+    x: i32,
+    y: i32,
+    information: u32,
+}
+
+type CreateContextFn = unsafe extern "C" fn() -> *mut c_void;
+type SendFn = unsafe extern "C" fn(*mut c_void, i32, *const c_void, u32) -> i32;
+
+struct Driver {
+    context: *mut c_void,
+    send: SendFn,
+}
+
+// SAFETY: An Interception context can be used from any thread.
+unsafe impl Send for Driver {}
+unsafe impl Sync for Driver {}
+
+static DRIVER: LazyLock<Option<Driver>> = LazyLock::new(|| unsafe {
+    let library = LoadLibraryW(w!("interception.dll")).ok()?;
+    let create_context = GetProcAddress(library, s!("interception_create_context"))?;
+    let send = GetProcAddress(library, s!("interception_send"))?;
+    let create_context = mem::transmute::<_, CreateContextFn>(create_context);
+    let send = mem::transmute::<_, SendFn>(send);
+
+    // Context and library live for the rest of the process
+    let context = create_context();
+    (!context.is_null()).then_some(Driver { context, send })
+});
+
+#[inline]
+fn driver() -> Result<&'static Driver> {
+    DRIVER.as_ref().ok_or(Error::InputBackendNotAvailable)
+}
+
+/// Whether `interception.dll` is loaded and the driver is installed.
+pub fn is_available() -> bool {
+    DRIVER.is_some()
+}
+
+pub fn send_key(scan_code: u16, is_extended: bool, is_down: bool) -> Result<()> {
+    let driver = driver()?;
+    let mut state = if is_down { KEY_DOWN } else { KEY_UP };
+    if is_extended {
+        state |= KEY_E0;
+    }
+    let stroke = KeyStroke {
+        code: scan_code,
+        state,
+        ..KeyStroke::default()
+    };
+
+    let sent = unsafe {
+        (driver.send)(driver.context, KEYBOARD_DEVICE, (&raw const stroke).cast(), 1)
+    };
+    if sent == 1 {
+        Ok(())
+    } else {
+        Err(Error::KeyNotSent)
+    }
+}
+
+/// Sends a mouse stroke at absolute virtual desktop coordinates normalized to `0..65536`.
+pub fn send_mouse(dx: i32, dy: i32, state: u16, rolling: i16) -> Result<()> {
+    let driver = driver()?;
+    let stroke = MouseStroke {
+        state,
+        flags: MOUSE_MOVE_ABSOLUTE | MOUSE_VIRTUAL_DESKTOP,
+        rolling,
+        x: dx,
+        y: dy,
+        ..MouseStroke::default()
+    };
+
+    let sent = unsafe {
+        (driver.send)(driver.context, MOUSE_DEVICE, (&raw const stroke).cast(), 1)
+    };
+    if sent == 1 {
+        Ok(())
+    } else {
+        Err(Error::MouseNotSent)
+    }
+}
@@ -11,12 +11,20 @@ use windows::Win32::UI::WindowsAndMessaging::{
 };
 
 mod bitblt;
+mod clipboard;
+mod focus;
 mod handle;
 mod input;
+#[cfg(feature = "interception")]
+mod interception;
+mod ocr;
+mod thread;
 mod wgc;
 mod window_box;
 
-pub use {bitblt::*, handle::*, input::*, wgc::*, window_box::*};
+pub use {
+    bitblt::*, clipboard::*, focus::*, handle::*, input::*, ocr::*, thread::*, wgc::*, window_box::*,
+};
 
 use crate::{Error, Result, capture::Frame};
 
@@ -49,6 +57,7 @@ pub fn init() {
         let keys_barrier = barrier.clone();
         thread::spawn(move || {
             let _hook = input::init();
+            let _mouse_hook = input::init_mouse();
             let mut msg = MSG::default();
             keys_barrier.wait();
             while unsafe { GetMessageW(&mut msg, None, 0, 0) }.as_bool() {
@@ -62,6 +71,11 @@ pub fn init() {
     }
 }
 
+#[cfg(feature = "interception")]
+pub fn interception_available() -> bool {
+    interception::is_available()
+}
+
 impl Error {
     #[inline]
     pub(crate) fn from_last_win_error() -> Error {
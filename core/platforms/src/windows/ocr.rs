@@ -0,0 +1,71 @@
+use std::cell::RefCell;
+
+use windows::{
+    Graphics::Imaging::{BitmapPixelFormat, SoftwareBitmap},
+    Media::Ocr::OcrEngine,
+    Security::Cryptography::CryptographicBuffer,
+};
+
+use crate::{Error, Result, ocr::OcrLine, ocr::OcrWord};
+
+thread_local! {
+    /// Creating an engine loads the language model, so keep one per thread.
+    static ENGINE: RefCell<Option<OcrEngine>> = const { RefCell::new(None) };
+}
+
+fn with_engine<T>(f: impl FnOnce(&OcrEngine) -> Result<T>) -> Result<T> {
+    ENGINE.with_borrow_mut(|engine| {
+        if engine.is_none() {
+            // Fails when none of the user's languages has an OCR language pack installed
+            *engine = Some(
+                OcrEngine::TryCreateFromUserProfileLanguages()
+                    .map_err(|_| Error::OcrNotAvailable)?,
+            );
+        }
+        f(engine.as_ref().unwrap())
+    })
+}
+
+pub fn recognize_text(bgra: &[u8], width: u32, height: u32) -> Result<Vec<OcrLine>> {
+    if width == 0 || height == 0 || bgra.len() < width as usize * height as usize * 4 {
+        return Err(Error::OcrInvalidImage);
+    }
+    let max = OcrEngine::MaxImageDimension()?;
+    if width > max || height > max {
+        return Err(Error::OcrInvalidImage);
+    }
+
+    let buffer = CryptographicBuffer::CreateFromByteArray(bgra)?;
+    let bitmap = SoftwareBitmap::CreateCopyFromBuffer(
+        &buffer,
+        BitmapPixelFormat::Bgra8,
+        width as i32,
+        height as i32,
+    )?;
+
+    with_engine(|engine| {
+        let result = engine.RecognizeAsync(&bitmap)?.get()?;
+        let mut lines = Vec::new();
+        for line in result.Lines()? {
+            let words = line
+                .Words()?
+                .into_iter()
+                .map(|word| {
+                    let rect = word.BoundingRect()?;
+                    Ok(OcrWord {
+                        text: word.Text()?.to_string_lossy(),
+                        x: rect.X,
+                        y: rect.Y,
+                        width: rect.Width,
+                        height: rect.Height,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            lines.push(OcrLine {
+                text: line.Text()?.to_string_lossy(),
+                words,
+            });
+        }
+        Ok(lines)
+    })
+}
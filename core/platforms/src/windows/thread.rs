@@ -0,0 +1,45 @@
+use windows::Win32::System::Threading::{
+    GetCurrentProcess, GetCurrentThread, GetProcessAffinityMask, SetThreadAffinityMask,
+    SetThreadPriority, THREAD_PRIORITY_ABOVE_NORMAL, THREAD_PRIORITY_BELOW_NORMAL,
+    THREAD_PRIORITY_HIGHEST, THREAD_PRIORITY_LOWEST, THREAD_PRIORITY_NORMAL,
+    THREAD_PRIORITY_TIME_CRITICAL,
+};
+
+use crate::{Error, Result, thread::ThreadPriority};
+
+pub fn set_current_thread_priority(priority: ThreadPriority) -> Result<()> {
+    let priority = match priority {
+        ThreadPriority::Lowest => THREAD_PRIORITY_LOWEST,
+        ThreadPriority::BelowNormal => THREAD_PRIORITY_BELOW_NORMAL,
+        ThreadPriority::Normal => THREAD_PRIORITY_NORMAL,
+        ThreadPriority::AboveNormal => THREAD_PRIORITY_ABOVE_NORMAL,
+        ThreadPriority::Highest => THREAD_PRIORITY_HIGHEST,
+        ThreadPriority::TimeCritical => THREAD_PRIORITY_TIME_CRITICAL,
+    };
+    unsafe { SetThreadPriority(GetCurrentThread(), priority) }?;
+    Ok(())
+}
+
+pub fn set_current_thread_affinity(cores: &[usize]) -> Result<()> {
+    let mut process_mask = 0usize;
+    let mut system_mask = 0usize;
+    unsafe { GetProcessAffinityMask(GetCurrentProcess(), &mut process_mask, &mut system_mask) }?;
+
+    let mask = if cores.is_empty() {
+        process_mask
+    } else {
+        cores
+            .iter()
+            .filter(|&&core| core < usize::BITS as usize)
+            .fold(0usize, |mask, &core| mask | 1 << core)
+            & process_mask
+    };
+    if mask == 0 {
+        return Err(Error::InvalidThreadAffinity);
+    }
+
+    if unsafe { SetThreadAffinityMask(GetCurrentThread(), mask) } == 0 {
+        return Err(Error::from_last_win_error());
+    }
+    Ok(())
+}
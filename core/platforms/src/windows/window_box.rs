@@ -121,6 +121,8 @@ impl Default for WindowBoxCapture {
         barrier.wait();
         let handle = HWND(handle.lock().unwrap().unwrap().get() as *mut c_void);
         let handle = Handle::new(HandleKind::Fixed(handle));
+        // Keep the box itself out of any capture so it never shows up in its own frames
+        let _ = handle.set_excluded_from_capture(true);
         let capture = BitBltCapture::new(handle, true);
 
         Self {
@@ -15,7 +15,7 @@ use windows::Win32::Graphics::Dxgi::{
     DXGI_ERROR_INVALID_CALL,
 };
 use windows::core::Interface;
-use super::texture_processor::{TextureProcessor, ProcessedFrame};
+use super::texture_processor::{TextureProcessor, ProcessedFrame, FrameFormat};
 
 #[derive(thiserror::Error, Debug, Clone)]
 pub enum DxgiError {
@@ -115,6 +115,13 @@ impl DxgiDesktopDuplication {
     
     /// Capture a frame using DXGI Desktop Duplication
     pub fn capture_frame(&mut self) -> Result<Option<ID3D11Texture2D>, DxgiError> {
+        self.capture_frame_with_timeout(0)
+    }
+    
+    /// Capture a frame, blocking up to `timeout_ms` until the desktop updates
+    ///
+    /// Returns `Ok(None)` if nothing changed within the timeout.
+    pub fn capture_frame_with_timeout(&mut self, timeout_ms: u32) -> Result<Option<ID3D11Texture2D>, DxgiError> {
         let duplication = self.duplication.as_ref()
             .ok_or_else(|| DxgiError::InvalidCall)?;
         
@@ -122,7 +129,7 @@ impl DxgiDesktopDuplication {
             let mut frame_info = std::mem::zeroed();
             let mut desktop_resource = None;
             
-            match duplication.AcquireNextFrame(0, &mut frame_info, &mut desktop_resource) {
+            match duplication.AcquireNextFrame(timeout_ms, &mut frame_info, &mut desktop_resource) {
                 Ok(_) => {
                     if let Some(resource) = desktop_resource {
                         let texture: ID3D11Texture2D = resource.cast()
@@ -166,7 +173,7 @@ impl DxgiDesktopDuplication {
     }
     
     /// Extract raw frame data with high quality
-    pub fn extract_frame_data(&self, texture: &ID3D11Texture2D) -> Result<ProcessedFrame, DxgiError> {
+    pub fn extract_frame_data(&mut self, texture: &ID3D11Texture2D) -> Result<ProcessedFrame, DxgiError> {
         self.texture_processor.extract_frame_data(texture)
             .map_err(|e| DxgiError::DuplicationError(e.to_string()))
     }
@@ -176,6 +183,12 @@ impl DxgiDesktopDuplication {
         self.texture_processor.set_gpu_processing(enabled);
     }
     
+    /// Configure the pixel layout of processed frames (BGRA or NV12)
+    pub fn set_output_format(&mut self, format: FrameFormat) -> Result<(), DxgiError> {
+        self.texture_processor.set_output_format(format)
+            .map_err(|e| DxgiError::DuplicationError(e.to_string()))
+    }
+    
     /// Check if duplication is active
     pub fn is_active(&self) -> bool {
         self.duplication.is_some()
@@ -18,7 +18,7 @@ use windows::Media::Core::{
 };
 use windows::Media::MediaProperties::{
     AudioEncodingProperties, ContainerEncodingProperties, MediaEncodingProfile,
-    MediaEncodingSubtypes, VideoEncodingProperties,
+    VideoEncodingProperties,
 };
 use windows::Media::Transcoding::MediaTranscoder;
 use windows::Security::Cryptography::CryptographicBuffer;
@@ -31,6 +31,7 @@ use windows::core::{HSTRING, Interface};
 use crate::windows_capture::d3d11::SendDirectX;
 use crate::windows_capture::frame::{Frame, ImageFormat};
 use crate::windows_capture::settings::ColorFormat;
+use crate::windows_capture::texture_processor::bgra_to_nv12;
 
 #[derive(thiserror::Error, Eq, PartialEq, Clone, Debug)]
 pub enum ImageEncoderError {
@@ -141,6 +142,10 @@ pub enum VideoEncoderError {
     AudioDisabled,
     #[error("I/O error: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("Failed to read frame: {0}")]
+    FrameError(#[from] crate::windows_capture::frame::Error),
+    #[error("Frames in {0:?} can't be converted to the encoder input")]
+    UnsupportedInput(ColorFormat),
 }
 
 unsafe impl Send for VideoEncoderError {}
@@ -160,6 +165,7 @@ pub enum AudioEncoderSource {
 /// The `VideoSettingsBuilder` struct is used to configure settings for the video encoder.
 pub struct VideoSettingsBuilder {
     sub_type: VideoSettingsSubType,
+    input_sub_type: VideoSettingsSubType,
     bitrate: u32,
     width: u32,
     height: u32,
@@ -175,6 +181,7 @@ impl VideoSettingsBuilder {
             frame_rate: 60,
             pixel_aspect_ratio: (1, 1),
             sub_type: VideoSettingsSubType::HEVC,
+            input_sub_type: VideoSettingsSubType::BGRA8,
             width,
             height,
             disabled: false,
@@ -186,6 +193,14 @@ impl VideoSettingsBuilder {
         self
     }
 
+    /// Sets the layout of the raw frames sent to the encoder. Use `NV12` together with
+    /// `TextureProcessor::set_output_format` to skip the encoder's own color conversion,
+    /// frames passed to `send_frame` are converted to it on the CPU.
+    pub const fn input_sub_type(mut self, input_sub_type: VideoSettingsSubType) -> Self {
+        self.input_sub_type = input_sub_type;
+        self
+    }
+
     pub const fn bitrate(mut self, bitrate: u32) -> Self {
         self.bitrate = bitrate;
         self
@@ -253,6 +268,7 @@ impl AudioSettingsBuilder {
             disabled: false,
         }
     }
+
     pub const fn bitrate(mut self, bitrate: u32) -> Self {
         self.bitrate = bitrate;
         self
@@ -491,6 +507,8 @@ pub struct VideoEncoder {
     error_notify: Arc<AtomicBool>,
     is_video_disabled: bool,
     is_audio_disabled: bool,
+    /// Layout of the uncompressed frames the transcoder reads
+    input_sub_type: VideoSettingsSubType,
 }
 
 impl VideoEncoder {
@@ -518,6 +536,7 @@ impl VideoEncoder {
         let path = path.as_ref();
         let media_encoding_profile = MediaEncodingProfile::new()?;
 
+        let input_sub_type = video_settings.input_sub_type;
         let (video_encoding_properties, is_video_disabled) = video_settings.build()?;
         media_encoding_profile.SetVideo(&video_encoding_properties)?;
         let (audio_encoding_properties, is_audio_disabled) = audio_settings.build()?;
@@ -526,7 +545,7 @@ impl VideoEncoder {
         media_encoding_profile.SetContainer(&container_encoding_properties)?;
 
         let video_encoding_properties = VideoEncodingProperties::CreateUncompressed(
-            &MediaEncodingSubtypes::Bgra8()?,
+            &input_sub_type.to_hstring(),
             video_encoding_properties.Width()?,
             video_encoding_properties.Height()?,
         )?;
@@ -717,6 +736,7 @@ impl VideoEncoder {
             error_notify,
             is_video_disabled,
             is_audio_disabled,
+            input_sub_type,
         })
     }
 
@@ -743,6 +763,7 @@ impl VideoEncoder {
     ) -> Result<Self, VideoEncoderError> {
         let media_encoding_profile = MediaEncodingProfile::new()?;
 
+        let input_sub_type = video_settings.input_sub_type;
         let (video_encoding_properties, is_video_disabled) = video_settings.build()?;
         media_encoding_profile.SetVideo(&video_encoding_properties)?;
         let (audio_encoding_properties, is_audio_disabled) = audio_settings.build()?;
@@ -751,7 +772,7 @@ impl VideoEncoder {
         media_encoding_profile.SetContainer(&container_encoding_properties)?;
 
         let video_encoding_properties = VideoEncodingProperties::CreateUncompressed(
-            &MediaEncodingSubtypes::Bgra8()?,
+            &input_sub_type.to_hstring(),
             video_encoding_properties.Width()?,
             video_encoding_properties.Height()?,
         )?;
@@ -933,14 +954,29 @@ impl VideoEncoder {
             error_notify,
             is_video_disabled,
             is_audio_disabled,
+            input_sub_type,
         })
     }
 
+    /// Pixels of `frame` converted to the encoder input, `None` if its surface can be encoded
+    /// as is
+    fn converted_frame(&self, frame: &Frame) -> Result<Option<Vec<u8>>, VideoEncoderError> {
+        if self.input_sub_type != VideoSettingsSubType::NV12 {
+            return Ok(None);
+        }
+        if frame.color_format() != ColorFormat::Bgra8 {
+            return Err(VideoEncoderError::UnsupportedInput(frame.color_format()));
+        }
+        let pixels = frame.to_vec()?;
+        Ok(Some(bgra_to_nv12(&pixels, frame.width(), frame.height())))
+    }
+
     /// Sends a video frame to the video encoder for encoding.
     ///
     /// # Arguments
     ///
-    /// * `frame` - A reference to the `Frame` to be encoded.
+    /// * `frame` - A reference to the `Frame` to be encoded, copied into an owned NV12 buffer
+    ///   when the encoder input is `NV12`.
     ///
     /// # Returns
     ///
@@ -963,12 +999,13 @@ impl VideoEncoder {
             }
         };
 
-        self.frame_sender.send(Some((
-            VideoEncoderSource::DirectX(SendDirectX::new(unsafe {
-                frame.as_raw_surface().clone()
-            })),
-            timestamp,
-        )))?;
+        // Kept alive until the transcoder signals it processed the frame
+        let converted = self.converted_frame(frame)?;
+        let source = match &converted {
+            Some(buffer) => VideoEncoderSource::Buffer((SendDirectX::new(buffer.as_ptr()), buffer.len())),
+            None => VideoEncoderSource::DirectX(SendDirectX::new(unsafe { frame.as_raw_surface().clone() })),
+        };
+        self.frame_sender.send(Some((source, timestamp)))?;
 
         let (lock, cvar) = &*self.frame_notify;
         let mut processed = lock.lock();
@@ -1023,12 +1060,13 @@ impl VideoEncoder {
             }
         };
 
-        self.frame_sender.send(Some((
-            VideoEncoderSource::DirectX(SendDirectX::new(unsafe {
-                frame.as_raw_surface().clone()
-            })),
-            timestamp,
-        )))?;
+        // Kept alive until the transcoder signals it processed the frame
+        let converted = self.converted_frame(frame)?;
+        let source = match &converted {
+            Some(buffer) => VideoEncoderSource::Buffer((SendDirectX::new(buffer.as_ptr()), buffer.len())),
+            None => VideoEncoderSource::DirectX(SendDirectX::new(unsafe { frame.as_raw_surface().clone() })),
+        };
+        self.frame_sender.send(Some((source, timestamp)))?;
 
         let (lock, cvar) = &*self.frame_notify;
         let mut processed = lock.lock();
@@ -8,7 +8,7 @@ use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use windows::Foundation::TimeSpan;
 use windows::Graphics::DirectX::Direct3D11::IDirect3DSurface;
 use windows::Win32::Graphics::Direct3D11::{
-    D3D11_BOX, D3D11_CPU_ACCESS_READ, D3D11_CPU_ACCESS_WRITE, D3D11_MAP_READ_WRITE,
+    D3D11_BOX, D3D11_CPU_ACCESS_READ, D3D11_CPU_ACCESS_WRITE, D3D11_MAP_READ, D3D11_MAP_READ_WRITE,
     D3D11_MAPPED_SUBRESOURCE, D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING, ID3D11Device,
     ID3D11DeviceContext, ID3D11Texture2D,
 };
@@ -250,6 +250,72 @@ impl<'a> Frame<'a> {
         Ok(frame_buffer)
     }
 
+    /// Copies the frame data without padding into an owned buffer, leaving the frame untouched.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<u8>` containing the pixel data without padding.
+    #[inline]
+    pub fn to_vec(&self) -> Result<Vec<u8>, Error> {
+        // Texture Settings
+        let texture_desc = D3D11_TEXTURE2D_DESC {
+            Width: self.width,
+            Height: self.height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: DXGI_FORMAT(self.color_format as i32),
+            SampleDesc: DXGI_SAMPLE_DESC { Count: 1, Quality: 0 },
+            Usage: D3D11_USAGE_STAGING,
+            BindFlags: 0,
+            CPUAccessFlags: D3D11_CPU_ACCESS_READ.0 as u32,
+            MiscFlags: 0,
+        };
+
+        // Create a texture that the CPU can read
+        let mut texture = None;
+        unsafe {
+            self.d3d_device.CreateTexture2D(&texture_desc, None, Some(&mut texture))?;
+        };
+
+        let texture = texture.unwrap();
+
+        // Copy the real texture to the staging texture
+        unsafe {
+            self.context.CopyResource(&texture, &self.frame_texture);
+        };
+
+        // Map the texture to enable CPU access
+        let mut mapped_resource = D3D11_MAPPED_SUBRESOURCE::default();
+        unsafe {
+            self.context.Map(&texture, 0, D3D11_MAP_READ, 0, Some(&mut mapped_resource))?;
+        };
+
+        let multiplier = match self.color_format {
+            ColorFormat::Rgba16F => 8,
+            ColorFormat::Rgba8 => 4,
+            ColorFormat::Bgra8 => 4,
+        };
+
+        // Copy row by row, dropping the padding at the end of each
+        let width_size = (self.width * multiplier) as usize;
+        let mut pixels = Vec::with_capacity(width_size * self.height as usize);
+        for y in 0..self.height {
+            let row = unsafe {
+                slice::from_raw_parts(
+                    mapped_resource.pData.cast::<u8>().add((y * mapped_resource.RowPitch) as usize),
+                    width_size,
+                )
+            };
+            pixels.extend_from_slice(row);
+        }
+
+        unsafe {
+            self.context.Unmap(&texture, 0);
+        };
+
+        Ok(pixels)
+    }
+
     /// Get a cropped frame buffer.
     ///
     /// # Arguments
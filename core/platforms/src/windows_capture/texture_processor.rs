@@ -2,6 +2,7 @@
 // Supports both CPU and GPU processing paths
 
 use std::time::Instant;
+use rayon::prelude::*;
 use windows::Win32::Graphics::Direct3D11::{
     ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D, 
     D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING, D3D11_USAGE_DEFAULT, D3D11_CPU_ACCESS_READ,
@@ -24,6 +25,7 @@ pub enum FrameFormat {
     Rgba8,
     Rgb8,
     Jpeg,
+    Nv12,      // 4:2:0 Y plane followed by interleaved UV, as expected by hardware encoders
 }
 
 #[derive(Debug, Clone)]
@@ -44,6 +46,8 @@ pub enum TextureProcessingError {
     TextureCopy(String),
     #[error("GPU processing failed: {0}")]
     GpuProcessing(String),
+    #[error("Unsupported output format: {0:?}")]
+    UnsupportedFormat(FrameFormat),
     #[error("Windows API error: {0}")]
     WindowsError(#[from] windows::core::Error),
 }
@@ -52,6 +56,9 @@ pub struct TextureProcessor {
     device: ID3D11Device,
     context: ID3D11DeviceContext,
     gpu_processing_enabled: bool,
+    output_format: FrameFormat,
+    // Staging texture reused across frames, recreated when the source size or format changes
+    staging: Option<(D3D11_TEXTURE2D_DESC, ID3D11Texture2D)>,
     // TODO: Add compute shader resources for GPU processing
 }
 
@@ -61,17 +68,28 @@ impl TextureProcessor {
             device,
             context,
             gpu_processing_enabled: true, // Enable GPU processing by default for better performance
+            output_format: FrameFormat::Bgra8,
+            staging: None,
         }
     }
     
     /// Extract frame data from DXGI texture with high quality
-    pub fn extract_frame_data(&self, texture: &ID3D11Texture2D) -> Result<ProcessedFrame, TextureProcessingError> {
+    pub fn extract_frame_data(&mut self, texture: &ID3D11Texture2D) -> Result<ProcessedFrame, TextureProcessingError> {
+        let frame = self.extract_bgra(texture)?;
+
+        match self.output_format {
+            FrameFormat::Nv12 => Ok(frame.into_nv12()),
+            _ => Ok(frame),
+        }
+    }
+
+    fn extract_bgra(&mut self, texture: &ID3D11Texture2D) -> Result<ProcessedFrame, TextureProcessingError> {
         if self.gpu_processing_enabled {
             // Try GPU processing first for better performance
             match self.extract_with_gpu(texture) {
                 Ok(frame) => return Ok(frame),
                 Err(e) => {
-                    eprintln!("GPU processing failed, falling back to CPU: {}", e);
+                    tracing::warn!(error = %e, "GPU processing failed, falling back to CPU");
                     // Fall through to CPU processing
                 }
             }
@@ -82,7 +100,7 @@ impl TextureProcessor {
     }
     
     /// High-quality CPU extraction (slower but more compatible)
-    fn extract_with_cpu(&self, texture: &ID3D11Texture2D) -> Result<ProcessedFrame, TextureProcessingError> {
+    fn extract_with_cpu(&mut self, texture: &ID3D11Texture2D) -> Result<ProcessedFrame, TextureProcessingError> {
         unsafe {
             // Get texture description
             let mut desc = D3D11_TEXTURE2D_DESC::default();
@@ -102,14 +120,7 @@ impl TextureProcessor {
                 MiscFlags: desc.MiscFlags,
             };
             
-            let mut staging_texture = None;
-            self.device.CreateTexture2D(
-                &staging_desc,
-                None,
-                Some(&mut staging_texture),
-            ).map_err(|e| TextureProcessingError::StagingTextureCreation(e.to_string()))?;
-            
-            let staging_texture = staging_texture.unwrap();
+            let staging_texture = self.staging_texture(&staging_desc)?;
             
             // Copy from GPU texture to staging texture
             self.context.CopyResource(&staging_texture, texture);
@@ -157,7 +168,7 @@ impl TextureProcessor {
     }
     
     /// GPU-accelerated extraction (faster, uses optimized D3D11 operations)
-    fn extract_with_gpu(&self, texture: &ID3D11Texture2D) -> Result<ProcessedFrame, TextureProcessingError> {
+    fn extract_with_gpu(&mut self, texture: &ID3D11Texture2D) -> Result<ProcessedFrame, TextureProcessingError> {
         unsafe {
             // Get texture description
             let mut desc = D3D11_TEXTURE2D_DESC::default();
@@ -185,16 +196,10 @@ impl TextureProcessor {
                     MiscFlags: 0, // Remove unnecessary flags for better performance
                 };
                 
-                let mut staging_texture = None;
-                self.device.CreateTexture2D(
-                    &staging_desc,
-                    None,
-                    Some(&mut staging_texture),
-                ).map_err(|e| TextureProcessingError::GpuProcessing(
-                    format!("Failed to create GPU staging texture: {}", e)
-                ))?;
-                
-                let staging_texture = staging_texture.unwrap();
+                let staging_texture = self.staging_texture(&staging_desc)
+                    .map_err(|e| TextureProcessingError::GpuProcessing(
+                        format!("Failed to create GPU staging texture: {}", e)
+                    ))?;
                 
                 // Use GPU-optimized copy (faster than CPU copy)
                 self.context.CopyResource(&staging_texture, texture);
@@ -251,10 +256,47 @@ impl TextureProcessor {
         }
     }
     
+    /// Get the cached staging texture, creating it only when `desc` differs from the cached one
+    fn staging_texture(&mut self, desc: &D3D11_TEXTURE2D_DESC) -> Result<ID3D11Texture2D, TextureProcessingError> {
+        if let Some((cached_desc, texture)) = &self.staging {
+            if cached_desc == desc {
+                return Ok(texture.clone());
+            }
+        }
+
+        let mut staging_texture = None;
+        unsafe {
+            self.device.CreateTexture2D(desc, None, Some(&mut staging_texture))
+                .map_err(|e| TextureProcessingError::StagingTextureCreation(e.to_string()))?;
+        }
+        let staging_texture = staging_texture.ok_or_else(|| {
+            TextureProcessingError::StagingTextureCreation("CreateTexture2D returned no texture".to_string())
+        })?;
+
+        self.staging = Some((*desc, staging_texture.clone()));
+        Ok(staging_texture)
+    }
+    
     /// Enable/disable GPU processing
     pub fn set_gpu_processing(&mut self, enabled: bool) {
         self.gpu_processing_enabled = enabled;
     }
+
+    /// Select the pixel layout of extracted frames (`Bgra8` or `Nv12`)
+    pub fn set_output_format(&mut self, format: FrameFormat) -> Result<(), TextureProcessingError> {
+        match format {
+            FrameFormat::Bgra8 | FrameFormat::Nv12 => {
+                self.output_format = format;
+                Ok(())
+            }
+            _ => Err(TextureProcessingError::UnsupportedFormat(format)),
+        }
+    }
+
+    /// Get the pixel layout of extracted frames
+    pub fn output_format(&self) -> FrameFormat {
+        self.output_format.clone()
+    }
     
     /// Get processing capabilities
     pub fn get_capabilities(&self) -> ProcessingCapabilities {
@@ -267,6 +309,71 @@ impl TextureProcessor {
     }
 }
 
+impl ProcessedFrame {
+    /// Convert a BGRA frame to NV12, leaving frames in any other format untouched
+    pub fn into_nv12(self) -> Self {
+        if self.format != FrameFormat::Bgra8 {
+            return self;
+        }
+
+        Self {
+            data: bgra_to_nv12(&self.data, self.width, self.height),
+            format: FrameFormat::Nv12,
+            ..self
+        }
+    }
+}
+
+/// Convert a tightly packed BGRA buffer to NV12 using BT.601 limited-range coefficients.
+///
+/// Odd dimensions are handled by clamping the last row/column when averaging chroma, so the
+/// UV plane is `ceil(width / 2) * ceil(height / 2) * 2` bytes.
+pub fn bgra_to_nv12(bgra: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let width = width as usize;
+    let height = height as usize;
+    if width == 0 || height == 0 || bgra.len() < width * height * 4 {
+        return Vec::new();
+    }
+
+    let chroma_width = width.div_ceil(2);
+    let chroma_height = height.div_ceil(2);
+    let luma_size = width * height;
+    let mut nv12 = vec![0u8; luma_size + chroma_width * chroma_height * 2];
+    let (luma, chroma) = nv12.split_at_mut(luma_size);
+
+    luma.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        let src = &bgra[y * width * 4..(y + 1) * width * 4];
+        for (x, pixel) in src.chunks_exact(4).enumerate() {
+            let (b, g, r) = (pixel[0] as i32, pixel[1] as i32, pixel[2] as i32);
+            row[x] = (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+        }
+    });
+
+    chroma
+        .par_chunks_mut(chroma_width * 2)
+        .enumerate()
+        .for_each(|(cy, row)| {
+            for cx in 0..chroma_width {
+                // Average the 2x2 block this chroma sample covers
+                let (mut b, mut g, mut r) = (0i32, 0i32, 0i32);
+                for (dy, dx) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+                    let y = (cy * 2 + dy).min(height - 1);
+                    let x = (cx * 2 + dx).min(width - 1);
+                    let i = (y * width + x) * 4;
+                    b += bgra[i] as i32;
+                    g += bgra[i + 1] as i32;
+                    r += bgra[i + 2] as i32;
+                }
+                let (b, g, r) = (b / 4, g / 4, r / 4);
+
+                row[cx * 2] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+                row[cx * 2 + 1] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+            }
+        });
+
+    nv12
+}
+
 #[derive(Debug)]
 pub struct ProcessingCapabilities {
     pub supports_cpu: bool,
@@ -308,3 +415,37 @@ impl MinimapComputeShader {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [0, 0, 255, 255];
+    const BLUE: [u8; 4] = [255, 0, 0, 255];
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+    fn bgra(pixels: &[[u8; 4]]) -> Vec<u8> {
+        pixels.concat()
+    }
+
+    #[test]
+    fn test_bgra_to_nv12_odd_width() {
+        // One chroma sample covers both red pixels, the last one is clamped to the blue column
+        let nv12 = bgra_to_nv12(&bgra(&[RED, RED, BLUE]), 3, 1);
+        assert_eq!(nv12, vec![82, 82, 41, 90, 240, 240, 110]);
+    }
+
+    #[test]
+    fn test_bgra_to_nv12_odd_height() {
+        let nv12 = bgra_to_nv12(&bgra(&[RED, RED, BLUE, BLUE, WHITE, WHITE]), 2, 3);
+        // Y plane, then the red/blue block averaged and the white row clamped on its own
+        assert_eq!(nv12, vec![82, 82, 41, 41, 235, 235, 165, 175, 128, 128]);
+    }
+
+    #[test]
+    fn test_bgra_to_nv12_plane_sizes() {
+        let nv12 = bgra_to_nv12(&vec![0; 5 * 3 * 4], 5, 3);
+        assert_eq!(nv12.len(), 5 * 3 + 3 * 2 * 2);
+        assert!(bgra_to_nv12(&[0; 4], 2, 2).is_empty());
+    }
+}
@@ -15,7 +15,8 @@ use windows::Win32::UI::HiDpi::GetDpiForWindow;
 use windows::Win32::UI::WindowsAndMessaging::{
     EnumChildWindows, FindWindowW, GWL_EXSTYLE, GWL_STYLE, GetClientRect, GetDesktopWindow,
     GetForegroundWindow, GetWindowLongPtrW, GetWindowRect, GetWindowTextLengthW, GetWindowTextW,
-    GetWindowThreadProcessId, IsWindowVisible, WS_CHILD, WS_EX_TOOLWINDOW,
+    GetWindowThreadProcessId, IsWindowVisible, SetWindowDisplayAffinity, WDA_EXCLUDEFROMCAPTURE,
+    WDA_NONE, WS_CHILD, WS_EX_TOOLWINDOW,
 };
 use windows::core::{BOOL, HSTRING, Owned};
 
@@ -28,6 +29,8 @@ pub enum Error {
     NoActiveWindow,
     #[error("Failed to find a window with the name: {0}")]
     NotFound(String),
+    #[error("Failed to find a window for the process: {0}")]
+    ProcessNotFound(String),
     #[error("Failed to convert a Windows string from UTF-16")]
     FailedToConvertWindowsString,
     #[error("A Windows API call failed: {0}")]
@@ -116,6 +119,48 @@ impl Window {
         target_window.map_or_else(|| Err(Error::NotFound(String::from(title))), Ok)
     }
 
+    /// Finds the first capturable window owned by the given process.
+    ///
+    /// # Arguments
+    ///
+    /// * `pid` - The ID of the process that owns the window.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::ProcessNotFound` if the process has no capturable window.
+    #[inline]
+    pub fn from_pid(pid: u32) -> Result<Self, Error> {
+        Self::enumerate()?
+            .into_iter()
+            .find(|window| window.process_id().is_ok_and(|id| id == pid))
+            .ok_or_else(|| Error::ProcessNotFound(pid.to_string()))
+    }
+
+    /// Finds the first capturable window owned by a process with the given executable name.
+    ///
+    /// The comparison is case-insensitive and the `.exe` extension is optional.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The executable name of the process, e.g. `MapleStory.exe`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::ProcessNotFound` if no matching process has a capturable window.
+    #[inline]
+    pub fn from_process_name(name: &str) -> Result<Self, Error> {
+        let strip_exe = |name: &str| {
+            let name = name.to_ascii_lowercase();
+            name.strip_suffix(".exe").map(str::to_owned).unwrap_or(name)
+        };
+        let target = strip_exe(name);
+
+        Self::enumerate()?
+            .into_iter()
+            .find(|window| window.process_name().is_ok_and(|process| strip_exe(&process) == target))
+            .ok_or_else(|| Error::ProcessNotFound(String::from(name)))
+    }
+
     /// Returns the title of the window.
     ///
     /// # Errors
@@ -246,6 +291,22 @@ impl Window {
         Ok(actual_title_height as u32)
     }
 
+    /// Excludes or re-includes the window from screen capture.
+    ///
+    /// Excluded windows never appear in Graphics Capture or DXGI frames. Only windows owned by
+    /// the current process can be excluded.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::WindowsError` if the display affinity cannot be changed.
+    #[inline]
+    pub fn set_excluded_from_capture(&self, excluded: bool) -> Result<(), Error> {
+        let affinity = if excluded { WDA_EXCLUDEFROMCAPTURE } else { WDA_NONE };
+        unsafe { SetWindowDisplayAffinity(self.window, affinity) }?;
+
+        Ok(())
+    }
+
     /// Checks if the window is a valid target for capture.
     ///
     /// # Returns
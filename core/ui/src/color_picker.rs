@@ -0,0 +1,181 @@
+//! Color picker on the minimap preview, reading the captured pixel under a click to set up color
+//! probes and HSV detectors without guessing values
+
+use iced::widget::{button, column, container, row, text, text_input};
+use iced::{Color, Element, Length, Point, Size, Theme};
+use interface::services::frame_analyzer::pixel_bgra;
+use interface::services::{rgb_to_hsv, CapturedFrame, Probe, ProbeCondition, ProbeConfig, Rect};
+
+use crate::game_input::GameInput;
+use crate::notifications::Notifications;
+use crate::roi_selection::fitted;
+
+const SWATCH_SIZE: f32 = 24.0;
+
+/// Tolerance of new probes until changed
+const DEFAULT_TOLERANCE: u8 = 10;
+
+#[derive(Debug, Clone)]
+pub enum PickerMessage {
+    NameEdited(String),
+    ToleranceEdited(String),
+    /// Save the picked pixel as a color probe
+    AddProbe,
+}
+
+/// A pixel picked off the captured frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickedColor {
+    /// Position in frame pixels
+    pub x: i32,
+    pub y: i32,
+    pub bgra: [u8; 4],
+    /// OpenCV scale, H 0 - 179
+    pub hsv: [u8; 3],
+}
+
+impl PickedColor {
+    /// The pixel of `frame` under `point` of a `size` preview, which shows the `roi` of the frame
+    /// or all of it scaled to fit
+    pub fn at(frame: &CapturedFrame, roi: Option<Rect>, point: Point, size: Size) -> Option<Self> {
+        let (width, height) = (frame.width as i32, frame.height as i32);
+        let region = roi
+            .and_then(|roi| roi.clamp_to(width, height))
+            .unwrap_or_else(|| Rect::new(0, 0, width, height));
+        let fitted = fitted(size, Size::new(region.width as f32, region.height as f32))?;
+        // Clicks on the bars around the fitted image pick nothing
+        if !fitted.contains(point) {
+            return None;
+        }
+        let x = ((point.x - fitted.x) / fitted.width * region.width as f32) as i32;
+        let y = ((point.y - fitted.y) / fitted.height * region.height as f32) as i32;
+        let x = region.x + x.min(region.width - 1);
+        let y = region.y + y.min(region.height - 1);
+        let bgra = pixel_bgra(frame, x, y)?;
+        Some(Self {
+            x,
+            y,
+            bgra,
+            hsv: rgb_to_hsv(rgb(bgra)),
+        })
+    }
+}
+
+fn rgb([b, g, r, _]: [u8; 4]) -> [u8; 3] {
+    [r, g, b]
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ColorPicker {
+    active: bool,
+    picked: Option<PickedColor>,
+    name: String,
+    tolerance: String,
+}
+
+impl ColorPicker {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+        if !active {
+            self.picked = None;
+        }
+    }
+
+    pub fn picked(&mut self, picked: PickedColor) {
+        self.name = format!("color_{}_{}", picked.x, picked.y);
+        if self.tolerance.is_empty() {
+            self.tolerance = DEFAULT_TOLERANCE.to_string();
+        }
+        self.picked = Some(picked);
+    }
+
+    /// Handle `message`, new probes are also handed to the scripts of `input`
+    pub fn update(&mut self, message: PickerMessage, input: Option<&GameInput>, notifications: &mut Notifications) {
+        match message {
+            PickerMessage::NameEdited(name) => self.name = name,
+            PickerMessage::ToleranceEdited(tolerance) => self.tolerance = tolerance,
+            PickerMessage::AddProbe => match self.add_probe() {
+                Ok(probe) => {
+                    notifications.info(format!("Probe {} added at {}, {}", probe.name, probe.x, probe.y));
+                    if let Some(input) = input {
+                        input.add_probe(probe);
+                    }
+                }
+                Err(e) => notifications.error(e),
+            },
+        }
+    }
+
+    /// Save a color probe matching the picked pixel
+    fn add_probe(&self) -> Result<Probe, String> {
+        let picked = self.picked.ok_or("No color picked")?;
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err("Probe name is empty".to_string());
+        }
+        let tolerance = self
+            .tolerance
+            .trim()
+            .parse()
+            .map_err(|_| format!("Tolerance must be between 0 and 255, not {:?}", self.tolerance))?;
+        let probe = Probe::new(
+            name,
+            picked.x,
+            picked.y,
+            ProbeCondition::Color {
+                rgb: rgb(picked.bgra),
+                tolerance,
+            },
+        );
+        let mut config = ProbeConfig::load();
+        config.insert(probe.clone());
+        config.save()?;
+        Ok(probe)
+    }
+
+    pub fn view(&self) -> Element<'_, PickerMessage> {
+        let Some(picked) = self.picked else {
+            return text("Click the preview to pick a pixel").size(14).into();
+        };
+        let [b, g, r, a] = picked.bgra;
+        let [h, s, v] = picked.hsv;
+        let swatch = container(text(""))
+            .width(Length::Fixed(SWATCH_SIZE))
+            .height(Length::Fixed(SWATCH_SIZE))
+            .style(move |_theme: &Theme| container::Style {
+                background: Some(iced::Background::Color(Color::from_rgb8(r, g, b))),
+                border: iced::Border {
+                    color: Color::from_rgb(0.6, 0.6, 0.6),
+                    width: 1.0,
+                    radius: 3.0.into(),
+                },
+                ..Default::default()
+            });
+        column![
+            row![
+                swatch,
+                column![
+                    text(format!("Frame pixel {}, {}", picked.x, picked.y)).size(14),
+                    text(format!("BGRA {} {} {} {} · HSV {} {} {}", b, g, r, a, h, s, v)).size(14),
+                ],
+            ]
+            .spacing(10),
+            row![
+                text_input("Probe name", &self.name)
+                    .on_input(PickerMessage::NameEdited)
+                    .width(Length::Fill),
+                text_input("Tolerance", &self.tolerance)
+                    .on_input(PickerMessage::ToleranceEdited)
+                    .width(Length::Fixed(60.0)),
+            ]
+            .spacing(5),
+            button("Add Color Probe").on_press(PickerMessage::AddProbe).width(Length::Fill),
+        ]
+        .spacing(5)
+        .into()
+    }
+}
@@ -0,0 +1,131 @@
+//! Input sent to the captured window, shared by the scripts and macros tabs
+
+use std::sync::Arc;
+
+use interface::services::{
+    AutomationConfig, AutomationEngine, FrameAnalyzer, GraphicsCaptureService, InputMacro, InputRecorder,
+    InputScheduler, MacroPlayer, MinimapServiceV2, Probe, ProbeConfig, ProbeService, RouteConfig, RouteService, Script,
+    ScriptHost, ScriptService,
+};
+use interface::ConfigWatcher;
+use platforms::input::{InputBackendKind, InputKind};
+use platforms::Window;
+
+/// Scripts, macro recording and playback for one window
+///
+/// The input scheduler, the frames and the saved probes scripts read are started on first use
+/// and stopped with [`GameInput::shutdown`].
+#[derive(Clone)]
+pub struct GameInput {
+    hwnd: isize,
+    window: Window,
+    scheduler: Arc<InputScheduler>,
+    frames: FrameAnalyzer,
+    probes: ProbeService,
+    scripts: ScriptService,
+    recorder: InputRecorder,
+}
+
+impl GameInput {
+    /// Input for the window with the raw handle `hwnd`
+    pub fn new(
+        hwnd: isize,
+        graphics_service: Arc<GraphicsCaptureService>,
+        minimap: MinimapServiceV2,
+        config: &ConfigWatcher,
+    ) -> Self {
+        let window = Window::from_raw_hwnd(hwnd);
+        let scheduler = Arc::new(InputScheduler::new(window, InputKind::Focused, InputBackendKind::default()));
+        scheduler.follow_config(config.subscribe());
+        let probes = ProbeService::with_config(graphics_service.clone(), &ProbeConfig::load());
+        // Drawn on the detection overlay, to check they sit where they should
+        minimap.set_overlay_probes(Some(probes.clone()));
+        let minimap = Arc::new(minimap);
+        let route = RouteService::new(minimap.clone(), scheduler.clone(), RouteConfig::default());
+        let automation =
+            AutomationEngine::new(graphics_service.clone(), minimap, scheduler.clone(), route, AutomationConfig::load());
+        let frames = FrameAnalyzer::new(graphics_service);
+        let scripts = ScriptService::new(
+            ScriptHost::new(frames.clone(), automation, scheduler.clone()).with_probes(probes.clone()),
+        );
+        Self {
+            hwnd,
+            window,
+            scheduler,
+            frames,
+            probes,
+            scripts,
+            recorder: InputRecorder::new(),
+        }
+    }
+
+    pub fn hwnd(&self) -> isize {
+        self.hwnd
+    }
+
+    async fn start(&self) -> Result<(), String> {
+        self.frames.start_analyzer().await?;
+        self.probes.start_probes().await?;
+        self.scheduler.start_scheduler().await
+    }
+
+    pub async fn run_script(self, script: Script) -> Result<(), String> {
+        self.scripts.check(&script)?;
+        self.start().await?;
+        self.scripts.run(script).await
+    }
+
+    pub async fn stop_script(self, name: String) -> String {
+        self.scripts.stop_script(&name).await;
+        name
+    }
+
+    pub fn is_script_running(&self, name: &str) -> bool {
+        self.scripts.is_script_running(name)
+    }
+
+    /// Read by scripts with `probe(name)` from now on
+    pub fn add_probe(&self, probe: Probe) {
+        self.probes.add_probe(probe);
+    }
+
+    /// Record the keyboard and mouse, mouse positions relative to the window
+    pub async fn start_recording(self) -> Result<(), String> {
+        self.recorder.start_recording(self.window).await
+    }
+
+    pub async fn stop_recording(self, name: String) -> InputMacro {
+        self.recorder.stop_recording(name).await
+    }
+
+    /// Play `input_macro` with its playback settings until it ends or [`GameInput::stop_playback`]
+    pub async fn play(self, input_macro: InputMacro) -> Result<(), String> {
+        self.start().await?;
+        MacroPlayer::new()
+            .speed(input_macro.playback.speed)
+            .play_repeated(&self.scheduler, &input_macro, input_macro.playback.repeat)
+            .await
+    }
+
+    /// Cancel the macro being played, along with any other queued input
+    pub async fn stop_playback(self) {
+        self.scheduler.cancel_all().await;
+    }
+
+    /// Stop the scripts and any queued input, e.g. on the kill switch
+    pub async fn stop_all(&self) {
+        self.scripts.stop_all_scripts().await;
+        self.scheduler.cancel_all().await;
+    }
+
+    /// Stop everything and release the keys still held
+    pub async fn shutdown(self) {
+        self.stop_all().await;
+        if self.recorder.is_recording().await {
+            self.recorder.stop_recording("").await;
+        }
+        self.scheduler.stop_scheduler().await;
+        self.probes.stop_probes().await;
+        self.frames.stop_analyzer().await;
+    }
+}
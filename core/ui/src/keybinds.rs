@@ -0,0 +1,101 @@
+//! Panel binding the global hotkeys by pressing the key, saved to the active profile
+//!
+//! Keys are read from the keyboard hook rather than iced so they are named like the hotkeys
+//! see them, whichever window has focus.
+
+use iced::widget::{button, column, row, text};
+use iced::{Element, Length, Task};
+use interface::config::{BotConfig, HotkeyAction, Hotkeys};
+use interface::services::hotkeys::next_key_press;
+use interface::ConfigWatcher;
+use platforms::input::KeyKind;
+
+#[derive(Debug, Clone)]
+pub enum KeybindMessage {
+    /// Wait for the key to bind `action` to
+    Bind(HotkeyAction),
+    CancelBinding,
+    KeyPressed(HotkeyAction, Result<KeyKind, String>),
+    Clear(HotkeyAction),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct KeybindEditor {
+    hotkeys: Hotkeys,
+    /// Action waiting for a key press
+    binding: Option<HotkeyAction>,
+    error: Option<String>,
+}
+
+impl KeybindEditor {
+    pub fn from_config(config: &BotConfig) -> Self {
+        Self {
+            hotkeys: config.hotkeys(),
+            ..Self::default()
+        }
+    }
+
+    pub fn update(&mut self, message: KeybindMessage, config: &ConfigWatcher) -> Task<KeybindMessage> {
+        match message {
+            KeybindMessage::Bind(action) => {
+                self.binding = Some(action);
+                self.error = None;
+                return Task::perform(next_key_press(), move |key| KeybindMessage::KeyPressed(action, key));
+            }
+            KeybindMessage::CancelBinding => self.binding = None,
+            // Presses for a binding that was cancelled or replaced are dropped
+            KeybindMessage::KeyPressed(action, key) if self.binding == Some(action) => {
+                self.binding = None;
+                match key {
+                    Ok(KeyKind::Esc) => {}
+                    Ok(key) => self.save(action, Some(key), config),
+                    Err(e) => self.error = Some(e),
+                }
+            }
+            KeybindMessage::KeyPressed(..) => {}
+            KeybindMessage::Clear(action) => self.save(action, None, config),
+        }
+        Task::none()
+    }
+
+    fn save(&mut self, action: HotkeyAction, key: Option<KeyKind>, config: &ConfigWatcher) {
+        match config.update(|config| config.hotkeys_mut().set(action, key)) {
+            Ok(()) => {
+                self.hotkeys = config.current().hotkeys();
+                self.error = None;
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to save hotkey");
+                self.error = Some(e);
+            }
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, KeybindMessage> {
+        let bindings = HotkeyAction::ALL.into_iter().map(|action| {
+            let key = self.hotkeys.get(action);
+            let (label, bind) = if self.binding == Some(action) {
+                ("Press a key (Esc cancels)".to_string(), button("Cancel").on_press(KeybindMessage::CancelBinding))
+            } else {
+                (
+                    key.map_or_else(|| "Unbound".to_string(), |key| format!("{:?}", key)),
+                    button("Bind").on_press(KeybindMessage::Bind(action)),
+                )
+            };
+            row![
+                text(action.to_string()).size(14).width(Length::Fixed(180.0)),
+                text(label).size(14).width(Length::Fixed(200.0)),
+                bind,
+                button("Clear").on_press_maybe(key.map(|_| KeybindMessage::Clear(action))),
+            ]
+            .spacing(10)
+            .into()
+        });
+
+        let mut panel = column![text("Hotkeys").size(18)].extend(bindings).spacing(8);
+        if let Some(e) = &self.error {
+            panel = panel.push(text(e.clone()).size(14).color([0.9, 0.4, 0.4]));
+        }
+        panel.into()
+    }
+}
@@ -0,0 +1,236 @@
+//! Macros tab recording the keyboard and mouse over the captured window and playing saved
+//! macros back, each with its own speed and repeat count
+
+use iced::widget::{button, column, row, scrollable, text, text_input};
+use iced::{Element, Length, Task};
+use interface::services::{InputMacro, MacroPlayback};
+
+use crate::game_input::GameInput;
+
+#[derive(Debug, Clone)]
+pub enum MacroMessage {
+    Refresh,
+    /// Name the next recording is saved under
+    NameEdited(String),
+    Record,
+    RecordingStarted(Result<(), String>),
+    StopRecording,
+    Recorded(InputMacro),
+    Select(String),
+    SpeedEdited(String),
+    RepeatEdited(String),
+    /// Save the speed and repeat count of the selected macro
+    SavePlayback,
+    Play,
+    StopPlayback,
+    Played(Result<(), String>),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MacroPanel {
+    macros: Vec<String>,
+    name: String,
+    recording: bool,
+    selected: Option<InputMacro>,
+    speed: String,
+    repeat: String,
+    playing: bool,
+    /// Outcome of the last action
+    status: Option<Result<String, String>>,
+}
+
+impl MacroPanel {
+    /// Handle `message`, input is recorded and played with `input`, `None` while no window is
+    /// captured
+    pub fn update(&mut self, message: MacroMessage, input: Option<&GameInput>) -> Task<MacroMessage> {
+        match message {
+            MacroMessage::Refresh => {
+                self.macros = InputMacro::list();
+                self.macros.sort();
+            }
+            MacroMessage::NameEdited(name) => self.name = name,
+            MacroMessage::Record => {
+                if let Some(input) = input {
+                    self.recording = true;
+                    return Task::perform(input.clone().start_recording(), MacroMessage::RecordingStarted);
+                }
+            }
+            MacroMessage::RecordingStarted(Ok(())) => {
+                self.status = Some(Ok("Recording, press Stop to save".to_string()));
+            }
+            MacroMessage::RecordingStarted(Err(e)) => {
+                self.recording = false;
+                self.status = Some(Err(e));
+            }
+            MacroMessage::StopRecording => {
+                if let Some(input) = input {
+                    let name = self.name.trim().to_string();
+                    return Task::perform(input.clone().stop_recording(name), MacroMessage::Recorded);
+                }
+            }
+            MacroMessage::Recorded(input_macro) => {
+                self.recording = false;
+                match input_macro.save_named() {
+                    Ok(()) => {
+                        self.status = Some(Ok(format!(
+                            "Saved {} with {} events",
+                            input_macro.name,
+                            input_macro.events.len()
+                        )));
+                        self.select(input_macro);
+                        return self.update(MacroMessage::Refresh, input);
+                    }
+                    Err(e) => self.status = Some(Err(e)),
+                }
+            }
+            MacroMessage::Select(name) => match InputMacro::load_named(&name) {
+                Ok(input_macro) => self.select(input_macro),
+                Err(e) => self.status = Some(Err(e)),
+            },
+            MacroMessage::SpeedEdited(speed) => self.speed = speed,
+            MacroMessage::RepeatEdited(repeat) => self.repeat = repeat,
+            MacroMessage::SavePlayback => {
+                if let Err(e) = self.save_playback() {
+                    self.status = Some(Err(e));
+                }
+            }
+            MacroMessage::Play => {
+                let Some(input) = input else {
+                    return Task::none();
+                };
+                match self.save_playback() {
+                    Ok(input_macro) => {
+                        self.playing = true;
+                        self.status = Some(Ok(format!("Playing {}", input_macro.name)));
+                        return Task::perform(input.clone().play(input_macro), MacroMessage::Played);
+                    }
+                    Err(e) => self.status = Some(Err(e)),
+                }
+            }
+            MacroMessage::StopPlayback => {
+                if let Some(input) = input {
+                    return Task::future(input.clone().stop_playback()).discard();
+                }
+            }
+            MacroMessage::Played(result) => {
+                self.playing = false;
+                self.status = Some(result.map(|()| "Playback finished".to_string()));
+            }
+        }
+        Task::none()
+    }
+
+    fn select(&mut self, input_macro: InputMacro) {
+        self.speed = input_macro.playback.speed.to_string();
+        self.repeat = input_macro.playback.repeat.to_string();
+        self.selected = Some(input_macro);
+    }
+
+    /// Write the edited playback settings into the selected macro's file if they changed
+    fn save_playback(&mut self) -> Result<InputMacro, String> {
+        let Some(selected) = &mut self.selected else {
+            return Err("No macro selected".to_string());
+        };
+        let playback = MacroPlayback {
+            speed: parse(&self.speed, "Speed")?,
+            repeat: parse(&self.repeat, "Repeat count")?,
+        };
+        if playback.speed.is_nan() || playback.speed <= 0.0 {
+            return Err(format!("Speed must be above 0, not {}", playback.speed));
+        }
+        if playback != selected.playback {
+            selected.playback = playback;
+            selected.save_named()?;
+        }
+        Ok(selected.clone())
+    }
+
+    pub fn view(&self, input: Option<&GameInput>) -> Element<'_, MacroMessage> {
+        let record = if self.recording {
+            button("Stop").on_press(MacroMessage::StopRecording)
+        } else {
+            let ready = input.is_some() && !self.name.trim().is_empty();
+            button("Record").on_press_maybe(ready.then_some(MacroMessage::Record))
+        };
+        let recorder = column![
+            text("Record").size(18),
+            row![
+                // Kept while recording, it is what the recording is saved under
+                text_input("Macro name", &self.name)
+                    .on_input_maybe((!self.recording).then_some(MacroMessage::NameEdited))
+                    .width(Length::Fixed(200.0)),
+                record,
+            ]
+            .spacing(10),
+        ]
+        .spacing(10);
+
+        let macros = self.macros.iter().map(|name| {
+            let selected = self.selected.as_ref().is_some_and(|selected| &selected.name == name);
+            button(text(name.clone()).size(14))
+                .on_press(MacroMessage::Select(name.clone()))
+                .style(if selected { button::primary } else { button::text })
+                .width(Length::Fill)
+                .into()
+        });
+        let list = column![
+            row![text("Saved macros").size(18).width(Length::Fill), button("Refresh").on_press(MacroMessage::Refresh)],
+            scrollable(column(macros).spacing(2)).height(Length::Fixed(240.0)),
+        ]
+        .spacing(10)
+        .width(Length::Fixed(300.0));
+
+        let playback: Element<'_, MacroMessage> = match &self.selected {
+            Some(selected) => {
+                let play = if self.playing {
+                    button("Stop").on_press(MacroMessage::StopPlayback)
+                } else {
+                    button("Play").on_press_maybe(input.map(|_| MacroMessage::Play))
+                };
+                column![
+                    text(selected.name.clone()).size(18),
+                    text(format!(
+                        "{} events, {:.1} s",
+                        selected.events.len(),
+                        selected.duration().as_secs_f64()
+                    ))
+                    .size(14),
+                    row![
+                        text("Speed").size(14).width(Length::Fixed(100.0)),
+                        text_input("", &self.speed)
+                            .on_input(MacroMessage::SpeedEdited)
+                            .width(Length::Fixed(100.0)),
+                    ]
+                    .spacing(10),
+                    row![
+                        text("Repeat").size(14).width(Length::Fixed(100.0)),
+                        text_input("", &self.repeat)
+                            .on_input(MacroMessage::RepeatEdited)
+                            .width(Length::Fixed(100.0)),
+                    ]
+                    .spacing(10),
+                    row![button("Save").on_press(MacroMessage::SavePlayback), play].spacing(10),
+                ]
+                .spacing(10)
+                .into()
+            }
+            None => text("Select a macro to play it").size(14).into(),
+        };
+
+        let status = match &self.status {
+            Some(Ok(status)) => text(status.clone()).size(14),
+            Some(Err(e)) => text(e.clone()).size(14).color([0.9, 0.4, 0.4]),
+            None if input.is_none() => text("Start capturing a window to record or play macros").size(14),
+            None => text("").size(14),
+        };
+
+        column![recorder, row![list, playback].spacing(40), status].spacing(20).into()
+    }
+}
+
+fn parse<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("{} must be a number, not {:?}", name, value))
+}
@@ -1,18 +1,124 @@
-use iced::widget::{button, column, container, pick_list, text, image, row};
-use iced::{Element, Fill, Length, Task, Theme, Subscription};
-use interface::{list_window_handles, services::{GraphicsCaptureService, MinimapServiceV2, ServiceState}};
+use iced::widget::{button, column, container, mouse_area, pick_list, scrollable, stack, text, image, row, Space};
+use iced::{mouse, window, Element, Fill, Length, Point, Size, Task, Theme, Subscription};
+use interface::{config::{CaptureBackend, HotkeyAction}, exclude_own_windows_from_capture, kill_switch, list_window_handles, BotEvent, ConfigWatcher, EventBus, FrameAnalyzer, services::{ChatMonitor, ChatMonitorConfig, GraphicsCaptureService, MapBuilderConfig, MinimapServiceV2, OcrConfig, OcrService, PerformanceStats, PluginContext, PluginRegistry, Scheduler, Service, ServiceManager, ServiceState, SessionStats, SessionStatsConfig, Shutdown}, WindowEntry};
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio_stream::{wrappers::WatchStream, StreamExt};
+use tokio_stream::{wrappers::{BroadcastStream, WatchStream}, StreamExt};
 
-/// Convert JPEG bytes to an iced image handle
-fn jpeg_bytes_to_image_handle(jpeg_bytes: &[u8]) -> image::Handle {
-    image::Handle::from_bytes(jpeg_bytes.to_vec())
+mod color_picker;
+mod game_input;
+mod keybinds;
+mod macros;
+mod metrics_chart;
+mod notifications;
+mod preview_zoom;
+mod roi_selection;
+mod scripts;
+mod settings;
+
+use color_picker::{ColorPicker, PickedColor, PickerMessage};
+use game_input::GameInput;
+use keybinds::{KeybindEditor, KeybindMessage};
+use macros::{MacroMessage, MacroPanel};
+use metrics_chart::MetricsChart;
+use notifications::{NotificationMessage, Notifications};
+use preview_zoom::{PreviewZoom, ZoomMessage};
+use roi_selection::RoiSelection;
+use scripts::{ScriptMessage, ScriptPanel};
+use settings::{Settings, SettingsMessage};
+
+/// Width the explored map is scaled down to for display
+const MAP_PREVIEW_WIDTH: i32 = 800;
+
+/// Size of the minimap preview widget
+const PREVIEW_SIZE: Size = Size::new(400.0, 225.0);
+
+/// Size of the window icons in the window picker
+const WINDOW_ICON_SIZE: f32 = 16.0;
+
+/// Window size outside of the compact overlay, iced's default
+const WINDOW_SIZE: Size = Size::new(1024.0, 768.0);
+
+/// Window size of the compact overlay and of the minimap preview in it
+const COMPACT_SIZE: Size = Size::new(260.0, 180.0);
+const COMPACT_PREVIEW_SIZE: Size = Size::new(250.0, 140.0);
+
+/// Space between the compact overlay and the screen corner
+const COMPACT_MARGIN: f32 = 16.0;
+
+/// Turn the window into a small borderless always-on-top overlay in the top right corner of
+/// the screen, or back into the normal window
+fn set_compact(id: window::Id, compact: bool) -> Task<Message> {
+    if !compact {
+        return Task::batch([
+            window::toggle_decorations(id),
+            window::change_level(id, window::Level::Normal),
+            window::resize(id, WINDOW_SIZE),
+        ]);
+    }
+    Task::batch([
+        window::toggle_decorations(id),
+        window::change_level(id, window::Level::AlwaysOnTop),
+        window::resize(id, COMPACT_SIZE),
+        window::monitor_size(id).and_then(move |monitor| {
+            window::move_to(id, Point::new(monitor.width - COMPACT_SIZE.width - COMPACT_MARGIN, COMPACT_MARGIN))
+        }),
+    ])
+}
+
+/// Convert a preview frame, encoded or raw RGBA, to an iced image handle
+fn preview_to_image_handle(preview: &[u8]) -> image::Handle {
+    match interface::services::image_ops::decode_raw_rgba(preview) {
+        Some((width, height, pixels)) => image::Handle::from_rgba(width, height, pixels.to_vec()),
+        None => image::Handle::from_bytes(preview.to_vec()),
+    }
+}
+
+/// Register a service with the manager, a failure only leaves that service unmanaged
+fn register_service(services: &ServiceManager, service: Arc<dyn Service>, notifications: &mut Notifications) {
+    if let Err(e) = services.register(service) {
+        notifications.warning(e);
+    }
+}
+
+/// Views switched between by the tab bar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tab {
+    Main,
+    Settings,
+    Scripts,
+    Macros,
+    Log,
+}
+
+/// Save the next captured frame in `screenshots/`
+async fn screenshot(graphics: Arc<GraphicsCaptureService>) -> Result<PathBuf, String> {
+    if !graphics.is_capturing().await {
+        return Err("Capture is stopped".to_string());
+    }
+    let mut frames = graphics.subscribe();
+    let frame = tokio::time::timeout(std::time::Duration::from_secs(2), frames.recv())
+        .await
+        .map_err(|_| "No frame captured in time".to_string())?
+        .map_err(|e| format!("Failed to receive frame: {}", e))?;
+    tokio::task::spawn_blocking(move || interface::services::frame_analyzer::save_screenshot(&frame, "hotkey"))
+        .await
+        .map_err(|e| format!("Screenshot task failed: {}", e))?
 }
 
+/// Entry of the profile picker selecting the base settings
+const BASE_PROFILE: &str = "Base settings";
+
 fn main() -> iced::Result {
+    let _log = interface::init_logging().map_err(|e| eprintln!("{}", e)).ok();
+    // Installs the keyboard hook used by the kill switch hotkey
+    interface::init();
+
     iced::application("Starry Bot", StarryApp::update, StarryApp::view)
         .subscription(StarryApp::subscription)
         .theme(|_| Theme::Dark)
+        // Closing runs the shutdown first, see `Message::CloseRequested`
+        .exit_on_close_request(false)
         .run_with(|| (StarryApp::default(), Task::perform(async { 
             list_window_handles() 
         }, Message::WindowsRefreshed)))
@@ -20,48 +126,218 @@ fn main() -> iced::Result {
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    WindowSelected(String),
+    WindowSelected(WindowEntry),
     RefreshWindows,
     StartCapture,
     StopCapture,
-    WindowsRefreshed(Vec<String>),
+    WindowsRefreshed(Vec<WindowEntry>),
     CaptureStarted,
     CaptureStopped,
     CaptureError(String),
     FrameReceived(Option<Vec<u8>>),
-    CheckServiceStatus,
+    PreviewZoom(ZoomMessage),
+    TogglePicker,
+    Picker(PickerMessage),
+    ToggleFramePreview,
+    ToggleDetectionOverlay,
+    FramePreviewReceived(Option<Vec<u8>>),
     ServiceStatusChecked(ServiceState),
     ShowMetrics,
-    MetricsReceived(Option<String>),
+    MetricsReceived(PerformanceStats),
+    PerformanceReceived(Option<PerformanceStats>),
     UpdateMetrics,
     DxgiModeResult(Result<(), String>),
+    KillSwitch,
+    ToggleMap,
+    MapToggled(Result<bool, String>),
+    UpdateMap,
+    MapPreviewReceived(Option<Vec<u8>>),
+    BotEventReceived(BotEvent),
+    ServicesStatusReceived(String),
+    ProfileSelected(String),
+    SelectRoi,
+    CancelRoiSelection,
+    PreviewCursorMoved(Point),
+    PreviewPressed,
+    PreviewReleased,
+    ShowTab(Tab),
+    ToggleCompact,
+    DragWindow,
+    Settings(SettingsMessage),
+    Keybinds(KeybindMessage),
+    Scripts(ScriptMessage),
+    Macros(MacroMessage),
+    Notifications(NotificationMessage),
+    ScreenshotSaved(Result<PathBuf, String>),
+    AutomationPaused(Result<bool, String>),
+    CloseRequested,
+    ShutdownFinished(Result<(), String>),
 }
 
 pub struct StarryApp {
+    config: ConfigWatcher,
     graphics_service: Arc<GraphicsCaptureService>,
     minimap_service: MinimapServiceV2,
-    available_windows: Vec<String>,
-    selected_window: Option<String>,
+    available_windows: Vec<WindowEntry>,
+    /// Icons of `available_windows`, uploaded once per refresh
+    window_icons: Vec<Option<image::Handle>>,
+    selected_window: Option<WindowEntry>,
     service_state: ServiceState,
     current_frame: Option<image::Handle>,
-    error_message: Option<String>,
+    preview_zoom: PreviewZoom,
+    // Clicking the preview picks the color of the captured pixel under the cursor
+    color_picker: ColorPicker,
+    // Full frame with the ROI outlined, shown next to the minimap while enabled
+    show_frame_preview: bool,
+    frame_preview: Option<image::Handle>,
+    // Detections drawn onto the minimap preview, kept in the saved pipeline
+    detection_overlay: bool,
+    // Set while the minimap ROI is dragged over the preview
+    roi_selection: Option<RoiSelection>,
+    // Errors and warnings shown as toasts, all of them kept for the log tab
+    notifications: Notifications,
     metrics_text: Option<String>,
+    metrics_chart: MetricsChart,
+    mapping: bool,
+    map_preview: Option<image::Handle>,
+    session_stats: SessionStats,
+    session_text: Option<String>,
+    chat_alert: Option<String>,
+    // Services started alongside capture, in dependency order
+    services: Arc<ServiceManager>,
+    services_text: Option<String>,
+    // Game profiles of config.toml, the base settings first
+    profiles: Vec<String>,
+    selected_profile: String,
+    tab: Tab,
+    // Showing only the minimap and status in a small always-on-top window
+    compact: bool,
+    // Form of the settings tab, refilled from the config whenever the tab is opened
+    settings: Settings,
+    keybinds: KeybindEditor,
+    // Input for scripts and macros, sent to the captured window
+    game_input: Option<GameInput>,
+    scripts: ScriptPanel,
+    macros: MacroPanel,
+    // Automation services stopped by the hotkey while capture keeps running
+    automation_paused: bool,
+    shutting_down: bool,
 }
 
 impl Default for StarryApp {
     fn default() -> Self {
-        let graphics_service = Arc::new(GraphicsCaptureService::new());
-        let minimap_service = MinimapServiceV2::new(graphics_service.clone());
-        
+        // Edits of config.toml apply to the running services without restarting capture
+        let config = ConfigWatcher::new();
+        let bot_config = config.current();
+        let graphics_service = Arc::new(GraphicsCaptureService::with_target_fps(bot_config.capture.fps));
+        graphics_service.follow_config(config.subscribe());
+        let minimap_service = MinimapServiceV2::with_config(graphics_service.clone(), &bot_config);
+        minimap_service.follow_config(config.subscribe());
+        interface::services::thread_tuning::follow_config(config.subscribe());
+        interface::services::MemoryBudget::global().follow_config(config.subscribe());
+        interface::services::LowPowerMode::global().follow_config(config.subscribe());
+        interface::services::hotkeys::follow_config(config.subscribe());
+        let ocr_service = OcrService::new(graphics_service.clone(), OcrConfig::default());
+        let session_stats = SessionStats::new(ocr_service.clone(), SessionStatsConfig::load());
+        let chat_monitor = ChatMonitor::new(ocr_service.clone(), ChatMonitorConfig::load());
+
+        // Failures while setting up, shown once the window opens
+        let mut notifications = Notifications::default();
+
+        // Only run what is configured, OCR is only needed for the chat box and counters
+        let services = Arc::new(ServiceManager::new());
+        let watch_chat = chat_monitor.config().region.is_some();
+        let count_stats = !session_stats.config().counters.is_empty();
+        if watch_chat || count_stats {
+            register_service(&services, Arc::new(ocr_service), &mut notifications);
+        }
+        if watch_chat {
+            register_service(&services, Arc::new(chat_monitor.clone()), &mut notifications);
+        }
+        if count_stats {
+            register_service(&services, Arc::new(session_stats.clone()), &mut notifications);
+        }
+        #[cfg(feature = "history")]
+        match interface::services::SessionStore::open(&interface::services::SessionStore::path()) {
+            Ok(store) => {
+                let history = interface::services::SessionHistory::new(Arc::new(store), minimap_service.clone());
+                let history = if count_stats { history.with_stats(session_stats.clone()) } else { history };
+                register_service(&services, Arc::new(history), &mut notifications);
+            }
+            Err(e) => notifications.warning(e),
+        }
+        if !bot_config.scheduler.jobs.is_empty() {
+            let frames = FrameAnalyzer::new(graphics_service.clone());
+            register_service(&services, Arc::new(frames.clone()), &mut notifications);
+            let scheduler = Scheduler::new(bot_config.scheduler.clone()).with_frames(frames);
+            scheduler.follow_config(config.subscribe());
+            register_service(&services, Arc::new(scheduler), &mut notifications);
+        }
+        #[cfg(feature = "notifications")]
+        {
+            let notifier_config = interface::services::NotifierConfig::load();
+            if notifier_config.is_enabled() {
+                match interface::services::Notifier::new(notifier_config) {
+                    Ok(notifier) => register_service(
+                        &services,
+                        Arc::new(notifier.with_minimap(minimap_service.get_frame_receiver())),
+                        &mut notifications,
+                    ),
+                    Err(e) => notifications.warning(e),
+                }
+            }
+        }
+        #[cfg(feature = "dynamic-plugins")]
+        PluginRegistry::global().load_dir(&interface::config_dir().join("plugins"));
+        if let Err(e) = PluginRegistry::global().init_all(&PluginContext::new(graphics_service.clone()), &services) {
+            notifications.warning(e);
+        }
+
+        let profiles = std::iter::once(BASE_PROFILE.to_string())
+            .chain(bot_config.profiles.keys().cloned())
+            .collect();
+        let selected_profile = bot_config
+            .active_profile()
+            .and(bot_config.profile.clone())
+            .unwrap_or_else(|| BASE_PROFILE.to_string());
+        let detection_overlay = minimap_service.debug_overlay();
+
         Self {
+            config,
             graphics_service,
             minimap_service,
             available_windows: Vec::new(),
+            window_icons: Vec::new(),
             selected_window: None,
             service_state: ServiceState::Stopped,
             current_frame: None,
-            error_message: None,
+            preview_zoom: PreviewZoom::default(),
+            color_picker: ColorPicker::default(),
+            show_frame_preview: false,
+            frame_preview: None,
+            detection_overlay,
+            roi_selection: None,
+            notifications,
             metrics_text: None,
+            metrics_chart: MetricsChart::default(),
+            mapping: false,
+            map_preview: None,
+            session_stats,
+            session_text: None,
+            chat_alert: None,
+            services,
+            services_text: None,
+            profiles,
+            selected_profile,
+            tab: Tab::Main,
+            compact: false,
+            settings: Settings::from_config(&bot_config),
+            keybinds: KeybindEditor::from_config(&bot_config),
+            game_input: None,
+            scripts: ScriptPanel::default(),
+            macros: MacroPanel::default(),
+            automation_paused: false,
+            shutting_down: false,
         }
     }
 }
@@ -78,39 +354,67 @@ impl StarryApp {
                 )
             },
             Message::WindowsRefreshed(windows) => {
+                self.window_icons = windows
+                    .iter()
+                    .map(|window| {
+                        let icon = window.icon.as_ref()?;
+                        Some(image::Handle::from_rgba(icon.width, icon.height, icon.rgba.clone()))
+                    })
+                    .collect();
                 self.available_windows = windows;
+
+                // Keep our own window out of the captured frames
+                if let Err(e) = exclude_own_windows_from_capture() {
+                    self.notifications.warning(e);
+                }
                 
-                // Try to automatically select a Unity window (or any predefined window)
-                let predefined_windows = ["BPSR"];
-                for predefined in &predefined_windows {
-                    if let Some(window) = self.available_windows.iter()
-                        .find(|w| w.to_lowercase().contains(&predefined.to_lowercase())) {
-                        println!("🎯 Auto-selecting window: {}", window);
-                        self.selected_window = Some(window.clone());
-                        self.error_message = None;
-                        let service = self.minimap_service.clone();
-                        let window_title = window.clone();
-                        return Task::perform(
-                            async move {
-                                match service.set_window(window_title).await {
-                                    Ok(_) => Message::CaptureStarted,
-                                    Err(e) => Message::CaptureError(e),
-                                }
-                            },
-                            |result| result,
-                        );
-                    }
+                // Automatically select the window matching the configured pattern
+                let window_config = self.config.current().window().clone();
+                if !window_config.auto_select || window_config.pattern.is_empty() {
+                    return Task::none();
+                }
+                let pattern = window_config.pattern.to_lowercase();
+                if let Some(window) = self.available_windows.iter()
+                    .find(|w| w.title.to_lowercase().contains(&pattern)) {
+                    tracing::info!(window = %window, "Auto-selecting window");
+                    self.selected_window = Some(window.clone());
+                    let service = self.minimap_service.clone();
+                    let window = window.clone();
+                    return Task::perform(
+                        async move {
+                            match service.set_window_target(window.target(), window.title).await {
+                                Ok(_) => Message::CaptureStarted,
+                                Err(e) => Message::CaptureError(e),
+                            }
+                        },
+                        |result| result,
+                    );
                 }
-                println!("❌ No matching window found for: {:?}", predefined_windows);
+                tracing::warn!(pattern = %window_config.pattern, "No matching window found");
                 Task::none()
             },
+            Message::ProfileSelected(profile) => {
+                let name = (profile != BASE_PROFILE).then(|| profile.clone());
+                if let Err(e) = self.config.update(|config| config.profile = name) {
+                    self.notifications.error(e);
+                    return Task::none();
+                }
+                self.selected_profile = profile;
+                // Capture the window of the new profile
+                self.update(Message::RefreshWindows)
+            },
             Message::WindowSelected(window) => {
                 self.selected_window = Some(window.clone());
-                self.error_message = None; // Clear any previous errors
+
+                // Remember the choice for the next launch
+                if let Err(e) = self.config.update(|config| config.window_mut().pattern = window.title.clone()) {
+                    self.notifications.warning(e);
+                }
+
                 let service = self.minimap_service.clone();
                 Task::perform(
                     async move {
-                        match service.set_window(window).await {
+                        match service.set_window_target(window.target(), window.title).await {
                             Ok(_) => Message::CaptureStarted,
                             Err(e) => Message::CaptureError(e),
                         }
@@ -119,14 +423,13 @@ impl StarryApp {
                 )
             },
             Message::StartCapture => {
-                if let Some(window_title) = &self.selected_window {
+                if let Some(window) = &self.selected_window {
                     self.service_state = ServiceState::Starting;
-                    self.error_message = None; // Clear any previous errors
                     let service = self.minimap_service.clone();
-                    let window_title = window_title.clone();
+                    let window = window.clone();
                     Task::perform(
                         async move {
-                            match service.set_window(window_title).await {
+                            match service.set_window_target(window.target(), window.title).await {
                                 Ok(_) => Message::CaptureStarted,
                                 Err(e) => Message::CaptureError(e),
                             }
@@ -134,7 +437,7 @@ impl StarryApp {
                         |result| result,
                     )
                 } else {
-                    self.error_message = Some("No window selected".to_string());
+                    self.notifications.warning("No window selected");
                     Task::none()
                 }
             },
@@ -143,8 +446,11 @@ impl StarryApp {
                 if self.service_state != ServiceState::Stopping {
                     self.service_state = ServiceState::Stopping;
                     let service = self.minimap_service.clone();
+                    let services = self.services.clone();
                     Task::perform(
                         async move {
+                            // Services that fail to stop are reported on the event bus
+                            let _ = services.stop_all().await;
                             match service.stop_capture().await {
                                 Ok(_) => Message::CaptureStopped,
                                 Err(e) => Message::CaptureError(e),
@@ -158,73 +464,167 @@ impl StarryApp {
             },
             Message::CaptureStarted => {
                 self.service_state = ServiceState::Running;
-                self.error_message = None;
-                
-                println!("✅ Capture started successfully!");
-                
-                // Automatically enable high-performance DXGI mode
+                self.automation_paused = false;
+                self.metrics_chart.clear();
+
+                tracing::info!("Capture started");
+
+                // Switch to high-performance DXGI mode unless the config asks for WGC only
+                let use_dxgi = self.config.current().capture.backend != CaptureBackend::WindowsGraphicsCapture;
                 let service = self.minimap_service.clone();
-                let service2 = self.minimap_service.clone();
+                let services = self.services.clone();
+                // Scripts and macros send their input to the captured window
+                let game_input = match &self.selected_window {
+                    Some(window) if self.game_input.as_ref().map(GameInput::hwnd) != Some(window.hwnd) => {
+                        let input = GameInput::new(
+                            window.hwnd,
+                            self.graphics_service.clone(),
+                            self.minimap_service.clone(),
+                            &self.config,
+                        );
+                        match self.game_input.replace(input) {
+                            Some(previous) => Task::future(previous.shutdown()).discard(),
+                            None => Task::none(),
+                        }
+                    }
+                    _ => Task::none(),
+                };
                 Task::batch([
-                    // Enable DXGI mode for high performance
+                    game_input,
+                    // Chat monitor and session stats, after the OCR they read from
                     Task::perform(
                         async move {
-                            match service.enable_dxgi_mode().await {
-                                Ok(_) => {
-                                    println!("🚀 High-performance DXGI mode enabled automatically");
-                                    Ok(())
-                                }
-                                Err(e) => {
-                                    println!("⚠️  DXGI mode failed, using standard capture: {}", e);
-                                    Err(e)
-                                }
-                            }
+                            // Failures are reported on the event bus
+                            let _ = services.start_all().await;
                         },
-                        Message::DxgiModeResult,
-                    ),
-                    // Check service status
-                    Task::perform(
-                        async move {
-                            service2.get_service_state().await
-                        },
-                        Message::ServiceStatusChecked,
+                        |_| Message::UpdateMetrics,
                     ),
+                    // Enable DXGI mode for high performance
+                    if use_dxgi {
+                        Task::perform(
+                            // Logged once the result arrives
+                            async move { service.enable_dxgi_mode().await },
+                            Message::DxgiModeResult,
+                        )
+                    } else {
+                        Task::none()
+                    },
                 ])
             },
             Message::CaptureStopped => {
                 self.service_state = ServiceState::Stopped;
+                self.cancel_roi_selection();
+                self.color_picker.set_active(false);
                 self.current_frame = None;
-                self.error_message = None;
+                self.frame_preview = None;
                 Task::none()
             },
             Message::CaptureError(error) => {
                 self.service_state = ServiceState::Stopped;
+                self.cancel_roi_selection();
+                self.color_picker.set_active(false);
                 self.current_frame = None;
-                self.error_message = Some(error);
+                self.frame_preview = None;
+                self.notifications.error(error);
                 Task::none()
             },
-            Message::CheckServiceStatus => {
-                let service = self.minimap_service.clone();
-                Task::perform(
-                    async move {
-                        service.get_service_state().await
-                    },
-                    Message::ServiceStatusChecked,
-                )
-            },
             Message::ServiceStatusChecked(service_state) => {
                 // Synchronize UI state with actual service state
                 self.service_state = service_state;
                 Task::none()
             },
+            Message::SelectRoi => {
+                // Show the full frame to drag over
+                let previous = self.minimap_service.roi();
+                self.minimap_service.clear_roi();
+                self.roi_selection = Some(RoiSelection::new(previous));
+                self.color_picker.set_active(false);
+                Task::none()
+            },
+            Message::CancelRoiSelection => {
+                self.cancel_roi_selection();
+                Task::none()
+            },
+            Message::PreviewCursorMoved(position) => {
+                if let Some(selection) = &mut self.roi_selection {
+                    selection.cursor_moved(position);
+                }
+                Task::none()
+            },
+            Message::PreviewPressed => {
+                if let Some(selection) = &mut self.roi_selection {
+                    selection.pressed();
+                }
+                Task::none()
+            },
+            Message::PreviewReleased => {
+                let frame = self.graphics_service
+                    .subscribe_latest()
+                    .borrow()
+                    .as_ref()
+                    .map(|frame| (frame.width, frame.height));
+                let roi = match (&mut self.roi_selection, frame) {
+                    (Some(selection), Some(frame)) => selection.released(PREVIEW_SIZE, frame),
+                    _ => None,
+                };
+                // Keep selecting after a stray click
+                let Some(roi) = roi else {
+                    return Task::none();
+                };
+                self.roi_selection = None;
+                match self.minimap_service.set_roi(roi) {
+                    Ok(()) => {
+                        tracing::info!(x = roi.x, y = roi.y, width = roi.width, height = roi.height, "Minimap ROI set");
+                        if let Err(e) = self.config.update(|config| *config.roi_mut() = Some(roi)) {
+                            self.notifications.warning(e);
+                        }
+                    }
+                    Err(e) => self.notifications.error(e),
+                }
+                Task::none()
+            },
             Message::FrameReceived(frame_data) => {
-                if let Some(jpeg_bytes) = frame_data {
-                    self.current_frame = Some(jpeg_bytes_to_image_handle(&jpeg_bytes));
+                if let Some(preview) = frame_data {
+                    self.current_frame = Some(preview_to_image_handle(&preview));
                 } else {
                     self.current_frame = None;
                 }
                 Task::none()
             },
+            Message::PreviewZoom(message) => {
+                match message {
+                    ZoomMessage::Clicked(point) if self.color_picker.is_active() => self.pick_color(point),
+                    message => self.preview_zoom.update(message, PREVIEW_SIZE),
+                }
+                Task::none()
+            },
+            Message::TogglePicker => {
+                self.color_picker.set_active(!self.color_picker.is_active());
+                Task::none()
+            },
+            Message::Picker(message) => {
+                self.color_picker.update(message, self.game_input.as_ref(), &mut self.notifications);
+                Task::none()
+            },
+            Message::ToggleFramePreview => {
+                self.show_frame_preview = !self.show_frame_preview;
+                self.minimap_service.set_frame_preview(self.show_frame_preview);
+                if !self.show_frame_preview {
+                    self.frame_preview = None;
+                }
+                Task::none()
+            },
+            Message::ToggleDetectionOverlay => {
+                match self.minimap_service.set_debug_overlay(!self.detection_overlay) {
+                    Ok(()) => self.detection_overlay = !self.detection_overlay,
+                    Err(e) => self.notifications.error(e),
+                }
+                Task::none()
+            },
+            Message::FramePreviewReceived(preview) => {
+                self.frame_preview = preview.map(|preview| preview_to_image_handle(&preview));
+                Task::none()
+            },
             Message::ShowMetrics => {
                 let service = self.minimap_service.clone();
                 Task::perform(
@@ -234,45 +634,312 @@ impl StarryApp {
                     Message::MetricsReceived,
                 )
             },
-            Message::MetricsReceived(metrics) => {
-                if let Some(metrics_text) = metrics {
-                    // Store metrics for display in debug panel instead of printing to console
-                    let graphics_metrics = self.graphics_service.get_metrics();
-                    let combined_metrics = format!("{}\n\n📊 Graphics Service:\n{}", metrics_text, graphics_metrics);
-                    self.metrics_text = Some(combined_metrics);
+            Message::MetricsReceived(stats) => {
+                // Store metrics for display in debug panel instead of printing to console
+                self.metrics_text = Some(stats.to_string());
+                Task::none()
+            },
+            Message::PerformanceReceived(stats) => {
+                if let Some(stats) = stats {
+                    self.metrics_chart.push(&stats);
+                    self.metrics_text = Some(stats.to_string());
                 }
                 Task::none()
             },
             Message::UpdateMetrics => {
-                // Auto-update metrics every 3-5 seconds
-                if self.service_state == ServiceState::Running {
-                    let service = self.minimap_service.clone();
-                    Task::perform(
-                        async move {
-                            service.get_performance_metrics()
-                        },
-                        Message::MetricsReceived,
-                    )
-                } else {
-                    Task::none()
+                if !self.session_stats.config().counters.is_empty() {
+                    self.session_text = Some(self.session_stats.report().to_string());
                 }
+
+                // Performance arrives every second through `Message::PerformanceReceived`
+                let services = self.services.clone();
+                Task::perform(
+                    async move {
+                        services.status().await.to_string()
+                    },
+                    Message::ServicesStatusReceived,
+                )
             },
-            Message::DxgiModeResult(result) => {
+            Message::ServicesStatusReceived(status) => {
+                self.services_text = (!status.is_empty()).then_some(status);
+                Task::none()
+            },
+            Message::KillSwitch => {
+                tracing::warn!("Kill switch pressed, stopping all services");
+                self.service_state = ServiceState::Stopping;
+                let service = self.minimap_service.clone();
+                let services = self.services.clone();
+                let game_input = self.game_input.clone();
+                Task::perform(
+                    async move {
+                        if let Some(game_input) = game_input {
+                            game_input.stop_all().await;
+                        }
+                        // Services that fail to stop are reported on the event bus
+                        let _ = services.stop_all().await;
+                        match service.stop_capture().await {
+                            Ok(_) => Message::CaptureError("Stopped by kill switch".to_string()),
+                            Err(e) => Message::CaptureError(e),
+                        }
+                    },
+                    |result| result,
+                )
+            },
+            Message::ShowTab(tab) => {
+                if tab == Tab::Settings && self.tab != Tab::Settings {
+                    let config = self.config.current();
+                    self.settings = Settings::from_config(&config);
+                    self.keybinds = KeybindEditor::from_config(&config);
+                }
+                self.tab = tab;
+                match tab {
+                    Tab::Scripts => self.update(Message::Scripts(ScriptMessage::Refresh)),
+                    Tab::Macros => self.update(Message::Macros(MacroMessage::Refresh)),
+                    Tab::Main | Tab::Settings | Tab::Log => Task::none(),
+                }
+            }
+            Message::ToggleCompact => {
+                self.compact = !self.compact;
+                // Selecting the ROI needs the full size preview
+                self.cancel_roi_selection();
+                let compact = self.compact;
+                window::get_latest().and_then(move |id| set_compact(id, compact))
+            }
+            Message::DragWindow => window::get_latest().and_then(window::drag),
+            Message::Settings(message) => {
+                self.settings.update(message, &self.config);
+                Task::none()
+            }
+            Message::Keybinds(message) => self.keybinds.update(message, &self.config).map(Message::Keybinds),
+            Message::Scripts(message) => self.scripts.update(message, self.game_input.as_ref()).map(Message::Scripts),
+            Message::Macros(message) => self.macros.update(message, self.game_input.as_ref()).map(Message::Macros),
+            Message::Notifications(message) => {
+                self.notifications.update(message);
+                Task::none()
+            }
+            Message::ScreenshotSaved(result) => {
                 match result {
-                    Ok(_) => {
-                        println!("✅ DXGI high-performance mode enabled!");
-                        self.error_message = None;
+                    Ok(path) => self.notifications.info(format!("Screenshot saved to {}", path.display())),
+                    Err(e) => self.notifications.error(format!("Screenshot failed: {}", e)),
+                }
+                Task::none()
+            }
+            Message::AutomationPaused(result) => {
+                match result {
+                    Ok(paused) => {
+                        tracing::info!(paused, "Automation pause toggled");
+                        self.automation_paused = paused;
+                    }
+                    Err(e) => self.notifications.error(e),
+                }
+                Task::none()
+            }
+            Message::CloseRequested => {
+                // Closing again while shutting down doesn't start a second shutdown
+                if self.shutting_down {
+                    return Task::none();
+                }
+                self.shutting_down = true;
+                self.service_state = ServiceState::Stopping;
+                let shutdown = Shutdown::new(self.services.clone(), self.graphics_service.clone())
+                    .with_minimap(self.minimap_service.clone());
+                let game_input = self.game_input.clone();
+                Task::perform(
+                    async move {
+                        if let Some(game_input) = game_input {
+                            game_input.shutdown().await;
+                        }
+                        shutdown.run().await
+                    },
+                    Message::ShutdownFinished,
+                )
+            },
+            Message::ShutdownFinished(result) => {
+                // Only the log file outlives the window
+                if let Err(e) = result {
+                    self.notifications.error(e);
+                }
+                iced::exit()
+            },
+            Message::ToggleMap => {
+                let service = self.minimap_service.clone();
+                let mapping = self.mapping;
+                Task::perform(
+                    async move {
+                        if mapping {
+                            service.stop_map().map(|saved| {
+                                if let Some(path) = saved {
+                                    tracing::info!(path = %path.display(), "Map saved");
+                                }
+                                false
+                            })
+                        } else {
+                            service.start_map(MapBuilderConfig::default()).map(|_| true)
+                        }
                     },
-                    Err(e) => {
-                        println!("❌ Failed to enable DXGI mode: {}", e);
-                        self.error_message = Some(format!("DXGI mode failed: {}", e));
+                    Message::MapToggled,
+                )
+            },
+            Message::MapToggled(result) => {
+                match result {
+                    Ok(mapping) => self.mapping = mapping,
+                    Err(e) => self.notifications.error(format!("Mapping failed: {}", e)),
+                }
+                Task::none()
+            },
+            Message::UpdateMap => {
+                let service = self.minimap_service.clone();
+                Task::perform(
+                    async move {
+                        service.map_preview(MAP_PREVIEW_WIDTH)
+                    },
+                    Message::MapPreviewReceived,
+                )
+            },
+            Message::MapPreviewReceived(png) => {
+                if let Some(png) = png {
+                    self.map_preview = Some(image::Handle::from_bytes(png));
+                }
+                Task::none()
+            },
+            Message::BotEventReceived(event) => {
+                self.scripts.event(&event);
+                self.notifications.event(&event);
+                match event {
+                    BotEvent::ChatKeyword { keyword, line, paused } => {
+                        tracing::info!(keyword = %keyword, line = %line, "Chat keyword");
+                        self.chat_alert = Some(if paused {
+                            format!("{} (automation stopped): {}", keyword, line)
+                        } else {
+                            format!("{}: {}", keyword, line)
+                        });
                     }
+                    BotEvent::WindowLost { reason } => {
+                        tracing::warn!(reason = %reason, "Window lost, stopping capture");
+                        self.service_state = ServiceState::Stopping;
+                        let service = self.minimap_service.clone();
+                        let services = self.services.clone();
+                        return Task::perform(
+                            async move {
+                                let _ = services.stop_all().await;
+                                let _ = service.stop_capture().await;
+                                reason
+                            },
+                            Message::CaptureError,
+                        );
+                    }
+                    BotEvent::LowPowerChanged { enabled } => {
+                        tracing::info!(enabled, "Low-power mode changed");
+                    }
+                    BotEvent::HotkeyPressed { action } => return self.hotkey_pressed(action),
+                    _ => {}
+                }
+                Task::none()
+            },
+            Message::DxgiModeResult(result) => {
+                match result {
+                    Ok(_) => tracing::info!("High-performance DXGI mode enabled"),
+                    Err(e) => self.notifications.warning(format!("DXGI mode failed, using standard capture: {}", e)),
                 }
                 Task::none()
             },
         }
     }
 
+    /// Stop selecting the ROI and go back to the one used before
+    fn cancel_roi_selection(&mut self) {
+        let Some(selection) = self.roi_selection.take() else {
+            return;
+        };
+        if let Some(roi) = selection.previous {
+            if let Err(e) = self.minimap_service.set_roi(roi) {
+                self.notifications.error(e);
+            }
+        }
+    }
+
+    /// Show the captured pixel under `point` of the minimap preview, at 1x zoom
+    fn pick_color(&mut self, point: Point) {
+        let frame = self.graphics_service.subscribe_latest().borrow().clone();
+        let Some(frame) = frame else {
+            self.notifications.warning("No frame captured yet");
+            return;
+        };
+        // Nothing is picked beside the image
+        if let Some(picked) = PickedColor::at(&frame, self.minimap_service.roi(), point, PREVIEW_SIZE) {
+            self.color_picker.picked(picked);
+        }
+    }
+
+    /// Carry out a global hotkey, the panic stop arrives as `Message::KillSwitch` instead
+    fn hotkey_pressed(&mut self, action: HotkeyAction) -> Task<Message> {
+        match action {
+            HotkeyAction::StartStop => match self.service_state {
+                ServiceState::Running => self.update(Message::StopCapture),
+                ServiceState::Stopped => self.update(Message::StartCapture),
+                ServiceState::Starting | ServiceState::Stopping => Task::none(),
+            },
+            HotkeyAction::PanicStop => Task::none(),
+            HotkeyAction::Screenshot => Task::perform(screenshot(self.graphics_service.clone()), Message::ScreenshotSaved),
+            HotkeyAction::PauseAutomation => {
+                if self.service_state != ServiceState::Running {
+                    return Task::none();
+                }
+                let services = self.services.clone();
+                let pause = !self.automation_paused;
+                Task::perform(
+                    async move {
+                        if pause {
+                            services.stop_all().await.map(|_| true)
+                        } else {
+                            services.start_all().await.map(|_| false)
+                        }
+                    },
+                    Message::AutomationPaused,
+                )
+            }
+        }
+    }
+
+    /// Just the minimap and the bot status, for the always-on-top overlay
+    fn compact_view(&self) -> Element<'_, Message> {
+        let preview: Element<'_, Message> = match &self.current_frame {
+            Some(frame) => image(frame.clone())
+                .width(Length::Fixed(COMPACT_PREVIEW_SIZE.width))
+                .height(Length::Fixed(COMPACT_PREVIEW_SIZE.height))
+                .into(),
+            None => container(text("Waiting for capture...").size(12))
+                .center_x(Length::Fixed(COMPACT_PREVIEW_SIZE.width))
+                .center_y(Length::Fixed(COMPACT_PREVIEW_SIZE.height))
+                .into(),
+        };
+        let status = match self.service_state {
+            ServiceState::Running if self.automation_paused => "⏸️ Automation paused",
+            ServiceState::Running => "▶️ Running",
+            ServiceState::Starting => "Starting...",
+            ServiceState::Stopping => "Stopping...",
+            ServiceState::Stopped => "⏹️ Stopped",
+        };
+        // Borderless, so dragging anywhere but the button moves the window
+        mouse_area(
+            container(
+                column![
+                    preview,
+                    row![
+                        text(status).size(12).width(Fill),
+                        button(text("Expand").size(12)).on_press(Message::ToggleCompact),
+                    ]
+                    .spacing(5)
+                    .align_y(iced::Alignment::Center),
+                ]
+                .spacing(5),
+            )
+            .padding(5),
+        )
+        .on_press(Message::DragWindow)
+        .into()
+    }
+
     fn subscription(&self) -> Subscription<Message> {
         let frame_subscription = if self.service_state == ServiceState::Running {
             // Create a subscription that listens to frame updates using WatchStream
@@ -286,10 +953,33 @@ impl StarryApp {
             Subscription::none()
         };
 
-        let status_check_subscription = iced::time::every(std::time::Duration::from_secs(2))
-            .map(|_| Message::CheckServiceStatus);
+        // Full frames only cost anything while they are shown
+        let frame_preview_subscription = if self.service_state == ServiceState::Running && self.show_frame_preview {
+            Subscription::run_with_id(
+                "frame_preview",
+                WatchStream::new(self.minimap_service.subscribe_frame_preview()).map(Message::FramePreviewReceived),
+            )
+        } else {
+            Subscription::none()
+        };
+
+        // Frame rates and latency for the chart, published every second
+        let performance_subscription = if self.service_state == ServiceState::Running {
+            Subscription::run_with_id(
+                "performance",
+                WatchStream::new(self.minimap_service.subscribe_performance()).map(Message::PerformanceReceived),
+            )
+        } else {
+            Subscription::none()
+        };
+
+        // Follow the service state as it changes instead of polling it
+        let status_check_subscription = Subscription::run_with_id(
+            "service_state",
+            WatchStream::new(self.minimap_service.subscribe_state()).map(Message::ServiceStatusChecked),
+        );
 
-        // Auto-update metrics every 4 seconds when running
+        // Refresh the session counters and service status every 4 seconds when running
         let metrics_update_subscription = if self.service_state == ServiceState::Running {
             iced::time::every(std::time::Duration::from_secs(4))
                 .map(|_| Message::UpdateMetrics)
@@ -297,24 +987,93 @@ impl StarryApp {
             Subscription::none()
         };
 
-        Subscription::batch([frame_subscription, status_check_subscription, metrics_update_subscription])
+        // Emergency stop from the kill switch hotkey
+        let kill_switch_subscription = match kill_switch() {
+            Ok(receiver) => Subscription::run_with_id(
+                "kill_switch",
+                BroadcastStream::new(receiver).map(|_| Message::KillSwitch),
+            ),
+            Err(_) => Subscription::none(),
+        };
+
+        // Refresh the explored map while it is being built
+        let map_update_subscription = if self.mapping {
+            iced::time::every(std::time::Duration::from_secs(2))
+                .map(|_| Message::UpdateMap)
+        } else {
+            Subscription::none()
+        };
+
+        // Chat alerts, lost windows and failures from the services
+        let event_subscription = Subscription::run_with_id(
+            "event_bus",
+            BroadcastStream::new(EventBus::global().subscribe()).filter_map(|event| event.ok().map(Message::BotEventReceived)),
+        );
+
+        // Time out the toasts while any are shown
+        let toast_subscription = if self.notifications.has_toasts() {
+            iced::time::every(std::time::Duration::from_secs(1))
+                .map(|_| Message::Notifications(NotificationMessage::Tick))
+        } else {
+            Subscription::none()
+        };
+
+        Subscription::batch([
+            iced::window::close_requests().map(|_| Message::CloseRequested),
+            frame_subscription,
+            frame_preview_subscription,
+            performance_subscription,
+            event_subscription,
+            status_check_subscription,
+            metrics_update_subscription,
+            kill_switch_subscription,
+            map_update_subscription,
+            toast_subscription,
+        ])
     }
 
     fn view(&self) -> Element<'_, Message> {
+        if self.compact {
+            return self.compact_view();
+        }
+
         // Left column: Minimap display
         let minimap_display = if let Some(frame_handle) = &self.current_frame {
-            column![
-                text("Current Minimap:").size(16),
-                image(frame_handle.clone())
-                    .width(Length::Fixed(400.0))
-                    .height(Length::Fixed(225.0))
-            ]
+            match &self.roi_selection {
+                Some(selection) => column![
+                    text("Drag over the frame to select the minimap area:").size(16),
+                    mouse_area(stack(
+                        std::iter::once(
+                            image(frame_handle.clone())
+                                .width(Length::Fixed(PREVIEW_SIZE.width))
+                                .height(Length::Fixed(PREVIEW_SIZE.height))
+                                .into(),
+                        )
+                        .chain(selection.overlay()),
+                    ))
+                        .on_move(Message::PreviewCursorMoved)
+                        .on_press(Message::PreviewPressed)
+                        .on_release(Message::PreviewReleased)
+                        .interaction(mouse::Interaction::Crosshair)
+                ],
+                // Scroll to zoom, drag to pan
+                None => column![
+                    row![
+                        text("Current Minimap:").size(16).width(Length::Fill),
+                        button(text("Reset Zoom").size(12))
+                            .on_press_maybe(self.preview_zoom.is_zoomed().then_some(Message::PreviewZoom(ZoomMessage::Reset))),
+                    ],
+                    self.preview_zoom
+                        .view(frame_handle, PREVIEW_SIZE, self.color_picker.is_active())
+                        .map(Message::PreviewZoom),
+                ],
+            }
             .spacing(10)
         } else {
             column![
                 container(text("Waiting for capture..."))
-                    .width(Length::Fixed(400.0))
-                    .height(Length::Fixed(225.0))
+                    .width(Length::Fixed(PREVIEW_SIZE.width))
+                    .height(Length::Fixed(PREVIEW_SIZE.height))
                     .style(|_theme: &iced::Theme| {
                         iced::widget::container::Style {
                             background: Some(iced::Background::Color(iced::Color::from_rgba(0.1, 0.1, 0.1, 0.8))),
@@ -332,30 +1091,114 @@ impl StarryApp {
             .spacing(10)
         };
 
+        // Explored map below the minimap once something was stitched
+        let minimap_display = match &self.map_preview {
+            Some(map_handle) => minimap_display.push(text("Explored Map:").size(16)).push(
+                image(map_handle.clone())
+                    .width(Length::Fixed(400.0))
+                    .height(Length::Fixed(400.0))
+            ),
+            None => minimap_display,
+        };
+
+        // Full frame next to the minimap, to check the ROI lines up with the game
+        let minimap_display: Element<'_, Message> = if self.show_frame_preview {
+            let frame_display = match &self.frame_preview {
+                Some(handle) => column![
+                    text("Full Frame:").size(16),
+                    image(handle.clone())
+                        .width(Length::Fixed(PREVIEW_SIZE.width))
+                        .height(Length::Fixed(PREVIEW_SIZE.height)),
+                ],
+                None => column![text("Full Frame:").size(16), text("Waiting for frame...").size(14)],
+            };
+            row![minimap_display, frame_display.spacing(10)].spacing(20).into()
+        } else {
+            minimap_display.into()
+        };
+
         // Right column: Controls and information
+        // One row per window so its icon can be shown, the title alone doesn't tell duplicates apart
+        let windows = self.available_windows.iter().zip(&self.window_icons).map(|(window, icon)| {
+            let icon: Element<'_, Message> = match icon {
+                Some(handle) => image(handle.clone())
+                    .width(Length::Fixed(WINDOW_ICON_SIZE))
+                    .height(Length::Fixed(WINDOW_ICON_SIZE))
+                    .into(),
+                None => Space::new(Length::Fixed(WINDOW_ICON_SIZE), Length::Fixed(WINDOW_ICON_SIZE))
+                    .into(),
+            };
+            let style = match self.selected_window.as_ref() == Some(window) {
+                true => button::primary,
+                false => button::text,
+            };
+            button(row![icon, text(window.to_string()).size(14)].spacing(8))
+                .on_press(Message::WindowSelected(window.clone()))
+                .style(style)
+                .width(Length::Fill)
+                .into()
+        });
+        let window_list: Element<'_, Message> = if self.available_windows.is_empty() {
+            text("No windows found").size(14).into()
+        } else {
+            scrollable(column(windows).spacing(2)).height(Length::Fixed(180.0)).into()
+        };
         let window_picker = column![
             text("Select Window:").size(16),
-            pick_list(
-                &self.available_windows[..],
-                self.selected_window.as_ref(),
-                Message::WindowSelected,
-            )
-            .placeholder("Select a window to capture..."),
+            window_list,
             button("Refresh Windows")
                 .on_press(Message::RefreshWindows)
                 .width(Length::Fill),
         ]
         .spacing(10);
 
+        // Only worth showing once there is a profile to switch to
+        let window_picker = if self.profiles.len() > 1 {
+            column![
+                text("Game Profile:").size(16),
+                pick_list(&self.profiles[..], Some(&self.selected_profile), Message::ProfileSelected),
+            ]
+            .spacing(10)
+            .push(window_picker)
+        } else {
+            window_picker
+        };
+
+        // Name the global hotkey on the buttons it stands in for
+        let start_stop = |label: &str| match self.config.current().hotkeys().start_stop {
+            Some(key) => format!("{} ({:?})", label, key),
+            None => label.to_string(),
+        };
         let capture_controls = match self.service_state {
             ServiceState::Running => {
                 column![
-                    button("Stop Capture")
+                    button(text(start_stop("Stop Capture")))
                         .on_press(Message::StopCapture)
                         .width(Length::Fill),
                     button("Show Performance Metrics")
                         .on_press(Message::ShowMetrics)
-                        .width(Length::Fill)
+                        .width(Length::Fill),
+                    button(if self.mapping { "Stop Mapping" } else { "Start Mapping" })
+                        .on_press(Message::ToggleMap)
+                        .width(Length::Fill),
+                    button(if self.show_frame_preview { "Hide Full Frame" } else { "Show Full Frame" })
+                        .on_press(Message::ToggleFramePreview)
+                        .width(Length::Fill),
+                    button(if self.detection_overlay { "Hide Detections" } else { "Show Detections" })
+                        .on_press(Message::ToggleDetectionOverlay)
+                        .width(Length::Fill),
+                    button(if self.color_picker.is_active() { "Stop Picking Colors" } else { "Pick Color" })
+                        .on_press(Message::TogglePicker)
+                        .width(Length::Fill),
+                    button("Compact Overlay")
+                        .on_press(Message::ToggleCompact)
+                        .width(Length::Fill),
+                    if self.roi_selection.is_some() {
+                        button("Cancel Area Selection").on_press(Message::CancelRoiSelection)
+                    } else {
+                        button("Select Minimap Area").on_press(Message::SelectRoi)
+                    }
+                    .width(Length::Fill)
                 ].spacing(5)
             },
             ServiceState::Stopping => {
@@ -375,7 +1218,7 @@ impl StarryApp {
             },
             ServiceState::Stopped => {
                 column![
-                    button("Start Capture")
+                    button(text(start_stop("Start Capture")))
                         .on_press_maybe(self.selected_window.as_ref().map(|_| Message::StartCapture))
                         .width(Length::Fill)
                 ]
@@ -387,22 +1230,16 @@ impl StarryApp {
             ServiceState::Starting => "Starting minimap capture...".to_string(),
             ServiceState::Running => {
                 if let Some(window) = &self.selected_window {
-                    format!("Minimap capture is running ({})", window)
+                    format!("Minimap capture is running ({})", window.title)
                 } else {
                     "Minimap capture is running".to_string()
                 }
             },
             ServiceState::Stopped => "Minimap capture is stopped".to_string(),
         };
-
-        let error_display = if let Some(error) = &self.error_message {
-            Some(column![
-                text("Error:").size(16),
-                text(error.clone()).size(14)
-            ]
-            .spacing(5))
-        } else {
-            None
+        let status_text = match self.preview_zoom.is_zoomed() {
+            true => format!("{} · Zoom {:.1}x", status_text, self.preview_zoom.zoom()),
+            false => status_text,
         };
 
         let mut right_column_elements = vec![
@@ -411,8 +1248,45 @@ impl StarryApp {
             text(status_text).size(14).into(),
         ];
 
-        if let Some(error_widget) = error_display {
-            right_column_elements.push(error_widget.into());
+        if self.color_picker.is_active() {
+            right_column_elements.push(
+                column![
+                    text("Color Picker:").size(16),
+                    self.color_picker.view().map(Message::Picker)
+                ]
+                .spacing(5)
+                .into()
+            );
+        }
+
+        if !self.metrics_chart.is_empty() {
+            right_column_elements.push(self.metrics_chart.view());
+        }
+
+        if let Some(chat_alert) = &self.chat_alert {
+            right_column_elements.push(
+                column![
+                    text("Chat Alert:").size(16),
+                    text(chat_alert.clone()).size(14)
+                ]
+                .spacing(5)
+                .into()
+            );
+        }
+
+        if let Some(session_text) = &self.session_text {
+            right_column_elements.push(text(session_text.clone()).size(14).into());
+        }
+
+        if let Some(services_text) = &self.services_text {
+            right_column_elements.push(
+                column![
+                    text("Services:").size(16),
+                    text(services_text.clone()).size(14)
+                ]
+                .spacing(5)
+                .into()
+            );
         }
 
         let right_column = column(right_column_elements)
@@ -420,15 +1294,41 @@ impl StarryApp {
             .width(Length::Fixed(300.0));
 
         // Main two-column layout
-        let main_content = row![
-            container(minimap_display)
-                .width(Length::Fixed(420.0))
-                .padding(10),
-            container(right_column)
-                .width(Length::Fixed(320.0))
-                .padding(10)
+        let main_content: Element<'_, Message> = match self.tab {
+            Tab::Main => row![
+                container(minimap_display)
+                    .width(Length::Fixed(if self.show_frame_preview { 840.0 } else { 420.0 }))
+                    .padding(10),
+                container(right_column)
+                    .width(Length::Fixed(320.0))
+                    .padding(10)
+            ]
+            .spacing(20)
+            .into(),
+            Tab::Scripts => container(self.scripts.view(self.game_input.as_ref()).map(Message::Scripts)).padding(10).into(),
+            Tab::Macros => container(self.macros.view(self.game_input.as_ref()).map(Message::Macros)).padding(10).into(),
+            Tab::Log => container(self.notifications.history().map(Message::Notifications)).padding(10).into(),
+            Tab::Settings => container(
+                column![self.settings.view().map(Message::Settings), self.keybinds.view().map(Message::Keybinds)]
+                    .spacing(30),
+            )
+            .padding(10)
+            .into(),
+        };
+
+        let tab_button = |label, tab| button(text(label)).on_press_maybe((self.tab != tab).then_some(Message::ShowTab(tab)));
+        let header = column![
+            text("Starry Bot Minimap").size(24),
+            row![
+                tab_button("Minimap", Tab::Main),
+                tab_button("Settings", Tab::Settings),
+                tab_button("Scripts", Tab::Scripts),
+                tab_button("Macros", Tab::Macros),
+                tab_button("Log", Tab::Log),
+            ]
+            .spacing(10),
         ]
-        .spacing(20);
+        .spacing(10);
 
         // Debug panel - only show in debug builds as a separate right panel
         #[cfg(debug_assertions)]
@@ -441,9 +1341,9 @@ impl StarryApp {
                 column![
                     text("🐛 Debug Panel").size(16).color([0.8, 0.4, 0.4]),
                     text(format!("Build: Debug")).size(12).color([0.6, 0.6, 0.6]),
-                    text(format!("Selected Window: {:?}", self.selected_window)).size(12).color([0.6, 0.6, 0.6]),
+                    text(format!("Selected Window: {:?}", self.selected_window.as_ref().map(|window| window.to_string()))).size(12).color([0.6, 0.6, 0.6]),
                     text(format!("Service State: {:?}", self.service_state)).size(12).color([0.6, 0.6, 0.6]),
-                    text(format!("Error Message: {:?}", self.error_message)).size(12).color([0.6, 0.6, 0.6]),
+                    text(format!("Notifications: {}", self.notifications.logged())).size(12).color([0.6, 0.6, 0.6]),
                     text(format!("Available Windows: {}", self.available_windows.len())).size(12).color([0.6, 0.6, 0.6]),
                     text("").size(8), // Spacer
                     text("📊 Performance Metrics:").size(14).color([0.4, 0.8, 0.4]),
@@ -475,9 +1375,9 @@ impl StarryApp {
             ]
             .spacing(10);
             
-            container(
+            let page = container(
                 column![
-                    text("Starry Bot Minimap").size(24),
+                    header,
                     content_with_debug
                 ]
                 .spacing(20)
@@ -486,15 +1386,15 @@ impl StarryApp {
             .width(Fill)
             .height(Fill)
             .center_x(Fill)
-            .center_y(Fill)
-            .into()
+            .center_y(Fill);
+            stack([page.into(), self.notifications.toasts().map(Message::Notifications)]).into()
         }
         
         #[cfg(not(debug_assertions))]
         {
-            container(
+            let page = container(
                 column![
-                    text("Starry Bot Minimap").size(24),
+                    header,
                     main_content
                 ]
                 .spacing(20)
@@ -503,8 +1403,8 @@ impl StarryApp {
             .width(Fill)
             .height(Fill)
             .center_x(Fill)
-            .center_y(Fill)
-            .into()
+            .center_y(Fill);
+            stack([page.into(), self.notifications.toasts().map(Message::Notifications)]).into()
         }
     }
 }
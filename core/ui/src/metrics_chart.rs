@@ -0,0 +1,130 @@
+//! Rolling chart of the capture and processing frame rates and the end-to-end latency
+
+use std::collections::VecDeque;
+
+use iced::widget::canvas::{self, Cache, Canvas, Frame, Geometry, Path, Stroke};
+use iced::widget::{column, row, text};
+use iced::{mouse, Color, Element, Length, Point, Rectangle, Renderer, Size, Theme, Vector};
+use interface::services::PerformanceStats;
+
+/// Samples shown, the minimap service publishes one a second
+const HISTORY: usize = 60;
+
+/// Space between the frame rate and latency plots
+const PANEL_GAP: f32 = 6.0;
+
+const CAPTURE_FPS: usize = 0;
+const PROCESSING_FPS: usize = 1;
+const LATENCY: usize = 2;
+
+const CAPTURE_COLOR: Color = Color::from_rgb(0.4, 0.7, 1.0);
+const PROCESSING_COLOR: Color = Color::from_rgb(0.4, 0.8, 0.4);
+const LATENCY_COLOR: Color = Color::from_rgb(1.0, 0.6, 0.3);
+const BACKGROUND_COLOR: Color = Color::from_rgba(0.1, 0.1, 0.1, 0.8);
+const AXIS_COLOR: Color = Color::from_rgb(0.6, 0.6, 0.6);
+
+#[derive(Default)]
+pub struct MetricsChart {
+    /// Capture FPS, processing FPS and p50 end-to-end latency in ms, oldest first
+    samples: VecDeque<[f32; 3]>,
+    cache: Cache,
+}
+
+impl MetricsChart {
+    pub fn push(&mut self, stats: &PerformanceStats) {
+        if self.samples.len() == HISTORY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back([
+            stats.capture.fps as f32,
+            stats.minimap.fps as f32,
+            stats.minimap.end_to_end_latency.p50_ms as f32,
+        ]);
+        self.cache.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.cache.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn view<'a, Message: 'a>(&'a self) -> Element<'a, Message> {
+        let latest = self.samples.back().copied().unwrap_or_default();
+        let legend = row![
+            text(format!("Capture {:.1} fps", latest[CAPTURE_FPS])).size(12).color(CAPTURE_COLOR),
+            text(format!("Processing {:.1} fps", latest[PROCESSING_FPS])).size(12).color(PROCESSING_COLOR),
+            text(format!("Latency {:.1} ms", latest[LATENCY])).size(12).color(LATENCY_COLOR),
+        ]
+        .spacing(10);
+        column![
+            legend,
+            Canvas::new(self).width(Length::Fill).height(Length::Fixed(140.0)),
+        ]
+        .spacing(5)
+        .into()
+    }
+
+    /// Draw the `series` into `area`, scaled to the largest value shown
+    fn plot(&self, frame: &mut Frame, area: Rectangle, unit: &str, series: &[(usize, Color)]) {
+        frame.fill_rectangle(area.position(), area.size(), BACKGROUND_COLOR);
+        let max = self
+            .samples
+            .iter()
+            .flat_map(|sample| series.iter().map(move |(index, _)| sample[*index]))
+            .fold(0.0, f32::max);
+        let top = (max * 1.1).max(1.0).ceil();
+        frame.fill_text(canvas::Text {
+            content: format!("{} {}", top, unit),
+            position: area.position() + Vector::new(4.0, 2.0),
+            color: AXIS_COLOR,
+            size: 10.0.into(),
+            ..canvas::Text::default()
+        });
+
+        // The newest sample is at the right edge
+        let step = area.width / (HISTORY - 1) as f32;
+        let offset = HISTORY - self.samples.len();
+        for (index, color) in series {
+            let line = Path::new(|builder| {
+                for (i, sample) in self.samples.iter().enumerate() {
+                    let point = Point::new(
+                        area.x + (offset + i) as f32 * step,
+                        area.y + area.height * (1.0 - sample[*index] / top),
+                    );
+                    if i == 0 {
+                        builder.move_to(point);
+                    } else {
+                        builder.line_to(point);
+                    }
+                }
+            });
+            frame.stroke(&line, Stroke::default().with_color(*color).with_width(1.5));
+        }
+    }
+}
+
+impl<Message> canvas::Program<Message> for MetricsChart {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &Self::State,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let geometry = self.cache.draw(renderer, bounds.size(), |frame| {
+            let height = (frame.height() - PANEL_GAP) / 2.0;
+            let fps = Rectangle::new(Point::ORIGIN, Size::new(frame.width(), height));
+            let latency = Rectangle::new(Point::new(0.0, height + PANEL_GAP), Size::new(frame.width(), height));
+            self.plot(frame, fps, "fps", &[(CAPTURE_FPS, CAPTURE_COLOR), (PROCESSING_FPS, PROCESSING_COLOR)]);
+            self.plot(frame, latency, "ms", &[(LATENCY, LATENCY_COLOR)]);
+        });
+        vec![geometry]
+    }
+}
@@ -0,0 +1,275 @@
+//! Toasts for errors and other things worth telling the user, stacked in the corner of the window
+//! until they time out or are dismissed, and the history of every one of them for the log tab
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use iced::widget::{button, column, container, row, scrollable, text};
+use iced::{Alignment, Border, Color, Element, Font, Length, Theme};
+use interface::BotEvent;
+
+/// Toasts shown at once, older ones only stay in the history
+const MAX_TOASTS: usize = 5;
+
+/// Notifications kept in the history, older ones are dropped
+const HISTORY_LENGTH: usize = 500;
+
+const TOAST_WIDTH: f32 = 320.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// How long a toast of this severity is shown, errors stay the longest
+    fn timeout(self) -> Duration {
+        match self {
+            Severity::Info => Duration::from_secs(4),
+            Severity::Warning => Duration::from_secs(8),
+            Severity::Error => Duration::from_secs(15),
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            Severity::Info => Color::from_rgb(0.4, 0.6, 0.9),
+            Severity::Warning => Color::from_rgb(0.9, 0.7, 0.3),
+            Severity::Error => Color::from_rgb(0.9, 0.4, 0.4),
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Severity::Info => "Info",
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum NotificationMessage {
+    Dismiss(u64),
+    /// Drop the toasts that timed out
+    Tick,
+    ClearHistory,
+}
+
+#[derive(Debug, Clone)]
+struct Notification {
+    id: u64,
+    severity: Severity,
+    text: String,
+    /// Times it was raised in a row, repeats bump this instead of stacking copies
+    count: u32,
+    raised: Instant,
+}
+
+/// Toasts on screen and the history of all notifications
+pub struct Notifications {
+    started: Instant,
+    next_id: u64,
+    /// Ids of the notifications shown as toasts, oldest first
+    toasts: Vec<u64>,
+    history: VecDeque<Notification>,
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            next_id: 0,
+            toasts: Vec::new(),
+            history: VecDeque::new(),
+        }
+    }
+}
+
+impl Notifications {
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.push(Severity::Info, text.into());
+    }
+
+    pub fn warning(&mut self, text: impl Into<String>) {
+        self.push(Severity::Warning, text.into());
+    }
+
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.push(Severity::Error, text.into());
+    }
+
+    fn push(&mut self, severity: Severity, text: String) {
+        // Also in the log file, the history is gone once the window closes
+        match severity {
+            Severity::Info => tracing::info!("{}", text),
+            Severity::Warning => tracing::warn!("{}", text),
+            Severity::Error => tracing::error!("{}", text),
+        }
+        let now = Instant::now();
+        // A service failing over and over shows up once with a count
+        if let Some(last) = self.history.back_mut() {
+            if last.severity == severity && last.text == text && self.toasts.contains(&last.id) {
+                last.count += 1;
+                last.raised = now;
+                return;
+            }
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+        self.history.push_back(Notification {
+            id,
+            severity,
+            text,
+            count: 1,
+            raised: now,
+        });
+        self.toasts.push(id);
+        if self.toasts.len() > MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+    }
+
+    /// Raise a notification for the events the user should hear about
+    pub fn event(&mut self, event: &BotEvent) {
+        match event {
+            BotEvent::ServiceFailed { service, error } => self.error(format!("{} failed: {}", service, error)),
+            BotEvent::TaskRestarting { task, error, attempt, delay_ms } => self.warning(format!(
+                "{} failed ({}), restarting in {:.1} s (attempt {})",
+                task,
+                error,
+                *delay_ms as f64 / 1000.0,
+                attempt
+            )),
+            BotEvent::FrameLagging { effective_fps, target_fps } => self.warning(format!(
+                "Frames are processed too slowly, capturing at {} of {} FPS",
+                effective_fps, target_fps
+            )),
+            BotEvent::MemoryBudgetExceeded { used_bytes, limit_bytes } => self.warning(format!(
+                "Queued frames use {} MB of the {} MB budget, frames are dropped",
+                used_bytes / (1024 * 1024),
+                limit_bytes / (1024 * 1024)
+            )),
+            BotEvent::ChatKeyword { keyword, line, paused: true } => {
+                self.warning(format!("{} in chat, automation stopped: {}", keyword, line))
+            }
+            BotEvent::ScriptError { script, error } => self.error(format!("{}: {}", script, error)),
+            _ => {}
+        }
+    }
+
+    pub fn update(&mut self, message: NotificationMessage) {
+        match message {
+            NotificationMessage::Dismiss(id) => self.toasts.retain(|toast| *toast != id),
+            NotificationMessage::Tick => {
+                let now = Instant::now();
+                let history = &self.history;
+                self.toasts.retain(|id| {
+                    history
+                        .iter()
+                        .rfind(|notification| notification.id == *id)
+                        .is_some_and(|notification| now - notification.raised < notification.severity.timeout())
+                });
+            }
+            NotificationMessage::ClearHistory => {
+                self.history.clear();
+                self.toasts.clear();
+            }
+        }
+    }
+
+    /// Whether toasts are waiting to time out
+    pub fn has_toasts(&self) -> bool {
+        !self.toasts.is_empty()
+    }
+
+    /// Notifications in the history
+    pub fn logged(&self) -> usize {
+        self.history.len()
+    }
+
+    /// The toasts stacked in the bottom right corner, to be laid over the window
+    pub fn toasts(&self) -> Element<'_, NotificationMessage> {
+        let toasts = self
+            .toasts
+            .iter()
+            .filter_map(|id| self.history.iter().rfind(|notification| notification.id == *id))
+            .map(|notification| {
+                let color = notification.severity.color();
+                let content = row![
+                    text(notification_text(notification)).size(14).width(Length::Fill),
+                    button(text("×").size(14))
+                        .on_press(NotificationMessage::Dismiss(notification.id))
+                        .style(button::text)
+                        .padding(0),
+                ]
+                .spacing(10)
+                .align_y(Alignment::Start);
+                container(content)
+                    .padding(10)
+                    .width(Length::Fixed(TOAST_WIDTH))
+                    .style(move |_theme: &Theme| container::Style {
+                        background: Some(iced::Background::Color(Color::from_rgba(0.15, 0.15, 0.15, 0.95))),
+                        border: Border {
+                            color,
+                            width: 1.0,
+                            radius: 5.0.into(),
+                        },
+                        text_color: Some(color),
+                        ..Default::default()
+                    })
+                    .into()
+            });
+        container(column(toasts).spacing(10))
+            .padding(20)
+            .align_right(Length::Fill)
+            .align_bottom(Length::Fill)
+            .into()
+    }
+
+    /// Every notification, newest first, with the time since launch it was raised at
+    pub fn history(&self) -> Element<'_, NotificationMessage> {
+        let lines = self.history.iter().rev().map(|notification| {
+            let elapsed = notification.raised.duration_since(self.started).as_secs();
+            row![
+                text(format!("{:02}:{:02}:{:02}", elapsed / 3600, elapsed / 60 % 60, elapsed % 60))
+                    .size(12)
+                    .font(Font::MONOSPACE)
+                    .color([0.6, 0.6, 0.6]),
+                text(notification.severity.label())
+                    .size(12)
+                    .width(Length::Fixed(60.0))
+                    .color(notification.severity.color()),
+                text(notification_text(notification)).size(12),
+            ]
+            .spacing(10)
+            .into()
+        });
+        let history: Element<'_, NotificationMessage> = if self.history.is_empty() {
+            text("Nothing happened yet").size(14).into()
+        } else {
+            scrollable(column(lines).spacing(4)).height(Length::Fill).width(Length::Fill).into()
+        };
+        column![
+            row![
+                text("Log").size(18).width(Length::Fill),
+                button("Clear").on_press_maybe((!self.history.is_empty()).then_some(NotificationMessage::ClearHistory)),
+            ],
+            history,
+        ]
+        .spacing(10)
+        .into()
+    }
+}
+
+fn notification_text(notification: &Notification) -> String {
+    match notification.count {
+        1 => notification.text.clone(),
+        count => format!("{} (×{})", notification.text, count),
+    }
+}
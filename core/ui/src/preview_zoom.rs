@@ -0,0 +1,203 @@
+//! Mouse-wheel zoom and drag-pan on the minimap preview, for inspecting detections pixel by pixel
+
+use iced::advanced::image::Renderer as _;
+use iced::widget::canvas::{self, Canvas, Event, Frame, Geometry};
+use iced::widget::image::{FilterMethod, Handle};
+use iced::{event, mouse, Element, Length, Point, Rectangle, Renderer, Size, Theme, Vector};
+
+use crate::roi_selection::fitted;
+
+const MAX_ZOOM: f32 = 16.0;
+
+/// Zoom change of one wheel notch
+const ZOOM_STEP: f32 = 1.25;
+
+/// Pixels scrolled by touchpads that count as one wheel notch
+const PIXELS_PER_LINE: f32 = 50.0;
+
+/// Pixels the cursor may move between press and release of a click that isn't a drag
+const CLICK_SLOP: f32 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZoomMessage {
+    /// Zoom by `notches` of the wheel, keeping the point under `cursor` in place
+    Zoomed { notches: f32, cursor: Point },
+    /// Move the view by a drag of the given preview pixels
+    Panned(Vector),
+    /// Clicked without dragging, at this point of the preview at 1x
+    Clicked(Point),
+    Reset,
+}
+
+/// Zoom and pan of a preview, 1x shows the whole image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PreviewZoom {
+    zoom: f32,
+    /// Top left corner of the part shown, in preview pixels at 1x
+    offset: Vector,
+}
+
+impl Default for PreviewZoom {
+    fn default() -> Self {
+        Self {
+            zoom: 1.0,
+            offset: Vector::ZERO,
+        }
+    }
+}
+
+impl PreviewZoom {
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    pub fn is_zoomed(&self) -> bool {
+        self.zoom > 1.0
+    }
+
+    /// Apply `message` to a preview of `size`
+    pub fn update(&mut self, message: ZoomMessage, size: Size) {
+        match message {
+            ZoomMessage::Zoomed { notches, cursor } => {
+                let zoom = (self.zoom * ZOOM_STEP.powf(notches)).clamp(1.0, MAX_ZOOM);
+                // The point under the cursor stays there
+                let anchor = self.offset + Vector::new(cursor.x, cursor.y) * (1.0 / self.zoom);
+                self.offset = anchor - Vector::new(cursor.x, cursor.y) * (1.0 / zoom);
+                self.zoom = zoom;
+            }
+            ZoomMessage::Panned(delta) => self.offset = self.offset - delta * (1.0 / self.zoom),
+            ZoomMessage::Clicked(_) => {}
+            ZoomMessage::Reset => *self = Self::default(),
+        }
+        // Keep the view over the preview
+        let max = Vector::new(size.width - size.width / self.zoom, size.height - size.height / self.zoom);
+        self.offset = Vector::new(self.offset.x.clamp(0.0, max.x), self.offset.y.clamp(0.0, max.y));
+    }
+
+    /// Point of the preview at 1x under `cursor`
+    fn unzoomed(&self, cursor: Point) -> Point {
+        Point::ORIGIN + self.offset + Vector::new(cursor.x, cursor.y) * (1.0 / self.zoom)
+    }
+
+    /// `handle` drawn with this zoom in a `size` widget, scaled to fit at 1x like an image widget
+    ///
+    /// `picking` shows a crosshair for clicking pixels.
+    pub fn view<'a>(&'a self, handle: &'a Handle, size: Size, picking: bool) -> Element<'a, ZoomMessage> {
+        Canvas::new(ZoomedImage {
+            zoom: self,
+            handle,
+            picking,
+        })
+            .width(Length::Fixed(size.width))
+            .height(Length::Fixed(size.height))
+            .into()
+    }
+}
+
+struct ZoomedImage<'a> {
+    zoom: &'a PreviewZoom,
+    handle: &'a Handle,
+    picking: bool,
+}
+
+/// Left button held down over the preview
+struct Drag {
+    pressed: Point,
+    last: Point,
+}
+
+impl canvas::Program<ZoomMessage> for ZoomedImage<'_> {
+    type State = Option<Drag>;
+
+    fn update(
+        &self,
+        drag: &mut Self::State,
+        event: Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (event::Status, Option<ZoomMessage>) {
+        let Event::Mouse(event) = event else {
+            return (event::Status::Ignored, None);
+        };
+        let position = cursor.position_in(bounds);
+        match event {
+            mouse::Event::WheelScrolled { delta } => {
+                let Some(cursor) = position else {
+                    return (event::Status::Ignored, None);
+                };
+                let notches = match delta {
+                    mouse::ScrollDelta::Lines { y, .. } => y,
+                    mouse::ScrollDelta::Pixels { y, .. } => y / PIXELS_PER_LINE,
+                };
+                (event::Status::Captured, Some(ZoomMessage::Zoomed { notches, cursor }))
+            }
+            mouse::Event::ButtonPressed(mouse::Button::Left) => match position {
+                Some(position) => {
+                    *drag = Some(Drag {
+                        pressed: position,
+                        last: position,
+                    });
+                    (event::Status::Captured, None)
+                }
+                None => (event::Status::Ignored, None),
+            },
+            mouse::Event::ButtonReleased(mouse::Button::Left) => match drag.take() {
+                Some(Drag { pressed, .. }) => {
+                    let click = position
+                        .filter(|position| position.distance(pressed) <= CLICK_SLOP)
+                        .map(|position| ZoomMessage::Clicked(self.zoom.unzoomed(position)));
+                    (event::Status::Captured, click)
+                }
+                None => (event::Status::Ignored, None),
+            },
+            mouse::Event::CursorMoved { .. } => match (drag.as_mut(), cursor.position_from(bounds.position())) {
+                (Some(drag), Some(position)) => {
+                    let delta = position - drag.last;
+                    drag.last = position;
+                    (event::Status::Captured, Some(ZoomMessage::Panned(delta)))
+                }
+                _ => (event::Status::Ignored, None),
+            },
+            _ => (event::Status::Ignored, None),
+        }
+    }
+
+    fn draw(
+        &self,
+        _drag: &Self::State,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let image = renderer.measure_image(self.handle);
+        if let Some(fitted) = fitted(bounds.size(), Size::new(image.width as f32, image.height as f32)) {
+            let zoom = self.zoom.zoom;
+            let shown = Rectangle {
+                x: (fitted.x - self.zoom.offset.x) * zoom,
+                y: (fitted.y - self.zoom.offset.y) * zoom,
+                width: fitted.width * zoom,
+                height: fitted.height * zoom,
+            };
+            frame.with_clip(Rectangle::with_size(bounds.size()), |frame| {
+                // Sharp pixels when zoomed in
+                let image = canvas::Image {
+                    filter_method: FilterMethod::Nearest,
+                    ..canvas::Image::new(self.handle.clone())
+                };
+                frame.draw_image(shown, image);
+            });
+        }
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(&self, drag: &Self::State, bounds: Rectangle, cursor: mouse::Cursor) -> mouse::Interaction {
+        match (drag, cursor.is_over(bounds)) {
+            (_, true) if self.picking => mouse::Interaction::Crosshair,
+            (Some(_), _) => mouse::Interaction::Grabbing,
+            (None, true) if self.zoom.is_zoomed() => mouse::Interaction::Grab,
+            _ => mouse::Interaction::default(),
+        }
+    }
+}
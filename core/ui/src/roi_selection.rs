@@ -0,0 +1,108 @@
+//! Choosing the minimap ROI by dragging a rectangle over the preview
+
+use iced::widget::{container, Space};
+use iced::{Color, Element, Padding, Point, Rectangle, Size};
+use interface::services::Rect;
+
+/// Drags shorter than this (in widget pixels) are taken as stray clicks
+const MIN_DRAG: f32 = 4.0;
+
+/// A selection in progress, the preview shows the full frame until it ends
+#[derive(Debug, Clone, Default)]
+pub struct RoiSelection {
+    /// ROI before the selection started, restored if it is cancelled
+    pub previous: Option<Rect>,
+    cursor: Option<Point>,
+    start: Option<Point>,
+}
+
+impl RoiSelection {
+    pub fn new(previous: Option<Rect>) -> Self {
+        Self {
+            previous,
+            cursor: None,
+            start: None,
+        }
+    }
+
+    /// Cursor position over the preview, relative to its top left corner
+    pub fn cursor_moved(&mut self, position: Point) {
+        self.cursor = Some(position);
+    }
+
+    pub fn pressed(&mut self) {
+        self.start = self.cursor;
+    }
+
+    /// End the drag, the dragged rectangle in pixels of a `frame` sized frame shown in a
+    /// `widget` sized preview, `None` if nothing of the frame was dragged over
+    pub fn released(&mut self, widget: Size, frame: (u32, u32)) -> Option<Rect> {
+        let dragged = self.dragged()?;
+        self.start = None;
+        if dragged.width < MIN_DRAG || dragged.height < MIN_DRAG {
+            return None;
+        }
+        let image = fitted(widget, Size::new(frame.0 as f32, frame.1 as f32))?;
+        let dragged = dragged.intersection(&image)?;
+        let scale = frame.0 as f32 / image.width;
+        let rect = Rect::new(
+            ((dragged.x - image.x) * scale).round() as i32,
+            ((dragged.y - image.y) * scale).round() as i32,
+            (dragged.width * scale).round() as i32,
+            (dragged.height * scale).round() as i32,
+        );
+        (!rect.is_empty()).then_some(rect)
+    }
+
+    /// Rectangle dragged so far, in preview pixels
+    fn dragged(&self) -> Option<Rectangle> {
+        let (start, end) = (self.start?, self.cursor?);
+        Some(Rectangle {
+            x: start.x.min(end.x),
+            y: start.y.min(end.y),
+            width: (start.x - end.x).abs(),
+            height: (start.y - end.y).abs(),
+        })
+    }
+
+    /// Outline of the dragged rectangle, to be stacked over a preview of the same size
+    pub fn overlay<'a, Message: 'a>(&self) -> Option<Element<'a, Message>> {
+        let dragged = self.dragged()?;
+        let outline = container(Space::new(dragged.width, dragged.height)).style(|_theme: &iced::Theme| {
+            container::Style {
+                background: Some(iced::Background::Color(Color::from_rgba(0.3, 0.6, 1.0, 0.2))),
+                border: iced::Border {
+                    color: Color::from_rgb(0.3, 0.6, 1.0),
+                    width: 1.0,
+                    radius: 0.0.into(),
+                },
+                ..Default::default()
+            }
+        });
+        Some(
+            container(outline)
+                .padding(Padding {
+                    top: dragged.y,
+                    left: dragged.x,
+                    ..Padding::ZERO
+                })
+                .into(),
+        )
+    }
+}
+
+/// Where an image of `content` size is drawn in a `widget` sized image widget, which scales
+/// it to fit and centers it
+pub fn fitted(widget: Size, content: Size) -> Option<Rectangle> {
+    if content.width <= 0.0 || content.height <= 0.0 {
+        return None;
+    }
+    let scale = (widget.width / content.width).min(widget.height / content.height);
+    let size = Size::new(content.width * scale, content.height * scale);
+    Some(Rectangle {
+        x: (widget.width - size.width) / 2.0,
+        y: (widget.height - size.height) / 2.0,
+        width: size.width,
+        height: size.height,
+    })
+}
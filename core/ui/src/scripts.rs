@@ -0,0 +1,185 @@
+//! Scripts tab listing the saved `.rhai` scripts, editing one and running it against the
+//! captured window
+//!
+//! Scripts run in the embedded engine of the script service, what they print, the error they
+//! fail with and their end arrive on the event bus and are shown under the editor.
+
+use std::collections::VecDeque;
+
+use iced::widget::{button, column, row, scrollable, text, text_editor};
+use iced::{Color, Element, Font, Length, Task};
+use interface::services::Script;
+use interface::BotEvent;
+
+use crate::game_input::GameInput;
+
+/// Output lines kept, older ones scroll away
+const OUTPUT_LINES: usize = 200;
+
+const ERROR_COLOR: Color = Color::from_rgb(0.9, 0.4, 0.4);
+const NOTE_COLOR: Color = Color::from_rgb(0.6, 0.6, 0.6);
+
+#[derive(Debug, Clone)]
+pub enum ScriptMessage {
+    Refresh,
+    Open(String),
+    Edit(text_editor::Action),
+    Save,
+    /// Run what is in the editor, saved or not
+    Run,
+    Stop,
+    Started(String, Result<(), String>),
+    Stopped(String),
+}
+
+struct OutputLine {
+    text: String,
+    color: Option<Color>,
+}
+
+#[derive(Default)]
+pub struct ScriptPanel {
+    scripts: Vec<String>,
+    /// Name of the script in the editor
+    open: Option<String>,
+    editor: text_editor::Content,
+    output: VecDeque<OutputLine>,
+}
+
+impl ScriptPanel {
+    /// Show what scripts print and how they end
+    pub fn event(&mut self, event: &BotEvent) {
+        match event {
+            BotEvent::ScriptOutput { script, text } => self.push(format!("[{}] {}", script, text), None),
+            BotEvent::ScriptError { script, error } => self.push(format!("[{}] {}", script, error), Some(ERROR_COLOR)),
+            BotEvent::ScriptFinished { script } => self.push(format!("[{}] finished", script), Some(NOTE_COLOR)),
+            _ => {}
+        }
+    }
+
+    fn push(&mut self, text: String, color: Option<Color>) {
+        if self.output.len() == OUTPUT_LINES {
+            self.output.pop_front();
+        }
+        self.output.push_back(OutputLine { text, color });
+    }
+
+    fn is_running(&self, input: Option<&GameInput>) -> bool {
+        match (input, &self.open) {
+            (Some(input), Some(name)) => input.is_script_running(name),
+            _ => false,
+        }
+    }
+
+    /// Handle `message`, scripts run with `input`, `None` while no window is captured
+    pub fn update(&mut self, message: ScriptMessage, input: Option<&GameInput>) -> Task<ScriptMessage> {
+        match message {
+            ScriptMessage::Refresh => {
+                self.scripts = Script::list();
+                self.scripts.sort();
+            }
+            ScriptMessage::Open(name) => match Script::load(&name) {
+                Ok(script) => {
+                    self.editor = text_editor::Content::with_text(&script.source);
+                    self.open = Some(name);
+                }
+                Err(e) => self.push(e, Some(ERROR_COLOR)),
+            },
+            ScriptMessage::Edit(action) => self.editor.perform(action),
+            ScriptMessage::Save => {
+                if let Some(script) = self.script() {
+                    match script.save() {
+                        Ok(()) => self.push(format!("[{}] saved", script.name), Some(NOTE_COLOR)),
+                        Err(e) => self.push(e, Some(ERROR_COLOR)),
+                    }
+                }
+            }
+            ScriptMessage::Run => {
+                if let (Some(input), Some(script)) = (input, self.script()) {
+                    let name = script.name.clone();
+                    return Task::perform(input.clone().run_script(script), move |result| {
+                        ScriptMessage::Started(name.clone(), result)
+                    });
+                }
+            }
+            ScriptMessage::Stop => {
+                if let (Some(input), Some(name)) = (input, self.open.clone()) {
+                    return Task::perform(input.clone().stop_script(name), ScriptMessage::Stopped);
+                }
+            }
+            ScriptMessage::Started(name, Ok(())) => self.push(format!("[{}] started", name), Some(NOTE_COLOR)),
+            ScriptMessage::Started(name, Err(e)) => self.push(format!("[{}] {}", name, e), Some(ERROR_COLOR)),
+            ScriptMessage::Stopped(name) => self.push(format!("[{}] stopped", name), Some(NOTE_COLOR)),
+        }
+        Task::none()
+    }
+
+    /// The open script as edited
+    fn script(&self) -> Option<Script> {
+        Some(Script {
+            name: self.open.clone()?,
+            source: self.editor.text(),
+        })
+    }
+
+    pub fn view(&self, input: Option<&GameInput>) -> Element<'_, ScriptMessage> {
+        let scripts = self.scripts.iter().map(|name| {
+            let style = match self.open.as_ref() == Some(name) {
+                true => button::primary,
+                false => button::text,
+            };
+            button(text(name.clone()).size(14))
+                .on_press(ScriptMessage::Open(name.clone()))
+                .style(style)
+                .width(Length::Fill)
+                .into()
+        });
+        let list = column![
+            text("Scripts").size(18),
+            button("Refresh").on_press(ScriptMessage::Refresh),
+            scrollable(column(scripts).spacing(2)).height(Length::Fill),
+        ]
+        .spacing(10)
+        .width(Length::Fixed(200.0));
+
+        let running = self.is_running(input);
+        let opened = self.open.is_some();
+        let run = if running {
+            button("Stop").on_press(ScriptMessage::Stop)
+        } else {
+            button("Run").on_press_maybe((opened && input.is_some()).then_some(ScriptMessage::Run))
+        };
+        let title = match (&self.open, input.is_some()) {
+            (None, _) => "Open a script".to_string(),
+            (Some(name), true) => name.clone(),
+            (Some(name), false) => format!("{} (start capturing a window to run it)", name),
+        };
+        let toolbar = row![
+            text(title).size(18).width(Length::Fill),
+            button("Save").on_press_maybe(opened.then_some(ScriptMessage::Save)),
+            run,
+        ]
+        .spacing(10);
+
+        let output = self.output.iter().map(|line| {
+            let mut line_text = text(line.text.clone()).size(12).font(Font::MONOSPACE);
+            if let Some(color) = line.color {
+                line_text = line_text.color(color);
+            }
+            line_text.into()
+        });
+
+        let editor = column![
+            toolbar,
+            text_editor(&self.editor)
+                .on_action(ScriptMessage::Edit)
+                .font(Font::MONOSPACE)
+                .height(Length::Fixed(360.0)),
+            text("Output").size(16),
+            scrollable(column(output).spacing(2)).height(Length::Fixed(150.0)).width(Length::Fill),
+        ]
+        .spacing(10);
+
+        row![list, editor].spacing(20).into()
+    }
+}
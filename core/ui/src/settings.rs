@@ -0,0 +1,243 @@
+//! Settings tab editing `config.toml`
+//!
+//! The form keeps what was typed until it is saved, running services pick the saved config up
+//! through their `follow_config`.
+
+use std::time::Duration;
+
+use iced::widget::{button, checkbox, column, pick_list, row, text, text_input, Row};
+use iced::{Element, Length};
+use interface::config::{BotConfig, CaptureBackend, MAX_CAPTURE_FPS};
+use interface::services::Rect;
+use interface::ConfigWatcher;
+use platforms::input::InputProfile;
+
+/// Text fields of the form
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Fps,
+    PreviewQuality,
+    RoiX,
+    RoiY,
+    RoiWidth,
+    RoiHeight,
+    WindowPattern,
+    KeyPress,
+    ActionDelay,
+    Jitter,
+    MouseSpeed,
+}
+
+#[derive(Debug, Clone)]
+pub enum SettingsMessage {
+    Backend(CaptureBackend),
+    AutoSelect(bool),
+    Edited(Field, String),
+    /// Fill the humanization fields with [`InputProfile::human`]
+    HumanPreset,
+    Save,
+    /// Discard the edits
+    Revert,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    backend: CaptureBackend,
+    auto_select: bool,
+    fps: String,
+    preview_quality: String,
+    roi: [String; 4],
+    window_pattern: String,
+    key_press: String,
+    action_delay: String,
+    jitter: String,
+    mouse_speed: String,
+    /// Outcome of the last save
+    status: Option<Result<(), String>>,
+}
+
+impl Settings {
+    pub fn from_config(config: &BotConfig) -> Self {
+        let roi = config.roi();
+        let mut settings = Self {
+            backend: config.capture.backend,
+            auto_select: config.window().auto_select,
+            fps: config.capture.fps.to_string(),
+            preview_quality: config.capture.preview_quality.map(|quality| quality.to_string()).unwrap_or_default(),
+            roi: [
+                roi.map(|roi| roi.x),
+                roi.map(|roi| roi.y),
+                roi.map(|roi| roi.width),
+                roi.map(|roi| roi.height),
+            ]
+            .map(|value| value.map(|value| value.to_string()).unwrap_or_default()),
+            window_pattern: config.window().pattern.clone(),
+            status: None,
+            ..Self::default()
+        };
+        settings.set_input(&config.input);
+        settings
+    }
+
+    fn set_input(&mut self, input: &InputProfile) {
+        self.key_press = input.key_press_duration.as_millis().to_string();
+        self.action_delay = input.inter_action_delay.as_millis().to_string();
+        self.jitter = input.jitter.as_millis().to_string();
+        self.mouse_speed = input.mouse_speed.to_string();
+    }
+
+    fn field_mut(&mut self, field: Field) -> &mut String {
+        match field {
+            Field::Fps => &mut self.fps,
+            Field::PreviewQuality => &mut self.preview_quality,
+            Field::RoiX => &mut self.roi[0],
+            Field::RoiY => &mut self.roi[1],
+            Field::RoiWidth => &mut self.roi[2],
+            Field::RoiHeight => &mut self.roi[3],
+            Field::WindowPattern => &mut self.window_pattern,
+            Field::KeyPress => &mut self.key_press,
+            Field::ActionDelay => &mut self.action_delay,
+            Field::Jitter => &mut self.jitter,
+            Field::MouseSpeed => &mut self.mouse_speed,
+        }
+    }
+
+    /// Write the form into `config`, leaving it untouched if a field doesn't parse
+    fn apply(&self, config: &mut BotConfig) -> Result<(), String> {
+        let fps = parse(&self.fps, "Capture FPS")?;
+        let preview_quality = parse_optional(&self.preview_quality, "Preview quality")?;
+        let roi = match self.roi.iter().all(|value| value.trim().is_empty()) {
+            true => None,
+            false => Some(Rect::new(
+                parse(&self.roi[0], "ROI x")?,
+                parse(&self.roi[1], "ROI y")?,
+                parse(&self.roi[2], "ROI width")?,
+                parse(&self.roi[3], "ROI height")?,
+            )),
+        };
+        let millis = |value: &str, name| parse(value, name).map(Duration::from_millis);
+        let input = InputProfile {
+            key_press_duration: millis(&self.key_press, "Key press duration")?,
+            inter_action_delay: millis(&self.action_delay, "Delay between actions")?,
+            jitter: millis(&self.jitter, "Jitter")?,
+            mouse_speed: parse(&self.mouse_speed, "Mouse speed")?,
+        };
+
+        config.capture.backend = self.backend;
+        config.capture.fps = fps;
+        config.capture.preview_quality = preview_quality;
+        *config.roi_mut() = roi;
+        let window = config.window_mut();
+        window.pattern = self.window_pattern.trim().to_string();
+        window.auto_select = self.auto_select;
+        config.input = input;
+        Ok(())
+    }
+
+    pub fn update(&mut self, message: SettingsMessage, config: &ConfigWatcher) {
+        match message {
+            SettingsMessage::Backend(backend) => self.backend = backend,
+            SettingsMessage::AutoSelect(auto_select) => self.auto_select = auto_select,
+            SettingsMessage::Edited(field, value) => *self.field_mut(field) = value,
+            SettingsMessage::HumanPreset => self.set_input(&InputProfile::human()),
+            SettingsMessage::Save => {
+                let mut next = config.current();
+                let saved = self.apply(&mut next).and_then(|()| config.update(|config| *config = next));
+                if let Err(e) = &saved {
+                    tracing::warn!(error = %e, "Failed to save settings");
+                }
+                self.status = Some(saved);
+            }
+            SettingsMessage::Revert => *self = Self::from_config(&config.current()),
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, SettingsMessage> {
+        let capture = column![
+            text("Capture").size(18),
+            labelled(
+                "Backend",
+                pick_list(CaptureBackend::ALL, Some(self.backend), SettingsMessage::Backend),
+            ),
+            input(format!("FPS (1 - {})", MAX_CAPTURE_FPS), Field::Fps, &self.fps),
+            input("Preview quality (0 - 100)", Field::PreviewQuality, &self.preview_quality),
+        ]
+        .spacing(8);
+
+        let roi = column![
+            text("Minimap area (empty for the full frame)").size(18),
+            input("X", Field::RoiX, &self.roi[0]),
+            input("Y", Field::RoiY, &self.roi[1]),
+            input("Width", Field::RoiWidth, &self.roi[2]),
+            input("Height", Field::RoiHeight, &self.roi[3]),
+        ]
+        .spacing(8);
+
+        let window = column![
+            text("Window").size(18),
+            input("Title contains", Field::WindowPattern, &self.window_pattern),
+            checkbox("Capture it on launch", self.auto_select).on_toggle(SettingsMessage::AutoSelect),
+        ]
+        .spacing(8);
+
+        let humanization = column![
+            text("Humanization").size(18),
+            input("Key press (ms)", Field::KeyPress, &self.key_press),
+            input("Delay between actions (ms)", Field::ActionDelay, &self.action_delay),
+            input("Jitter (ms)", Field::Jitter, &self.jitter),
+            input("Mouse speed (px/s, 0 jumps)", Field::MouseSpeed, &self.mouse_speed),
+            button("Use human timings").on_press(SettingsMessage::HumanPreset),
+        ]
+        .spacing(8);
+
+        let status = match &self.status {
+            Some(Ok(())) => text("Saved").size(14),
+            Some(Err(e)) => text(e.clone()).size(14).color([0.9, 0.4, 0.4]),
+            None => text("").size(14),
+        };
+
+        column![
+            row![capture, roi].spacing(40),
+            row![window, humanization].spacing(40),
+            row![
+                button("Save").on_press(SettingsMessage::Save),
+                button("Revert").on_press(SettingsMessage::Revert),
+                status,
+            ]
+            .spacing(10),
+        ]
+        .spacing(20)
+        .into()
+    }
+}
+
+fn labelled<'a>(
+    label: impl text::IntoFragment<'a>,
+    widget: impl Into<Element<'a, SettingsMessage>>,
+) -> Row<'a, SettingsMessage> {
+    row![text(label).size(14).width(Length::Fixed(180.0)), widget.into()].spacing(10)
+}
+
+fn input<'a>(label: impl text::IntoFragment<'a>, field: Field, value: &str) -> Row<'a, SettingsMessage> {
+    labelled(
+        label,
+        text_input("", value)
+            .on_input(move |value| SettingsMessage::Edited(field, value))
+            .width(Length::Fixed(200.0)),
+    )
+}
+
+fn parse<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("{} must be a number, not {:?}", name, value))
+}
+
+/// `None` for an empty field
+fn parse_optional<T: std::str::FromStr>(value: &str, name: &str) -> Result<Option<T>, String> {
+    match value.trim() {
+        "" => Ok(None),
+        value => parse(value, name).map(Some),
+    }
+}